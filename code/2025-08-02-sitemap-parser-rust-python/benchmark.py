@@ -8,8 +8,10 @@ websites and provides detailed performance metrics.
 
 Usage:
     poetry run python benchmark.py
+    poetry run python benchmark.py --synthetic --sites 20 --urls-per-sitemap 50000
 """
 
+import argparse
 import logging
 import sys
 import time
@@ -22,11 +24,49 @@ logging.basicConfig(
     datefmt='%Y-%m-%d %H:%M:%S'
 )
 
+def parse_args() -> argparse.Namespace:
+    """Parse command line options."""
+    parser = argparse.ArgumentParser(description="Benchmark Python vs Rust sitemap parsing")
+    parser.add_argument("--synthetic", action="store_true",
+                        help="Benchmark against generated sitemap trees served from localhost instead of real sites")
+    parser.add_argument("--sites", type=int, default=10, help="Number of synthetic sites to serve")
+    parser.add_argument("--urls-per-sitemap", type=int, default=1000, help="URLs in each synthetic urlset")
+    parser.add_argument("--depth", type=int, default=2, help="Levels in each synthetic sitemap tree")
+    parser.add_argument("--fanout", type=int, default=5, help="Children per synthetic sitemap index")
+    parser.add_argument("--broken-every", type=int, default=0, help="Make every Nth synthetic urlset malformed")
+    parser.add_argument("--gzip", action="store_true", help="Serve synthetic urlsets gzipped")
+    parser.add_argument("--tokio-console", action="store_true",
+                        help="Expose the Rust parser to tokio-console (needs the extension built with the console feature)")
+    return parser.parse_args()
+
+def start_synthetic_sites(args: argparse.Namespace) -> List[Any]:
+    """Start one local synthetic sitemap server per requested site."""
+    from sitemap_parser_showdown import serve_synthetic_site
+
+    return [
+        serve_synthetic_site(
+            urls_per_sitemap=args.urls_per_sitemap,
+            depth=args.depth,
+            fanout=args.fanout,
+            broken_every=args.broken_every,
+            gzip=args.gzip,
+        )
+        for _ in range(args.sites)
+    ]
+
 def main():
     """Main benchmark function."""
+    args = parse_args()
+    if args.tokio_console:
+        from sitemap_parser_showdown import rust_parser
+        rust_parser.enable_tokio_console()
+
     print("🚀 Sitemap Parser Performance Benchmark")
     print("=" * 60)
-    print("Comparing Python vs Rust implementations with real-world data")
+    if args.synthetic:
+        print("Comparing Python vs Rust implementations with synthetic sitemaps")
+    else:
+        print("Comparing Python vs Rust implementations with real-world data")
     print()
     
     # Configuration
@@ -60,6 +100,12 @@ def main():
         "https://www.cbsnews.com/",
         "https://www.nbcnews.com/",
     ]
+
+    servers = []
+    if args.synthetic:
+        servers = start_synthetic_sites(args)
+        test_urls = [server.base_url for server in servers]
+        print(f"🧪 Serving {len(servers)} synthetic sites, {sum(s.expected_urls for s in servers):,} URLs total")
     
     print(f"📊 Configuration: {config}")
     print(f"🌐 Testing {len(test_urls)} websites with robots.txt discovery")
@@ -78,6 +124,9 @@ def main():
     print("📈 Performance Summary:")
     display_comparison(python_result, rust_result)
 
+    for server in servers:
+        server.stop()
+
 def run_python_benchmark(test_urls: List[str], config: Dict[str, Any]) -> Dict[str, Any]:
     """Run the Python implementation benchmark."""
     try:
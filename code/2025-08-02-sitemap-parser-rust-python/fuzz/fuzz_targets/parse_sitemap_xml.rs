@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_parser::{parse_sitemap_xml, parse_sitemap_xml_with};
+
+// Bodies reach the parser via lossy UTF-8 decoding, so fuzz through the same path
+fuzz_target!(|data: &[u8]| {
+    let content = String::from_utf8_lossy(data);
+    let _ = parse_sitemap_xml(&content, "https://example.com/");
+    let _ = parse_sitemap_xml_with(&content, "https://example.com/", true);
+});
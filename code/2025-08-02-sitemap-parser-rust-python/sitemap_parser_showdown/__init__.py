@@ -10,11 +10,47 @@ from .benchmark import run_benchmark, compare_implementations
 
 # Try to import Rust parser, gracefully handle if not built
 try:
-    from .rust_parser import RustParser, parse_sitemaps_rust
+    from .rust_parser import (
+        BlockedError,
+        CancelToken,
+        FetchTimeoutError,
+        HttpError,
+        InvalidUrlError,
+        NetworkError,
+        NoSitemapError,
+        ParseInterruptedError,
+        RedirectRefusedError,
+        RobotsFetchError,
+        RustParser,
+        SiteSkippedError,
+        SitemapError,
+        TooManyRedirectsError,
+        XmlParseError,
+        coordinate_sites,
+        health_check,
+        merge_robots_txt,
+        parse_sitemaps_rust,
+        serve_synthetic_site,
+        sitemap_directives,
+        unpack_urls,
+        write_synthetic_site,
+    )
     RUST_AVAILABLE = True
 except ImportError:
+    CancelToken = None
     RustParser = None
+    SitemapError = HttpError = FetchTimeoutError = NetworkError = None
+    TooManyRedirectsError = XmlParseError = RobotsFetchError = NoSitemapError = None
+    InvalidUrlError = ParseInterruptedError = SiteSkippedError = RedirectRefusedError = None
+    BlockedError = None
+    coordinate_sites = None
+    health_check = None
+    merge_robots_txt = None
     parse_sitemaps_rust = None
+    serve_synthetic_site = None
+    sitemap_directives = None
+    unpack_urls = None
+    write_synthetic_site = None
     RUST_AVAILABLE = False
 
 __version__ = "0.1.0"
@@ -23,7 +59,28 @@ __all__ = [
     "SitemapResult", 
     "parse_sitemaps_sync",
     "RustParser",
+    "CancelToken",
+    "SitemapError",
+    "HttpError",
+    "FetchTimeoutError",
+    "NetworkError",
+    "TooManyRedirectsError",
+    "RedirectRefusedError",
+    "XmlParseError",
+    "RobotsFetchError",
+    "NoSitemapError",
+    "InvalidUrlError",
+    "BlockedError",
+    "ParseInterruptedError",
+    "SiteSkippedError",
     "parse_sitemaps_rust",
+    "coordinate_sites",
+    "health_check",
+    "sitemap_directives",
+    "merge_robots_txt",
+    "unpack_urls",
+    "serve_synthetic_site",
+    "write_synthetic_site",
     "run_benchmark",
     "compare_implementations",
     "RUST_AVAILABLE",
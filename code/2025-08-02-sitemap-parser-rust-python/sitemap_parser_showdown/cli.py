@@ -0,0 +1,286 @@
+#!/usr/bin/env python3
+"""
+Command line entry point for the sitemap parser.
+
+Usage:
+    sitemap-parser doctor [--url URL] [--timeout SECONDS]
+    sitemap-parser job run DOMAINS_FILE --state STATE --output RESULTS.jsonl [--tui]
+    sitemap-parser job resume --state STATE --output RESULTS.jsonl [--tui]
+    sitemap-parser job status --state STATE
+    sitemap-parser coordinate DOMAINS_FILE --bind HOST:PORT --output RESULTS.jsonl
+    sitemap-parser worker --coordinator HOST:PORT
+    sitemap-parser indexnow URLS_FILE --key KEY [--key-location URL] [--endpoint URL ...]
+    sitemap-parser search-console PROPERTY (--service-account KEY.json | --access-token TOKEN)
+"""
+
+import argparse
+import asyncio
+import contextlib
+import json
+import logging
+import sys
+
+STATUS_ICONS = {"ok": "✅", "failed": "❌", "skipped": "⏭️ "}
+
+
+def doctor(args: argparse.Namespace) -> int:
+    """Diagnose the network environment and print one line per check."""
+    try:
+        from .rust_parser import health_check
+    except ImportError:
+        print("❌ Rust extension not built; run `poetry run maturin develop` first")
+        return 1
+
+    report = health_check(args.url, timeout_seconds=args.timeout)
+    print(f"🩺 Environment check against {report.target}")
+    for check in report.checks:
+        icon = STATUS_ICONS.get(check.status, "  ")
+        print(f"{icon} {check.name:<6} {check.detail} ({check.elapsed_ms:.0f}ms)")
+
+    if report.ok:
+        print("\nAll checks passed")
+        return 0
+    print("\nSome checks failed; fix these before debugging parse results")
+    return 1
+
+
+def read_domains(path: str) -> list:
+    """Base URLs from a file, one per line, skipping blanks and # comments."""
+    with open(path) as f:
+        return [line.strip() for line in f if line.strip() and not line.startswith("#")]
+
+
+def job_run(args: argparse.Namespace) -> int:
+    """Start a job over a file of base URLs, one per line."""
+    base_urls = read_domains(args.domains)
+    return run_job(args, lambda parser: parser.start_job(base_urls, state_path=args.state))
+
+
+def job_resume(args: argparse.Namespace) -> int:
+    """Continue a job from its state file, appending to the same results file."""
+    return run_job(args, lambda parser: parser.resume_job(args.state))
+
+
+def run_job(args: argparse.Namespace, start) -> int:
+    """Drive a job to completion, writing one JSON line per site.
+
+    Ctrl-C stops starting sites and gives in-flight requests --grace seconds to
+    finish. Their partial results are written flagged "interrupted" and the sites
+    stay pending in the state file, so the job can be resumed. A second Ctrl-C
+    exits at once.
+
+    With --tui, a live dashboard replaces the progress lines until the job ends.
+    """
+    try:
+        from .rust_parser import RustParser
+    except ImportError:
+        print("❌ Rust extension not built; run `poetry run maturin develop` first")
+        return 1
+    if args.tui and not hasattr(RustParser, "dashboard"):
+        print("❌ Rust extension built without the dashboard; run `poetry run maturin develop --features tui`")
+        return 1
+
+    parser = RustParser(max_concurrent=args.max_concurrent, timeout_seconds=args.timeout)
+    job = start(parser)
+    if args.tui:
+        # Log lines would tear through the dashboard; errors still reach the results file
+        logging.disable(logging.CRITICAL)
+        progress = job.progress
+        dashboard = parser.dashboard(total=progress.total - progress.completed)
+    else:
+        print(f"🚀 Job of {job.progress.total} sites running (state in {args.state})")
+        dashboard = contextlib.nullcontext()
+
+    interrupted = False
+    with dashboard, open(args.output, "a") as out:
+        try:
+            for result in job:
+                write_result(out, result)
+                progress = job.progress
+                if not args.tui and progress.completed % 100 == 0:
+                    print(f"   {progress.completed}/{progress.total} sites done, {progress.failed} failed")
+        except KeyboardInterrupt:
+            if not args.tui:
+                print(f"\n⏳ Interrupted; waiting up to {args.grace}s for in-flight requests (Ctrl-C again to quit now)")
+            parser.interrupt(grace_seconds=args.grace)
+            # Results already buffered count as done in the saved state, so keep them
+            for result in job:
+                write_result(out, result)
+            interrupted = True
+
+    if interrupted:
+        print(f"⏸️  Stopped; continue with: sitemap-parser job resume --state {args.state} --output {args.output}")
+        return 130
+    progress = job.progress
+    print(f"✅ Job {progress.status}: {progress.completed}/{progress.total} sites, {progress.failed} failed")
+    return 0
+
+
+def write_result(out, result) -> None:
+    record = {"base_url": result.base_url, "urls": result.urls, "errors": result.errors}
+    if result.interrupted:
+        record["interrupted"] = True
+    out.write(json.dumps(record) + "\n")
+
+
+def job_status(args: argparse.Namespace) -> int:
+    """Summarize a job's state file without running it."""
+    with open(args.state) as f:
+        state = json.load(f)
+    print(f"Status:    {state['status']}")
+    print(f"Completed: {state['completed']}/{state['total']} ({state['failed']} failed)")
+    print(f"Pending:   {len(state['pending'])}")
+    return 0
+
+
+def coordinate(args: argparse.Namespace) -> int:
+    """Hand a domain list out to workers and write the merged results."""
+    try:
+        from .rust_parser import coordinate_sites
+    except ImportError:
+        print("❌ Rust extension not built; run `poetry run maturin develop` first")
+        return 1
+
+    base_urls = read_domains(args.domains)
+    print(f"📡 Coordinating {len(base_urls)} sites on {args.bind}; start workers with:")
+    print(f"   sitemap-parser worker --coordinator {args.bind}")
+    results = coordinate_sites(base_urls, bind=args.bind, shard_size=args.shard_size)
+    with open(args.output, "w") as out:
+        for result in results:
+            write_result(out, result)
+    failed = sum(1 for r in results if r.errors and not r.urls)
+    print(f"✅ Merged {len(results)} sites into {args.output}, {failed} failed")
+    return 0
+
+
+def worker(args: argparse.Namespace) -> int:
+    """Parse shards from a coordinator until it runs out."""
+    try:
+        from .rust_parser import RustParser
+    except ImportError:
+        print("❌ Rust extension not built; run `poetry run maturin develop` first")
+        return 1
+
+    async def work() -> int:
+        parser = RustParser(max_concurrent=args.max_concurrent, timeout_seconds=args.timeout)
+        return await parser.run_worker(args.coordinator)
+
+    parsed = asyncio.run(work())
+    print(f"✅ Parsed {parsed} sites for {args.coordinator}")
+    return 0
+
+
+def indexnow(args: argparse.Namespace) -> int:
+    """Submit a file of added or changed URLs to IndexNow."""
+    try:
+        from .rust_parser import RustParser
+    except ImportError:
+        print("❌ Rust extension not built; run `poetry run maturin develop` first")
+        return 1
+
+    urls = read_domains(args.urls)
+
+    async def submit() -> list:
+        parser = RustParser(timeout_seconds=args.timeout)
+        return await parser.submit_indexnow(urls, args.key, key_location=args.key_location, endpoints=args.endpoint)
+
+    submissions = asyncio.run(submit())
+    for s in submissions:
+        icon = "✅" if s.accepted else "❌"
+        print(f"{icon} {s.host}: {s.urls} URLs to {s.endpoint}: {s.message}")
+    return 0 if submissions and all(s.accepted for s in submissions) else 1
+
+
+def search_console(args: argparse.Namespace) -> int:
+    """Compare Search Console's URL counts for a property's sitemaps with parsed counts."""
+    try:
+        from .rust_parser import RustParser
+    except ImportError:
+        print("❌ Rust extension not built; run `poetry run maturin develop` first")
+        return 1
+    if not hasattr(RustParser, "compare_search_console"):
+        print("❌ Rust extension built without Search Console support; run `poetry run maturin develop --features search-console`")
+        return 1
+
+    async def compare() -> list:
+        parser = RustParser(max_depth=args.max_depth, max_nested_per_level=args.max_nested, timeout_seconds=args.timeout)
+        return await parser.compare_search_console(
+            args.property, access_token=args.access_token, service_account_file=args.service_account
+        )
+
+    comparisons = asyncio.run(compare())
+    for c in comparisons:
+        if c.is_pending:
+            print(f"⏳ {c.sitemap}: pending in Search Console; parsed {c.parsed_urls}")
+        elif c.error:
+            print(f"❌ {c.sitemap}: {c.error}")
+        else:
+            icon = "❌" if c.discrepancy else "✅"
+            print(f"{icon} {c.sitemap}: Search Console {c.reported_urls}, parsed {c.parsed_urls} ({c.difference:+d})")
+    return 1 if any(c.discrepancy for c in comparisons) else 0
+
+
+def main() -> int:
+    parser = argparse.ArgumentParser(prog="sitemap-parser")
+    commands = parser.add_subparsers(dest="command", required=True)
+
+    doctor_parser = commands.add_parser("doctor", help="Check DNS, proxy, TLS and HTTP connectivity")
+    doctor_parser.add_argument("--url", default="https://www.example.com/", help="Site to test against")
+    doctor_parser.add_argument("--timeout", type=int, default=10, help="Per-check timeout in seconds")
+    doctor_parser.set_defaults(func=doctor)
+
+    job_parser = commands.add_parser("job", help="Run a resumable batch job")
+    job_commands = job_parser.add_subparsers(dest="job_command", required=True)
+    run_parser = job_commands.add_parser("run", help="Parse every site in a file of base URLs")
+    run_parser.add_argument("domains", help="File with one base URL per line")
+    resume_parser = job_commands.add_parser("resume", help="Continue a stopped or interrupted job")
+    for sub in (run_parser, resume_parser):
+        sub.add_argument("--state", required=True, help="Job state file, written as the job runs")
+        sub.add_argument("--output", required=True, help="JSON Lines file results are appended to")
+        sub.add_argument("--max-concurrent", type=int, default=10, help="Sites parsed at once")
+        sub.add_argument("--timeout", type=int, default=30, help="Per-request timeout in seconds")
+        sub.add_argument("--grace", type=float, default=10, help="Seconds in-flight requests get to finish after Ctrl-C")
+        sub.add_argument("--tui", action="store_true", help="Show a live dashboard instead of progress lines (needs the tui feature)")
+    run_parser.set_defaults(func=job_run)
+    resume_parser.set_defaults(func=job_resume)
+    status_parser = job_commands.add_parser("status", help="Show a job's progress from its state file")
+    status_parser.add_argument("--state", required=True, help="Job state file")
+    status_parser.set_defaults(func=job_status)
+
+    coordinate_parser = commands.add_parser("coordinate", help="Shard a domain list across worker processes")
+    coordinate_parser.add_argument("domains", help="File with one base URL per line")
+    coordinate_parser.add_argument("--bind", default="127.0.0.1:7878", help="Address workers connect to")
+    coordinate_parser.add_argument("--shard-size", type=int, default=100, help="Sites handed to a worker at a time")
+    coordinate_parser.add_argument("--output", required=True, help="JSON Lines file for the merged results")
+    coordinate_parser.set_defaults(func=coordinate)
+
+    worker_parser = commands.add_parser("worker", help="Parse shards handed out by a coordinator")
+    worker_parser.add_argument("--coordinator", required=True, help="Coordinator address, host:port")
+    worker_parser.add_argument("--max-concurrent", type=int, default=10, help="Sites parsed at once")
+    worker_parser.add_argument("--timeout", type=int, default=30, help="Per-request timeout in seconds")
+    worker_parser.set_defaults(func=worker)
+
+    indexnow_parser = commands.add_parser("indexnow", help="Submit added or changed URLs to IndexNow")
+    indexnow_parser.add_argument("urls", help="File with one URL per line")
+    indexnow_parser.add_argument("--key", required=True, help="IndexNow key served by each host")
+    indexnow_parser.add_argument("--key-location", help="URL of the key file, if not https://<host>/<key>.txt")
+    indexnow_parser.add_argument("--endpoint", action="append", help="IndexNow endpoint; repeat for several (default: api.indexnow.org)")
+    indexnow_parser.add_argument("--timeout", type=int, default=30, help="Per-request timeout in seconds")
+    indexnow_parser.set_defaults(func=indexnow)
+
+    gsc_parser = commands.add_parser("search-console", help="Compare Search Console sitemap URL counts with parsed counts")
+    gsc_parser.add_argument("property", help="Search Console property, e.g. https://example.com/ or sc-domain:example.com")
+    credentials = gsc_parser.add_mutually_exclusive_group(required=True)
+    credentials.add_argument("--service-account", help="Service account JSON key with access to the property")
+    credentials.add_argument("--access-token", help="OAuth access token with the webmasters.readonly scope")
+    gsc_parser.add_argument("--max-depth", type=int, default=3, help="Index levels to follow when counting")
+    gsc_parser.add_argument("--max-nested", type=int, default=1000, help="Sitemaps to follow per index level when counting")
+    gsc_parser.add_argument("--timeout", type=int, default=30, help="Per-request timeout in seconds")
+    gsc_parser.set_defaults(func=search_console)
+
+    args = parser.parse_args()
+    return args.func(args)
+
+
+if __name__ == "__main__":
+    sys.exit(main())
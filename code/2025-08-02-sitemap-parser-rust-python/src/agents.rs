@@ -0,0 +1,111 @@
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Which agent a host is on, and how many of the pool it has been offered
+#[derive(Debug, Clone, Copy)]
+struct HostAgent {
+    current: usize,
+    tried: usize,
+}
+
+/// A pool of user-agent strings shared by every request of a run.
+///
+/// Each host sticks to one agent, picked from a hash of the host name so reruns
+/// present the same one. When a host challenges the agent (see [`is_challenge`]),
+/// it moves on to the next agent in the pool until every agent has been tried.
+/// The agent that last got a successful response from a host is remembered.
+pub struct UserAgentPool {
+    agents: Vec<String>,
+    hosts: Mutex<HashMap<String, HostAgent>>,
+    succeeded: Mutex<HashMap<String, usize>>,
+}
+
+impl UserAgentPool {
+    /// `None` if `agents` is empty
+    pub fn new(agents: Vec<String>) -> Option<Self> {
+        (!agents.is_empty()).then(|| Self { agents, hosts: Mutex::new(HashMap::new()), succeeded: Mutex::new(HashMap::new()) })
+    }
+
+    pub fn agent(&self, index: usize) -> &str {
+        &self.agents[index]
+    }
+
+    /// Index of the agent `host` currently uses, assigning one on first contact
+    pub fn agent_for(&self, host: &str) -> usize {
+        let mut hosts = self.hosts.lock().unwrap();
+        hosts.entry(host.to_string()).or_insert_with(|| HostAgent { current: self.initial(host), tried: 1 }).current
+    }
+
+    /// Move `host` off agent `from` after a challenge, returning the agent to retry
+    /// with, or `None` once the whole pool has been tried
+    pub fn rotate(&self, host: &str, from: usize) -> Option<usize> {
+        let mut hosts = self.hosts.lock().unwrap();
+        let state = hosts.entry(host.to_string()).or_insert(HostAgent { current: from, tried: 1 });
+        if state.current != from {
+            // A concurrent request already moved the host on
+            return Some(state.current);
+        }
+        if state.tried >= self.agents.len() {
+            return None;
+        }
+        state.current = (state.current + 1) % self.agents.len();
+        state.tried += 1;
+        Some(state.current)
+    }
+
+    pub fn record_success(&self, host: &str, agent: usize) {
+        self.succeeded.lock().unwrap().insert(host.to_string(), agent);
+    }
+
+    /// The agent that last got a successful response from `host`
+    pub fn succeeded(&self, host: &str) -> Option<String> {
+        self.succeeded.lock().unwrap().get(host).map(|&agent| self.agents[agent].clone())
+    }
+
+    fn initial(&self, host: &str) -> usize {
+        let digest = sha1_smol::Sha1::from(host).digest().bytes();
+        u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) as usize % self.agents.len()
+    }
+}
+
+/// Whether a response is a bot challenge or block aimed at the user agent rather
+/// than an answer: a 403 or 406, or any response Cloudflare marks `cf-mitigated`
+pub fn is_challenge(status: StatusCode, headers: &HeaderMap) -> bool {
+    matches!(status.as_u16(), 403 | 406) || headers.contains_key("cf-mitigated")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sticky_then_rotates_through_pool() {
+        let pool = UserAgentPool::new(vec!["a".to_string(), "b".to_string(), "c".to_string()]).unwrap();
+        let first = pool.agent_for("example.com");
+        assert_eq!(pool.agent_for("example.com"), first);
+
+        let second = pool.rotate("example.com", first).unwrap();
+        assert_ne!(second, first);
+        // A request still holding the old agent follows the host to the new one
+        assert_eq!(pool.rotate("example.com", first), Some(second));
+        let third = pool.rotate("example.com", second).unwrap();
+        assert_eq!(pool.rotate("example.com", third), None);
+        assert_eq!(pool.agent_for("example.com"), third);
+
+        assert_eq!(pool.succeeded("example.com"), None);
+        pool.record_success("example.com", third);
+        assert_eq!(pool.succeeded("example.com").as_deref(), Some(pool.agent(third)));
+        assert!(UserAgentPool::new(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_is_challenge() {
+        let mut headers = HeaderMap::new();
+        assert!(is_challenge(StatusCode::FORBIDDEN, &headers));
+        assert!(!is_challenge(StatusCode::TOO_MANY_REQUESTS, &headers));
+        headers.insert("cf-mitigated", "challenge".parse().unwrap());
+        assert!(is_challenge(StatusCode::SERVICE_UNAVAILABLE, &headers));
+    }
+}
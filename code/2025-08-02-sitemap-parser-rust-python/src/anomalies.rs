@@ -0,0 +1,194 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use url::Url;
+
+use crate::score::parse_lastmod;
+use crate::sitemap::UrlMetadata;
+
+/// Example URLs kept per anomaly
+const EXAMPLES: usize = 5;
+
+/// URLs at least this long are flagged whatever the rest of the site looks like;
+/// many clients and crawlers refuse longer ones
+pub const MAX_URL_LENGTH: usize = 2048;
+
+/// What kind of suspicious entry an anomaly groups
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AnomalyKind {
+    /// URLs on a host that isn't the site's, or one of its subdomains
+    OffDomain,
+    /// Many URLs that differ only in their query string
+    QueryVariants,
+    /// lastmod dates later than now
+    FutureLastmod,
+    /// URLs far longer than the rest of the site's
+    LongUrl,
+}
+
+impl fmt::Display for AnomalyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnomalyKind::OffDomain => write!(f, "off_domain"),
+            AnomalyKind::QueryVariants => write!(f, "query_variants"),
+            AnomalyKind::FutureLastmod => write!(f, "future_lastmod"),
+            AnomalyKind::LongUrl => write!(f, "long_url"),
+        }
+    }
+}
+
+/// A group of suspicious sitemap entries
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    /// URLs in the group
+    pub count: usize,
+    /// Up to 5 of them
+    pub examples: Vec<String>,
+    /// What the group has in common, e.g. the foreign host or the shared path
+    pub detail: String,
+}
+
+/// When entries count as suspicious
+#[derive(Debug, Clone)]
+pub struct AnomalyThresholds {
+    /// Query-string variants of one path before they're flagged
+    pub max_query_variants: usize,
+    /// How far in the future a lastmod may be, to allow for clock and time zone skew
+    pub future_tolerance: Duration,
+    /// A URL is long if it's longer than the upper quartile plus this many interquartile ranges...
+    pub length_iqr_factor: f64,
+    /// ...and longer than this
+    pub min_long_length: usize,
+}
+
+impl Default for AnomalyThresholds {
+    fn default() -> Self {
+        Self {
+            max_query_variants: 1000,
+            future_tolerance: Duration::days(1),
+            length_iqr_factor: 3.0,
+            min_long_length: 200,
+        }
+    }
+}
+
+/// Flag off-domain URLs, floods of query-string variants, future lastmods, and
+/// URL-length outliers among a site's entries, most widespread first.
+///
+/// lastmod checks need `metadata`, which is empty unless the parse kept it.
+pub fn detect_anomalies(
+    base_url: &str,
+    urls: &[String],
+    metadata: &HashMap<String, UrlMetadata>,
+    thresholds: &AnomalyThresholds,
+    now: DateTime<Utc>,
+) -> Vec<Anomaly> {
+    let mut anomalies = Vec::new();
+    let site_host = Url::parse(base_url).ok().and_then(|u| u.host_str().map(|h| h.trim_start_matches("www.").to_string()));
+    let mut foreign: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    let mut variants: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for url in urls {
+        let Ok(parsed) = Url::parse(url) else { continue };
+        let host = parsed.host_str().unwrap_or_default();
+        if let Some(site) = &site_host {
+            let host = host.trim_start_matches("www.");
+            if host != site && !host.ends_with(&format!(".{}", site)) {
+                foreign.entry(host.to_string()).or_default().push(url);
+            }
+        }
+        if parsed.query().is_some() {
+            variants.entry(format!("{}{}", host, parsed.path())).or_default().push(url);
+        }
+    }
+    for (host, listed) in foreign {
+        anomalies.push(group(AnomalyKind::OffDomain, &listed, format!("host {}", host)));
+    }
+    for (path, listed) in variants {
+        if listed.len() > thresholds.max_query_variants {
+            let params: BTreeSet<String> = listed.iter()
+                .filter_map(|url| Url::parse(url).ok())
+                .flat_map(|u| u.query_pairs().map(|(k, _)| k.into_owned()).collect::<Vec<_>>())
+                .collect();
+            let params: Vec<String> = params.into_iter().collect();
+            anomalies.push(group(AnomalyKind::QueryVariants, &listed, format!("{} varying {}", path, params.join(", "))));
+        }
+    }
+
+    let cutoff = now + thresholds.future_tolerance;
+    let mut future: Vec<(&str, DateTime<Utc>)> = urls.iter()
+        .filter_map(|url| Some((url.as_str(), parse_lastmod(metadata.get(url)?.lastmod.as_deref()?)?)))
+        .filter(|(_, lastmod)| *lastmod > cutoff)
+        .collect();
+    if !future.is_empty() {
+        future.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        let listed: Vec<&str> = future.iter().map(|(url, _)| *url).collect();
+        anomalies.push(group(AnomalyKind::FutureLastmod, &listed, format!("latest lastmod {}", future[0].1.to_rfc3339())));
+    }
+
+    if let Some(limit) = long_url_limit(urls, thresholds) {
+        let mut long: Vec<&str> = urls.iter().map(String::as_str).filter(|url| url.len() > limit).collect();
+        if !long.is_empty() {
+            long.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+            anomalies.push(group(AnomalyKind::LongUrl, &long, format!("longer than {} characters", limit)));
+        }
+    }
+
+    anomalies.sort_by(|a, b| b.count.cmp(&a.count).then(a.kind.cmp(&b.kind)));
+    anomalies
+}
+
+/// Length above which a URL is an outlier, from the site's quartiles but never above `MAX_URL_LENGTH`
+fn long_url_limit(urls: &[String], thresholds: &AnomalyThresholds) -> Option<usize> {
+    if urls.is_empty() {
+        return None;
+    }
+    let mut lengths: Vec<usize> = urls.iter().map(String::len).collect();
+    lengths.sort_unstable();
+    let (q1, q3) = (lengths[lengths.len() / 4], lengths[lengths.len() * 3 / 4]);
+    let fence = q3 as f64 + thresholds.length_iqr_factor * (q3 - q1) as f64;
+    Some((fence as usize).max(thresholds.min_long_length).min(MAX_URL_LENGTH))
+}
+
+fn group(kind: AnomalyKind, urls: &[&str], detail: String) -> Anomaly {
+    let mut examples: Vec<String> = urls.iter().take(EXAMPLES).map(|url| url.to_string()).collect();
+    if kind != AnomalyKind::LongUrl && kind != AnomalyKind::FutureLastmod {
+        examples.sort();
+    }
+    Anomaly { kind, count: urls.len(), examples, detail }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_each_kind() {
+        let mut urls: Vec<String> = (0..50).map(|i| format!("https://www.example.com/products/{}", i)).collect();
+        urls.push("https://shop.example.com/cart".to_string());
+        urls.push("https://casino.example.net/win".to_string());
+        urls.extend((0..6).map(|i| format!("https://example.com/search?q={}&page=1", i)));
+        urls.push(format!("https://example.com/{}", "a".repeat(400)));
+        let now = parse_lastmod("2024-06-01").unwrap();
+        let metadata = HashMap::from([
+            ("https://www.example.com/products/1".to_string(), UrlMetadata { lastmod: Some("2031-01-01".to_string()), ..Default::default() }),
+            ("https://www.example.com/products/2".to_string(), UrlMetadata { lastmod: Some("2024-06-01T20:00:00Z".to_string()), ..Default::default() }),
+        ]);
+        let thresholds = AnomalyThresholds { max_query_variants: 5, ..Default::default() };
+
+        let anomalies = detect_anomalies("https://www.example.com", &urls, &metadata, &thresholds, now);
+        let kinds: Vec<String> = anomalies.iter().map(|a| format!("{}: {}", a.kind, a.detail)).collect();
+        assert_eq!(kinds, [
+            "query_variants: example.com/search varying page, q",
+            "off_domain: host casino.example.net",
+            "future_lastmod: latest lastmod 2031-01-01T00:00:00+00:00",
+            "long_url: longer than 200 characters",
+        ]);
+        assert_eq!(anomalies[0].count, 6);
+        assert_eq!(anomalies[0].examples.len(), 5);
+        assert_eq!(anomalies[2].examples, ["https://www.example.com/products/1"]);
+
+        let quiet = AnomalyThresholds { max_query_variants: 10, ..Default::default() };
+        assert_eq!(detect_anomalies("https://www.example.com", &urls[..50], &HashMap::new(), &quiet, now), []);
+    }
+}
@@ -0,0 +1,500 @@
+//! `sitemap-parse`: parse sites' sitemaps from the command line and write the URLs
+//! they list, for scripts and cron jobs that shouldn't need Python.
+
+use chrono::{DateTime, Utc};
+use clap::{Parser, ValueEnum};
+use futures::StreamExt;
+use ipnet::IpNet;
+use serde_json::{json, Map, Value};
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rust_parser::{
+    parse_cutoff, parse_nameserver, parse_network, Checkpoint, ClientConfig, DiscoveryPolicy, DnsPolicy, DnsResolver, HostPacer,
+    ParsedSiteResult, RedirectPolicy, RequestLimiter, RetryPolicy, RustSitemapParser, Shutdown, SiteOutcome, TrailingSlash,
+    UrlCanonicalizer, UrlFilter, UrlMetadata, DEFAULT_GRACE, DEFAULT_MAX_RESPONSE_BYTES, MAX_REDIRECTS,
+};
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Every site parsed without errors
+const EXIT_OK: u8 = 0;
+/// The run itself failed, e.g. the output couldn't be written (2 is clap's usage error)
+const EXIT_FATAL: u8 = 1;
+/// Some sites recorded errors but still gave URLs
+const EXIT_PARTIAL: u8 = 3;
+/// At least one site gave no URLs, only errors
+const EXIT_FAILED: u8 = 4;
+/// Ctrl-C stopped the run; what finished was still written
+const EXIT_INTERRUPTED: u8 = 130;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// One URL per line
+    Text,
+    /// One JSON object per URL, with its site and any metadata
+    Jsonl,
+    /// One JSON array with an object per site: URLs, sitemaps, errors, and timings
+    Json,
+}
+
+/// Parse the sitemaps of one or more sites and write the URLs they list.
+///
+/// Exit status: 0 if every site parsed cleanly, 3 if some recorded errors but still
+/// gave URLs, 4 if any site gave no URLs, 130 if interrupted, 1 on other failures.
+#[derive(Debug, Parser)]
+#[command(name = "sitemap-parse", version)]
+struct Args {
+    /// Base URLs like https://example.com; `-` reads more from stdin, one per line
+    #[arg(required = true)]
+    sites: Vec<String>,
+    /// Sites parsed at once
+    #[arg(short = 'j', long, default_value_t = 10)]
+    concurrency: usize,
+    /// Sitemap nesting levels to follow below robots.txt
+    #[arg(long, default_value_t = 2)]
+    max_depth: usize,
+    /// Sitemaps taken from each site's robots.txt
+    #[arg(long, default_value_t = 10)]
+    max_sitemaps: usize,
+    /// Nested sitemaps followed from each sitemap index
+    #[arg(long, default_value_t = 5)]
+    max_nested_per_level: usize,
+    /// Per-request timeout in seconds
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+    /// Seconds any one site gets in all; it keeps the URLs found by then
+    #[arg(long, value_name = "SECONDS")]
+    site_timeout: Option<u64>,
+    /// URLs kept from any one site; its remaining sitemaps aren't fetched once reached
+    #[arg(long, value_name = "N")]
+    max_urls_per_site: Option<usize>,
+    /// Lowercase hosts and drop fragments before deduplicating URLs; the options below imply it
+    #[arg(long)]
+    canonicalize: bool,
+    /// What canonicalization does with trailing slashes: keep, strip, or add
+    #[arg(long, value_name = "MODE", default_value = "keep")]
+    trailing_slash: TrailingSlash,
+    /// Sort query parameters by name when canonicalizing
+    #[arg(long)]
+    sort_query: bool,
+    /// Query parameter to drop when canonicalizing, like `gclid` or `utm_*` (repeatable)
+    #[arg(long = "strip-param", value_name = "NAME")]
+    strip_params: Vec<String>,
+    /// Bytes read from any one response before it's cut off
+    #[arg(long, default_value_t = DEFAULT_MAX_RESPONSE_BYTES)]
+    max_response_bytes: usize,
+    /// Requests in flight across every site
+    #[arg(long)]
+    max_requests: Option<usize>,
+    /// Requests in flight to any one host
+    #[arg(long)]
+    max_requests_per_host: Option<usize>,
+    /// Minimum milliseconds between requests to any one host (default: its Crawl-delay with --respect-robots)
+    #[arg(long, value_name = "MS")]
+    host_delay_ms: Option<u64>,
+    /// Retries for timeouts, connection errors, 429s, and 5xx responses
+    #[arg(long, default_value_t = 0)]
+    max_retries: usize,
+    /// Redirects followed for any one request
+    #[arg(long, default_value_t = MAX_REDIRECTS)]
+    max_redirects: usize,
+    /// Refuse redirects to another host, like www.example.com to example.com
+    #[arg(long)]
+    same_host_redirects: bool,
+    /// Path tried when robots.txt lists no sitemaps (repeatable; default: /sitemap.xml and other usual ones)
+    #[arg(long = "sitemap-path", value_name = "PATH")]
+    sitemap_paths: Vec<String>,
+    /// GET every candidate path instead of probing it with a HEAD first
+    #[arg(long)]
+    no_probe: bool,
+    /// Don't look for <link rel="sitemap"> on the homepage when no candidate path exists
+    #[arg(long)]
+    no_homepage_links: bool,
+    /// Repair common sitemap breakage instead of failing on it
+    #[arg(long)]
+    quirks: bool,
+    /// Header sent with every request, as `Name: value` (repeatable)
+    #[arg(short = 'H', long = "header", value_name = "HEADER", value_parser = header)]
+    headers: Vec<(String, String)>,
+    /// User agent to send instead of the default
+    #[arg(long)]
+    user_agent: Option<String>,
+    /// HTTP(S) or SOCKS5 proxy URL to send every request through
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+    /// Accept invalid TLS certificates (self-signed staging hosts)
+    #[arg(long)]
+    insecure: bool,
+    /// Never negotiate HTTP/2
+    #[arg(long)]
+    http1: bool,
+    /// Reuse each host's resolved addresses for this many seconds
+    #[arg(long, value_name = "SECONDS")]
+    dns_cache_ttl: Option<u64>,
+    /// Refuse hosts that resolve to loopback, private, link-local, or other non-public addresses
+    #[arg(long)]
+    block_private_ips: bool,
+    /// Refuse addresses in this CIDR network (repeatable)
+    #[arg(long = "deny-network", value_name = "CIDR", value_parser = parse_network)]
+    deny_networks: Vec<IpNet>,
+    /// Allow addresses in this CIDR network even if otherwise refused (repeatable)
+    #[arg(long = "allow-network", value_name = "CIDR", value_parser = parse_network)]
+    allow_networks: Vec<IpNet>,
+    /// Fetch only from this host and its subdomains (repeatable)
+    #[arg(long = "allow-host", value_name = "HOST")]
+    allow_hosts: Vec<String>,
+    /// Never fetch from this host or its subdomains (repeatable)
+    #[arg(long = "deny-host", value_name = "HOST")]
+    deny_hosts: Vec<String>,
+    /// Query this nameserver, `ADDR` or `ADDR:PORT`, instead of the system resolver;
+    /// needs the `hickory` feature (repeatable)
+    #[arg(long = "dns-server", value_name = "ADDR", value_parser = parse_nameserver)]
+    dns_servers: Vec<SocketAddr>,
+    /// Resolve a host to a fixed address, as `HOST=ADDR` (repeatable)
+    #[arg(long = "resolve", value_name = "HOST=ADDR", value_parser = resolve_override)]
+    resolve: Vec<(String, IpAddr)>,
+    /// Leave out URLs robots.txt disallows and honor its Crawl-delay
+    #[arg(long)]
+    respect_robots: bool,
+    /// Keep only URLs matching a glob or `re:` pattern (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<String>,
+    /// Drop URLs matching a glob or `re:` pattern (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+    /// Follow only nested sitemaps matching a glob or `re:` pattern (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    include_sitemaps: Vec<String>,
+    /// Skip sitemaps and URLs whose lastmod is before this ISO 8601 date or timestamp
+    #[arg(long, value_name = "DATE", value_parser = cutoff)]
+    modified_since: Option<DateTime<Utc>>,
+    /// Include lastmod, changefreq, and priority in JSON output
+    #[arg(long)]
+    metadata: bool,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Write here instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+    /// Sort each site's URLs and write sites in the order given
+    #[arg(long)]
+    sorted: bool,
+    /// Don't print per-site summaries and errors to stderr
+    #[arg(short, long)]
+    quiet: bool,
+    /// Record progress here, and skip sites and sitemaps an earlier run recorded
+    #[arg(long, value_name = "FILE")]
+    checkpoint: Option<PathBuf>,
+}
+
+fn header(text: &str) -> Result<(String, String), String> {
+    let (name, value) = text.split_once(':').ok_or_else(|| format!("expected `Name: value`, got {:?}", text))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+fn resolve_override(text: &str) -> Result<(String, IpAddr), String> {
+    let (host, addr) = text.split_once('=').ok_or_else(|| format!("expected `HOST=ADDR`, got {:?}", text))?;
+    let addr = addr.trim().trim_start_matches('[').trim_end_matches(']');
+    Ok((host.trim().to_ascii_lowercase(), addr.parse().map_err(|_| format!("not an IP address: {:?}", addr))?))
+}
+
+fn cutoff(text: &str) -> Result<DateTime<Utc>, String> {
+    parse_cutoff(text).ok_or_else(|| format!("not an ISO 8601 date or timestamp: {:?}", text))
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(args).await {
+        Ok(code) => ExitCode::from(code),
+        Err(e) => {
+            eprintln!("sitemap-parse: {}", e);
+            ExitCode::from(EXIT_FATAL)
+        }
+    }
+}
+
+async fn run(args: Args) -> Result<u8, Error> {
+    let sites = read_sites(&args.sites)?;
+    let filter = match (args.include.is_empty(), args.exclude.is_empty(), args.include_sitemaps.is_empty()) {
+        (true, true, true) => None,
+        _ => Some(Arc::new(UrlFilter::new(&args.include, &args.exclude, &args.include_sitemaps)?)),
+    };
+    let checkpoint = match &args.checkpoint {
+        Some(path) => Some(Arc::new(Checkpoint::open(path).map_err(|e| format!("Failed to open checkpoint {}: {}", path.display(), e))?)),
+        None => None,
+    };
+    let shutdown = Arc::new(Shutdown::new());
+    let dns = dns_resolver(&args);
+    let client_config = ClientConfig {
+        headers: ClientConfig::parse_headers(args.headers.iter().map(|(name, value)| (name.as_str(), value.as_str())))?,
+        user_agent: args.user_agent.clone(),
+        proxy: args.proxy.clone(),
+        danger_accept_invalid_certs: args.insecure,
+        http2: !args.http1,
+        dns: dns.clone(),
+    };
+    let client = client_config.build(Duration::from_secs(args.timeout))?;
+    let parser = RustSitemapParser::from_client(client, args.concurrency, args.max_sitemaps, args.max_depth, args.max_nested_per_level)
+        .with_user_agent(client_config.user_agent())
+        .with_max_response_bytes(args.max_response_bytes)
+        .with_request_limits(Arc::new(RequestLimiter::new(args.max_requests, args.max_requests_per_host)))
+        .with_host_pacer(Arc::new(HostPacer::new(args.host_delay_ms.map(Duration::from_millis))))
+        .with_retry_policy(RetryPolicy { max_retries: args.max_retries, ..Default::default() })
+        .with_redirect_policy(RedirectPolicy { max_redirects: args.max_redirects, cross_host: !args.same_host_redirects })
+        .with_discovery(discovery_policy(&args))
+        .with_quirks(args.quirks)
+        .with_respect_robots(args.respect_robots)
+        .with_url_filter(filter)
+        .with_canonicalizer(canonicalizer(&args))
+        .with_dns(dns)
+        .with_modified_since(args.modified_since)
+        .with_url_metadata(args.metadata)
+        .with_deterministic(args.sorted)
+        .with_checkpoint(checkpoint)
+        .with_site_timeout(args.site_timeout.map(Duration::from_secs))
+        .with_max_urls_per_site(args.max_urls_per_site)
+        .with_shutdown(Arc::clone(&shutdown));
+
+    let stop = Arc::clone(&shutdown);
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("sitemap-parse: interrupted, finishing requests in flight (Ctrl-C again to quit)");
+            stop.request(DEFAULT_GRACE);
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(EXIT_INTERRUPTED.into());
+            }
+        }
+    });
+
+    let out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut out = BufWriter::new(out);
+    let mut outcomes = Vec::new();
+    if args.format == Format::Json {
+        out.write_all(b"[")?;
+    }
+    let mut results = parser.parse_site_stream(futures::stream::iter(sites));
+    while let Some(result) = results.next().await {
+        if !args.quiet {
+            report(&result);
+        }
+        if args.format == Format::Json && !outcomes.is_empty() {
+            out.write_all(b",")?;
+        }
+        write_site(&mut out, args.format, &result, args.metadata, args.sorted)?;
+        outcomes.push(SiteOutcome::of(&result));
+    }
+    if args.format == Format::Json {
+        out.write_all(b"]\n")?;
+    }
+    out.flush()?;
+    // Sites the interrupt kept from starting never come out of the stream
+    if shutdown.is_requested() {
+        outcomes.push(SiteOutcome::Interrupted);
+    }
+    Ok(exit_code(&outcomes))
+}
+
+/// Where to look for sitemaps robots.txt doesn't list, from the discovery flags
+fn discovery_policy(args: &Args) -> DiscoveryPolicy {
+    let defaults = DiscoveryPolicy::default();
+    DiscoveryPolicy {
+        candidate_paths: if args.sitemap_paths.is_empty() { defaults.candidate_paths } else { args.sitemap_paths.clone() },
+        probe: !args.no_probe,
+        homepage_links: !args.no_homepage_links,
+    }
+}
+
+/// URL canonicalization from the canonicalization flags, if any are given
+fn canonicalizer(args: &Args) -> Option<Arc<UrlCanonicalizer>> {
+    let canonicalizer = UrlCanonicalizer { trailing_slash: args.trailing_slash, sort_query: args.sort_query, strip_params: args.strip_params.clone() };
+    (args.canonicalize || canonicalizer != UrlCanonicalizer::default()).then(|| Arc::new(canonicalizer))
+}
+
+/// Name resolution and address checks from the DNS flags, if any are given
+fn dns_resolver(args: &Args) -> Option<DnsResolver> {
+    let mut policy = DnsPolicy {
+        cache_ttl: args.dns_cache_ttl.map(Duration::from_secs),
+        block_private: args.block_private_ips,
+        deny_networks: args.deny_networks.clone(),
+        allow_networks: args.allow_networks.clone(),
+        allow_hosts: args.allow_hosts.clone(),
+        deny_hosts: args.deny_hosts.clone(),
+        nameservers: args.dns_servers.clone(),
+        ..Default::default()
+    };
+    for (host, addr) in &args.resolve {
+        policy.overrides.entry(host.clone()).or_default().push(*addr);
+    }
+    (policy != DnsPolicy::default()).then(|| DnsResolver::new(policy))
+}
+
+/// `sites` with each `-` replaced by the non-blank, non-`#` lines of stdin
+fn read_sites(sites: &[String]) -> io::Result<Vec<String>> {
+    let mut all = Vec::new();
+    for site in sites {
+        if site != "-" {
+            all.push(site.clone());
+            continue;
+        }
+        for line in io::stdin().lock().lines() {
+            let line = line?;
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                all.push(line.to_string());
+            }
+        }
+    }
+    Ok(all)
+}
+
+/// One summary line per site on stderr, followed by its errors
+fn report(result: &ParsedSiteResult) {
+    eprintln!(
+        "{}: {} ({} URLs from {} sitemaps, {} requests, {:.1}s)",
+        result.base_url,
+        SiteOutcome::of(result),
+        result.url_count(),
+        result.sitemaps_found.len(),
+        result.total_requests,
+        result.parse_time,
+    );
+    for error in &result.errors {
+        eprintln!("  {}", error);
+    }
+}
+
+/// The worst outcome's exit status
+fn exit_code(outcomes: &[SiteOutcome]) -> u8 {
+    if outcomes.contains(&SiteOutcome::Interrupted) {
+        EXIT_INTERRUPTED
+    } else if outcomes.contains(&SiteOutcome::Failed) {
+        EXIT_FAILED
+    } else if outcomes.contains(&SiteOutcome::Partial) {
+        EXIT_PARTIAL
+    } else {
+        EXIT_OK
+    }
+}
+
+fn write_site(out: &mut impl Write, format: Format, result: &ParsedSiteResult, metadata: bool, sorted: bool) -> io::Result<()> {
+    let urls = result.url_list(sorted);
+    match format {
+        Format::Text => {
+            for url in &urls {
+                writeln!(out, "{}", url)?;
+            }
+        }
+        Format::Jsonl => {
+            for url in &urls {
+                let mut record = Map::new();
+                record.insert("site".to_string(), json!(result.base_url));
+                record.insert("url".to_string(), json!(url));
+                if metadata {
+                    record.extend(metadata_fields(result.url_metadata.get(url)));
+                }
+                writeln!(out, "{}", Value::Object(record))?;
+            }
+        }
+        Format::Json => {
+            let errors: Vec<Value> = result.error_details.iter()
+                .map(|e| json!({"kind": e.kind(), "url": e.url(), "message": e.to_string()}))
+                .collect();
+            let mut site = json!({
+                "base_url": result.base_url,
+                "outcome": SiteOutcome::of(result).to_string(),
+                "urls": urls,
+                "sitemaps_found": result.sitemaps_found,
+                "redirects": result.redirects,
+                "errors": errors,
+                "total_requests": result.total_requests,
+                "bytes_downloaded": result.bytes_downloaded(),
+                "parse_time": result.parse_time,
+            });
+            if metadata {
+                site["url_metadata"] = urls.iter()
+                    .map(|url| (url.clone(), Value::Object(metadata_fields(result.url_metadata.get(url)))))
+                    .collect::<Map<_, _>>()
+                    .into();
+            }
+            write!(out, "{}", site)?;
+        }
+    }
+    Ok(())
+}
+
+/// The fields a sitemap gave for a URL, leaving out those it didn't
+fn metadata_fields(meta: Option<&UrlMetadata>) -> Map<String, Value> {
+    let mut fields = Map::new();
+    let Some(meta) = meta else { return fields };
+    if let Some(lastmod) = &meta.lastmod {
+        fields.insert("lastmod".to_string(), json!(lastmod));
+    }
+    if let Some(changefreq) = &meta.changefreq {
+        fields.insert("changefreq".to_string(), json!(changefreq));
+    }
+    if let Some(priority) = meta.priority {
+        fields.insert("priority".to_string(), json!(priority));
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_parser::SitemapError;
+
+    fn site() -> ParsedSiteResult {
+        let mut result = ParsedSiteResult::new("https://example.com".to_string());
+        result.urls.extend(["https://example.com/b".to_string(), "https://example.com/a".to_string()]);
+        result.url_metadata.insert("https://example.com/a".to_string(), UrlMetadata {
+            lastmod: Some("2024-06-01".to_string()),
+            priority: Some(0.8),
+            ..Default::default()
+        });
+        result
+    }
+
+    #[test]
+    fn test_exit_code_reports_the_worst_outcome() {
+        assert_eq!(exit_code(&[]), EXIT_OK);
+        assert_eq!(exit_code(&[SiteOutcome::Ok, SiteOutcome::Partial]), EXIT_PARTIAL);
+        assert_eq!(exit_code(&[SiteOutcome::Failed, SiteOutcome::Partial]), EXIT_FAILED);
+        assert_eq!(exit_code(&[SiteOutcome::Failed, SiteOutcome::Interrupted]), EXIT_INTERRUPTED);
+    }
+
+    #[test]
+    fn test_write_site_formats() {
+        let mut result = site();
+        let write = |format, result: &ParsedSiteResult| {
+            let mut out = Vec::new();
+            write_site(&mut out, format, result, true, true).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(write(Format::Text, &result), "https://example.com/a\nhttps://example.com/b\n");
+        assert_eq!(write(Format::Jsonl, &result), concat!(
+            r#"{"lastmod":"2024-06-01","priority":0.8,"site":"https://example.com","url":"https://example.com/a"}"#, "\n",
+            r#"{"site":"https://example.com","url":"https://example.com/b"}"#, "\n",
+        ));
+
+        result.record_error("HTTP 404 Not Found for https://example.com/sitemap-2.xml".to_string(), SitemapError::Http {
+            url: "https://example.com/sitemap-2.xml".to_string(),
+            status: 404,
+        });
+        let site: Value = serde_json::from_str(&write(Format::Json, &result)).unwrap();
+        assert_eq!(site["outcome"], "partial");
+        assert_eq!(site["urls"], json!(["https://example.com/a", "https://example.com/b"]));
+        assert_eq!(site["errors"][0]["kind"], "http");
+        assert_eq!(site["url_metadata"]["https://example.com/a"]["lastmod"], "2024-06-01");
+        assert_eq!(site["url_metadata"]["https://example.com/b"], json!({}));
+    }
+}
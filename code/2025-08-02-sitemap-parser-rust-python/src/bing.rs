@@ -0,0 +1,79 @@
+use reqwest::Client;
+use serde_json::Value;
+use url::Url;
+
+const GET_FEEDS_API: &str = "https://ssl.bing.com/webmaster/api.svc/json/GetFeeds";
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Lists the sitemaps a site owner submitted to Bing Webmaster Tools.
+///
+/// Sites often register sitemaps there that robots.txt never mentions. The API key
+/// comes from the Bing Webmaster Tools settings of an account that has verified the
+/// property; it only sees that account's sites.
+pub struct BingWebmasterSource {
+    api_key: String,
+}
+
+impl BingWebmasterSource {
+    pub fn new(api_key: String) -> Self {
+        Self { api_key }
+    }
+
+    /// Sitemap and feed URLs registered for `site_url`, the property as verified (e.g. `https://example.com/`)
+    pub async fn sitemaps(&self, client: &Client, site_url: &str) -> Result<Vec<String>, Error> {
+        let query = Url::parse_with_params(GET_FEEDS_API, &[("siteUrl", site_url), ("apikey", self.api_key.as_str())])?;
+        // The key is in the query string; keep it out of error messages that end up in results
+        let answer: Value = client
+            .get(query)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|e| e.without_url())?
+            .json()
+            .await
+            .map_err(|e| e.without_url())?;
+        sitemaps_from_answer(&answer)
+    }
+}
+
+fn sitemaps_from_answer(answer: &Value) -> Result<Vec<String>, Error> {
+    if let Some(message) = answer["Message"].as_str() {
+        return Err(format!("Bing Webmaster API error: {}", message).into());
+    }
+    let feeds = answer["d"].as_array().ok_or("Bing Webmaster API returned no feed list")?;
+    Ok(feeds.iter().filter_map(|feed| feed["Url"].as_str()).map(str::to_string).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sitemaps_from_answer() {
+        let answer = json!({
+            "d": [
+                {
+                    "__type": "Feed:#Microsoft.Bing.Webmaster.Api",
+                    "Compressed": false,
+                    "FileSize": 5120,
+                    "LastCrawled": "/Date(1718000000000)/",
+                    "Status": "Success",
+                    "Submitted": "/Date(1717000000000)/",
+                    "Type": "Sitemap",
+                    "Url": "https://example.com/sitemap-products.xml",
+                    "UrlCount": 120
+                },
+                { "Type": "Atom", "Url": "https://example.com/feed.atom" }
+            ]
+        });
+        assert_eq!(sitemaps_from_answer(&answer).unwrap(), vec![
+            "https://example.com/sitemap-products.xml".to_string(),
+            "https://example.com/feed.atom".to_string(),
+        ]);
+
+        let denied = json!({ "ErrorCode": 14, "Message": "NotAuthorized" });
+        assert_eq!(sitemaps_from_answer(&denied).unwrap_err().to_string(), "Bing Webmaster API error: NotAuthorized");
+    }
+}
@@ -0,0 +1,198 @@
+use log::{debug, warn};
+use reqwest::blocking::Client;
+use std::collections::HashSet;
+use std::io::Read;
+use std::time::{Duration, Instant};
+use url::Url;
+
+use crate::decode::BodyDecoder;
+use crate::parser::{DEFAULT_MAX_RESPONSE_BYTES, USER_AGENT};
+use crate::robots::parse_robots_txt;
+use crate::sitemap::parse_sitemap_xml_with;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Bytes read from a response at a time
+const READ_CHUNK: usize = 64 * 1024;
+
+/// URLs and bookkeeping from parsing one site
+#[derive(Debug, Clone, Default)]
+pub struct SiteResult {
+    pub base_url: String,
+    pub urls: HashSet<String>,
+    pub sitemaps_found: Vec<String>,
+    pub errors: Vec<String>,
+    pub total_requests: usize,
+    pub parse_time: f64,
+}
+
+/// Synchronous sitemap parser on `reqwest::blocking`, for callers without an async runtime.
+///
+/// Same limits and defaults as the async parser, and the same robots.txt parsing,
+/// sitemap parsing, and gzip sniffing. Sitemaps are fetched one at a time, so it
+/// gives up the async parser's throughput in exchange for a plain function call.
+pub struct SitemapParser {
+    client: Client,
+    max_sitemaps: usize,
+    max_depth: usize,
+    max_nested_per_level: usize,
+    max_response_bytes: usize,
+    quirks: bool,
+}
+
+impl SitemapParser {
+    pub fn new(max_sitemaps: usize, max_depth: usize, max_nested_per_level: usize, timeout: Duration) -> Result<Self, Error> {
+        let client = Client::builder().timeout(timeout).user_agent(USER_AGENT).build()?;
+        Ok(Self {
+            client,
+            max_sitemaps,
+            max_depth,
+            max_nested_per_level,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            quirks: false,
+        })
+    }
+
+    /// Cap the bytes read from any single response; larger sitemaps keep the URLs parsed so far
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = max_response_bytes;
+        self
+    }
+
+    /// Tolerate common sitemap breakage (stray BOMs, HTML wrappers, unclosed tags, double-encoded entities)
+    pub fn with_quirks(mut self, quirks: bool) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Fetch robots.txt and every sitemap it leads to, within the configured limits
+    pub fn parse_site(&self, base_url: &str) -> Result<SiteResult, Error> {
+        let start_time = Instant::now();
+        let mut result = SiteResult { base_url: base_url.to_string(), ..Default::default() };
+
+        let normalized = normalize_url(base_url)?;
+        let root = normalized.trim_end_matches('/');
+        let robots_url = format!("{}/robots.txt", root);
+
+        match self.fetch(&robots_url) {
+            Ok(robots) => {
+                result.total_requests += 1;
+                let sitemaps = parse_robots_txt(&robots, &normalized);
+                result.sitemaps_found = if sitemaps.is_empty() {
+                    vec![
+                        format!("{}/sitemap.xml", root),
+                        format!("{}/sitemap_index.xml", root),
+                        format!("{}/sitemaps.xml", root),
+                    ]
+                } else {
+                    sitemaps
+                };
+
+                for sitemap_url in result.sitemaps_found.iter().take(self.max_sitemaps) {
+                    match self.process_sitemap(sitemap_url, &normalized, self.max_depth, &mut result.urls) {
+                        Ok(requests) => result.total_requests += requests,
+                        Err(e) => result.errors.push(format!("Error processing sitemap: {}", e)),
+                    }
+                }
+            }
+            Err(e) => result.errors.push(format!("Could not fetch robots.txt from {}: {}", robots_url, e)),
+        }
+
+        result.parse_time = start_time.elapsed().as_secs_f64();
+        Ok(result)
+    }
+
+    /// Parse one sitemap into `urls` and recurse into its children; returns the requests made
+    fn process_sitemap(&self, sitemap_url: &str, base_url: &str, max_depth: usize, urls: &mut HashSet<String>) -> Result<usize, Error> {
+        if max_depth == 0 {
+            return Ok(0);
+        }
+        debug!("🦀 Processing single sitemap: {} (depth: {})", sitemap_url, max_depth);
+
+        let body = self.fetch(sitemap_url)?;
+        let parsed = parse_sitemap_xml_with(&body, base_url, self.quirks)?;
+        urls.extend(parsed.urls);
+
+        let mut requests = 1;
+        if max_depth > 1 {
+            for nested_url in parsed.nested_sitemaps.iter().take(self.max_nested_per_level) {
+                match self.process_sitemap(nested_url, base_url, max_depth - 1, urls) {
+                    Ok(nested) => requests += nested,
+                    Err(e) => warn!("🦀 Error processing nested sitemap: {}", e),
+                }
+            }
+        }
+        Ok(requests)
+    }
+
+    /// GET `url` and decode its body, keeping at most `max_response_bytes`
+    fn fetch(&self, url: &str) -> Result<String, Error> {
+        let mut resp = self.client.get(url).send()?;
+        if !resp.status().is_success() {
+            return Err(format!("HTTP {} for {}", resp.status(), url).into());
+        }
+
+        let content_type = resp.headers().get(reqwest::header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).map(str::to_string);
+        let mut decoder = BodyDecoder::new(self.max_response_bytes).with_content_type(content_type.as_deref());
+        let mut chunk = vec![0; READ_CHUNK];
+        let mut bytes_read = 0;
+        let mut truncated = false;
+        loop {
+            match resp.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let kept = n.min(self.max_response_bytes - bytes_read);
+                    bytes_read += kept;
+                    decoder.push(&chunk[..kept]);
+                    if kept < n || decoder.is_full() {
+                        truncated = true;
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) if bytes_read > 0 => {
+                    warn!("🦀 Body from {} cut off after {} bytes: {}", url, bytes_read, e);
+                    truncated = true;
+                    break;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if truncated {
+            warn!("🦀 Keeping partial body of {} ({} bytes)", url, bytes_read);
+        }
+        Ok(decoder.finish(truncated)?.text)
+    }
+}
+
+/// Add a scheme if missing and drop any fragment, as the async parser does
+fn normalize_url(url: &str) -> Result<String, Error> {
+    let with_scheme = if url.starts_with("http://") || url.starts_with("https://") {
+        url.to_string()
+    } else {
+        format!("https://{}", url)
+    };
+    let mut parsed = Url::parse(&with_scheme)?;
+    parsed.set_fragment(None);
+    Ok(parsed.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticConfig, SyntheticServer};
+
+    #[test]
+    fn test_blocking_parse_synthetic_site() {
+        // The server needs a runtime; the parser must run outside it
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let config = SyntheticConfig { urls_per_sitemap: 20, depth: 2, fanout: 3, gzip: true, ..Default::default() };
+        let server = runtime.block_on(SyntheticServer::start(&config)).unwrap();
+
+        let parser = SitemapParser::new(10, 2, 5, Duration::from_secs(5)).unwrap();
+        let result = parser.parse_site(&server.base_url()).unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.urls.len(), server.expected_urls);
+        assert_eq!(result.total_requests, 5);
+    }
+}
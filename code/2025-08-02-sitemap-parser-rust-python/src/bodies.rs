@@ -0,0 +1,65 @@
+use serde_json::json;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Longest readable prefix a body's file name keeps before its hash
+const MAX_SLUG_LEN: usize = 80;
+
+/// Writes decoded sitemap bodies into a directory, one file per sitemap URL.
+///
+/// Each body goes to [`body_file_name`] of its URL, so the file for a URL can be
+/// found without a lookup, and `index.jsonl` gets a line mapping the URL to its
+/// file. Refetching a sitemap overwrites its file; the index keeps every line.
+pub struct BodyStore {
+    dir: PathBuf,
+    index: Mutex<File>,
+}
+
+impl BodyStore {
+    pub fn create(dir: &Path) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let index = OpenOptions::new().create(true).append(true).open(dir.join("index.jsonl"))?;
+        Ok(Self { dir: dir.to_path_buf(), index: Mutex::new(index) })
+    }
+
+    /// Write `body` as fetched from `url`, returning the file it went to
+    pub fn store(&self, url: &str, body: &str) -> std::io::Result<PathBuf> {
+        let file_name = body_file_name(url);
+        let path = self.dir.join(&file_name);
+        std::fs::write(&path, body)?;
+        let mut line = serde_json::to_vec(&json!({ "url": url, "file": file_name, "bytes": body.len() })).map_err(std::io::Error::other)?;
+        line.push(b'\n');
+        self.index.lock().unwrap().write_all(&line)?;
+        Ok(path)
+    }
+}
+
+/// File name for the body of `url`: a readable slug of the URL plus a hash of all of it,
+/// e.g. `example.com_sitemap.xml-97f98fca8f35.xml`
+pub fn body_file_name(url: &str) -> String {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let slug: String = without_scheme
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-') { c } else { '_' })
+        .take(MAX_SLUG_LEN)
+        .collect();
+    let hash = sha1_smol::Sha1::from(url).digest().to_string();
+    format!("{}-{}.xml", slug.trim_matches('_'), &hash[..12])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_file_names_are_safe_and_distinct() {
+        let a = body_file_name("https://example.com/sitemap.xml?page=1");
+        let b = body_file_name("https://example.com/sitemap.xml?page=2");
+        assert!(a.starts_with("example.com_sitemap.xml_page_1-"), "{}", a);
+        assert_ne!(a, b);
+        assert!(!body_file_name("https://example.com/../../etc/passwd").contains('/'));
+        assert!(body_file_name(&format!("https://example.com/{}", "a".repeat(500))).len() < 100);
+    }
+}
@@ -0,0 +1,151 @@
+use log::warn;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::bodies::body_file_name;
+use crate::job::write_atomically;
+
+/// A response kept for revalidation: its raw body as received (before gunzipping or
+/// transcoding) and the validators to send next time
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedResponse {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+impl CachedResponse {
+    /// The response to cache from `headers` and its full body, if it carries an ETag or
+    /// Last-Modified to revalidate with
+    pub fn from_response(headers: &HeaderMap, body: Vec<u8>) -> Option<Self> {
+        let header = |name| headers.get(name).and_then(|v: &HeaderValue| v.to_str().ok()).map(str::to_string);
+        let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+        Some(Self { etag, last_modified, content_type: header(CONTENT_TYPE), body })
+    }
+
+    /// `If-None-Match` and `If-Modified-Since` for a conditional request
+    pub fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in [(IF_NONE_MATCH, &self.etag), (IF_MODIFIED_SINCE, &self.last_modified)] {
+            if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+        headers
+    }
+}
+
+/// Where fetched responses are kept between requests, so an unchanged sitemap is
+/// answered with a 304 instead of downloaded again.
+///
+/// Implementations are shared by every fetch a parser makes and must not block for
+/// long; failures to store should be logged rather than surfaced.
+pub trait HttpCache: Send + Sync {
+    fn get(&self, url: &str) -> Option<CachedResponse>;
+    fn put(&self, url: &str, response: CachedResponse);
+}
+
+/// Responses kept in memory for as long as the cache lives
+#[derive(Default)]
+pub struct MemoryCache {
+    entries: Mutex<HashMap<String, CachedResponse>>,
+}
+
+impl HttpCache for MemoryCache {
+    fn get(&self, url: &str) -> Option<CachedResponse> {
+        self.entries.lock().unwrap().get(url).cloned()
+    }
+
+    fn put(&self, url: &str, response: CachedResponse) {
+        self.entries.lock().unwrap().insert(url.to_string(), response);
+    }
+}
+
+/// Responses kept in a directory, so they outlive the process: each URL's body goes
+/// to [`body_file_name`] of it, and its validators to the same name plus `.json`
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    pub fn open(dir: &Path) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self { dir: dir.to_path_buf() })
+    }
+
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let name = body_file_name(url);
+        (self.dir.join(&name), self.dir.join(format!("{}.json", name)))
+    }
+}
+
+impl HttpCache for DiskCache {
+    /// A missing, unreadable, or mismatched entry reads as a miss
+    fn get(&self, url: &str) -> Option<CachedResponse> {
+        let (body_path, meta_path) = self.paths(url);
+        let meta: Value = serde_json::from_slice(&std::fs::read(meta_path).ok()?).ok()?;
+        // A hash collision would otherwise serve another URL's body
+        if meta["url"].as_str() != Some(url) {
+            return None;
+        }
+        let field = |key: &str| meta[key].as_str().map(str::to_string);
+        Some(CachedResponse {
+            etag: field("etag"),
+            last_modified: field("last_modified"),
+            content_type: field("content_type"),
+            body: std::fs::read(body_path).ok()?,
+        })
+    }
+
+    fn put(&self, url: &str, response: CachedResponse) {
+        let (body_path, meta_path) = self.paths(url);
+        let meta = json!({
+            "url": url,
+            "etag": response.etag,
+            "last_modified": response.last_modified,
+            "content_type": response.content_type,
+        });
+        // The body goes first, so validators never point at a stale one
+        let written = write_atomically(&body_path, &response.body)
+            .and_then(|_| write_atomically(&meta_path, meta.to_string().as_bytes()));
+        if let Err(e) = written {
+            warn!("🦀 Could not cache response for {}: {}", url, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_cache_round_trip() {
+        let dir = std::env::temp_dir().join(format!("sitemap-cache-{}", uuid::Uuid::new_v4()));
+        let cache = DiskCache::open(&dir).unwrap();
+        let url = "https://example.com/sitemap.xml";
+        assert_eq!(cache.get(url), None);
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(CachedResponse::from_response(&headers, b"<urlset/>".to_vec()), None);
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/xml"));
+        let response = CachedResponse::from_response(&headers, b"<urlset/>".to_vec()).unwrap();
+        cache.put(url, response.clone());
+
+        let reopened = DiskCache::open(&dir).unwrap();
+        assert_eq!(reopened.get(url), Some(response.clone()));
+        assert_eq!(reopened.get("https://example.com/other.xml"), None);
+        let conditional = response.conditional_headers();
+        assert_eq!(conditional[IF_NONE_MATCH], "\"v1\"");
+        assert!(!conditional.contains_key(IF_MODIFIED_SINCE));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
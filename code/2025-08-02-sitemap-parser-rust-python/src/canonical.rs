@@ -0,0 +1,148 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use url::Url;
+
+use crate::sitemap::UrlMetadata;
+
+/// What canonicalization does with a trailing slash on a URL's path
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Leave paths as listed
+    #[default]
+    Keep,
+    /// `/a/` becomes `/a`; the root path stays `/`
+    Strip,
+    /// `/a` becomes `/a/`, unless the last segment looks like a file (`/feed.xml`)
+    Add,
+}
+
+impl FromStr for TrailingSlash {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "strip" => Ok(Self::Strip),
+            "add" => Ok(Self::Add),
+            _ => Err(format!("Unknown trailing slash mode {:?}; expected \"keep\", \"strip\", or \"add\"", s)),
+        }
+    }
+}
+
+/// Rewrites each URL a sitemap lists to one canonical form before it joins the site's
+/// URL set, so `https://Example.com/a#top` and `https://example.com/a` count once.
+///
+/// The scheme and host are lowercased and default ports, fragments, and empty queries
+/// dropped, with percent-encoding normalized as the URL standard does. Trailing
+/// slashes, query parameter order, and tracking parameters are up to the options.
+/// URLs that don't parse are kept as listed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UrlCanonicalizer {
+    pub trailing_slash: TrailingSlash,
+    /// Sort query parameters by name, keeping repeated ones in their listed order
+    pub sort_query: bool,
+    /// Query parameters removed, by name; a name ending in `*` removes every parameter
+    /// starting with the rest, as `utm_*` does
+    pub strip_params: Vec<String>,
+}
+
+impl UrlCanonicalizer {
+    pub fn canonicalize(&self, url: &str) -> String {
+        let Ok(mut parsed) = Url::parse(url) else { return url.to_string() };
+        parsed.set_fragment(None);
+        if let Some(query) = parsed.query() {
+            let mut params: Vec<&str> = query.split('&').filter(|param| !param.is_empty() && !self.strips(param_name(param))).collect();
+            if self.sort_query {
+                params.sort_by_key(|param| param_name(param));
+            }
+            let query = params.join("&");
+            parsed.set_query((!query.is_empty()).then_some(query.as_str()));
+        }
+        if !parsed.cannot_be_a_base() {
+            let path = parsed.path();
+            match self.trailing_slash {
+                TrailingSlash::Strip if path.len() > 1 && path.ends_with('/') => {
+                    let stripped = path.trim_end_matches('/').to_string();
+                    parsed.set_path(&stripped);
+                }
+                TrailingSlash::Add if !path.ends_with('/') && !path.rsplit('/').next().is_some_and(|segment| segment.contains('.')) => {
+                    let added = format!("{}/", path);
+                    parsed.set_path(&added);
+                }
+                _ => {}
+            }
+        }
+        parsed.into()
+    }
+
+    /// Canonicalize a sitemap's `urls` in place, moving their `metadata` along, and
+    /// return how many collapsed into a URL already there
+    pub fn canonicalize_all(&self, urls: &mut HashSet<String>, metadata: &mut HashMap<String, UrlMetadata>) -> usize {
+        let listed = urls.len();
+        *urls = urls.drain()
+            .map(|url| {
+                let canonical = self.canonicalize(&url);
+                if canonical != url {
+                    if let Some(meta) = metadata.remove(&url) {
+                        metadata.entry(canonical.clone()).or_insert(meta);
+                    }
+                }
+                canonical
+            })
+            .collect();
+        listed - urls.len()
+    }
+
+    fn strips(&self, name: &str) -> bool {
+        self.strip_params.iter().any(|strip| match strip.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == strip,
+        })
+    }
+}
+
+fn param_name(param: &str) -> &str {
+    param.split_once('=').map_or(param, |(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize_options() {
+        let basic = UrlCanonicalizer::default();
+        assert_eq!(basic.canonicalize("HTTPS://Example.COM:443/a/?#top"), "https://example.com/a/");
+        assert_eq!(basic.canonicalize("https://example.com/a?b=1&a=2"), "https://example.com/a?b=1&a=2");
+        assert_eq!(basic.canonicalize("not a url"), "not a url");
+
+        let strict = UrlCanonicalizer {
+            trailing_slash: TrailingSlash::Strip,
+            sort_query: true,
+            strip_params: vec!["utm_*".into(), "gclid".into()],
+        };
+        assert_eq!(strict.canonicalize("https://example.com/a/?utm_source=x&z=1&gclid=9&b=2&z=0"), "https://example.com/a?b=2&z=1&z=0");
+        assert_eq!(strict.canonicalize("https://example.com/?utm_medium=email"), "https://example.com/");
+        assert_eq!(strict.canonicalize("https://example.com/a?gclidx=1"), "https://example.com/a?gclidx=1");
+
+        let slashed = UrlCanonicalizer { trailing_slash: TrailingSlash::Add, ..Default::default() };
+        assert_eq!(slashed.canonicalize("https://example.com/a"), "https://example.com/a/");
+        assert_eq!(slashed.canonicalize("https://example.com/feed.xml?x=1"), "https://example.com/feed.xml?x=1");
+        assert_eq!("add".parse(), Ok(TrailingSlash::Add));
+        assert!("both".parse::<TrailingSlash>().is_err());
+    }
+
+    #[test]
+    fn test_canonicalize_all_collapses_variants() {
+        let canonicalizer = UrlCanonicalizer { trailing_slash: TrailingSlash::Strip, strip_params: vec!["ref".into()], ..Default::default() };
+        let mut urls: HashSet<String> = ["https://example.com/a", "https://example.com/a/", "https://example.com/a?ref=nav", "https://example.com/b#x"]
+            .map(String::from)
+            .into();
+        let mut metadata = HashMap::from([("https://example.com/b#x".to_string(), UrlMetadata { priority: Some(0.5), ..Default::default() })]);
+
+        assert_eq!(canonicalizer.canonicalize_all(&mut urls, &mut metadata), 2);
+        assert_eq!(urls, ["https://example.com/a", "https://example.com/b"].map(String::from).into());
+        assert_eq!(metadata["https://example.com/b"].priority, Some(0.5));
+    }
+}
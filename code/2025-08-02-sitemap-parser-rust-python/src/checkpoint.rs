@@ -0,0 +1,216 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::job::write_atomically;
+use crate::parser::{ParsedSiteResult, TruncatedSitemap};
+use crate::redirect::RedirectChain;
+use crate::sitemap::SitemapParseResult;
+use crate::wayback::ArchivedCapture;
+
+/// One sitemap of a site that hadn't finished, as it was fetched and parsed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SitemapRecord {
+    /// Base URL of the site it was fetched for, as given
+    pub site: String,
+    pub url: String,
+    /// What the body parsed to, before URL hooks and robots.txt rules
+    pub parsed: SitemapParseResult,
+    pub decompressed: bool,
+    pub encoding: Option<String>,
+    pub redirect: Option<RedirectChain>,
+    pub truncated: Option<TruncatedSitemap>,
+    pub archived: Option<ArchivedCapture>,
+}
+
+/// One line of a checkpoint file
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Record {
+    Site(Box<ParsedSiteResult>),
+    Sitemap(Box<SitemapRecord>),
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum RecordRef<'a> {
+    Site(&'a ParsedSiteResult),
+    Sitemap(&'a SitemapRecord),
+}
+
+struct CheckpointState {
+    file: File,
+    /// Results of sites an earlier run finished, by base URL
+    sites: HashMap<String, ParsedSiteResult>,
+    /// Sitemaps an earlier run fetched for sites it didn't finish, by site then sitemap URL
+    sitemaps: HashMap<String, HashMap<String, SitemapRecord>>,
+}
+
+/// Progress of a multi-site run, kept on disk so a run that dies part-way can be
+/// started again without redoing what it finished.
+///
+/// The file is JSON Lines, appended to as the run goes: each finished site's whole
+/// result, and each sitemap fetched for a site that hasn't finished yet. A parser
+/// given the checkpoint with `with_checkpoint` returns finished sites' results
+/// straight from it and replays recorded sitemaps instead of fetching them again, so
+/// only robots.txt and the sitemaps still missing cost requests.
+///
+/// Resume with the options the checkpoint was written with: recorded sitemaps were
+/// filtered as they were parsed, and finished results are returned as they were.
+pub struct Checkpoint {
+    path: PathBuf,
+    state: Mutex<CheckpointState>,
+}
+
+impl Checkpoint {
+    /// Open the checkpoint at `path`, picking up what an earlier run recorded there
+    /// or starting a new one.
+    ///
+    /// A final line cut short by a crash is dropped; any other line that doesn't
+    /// parse fails with `InvalidData`. The file is compacted as it's opened, leaving
+    /// out sitemaps of sites that went on to finish.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let (sites, sitemaps) = match std::fs::read(path) {
+            Ok(contents) => read_records(path, &contents)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (HashMap::new(), HashMap::new()),
+            Err(e) => return Err(e),
+        };
+        let mut compacted = Vec::new();
+        for result in sites.values() {
+            write_record(&mut compacted, &RecordRef::Site(result))?;
+        }
+        for record in sitemaps.values().flat_map(HashMap::values) {
+            write_record(&mut compacted, &RecordRef::Sitemap(record))?;
+        }
+        write_atomically(path, &compacted)?;
+        if !sites.is_empty() || !sitemaps.is_empty() {
+            let recorded: usize = sitemaps.values().map(HashMap::len).sum();
+            info!("🦀 Resuming from {}: {} sites done, {} sitemaps of unfinished sites recorded", path.display(), sites.len(), recorded);
+        }
+
+        let file = OpenOptions::new().append(true).open(path)?;
+        Ok(Self { path: path.to_path_buf(), state: Mutex::new(CheckpointState { file, sites, sitemaps }) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Sites an earlier run finished
+    pub fn completed_sites(&self) -> usize {
+        self.state.lock().unwrap().sites.len()
+    }
+
+    /// The result an earlier run recorded for `base_url`, if it finished the site
+    pub fn completed(&self, base_url: &str) -> Option<ParsedSiteResult> {
+        self.state.lock().unwrap().sites.get(base_url).cloned()
+    }
+
+    /// Take the record of `url` fetched for `site` by an earlier run, if there is one
+    pub fn take_sitemap(&self, site: &str, url: &str) -> Option<SitemapRecord> {
+        self.state.lock().unwrap().sitemaps.get_mut(site)?.remove(url)
+    }
+
+    /// Record a finished site; its sitemap records are no longer needed
+    pub fn record_site(&self, result: &ParsedSiteResult) -> io::Result<()> {
+        let mut line = Vec::new();
+        write_record(&mut line, &RecordRef::Site(result))?;
+        let mut state = self.state.lock().unwrap();
+        state.sitemaps.remove(&result.base_url);
+        state.file.write_all(&line)
+    }
+
+    /// Record a sitemap fetched for a site that hasn't finished
+    pub fn record_sitemap(&self, record: &SitemapRecord) -> io::Result<()> {
+        let mut line = Vec::new();
+        write_record(&mut line, &RecordRef::Sitemap(record))?;
+        self.state.lock().unwrap().file.write_all(&line)
+    }
+}
+
+type Records = (HashMap<String, ParsedSiteResult>, HashMap<String, HashMap<String, SitemapRecord>>);
+
+fn read_records(path: &Path, contents: &[u8]) -> io::Result<Records> {
+    let mut sites = HashMap::new();
+    let mut sitemaps: HashMap<String, HashMap<String, SitemapRecord>> = HashMap::new();
+    let mut lines = contents.split(|&b| b == b'\n').filter(|line| !line.is_empty()).enumerate().peekable();
+    while let Some((i, line)) = lines.next() {
+        match serde_json::from_slice(line) {
+            Ok(Record::Site(result)) => {
+                sites.insert(result.base_url.clone(), *result);
+            }
+            Ok(Record::Sitemap(record)) => {
+                sitemaps.entry(record.site.clone()).or_default().insert(record.url.clone(), *record);
+            }
+            Err(e) if lines.peek().is_none() && !contents.ends_with(b"\n") => {
+                warn!("🦀 Dropping the last record of {}, cut short: {}", path.display(), e);
+            }
+            Err(e) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} line {} is not a checkpoint record: {}", path.display(), i + 1, e)));
+            }
+        }
+    }
+    sitemaps.retain(|site, _| !sites.contains_key(site));
+    Ok((sites, sitemaps))
+}
+
+fn write_record(out: &mut Vec<u8>, record: &RecordRef) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, record).map_err(io::Error::other)?;
+    out.push(b'\n');
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sitemap(site: &str, url: &str) -> SitemapRecord {
+        SitemapRecord {
+            site: site.to_string(),
+            url: url.to_string(),
+            parsed: SitemapParseResult { urls: [format!("{}/page", site)].into(), ..Default::default() },
+            decompressed: false,
+            encoding: None,
+            redirect: None,
+            truncated: None,
+            archived: None,
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_reopens_and_drops_cut_off_record() {
+        let dir = std::env::temp_dir().join(format!("sitemap-checkpoint-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("run.ckpt");
+
+        let checkpoint = Checkpoint::open(&path).unwrap();
+        checkpoint.record_sitemap(&sitemap("https://a.example", "https://a.example/sitemap.xml")).unwrap();
+        checkpoint.record_sitemap(&sitemap("https://b.example", "https://b.example/sitemap.xml")).unwrap();
+        let mut done = ParsedSiteResult::new("https://a.example".to_string());
+        done.urls.insert("https://a.example/page".to_string());
+        checkpoint.record_site(&done).unwrap();
+        drop(checkpoint);
+        // A crash part-way through writing a record
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"sitemap\":{\"site\":\"https://b.exa").unwrap();
+        drop(file);
+
+        let resumed = Checkpoint::open(&path).unwrap();
+        assert_eq!(resumed.completed_sites(), 1);
+        assert_eq!(resumed.completed("https://a.example").unwrap().urls, done.urls);
+        assert!(resumed.completed("https://b.example").is_none());
+        assert!(resumed.take_sitemap("https://a.example", "https://a.example/sitemap.xml").is_none());
+        let record = resumed.take_sitemap("https://b.example", "https://b.example/sitemap.xml").unwrap();
+        assert!(record.parsed.urls.contains("https://b.example/page"));
+        // Compacted to the finished site and the other site's sitemap
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        std::fs::write(&path, "{\"site\": 1}\n{}\n").unwrap();
+        assert_eq!(Checkpoint::open(&path).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Deduplicates sitemap fetches across the sites of a single batch.
+///
+/// Apex/www pairs and multi-country storefronts often point at the same CDN-hosted
+/// sitemap. The first site to ask for a sitemap URL starts the fetch; every other
+/// site asking for the same URL awaits that fetch instead of issuing its own. Only
+/// the fetch is shared: each site parses and filters what came back under its own
+/// policies.
+pub struct SitemapCoalescer<T> {
+    fetches: Mutex<HashMap<String, Arc<OnceCell<T>>>>,
+}
+
+impl<T: Clone> SitemapCoalescer<T> {
+    pub fn new() -> Self {
+        Self {
+            fetches: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Join the fetch of `sitemap_url`, or start one with `fetch`.
+    ///
+    /// Returns what the fetch gave and `true` if this caller's `fetch` ran (and is
+    /// therefore the one that should be billed for its requests). If the caller that
+    /// started the fetch drops it, e.g. at its site's deadline, the next one waiting
+    /// starts its own.
+    pub async fn fetch<F, Fut>(&self, sitemap_url: &str, fetch: F) -> (T, bool)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let cell = Arc::clone(self.fetches.lock().unwrap().entry(sitemap_url.to_string()).or_default());
+        let mut started_here = false;
+        let outcome = cell.get_or_init(|| {
+            started_here = true;
+            fetch()
+        }).await;
+        (outcome.clone(), started_here)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_second_caller_joins_existing_fetch() {
+        let coalescer = SitemapCoalescer::new();
+        let started = AtomicUsize::new(0);
+
+        let first = coalescer.fetch("https://cdn.example.com/sitemap.xml", || {
+            started.fetch_add(1, Ordering::SeqCst);
+            async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                "a"
+            }
+        });
+        let second = coalescer.fetch("https://cdn.example.com/sitemap.xml", || {
+            started.fetch_add(1, Ordering::SeqCst);
+            async { "b" }
+        });
+        let (first, second) = futures::join!(first, second);
+
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        assert_eq!(first, ("a", true));
+        assert_eq!(second, ("a", false));
+    }
+
+    #[tokio::test]
+    async fn test_waiting_caller_takes_over_a_dropped_fetch() {
+        let coalescer = SitemapCoalescer::new();
+
+        let abandoned = coalescer.fetch("https://example.com/sitemap.xml", std::future::pending::<&str>);
+        assert!(tokio::time::timeout(Duration::from_millis(20), abandoned).await.is_err());
+
+        let taken_over = coalescer.fetch("https://example.com/sitemap.xml", || async { "b" }).await;
+        assert_eq!(taken_over, ("b", true));
+    }
+}
@@ -0,0 +1,166 @@
+use log::debug;
+use reqwest::header::RANGE;
+use reqwest::Client;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use url::Url;
+
+use crate::parser::USER_AGENT;
+use crate::warc::{read_gzipped_response, ArchivedResponse};
+
+const INDEX_SERVER: &str = "https://index.commoncrawl.org";
+const DATA_SERVER: &str = "https://data.commoncrawl.org";
+
+/// Redirect captures followed before giving up, e.g. http:// to https:// to www.
+const MAX_REDIRECTS: usize = 5;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Looks up captures in the Common Crawl URL index and reads them out of the crawl's WARC files.
+///
+/// Nothing is requested from the sites themselves: the index says which WARC file
+/// and byte range holds a capture, and one ranged GET against the Common Crawl
+/// bucket returns that single gzipped record.
+pub struct CommonCrawlSource {
+    client: Client,
+    /// Crawl to query, e.g. `CC-MAIN-2024-33`; `None` means the newest one
+    requested: Option<String>,
+    crawl: OnceCell<String>,
+}
+
+/// One line of a CDX index answer: where a capture lives and what it returned
+#[derive(Debug, Clone, PartialEq)]
+struct Capture {
+    status: u16,
+    filename: String,
+    offset: u64,
+    length: u64,
+    redirect: Option<String>,
+}
+
+impl CommonCrawlSource {
+    /// `crawl` is a crawl ID like `CC-MAIN-2024-33`, or `None` / `"latest"` for the newest crawl
+    pub fn new(crawl: Option<String>, timeout: Duration) -> Result<Self, Error> {
+        let client = Client::builder().timeout(timeout).user_agent(USER_AGENT).build()?;
+        Ok(Self {
+            client,
+            requested: crawl.filter(|c| c != "latest"),
+            crawl: OnceCell::new(),
+        })
+    }
+
+    /// The crawl being queried, asking the index server for the newest one on first use
+    pub async fn crawl(&self) -> Result<&str, Error> {
+        let crawl = self
+            .crawl
+            .get_or_try_init(|| async {
+                if let Some(crawl) = &self.requested {
+                    return Ok::<_, Error>(crawl.clone());
+                }
+                let collections: Value = self
+                    .client
+                    .get(format!("{}/collinfo.json", INDEX_SERVER))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                let latest = collections[0]["id"].as_str().ok_or("Common Crawl returned no crawls")?;
+                debug!("🦀 Using latest Common Crawl crawl {}", latest);
+                Ok(latest.to_string())
+            })
+            .await?;
+        Ok(crawl)
+    }
+
+    /// The newest successful capture of `url`, following archived redirects
+    pub async fn fetch(&self, url: &str) -> Result<ArchivedResponse, Error> {
+        let crawl = self.crawl().await?;
+        let mut target = url.to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            let capture = self.lookup(crawl, &target).await?;
+            match capture.redirect {
+                Some(location) if (300..400).contains(&capture.status) => {
+                    debug!("🦀 Common Crawl capture of {} redirects to {}", target, location);
+                    target = Url::parse(&target)?.join(&location)?.to_string();
+                }
+                _ => return self.read_capture(&capture).await,
+            }
+        }
+        Err(format!("Too many archived redirects for {} in {}", url, crawl).into())
+    }
+
+    async fn lookup(&self, crawl: &str, url: &str) -> Result<Capture, Error> {
+        let endpoint = format!("{}/{}-index", INDEX_SERVER, crawl);
+        let query = Url::parse_with_params(&endpoint, &[("url", url), ("output", "json")])?;
+        let resp = self.client.get(query).send().await?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(format!("No capture of {} in {}", url, crawl).into());
+        }
+        let body = resp.error_for_status()?.text().await?;
+        pick_capture(&body).ok_or_else(|| format!("No usable capture of {} in {}", url, crawl).into())
+    }
+
+    async fn read_capture(&self, capture: &Capture) -> Result<ArchivedResponse, Error> {
+        let range = format!("bytes={}-{}", capture.offset, capture.offset + capture.length - 1);
+        let bytes = self
+            .client
+            .get(format!("{}/{}", DATA_SERVER, capture.filename))
+            .header(RANGE, range)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        read_gzipped_response(&bytes)?.ok_or_else(|| format!("No response record at {}:{}", capture.filename, capture.offset).into())
+    }
+}
+
+/// Pick from a CDX JSON-lines answer (oldest first): the newest 2xx capture, else the newest of any status
+fn pick_capture(body: &str) -> Option<Capture> {
+    let captures: Vec<Capture> = body
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter_map(|entry| {
+            let field = |name: &str| entry[name].as_str().map(str::to_string);
+            Some(Capture {
+                status: field("status")?.parse().ok()?,
+                filename: field("filename")?,
+                offset: field("offset")?.parse().ok()?,
+                length: field("length")?.parse().ok().filter(|&l| l > 0)?,
+                redirect: field("redirect"),
+            })
+        })
+        .collect();
+    let newest_ok = captures.iter().rposition(|c| (200..300).contains(&c.status));
+    let chosen = newest_ok.or(captures.len().checked_sub(1))?;
+    captures.into_iter().nth(chosen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(status: &str, offset: u64) -> String {
+        format!(
+            r#"{{"urlkey": "com,example)/robots.txt", "timestamp": "2024010{}000000", "status": "{}", "filename": "crawl-data/x.warc.gz", "offset": "{}", "length": "512"}}"#,
+            offset, status, offset
+        )
+    }
+
+    #[test]
+    fn test_pick_capture_prefers_newest_success() {
+        let body = [line("200", 1), line("200", 2), line("503", 3)].join("\n");
+        let capture = pick_capture(&body).unwrap();
+        assert_eq!(capture.status, 200);
+        assert_eq!(capture.offset, 2);
+    }
+
+    #[test]
+    fn test_pick_capture_falls_back_to_newest() {
+        let body = [line("404", 1), "not json".to_string(), line("301", 2)].join("\n");
+        assert_eq!(pick_capture(&body).unwrap().status, 301);
+        assert!(pick_capture("").is_none());
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Count, total, and longest of a set of durations
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DurationStats {
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl DurationStats {
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64)
+        }
+    }
+}
+
+#[derive(Default)]
+struct DurationCounter {
+    count: AtomicU64,
+    total_micros: AtomicU64,
+    max_micros: AtomicU64,
+}
+
+impl DurationCounter {
+    fn record(&self, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> DurationStats {
+        DurationStats {
+            count: self.count.load(Ordering::Relaxed),
+            total: Duration::from_micros(self.total_micros.load(Ordering::Relaxed)),
+            max: Duration::from_micros(self.max_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// A current count that also remembers its high-water mark
+#[derive(Default)]
+struct Gauge {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl Gauge {
+    fn enter(&self) -> GaugeGuard<'_> {
+        let now = self.current.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak.fetch_max(now, Ordering::Relaxed);
+        GaugeGuard(self)
+    }
+}
+
+struct GaugeGuard<'a>(&'a Gauge);
+
+impl Drop for GaugeGuard<'_> {
+    fn drop(&mut self) {
+        self.0.current.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Point-in-time view of where a parser's time goes: waiting on its own limits, or on the network
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ContentionSnapshot {
+    /// Sites waiting for one of the `max_concurrent` slots, now and at most
+    pub sites_waiting: usize,
+    pub peak_sites_waiting: usize,
+    pub sites_in_flight: usize,
+    /// How long sites waited for a `max_concurrent` slot
+    pub site_waits: DurationStats,
+    /// Requests waiting on their site's in-flight limit (only degraded or capped sites
+    /// have one) or on the parser's request limits
+    pub requests_waiting: usize,
+    pub peak_requests_waiting: usize,
+    /// How long requests waited on those limits
+    pub request_waits: DurationStats,
+    pub requests_in_flight: usize,
+    /// Time from sending a request to having its whole body
+    pub request_times: DurationStats,
+    /// Requests in flight per host right now; hosts with none are left out
+    pub hosts_in_flight: HashMap<String, usize>,
+    /// The most requests ever in flight to a single host, and that host
+    pub peak_host_in_flight: Option<(String, usize)>,
+}
+
+/// Queue depths, wait times, and in-flight counts for one parser, across all its runs.
+///
+/// Everything is a relaxed atomic except the per-host table, which only holds hosts
+/// with requests in flight so it stays small however many sites a run covers.
+#[derive(Default)]
+pub struct ContentionTracker {
+    sites_waiting: Gauge,
+    sites_in_flight: Gauge,
+    site_waits: DurationCounter,
+    requests_waiting: Gauge,
+    request_waits: DurationCounter,
+    requests_in_flight: Gauge,
+    request_times: DurationCounter,
+    hosts: Mutex<HashMap<String, usize>>,
+    peak_host: Mutex<Option<(String, usize)>>,
+}
+
+/// Held while a site is being parsed
+pub struct SiteGuard<'a> {
+    _in_flight: GaugeGuard<'a>,
+}
+
+/// Held while a request is in flight; records its duration when dropped
+pub struct RequestGuard<'a> {
+    tracker: &'a ContentionTracker,
+    host: String,
+    started: Instant,
+    _in_flight: GaugeGuard<'a>,
+}
+
+impl Drop for RequestGuard<'_> {
+    fn drop(&mut self) {
+        self.tracker.request_times.record(self.started.elapsed());
+        let mut hosts = self.tracker.hosts.lock().unwrap();
+        if let Some(count) = hosts.get_mut(&self.host) {
+            *count -= 1;
+            if *count == 0 {
+                hosts.remove(&self.host);
+            }
+        }
+    }
+}
+
+impl ContentionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Await `slot` (a `max_concurrent` permit), counting the site as queued meanwhile
+    pub async fn wait_for_site<F: Future>(&self, slot: F) -> F::Output {
+        Self::timed(&self.sites_waiting, &self.site_waits, slot).await
+    }
+
+    /// Await `slot` (a site's in-flight limit or a request limit), counting the request as queued meanwhile
+    pub async fn wait_for_request<F: Future>(&self, slot: F) -> F::Output {
+        Self::timed(&self.requests_waiting, &self.request_waits, slot).await
+    }
+
+    pub fn site(&self) -> SiteGuard<'_> {
+        SiteGuard { _in_flight: self.sites_in_flight.enter() }
+    }
+
+    pub fn request(&self, host: &str) -> RequestGuard<'_> {
+        let mut hosts = self.hosts.lock().unwrap();
+        let count = hosts.entry(host.to_string()).or_insert(0);
+        *count += 1;
+        let mut peak = self.peak_host.lock().unwrap();
+        if peak.as_ref().is_none_or(|(_, most)| *count > *most) {
+            *peak = Some((host.to_string(), *count));
+        }
+        RequestGuard { tracker: self, host: host.to_string(), started: Instant::now(), _in_flight: self.requests_in_flight.enter() }
+    }
+
+    pub fn snapshot(&self) -> ContentionSnapshot {
+        ContentionSnapshot {
+            sites_waiting: self.sites_waiting.current.load(Ordering::Relaxed),
+            peak_sites_waiting: self.sites_waiting.peak.load(Ordering::Relaxed),
+            sites_in_flight: self.sites_in_flight.current.load(Ordering::Relaxed),
+            site_waits: self.site_waits.snapshot(),
+            requests_waiting: self.requests_waiting.current.load(Ordering::Relaxed),
+            peak_requests_waiting: self.requests_waiting.peak.load(Ordering::Relaxed),
+            request_waits: self.request_waits.snapshot(),
+            requests_in_flight: self.requests_in_flight.current.load(Ordering::Relaxed),
+            request_times: self.request_times.snapshot(),
+            hosts_in_flight: self.hosts.lock().unwrap().clone(),
+            peak_host_in_flight: self.peak_host.lock().unwrap().clone(),
+        }
+    }
+
+    async fn timed<F: Future>(queue: &Gauge, waits: &DurationCounter, slot: F) -> F::Output {
+        // Dropped with the future too, so a cancelled wait leaves the queue
+        let _queued = queue.enter();
+        let started = Instant::now();
+        let output = slot.await;
+        waits.record(started.elapsed());
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Semaphore;
+
+    #[tokio::test]
+    async fn test_counts_waits_and_in_flight() {
+        let tracker = ContentionTracker::new();
+        let semaphore = Semaphore::new(1);
+        let held = tracker.wait_for_site(semaphore.acquire()).await.unwrap();
+        let site = tracker.site();
+
+        let waiting = tracker.wait_for_site(semaphore.acquire());
+        tokio::pin!(waiting);
+        assert!(futures::poll!(waiting.as_mut()).is_pending());
+        assert_eq!(tracker.snapshot().sites_waiting, 1);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(held);
+        drop(waiting.await.unwrap());
+
+        let first = tracker.request("a.example");
+        let second = tracker.request("a.example");
+        let _other = tracker.request("b.example");
+        let snapshot = tracker.snapshot();
+        assert_eq!((snapshot.sites_waiting, snapshot.peak_sites_waiting, snapshot.sites_in_flight), (0, 1, 1));
+        assert_eq!(snapshot.site_waits.count, 2);
+        assert!(snapshot.site_waits.max >= Duration::from_millis(20));
+        assert_eq!(snapshot.requests_in_flight, 3);
+        assert_eq!(snapshot.hosts_in_flight["a.example"], 2);
+        assert_eq!(snapshot.peak_host_in_flight, Some(("a.example".to_string(), 2)));
+
+        drop((first, second, site));
+        let snapshot = tracker.snapshot();
+        assert_eq!(snapshot.request_times.count, 2);
+        assert!(!snapshot.hosts_in_flight.contains_key("a.example"));
+        assert_eq!((snapshot.sites_in_flight, snapshot.requests_in_flight), (0, 1));
+    }
+}
@@ -0,0 +1,373 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use flate2::write::GzDecoder;
+use std::io::{self, Write};
+
+/// Every gzip stream starts with these two bytes (RFC 1952)
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// How far into a body to look for the XML declaration
+const DECLARATION_SCAN: usize = 1024;
+
+/// A response body decoded to text
+#[derive(Debug, Clone)]
+pub struct DecodedBody {
+    pub text: String,
+    /// The body was gzip data that we decompressed ourselves
+    pub decompressed: bool,
+    /// Decompression stopped early, either at `max_bytes` or because the gzip stream was cut off
+    pub truncated: bool,
+    /// Name of the encoding the body was transcoded from, when it wasn't UTF-8
+    pub encoding: Option<&'static str>,
+}
+
+/// Decode a raw response body, gunzipping it first if it starts with the gzip magic bytes.
+///
+/// Plenty of servers hand out gzipped sitemaps without a `.gz` suffix or a
+/// `Content-Encoding` header, so we sniff the body instead of trusting either.
+/// Decompressed output is capped at `max_bytes`. When `partial` is set the input
+/// is known to be cut off, so a gzip stream ending early keeps whatever it had
+/// decompressed instead of failing. Text in another encoding than UTF-8 is
+/// transcoded; see [`detect_encoding`].
+pub fn decode_body(bytes: &[u8], max_bytes: usize, partial: bool, content_type: Option<&str>) -> Result<DecodedBody, Box<dyn std::error::Error + Send + Sync>> {
+    let mut decoder = BodyDecoder::new(max_bytes).with_content_type(content_type);
+    decoder.push(bytes);
+    decoder.finish(partial)
+}
+
+/// The encoding a decoded body is in: a byte order mark wins, then the
+/// `Content-Type` charset, then the `<?xml ... encoding="..."?>` declaration, and
+/// UTF-8 if none of them says.
+///
+/// A header claiming UTF-8 for a body that isn't valid UTF-8 gives way to the
+/// declaration, since servers often label everything UTF-8 whatever the file is in.
+/// Labels are read as browsers read them, so `ISO-8859-1` decodes as windows-1252.
+pub fn detect_encoding(bytes: &[u8], content_type: Option<&str>) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    let declared = declared_encoding(bytes);
+    match content_type.and_then(charset).and_then(|label| Encoding::for_label(label.as_bytes())) {
+        Some(encoding) if encoding != UTF_8 || is_utf8(bytes) => encoding,
+        _ => declared.unwrap_or(UTF_8),
+    }
+}
+
+/// The `charset` parameter of a `Content-Type` header value
+fn charset(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim().eq_ignore_ascii_case("charset").then(|| value.trim().trim_matches(|c| c == '"' || c == '\''))
+    })
+}
+
+/// The encoding an XML declaration at the start of `bytes` names
+fn declared_encoding(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = bytes[..bytes.len().min(DECLARATION_SCAN)].trim_ascii_start();
+    let declaration = head.strip_prefix(b"<?xml")?;
+    let declaration = &declaration[..declaration.windows(2).position(|w| w == b"?>")?];
+    let at = declaration.windows(8).position(|w| w == b"encoding")?;
+    let value = declaration[at + 8..].trim_ascii_start().strip_prefix(b"=")?.trim_ascii_start();
+    let quote = *value.first().filter(|&&q| q == b'"' || q == b'\'')?;
+    let value = &value[1..];
+    let encoding = Encoding::for_label(&value[..value.iter().position(|&b| b == quote)?])?;
+    // A declaration we could read as ASCII isn't really in UTF-16
+    Some(if encoding == UTF_16LE || encoding == UTF_16BE { UTF_8 } else { encoding })
+}
+
+/// Whether `bytes` are UTF-8, allowing a character cut off at the end of a partial body
+fn is_utf8(bytes: &[u8]) -> bool {
+    std::str::from_utf8(bytes).map_or_else(|e| e.error_len().is_none(), |_| true)
+}
+
+/// Decodes a response body chunk by chunk as it arrives, the way [`decode_body`] does
+/// a whole one.
+///
+/// Gzip is inflated as each chunk comes in and output past `max_bytes` is dropped
+/// straight away, so neither the compressed body nor a gzip bomb's expansion is
+/// ever held whole; [`is_full`](Self::is_full) tells the reader to stop downloading.
+/// The decoded bytes become the body's `String` without another copy when they're
+/// valid UTF-8.
+pub struct BodyDecoder {
+    state: DecoderState,
+    max_bytes: usize,
+    content_type: Option<String>,
+    /// Inflating failed; later chunks are ignored and `finish` reports it
+    failed: Option<io::Error>,
+}
+
+enum DecoderState {
+    /// Fewer bytes than the gzip magic have arrived, so the format isn't known yet
+    Sniffing(Vec<u8>),
+    Plain(CappedBuffer),
+    Gzip(Box<GzDecoder<CappedBuffer>>),
+}
+
+/// Keeps the first `max_bytes` written to it and counts the rest as overflow
+struct CappedBuffer {
+    data: Vec<u8>,
+    max_bytes: usize,
+    overflowed: bool,
+}
+
+impl CappedBuffer {
+    fn extend(&mut self, buf: &[u8]) {
+        let room = self.max_bytes - self.data.len();
+        if buf.len() > room {
+            self.overflowed = true;
+        }
+        self.data.extend_from_slice(&buf[..buf.len().min(room)]);
+    }
+}
+
+impl Write for CappedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.extend(buf);
+        // Claim all of it: the decoder treats a short write as an error
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl BodyDecoder {
+    pub fn new(max_bytes: usize) -> Self {
+        Self { state: DecoderState::Sniffing(Vec::with_capacity(GZIP_MAGIC.len())), max_bytes, content_type: None, failed: None }
+    }
+
+    /// The response's `Content-Type`, whose charset the body is decoded with
+    pub fn with_content_type(mut self, content_type: Option<&str>) -> Self {
+        self.content_type = content_type.map(str::to_string);
+        self
+    }
+
+    /// Decode the next chunk of the body
+    pub fn push(&mut self, mut chunk: &[u8]) {
+        if let DecoderState::Sniffing(head) = &mut self.state {
+            let take = chunk.len().min(GZIP_MAGIC.len() - head.len());
+            head.extend_from_slice(&chunk[..take]);
+            chunk = &chunk[take..];
+            if head.len() < GZIP_MAGIC.len() {
+                return;
+            }
+            let head = std::mem::take(head);
+            self.start(&head);
+        }
+        if self.failed.is_some() || self.is_full() {
+            return;
+        }
+        match &mut self.state {
+            DecoderState::Sniffing(_) => unreachable!("sniffing ends once the gzip magic's length has arrived"),
+            DecoderState::Plain(buffer) => buffer.extend(chunk),
+            DecoderState::Gzip(decoder) => {
+                while !chunk.is_empty() && !decoder.get_ref().overflowed {
+                    match decoder.write(chunk) {
+                        // The gzip member has ended; anything after it is ignored
+                        Ok(0) => break,
+                        Ok(n) => chunk = &chunk[n..],
+                        Err(e) => {
+                            self.failed = Some(e);
+                            return;
+                        }
+                    }
+                }
+                // Hand over what the decoder buffered, so `decoded` and `is_full` are current
+                if let Err(e) = decoder.flush() {
+                    self.failed = Some(e);
+                }
+            }
+        }
+    }
+
+    /// Pick plain or gzip decoding from the body's first bytes and decode them
+    fn start(&mut self, head: &[u8]) {
+        let buffer = CappedBuffer { data: Vec::new(), max_bytes: self.max_bytes, overflowed: false };
+        self.state = if head.starts_with(&GZIP_MAGIC) {
+            DecoderState::Gzip(Box::new(GzDecoder::new(buffer)))
+        } else {
+            DecoderState::Plain(buffer)
+        };
+        self.push(head);
+    }
+
+    /// Whether the decoded body has reached `max_bytes`, so reading more is pointless
+    pub fn is_full(&self) -> bool {
+        match &self.state {
+            DecoderState::Sniffing(_) => false,
+            DecoderState::Plain(buffer) => buffer.overflowed,
+            DecoderState::Gzip(decoder) => decoder.get_ref().overflowed,
+        }
+    }
+
+    /// The body decoded so far
+    pub fn decoded(&self) -> &[u8] {
+        match &self.state {
+            DecoderState::Sniffing(head) => head,
+            DecoderState::Plain(buffer) => &buffer.data,
+            DecoderState::Gzip(decoder) => &decoder.get_ref().data,
+        }
+    }
+
+    /// The decoded body. `partial` means the input was cut off, so a gzip stream
+    /// ending early keeps what it had instead of failing.
+    pub fn finish(mut self, partial: bool) -> Result<DecodedBody, Box<dyn std::error::Error + Send + Sync>> {
+        if let DecoderState::Sniffing(head) = &mut self.state {
+            let head = std::mem::take(head);
+            self.start(&head);
+        }
+        let content_type = self.content_type.as_deref();
+        match self.state {
+            DecoderState::Sniffing(_) => unreachable!("start always picks a format"),
+            DecoderState::Plain(buffer) => {
+                let (text, encoding) = into_text(buffer.data, content_type);
+                Ok(DecodedBody { text, decompressed: false, truncated: buffer.overflowed, encoding })
+            }
+            DecoderState::Gzip(mut decoder) => {
+                let mut truncated = decoder.get_ref().overflowed;
+                if !truncated {
+                    let finished = match self.failed {
+                        Some(e) => Err(e),
+                        None => decoder.try_finish(),
+                    };
+                    match finished {
+                        Ok(()) => {}
+                        Err(_) if partial => truncated = true,
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                // Output the decoder still buffered may have gone past the cap
+                let buffer = decoder.get_mut();
+                truncated |= buffer.overflowed;
+                let (text, encoding) = into_text(std::mem::take(&mut buffer.data), content_type);
+                Ok(DecodedBody { text, decompressed: true, truncated, encoding })
+            }
+        }
+    }
+}
+
+/// `bytes` as text, transcoded if [`detect_encoding`] finds them in another encoding
+/// than UTF-8, whose name comes back too. UTF-8 reuses their allocation unless invalid
+/// sequences have to be replaced.
+fn into_text(bytes: Vec<u8>, content_type: Option<&str>) -> (String, Option<&'static str>) {
+    let encoding = detect_encoding(&bytes, content_type);
+    if encoding == UTF_8 {
+        return (String::from_utf8(bytes).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned()), None);
+    }
+    let (text, encoding, _) = encoding.decode(&bytes);
+    (text.into_owned(), Some(encoding.name()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_decode_gzip_body() {
+        let xml = "<urlset><url><loc>https://example.com/</loc></url></urlset>";
+
+        let body = decode_body(&gzip(xml.as_bytes()), 1024, false, None).unwrap();
+        assert!(body.decompressed);
+        assert!(!body.truncated);
+        assert_eq!(body.text, xml);
+    }
+
+    #[test]
+    fn test_decode_plain_body() {
+        let body = decode_body(b"User-agent: *", 1024, false, None).unwrap();
+        assert!(!body.decompressed);
+        assert_eq!(body.text, "User-agent: *");
+    }
+
+    #[test]
+    fn test_decode_corrupt_gzip() {
+        assert!(decode_body(&[0x1f, 0x8b, 0x00, 0x01], 1024, false, None).is_err());
+    }
+
+    #[test]
+    fn test_decode_gzip_over_limit_is_truncated() {
+        let body = decode_body(&gzip(&[b'a'; 100]), 10, false, None).unwrap();
+        assert!(body.truncated);
+        assert_eq!(body.text.len(), 10);
+    }
+
+    #[test]
+    fn test_body_decoder_inflates_chunk_by_chunk() {
+        let xml: String = (0..2_000).map(|i| format!("<url><loc>https://example.com/{}</loc></url>", i)).collect();
+        let gzipped = gzip(xml.as_bytes());
+
+        // A one-byte first chunk leaves the format undecided until the next
+        let mut decoder = BodyDecoder::new(1 << 20);
+        decoder.push(&gzipped[..1]);
+        assert!(decoder.decoded().len() <= 1);
+        for chunk in gzipped[1..].chunks(100) {
+            decoder.push(chunk);
+        }
+        assert!(decoder.decoded().starts_with(b"<url><loc>https://example.com/0<"));
+        let body = decoder.finish(false).unwrap();
+        assert!(body.decompressed && !body.truncated);
+        assert_eq!(body.text, xml);
+
+        // Output past the cap is dropped as it's inflated, and the reader is told to stop
+        let mut decoder = BodyDecoder::new(1000);
+        decoder.push(&gzip(&[b'a'; 1 << 20])[..]);
+        assert!(decoder.is_full());
+        assert_eq!(decoder.decoded().len(), 1000);
+        assert!(decoder.finish(false).unwrap().truncated);
+
+        let mut decoder = BodyDecoder::new(4);
+        decoder.push(b"U");
+        decoder.push(b"ser-agent");
+        assert!(decoder.is_full());
+        let body = decoder.finish(false).unwrap();
+        assert!(!body.decompressed && body.truncated);
+        assert_eq!(body.text, "User");
+    }
+
+    #[test]
+    fn test_detect_encoding_from_header_and_declaration() {
+        let latin1 = b"<?xml version='1.0' encoding=\"ISO-8859-1\"?><urlset><url><loc>https://example.com/caf\xe9</loc></url></urlset>";
+
+        let body = decode_body(latin1, 1024, false, None).unwrap();
+        assert_eq!(body.encoding, Some("windows-1252"));
+        assert!(body.text.contains("https://example.com/café"));
+        // The header's charset wins over the declaration, unless it claims UTF-8 for bytes that aren't
+        assert_eq!(detect_encoding(latin1, Some("text/xml; charset=\"utf-8\"")), encoding_rs::WINDOWS_1252);
+        assert_eq!(detect_encoding(b"<?xml version=\"1.0\" encoding=\"latin1\"?><a/>", Some("text/xml; charset=UTF-8")), UTF_8);
+        assert_eq!(detect_encoding(b"<urlset/>", Some("application/xml;charset=gb2312")), encoding_rs::GBK);
+
+        let gbk = b"<urlset><url><loc>https://example.cn/\xd6\xd0\xce\xc4</loc></url></urlset>";
+        let body = decode_body(&gzip(gbk), 1024, false, Some("text/xml; charset=GB2312")).unwrap();
+        assert!(body.decompressed);
+        assert_eq!(body.encoding, Some("GBK"));
+        assert!(body.text.contains("https://example.cn/中文"));
+
+        let utf16: Vec<u8> = "\u{feff}<urlset/>".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(decode_body(&utf16, 1024, false, None).unwrap().text, "<urlset/>");
+        // UTF-8 is left alone and not reported
+        let body = decode_body("<loc>https://example.com/café</loc>".as_bytes(), 1024, false, Some("text/xml")).unwrap();
+        assert_eq!(body.encoding, None);
+        assert!(body.text.contains("café"));
+    }
+
+    #[test]
+    fn test_decode_cut_off_gzip_keeps_prefix() {
+        let data: Vec<u8> = (0..20_000u32).flat_map(|i| i.to_le_bytes()).collect();
+        let gzipped = gzip(&data);
+        let cut = &gzipped[..gzipped.len() / 2];
+
+        assert!(decode_body(cut, 1 << 20, false, None).is_err());
+        let body = decode_body(cut, 1 << 20, true, None).unwrap();
+        assert!(body.truncated);
+        assert!(!body.text.is_empty());
+    }
+}
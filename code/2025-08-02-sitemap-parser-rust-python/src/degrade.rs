@@ -0,0 +1,174 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// How many timeouts a site may rack up before each step down
+pub const TIMEOUTS_PER_STEP: usize = 2;
+
+/// Deepest degradation level; past this a site keeps its reduced limits
+pub const MAX_DEGRADATION_LEVEL: usize = 3;
+
+/// Tracks timeouts for a single site and steps its limits down when the host is struggling.
+///
+/// Every `TIMEOUTS_PER_STEP` timeouts raise the degradation level by one, which
+/// reduces the effective depth by one and halves the nested-sitemap limit and the
+/// number of in-flight requests for the rest of that site's parse. Healthy sites
+/// (level 0) are never throttled by this unless they start capped.
+pub struct SiteDegradation {
+    timeouts: AtomicUsize,
+    level: AtomicUsize,
+    base_slots: usize,
+    /// In-flight requests are limited even at level 0
+    capped: bool,
+    slots: Semaphore,
+    slots_to_shed: AtomicUsize,
+}
+
+impl SiteDegradation {
+    pub fn new(base_slots: usize) -> Self {
+        let base_slots = base_slots.max(1);
+        Self {
+            timeouts: AtomicUsize::new(0),
+            level: AtomicUsize::new(0),
+            base_slots,
+            capped: false,
+            slots: Semaphore::new(base_slots),
+            slots_to_shed: AtomicUsize::new(0),
+        }
+    }
+
+    /// Start with at most `cap` requests in flight, for hosts known to throttle
+    pub fn capped(base_slots: usize, cap: usize) -> Self {
+        Self { capped: true, ..Self::new(base_slots.min(cap)) }
+    }
+
+    pub fn level(&self) -> usize {
+        self.level.load(Ordering::SeqCst)
+    }
+
+    /// Record a timeout, returning the new level if it triggered a step down
+    pub fn record_timeout(&self) -> Option<usize> {
+        let timeouts = self.timeouts.fetch_add(1, Ordering::SeqCst) + 1;
+        if !timeouts.is_multiple_of(TIMEOUTS_PER_STEP) {
+            return None;
+        }
+
+        let previous = self
+            .level
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |level| {
+                (level < MAX_DEGRADATION_LEVEL).then_some(level + 1)
+            })
+            .ok()?;
+        let level = previous + 1;
+
+        // Shed the permits between the old and new in-flight limits
+        let shed = self.slot_limit(previous) - self.slot_limit(level);
+        let forgotten = self.slots.forget_permits(shed);
+        self.slots_to_shed.fetch_add(shed - forgotten, Ordering::SeqCst);
+
+        Some(level)
+    }
+
+    /// Depth limit for the site at the current degradation level
+    pub fn depth(&self, max_depth: usize) -> usize {
+        max_depth.saturating_sub(self.level()).max(1)
+    }
+
+    /// Nested sitemaps to follow per level at the current degradation level
+    pub fn nested_limit(&self, max_nested_per_level: usize) -> usize {
+        (max_nested_per_level >> self.level()).max(1)
+    }
+
+    /// In-flight request limit at the current degradation level
+    pub fn slots(&self) -> usize {
+        self.slot_limit(self.level())
+    }
+
+    /// Whether requests have to take a slot: the site is degraded or capped
+    pub fn limits_requests(&self) -> bool {
+        self.level() > 0 || self.capped
+    }
+
+    /// Wait for a request slot; only degraded or capped sites actually have to wait
+    pub async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        if !self.limits_requests() {
+            return None;
+        }
+        self.slots.acquire().await.ok()
+    }
+
+    /// Return a request slot, dropping it instead if the limit was lowered while it was out
+    pub fn release(&self, permit: Option<SemaphorePermit<'_>>) {
+        let Some(permit) = permit else { return };
+        let shed = self
+            .slots_to_shed
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if shed {
+            permit.forget();
+        }
+    }
+
+    fn slot_limit(&self, level: usize) -> usize {
+        if level == 0 {
+            self.base_slots
+        } else {
+            (self.base_slots >> level).max(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_down_every_few_timeouts() {
+        let degradation = SiteDegradation::new(8);
+
+        assert_eq!(degradation.record_timeout(), None);
+        assert_eq!(degradation.record_timeout(), Some(1));
+        assert_eq!(degradation.depth(3), 2);
+        assert_eq!(degradation.nested_limit(5), 2);
+
+        for _ in 0..10 {
+            degradation.record_timeout();
+        }
+        assert_eq!(degradation.level(), MAX_DEGRADATION_LEVEL);
+        assert_eq!(degradation.depth(3), 1);
+        assert_eq!(degradation.nested_limit(5), 1);
+    }
+
+    #[tokio::test]
+    async fn test_capped_site_waits_at_level_zero() {
+        let degradation = SiteDegradation::capped(8, 2);
+        let first = degradation.acquire().await;
+        let _second = degradation.acquire().await;
+        assert!(first.is_some());
+        assert_eq!(degradation.slots(), 2);
+        assert_eq!(degradation.slots.available_permits(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_limit_shrinks() {
+        let degradation = SiteDegradation::new(8);
+        assert!(degradation.acquire().await.is_none());
+
+        degradation.record_timeout();
+        degradation.record_timeout();
+        assert_eq!(degradation.slots.available_permits(), 4);
+
+        let mut permits = Vec::new();
+        for _ in 0..4 {
+            permits.push(degradation.acquire().await);
+        }
+        degradation.record_timeout();
+        degradation.record_timeout();
+        assert_eq!(degradation.slots.available_permits(), 0);
+
+        // Permits out while the limit dropped are shed as they come back
+        for permit in permits {
+            degradation.release(permit);
+        }
+        assert_eq!(degradation.slots.available_permits(), 2);
+    }
+}
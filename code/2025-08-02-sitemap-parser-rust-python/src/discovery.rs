@@ -0,0 +1,120 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use reqwest::StatusCode;
+use url::Url;
+
+/// Where sitemaps usually live when robots.txt doesn't say: the protocol's default,
+/// the index names Yoast and other SEO plugins use, and WordPress core's
+pub const DEFAULT_CANDIDATE_PATHS: &[&str] = &["/sitemap.xml", "/sitemap_index.xml", "/sitemaps.xml", "/wp-sitemap.xml", "/sitemap.xml.gz"];
+
+/// How the parser looks for a site's sitemaps when robots.txt lists none, or couldn't
+/// be fetched
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveryPolicy {
+    /// Paths tried on the site, in order
+    pub candidate_paths: Vec<String>,
+    /// Send a HEAD for each candidate first and only fetch those that weren't a 404 or
+    /// 410. Probes are skipped when responses come from archives or a custom fetcher.
+    pub probe: bool,
+    /// When no candidate exists, fetch the homepage and follow its
+    /// `<link rel="sitemap">` elements
+    pub homepage_links: bool,
+}
+
+impl Default for DiscoveryPolicy {
+    fn default() -> Self {
+        Self {
+            candidate_paths: DEFAULT_CANDIDATE_PATHS.iter().map(|path| path.to_string()).collect(),
+            probe: true,
+            homepage_links: true,
+        }
+    }
+}
+
+impl DiscoveryPolicy {
+    /// The candidate sitemap URLs for the site at `base_url`
+    pub fn candidates(&self, base_url: &str) -> Vec<String> {
+        let base_url = base_url.trim_end_matches('/');
+        self.candidate_paths.iter()
+            .map(|path| format!("{}/{}", base_url, path.trim_start_matches('/')))
+            .collect()
+    }
+}
+
+/// What a HEAD probe says about a candidate sitemap
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Probe {
+    Found,
+    Missing,
+    /// The server doesn't answer HEAD usefully (405, 5xx, a failure), so fetch it to find out
+    Unknown,
+}
+
+impl Probe {
+    pub(crate) fn from_status(status: StatusCode) -> Self {
+        match status.as_u16() {
+            200..=299 => Probe::Found,
+            404 | 410 => Probe::Missing,
+            _ => Probe::Unknown,
+        }
+    }
+}
+
+static LINK_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<link\b([^>]*)>").unwrap());
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?s)([a-zA-Z-]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap());
+
+/// Absolute URLs of the sitemaps an HTML page at `page_url` links with
+/// `<link rel="sitemap" href="...">`, in document order and without duplicates
+pub fn sitemap_links(html: &str, page_url: &str) -> Vec<String> {
+    let Ok(page) = Url::parse(page_url) else { return Vec::new() };
+    let mut links = Vec::new();
+    for tag in LINK_TAG.captures_iter(html) {
+        let (mut rel, mut href) = (None, None);
+        for attribute in ATTRIBUTE.captures_iter(&tag[1]) {
+            let value = attribute.get(2).or(attribute.get(3)).or(attribute.get(4)).map_or("", |m| m.as_str());
+            match attribute[1].to_ascii_lowercase().as_str() {
+                "rel" => rel = Some(value),
+                "href" => href = Some(value),
+                _ => {}
+            }
+        }
+        let is_sitemap = rel.is_some_and(|rel| rel.split_ascii_whitespace().any(|token| token.eq_ignore_ascii_case("sitemap")));
+        let Some(href) = href.filter(|_| is_sitemap) else { continue };
+        let Ok(url) = page.join(href.trim().replace("&amp;", "&").as_str()) else { continue };
+        let url = String::from(url);
+        if !links.contains(&url) {
+            links.push(url);
+        }
+    }
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sitemap_links_from_homepage() {
+        let html = r#"<!doctype html><html><head>
+            <link rel="stylesheet" href="/style.css">
+            <LINK REL="Sitemap" TYPE="application/xml" TITLE="Sitemap" HREF="/sitemap-main.xml">
+            <link href='https://cdn.example.com/sitemaps/news.xml?a=1&amp;b=2' rel='alternate sitemap'>
+            <link rel=sitemap href=sitemap-main.xml>
+        </head><body><a rel="sitemap" href="/not-a-link.xml">x</a></body></html>"#;
+
+        assert_eq!(sitemap_links(html, "https://example.com/"), [
+            "https://example.com/sitemap-main.xml",
+            "https://cdn.example.com/sitemaps/news.xml?a=1&b=2",
+        ]);
+        assert!(sitemap_links("<p>No links</p>", "https://example.com/").is_empty());
+    }
+
+    #[test]
+    fn test_candidates_join_paths() {
+        let policy = DiscoveryPolicy { candidate_paths: vec!["/sitemap.xml".into(), "feeds/sitemap.txt".into()], ..Default::default() };
+        assert_eq!(policy.candidates("https://example.com/"), ["https://example.com/sitemap.xml", "https://example.com/feeds/sitemap.txt"]);
+        assert_eq!(Probe::from_status(StatusCode::GONE), Probe::Missing);
+        assert_eq!(Probe::from_status(StatusCode::METHOD_NOT_ALLOWED), Probe::Unknown);
+    }
+}
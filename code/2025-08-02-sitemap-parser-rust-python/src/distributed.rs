@@ -0,0 +1,301 @@
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Notify;
+
+use crate::error::SitemapError;
+use crate::parser::{ParsedSiteResult, RustSitemapParser};
+
+/// Shards not yet handed out, shards a worker holds, and everything merged so far
+#[derive(Default)]
+struct Shards {
+    pending: VecDeque<(usize, Vec<String>)>,
+    outstanding: HashMap<usize, Vec<String>>,
+    results: Vec<ParsedSiteResult>,
+}
+
+#[derive(Default)]
+struct CoordinatorState {
+    shards: Mutex<Shards>,
+    changed: Notify,
+}
+
+impl CoordinatorState {
+    fn is_done(&self) -> bool {
+        let shards = self.shards.lock().unwrap();
+        shards.pending.is_empty() && shards.outstanding.is_empty()
+    }
+
+    /// The next shard to hand out, waiting while other workers still hold the last ones
+    /// in case they drop out; `None` once every shard is merged
+    async fn next_shard(&self) -> Option<(usize, Vec<String>)> {
+        loop {
+            let changed = self.changed.notified();
+            {
+                let mut shards = self.shards.lock().unwrap();
+                if let Some((id, base_urls)) = shards.pending.pop_front() {
+                    shards.outstanding.insert(id, base_urls.clone());
+                    return Some((id, base_urls));
+                }
+                if shards.outstanding.is_empty() {
+                    return None;
+                }
+            }
+            changed.await;
+        }
+    }
+
+    fn complete(&self, id: usize, results: Vec<ParsedSiteResult>) {
+        let mut shards = self.shards.lock().unwrap();
+        // A shard requeued after a slow worker was presumed gone may be reported twice; keep the first
+        if shards.outstanding.remove(&id).is_some() {
+            shards.pending.retain(|(pending, _)| *pending != id);
+            shards.results.extend(results);
+        }
+        drop(shards);
+        self.changed.notify_waiters();
+    }
+
+    /// Put shards a disconnected worker never reported back at the front of the queue
+    fn requeue(&self, ids: &[usize]) {
+        let mut shards = self.shards.lock().unwrap();
+        for id in ids {
+            if let Some(base_urls) = shards.outstanding.remove(id) {
+                warn!("🦀 Requeueing shard {} ({} sites) from a lost worker", id, base_urls.len());
+                shards.pending.push_front((*id, base_urls));
+            }
+        }
+        drop(shards);
+        self.changed.notify_waiters();
+    }
+}
+
+/// Hands shards of a domain list to workers over TCP and merges their results.
+///
+/// The protocol is one JSON object per line: workers send `{"type": "next"}` and get
+/// back a `shard` or `done`, then report each shard with `results`. Shards held by a
+/// worker that disconnects go back in the queue for another worker. There is no
+/// authentication, so only bind to networks the workers and coordinator trust.
+pub struct Coordinator {
+    listener: TcpListener,
+    state: Arc<CoordinatorState>,
+}
+
+impl Coordinator {
+    pub async fn bind(addr: &str, base_urls: Vec<String>, shard_size: usize) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let state = CoordinatorState::default();
+        {
+            let mut shards = state.shards.lock().unwrap();
+            shards.pending = base_urls
+                .chunks(shard_size.max(1))
+                .map(<[String]>::to_vec)
+                .enumerate()
+                .collect();
+            info!("🦀 Split {} sites into {} shards", base_urls.len(), shards.pending.len());
+        }
+        Ok(Self { listener, state: Arc::new(state) })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve workers until every shard has been parsed, returning all results
+    pub async fn run(self) -> io::Result<Vec<ParsedSiteResult>> {
+        info!("🦀 Coordinator waiting for workers on {}", self.local_addr()?);
+        loop {
+            let changed = self.state.changed.notified();
+            if self.state.is_done() {
+                break;
+            }
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (stream, peer) = accepted?;
+                    info!("🦀 Worker connected from {}", peer);
+                    let state = Arc::clone(&self.state);
+                    tokio::spawn(async move {
+                        if let Err(e) = serve_worker(stream, &state).await {
+                            warn!("🦀 Worker {} dropped: {}", peer, e);
+                        }
+                    });
+                }
+                _ = changed => {}
+            }
+        }
+        let results = std::mem::take(&mut self.state.shards.lock().unwrap().results);
+        info!("🦀 Coordinator merged results for {} sites", results.len());
+        Ok(results)
+    }
+}
+
+async fn serve_worker(stream: TcpStream, state: &CoordinatorState) -> io::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    let mut assigned = Vec::new();
+
+    let outcome = async {
+        while let Some(line) = lines.next_line().await? {
+            let message: Value = serde_json::from_str(&line).map_err(invalid_data)?;
+            match message["type"].as_str() {
+                Some("next") => match state.next_shard().await {
+                    Some((id, base_urls)) => {
+                        assigned.push(id);
+                        send(&mut write, json!({ "type": "shard", "id": id, "base_urls": base_urls })).await?;
+                    }
+                    None => {
+                        send(&mut write, json!({ "type": "done" })).await?;
+                        return Ok(());
+                    }
+                },
+                Some("results") => {
+                    let id = message["shard"].as_u64().ok_or_else(|| invalid_data("results without a shard id"))? as usize;
+                    let results = message["results"]
+                        .as_array()
+                        .ok_or_else(|| invalid_data("results without a results list"))?
+                        .iter()
+                        .map(result_from_json)
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| invalid_data("malformed site result"))?;
+                    state.complete(id, results);
+                    assigned.retain(|held| *held != id);
+                }
+                other => return Err(invalid_data(format!("unknown message type {:?}", other))),
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    // Whatever this worker didn't report goes to someone else
+    state.requeue(&assigned);
+    outcome
+}
+
+/// Pull shards from the coordinator at `coordinator` and parse them until it says
+/// there are none left, returning how many sites this worker parsed
+pub async fn run_worker(parser: &RustSitemapParser, coordinator: &str) -> io::Result<usize> {
+    let stream = TcpStream::connect(coordinator).await?;
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    let mut parsed = 0;
+
+    loop {
+        send(&mut write, json!({ "type": "next" })).await?;
+        let line = lines
+            .next_line()
+            .await?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "coordinator closed the connection"))?;
+        let message: Value = serde_json::from_str(&line).map_err(invalid_data)?;
+        match message["type"].as_str() {
+            Some("shard") => {
+                let id = message["id"].as_u64().ok_or_else(|| invalid_data("shard without an id"))?;
+                let base_urls: Vec<String> = message["base_urls"]
+                    .as_array()
+                    .map(|urls| urls.iter().filter_map(Value::as_str).map(str::to_string).collect())
+                    .unwrap_or_default();
+                info!("🦀 Worker parsing shard {} ({} sites)", id, base_urls.len());
+                let results = parser.parse_multiple_sites(base_urls).await.map_err(io::Error::other)?;
+                parsed += results.len();
+                let results: Vec<Value> = results.iter().map(result_to_json).collect();
+                send(&mut write, json!({ "type": "results", "shard": id, "results": results })).await?;
+            }
+            Some("done") => {
+                info!("🦀 Coordinator has no more shards; this worker parsed {} sites", parsed);
+                return Ok(parsed);
+            }
+            other => return Err(invalid_data(format!("unknown message type {:?}", other))),
+        }
+    }
+}
+
+async fn send(write: &mut OwnedWriteHalf, message: Value) -> io::Result<()> {
+    let mut line = serde_json::to_vec(&message).map_err(io::Error::other)?;
+    line.push(b'\n');
+    write.write_all(&line).await
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// The parts of a site result that travel back to the coordinator
+fn result_to_json(result: &ParsedSiteResult) -> Value {
+    json!({
+        "base_url": result.base_url,
+        "urls": result.urls,
+        "sitemaps_found": result.sitemaps_found,
+        "errors": result.errors,
+        "total_requests": result.total_requests,
+        "parse_time": result.parse_time,
+    })
+}
+
+fn result_from_json(value: &Value) -> Option<ParsedSiteResult> {
+    let strings = |key: &str| -> Option<Vec<String>> {
+        value[key].as_array()?.iter().map(|s| s.as_str().map(str::to_string)).collect()
+    };
+    let mut result = ParsedSiteResult::new(value["base_url"].as_str()?.to_string());
+    result.urls = strings("urls")?.into_iter().collect();
+    result.sitemaps_found = strings("sitemaps_found")?;
+    result.errors = strings("errors")?;
+    // Only messages travel, so their types are lost on the way
+    result.error_details = result.errors.iter()
+        .map(|message| SitemapError::Other { url: result.base_url.clone(), message: message.clone() })
+        .collect();
+    result.total_requests = value["total_requests"].as_u64()? as usize;
+    result.parse_time = value["parse_time"].as_f64()?;
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticConfig, SyntheticServer};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_workers_share_and_merge_shards() {
+        let config = SyntheticConfig { urls_per_sitemap: 4, depth: 1, ..Default::default() };
+        let server = SyntheticServer::start(&config).await.unwrap();
+        let coordinator = Coordinator::bind("127.0.0.1:0", vec![server.base_url(); 7], 2).await.unwrap();
+        let addr = coordinator.local_addr().unwrap().to_string();
+        let parser = RustSitemapParser::new(2, 10, 1, 5, Duration::from_secs(5));
+
+        let (merged, first, second) = tokio::join!(coordinator.run(), run_worker(&parser, &addr), run_worker(&parser, &addr));
+        let merged = merged.unwrap();
+        assert_eq!(merged.len(), 7);
+        assert!(merged.iter().all(|r| r.urls.len() == 4 && r.errors.is_empty()));
+        assert_eq!(first.unwrap() + second.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_lost_worker_shard_is_requeued() {
+        let config = SyntheticConfig { urls_per_sitemap: 4, depth: 1, ..Default::default() };
+        let server = SyntheticServer::start(&config).await.unwrap();
+        let coordinator = Coordinator::bind("127.0.0.1:0", vec![server.base_url(); 3], 3).await.unwrap();
+        let addr = coordinator.local_addr().unwrap().to_string();
+        let parser = RustSitemapParser::new(2, 10, 1, 5, Duration::from_secs(5));
+
+        let flaky_then_healthy = async {
+            // Take the only shard and vanish without reporting it
+            let mut stream = TcpStream::connect(&addr).await.unwrap();
+            stream.write_all(b"{\"type\":\"next\"}\n").await.unwrap();
+            let mut line = String::new();
+            BufReader::new(&mut stream).read_line(&mut line).await.unwrap();
+            assert!(line.contains("\"shard\""));
+            drop(stream);
+
+            run_worker(&parser, &addr).await.unwrap()
+        };
+        let (merged, parsed) = tokio::join!(coordinator.run(), flaky_then_healthy);
+        assert_eq!(merged.unwrap().len(), 3);
+        assert_eq!(parsed, 3);
+    }
+}
@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ipnet::IpNet;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use url::{Host, Url};
+
+use crate::error::SitemapError;
+use crate::telemetry;
+
+/// How many hosts the cache holds before expired entries are swept out
+const MAX_CACHED_HOSTS: usize = 10_000;
+
+/// Each host's addresses and when they were looked up
+type LookupCache = Mutex<HashMap<String, (Instant, Vec<IpAddr>)>>;
+
+/// Which hosts and addresses the parser may fetch from, and how their names resolve.
+/// The default resolves every connection with the system resolver and allows anything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DnsPolicy {
+    /// Reuse a host's addresses for this long instead of resolving it for every new
+    /// connection; `None` doesn't cache
+    pub cache_ttl: Option<Duration>,
+    /// Refuse loopback, private, link-local, carrier-grade NAT, unique local, multicast,
+    /// and reserved addresses, so a sitemap can't point the parser at internal services
+    pub block_private: bool,
+    /// Further networks refused, like `203.0.113.0/24`
+    pub deny_networks: Vec<IpNet>,
+    /// Networks allowed even when `block_private` or `deny_networks` would refuse them,
+    /// like a staging server's `10.1.2.0/24`
+    pub allow_networks: Vec<IpNet>,
+    /// If not empty, only these hosts are fetched; `example.com` covers its subdomains too
+    pub allow_hosts: Vec<String>,
+    /// Hosts never fetched, subdomains included
+    pub deny_hosts: Vec<String>,
+    /// Addresses used for a host in place of a lookup, as in `/etc/hosts`. They are
+    /// still checked against the networks above.
+    pub overrides: HashMap<String, Vec<IpAddr>>,
+    /// Nameservers queried over UDP and TCP in place of the system resolver. Querying
+    /// them takes the `hickory` feature; without it they're ignored, and
+    /// [`parse_nameserver`] refuses them.
+    pub nameservers: Vec<SocketAddr>,
+}
+
+impl DnsPolicy {
+    /// Whether `host` passes the allow and deny lists
+    pub fn allows_host(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        if self.deny_hosts.iter().any(|pattern| covers(pattern, &host)) {
+            return false;
+        }
+        self.allow_hosts.is_empty() || self.allow_hosts.iter().any(|pattern| covers(pattern, &host))
+    }
+
+    /// Whether the parser may connect to `ip`
+    pub fn allows_ip(&self, ip: IpAddr) -> bool {
+        if self.allow_networks.iter().any(|network| network.contains(&ip)) {
+            return true;
+        }
+        if self.block_private && is_non_public(ip) {
+            return false;
+        }
+        !self.deny_networks.iter().any(|network| network.contains(&ip))
+    }
+}
+
+/// A network in CIDR notation, like `10.0.0.0/8`, or a single address
+pub fn parse_network(network: &str) -> Result<IpNet, String> {
+    let network = network.trim();
+    network.parse::<IpNet>()
+        .or_else(|_| network.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("not a network or IP address: {:?}", network))
+}
+
+/// A nameserver address, like `1.1.1.1`, `1.1.1.1:5353`, or `[2606:4700::1111]:53`;
+/// the port defaults to 53. Fails when the crate was built without the `hickory`
+/// feature, which is what queries them.
+pub fn parse_nameserver(server: &str) -> Result<SocketAddr, String> {
+    let server = server.trim();
+    if !cfg!(feature = "hickory") {
+        return Err(format!("can't query nameserver {:?}: built without the `hickory` feature", server));
+    }
+    server.parse::<SocketAddr>()
+        .or_else(|_| server.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| format!("not a nameserver address: {:?}", server))
+}
+
+/// Whether host list entry `pattern` covers `host`, itself or as a subdomain
+fn covers(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().trim_start_matches("*.").trim_matches('.').to_ascii_lowercase();
+    host == pattern || host.strip_suffix(pattern.as_str()).is_some_and(|sub| sub.ends_with('.'))
+}
+
+/// Whether `ip` is one of the addresses [`DnsPolicy::block_private`] refuses; an IPv6
+/// address carrying an IPv4 one is also refused when that IPv4 address would be
+pub fn is_non_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_non_public_v4(ip),
+        IpAddr::V6(ip) => is_non_public_v6(ip) || embedded_v4(ip).is_some_and(is_non_public_v4),
+    }
+}
+
+/// The IPv4 address inside an IPv4-mapped `::ffff:a.b.c.d`, deprecated IPv4-compatible
+/// `::a.b.c.d`, NAT64 `64:ff9b::/96`, 6to4 `2002::/16`, or Teredo `2001::/32` address,
+/// the last being the client's, which Teredo stores inverted
+fn embedded_v4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let v4 = |high: u16, low: u16| Ipv4Addr::from((u32::from(high) << 16) | u32::from(low));
+    match segments {
+        [0, 0, 0, 0, 0, 0xffff, high, low] | [0, 0, 0, 0, 0, 0, high, low] | [0x64, 0xff9b, 0, 0, 0, 0, high, low] => Some(v4(high, low)),
+        [0x2002, high, low, ..] => Some(v4(high, low)),
+        [0x2001, 0, .., high, low] => Some(v4(!high, !low)),
+        _ => None,
+    }
+}
+
+fn is_non_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // Carrier-grade NAT, 100.64.0.0/10
+        || (a == 100 && (b & 0xc0) == 64)
+        // IETF protocol assignments, 192.0.0.0/24
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        // Benchmarking, 198.18.0.0/15
+        || (a == 198 && (b & 0xfe) == 18)
+        // Reserved, 240.0.0.0/4
+        || a >= 240
+}
+
+fn is_non_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10
+        || (first & 0xffc0) == 0xfe80
+        // Documentation, 2001:db8::/32
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8)
+}
+
+/// Resolves hosts under a [`DnsPolicy`], caching lookups and leaving out the addresses
+/// the policy refuses. Clones share one cache.
+///
+/// Give it to both the client, through [`ClientConfig::dns`](crate::client::ClientConfig::dns),
+/// and the parser, through [`with_dns`](crate::RustSitemapParser::with_dns). The parser
+/// checks each URL before fetching it, redirects included; the client's connections then
+/// use the same cached addresses, so a host can't resolve to an allowed address for the
+/// check and a refused one for the connection.
+#[derive(Clone, Default)]
+pub struct DnsResolver {
+    policy: Arc<DnsPolicy>,
+    resolver: Option<Arc<dyn Resolve>>,
+    cache: Arc<LookupCache>,
+}
+
+impl fmt::Debug for DnsResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnsResolver")
+            .field("policy", &self.policy)
+            .field("custom_resolver", &self.resolver.is_some())
+            .finish()
+    }
+}
+
+impl DnsResolver {
+    pub fn new(policy: DnsPolicy) -> Self {
+        let resolver = nameserver_resolver(&policy.nameservers);
+        Self { policy: Arc::new(policy), resolver, ..Default::default() }
+    }
+
+    /// Look names up with `resolver` instead of the system resolver or the policy's
+    /// nameservers, e.g. one querying DNS over HTTPS
+    pub fn with_resolver(mut self, resolver: Arc<dyn Resolve>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    pub fn policy(&self) -> &DnsPolicy {
+        &self.policy
+    }
+
+    /// The addresses of `host` the policy allows, from its overrides, the cache, or a
+    /// lookup. A refused host, or one with only refused addresses, fails with
+    /// [`SitemapError::Blocked`].
+    pub async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>, Box<dyn std::error::Error + Send + Sync>> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        if !self.policy.allows_host(&host) {
+            return Err(blocked(&host, format!("the DNS policy doesn't allow host {}", host)).into());
+        }
+        let addrs = match self.policy.overrides.get(&host) {
+            Some(addrs) => addrs.clone(),
+            None => match self.cached(&host) {
+                Some(addrs) => addrs,
+                None => self.resolve_uncached(&host).await?,
+            },
+        };
+        let (allowed, refused): (Vec<IpAddr>, Vec<IpAddr>) = addrs.iter().partition(|ip| self.policy.allows_ip(**ip));
+        if allowed.is_empty() && !refused.is_empty() {
+            let refused: Vec<String> = refused.iter().map(IpAddr::to_string).collect();
+            return Err(blocked(&host, format!("{} resolves only to addresses the DNS policy refuses ({})", host, refused.join(", "))).into());
+        }
+        Ok(allowed)
+    }
+
+    /// Check `url` against the policy before fetching it, resolving its host. Lookups
+    /// that fail aren't reported here but by the fetch, which may retry them.
+    pub async fn check(&self, url: &str) -> Result<(), SitemapError> {
+        let parsed = Url::parse(url).map_err(|e| SitemapError::InvalidUrl { url: url.to_string(), message: format!("Invalid URL {}: {}", url, e) })?;
+        let refuse = |reason: String| SitemapError::Blocked { url: url.to_string(), message: format!("Refused to fetch {}: {}", url, reason) };
+        let ip = match parsed.host() {
+            None => return Ok(()),
+            Some(Host::Domain(host)) => {
+                return match self.lookup(host).await.map_err(|e| e.downcast::<SitemapError>()) {
+                    Err(Ok(blocked)) => match *blocked {
+                        SitemapError::Blocked { message, .. } => Err(refuse(message)),
+                        other => Err(other),
+                    },
+                    _ => Ok(()),
+                };
+            }
+            Some(Host::Ipv4(ip)) => IpAddr::V4(ip),
+            Some(Host::Ipv6(ip)) => IpAddr::V6(ip),
+        };
+        if !self.policy.allows_host(&ip.to_string()) {
+            return Err(refuse(format!("the DNS policy doesn't allow host {}", ip)));
+        }
+        if !self.policy.allows_ip(ip) {
+            return Err(refuse(format!("the DNS policy refuses address {}", ip)));
+        }
+        Ok(())
+    }
+
+    fn cached(&self, host: &str) -> Option<Vec<IpAddr>> {
+        let ttl = self.policy.cache_ttl?;
+        let cache = self.cache.lock().unwrap();
+        cache.get(host).filter(|(resolved, _)| resolved.elapsed() < ttl).map(|(_, addrs)| addrs.clone())
+    }
+
+    async fn resolve_uncached(&self, host: &str) -> Result<Vec<IpAddr>, Box<dyn std::error::Error + Send + Sync>> {
+        let started = Instant::now();
+        let addrs: Vec<IpAddr> = match &self.resolver {
+            Some(resolver) => {
+                let name = Name::from_str(host).map_err(|_| format!("Invalid host name {:?}", host))?;
+                resolver.resolve(name).await?.map(|addr| addr.ip()).collect()
+            }
+            None => tokio::net::lookup_host((host, 0)).await?.map(|addr| addr.ip()).collect(),
+        };
+        telemetry::charge_dns(started.elapsed());
+        if let Some(ttl) = self.policy.cache_ttl {
+            let mut cache = self.cache.lock().unwrap();
+            if cache.len() >= MAX_CACHED_HOSTS {
+                cache.retain(|_, (resolved, _)| resolved.elapsed() < ttl);
+            }
+            cache.insert(host.to_string(), (Instant::now(), addrs.clone()));
+        }
+        Ok(addrs)
+    }
+}
+
+/// A resolver querying `nameservers`, if there are any
+#[cfg(feature = "hickory")]
+fn nameserver_resolver(nameservers: &[SocketAddr]) -> Option<Arc<dyn Resolve>> {
+    use hickory_resolver::config::{NameServerConfig, ResolverConfig};
+    use hickory_resolver::name_server::TokioConnectionProvider;
+    use hickory_resolver::proto::xfer::Protocol;
+
+    if nameservers.is_empty() {
+        return None;
+    }
+    let servers: Vec<NameServerConfig> = nameservers.iter()
+        .flat_map(|addr| [NameServerConfig::new(*addr, Protocol::Udp), NameServerConfig::new(*addr, Protocol::Tcp)])
+        .collect();
+    let config = ResolverConfig::from_parts(None, Vec::new(), servers);
+    let resolver = hickory_resolver::Resolver::builder_with_config(config, TokioConnectionProvider::default()).build();
+    Some(Arc::new(HickoryResolver(resolver)))
+}
+
+#[cfg(not(feature = "hickory"))]
+fn nameserver_resolver(_nameservers: &[SocketAddr]) -> Option<Arc<dyn Resolve>> {
+    None
+}
+
+/// hickory-resolver as a [`Resolve`]
+#[cfg(feature = "hickory")]
+struct HickoryResolver(hickory_resolver::TokioResolver);
+
+#[cfg(feature = "hickory")]
+impl Resolve for HickoryResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.0.clone();
+        Box::pin(async move {
+            let lookup = resolver.lookup_ip(name.as_str()).await?;
+            let addrs: Vec<SocketAddr> = lookup.iter().map(|ip| SocketAddr::new(ip, 0)).collect();
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn blocked(host: &str, message: String) -> SitemapError {
+    SitemapError::Blocked { url: host.to_string(), message }
+}
+
+impl Resolve for DnsResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            // The connector sets the port
+            let addrs = resolver.lookup(name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter().map(|ip| SocketAddr::new(ip, 0))) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_policy_hosts_and_networks() {
+        let policy = DnsPolicy {
+            block_private: true,
+            deny_networks: vec!["203.0.113.0/24".parse().unwrap()],
+            allow_networks: vec!["10.1.2.0/24".parse().unwrap()],
+            allow_hosts: vec!["example.com".into(), "*.example.org".into()],
+            deny_hosts: vec!["admin.example.com".into()],
+            ..Default::default()
+        };
+        assert!(policy.allows_host("www.example.com") && policy.allows_host("Example.COM.") && policy.allows_host("example.org"));
+        assert!(!policy.allows_host("admin.example.com") && !policy.allows_host("x.admin.example.com"));
+        assert!(!policy.allows_host("notexample.com") && !policy.allows_host("example.net"));
+
+        for refused in ["127.0.0.1", "10.0.0.5", "169.254.169.254", "100.64.1.1", "0.0.0.0", "203.0.113.9", "::1", "fd00::1", "fe80::1", "::ffff:192.168.1.1"] {
+            assert!(!policy.allows_ip(refused.parse().unwrap()), "{}", refused);
+        }
+        for allowed in ["93.184.216.34", "10.1.2.3", "2606:2800:220:1::1", "::ffff:93.184.216.34"] {
+            assert!(policy.allows_ip(allowed.parse().unwrap()), "{}", allowed);
+        }
+        assert!(DnsPolicy::default().allows_ip("127.0.0.1".parse().unwrap()));
+        assert_eq!(parse_network("192.0.2.1"), Ok("192.0.2.1/32".parse().unwrap()));
+        assert!(parse_network("10.0.0.0/33").is_err());
+    }
+
+    #[test]
+    fn test_ipv6_carrying_private_ipv4_is_refused() {
+        let policy = DnsPolicy { block_private: true, ..Default::default() };
+        // NAT64, 6to4, IPv4-compatible, and Teredo (client 10.0.0.1, stored inverted),
+        // plus 192.0.0.0/24 itself
+        for refused in ["64:ff9b::10.0.0.1", "64:ff9b::7f00:1", "2002:c0a8:101::1", "::10.0.0.1", "::169.254.169.254", "2001:0:4136:e378:8000:63bf:f5ff:fffe", "192.0.0.8"] {
+            assert!(!policy.allows_ip(refused.parse().unwrap()), "{}", refused);
+        }
+        for allowed in ["64:ff9b::93.184.216.34", "2002:5db8:d822::1", "2001:0:4136:e378:8000:63bf:a247:27dd"] {
+            assert!(policy.allows_ip(allowed.parse().unwrap()), "{}", allowed);
+        }
+    }
+
+    #[cfg(feature = "hickory")]
+    #[tokio::test]
+    async fn test_nameservers_are_queried() {
+        use hickory_resolver::proto::op::{Message, MessageType};
+        use hickory_resolver::proto::rr::{rdata::A, RData, Record, RecordType};
+        use hickory_resolver::proto::serialize::binary::BinEncodable;
+
+        // A nameserver answering every A query with 10.0.0.9 and everything else with nothing
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let nameserver = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0; 512];
+            while let Ok((len, from)) = socket.recv_from(&mut buf).await {
+                let query = Message::from_vec(&buf[..len]).unwrap();
+                let mut response = Message::new();
+                response.set_id(query.id()).set_message_type(MessageType::Response).add_queries(query.queries().to_vec());
+                if let Some(question) = query.queries().first().filter(|q| q.query_type() == RecordType::A) {
+                    response.add_answer(Record::from_rdata(question.name().clone(), 60, RData::A(A::new(10, 0, 0, 9))));
+                }
+                socket.send_to(&response.to_bytes().unwrap(), from).await.unwrap();
+            }
+        });
+
+        let resolver = DnsResolver::new(DnsPolicy { nameservers: vec![nameserver], ..Default::default() });
+        assert_eq!(resolver.lookup("sitemaps.test").await.unwrap(), ["10.0.0.9".parse::<IpAddr>().unwrap()]);
+        let strict = DnsResolver::new(DnsPolicy { nameservers: vec![nameserver], block_private: true, ..Default::default() });
+        assert_eq!(strict.check("https://sitemaps.test/").await.unwrap_err().kind(), "blocked");
+
+        assert_eq!(parse_nameserver("1.1.1.1"), Ok("1.1.1.1:53".parse().unwrap()));
+        assert_eq!(parse_nameserver("[2606:4700::1111]"), Ok("[2606:4700::1111]:53".parse().unwrap()));
+        assert_eq!(parse_nameserver("127.0.0.1:5353"), Ok("127.0.0.1:5353".parse().unwrap()));
+        assert!(parse_nameserver("dns.example").is_err());
+    }
+
+    struct CountingResolver(Arc<AtomicUsize>);
+
+    impl Resolve for CountingResolver {
+        fn resolve(&self, name: Name) -> Resolving {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            let addrs: Vec<SocketAddr> = match name.as_str() {
+                "internal.test" => vec!["10.0.0.7:0".parse().unwrap()],
+                _ => vec!["10.0.0.8:0".parse().unwrap(), "93.184.216.34:0".parse().unwrap()],
+            };
+            Box::pin(async move { Ok(Box::new(addrs.into_iter()) as Addrs) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolver_caches_and_refuses() {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let policy = DnsPolicy {
+            cache_ttl: Some(Duration::from_secs(60)),
+            block_private: true,
+            overrides: HashMap::from([("pinned.test".to_string(), vec!["127.0.0.1".parse().unwrap()])]),
+            ..Default::default()
+        };
+        let resolver = DnsResolver::new(policy).with_resolver(Arc::new(CountingResolver(Arc::clone(&lookups))));
+
+        let public: Vec<IpAddr> = vec!["93.184.216.34".parse().unwrap()];
+        assert_eq!(resolver.lookup("mixed.test").await.unwrap(), public);
+        assert_eq!(resolver.clone().lookup("MIXED.test").await.unwrap(), public);
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        assert!(resolver.check("https://mixed.test/sitemap.xml").await.is_ok());
+        let refused = resolver.check("https://internal.test/sitemap.xml").await.unwrap_err();
+        assert_eq!(refused.kind(), "blocked");
+        assert_eq!(refused.url(), "https://internal.test/sitemap.xml");
+        assert!(refused.to_string().contains("internal.test resolves only to addresses the DNS policy refuses (10.0.0.7)"), "{}", refused);
+        assert_eq!(resolver.check("http://[::1]:8080/").await.unwrap_err().kind(), "blocked");
+        assert_eq!(resolver.check("http://pinned.test/").await.unwrap_err().kind(), "blocked");
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+}
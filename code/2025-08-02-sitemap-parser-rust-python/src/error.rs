@@ -0,0 +1,160 @@
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::retry::HttpStatusError;
+
+/// What went wrong with a site, its robots.txt, or one of its sitemaps, kept typed
+/// so callers can tell a site with no sitemap from a broken network.
+///
+/// Each error displays as the message it was recorded with in `errors`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SitemapError {
+    /// The server answered with a non-success status
+    Http { url: String, status: u16 },
+    Timeout { url: String, message: String },
+    /// DNS, connection, TLS, or a connection dropped before any body arrived
+    Network { url: String, message: String },
+    TooManyRedirects { url: String, message: String },
+    /// The redirect policy doesn't allow following `url`'s redirect to `location`
+    RedirectRefused { url: String, location: String, message: String },
+    /// The body was fetched but couldn't be parsed as a sitemap (XML, feed, JSON, or text)
+    XmlParse { url: String, message: String },
+    /// robots.txt couldn't be fetched, so none of its sitemaps were tried
+    RobotsFetch { url: String, cause: Box<SitemapError> },
+    /// The site answered, but robots.txt is missing or lists no sitemaps and none of the
+    /// usual sitemap locations exist
+    NoSitemap { base_url: String },
+    InvalidUrl { url: String, message: String },
+    /// The DNS policy refuses the URL's host, or every address it resolves to
+    Blocked { url: String, message: String },
+    /// A fetch was skipped or cut off by an interrupt or a cancelled call
+    Interrupted { url: String, message: String },
+    /// The reputation store says the site asked for no requests until `until`
+    Skipped { base_url: String, until: String },
+    Other { url: String, message: String },
+}
+
+impl SitemapError {
+    /// Type an error from fetching or parsing `url`, falling back to `Other`
+    pub fn from_fetch(url: &str, e: &(dyn std::error::Error + Send + Sync + 'static)) -> Self {
+        if let Some(e) = e.downcast_ref::<SitemapError>() {
+            return e.clone();
+        }
+        if let Some(e) = e.downcast_ref::<HttpStatusError>() {
+            return SitemapError::Http { url: e.url.clone(), status: e.status.as_u16() };
+        }
+        let (url, message) = (url.to_string(), e.to_string());
+        if e.is::<url::ParseError>() {
+            return SitemapError::InvalidUrl { url, message };
+        }
+        match e.downcast_ref::<reqwest::Error>() {
+            Some(e) if e.is_timeout() => SitemapError::Timeout { url, message },
+            Some(e) if e.is_redirect() => SitemapError::TooManyRedirects { url, message },
+            Some(e) if e.is_connect() || e.is_request() || e.is_body() || e.is_decode() => SitemapError::Network { url, message },
+            _ => SitemapError::Other { url, message },
+        }
+    }
+
+    /// Short name of the variant: "http", "timeout", "no_sitemap", ...
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SitemapError::Http { .. } => "http",
+            SitemapError::Timeout { .. } => "timeout",
+            SitemapError::Network { .. } => "network",
+            SitemapError::TooManyRedirects { .. } => "too_many_redirects",
+            SitemapError::RedirectRefused { .. } => "redirect_refused",
+            SitemapError::XmlParse { .. } => "xml_parse",
+            SitemapError::RobotsFetch { .. } => "robots_fetch",
+            SitemapError::NoSitemap { .. } => "no_sitemap",
+            SitemapError::InvalidUrl { .. } => "invalid_url",
+            SitemapError::Blocked { .. } => "blocked",
+            SitemapError::Interrupted { .. } => "interrupted",
+            SitemapError::Skipped { .. } => "skipped",
+            SitemapError::Other { .. } => "other",
+        }
+    }
+
+    /// The site, robots.txt, or sitemap URL the error is about
+    pub fn url(&self) -> &str {
+        match self {
+            SitemapError::Http { url, .. }
+            | SitemapError::Timeout { url, .. }
+            | SitemapError::Network { url, .. }
+            | SitemapError::TooManyRedirects { url, .. }
+            | SitemapError::RedirectRefused { url, .. }
+            | SitemapError::XmlParse { url, .. }
+            | SitemapError::RobotsFetch { url, .. }
+            | SitemapError::InvalidUrl { url, .. }
+            | SitemapError::Blocked { url, .. }
+            | SitemapError::Interrupted { url, .. }
+            | SitemapError::Other { url, .. } => url,
+            SitemapError::NoSitemap { base_url } | SitemapError::Skipped { base_url, .. } => base_url,
+        }
+    }
+
+    /// Whether the error was an HTTP answer of 404 or 410
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, SitemapError::Http { status: 404 | 410, .. })
+    }
+}
+
+impl fmt::Display for SitemapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SitemapError::Http { url, status } => match StatusCode::from_u16(*status) {
+                Ok(status) => write!(f, "HTTP {} for {}", status, url),
+                Err(_) => write!(f, "HTTP {} for {}", status, url),
+            },
+            SitemapError::RobotsFetch { url, cause } => write!(f, "Could not fetch robots.txt from {}: {}", url, cause),
+            SitemapError::NoSitemap { base_url } => write!(f, "No sitemap found for {}: robots.txt lists none and the usual locations don't exist", base_url),
+            SitemapError::Skipped { base_url, until } => write!(f, "Skipped {}: it asked for no requests until {}", base_url, until),
+            SitemapError::Timeout { message, .. }
+            | SitemapError::Network { message, .. }
+            | SitemapError::TooManyRedirects { message, .. }
+            | SitemapError::RedirectRefused { message, .. }
+            | SitemapError::XmlParse { message, .. }
+            | SitemapError::InvalidUrl { message, .. }
+            | SitemapError::Blocked { message, .. }
+            | SitemapError::Interrupted { message, .. }
+            | SitemapError::Other { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SitemapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SitemapError::RobotsFetch { cause, .. } => Some(cause.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderMap;
+
+    #[test]
+    fn test_from_fetch_keeps_types_and_messages() {
+        let url = "https://example.com/sitemap.xml";
+        let status = HttpStatusError::new(StatusCode::NOT_FOUND, url, &HeaderMap::new());
+        let error = SitemapError::from_fetch(url, &status);
+        assert_eq!(error, SitemapError::Http { url: url.to_string(), status: 404 });
+        assert_eq!(error.to_string(), status.to_string());
+        assert!(error.is_not_found());
+
+        let interrupted = SitemapError::Interrupted { url: url.to_string(), message: format!("Interrupted while fetching {}", url) };
+        assert_eq!(SitemapError::from_fetch(url, &interrupted), interrupted);
+
+        let invalid = url::Url::parse("http://[::1").unwrap_err();
+        assert_eq!(SitemapError::from_fetch("http://[::1", &invalid).kind(), "invalid_url");
+        let other: Box<dyn std::error::Error + Send + Sync> = "something odd".into();
+        assert_eq!(SitemapError::from_fetch(url, other.as_ref()), SitemapError::Other { url: url.to_string(), message: "something odd".to_string() });
+
+        let robots = SitemapError::RobotsFetch { url: "https://example.com/robots.txt".to_string(), cause: Box::new(error) };
+        assert_eq!(robots.to_string(), "Could not fetch robots.txt from https://example.com/robots.txt: HTTP 404 Not Found for https://example.com/sitemap.xml");
+    }
+}
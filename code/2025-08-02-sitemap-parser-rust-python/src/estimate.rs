@@ -0,0 +1,125 @@
+use crate::plan::{PlannedSitemap, SitePlan, SitemapKind};
+
+/// A urlset downloaded in full to calibrate an estimate
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampledSitemap {
+    pub url: String,
+    pub urls: usize,
+    pub bytes: usize,
+}
+
+/// A site's approximate size, from its indexes, its urlsets' advertised sizes, and a few samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteEstimate {
+    pub base_url: String,
+    /// Urlsets a full parse would fetch
+    pub urlsets: usize,
+    pub samples: Vec<SampledSitemap>,
+    pub estimated_urls: u64,
+    /// Bytes a full parse would download, robots.txt and indexes included
+    pub estimated_bytes: u64,
+    /// Requests the estimate itself made
+    pub requests_made: usize,
+    pub errors: Vec<String>,
+    pub estimate_time: f64,
+}
+
+impl SiteEstimate {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            base_url,
+            urlsets: 0,
+            samples: Vec::new(),
+            estimated_urls: 0,
+            estimated_bytes: 0,
+            requests_made: 0,
+            errors: Vec::new(),
+            estimate_time: 0.0,
+        }
+    }
+}
+
+/// Sitemaps a real run would parse URLs from; unidentified ones might be urlsets too
+pub fn urlsets(plan: &SitePlan) -> Vec<&PlannedSitemap> {
+    plan.sitemaps
+        .iter()
+        .filter(|s| matches!(s.kind, SitemapKind::Urlset | SitemapKind::Unknown))
+        .collect()
+}
+
+/// Up to `count` indices spread evenly over `0..len`, so samples aren't all from one end of the site
+pub fn sample_indices(len: usize, count: usize) -> Vec<usize> {
+    let count = count.min(len);
+    (0..count).map(|i| i * len / count).collect()
+}
+
+/// Scale the samples up to every urlset in the plan.
+///
+/// Sampled urlsets count exactly. Others with a Content-Length are sized by the
+/// samples' bytes per URL, and the rest are assumed to be an average sample.
+pub fn extrapolate(plan: &SitePlan, samples: &[SampledSitemap]) -> (u64, u64) {
+    let sampled_urls: usize = samples.iter().map(|s| s.urls).sum();
+    let sampled_bytes: usize = samples.iter().map(|s| s.bytes).sum();
+    if samples.is_empty() {
+        return (0, plan.estimated_bytes);
+    }
+    let bytes_per_url = (sampled_urls > 0).then(|| sampled_bytes as f64 / sampled_urls as f64);
+    let average_urls = sampled_urls as f64 / samples.len() as f64;
+    let average_bytes = sampled_bytes as f64 / samples.len() as f64;
+
+    let mut urls = 0.0;
+    let mut bytes = plan.estimated_bytes as f64;
+    for urlset in urlsets(plan) {
+        if let Some(sample) = samples.iter().find(|s| s.url == urlset.url) {
+            urls += sample.urls as f64;
+            if urlset.content_length.is_none() {
+                bytes += sample.bytes as f64;
+            }
+            continue;
+        }
+        match (urlset.content_length, bytes_per_url) {
+            (Some(length), Some(per_url)) => urls += length as f64 / per_url,
+            (Some(_), None) => {}
+            (None, _) => {
+                urls += average_urls;
+                bytes += average_bytes;
+            }
+        }
+    }
+    (urls.round() as u64, bytes.round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urlset(url: &str, content_length: Option<u64>) -> PlannedSitemap {
+        PlannedSitemap { url: url.to_string(), kind: SitemapKind::Urlset, level: 2, content_length }
+    }
+
+    #[test]
+    fn test_sample_indices_spread() {
+        assert_eq!(sample_indices(10, 2), vec![0, 5]);
+        assert_eq!(sample_indices(1, 3), vec![0]);
+        assert!(sample_indices(0, 2).is_empty());
+    }
+
+    #[test]
+    fn test_extrapolate() {
+        let mut plan = SitePlan::new("https://example.com".to_string());
+        plan.sitemaps = vec![
+            PlannedSitemap { url: "https://example.com/index.xml".to_string(), kind: SitemapKind::Index, level: 1, content_length: None },
+            urlset("https://example.com/a.xml", Some(10_000)),
+            urlset("https://example.com/b.xml", Some(20_000)),
+            urlset("https://example.com/c.xml", None),
+        ];
+        // robots.txt and the index, plus the two advertised sizes
+        plan.estimated_bytes = 1_000 + 30_000;
+        let samples = vec![SampledSitemap { url: "https://example.com/a.xml".to_string(), urls: 100, bytes: 10_000 }];
+
+        let (urls, bytes) = extrapolate(&plan, &samples);
+        // a: sampled, b: 20KB at 100 bytes per URL, c: no size so one average sample
+        assert_eq!(urls, 100 + 200 + 100);
+        assert_eq!(bytes, 31_000 + 10_000);
+    }
+}
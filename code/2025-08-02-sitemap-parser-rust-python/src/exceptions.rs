@@ -0,0 +1,73 @@
+use pyo3::prelude::*;
+
+use crate::error;
+
+// Python counterparts of `error::SitemapError`'s variants, all catchable as `SitemapError`
+pyo3::create_exception!(rust_parser, SitemapError, pyo3::exceptions::PyException, "Base class of every error a sitemap parse records or raises");
+pyo3::create_exception!(rust_parser, HttpError, SitemapError, "The server answered with a non-success status, in `status`");
+pyo3::create_exception!(rust_parser, FetchTimeoutError, SitemapError, "A request timed out");
+pyo3::create_exception!(rust_parser, NetworkError, SitemapError, "DNS, connection, or TLS failure, or a connection dropped before any body arrived");
+pyo3::create_exception!(rust_parser, TooManyRedirectsError, SitemapError, "A request was redirected too many times");
+pyo3::create_exception!(rust_parser, RedirectRefusedError, SitemapError, "A redirect the redirect policy doesn't allow, like one to another host");
+pyo3::create_exception!(rust_parser, XmlParseError, SitemapError, "A sitemap was fetched but couldn't be parsed");
+pyo3::create_exception!(rust_parser, RobotsFetchError, SitemapError, "robots.txt couldn't be fetched; `cause` is the underlying error");
+pyo3::create_exception!(rust_parser, NoSitemapError, SitemapError, "The site answered but has no sitemap in robots.txt or the usual locations");
+pyo3::create_exception!(rust_parser, InvalidUrlError, SitemapError, "A base URL couldn't be parsed");
+pyo3::create_exception!(rust_parser, BlockedError, SitemapError, "The DNS policy refuses a URL's host or every address it resolves to");
+pyo3::create_exception!(rust_parser, ParseInterruptedError, SitemapError, "A fetch was skipped or cut off by an interrupt or a cancelled call");
+pyo3::create_exception!(rust_parser, SiteSkippedError, SitemapError, "The site was skipped because it asked for no requests for a while");
+
+/// `failure` as an instance of its exception class, with `kind`, `url`, `status`, and
+/// `cause` attributes
+pub(crate) fn to_py_err(py: Python<'_>, failure: &error::SitemapError) -> PyResult<PyErr> {
+    let message = failure.to_string();
+    let err = match failure {
+        error::SitemapError::Http { .. } => HttpError::new_err(message),
+        error::SitemapError::Timeout { .. } => FetchTimeoutError::new_err(message),
+        error::SitemapError::Network { .. } => NetworkError::new_err(message),
+        error::SitemapError::TooManyRedirects { .. } => TooManyRedirectsError::new_err(message),
+        error::SitemapError::RedirectRefused { .. } => RedirectRefusedError::new_err(message),
+        error::SitemapError::XmlParse { .. } => XmlParseError::new_err(message),
+        error::SitemapError::RobotsFetch { .. } => RobotsFetchError::new_err(message),
+        error::SitemapError::NoSitemap { .. } => NoSitemapError::new_err(message),
+        error::SitemapError::InvalidUrl { .. } => InvalidUrlError::new_err(message),
+        error::SitemapError::Blocked { .. } => BlockedError::new_err(message),
+        error::SitemapError::Interrupted { .. } => ParseInterruptedError::new_err(message),
+        error::SitemapError::Skipped { .. } => SiteSkippedError::new_err(message),
+        error::SitemapError::Other { .. } => SitemapError::new_err(message),
+    };
+    let value = err.value(py);
+    value.setattr("kind", failure.kind())?;
+    value.setattr("url", failure.url())?;
+    value.setattr("status", match failure {
+        error::SitemapError::Http { status, .. } => Some(*status),
+        _ => None,
+    })?;
+    match failure {
+        error::SitemapError::RobotsFetch { cause, .. } => {
+            let cause = to_py_err(py, cause)?;
+            value.setattr("cause", cause.value(py))?;
+            err.set_cause(py, Some(cause));
+        }
+        _ => value.setattr("cause", py.None())?,
+    }
+    Ok(err)
+}
+
+pub(crate) fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("SitemapError", py.get_type::<SitemapError>())?;
+    m.add("HttpError", py.get_type::<HttpError>())?;
+    m.add("FetchTimeoutError", py.get_type::<FetchTimeoutError>())?;
+    m.add("NetworkError", py.get_type::<NetworkError>())?;
+    m.add("TooManyRedirectsError", py.get_type::<TooManyRedirectsError>())?;
+    m.add("RedirectRefusedError", py.get_type::<RedirectRefusedError>())?;
+    m.add("XmlParseError", py.get_type::<XmlParseError>())?;
+    m.add("RobotsFetchError", py.get_type::<RobotsFetchError>())?;
+    m.add("NoSitemapError", py.get_type::<NoSitemapError>())?;
+    m.add("InvalidUrlError", py.get_type::<InvalidUrlError>())?;
+    m.add("BlockedError", py.get_type::<BlockedError>())?;
+    m.add("ParseInterruptedError", py.get_type::<ParseInterruptedError>())?;
+    m.add("SiteSkippedError", py.get_type::<SiteSkippedError>())?;
+    Ok(())
+}
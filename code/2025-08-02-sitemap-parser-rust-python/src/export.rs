@@ -0,0 +1,365 @@
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::monitor::SiteOutcome;
+use crate::parser::ParsedSiteResult;
+#[cfg(feature = "arrow")]
+use crate::score::parse_lastmod;
+use crate::sitemap::UrlMetadata;
+
+/// Columns of every export, one row per URL; metadata columns are empty unless the
+/// parser kept metadata
+pub const COLUMNS: [&str; 6] = ["site", "url", "lastmod", "changefreq", "priority", "depth"];
+
+/// Rows buffered before a Parquet row group is written
+#[cfg(feature = "parquet")]
+const PARQUET_BATCH_ROWS: usize = 64 * 1024;
+
+/// File formats URLs can be exported in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One JSON object per URL, leaving out metadata the sitemap didn't give
+    Jsonl,
+    /// RFC 4180 CSV with a header row
+    Csv,
+    /// Snappy-compressed Parquet; needs the `parquet` feature
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "jsonl" | "ndjson" => Some(ExportFormat::Jsonl),
+            "csv" => Some(ExportFormat::Csv),
+            "parquet" => Some(ExportFormat::Parquet),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Jsonl => write!(f, "jsonl"),
+            ExportFormat::Csv => write!(f, "csv"),
+            ExportFormat::Parquet => write!(f, "parquet"),
+        }
+    }
+}
+
+/// What an export wrote, in place of the URLs themselves
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExportSummary {
+    pub sites: usize,
+    /// Sites that gave no URLs, only errors
+    pub failed_sites: usize,
+    pub urls: usize,
+    pub errors: Vec<String>,
+    pub bytes_written: u64,
+}
+
+enum Sink {
+    Text(BufWriter<File>),
+    #[cfg(feature = "parquet")]
+    Parquet(Box<parquet_sink::ParquetSink>),
+}
+
+/// Writes sites' URLs to a file as each site finishes, so a batch never holds more
+/// than one site's URLs in memory and none of them have to cross into Python.
+pub struct UrlExporter {
+    format: ExportFormat,
+    sink: Sink,
+    summary: ExportSummary,
+}
+
+impl UrlExporter {
+    /// Create (or truncate) `path`; Parquet fails with `Unsupported` when the crate was
+    /// built without the `parquet` feature
+    pub fn create(path: &Path, format: ExportFormat) -> io::Result<Self> {
+        let sink = match format {
+            ExportFormat::Jsonl | ExportFormat::Csv => {
+                let mut out = BufWriter::new(File::create(path)?);
+                if format == ExportFormat::Csv {
+                    writeln!(out, "{}", COLUMNS.join(","))?;
+                }
+                Sink::Text(out)
+            }
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => Sink::Parquet(Box::new(parquet_sink::ParquetSink::create(path)?)),
+            #[cfg(not(feature = "parquet"))]
+            ExportFormat::Parquet => {
+                return Err(io::Error::new(io::ErrorKind::Unsupported, "Parquet export needs the `parquet` cargo feature"));
+            }
+        };
+        Ok(Self { format, sink, summary: ExportSummary::default() })
+    }
+
+    /// Write one row per URL of `result`, sorted when a stable order is wanted
+    pub fn write_site(&mut self, result: &ParsedSiteResult, sorted: bool) -> io::Result<()> {
+        for url in result.url_list(sorted) {
+            let meta = result.url_metadata.get(&url);
+            match &mut self.sink {
+                Sink::Text(out) if self.format == ExportFormat::Csv => writeln!(out, "{}", csv_row(&result.base_url, &url, meta))?,
+                Sink::Text(out) => writeln!(out, "{}", json_row(&result.base_url, &url, meta))?,
+                #[cfg(feature = "parquet")]
+                Sink::Parquet(sink) => sink.push(&result.base_url, &url, meta)?,
+            }
+        }
+        self.summary.sites += 1;
+        self.summary.failed_sites += usize::from(SiteOutcome::of(result) == SiteOutcome::Failed);
+        self.summary.urls += result.urls.len();
+        self.summary.errors.extend(result.errors.iter().cloned());
+        Ok(())
+    }
+
+    /// Flush everything (and write Parquet's footer), returning what was written
+    pub fn finish(mut self) -> io::Result<ExportSummary> {
+        let file = match self.sink {
+            Sink::Text(out) => out.into_inner().map_err(|e| e.into_error())?,
+            #[cfg(feature = "parquet")]
+            Sink::Parquet(sink) => sink.finish()?,
+        };
+        file.sync_all()?;
+        self.summary.bytes_written = file.metadata()?.len();
+        Ok(self.summary)
+    }
+}
+
+fn json_row(site: &str, url: &str, meta: Option<&UrlMetadata>) -> Value {
+    let mut row = Map::new();
+    row.insert("site".to_string(), json!(site));
+    row.insert("url".to_string(), json!(url));
+    if let Some(meta) = meta {
+        if let Some(lastmod) = &meta.lastmod {
+            row.insert("lastmod".to_string(), json!(lastmod));
+        }
+        if let Some(changefreq) = &meta.changefreq {
+            row.insert("changefreq".to_string(), json!(changefreq));
+        }
+        if let Some(priority) = meta.priority {
+            row.insert("priority".to_string(), json!(priority));
+        }
+        row.insert("depth".to_string(), json!(meta.depth));
+    }
+    Value::Object(row)
+}
+
+fn csv_row(site: &str, url: &str, meta: Option<&UrlMetadata>) -> String {
+    let fields = [
+        site.to_string(),
+        url.to_string(),
+        meta.and_then(|m| m.lastmod.clone()).unwrap_or_default(),
+        meta.and_then(|m| m.changefreq.clone()).unwrap_or_default(),
+        meta.and_then(|m| m.priority).map(|p| p.to_string()).unwrap_or_default(),
+        meta.map(|m| m.depth.to_string()).unwrap_or_default(),
+    ];
+    fields.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(",")
+}
+
+/// `field` quoted if it holds a comma, quote, or line break, with quotes doubled
+fn csv_field(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+/// `urls` in order as an Arrow record batch of `url`, `lastmod` (UTC milliseconds, null
+/// when missing or unparseable), `priority`, and `source_sitemap`, the last three
+/// taken from `metadata`
+#[cfg(feature = "arrow")]
+pub fn url_record_batch(urls: &[String], metadata: &std::collections::HashMap<String, UrlMetadata>) -> Result<arrow_array::RecordBatch, arrow_schema::ArrowError> {
+    use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray, TimestampMillisecondArray};
+    use arrow_schema::{DataType, Field, Schema, TimeUnit};
+    use std::sync::Arc;
+
+    let metas: Vec<Option<&UrlMetadata>> = urls.iter().map(|url| metadata.get(url)).collect();
+    let lastmod: TimestampMillisecondArray = metas.iter()
+        .map(|m| m.and_then(|m| m.lastmod.as_deref()).and_then(parse_lastmod).map(|t| t.timestamp_millis()))
+        .collect();
+    let schema = Schema::new(vec![
+        Field::new("url", DataType::Utf8, false),
+        Field::new("lastmod", DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())), true),
+        Field::new("priority", DataType::Float64, true),
+        Field::new("source_sitemap", DataType::Utf8, true),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(urls)),
+        Arc::new(lastmod.with_timezone("UTC")),
+        Arc::new(metas.iter().map(|m| m.and_then(|m| m.priority)).collect::<Float64Array>()),
+        Arc::new(metas.iter().map(|m| m.and_then(|m| m.source_sitemap.as_deref())).collect::<StringArray>()),
+    ];
+    RecordBatch::try_new(Arc::new(schema), columns)
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_sink {
+    use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray, UInt64Array};
+    use arrow_schema::{DataType, Field, Schema, SchemaRef};
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use super::PARQUET_BATCH_ROWS;
+    use crate::sitemap::UrlMetadata;
+
+    /// Rows gathered column by column and written a row group at a time
+    pub struct ParquetSink {
+        writer: ArrowWriter<File>,
+        schema: SchemaRef,
+        site: Vec<String>,
+        url: Vec<String>,
+        lastmod: Vec<Option<String>>,
+        changefreq: Vec<Option<String>>,
+        priority: Vec<Option<f64>>,
+        depth: Vec<Option<u64>>,
+    }
+
+    impl ParquetSink {
+        pub fn create(path: &Path) -> io::Result<Self> {
+            let schema = Arc::new(Schema::new(vec![
+                Field::new("site", DataType::Utf8, false),
+                Field::new("url", DataType::Utf8, false),
+                Field::new("lastmod", DataType::Utf8, true),
+                Field::new("changefreq", DataType::Utf8, true),
+                Field::new("priority", DataType::Float64, true),
+                Field::new("depth", DataType::UInt64, true),
+            ]));
+            let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+            let writer = ArrowWriter::try_new(File::create(path)?, Arc::clone(&schema), Some(properties)).map_err(io::Error::other)?;
+            Ok(Self {
+                writer,
+                schema,
+                site: Vec::new(),
+                url: Vec::new(),
+                lastmod: Vec::new(),
+                changefreq: Vec::new(),
+                priority: Vec::new(),
+                depth: Vec::new(),
+            })
+        }
+
+        pub fn push(&mut self, site: &str, url: &str, meta: Option<&UrlMetadata>) -> io::Result<()> {
+            self.site.push(site.to_string());
+            self.url.push(url.to_string());
+            self.lastmod.push(meta.and_then(|m| m.lastmod.clone()));
+            self.changefreq.push(meta.and_then(|m| m.changefreq.clone()));
+            self.priority.push(meta.and_then(|m| m.priority));
+            self.depth.push(meta.map(|m| m.depth as u64));
+            if self.url.len() >= PARQUET_BATCH_ROWS {
+                self.flush()?;
+            }
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if self.url.is_empty() {
+                return Ok(());
+            }
+            let columns: Vec<ArrayRef> = vec![
+                Arc::new(StringArray::from(std::mem::take(&mut self.site))),
+                Arc::new(StringArray::from(std::mem::take(&mut self.url))),
+                Arc::new(StringArray::from(std::mem::take(&mut self.lastmod))),
+                Arc::new(StringArray::from(std::mem::take(&mut self.changefreq))),
+                Arc::new(Float64Array::from(std::mem::take(&mut self.priority))),
+                Arc::new(UInt64Array::from(std::mem::take(&mut self.depth))),
+            ];
+            let batch = RecordBatch::try_new(Arc::clone(&self.schema), columns).map_err(io::Error::other)?;
+            self.writer.write(&batch).map_err(io::Error::other)
+        }
+
+        pub fn finish(mut self) -> io::Result<File> {
+            self.flush()?;
+            self.writer.into_inner().map_err(io::Error::other)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site() -> ParsedSiteResult {
+        let mut result = ParsedSiteResult::new("https://example.com".to_string());
+        result.urls.extend(["https://example.com/b?x=1,2".to_string(), "https://example.com/a".to_string()]);
+        result.url_metadata.insert("https://example.com/a".to_string(), UrlMetadata {
+            lastmod: Some("2024-06-01".to_string()),
+            priority: Some(0.5),
+            depth: 1,
+            ..Default::default()
+        });
+        result
+    }
+
+    fn export(format: ExportFormat) -> (ExportSummary, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!("sitemap-export-{}.{}", uuid::Uuid::new_v4(), format));
+        let mut exporter = UrlExporter::create(&path, format).unwrap();
+        exporter.write_site(&site(), true).unwrap();
+        exporter.write_site(&ParsedSiteResult::failed("https://down.example".to_string(), &io::Error::other("refused")), true).unwrap();
+        (exporter.finish().unwrap(), path)
+    }
+
+    #[test]
+    fn test_jsonl_and_csv_exports() {
+        let (summary, path) = export(ExportFormat::Jsonl);
+        assert_eq!((summary.sites, summary.failed_sites, summary.urls, summary.errors.len()), (2, 1, 2, 1));
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(summary.bytes_written, written.len() as u64);
+        assert_eq!(written, concat!(
+            r#"{"depth":1,"lastmod":"2024-06-01","priority":0.5,"site":"https://example.com","url":"https://example.com/a"}"#, "\n",
+            r#"{"site":"https://example.com","url":"https://example.com/b?x=1,2"}"#, "\n",
+        ));
+        std::fs::remove_file(path).unwrap();
+
+        let (_, path) = export(ExportFormat::Csv);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), concat!(
+            "site,url,lastmod,changefreq,priority,depth\n",
+            "https://example.com,https://example.com/a,2024-06-01,,0.5,1\n",
+            "https://example.com,\"https://example.com/b?x=1,2\",,,,\n",
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_url_record_batch() {
+        use arrow_array::{Array, Float64Array, StringArray, TimestampMillisecondArray};
+
+        let result = site();
+        let urls = result.url_list(true);
+        let batch = url_record_batch(&urls, &result.url_metadata).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        let column = |name| batch.column_by_name(name).unwrap();
+        let lastmod = column("lastmod").as_any().downcast_ref::<TimestampMillisecondArray>().unwrap();
+        assert_eq!((lastmod.value(0), lastmod.is_null(1)), (1_717_200_000_000, true));
+        let priority = column("priority").as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!((priority.value(0), priority.is_null(1)), (0.5, true));
+        let url = column("url").as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(url.value(1), "https://example.com/b?x=1,2");
+        assert_eq!(column("source_sitemap").null_count(), 2);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_parquet_export() {
+        use arrow_array::{Array, StringArray};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let (summary, path) = export(ExportFormat::Parquet);
+        assert_eq!(summary.urls, 2);
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap().build().unwrap();
+        let batches: Vec<_> = reader.map(Result::unwrap).collect();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+        let lastmod = batches[0].column_by_name("lastmod").unwrap().as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!((lastmod.value(0), lastmod.is_null(1)), ("2024-06-01", true));
+        std::fs::remove_file(path).unwrap();
+    }
+}
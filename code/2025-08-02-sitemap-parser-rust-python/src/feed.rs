@@ -0,0 +1,325 @@
+use chrono::DateTime;
+use log::warn;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::NsReader;
+
+use crate::quirks;
+use crate::sitemap::{
+    is_predefined_entity, make_absolute_url, reject_entity, ParseOptions, SitemapParseResult, UrlMetadata, XmlLimits, XmlUsage,
+};
+
+/// Namespace of Google Merchant Center product attributes (`g:id`, `g:price`, ...)
+const GOOGLE_NS: &[u8] = b"http://base.google.com/ns/1.0";
+
+/// Whether a document whose root element has this local name is an RSS or Atom feed
+/// (as Google Merchant product feeds are) rather than a sitemap
+pub fn is_feed_root(local_name: &[u8]) -> bool {
+    matches!(local_name, b"rss" | b"feed")
+}
+
+/// A direct child of an `<item>` / `<entry>` whose text we keep
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Link,
+    GoogleLink,
+    Id,
+    Price,
+    SalePrice,
+    /// Atom `<updated>`
+    Updated,
+    /// RSS `<pubDate>` or Atom `<published>`
+    Published,
+}
+
+#[derive(Debug, Default)]
+struct Item {
+    link: Option<String>,
+    /// `g:link`, which wins over the feed's own `<link>` when both are given
+    google_link: Option<String>,
+    id: Option<String>,
+    price: Option<String>,
+    sale_price: Option<String>,
+    updated: Option<String>,
+    published: Option<String>,
+}
+
+/// Parse a Google Merchant product feed, RSS 2.0 or Atom, into `result`.
+///
+/// Each item's product page (`g:link`, else `<link>`) becomes a URL. With
+/// `options.extensions` its `g:id`, `g:price`, and `g:sale_price` are kept in
+/// `metadata` as extension fields under those names, whatever prefix the feed
+/// bound the namespace to. Other feeds with these roots yield their item links.
+///
+/// An item's Atom `<updated>`, else its `<published>` or RSS `<pubDate>`, becomes its
+/// `lastmod`; RSS's RFC 2822 dates are rewritten as W3C datetimes so they sort and
+/// score like sitemap ones.
+pub(crate) fn parse_product_feed(
+    content: &str,
+    base_url: &str,
+    options: &ParseOptions,
+    limits: &XmlLimits,
+    result: &mut SitemapParseResult,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut reader = NsReader::from_str(content);
+    reader.config_mut().trim_text(true);
+    if options.quirks {
+        let config = reader.config_mut();
+        config.check_end_names = false;
+        config.allow_unmatched_ends = true;
+        config.allow_dangling_amp = true;
+    }
+
+    let mut usage = XmlUsage::default();
+    let mut item: Option<Item> = None;
+    // Elements open below the current item; fields are only read from its direct children
+    let mut child_depth = 0;
+    let mut field: Option<Field> = None;
+    let mut field_rejected = false;
+    let mut text = String::new();
+
+    loop {
+        let (namespace, event) = match reader.read_resolved_event() {
+            Ok((namespace, event)) => (is_google(&namespace), event),
+            Err(e) => {
+                warn!("🦀 Product feed parsing stopped early: {}", e);
+                break;
+            }
+        };
+        usage.track(&event, limits)?;
+        match event {
+            Event::Start(e) if item.is_none() && !namespace && is_item(&e) => {
+                item = Some(Item::default());
+                child_depth = 0;
+            }
+            Event::Start(_) if item.is_none() => {}
+            Event::Start(e) => {
+                child_depth += 1;
+                if child_depth == 1 {
+                    field = field_of(&e, namespace);
+                    field_rejected = false;
+                    text.clear();
+                    // Atom puts the link in an attribute, whether or not the element is empty
+                    if let (Some(Field::Link), Some(href)) = (field, atom_href(&e)) {
+                        item.as_mut().unwrap().link.get_or_insert(href);
+                    }
+                }
+            }
+            Event::Empty(e) => {
+                if let (Some(item), 0, false) = (item.as_mut(), child_depth, namespace) {
+                    if e.local_name().as_ref() == b"link" {
+                        if let Some(href) = atom_href(&e) {
+                            item.link.get_or_insert(href);
+                        }
+                    }
+                }
+            }
+            Event::Text(e) if field.is_some() => text.push_str(&String::from_utf8_lossy(&e)),
+            Event::CData(e) if field.is_some() => text.push_str(&String::from_utf8_lossy(&e)),
+            Event::GeneralRef(e) if field.is_some() => {
+                let name = String::from_utf8_lossy(&e);
+                if is_predefined_entity(&name) {
+                    text.push('&');
+                    text.push_str(&name);
+                    text.push(';');
+                } else {
+                    reject_entity(&name, &mut result.security_warnings);
+                    field_rejected = true;
+                }
+            }
+            Event::End(_) if item.is_some() && child_depth > 0 => {
+                if child_depth == 1 {
+                    if let (Some(field), false) = (field.take(), field_rejected) {
+                        item.as_mut().unwrap().set(field, text.trim());
+                    }
+                }
+                child_depth -= 1;
+            }
+            Event::End(_) => {
+                if let Some(done) = item.take() {
+                    add_item(done, base_url, options, result)?;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+impl Item {
+    fn set(&mut self, field: Field, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+        let slot = match field {
+            Field::Link => &mut self.link,
+            Field::GoogleLink => &mut self.google_link,
+            Field::Id => &mut self.id,
+            Field::Price => &mut self.price,
+            Field::SalePrice => &mut self.sale_price,
+            Field::Updated => &mut self.updated,
+            Field::Published => &mut self.published,
+        };
+        // Atom may already have filled the link from an attribute
+        slot.get_or_insert_with(|| value.to_string());
+    }
+}
+
+fn add_item(item: Item, base_url: &str, options: &ParseOptions, result: &mut SitemapParseResult) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(link) = item.google_link.or(item.link) else { return Ok(()) };
+    let link = if options.quirks { quirks::decode_loc(&link) } else { link.as_str().into() };
+    let url = make_absolute_url(&link, base_url)?;
+    if !options.keeps_url(&url) {
+        return Ok(());
+    }
+
+    let mut meta = UrlMetadata { lastmod: item.updated.or(item.published).map(|date| w3c_date(&date)), ..Default::default() };
+    if options.is_unmodified(meta.lastmod.as_deref()) {
+        result.unmodified_urls += 1;
+        return Ok(());
+    }
+    if options.extensions {
+        meta.extensions = [("g:id", item.id), ("g:price", item.price), ("g:sale_price", item.sale_price)]
+            .into_iter()
+            .filter_map(|(key, value)| value.map(|value| (key.to_string(), value)))
+            .collect();
+    }
+    if meta.has_fields() {
+        result.metadata.insert(url.clone(), meta);
+    }
+    result.urls.insert(url);
+    Ok(())
+}
+
+/// An RSS `pubDate` as a W3C datetime; anything else, Atom's dates included, as written
+fn w3c_date(date: &str) -> String {
+    match DateTime::parse_from_rfc2822(date) {
+        Ok(date) => date.to_rfc3339(),
+        Err(_) => date.to_string(),
+    }
+}
+
+fn is_google(namespace: &ResolveResult) -> bool {
+    matches!(namespace, ResolveResult::Bound(Namespace(ns)) if *ns == GOOGLE_NS)
+}
+
+fn is_item(e: &BytesStart) -> bool {
+    matches!(e.local_name().as_ref(), b"item" | b"entry")
+}
+
+fn field_of(e: &BytesStart, google: bool) -> Option<Field> {
+    match (google, e.local_name().as_ref()) {
+        (false, b"link") => Some(Field::Link),
+        (true, b"link") => Some(Field::GoogleLink),
+        (true, b"id") => Some(Field::Id),
+        (true, b"price") => Some(Field::Price),
+        (true, b"sale_price") => Some(Field::SalePrice),
+        (false, b"updated") => Some(Field::Updated),
+        (false, b"pubDate" | b"published") => Some(Field::Published),
+        _ => None,
+    }
+}
+
+/// The `href` of an Atom `<link>` pointing at the item's page (no `rel`, or `rel="alternate"`)
+fn atom_href(e: &BytesStart) -> Option<String> {
+    let mut href = None;
+    for attr in e.attributes().flatten() {
+        match attr.key.as_ref() {
+            b"href" => href = Some(String::from_utf8_lossy(&attr.value).trim().to_string()),
+            b"rel" if attr.value.as_ref() != b"alternate" => return None,
+            _ => {}
+        }
+    }
+    href.filter(|href| !href.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sitemap::{parse_sitemap_xml, parse_sitemap_xml_with_options, ParseOptions};
+
+    const RSS_FEED: &str = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:g="http://base.google.com/ns/1.0">
+  <channel>
+    <title>Example Store</title>
+    <link>https://shop.example.com</link>
+    <item>
+      <g:id>SKU-1</g:id>
+      <title>Mug</title>
+      <link>https://shop.example.com/mug?ref=feed&amp;utm=x</link>
+      <g:price>9.99 USD</g:price>
+      <g:shipping><g:country>US</g:country><g:price>4.99 USD</g:price></g:shipping>
+    </item>
+    <item>
+      <g:id>SKU-2</g:id>
+      <link>https://shop.example.com/fallback</link>
+      <g:link>https://shop.example.com/teapot</g:link>
+      <g:sale_price>19.00 USD</g:sale_price>
+    </item>
+    <item><title>No link at all</title></item>
+  </channel>
+</rss>"#;
+
+    #[test]
+    fn test_rss_product_feed() {
+        let result = parse_sitemap_xml(RSS_FEED, "https://shop.example.com").unwrap();
+        let mut urls: Vec<_> = result.urls.iter().map(String::as_str).collect();
+        urls.sort();
+        assert_eq!(urls, vec!["https://shop.example.com/mug?ref=feed&amp;utm=x", "https://shop.example.com/teapot"]);
+        assert!(result.metadata.is_empty());
+
+        let options = ParseOptions { extensions: true, ..Default::default() };
+        let result = parse_sitemap_xml_with_options(RSS_FEED, "https://shop.example.com", &options).unwrap();
+        let field = |k: &str, v: &str| (k.to_string(), v.to_string());
+        // The shipping price belongs to g:shipping, not the product
+        assert_eq!(result.metadata["https://shop.example.com/mug?ref=feed&amp;utm=x"].extensions, vec![
+            field("g:id", "SKU-1"),
+            field("g:price", "9.99 USD"),
+        ]);
+        assert_eq!(result.metadata["https://shop.example.com/teapot"].extensions, vec![
+            field("g:id", "SKU-2"),
+            field("g:sale_price", "19.00 USD"),
+        ]);
+    }
+
+    #[test]
+    fn test_feed_dates_become_lastmod() {
+        let rss = r#"<rss version="2.0"><channel>
+  <item><link>https://blog.example.com/hello</link><pubDate>Sat, 01 Jun 2024 09:30:00 +0200</pubDate></item>
+  <item><link>https://blog.example.com/undated</link></item>
+</channel></rss>"#;
+        let result = parse_sitemap_xml(rss, "https://blog.example.com/feed").unwrap();
+        assert_eq!(result.urls.len(), 2);
+        assert_eq!(result.metadata.len(), 1);
+        assert_eq!(result.metadata["https://blog.example.com/hello"].lastmod.as_deref(), Some("2024-06-01T09:30:00+02:00"));
+
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+  <updated>2024-06-03T00:00:00Z</updated>
+  <entry><link href="/posts/1"/><published>2024-05-01T00:00:00Z</published><updated>2024-05-02T00:00:00Z</updated></entry>
+  <entry><link href="/posts/2"/><published>2024-04-01T00:00:00Z</published></entry>
+</feed>"#;
+        let result = parse_sitemap_xml(atom, "https://blog.example.com/atom.xml").unwrap();
+        assert_eq!(result.metadata["https://blog.example.com/posts/1"].lastmod.as_deref(), Some("2024-05-02T00:00:00Z"));
+        assert_eq!(result.metadata["https://blog.example.com/posts/2"].lastmod.as_deref(), Some("2024-04-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_atom_product_feed() {
+        let feed = r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:product="http://base.google.com/ns/1.0">
+  <link rel="self" href="https://shop.example.com/feed.xml"/>
+  <entry>
+    <product:id>7</product:id>
+    <link rel="self" href="https://shop.example.com/api/7"/>
+    <link href="/products/7"/>
+    <product:price>3.50 EUR</product:price>
+  </entry>
+</feed>"#;
+
+        let options = ParseOptions { extensions: true, ..Default::default() };
+        let result = parse_sitemap_xml_with_options(feed, "https://shop.example.com/feed.xml", &options).unwrap();
+        assert_eq!(result.urls.len(), 1);
+        let meta = &result.metadata["https://shop.example.com/products/7"];
+        assert_eq!(meta.extensions[1], ("g:price".to_string(), "3.50 EUR".to_string()));
+    }
+}
@@ -0,0 +1,50 @@
+use futures::future::BoxFuture;
+use reqwest::header::HeaderMap;
+use reqwest::{Body, Client, ResponseBuilderExt};
+use url::Url;
+
+/// What a [`Fetcher`] answers with; the body may stream and is read as it arrives
+pub type FetchResponse = http::Response<Body>;
+
+/// Sends the parser's robots.txt and sitemap GETs in place of its own HTTP client, to
+/// route them through other infrastructure: a crawler's middlewares, a headless-browser
+/// pool, a mirror in object storage.
+///
+/// Each call is a single request. Answer a redirect with its 3xx response and the parser
+/// follows it under its redirect policy. Retries, the HTTP cache, request limits, size
+/// limits, and HAR and WARC recording still apply; hedging, proxies, user-agent rotation
+/// and the client's timeout belong to the parser's own client and don't.
+pub trait Fetcher: Send + Sync {
+    /// GET `url`, sending `headers` (the HTTP cache's conditional ones, if any)
+    fn fetch<'a>(&'a self, url: &'a str, headers: &'a HeaderMap) -> BoxFuture<'a, Result<FetchResponse, Box<dyn std::error::Error + Send + Sync>>>;
+}
+
+/// A plain GET through a reqwest client, as a [`Fetcher`] for custom ones to hand the
+/// URLs they don't handle themselves. Build the client with
+/// [`client_builder`](crate::parser::client_builder) so redirects come back to the parser.
+#[derive(Debug, Clone)]
+pub struct ReqwestFetcher {
+    client: Client,
+}
+
+impl ReqwestFetcher {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Fetcher for ReqwestFetcher {
+    fn fetch<'a>(&'a self, url: &'a str, headers: &'a HeaderMap) -> BoxFuture<'a, Result<FetchResponse, Box<dyn std::error::Error + Send + Sync>>> {
+        Box::pin(async move { Ok(self.client.get(url).headers(headers.clone()).send().await?.into()) })
+    }
+}
+
+/// `response` as the parser reads it, answered for `url`
+pub(crate) fn into_response(response: FetchResponse, url: &str) -> Result<reqwest::Response, Box<dyn std::error::Error + Send + Sync>> {
+    let (parts, body) = response.into_parts();
+    let mut builder = http::Response::builder().status(parts.status).version(parts.version).url(Url::parse(url)?);
+    if let Some(headers) = builder.headers_mut() {
+        *headers = parts.headers;
+    }
+    Ok(builder.body(body)?.into())
+}
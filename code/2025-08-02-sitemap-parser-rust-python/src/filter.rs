@@ -0,0 +1,115 @@
+use regex::Regex;
+use url::Url;
+
+/// Prefix marking a pattern as a regular expression rather than a glob
+pub const REGEX_PREFIX: &str = "re:";
+
+/// One include or exclude pattern
+#[derive(Debug, Clone)]
+enum UrlPattern {
+    /// A glob starting with `/`, matched against the URL's path
+    PathGlob(Regex),
+    /// Any other glob, matched against the whole URL
+    UrlGlob(Regex),
+    /// A `re:` pattern, searched for anywhere in the URL
+    Regex(Regex),
+}
+
+impl UrlPattern {
+    fn parse(pattern: &str) -> Result<Self, regex::Error> {
+        if let Some(regex) = pattern.strip_prefix(REGEX_PREFIX) {
+            return Ok(UrlPattern::Regex(Regex::new(regex)?));
+        }
+        let glob = glob_regex(pattern)?;
+        Ok(if pattern.starts_with('/') { UrlPattern::PathGlob(glob) } else { UrlPattern::UrlGlob(glob) })
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        match self {
+            UrlPattern::PathGlob(glob) => Url::parse(url).is_ok_and(|parsed| glob.is_match(parsed.path())),
+            UrlPattern::UrlGlob(glob) | UrlPattern::Regex(glob) => glob.is_match(url),
+        }
+    }
+}
+
+/// `pattern` as an anchored regex: `**` matches anything, `*` anything but `/`, and
+/// `?` one character other than `/`
+fn glob_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                regex.push_str(".*");
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex)
+}
+
+/// Which URLs a parse keeps and which nested sitemaps it follows, checked as each
+/// `<loc>` is read so unwanted URLs never reach the result and pruned sitemaps are
+/// never fetched.
+///
+/// Patterns are globs (`/products/**`, `https://shop.example/*/p/*`) unless prefixed
+/// with `re:`. A URL is kept if it matches any include pattern (or there are none)
+/// and no exclude pattern.
+#[derive(Debug, Clone, Default)]
+pub struct UrlFilter {
+    include: Vec<UrlPattern>,
+    exclude: Vec<UrlPattern>,
+    sitemaps: Vec<UrlPattern>,
+}
+
+impl UrlFilter {
+    /// A filter keeping URLs per `include` and `exclude`, following only nested
+    /// sitemaps that match one of `sitemaps` (all of them if it's empty)
+    pub fn new(include: &[String], exclude: &[String], sitemaps: &[String]) -> Result<Self, regex::Error> {
+        let parse = |patterns: &[String]| patterns.iter().map(|p| UrlPattern::parse(p)).collect::<Result<Vec<_>, _>>();
+        Ok(Self { include: parse(include)?, exclude: parse(exclude)?, sitemaps: parse(sitemaps)? })
+    }
+
+    pub fn keeps_url(&self, url: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(url))) && !self.exclude.iter().any(|p| p.matches(url))
+    }
+
+    pub fn follows_sitemap(&self, sitemap_url: &str) -> bool {
+        self.sitemaps.is_empty() || self.sitemaps.iter().any(|p| p.matches(sitemap_url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_globs_and_regexes() {
+        let filter = UrlFilter::new(
+            &patterns(&["/products/**", "https://blog.example.com/*/"]),
+            &patterns(&["re:[?&]sessionid=", "/products/*/reviews"]),
+            &patterns(&["**/sitemap-products*.xml"]),
+        ).unwrap();
+        assert!(filter.keeps_url("https://example.com/products/shoes/red"));
+        assert!(filter.keeps_url("https://example.com/products/shoes?color=red"));
+        assert!(filter.keeps_url("https://blog.example.com/hello/"));
+        assert!(!filter.keeps_url("https://blog.example.com/2024/hello/"));
+        assert!(!filter.keeps_url("https://example.com/about"));
+        assert!(!filter.keeps_url("https://example.com/products/shoes?sessionid=1"));
+        assert!(!filter.keeps_url("https://example.com/products/shoes/reviews"));
+        assert!(filter.follows_sitemap("https://example.com/maps/sitemap-products-2.xml"));
+        assert!(!filter.follows_sitemap("https://example.com/sitemap-posts.xml"));
+
+        let everything = UrlFilter::default();
+        assert!(everything.keeps_url("https://example.com/about") && everything.follows_sitemap("https://example.com/s.xml"));
+        assert!(UrlFilter::new(&patterns(&["re:("]), &[], &[]).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::parser::USER_AGENT;
+
+/// Response text kept per entry; sitemaps run to tens of megabytes
+pub const HAR_BODY_LIMIT: usize = 64 * 1024;
+
+/// Collects every HTTP exchange of a run for export as a HAR 1.2 file
+#[derive(Debug, Default)]
+pub struct HarRecorder {
+    entries: Mutex<Vec<Value>>,
+}
+
+impl HarRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, exchange: HarExchange) {
+        self.entries.lock().unwrap().push(exchange.into_entry());
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "log": {
+                "version": "1.2",
+                "creator": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                "entries": *self.entries.lock().unwrap(),
+            }
+        })
+    }
+
+    pub fn write_to(&self, path: &Path) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(file, &self.to_json()).map_err(std::io::Error::other)
+    }
+}
+
+/// One request/response pair, filled in as the exchange progresses
+#[derive(Debug)]
+pub struct HarExchange {
+    method: &'static str,
+    url: String,
+    started_at: DateTime<Utc>,
+    started: Instant,
+    waited: Option<Duration>,
+    status: u16,
+    status_text: String,
+    http_version: String,
+    headers: Vec<(String, String)>,
+    body: String,
+    body_size: usize,
+    comment: Option<String>,
+}
+
+impl HarExchange {
+    pub fn start(method: &'static str, url: &str) -> Self {
+        Self {
+            method,
+            url: url.to_string(),
+            started_at: Utc::now(),
+            started: Instant::now(),
+            waited: None,
+            status: 0,
+            status_text: String::new(),
+            http_version: String::new(),
+            headers: Vec::new(),
+            body: String::new(),
+            body_size: 0,
+            comment: None,
+        }
+    }
+
+    /// Note the response line and headers as soon as they arrive
+    pub fn response(&mut self, resp: &reqwest::Response) {
+        self.waited = Some(self.started.elapsed());
+        self.status = resp.status().as_u16();
+        self.status_text = resp.status().canonical_reason().unwrap_or("").to_string();
+        self.http_version = format!("{:?}", resp.version());
+        self.headers = resp
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+            .collect();
+    }
+
+    /// Keep the start of the (decoded) body and the number of bytes received
+    pub fn body(&mut self, text: &str, bytes_read: usize) {
+        let mut end = text.len().min(HAR_BODY_LIMIT);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.body = text[..end].to_string();
+        self.body_size = bytes_read;
+        if end < text.len() {
+            self.note(format!("body truncated to {} of {} bytes", end, text.len()));
+        }
+    }
+
+    /// Attach a remark, e.g. why the exchange failed or stopped early
+    pub fn note(&mut self, comment: impl Into<String>) {
+        let comment = comment.into();
+        self.comment = Some(match self.comment.take() {
+            Some(existing) => format!("{}; {}", existing, comment),
+            None => comment,
+        });
+    }
+
+    fn into_entry(self) -> Value {
+        let total = self.started.elapsed();
+        let wait = self.waited.unwrap_or(total);
+        let mime_type = self
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default();
+        let headers: Vec<Value> = self.headers.iter().map(|(name, value)| json!({ "name": name, "value": value })).collect();
+
+        let mut entry = json!({
+            "startedDateTime": self.started_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "time": millis(total),
+            "request": {
+                "method": self.method,
+                "url": self.url,
+                "httpVersion": self.http_version,
+                "headers": [{ "name": "User-Agent", "value": USER_AGENT }],
+                "queryString": [],
+                "cookies": [],
+                "headersSize": -1,
+                "bodySize": 0,
+            },
+            "response": {
+                "status": self.status,
+                "statusText": self.status_text,
+                "httpVersion": self.http_version,
+                "headers": headers,
+                "cookies": [],
+                "content": { "size": self.body_size, "mimeType": mime_type, "text": self.body },
+                "redirectURL": "",
+                "headersSize": -1,
+                "bodySize": self.body_size,
+            },
+            "cache": {},
+            "timings": { "send": 0, "wait": millis(wait), "receive": millis(total.saturating_sub(wait)) },
+        });
+        if let Some(comment) = self.comment {
+            entry["comment"] = json!(comment);
+        }
+        entry
+    }
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_exchange_entry() {
+        let recorder = HarRecorder::new();
+        let mut exchange = HarExchange::start("GET", "https://example.com/robots.txt");
+        exchange.note("connection refused");
+        recorder.record(exchange);
+
+        let har = recorder.to_json();
+        let entry = &har["log"]["entries"][0];
+        assert_eq!(har["log"]["version"], "1.2");
+        assert_eq!(entry["request"]["url"], "https://example.com/robots.txt");
+        assert_eq!(entry["response"]["status"], 0);
+        assert_eq!(entry["comment"], "connection refused");
+    }
+
+    #[test]
+    fn test_body_is_truncated_on_char_boundary() {
+        let mut exchange = HarExchange::start("GET", "https://example.com/sitemap.xml");
+        let text = "é".repeat(HAR_BODY_LIMIT);
+        exchange.body(&text, text.len());
+
+        assert!(exchange.body.len() <= HAR_BODY_LIMIT);
+        assert_eq!(exchange.body_size, text.len());
+        assert!(exchange.comment.unwrap().starts_with("body truncated"));
+    }
+}
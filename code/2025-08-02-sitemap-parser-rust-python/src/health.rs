@@ -0,0 +1,235 @@
+use reqwest::Client;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::{lookup_host, TcpStream};
+use url::Url;
+
+use crate::parser::USER_AGENT;
+
+/// Environment variables reqwest reads its proxy settings from
+const PROXY_VARS: [&str; 6] = ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Failed,
+    /// Not applicable here, or an earlier check already failed
+    Skipped,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Ok => write!(f, "ok"),
+            CheckStatus::Failed => write!(f, "failed"),
+            CheckStatus::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// Outcome of one diagnostic step
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub elapsed: Duration,
+}
+
+/// Every check run against one target, in the order they ran
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    pub target: String,
+    pub checks: Vec<CheckResult>,
+}
+
+impl HealthReport {
+    pub fn ok(&self) -> bool {
+        self.checks.iter().all(|c| c.status != CheckStatus::Failed)
+    }
+
+    fn record(&mut self, name: &'static str, started: Instant, outcome: Result<String, String>) {
+        let (status, detail) = match outcome {
+            Ok(detail) => (CheckStatus::Ok, detail),
+            Err(detail) => (CheckStatus::Failed, detail),
+        };
+        self.checks.push(CheckResult { name, status, detail, elapsed: started.elapsed() });
+    }
+
+    fn skip(&mut self, name: &'static str, detail: &str) {
+        self.checks.push(CheckResult {
+            name,
+            status: CheckStatus::Skipped,
+            detail: detail.to_string(),
+            elapsed: Duration::ZERO,
+        });
+    }
+}
+
+/// Exercise the network path a parse depends on: proxy, DNS, TCP, TLS, and an HTTP round trip.
+///
+/// Most "it returns zero URLs" reports come down to the environment (no DNS, a
+/// proxy that's down, TLS interception, a WAF blocking the user agent), and each
+/// of those fails a different step here.
+pub async fn health_check(target: &str, timeout: Duration) -> HealthReport {
+    let mut report = HealthReport { target: target.to_string(), checks: Vec::new() };
+
+    let url = match Url::parse(target) {
+        Ok(url) if url.host_str().is_some() => url,
+        _ => {
+            report.record("url", Instant::now(), Err(format!("{} is not an absolute http(s) URL", target)));
+            return report;
+        }
+    };
+    let host = url.host_str().unwrap_or_default().to_string();
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let started = Instant::now();
+    match configured_proxy() {
+        Some((var, proxy)) => {
+            let outcome = check_proxy(&proxy, timeout).await.map(|d| format!("{} from {}: {}", proxy, var, d));
+            report.record("proxy", started, outcome);
+        }
+        None => report.skip("proxy", "no proxy configured"),
+    }
+
+    let started = Instant::now();
+    match resolve(&host, port, timeout).await {
+        Ok(addrs) => {
+            report.record("dns", started, Ok(format!("{} resolved to {:?}", host, addrs)));
+            let started = Instant::now();
+            let outcome = connect(addrs[0], timeout).await.map(|_| format!("connected to {}", addrs[0]));
+            report.record("tcp", started, outcome);
+        }
+        Err(e) => {
+            report.record("dns", started, Err(e));
+            report.skip("tcp", "DNS resolution failed");
+        }
+    }
+
+    let client = match Client::builder().timeout(timeout).user_agent(USER_AGENT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            report.record("http", Instant::now(), Err(format!("could not create HTTP client: {}", e)));
+            return report;
+        }
+    };
+
+    if url.scheme() == "https" {
+        let started = Instant::now();
+        let outcome = match client.head(url.as_str()).send().await {
+            Ok(resp) => Ok(format!("handshake with {} completed ({:?})", host, resp.version())),
+            Err(e) => Err(describe(&e)),
+        };
+        report.record("tls", started, outcome);
+    } else {
+        report.skip("tls", "target is plain http");
+    }
+
+    let started = Instant::now();
+    let robots_url = url.join("/robots.txt").map(String::from).unwrap_or_else(|_| target.to_string());
+    let outcome = match client.get(&robots_url).send().await {
+        Ok(resp) => {
+            let status = resp.status();
+            let bytes = resp.bytes().await.map(|b| b.len()).unwrap_or(0);
+            match status.as_u16() {
+                401 | 403 | 429 => Err(format!("{} returned HTTP {}: the server is refusing this client", robots_url, status.as_u16())),
+                code => Ok(format!("{} returned HTTP {} with {} bytes", robots_url, code, bytes)),
+            }
+        }
+        Err(e) => Err(describe(&e)),
+    };
+    report.record("http", started, outcome);
+
+    report
+}
+
+/// The first proxy environment variable set, as reqwest would pick it up
+fn configured_proxy() -> Option<(&'static str, String)> {
+    PROXY_VARS
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()).map(|v| (*var, v)))
+}
+
+async fn check_proxy(proxy: &str, timeout: Duration) -> Result<String, String> {
+    let url = Url::parse(proxy).map_err(|e| format!("invalid proxy URL {}: {}", proxy, e))?;
+    let host = url.host_str().ok_or_else(|| format!("proxy URL {} has no host", proxy))?;
+    let port = url.port_or_known_default().unwrap_or(8080);
+    let addrs = resolve(host, port, timeout).await?;
+    connect(addrs[0], timeout).await?;
+    Ok(format!("reachable at {}", addrs[0]))
+}
+
+async fn resolve(host: &str, port: u16, timeout: Duration) -> Result<Vec<SocketAddr>, String> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match tokio::time::timeout(timeout, lookup_host((host, port))).await {
+        Ok(Ok(addrs)) => {
+            let addrs: Vec<SocketAddr> = addrs.collect();
+            if addrs.is_empty() {
+                Err(format!("{} has no addresses", host))
+            } else {
+                Ok(addrs)
+            }
+        }
+        Ok(Err(e)) => Err(format!("could not resolve {}: {}", host, e)),
+        Err(_) => Err(format!("resolving {} timed out", host)),
+    }
+}
+
+async fn connect(addr: SocketAddr, timeout: Duration) -> Result<(), String> {
+    match tokio::time::timeout(timeout, TcpStream::connect(addr)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("could not connect to {}: {}", addr, e)),
+        Err(_) => Err(format!("connecting to {} timed out", addr)),
+    }
+}
+
+/// Flatten a reqwest error and its sources into one line, since the useful part
+/// (certificate problem, connection refused) is usually a few levels down
+fn describe(e: &reqwest::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(inner) = source {
+        message.push_str(": ");
+        message.push_str(&inner.to_string());
+        source = inner.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthetic::{SyntheticConfig, SyntheticServer};
+
+    fn status(report: &HealthReport, name: &str) -> CheckStatus {
+        report.checks.iter().find(|c| c.name == name).unwrap().status
+    }
+
+    #[tokio::test]
+    async fn test_health_check_against_local_server() {
+        let server = SyntheticServer::start(&SyntheticConfig::default()).await.unwrap();
+
+        let report = health_check(&server.base_url(), Duration::from_secs(5)).await;
+        assert_eq!(status(&report, "dns"), CheckStatus::Ok);
+        assert_eq!(status(&report, "tcp"), CheckStatus::Ok);
+        assert_eq!(status(&report, "tls"), CheckStatus::Skipped);
+        assert_eq!(status(&report, "http"), CheckStatus::Ok);
+    }
+
+    #[tokio::test]
+    async fn test_health_check_unresolvable_host() {
+        let report = health_check("https://does-not-exist.invalid/", Duration::from_secs(5)).await;
+        assert!(!report.ok());
+        assert_eq!(status(&report, "dns"), CheckStatus::Failed);
+        assert_eq!(status(&report, "tcp"), CheckStatus::Skipped);
+    }
+
+    #[tokio::test]
+    async fn test_health_check_rejects_relative_url() {
+        let report = health_check("example.com", Duration::from_secs(1)).await;
+        assert!(!report.ok());
+        assert_eq!(report.checks.len(), 1);
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// URLs handed to a hook per call unless configured otherwise
+pub const DEFAULT_HOOK_BATCH_SIZE: usize = 1000;
+
+/// Filters or rewrites each sitemap's URLs before they join a result, e.g. to apply
+/// business rules or custom canonicalization.
+///
+/// Hooks may block (the Python one waits for the GIL), so they run on tokio's blocking
+/// pool rather than on the runtime's workers.
+pub trait UrlHook: Send + Sync {
+    /// `urls` is one batch from `sitemap_url`; return the URLs to keep, rewritten as needed
+    fn apply(&self, sitemap_url: &str, urls: Vec<String>) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// Pass `urls` through `hook` in batches of `batch_size`, sorted so batches are stable
+/// across runs; the first failing batch fails the whole sitemap
+pub async fn apply_hook(
+    hook: Arc<dyn UrlHook>,
+    sitemap_url: &str,
+    urls: HashSet<String>,
+    batch_size: usize,
+) -> Result<HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
+    if urls.is_empty() {
+        return Ok(urls);
+    }
+    let sitemap_url = sitemap_url.to_string();
+    tokio::task::spawn_blocking(move || apply_hook_blocking(hook.as_ref(), &sitemap_url, urls, batch_size)).await?
+}
+
+/// [`apply_hook`] on the calling thread, for callers already off the runtime
+pub fn apply_hook_blocking(
+    hook: &dyn UrlHook,
+    sitemap_url: &str,
+    urls: HashSet<String>,
+    batch_size: usize,
+) -> Result<HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut urls: Vec<String> = urls.into_iter().collect();
+    urls.sort_unstable();
+    let mut kept = HashSet::with_capacity(urls.len());
+    let mut rest = urls.into_iter().peekable();
+    while rest.peek().is_some() {
+        let batch: Vec<String> = rest.by_ref().take(batch_size.max(1)).collect();
+        let returned = hook.apply(sitemap_url, batch).map_err(|e| format!("URL hook failed on {}: {}", sitemap_url, e))?;
+        kept.extend(returned);
+    }
+    Ok(kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Drops URLs containing "drop", lowercases the rest, and records batch sizes
+    struct Canonicalize(Mutex<Vec<usize>>);
+
+    impl UrlHook for Canonicalize {
+        fn apply(&self, _sitemap_url: &str, urls: Vec<String>) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+            self.0.lock().unwrap().push(urls.len());
+            if urls.iter().any(|url| url.contains("fail")) {
+                return Err("rejected".into());
+            }
+            Ok(urls.into_iter().filter(|url| !url.contains("drop")).map(|url| url.to_lowercase()).collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hook_runs_in_batches() {
+        let hook = Arc::new(Canonicalize(Mutex::new(Vec::new())));
+        let mut urls: HashSet<String> = (0..5).map(|i| format!("https://a.example/Page{}", i)).collect();
+        urls.insert("https://a.example/drop".to_string());
+
+        let kept = apply_hook(hook.clone(), "https://a.example/sitemap.xml", urls, 2).await.unwrap();
+        assert_eq!(kept.len(), 5);
+        assert!(kept.contains("https://a.example/page3"));
+        assert_eq!(*hook.0.lock().unwrap(), vec![2, 2, 2]);
+
+        let failing = HashSet::from(["https://a.example/fail".to_string()]);
+        let e = apply_hook(hook, "https://a.example/sitemap.xml", failing, 2).await.unwrap_err();
+        assert_eq!(e.to_string(), "URL hook failed on https://a.example/sitemap.xml: rejected");
+    }
+}
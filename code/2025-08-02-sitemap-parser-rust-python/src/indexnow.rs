@@ -0,0 +1,115 @@
+use log::{info, warn};
+use reqwest::Client;
+use serde_json::json;
+use std::collections::BTreeMap;
+use url::Url;
+
+/// Shared endpoint that forwards submissions to every participating search engine
+pub const DEFAULT_ENDPOINT: &str = "https://api.indexnow.org/indexnow";
+
+/// Most URLs the protocol accepts in one request
+const MAX_URLS_PER_REQUEST: usize = 10_000;
+
+/// Outcome of one batch POSTed to one IndexNow endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexNowSubmission {
+    pub endpoint: String,
+    pub host: String,
+    pub urls: usize,
+    /// HTTP status, or `None` if the request never got an answer
+    pub status: Option<u16>,
+    /// 200 or 202; the search engines don't promise to crawl what they accept
+    pub accepted: bool,
+    pub message: String,
+}
+
+/// Submit added or changed URLs to each IndexNow endpoint.
+///
+/// The protocol takes one host per request, so URLs are grouped by host and sent in
+/// batches of up to 10,000. `key` must be served at `key_location`, or at
+/// `https://<host>/<key>.txt` when that is `None`, for a submission to be accepted.
+/// URLs that don't parse are skipped with a warning.
+pub async fn submit_urls(client: &Client, endpoints: &[String], key: &str, key_location: Option<&str>, urls: &[String]) -> Vec<IndexNowSubmission> {
+    let mut submissions = Vec::new();
+    for (host, batch) in batches(urls) {
+        for endpoint in endpoints {
+            let mut body = json!({ "host": host, "key": key, "urlList": batch });
+            if let Some(key_location) = key_location {
+                body["keyLocation"] = json!(key_location);
+            }
+            let status = match client.post(endpoint.as_str()).json(&body).send().await {
+                Ok(resp) => Some(resp.status().as_u16()),
+                Err(e) => {
+                    warn!("🦀 IndexNow submission to {} failed: {}", endpoint, e);
+                    None
+                }
+            };
+            let submission = IndexNowSubmission {
+                endpoint: endpoint.clone(),
+                host: host.clone(),
+                urls: batch.len(),
+                status,
+                accepted: matches!(status, Some(200 | 202)),
+                message: describe_status(status),
+            };
+            info!("🦀 IndexNow {} for {} URLs on {}: {}", endpoint, submission.urls, host, submission.message);
+            submissions.push(submission);
+        }
+    }
+    submissions
+}
+
+/// URLs grouped by host, in host order, and split into request-sized batches
+fn batches(urls: &[String]) -> Vec<(String, Vec<&str>)> {
+    let mut by_host: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for url in urls {
+        match Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string)) {
+            Some(host) => by_host.entry(host).or_default().push(url),
+            None => warn!("🦀 Not submitting {} to IndexNow: it has no host", url),
+        }
+    }
+    by_host
+        .into_iter()
+        .flat_map(|(host, urls)| {
+            urls.chunks(MAX_URLS_PER_REQUEST).map(|chunk| (host.clone(), chunk.to_vec())).collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// What an IndexNow status code means, per the protocol documentation
+fn describe_status(status: Option<u16>) -> String {
+    let description = match status {
+        None => "no response",
+        Some(200) => "submitted",
+        Some(202) => "accepted; key validation pending",
+        Some(400) => "bad request",
+        Some(403) => "key not valid (not found at the key location, or does not match)",
+        Some(422) => "URLs don't belong to the host, or the key doesn't match the protocol schema",
+        Some(429) => "too many requests; back off before resubmitting",
+        Some(status) => return format!("unexpected HTTP {}", status),
+    };
+    description.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batches_group_by_host_and_split() {
+        let mut urls: Vec<String> = (0..10_001).map(|i| format!("https://b.example/{}", i)).collect();
+        urls.push("https://a.example/x".to_string());
+        urls.push("not a url".to_string());
+
+        let batches = batches(&urls);
+        let shape: Vec<(&str, usize)> = batches.iter().map(|(host, urls)| (host.as_str(), urls.len())).collect();
+        assert_eq!(shape, vec![("a.example", 1), ("b.example", 10_000), ("b.example", 1)]);
+    }
+
+    #[test]
+    fn test_describe_status() {
+        assert_eq!(describe_status(Some(202)), "accepted; key validation pending");
+        assert_eq!(describe_status(Some(500)), "unexpected HTTP 500");
+        assert_eq!(describe_status(None), "no response");
+    }
+}
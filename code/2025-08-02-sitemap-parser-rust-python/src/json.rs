@@ -0,0 +1,151 @@
+use serde_json::Value;
+
+use crate::quirks::BOM;
+use crate::sitemap::{make_absolute_url, parse_changefreq, ParseOptions, SitemapParseResult, UrlMetadata};
+
+/// Object keys holding an entry's URL, in order of preference
+const URL_KEYS: &[&str] = &["url", "loc", "href", "link"];
+const LASTMOD_KEYS: &[&str] = &["lastmod", "lastModified", "last_modified", "updatedAt", "updated_at"];
+/// Keys of a top-level object that hold its page entries
+const LIST_KEYS: &[&str] = &["urls", "pages", "items", "entries"];
+
+/// Whether a body is JSON rather than XML; no XML document starts with `[` or `{`
+pub fn is_json_sitemap(content: &str) -> bool {
+    let content = content.trim_start_matches(|c: char| c == BOM || c.is_whitespace());
+    content.starts_with('[') || content.starts_with('{')
+}
+
+/// Parse a JSON "sitemap" as served by headless CMSs into `result`.
+///
+/// Accepts a top-level array of entries, or an object with its entries under
+/// `urls`, `pages`, `items`, or `entries` and nested sitemaps under `sitemaps`.
+/// An entry is either a URL string or an object with the URL in `url`, `loc`,
+/// `href`, or `link`, and optionally `lastmod` (or `lastModified`, `updatedAt`, ...)
+/// `changefreq`, and `priority`. With `options.extensions` an object's other keys are kept as
+/// extension fields, strings as-is and other values as JSON. Relative URLs are
+/// resolved against `base_url`.
+pub(crate) fn parse_json_sitemap(
+    content: &str,
+    base_url: &str,
+    options: &ParseOptions,
+    result: &mut SitemapParseResult,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let content = content.trim_start_matches(|c: char| c == BOM || c.is_whitespace());
+    let document: Value = serde_json::from_str(content).map_err(|e| format!("Invalid JSON sitemap: {}", e))?;
+
+    let (entries, sitemaps) = match &document {
+        Value::Array(entries) => (Some(entries), None),
+        Value::Object(object) => (LIST_KEYS.iter().find_map(|key| object.get(*key)?.as_array()), object.get("sitemaps").and_then(Value::as_array)),
+        _ => return Err("JSON sitemap is neither an array nor an object".into()),
+    };
+
+    for sitemap in sitemaps.into_iter().flatten() {
+        if let Some(url) = entry_url(sitemap) {
+            let url = make_absolute_url(url, base_url)?;
+            if !options.follows_sitemap(&url) {
+                continue;
+            }
+            if options.is_unmodified(entry_lastmod(sitemap)) {
+                result.unmodified_sitemaps += 1;
+                continue;
+            }
+            result.nested_sitemaps.push(url);
+        }
+    }
+    for entry in entries.into_iter().flatten() {
+        let Some(url) = entry_url(entry) else { continue };
+        let url = make_absolute_url(url, base_url)?;
+        if !options.keeps_url(&url) {
+            continue;
+        }
+        if options.is_unmodified(entry_lastmod(entry)) {
+            result.unmodified_urls += 1;
+            continue;
+        }
+        if let Value::Object(fields) = entry {
+            let meta = entry_metadata(fields, options);
+            if meta.has_fields() {
+                result.metadata.insert(url.clone(), meta);
+            }
+        }
+        result.urls.insert(url);
+    }
+    Ok(())
+}
+
+fn entry_lastmod(entry: &Value) -> Option<&str> {
+    LASTMOD_KEYS.iter().find_map(|key| entry.get(*key)?.as_str())
+}
+
+fn entry_url(entry: &Value) -> Option<&str> {
+    let url = match entry {
+        Value::String(url) => url.as_str(),
+        Value::Object(fields) => URL_KEYS.iter().find_map(|key| fields.get(*key)?.as_str())?,
+        _ => return None,
+    };
+    Some(url.trim()).filter(|url| !url.is_empty())
+}
+
+fn entry_metadata(fields: &serde_json::Map<String, Value>, options: &ParseOptions) -> UrlMetadata {
+    let priority = match fields.get("priority") {
+        Some(Value::Number(n)) => n.as_f64(),
+        Some(Value::String(s)) => s.trim().parse().ok(),
+        _ => None,
+    };
+    let mut meta = UrlMetadata {
+        lastmod: LASTMOD_KEYS.iter().find_map(|key| fields.get(*key)?.as_str()).map(|s| s.trim().to_string()),
+        changefreq: fields.get("changefreq").and_then(Value::as_str).and_then(parse_changefreq),
+        priority: priority.filter(|p| (0.0..=1.0).contains(p)),
+        ..Default::default()
+    };
+    if options.extensions {
+        let url_key = URL_KEYS.iter().find(|key| fields.get(**key).is_some_and(Value::is_string));
+        meta.extensions = fields
+            .iter()
+            .filter(|(key, _)| Some(&key.as_str()) != url_key && !LASTMOD_KEYS.contains(&key.as_str()) && !matches!(key.as_str(), "changefreq" | "priority"))
+            .map(|(key, value)| (key.clone(), value.as_str().map_or_else(|| value.to_string(), str::to_string)))
+            .collect();
+    }
+    meta
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sitemap::{parse_sitemap_xml, parse_sitemap_xml_with_options, ParseOptions};
+
+    #[test]
+    fn test_json_array_sitemap() {
+        let json = r#"[
+            {"url": "https://example.com/a", "lastmod": "2024-06-01", "changefreq": "weekly", "priority": 0.8, "title": "A", "tags": ["x"]},
+            {"loc": "/b", "updatedAt": "2024-06-02T10:00:00Z"},
+            "https://example.com/c",
+            {"title": "no url"},
+            42
+        ]"#;
+        let result = parse_sitemap_xml(json, "https://example.com/sitemap.json").unwrap();
+        let mut urls: Vec<_> = result.urls.iter().map(String::as_str).collect();
+        urls.sort();
+        assert_eq!(urls, vec!["https://example.com/a", "https://example.com/b", "https://example.com/c"]);
+        assert_eq!(result.metadata["https://example.com/a"].priority, Some(0.8));
+        assert_eq!(result.metadata["https://example.com/a"].changefreq.as_deref(), Some("weekly"));
+        assert_eq!(result.metadata["https://example.com/b"].lastmod.as_deref(), Some("2024-06-02T10:00:00Z"));
+        assert!(result.metadata["https://example.com/a"].extensions.is_empty());
+
+        let options = ParseOptions { extensions: true, ..Default::default() };
+        let result = parse_sitemap_xml_with_options(json, "https://example.com/sitemap.json", &options).unwrap();
+        assert_eq!(result.metadata["https://example.com/a"].extensions, vec![
+            ("tags".to_string(), r#"["x"]"#.to_string()),
+            ("title".to_string(), "A".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_json_object_sitemap() {
+        let json = "\u{feff}{\"sitemaps\": [{\"url\": \"/sitemap-posts.json\"}, \"https://example.com/sitemap-pages.json\"], \"pages\": [{\"href\": \"https://example.com/about\"}]}";
+        let result = parse_sitemap_xml(json, "https://example.com/sitemap.json").unwrap();
+        assert_eq!(result.nested_sitemaps, vec!["https://example.com/sitemap-posts.json", "https://example.com/sitemap-pages.json"]);
+        assert_eq!(result.urls.len(), 1);
+
+        assert!(parse_sitemap_xml("{\"urls\": [", "https://example.com").unwrap_err().to_string().starts_with("Invalid JSON sitemap"));
+    }
+}
@@ -1,233 +1,146 @@
-use log::{info, warn, error, debug};
-use pyo3::prelude::*;
-use pyo3_async_runtimes::tokio::future_into_py;
-use std::collections::HashSet;
-use std::time::Instant;
-
+//! Concurrent sitemap discovery and parsing: robots.txt, sitemap indexes, urlsets,
+//! feeds, and JSON or text sitemaps, fetched with bounded concurrency.
+//!
+//! [`RustSitemapParser`] is the entry point. Build one with its limits, turn on
+//! options with its `with_*` setters, and call [`RustSitemapParser::parse_site`] or
+//! [`RustSitemapParser::parse_multiple_sites`] from a tokio runtime:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use rust_parser::RustSitemapParser;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//! let parser = RustSitemapParser::new(10, 10, 2, 5, Duration::from_secs(30)).with_url_metadata(true);
+//! let result = parser.parse_site("https://example.com").await?;
+//! println!("{} URLs from {:?}", result.url_count(), result.sitemaps_found);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The parsing itself needs no network: [`parse_robots_txt`] and [`parse_sitemap_body`]
+//! work on text already in hand. The `blocking` feature adds a synchronous
+//! [`blocking::SitemapParser`], and the `python` feature builds the PyO3 extension
+//! module the Python package loads.
+
+mod agents;
+mod anomalies;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod bing;
+mod bodies;
+mod cache;
+mod canonical;
+mod checkpoint;
+mod client;
+mod coalesce;
+mod commoncrawl;
+mod contention;
+mod decode;
+mod degrade;
+mod discovery;
+mod dns;
+mod error;
+#[cfg(feature = "python")]
+mod exceptions;
+mod distributed;
+mod estimate;
+mod export;
+mod feed;
+mod fetcher;
+mod filter;
+mod har;
+mod health;
+mod hooks;
+mod indexnow;
+mod job;
+mod json;
+mod limits;
+mod local;
+mod media;
+mod memory;
+mod monitor;
 mod parser;
+mod plan;
+mod progress;
+mod proxies;
+#[cfg(feature = "python")]
+mod python;
+mod quirks;
+mod redirect;
+mod reputation;
+mod retry;
+mod score;
+mod templates;
+mod warc;
+mod wayback;
 mod robots;
+#[cfg(feature = "search-console")]
+mod search_console;
+mod shutdown;
 mod sitemap;
-
-use parser::RustSitemapParser;
-
-/// Sitemap parsing result returned to Python
-#[pyclass]
-#[derive(Clone, Debug)]
-pub struct SitemapResult {
-    #[pyo3(get)]
-    pub base_url: String,
-    #[pyo3(get)]
-    pub urls: Vec<String>,
-    #[pyo3(get)]
-    pub sitemaps_found: Vec<String>,
-    #[pyo3(get)]
-    pub errors: Vec<String>,
-    #[pyo3(get)]
-    pub parse_time: f64,
-    #[pyo3(get)]
-    pub total_requests: usize,
-}
-
-#[pymethods]
-impl SitemapResult {
-    #[new]
-    fn new(base_url: String) -> Self {
-        Self {
-            base_url,
-            urls: Vec::new(),
-            sitemaps_found: Vec::new(),
-            errors: Vec::new(),
-            parse_time: 0.0,
-            total_requests: 0,
-        }
-    }
-
-    fn __repr__(&self) -> String {
-        format!(
-            "SitemapResult(base_url='{}', urls={}, sitemaps={}, errors={}, time={:.2}s, requests={})",
-            self.base_url,
-            self.urls.len(),
-            self.sitemaps_found.len(),
-            self.errors.len(),
-            self.parse_time,
-            self.total_requests
-        )
-    }
-
-    fn __str__(&self) -> String {
-        self.__repr__()
-    }
-}
-
-/// Rust-powered sitemap parser exposed to Python
-#[pyclass]
-pub struct RustParser {
-    max_concurrent: usize,
-    max_sitemaps: usize,
-    max_depth: usize,
-    max_nested_per_level: usize,
-    timeout_seconds: u64,
-}
-
-#[pymethods]
-impl RustParser {
-    #[new]
-    #[pyo3(signature = (max_concurrent = 10, max_sitemaps = 10, max_depth = 2, max_nested_per_level = 5, timeout_seconds = 30))]
-    fn new(max_concurrent: usize, max_sitemaps: usize, max_depth: usize, max_nested_per_level: usize, timeout_seconds: u64) -> Self {
-        Self {
-            max_concurrent,
-            max_sitemaps,
-            max_depth,
-            max_nested_per_level,
-            timeout_seconds,
-        }
-    }
-
-    /// Parse a single site's sitemaps
-    fn parse_site<'py>(&self, py: Python<'py>, base_url: String) -> PyResult<Bound<'py, PyAny>> {
-        let max_concurrent = self.max_concurrent;
-        let max_sitemaps = self.max_sitemaps;
-        let max_depth = self.max_depth;
-        let max_nested_per_level = self.max_nested_per_level;
-        let timeout = tokio::time::Duration::from_secs(self.timeout_seconds);
-
-        future_into_py(py, async move {
-            let start_time = Instant::now();
-            let mut result = SitemapResult::new(base_url.clone());
-
-            let parser = RustSitemapParser::new(max_concurrent, max_sitemaps, max_depth, max_nested_per_level, timeout);
-            
-            match parser.parse_site(&base_url).await {
-                Ok(parsed_result) => {
-                    result.urls = parsed_result.urls.into_iter().collect();
-                    result.sitemaps_found = parsed_result.sitemaps_found;
-                    result.total_requests = parsed_result.total_requests;
-                    result.errors = parsed_result.errors;
-                }
-                Err(e) => {
-                    result.errors.push(format!("Failed to parse {}: {}", base_url, e));
-                }
-            }
-
-            result.parse_time = start_time.elapsed().as_secs_f64();
-            Ok(result)
-        })
-    }
-
-    /// Parse specific sitemap URLs directly (bypassing robots.txt discovery)
-    fn parse_sitemaps<'py>(&self, py: Python<'py>, sitemap_urls: Vec<String>) -> PyResult<Bound<'py, PyAny>> {
-        let max_concurrent = self.max_concurrent;
-        let max_sitemaps = self.max_sitemaps;
-        let max_depth = self.max_depth;
-        let max_nested_per_level = self.max_nested_per_level;
-        let timeout = tokio::time::Duration::from_secs(self.timeout_seconds);
-
-        future_into_py(py, async move {
-            let parser = RustSitemapParser::new(max_concurrent, max_sitemaps, max_depth, max_nested_per_level, timeout);
-            
-            match parser.parse_specific_sitemaps(sitemap_urls).await {
-                Ok(urls) => {
-                    let url_vec: Vec<String> = urls.into_iter().collect();
-                    info!("🦀 Finished parsing specific sitemaps, found {} URLs", url_vec.len());
-                    Ok(url_vec)
-                }
-                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                    format!("Failed to parse sitemaps: {}", e)
-                ))
-            }
-        })
-    }
-
-    /// Parse multiple sites concurrently
-    fn parse_multiple_sites<'py>(&self, py: Python<'py>, base_urls: Vec<String>) -> PyResult<Bound<'py, PyAny>> {
-        let max_concurrent = self.max_concurrent;
-        let max_sitemaps = self.max_sitemaps;
-        let max_depth = self.max_depth;
-        let max_nested_per_level = self.max_nested_per_level;
-        let timeout = tokio::time::Duration::from_secs(self.timeout_seconds);
-
-        future_into_py(py, async move {
-            let parser = RustSitemapParser::new(max_concurrent, max_sitemaps, max_depth, max_nested_per_level, timeout);
-            
-            match parser.parse_multiple_sites(base_urls).await {
-                Ok(results) => {
-                    let py_results: Vec<SitemapResult> = results
-                        .into_iter()
-                        .map(|r| {
-                            let mut result = SitemapResult::new(r.base_url);
-                            result.urls = r.urls.into_iter().collect();
-                            result.sitemaps_found = r.sitemaps_found;
-                            result.total_requests = r.total_requests;
-                            result.errors = r.errors;
-                            result.parse_time = r.parse_time;
-                            result
-                        })
-                        .collect();
-                    Ok(py_results)
-                }
-                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                    format!("Failed to parse sites: {}", e)
-                ))
-            }
-        })
-    }
-}
-
-/// Synchronous convenience function for parsing multiple sites
-#[pyfunction]
-#[pyo3(signature = (base_urls, max_concurrent = 10, max_sitemaps = 10, max_depth = 2, max_nested_per_level = 5, timeout_seconds = 30))]
-fn parse_sitemaps_rust(
-    base_urls: Vec<String>,
-    max_concurrent: usize,
-    max_sitemaps: usize,
-    max_depth: usize,
-    max_nested_per_level: usize,
-    timeout_seconds: u64,
-) -> PyResult<Vec<SitemapResult>> {
-    info!("🦀 Starting Rust sitemap parsing for {} URLs", base_urls.len());
-    debug!("🦀 Configuration: max_concurrent={}, max_sitemaps={}, max_depth={}, max_nested_per_level={}, timeout={}s", 
-           max_concurrent, max_sitemaps, max_depth, max_nested_per_level, timeout_seconds);
-    
-    let rt = tokio::runtime::Runtime::new().map_err(|e| {
-        error!("🦀 Failed to create Tokio runtime: {}", e);
-        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to create runtime: {}", e))
-    })?;
-
-    let timeout = tokio::time::Duration::from_secs(timeout_seconds);
-    let parser = RustSitemapParser::new(max_concurrent, max_sitemaps, max_depth, max_nested_per_level, timeout);
-
-    rt.block_on(async {
-        match parser.parse_multiple_sites(base_urls).await {
-            Ok(results) => {
-                let py_results: Vec<SitemapResult> = results
-                    .into_iter()
-                    .map(|r| {
-                        let mut result = SitemapResult::new(r.base_url);
-                        result.urls = r.urls.into_iter().collect();
-                        result.sitemaps_found = r.sitemaps_found;
-                        result.total_requests = r.total_requests;
-                        result.errors = r.errors;
-                        result.parse_time = r.parse_time;
-                        result
-                    })
-                    .collect();
-                Ok(py_results)
-            }
-            Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
-                format!("Failed to parse sites: {}", e)
-            ))
-        }
-    })
-}
-
-/// The Rust sitemap parser module
-#[pymodule]
-fn rust_parser(m: &Bound<'_, PyModule>) -> PyResult<()> {
-    // Initialize logging to send Rust logs to Python
-    pyo3_log::init();
-    
-    m.add_class::<SitemapResult>()?;
-    m.add_class::<RustParser>()?;
-    m.add_function(wrap_pyfunction!(parse_sitemaps_rust, m)?)?;
-    Ok(())
-}
+mod snapshot;
+mod stream;
+mod synthetic;
+mod telemetry;
+mod transfer;
+#[cfg(feature = "tui")]
+mod tui;
+mod writer;
+
+pub use agents::{is_challenge, UserAgentPool};
+pub use anomalies::{detect_anomalies, Anomaly, AnomalyKind, AnomalyThresholds};
+pub use bing::BingWebmasterSource;
+pub use bodies::{body_file_name, BodyStore};
+pub use cache::{CachedResponse, DiskCache, HttpCache, MemoryCache};
+pub use canonical::{TrailingSlash, UrlCanonicalizer};
+pub use checkpoint::Checkpoint;
+pub use client::ClientConfig;
+pub use commoncrawl::CommonCrawlSource;
+pub use contention::{ContentionSnapshot, ContentionTracker, DurationStats};
+pub use discovery::{DiscoveryPolicy, DEFAULT_CANDIDATE_PATHS};
+pub use distributed::{run_worker, Coordinator};
+pub use dns::{parse_nameserver, parse_network, DnsPolicy, DnsResolver};
+pub use error::SitemapError;
+pub use estimate::{SampledSitemap, SiteEstimate};
+pub use export::{ExportFormat, ExportSummary};
+#[cfg(feature = "arrow")]
+pub use export::url_record_batch;
+pub use fetcher::{FetchResponse, Fetcher, ReqwestFetcher};
+pub use filter::UrlFilter;
+pub use har::HarRecorder;
+pub use health::{health_check, CheckResult, CheckStatus, HealthReport};
+pub use hooks::{UrlHook, DEFAULT_HOOK_BATCH_SIZE};
+pub use indexnow::{IndexNowSubmission, DEFAULT_ENDPOINT as INDEXNOW_ENDPOINT};
+pub use job::{JobProgress, JobStatus, ParseJob};
+pub use limits::{HostPacer, RequestLimiter};
+pub use local::SitemapFetch;
+pub use media::{ImageEntry, NewsEntry, VideoEntry};
+pub use memory::MemoryUsage;
+pub use monitor::{FinishedSite, MonitorSnapshot, RunMonitor, SiteOutcome};
+pub use parser::{client_builder, ParsedSiteResult, RustSitemapParser, TruncatedSitemap, TruncationReason, DEFAULT_MAX_RESPONSE_BYTES, USER_AGENT};
+pub use plan::{PlannedSitemap, SitePlan, SitemapKind};
+pub use progress::{ProgressEvent, ProgressKind, ProgressListener};
+pub use proxies::{ProxyAssignment, ProxyPool, ProxyStats};
+pub use redirect::{RedirectChain, RedirectHop, RedirectPolicy, MAX_REDIRECTS};
+pub use reputation::{HostReputation, ReputationStore};
+pub use retry::{RetryAttempt, RetryPolicy, RetryRecord, RetryReport};
+pub use robots::{merge_sitemap_directives, parse_robots_txt, RobotsCache, RobotsPolicy, RobotsTxt};
+pub use score::{parse_cutoff, rank_urls, score_url, ScoreWeights, ScoredUrl};
+#[cfg(feature = "search-console")]
+pub use search_console::{SearchConsoleSource, SitemapCountComparison, SubmittedSitemap};
+pub use shutdown::{Shutdown, DEFAULT_GRACE};
+pub use sitemap::{
+    detect_format, parse_sitemap_body, parse_sitemap_xml, parse_sitemap_xml_with, parse_sitemap_xml_with_options, ParseOptions, SitemapFormat,
+    SitemapParseResult, UrlMetadata,
+};
+pub use snapshot::{diff as diff_snapshots, LastmodChange, SitemapSnapshot, SnapshotDiff, SnapshotFormat};
+pub use stream::{UrlSink, DEFAULT_STREAM_BATCH_SIZE};
+pub use synthetic::{SyntheticConfig, SyntheticServer, SyntheticSite};
+pub use telemetry::FetchStats;
+pub use templates::{mine_templates, UrlTemplate};
+pub use transfer::{pack_urls, unpack_urls, UnpackedUrls};
+#[cfg(feature = "tui")]
+pub use tui::Dashboard;
+pub use warc::{WarcArchive, WarcWriter};
+pub use wayback::ArchivedCapture;
+pub use writer::{SitemapWriteSummary, SitemapWriter, MAX_SITEMAP_BYTES, MAX_URLS_PER_SITEMAP};
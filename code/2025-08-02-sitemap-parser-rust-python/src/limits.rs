@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Hosts a `HostPacer` tracks before it drops those whose next slot has passed
+const PACER_PRUNE_AT: usize = 1024;
+
+/// Caps on requests in flight across every site a parser runs: one overall and one
+/// per host, so a site with hundreds of child sitemaps can't open hundreds of
+/// connections, and sites sharing a CDN host share its limit.
+///
+/// Only hosts with requests in flight or waiting have a semaphore, so the table stays
+/// small however many domains a run covers.
+#[derive(Default)]
+pub struct RequestLimiter {
+    global: Option<Arc<Semaphore>>,
+    per_host: Option<usize>,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+/// Held while a request is in flight; frees its slots when dropped
+pub struct RequestPermit<'a> {
+    limiter: &'a RequestLimiter,
+    host: Option<(String, Arc<Semaphore>, OwnedSemaphorePermit)>,
+    _global: Option<OwnedSemaphorePermit>,
+}
+
+impl RequestLimiter {
+    /// `None` leaves that limit off; a limit of 0 is treated as 1
+    pub fn new(max_requests: Option<usize>, max_per_host: Option<usize>) -> Self {
+        Self {
+            global: max_requests.map(|max| Arc::new(Semaphore::new(max.max(1)))),
+            per_host: max_per_host.map(|max| max.max(1)),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait for a slot on `host` and then an overall one. The host comes first so a
+    /// request stuck behind a busy host doesn't hold an overall slot others could use.
+    pub async fn acquire(&self, host: &str) -> RequestPermit<'_> {
+        let host_slot = match self.per_host {
+            Some(limit) => {
+                let semaphore = {
+                    let mut hosts = self.hosts.lock().unwrap();
+                    Arc::clone(hosts.entry(host.to_string()).or_insert_with(|| Arc::new(Semaphore::new(limit))))
+                };
+                // Semaphores here are never closed
+                let permit = Arc::clone(&semaphore).acquire_owned().await.expect("host semaphore closed");
+                Some((host.to_string(), semaphore, permit))
+            }
+            None => None,
+        };
+        let global = match &self.global {
+            Some(semaphore) => Some(Arc::clone(semaphore).acquire_owned().await.expect("request semaphore closed")),
+            None => None,
+        };
+        RequestPermit { limiter: self, host: host_slot, _global: global }
+    }
+}
+
+impl Drop for RequestPermit<'_> {
+    fn drop(&mut self) {
+        let Some((host, semaphore, permit)) = self.host.take() else { return };
+        drop(permit);
+        let mut hosts = self.limiter.hosts.lock().unwrap();
+        // Only the table and this permit still point at it: nobody else is using the host
+        if Arc::strong_count(&semaphore) == 2 {
+            hosts.remove(&host);
+        }
+    }
+}
+
+/// Minimum spacing between requests to the same host, across every site a parser runs,
+/// so a sitemap index with hundreds of children on one origin is fetched at a steady
+/// pace instead of as fast as the connection pool allows.
+///
+/// Each request reserves its host's next free slot and sleeps until it, so waiting
+/// requests go out in the order they asked, one delay apart.
+#[derive(Debug, Default)]
+pub struct HostPacer {
+    delay: Option<Duration>,
+    next: Mutex<HashMap<String, Instant>>,
+}
+
+impl HostPacer {
+    /// `delay` spaces every host's requests; with `None`, only hosts given a fallback
+    /// delay (their Crawl-delay) are paced
+    pub fn new(delay: Option<Duration>) -> Self {
+        Self { delay, next: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn delay(&self) -> Option<Duration> {
+        self.delay
+    }
+
+    /// Wait for `host`'s next slot: this pacer's delay after its last request, or
+    /// `fallback` when the pacer has none
+    pub async fn wait(&self, host: &str, fallback: Option<Duration>) {
+        if let Some(at) = self.reserve(host, fallback, Instant::now()) {
+            tokio::time::sleep_until(at.into()).await;
+        }
+    }
+
+    /// Count a request to `host` that just went out without waiting, so the next one
+    /// waits for it
+    pub fn record(&self, host: &str, fallback: Option<Duration>) {
+        self.reserve(host, fallback, Instant::now());
+    }
+
+    /// Take `host`'s next slot at or after `now`, returning when it starts, or `None`
+    /// if the host isn't paced
+    fn reserve(&self, host: &str, fallback: Option<Duration>, now: Instant) -> Option<Instant> {
+        let delay = self.delay.or(fallback).filter(|delay| !delay.is_zero())?;
+        let mut next = self.next.lock().unwrap();
+        if next.len() >= PACER_PRUNE_AT {
+            next.retain(|_, at| *at > now);
+        }
+        let slot = next.entry(host.to_string()).or_insert(now);
+        let at = (*slot).max(now);
+        *slot = at + delay;
+        Some(at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_limits_per_host_and_overall() {
+        let limiter = RequestLimiter::new(Some(3), Some(2));
+        let a1 = limiter.acquire("a.example").await;
+        let a2 = limiter.acquire("a.example").await;
+
+        let a3 = limiter.acquire("a.example");
+        tokio::pin!(a3);
+        assert!(futures::poll!(a3.as_mut()).is_pending());
+        // The waiting request holds no overall slot, so another host still gets one
+        let b1 = limiter.acquire("b.example").await;
+        let b2 = limiter.acquire("b.example");
+        tokio::pin!(b2);
+        assert!(futures::poll!(b2.as_mut()).is_pending());
+
+        drop(b1);
+        let b2 = b2.await;
+        // The overall limit is full, so a new host waits too, and gets the next free slot
+        let c1 = limiter.acquire("c.example");
+        tokio::pin!(c1);
+        assert!(futures::poll!(c1.as_mut()).is_pending());
+        drop(a1);
+        let c1 = c1.await;
+        assert!(futures::poll!(a3.as_mut()).is_pending());
+        drop(b2);
+        let a3 = a3.await;
+        assert_eq!(limiter.hosts.lock().unwrap().len(), 2);
+
+        drop((a2, a3, c1));
+        assert_eq!(limiter.hosts.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_host_pacer_queues_requests_per_host() {
+        let second = Duration::from_secs(1);
+        let now = Instant::now();
+        let pacer = HostPacer::new(Some(second));
+        assert_eq!(pacer.reserve("a.example", None, now), Some(now));
+        assert_eq!(pacer.reserve("a.example", None, now), Some(now + second));
+        assert_eq!(pacer.reserve("a.example", None, now), Some(now + 2 * second));
+        // Other hosts aren't held up, and the pacer's own delay wins over a fallback
+        assert_eq!(pacer.reserve("b.example", Some(10 * second), now), Some(now));
+        assert_eq!(pacer.reserve("b.example", None, now), Some(now + second));
+        // A slot long past doesn't bank time: the next request goes out now
+        let later = now + 60 * second;
+        assert_eq!(pacer.reserve("a.example", None, later), Some(later));
+
+        // Without a delay of its own, only hosts with a fallback are paced
+        let unpaced = HostPacer::new(None);
+        assert_eq!(unpaced.reserve("a.example", None, now), None);
+        assert_eq!(unpaced.reserve("a.example", Some(second), now), Some(now));
+        assert_eq!(unpaced.reserve("a.example", Some(second), now), Some(now + second));
+        assert_eq!(unpaced.next.lock().unwrap().len(), 1);
+    }
+}
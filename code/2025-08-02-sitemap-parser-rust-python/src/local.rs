@@ -0,0 +1,29 @@
+use std::path::Path;
+
+use url::Url;
+
+/// Supplies nested sitemaps to [`crate::RustSitemapParser::parse_sitemap_bytes`] from
+/// wherever they are stored, such as a directory or an object store, in place of HTTP.
+///
+/// Called from the parsing thread, one sitemap at a time, so it may block.
+pub trait SitemapFetch: Send + Sync {
+    /// The stored body of `url`, raw or gzipped, or `None` if there isn't one
+    fn fetch(&self, url: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+impl<F> SitemapFetch for F
+where
+    F: Fn(&str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>> + Send + Sync,
+{
+    fn fetch(&self, url: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>> {
+        self(url)
+    }
+}
+
+/// The `file://` URL of `path`, which relative sitemap references in the file resolve against
+pub fn file_url(path: &Path) -> std::io::Result<String> {
+    let path = path.canonicalize()?;
+    Url::from_file_path(&path)
+        .map(String::from)
+        .map_err(|()| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{} can't be made a file URL", path.display())))
+}
@@ -0,0 +1,168 @@
+use quick_xml::events::Event;
+use serde::{Deserialize, Serialize};
+
+use crate::sitemap::push_text;
+
+/// One `<image:image>` of a `<url>`, from Google's image sitemap extension
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImageEntry {
+    pub loc: String,
+    pub caption: Option<String>,
+    pub title: Option<String>,
+    pub geo_location: Option<String>,
+    pub license: Option<String>,
+}
+
+/// One `<video:video>` of a `<url>`, from Google's video sitemap extension
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VideoEntry {
+    pub thumbnail_loc: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub content_loc: Option<String>,
+    pub player_loc: Option<String>,
+    /// Length in seconds
+    pub duration: Option<u32>,
+    pub expiration_date: Option<String>,
+    pub publication_date: Option<String>,
+    pub rating: Option<f64>,
+    pub view_count: Option<u64>,
+    pub family_friendly: Option<bool>,
+    pub requires_subscription: Option<bool>,
+    pub live: Option<bool>,
+    pub uploader: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// The `<news:news>` of a `<url>`, from Google's news sitemap extension
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NewsEntry {
+    pub publication_name: Option<String>,
+    pub publication_language: Option<String>,
+    pub publication_date: Option<String>,
+    pub title: Option<String>,
+    /// The deprecated comma-separated `<news:keywords>`, split and trimmed
+    pub keywords: Vec<String>,
+}
+
+/// Image, video, and news entries of one `<url>`, collected from its parse events.
+///
+/// Elements are recognized by local name, whatever prefix the sitemap binds their
+/// namespace to. Text has its references resolved, like `<loc>`.
+/// Unknown children and values that don't parse (a duration that isn't a number, a
+/// flag other than yes/no) are left out rather than failing the sitemap.
+#[derive(Debug, Default)]
+pub(crate) struct MediaCapture {
+    /// Local names of the open elements below `<url>`, starting at `image`, `video`, or `news`
+    path: Vec<Vec<u8>>,
+    text: String,
+    images: Vec<ImageEntry>,
+    videos: Vec<VideoEntry>,
+    news: Option<NewsEntry>,
+}
+
+impl MediaCapture {
+    pub fn observe(&mut self, event: &Event) {
+        match event {
+            Event::Start(e) => {
+                let name = e.local_name().as_ref().to_vec();
+                if self.path.is_empty() {
+                    match name.as_slice() {
+                        b"image" => self.images.push(ImageEntry::default()),
+                        b"video" => self.videos.push(VideoEntry::default()),
+                        b"news" => self.news = Some(NewsEntry::default()),
+                        _ => return,
+                    }
+                }
+                self.text.clear();
+                self.path.push(name);
+            }
+            Event::Text(_) | Event::CData(_) | Event::GeneralRef(_) if !self.path.is_empty() => {
+                // An entity only a DTD could define is left out of the value
+                let _ = push_text(&mut self.text, event);
+            }
+            Event::End(_) if !self.path.is_empty() => {
+                let text = std::mem::take(&mut self.text);
+                let text = text.trim();
+                if !text.is_empty() {
+                    self.record(text);
+                }
+                self.path.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Store the text of the element at the end of `path` in the entry it belongs to
+    fn record(&mut self, text: &str) {
+        let value = || Some(text.to_string());
+        let path: Vec<&[u8]> = self.path.iter().map(Vec::as_slice).collect();
+        match path.as_slice() {
+            [b"image", field] => {
+                let Some(image) = self.images.last_mut() else { return };
+                match *field {
+                    b"loc" => image.loc = text.to_string(),
+                    b"caption" => image.caption = value(),
+                    b"title" => image.title = value(),
+                    b"geo_location" => image.geo_location = value(),
+                    b"license" => image.license = value(),
+                    _ => {}
+                }
+            }
+            [b"video", field] => {
+                let Some(video) = self.videos.last_mut() else { return };
+                match *field {
+                    b"thumbnail_loc" => video.thumbnail_loc = value(),
+                    b"title" => video.title = value(),
+                    b"description" => video.description = value(),
+                    b"content_loc" => video.content_loc = value(),
+                    b"player_loc" => video.player_loc = value(),
+                    b"duration" => video.duration = text.parse().ok(),
+                    b"expiration_date" => video.expiration_date = value(),
+                    b"publication_date" => video.publication_date = value(),
+                    b"rating" => video.rating = text.parse().ok().filter(|r: &f64| (0.0..=5.0).contains(r)),
+                    b"view_count" => video.view_count = text.parse().ok(),
+                    b"family_friendly" => video.family_friendly = parse_yes_no(text),
+                    b"requires_subscription" => video.requires_subscription = parse_yes_no(text),
+                    b"live" => video.live = parse_yes_no(text),
+                    b"uploader" => video.uploader = value(),
+                    b"tag" => video.tags.push(text.to_string()),
+                    _ => {}
+                }
+            }
+            [b"news", b"publication", field] => {
+                let Some(news) = self.news.as_mut() else { return };
+                match *field {
+                    b"name" => news.publication_name = value(),
+                    b"language" => news.publication_language = value(),
+                    _ => {}
+                }
+            }
+            [b"news", field] => {
+                let Some(news) = self.news.as_mut() else { return };
+                match *field {
+                    b"publication_date" => news.publication_date = value(),
+                    b"title" => news.title = value(),
+                    b"keywords" => news.keywords = text.split(',').map(str::trim).filter(|k| !k.is_empty()).map(str::to_string).collect(),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The captured entries, leaving out images without a `<image:loc>`
+    pub fn finish(mut self) -> (Vec<ImageEntry>, Vec<VideoEntry>, Option<NewsEntry>) {
+        self.images.retain(|image| !image.loc.is_empty());
+        (self.images, self.videos, self.news)
+    }
+}
+
+/// A `yes` / `no` flag, case-insensitively
+fn parse_yes_no(text: &str) -> Option<bool> {
+    match text.to_ascii_lowercase().as_str() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Approximate memory attributed to one site's parse
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryUsage {
+    /// Heap held by the site's final URL set
+    pub url_bytes: usize,
+    /// Most response-body bytes (raw plus decoded) held at once while parsing the site
+    pub peak_buffer_bytes: usize,
+    /// Shared-fetch results this site left in the batch's coalescing cache
+    pub cache_bytes: usize,
+}
+
+impl MemoryUsage {
+    /// Upper bound for the site's peak: every category at its own peak at the same time
+    pub fn peak_bytes(&self) -> usize {
+        self.url_bytes + self.peak_buffer_bytes + self.cache_bytes
+    }
+}
+
+/// Counts bytes currently held and the most ever held at once
+#[derive(Debug, Default)]
+pub struct MemoryTracker {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl MemoryTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count `bytes` as held until the returned guard is dropped
+    pub fn hold(&self, bytes: usize) -> MemoryHold<'_> {
+        let current = self.current.fetch_add(bytes, Ordering::SeqCst) + bytes;
+        self.peak.fetch_max(current, Ordering::SeqCst);
+        MemoryHold { tracker: self, bytes }
+    }
+
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+}
+
+/// Bytes counted against a `MemoryTracker`, released on drop
+pub struct MemoryHold<'a> {
+    tracker: &'a MemoryTracker,
+    bytes: usize,
+}
+
+impl Drop for MemoryHold<'_> {
+    fn drop(&mut self) {
+        self.tracker.current.fetch_sub(self.bytes, Ordering::SeqCst);
+    }
+}
+
+/// Approximate heap size of a URL set: string contents plus the table's buckets
+/// (one `String` and one control byte each)
+pub fn url_set_bytes(urls: &HashSet<String>) -> usize {
+    let table = urls.capacity() * (std::mem::size_of::<String>() + 1);
+    table + urls.iter().map(String::capacity).sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker_keeps_peak_after_release() {
+        let tracker = MemoryTracker::new();
+        {
+            let _a = tracker.hold(100);
+            let _b = tracker.hold(50);
+        }
+        let _c = tracker.hold(30);
+
+        assert_eq!(tracker.peak(), 150);
+        assert_eq!(tracker.current.load(Ordering::SeqCst), 30);
+    }
+
+    #[test]
+    fn test_url_set_bytes_grows_with_urls() {
+        let mut urls = HashSet::new();
+        assert_eq!(url_set_bytes(&urls), 0);
+
+        urls.insert("https://example.com/a".to_string());
+        let one = url_set_bytes(&urls);
+        assert!(one >= "https://example.com/a".len() + std::mem::size_of::<String>());
+
+        urls.extend((0..100).map(|i| format!("https://example.com/{}", i)));
+        assert!(url_set_bytes(&urls) > one);
+    }
+}
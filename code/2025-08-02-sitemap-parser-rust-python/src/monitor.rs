@@ -0,0 +1,201 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::parser::ParsedSiteResult;
+
+/// Finished sites remembered for the "recently finished" view
+pub const RECENT_SITES: usize = 200;
+
+/// How a finished site's parse ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiteOutcome {
+    Ok,
+    /// URLs were found, but so were errors
+    Partial,
+    /// No URLs and at least one error, as jobs count failures
+    Failed,
+    Interrupted,
+}
+
+impl SiteOutcome {
+    /// How `result` ended: cut short, clean, with URLs despite errors, or errors only
+    pub fn of(result: &ParsedSiteResult) -> Self {
+        if result.interrupted {
+            SiteOutcome::Interrupted
+        } else if result.errors.is_empty() {
+            SiteOutcome::Ok
+        } else if result.url_count() > 0 {
+            SiteOutcome::Partial
+        } else {
+            SiteOutcome::Failed
+        }
+    }
+}
+
+impl fmt::Display for SiteOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SiteOutcome::Ok => write!(f, "ok"),
+            SiteOutcome::Partial => write!(f, "partial"),
+            SiteOutcome::Failed => write!(f, "failed"),
+            SiteOutcome::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FinishedSite {
+    pub base_url: String,
+    pub outcome: SiteOutcome,
+    pub urls: usize,
+    pub errors: usize,
+    pub elapsed: Duration,
+}
+
+/// Point-in-time view of a parser's runs, for live dashboards
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MonitorSnapshot {
+    /// Time since the monitor was created
+    pub elapsed: Duration,
+    pub finished: usize,
+    pub failed: usize,
+    pub urls: usize,
+    pub errors: usize,
+    /// Sites being parsed and for how long, slowest first
+    pub in_flight: Vec<(String, Duration)>,
+    /// Up to `RECENT_SITES` finished sites, newest first
+    pub recent: Vec<FinishedSite>,
+}
+
+impl MonitorSnapshot {
+    pub fn sites_per_minute(&self) -> f64 {
+        self.finished as f64 * 60.0 / self.elapsed.as_secs_f64().max(1.0)
+    }
+
+    pub fn urls_per_second(&self) -> f64 {
+        self.urls as f64 / self.elapsed.as_secs_f64().max(1.0)
+    }
+}
+
+#[derive(Default)]
+struct MonitorState {
+    next_id: u64,
+    in_flight: HashMap<u64, (String, Instant)>,
+    finished: usize,
+    failed: usize,
+    urls: usize,
+    errors: usize,
+    recent: VecDeque<FinishedSite>,
+}
+
+/// Per-site status of everything one parser runs: what's in flight, what finished, and how.
+///
+/// Only counts and the last `RECENT_SITES` sites are kept, so a run over any number of
+/// domains holds the same small amount.
+pub struct RunMonitor {
+    started: Instant,
+    state: Mutex<MonitorState>,
+}
+
+/// Held while a site is being parsed; dropping it without `finish` (a cancelled parse)
+/// just takes the site off the in-flight list
+pub struct MonitoredSite<'a> {
+    monitor: &'a RunMonitor,
+    id: u64,
+}
+
+impl Default for RunMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RunMonitor {
+    pub fn new() -> Self {
+        Self { started: Instant::now(), state: Mutex::new(MonitorState::default()) }
+    }
+
+    pub fn site(&self, base_url: &str) -> MonitoredSite<'_> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.in_flight.insert(id, (base_url.to_string(), Instant::now()));
+        MonitoredSite { monitor: self, id }
+    }
+
+    pub fn snapshot(&self) -> MonitorSnapshot {
+        let state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let mut in_flight: Vec<(String, Duration)> = state.in_flight.values().map(|(url, started)| (url.clone(), now - *started)).collect();
+        in_flight.sort_by_key(|(_, elapsed)| std::cmp::Reverse(*elapsed));
+        MonitorSnapshot {
+            elapsed: self.started.elapsed(),
+            finished: state.finished,
+            failed: state.failed,
+            urls: state.urls,
+            errors: state.errors,
+            in_flight,
+            recent: state.recent.iter().cloned().collect(),
+        }
+    }
+}
+
+impl MonitoredSite<'_> {
+    /// Record how the site's parse ended; an `Err` counts as failed with one error
+    pub fn finish(self, result: &Result<ParsedSiteResult, Box<dyn std::error::Error + Send + Sync>>) {
+        let mut state = self.monitor.state.lock().unwrap();
+        let Some((base_url, started)) = state.in_flight.remove(&self.id) else { return };
+        let (outcome, urls, errors) = match result {
+            Ok(r) => (SiteOutcome::of(r), r.url_count(), r.errors.len()),
+            Err(_) => (SiteOutcome::Failed, 0, 1),
+        };
+        state.finished += 1;
+        state.failed += usize::from(outcome == SiteOutcome::Failed);
+        state.urls += urls;
+        state.errors += errors;
+        if state.recent.len() == RECENT_SITES {
+            state.recent.pop_back();
+        }
+        state.recent.push_front(FinishedSite { base_url, outcome, urls, errors, elapsed: started.elapsed() });
+    }
+}
+
+impl Drop for MonitoredSite<'_> {
+    fn drop(&mut self) {
+        self.monitor.state.lock().unwrap().in_flight.remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracks_in_flight_and_outcomes() {
+        let monitor = RunMonitor::new();
+        let slow = monitor.site("https://slow.example");
+        std::thread::sleep(Duration::from_millis(10));
+        let ok = monitor.site("https://ok.example");
+        let failed = monitor.site("https://failed.example");
+        let cancelled = monitor.site("https://cancelled.example");
+
+        let snapshot = monitor.snapshot();
+        assert_eq!(snapshot.in_flight.len(), 4);
+        assert_eq!(snapshot.in_flight[0].0, "https://slow.example");
+
+        let mut result = ParsedSiteResult::new("https://ok.example".to_string());
+        result.urls.insert("https://ok.example/a".to_string());
+        ok.finish(&Ok(result));
+        failed.finish(&Err("connection refused".into()));
+        drop(cancelled);
+
+        let snapshot = monitor.snapshot();
+        assert_eq!(snapshot.in_flight.len(), 1);
+        assert_eq!((snapshot.finished, snapshot.failed, snapshot.urls, snapshot.errors), (2, 1, 1, 1));
+        assert_eq!(snapshot.recent[0].outcome, SiteOutcome::Failed);
+        assert_eq!(snapshot.recent[1].outcome, SiteOutcome::Ok);
+        drop(slow);
+    }
+}
@@ -1,23 +1,133 @@
+use chrono::{DateTime, Utc};
 use log::{info, warn, error, debug};
-use reqwest::Client;
-use std::collections::HashSet;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
-use tokio::time::timeout;
 use tokio::sync::Semaphore;
 use url::Url;
 use futures::future::join_all;
+use futures::stream::{BoxStream, Stream, StreamExt};
 
-use crate::robots::parse_robots_txt;
-use crate::sitemap::{parse_sitemap_xml, SitemapParseResult};
+use crate::agents::{is_challenge, UserAgentPool};
+use crate::bodies::BodyStore;
+use crate::cache::{CachedResponse, HttpCache};
+use crate::canonical::UrlCanonicalizer;
+use crate::checkpoint::{Checkpoint, SitemapRecord};
+use crate::bing::BingWebmasterSource;
+use crate::coalesce::SitemapCoalescer;
+use crate::commoncrawl::CommonCrawlSource;
+use crate::contention::ContentionTracker;
+use crate::decode::{decode_body, BodyDecoder};
+use crate::degrade::SiteDegradation;
+use crate::discovery::{sitemap_links, DiscoveryPolicy, Probe};
+use crate::dns::DnsResolver;
+use crate::error::SitemapError;
+use crate::export::{ExportFormat, ExportSummary, UrlExporter};
+use crate::fetcher::{into_response, Fetcher};
+use crate::estimate::{extrapolate, sample_indices, urlsets, SampledSitemap, SiteEstimate};
+use crate::filter::UrlFilter;
+use crate::har::{HarExchange, HarRecorder};
+use crate::hooks::{apply_hook, apply_hook_blocking, UrlHook, DEFAULT_HOOK_BATCH_SIZE};
+use crate::indexnow::{submit_urls, IndexNowSubmission};
+use crate::limits::{HostPacer, RequestLimiter};
+use crate::local::{file_url, SitemapFetch};
+use crate::memory::{url_set_bytes, MemoryHold, MemoryTracker, MemoryUsage};
+use crate::monitor::RunMonitor;
+use crate::progress::{ProgressEvent, ProgressKind, ProgressListener, SiteProgress};
+use crate::proxies::{is_proxy_failure, ProxyPool, MAX_ATTEMPTS, MAX_CONSECUTIVE_FAILURES};
+use crate::plan::{sniff_kind, PlannedSitemap, SitePlan, SitemapKind, PEEK_BYTES};
+use crate::redirect::{RedirectChain, RedirectHop, RedirectPolicy};
+use crate::reputation::{unix_now, HostAdvice, ReputationStore};
+use crate::retry::{HttpStatusError, RetryAttempt, RetryLog, RetryPolicy, RetryRecord};
+use crate::robots::{parse_robots_txt, RobotsCache, RobotsPolicy, RobotsTxt};
+#[cfg(feature = "search-console")]
+use crate::search_console::{SearchConsoleSource, SitemapCountComparison};
+use crate::shutdown::Shutdown;
+use crate::sitemap::{parse_sitemap_body, parse_sitemap_xml_with, ParseOptions, SitemapParseResult, UrlMetadata};
+use crate::stream::UrlSink;
+use crate::telemetry::{timed, ConnectionTimings, FetchLog, FetchStats, TimedConnectLayer, TimedResolver};
+use crate::warc::{WarcArchive, WarcWriter};
+use crate::wayback::{latest_snapshot, ArchivedCapture};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParsedSiteResult {
     pub base_url: String,
     pub urls: HashSet<String>,
+    /// URLs sent to a `UrlSink` instead of kept in `urls`; only with `parse_site_streaming`
+    pub streamed_urls: usize,
+    /// URLs left out because the site's robots.txt disallows them; only with `with_respect_robots`
+    pub disallowed_urls: usize,
+    /// URLs dropped as duplicates of one already found: listed by more than one of the
+    /// site's sitemaps, or canonicalizing to the same URL with `with_canonicalizer`
+    pub duplicate_urls: usize,
+    /// Crawl-delay the site's robots.txt asked of us, which paced its requests
+    pub crawl_delay: Option<Duration>,
     pub sitemaps_found: Vec<String>,
+    /// Sitemaps Bing Webmaster Tools listed for the site; only looked up with `with_bing_webmaster`
+    pub bing_sitemaps: Vec<String>,
+    /// Sitemaps whose fetch was shared with another site in the same batch
+    pub coalesced_sitemaps: Vec<String>,
+    /// Sitemaps that arrived gzipped and were decompressed transparently
+    pub decompressed_sitemaps: Vec<String>,
+    /// Sitemaps that weren't UTF-8, with the encoding each was transcoded from
+    pub transcoded_sitemaps: HashMap<String, String>,
+    /// robots.txt and sitemaps that redirected, by the URL requested
+    pub redirects: HashMap<String, RedirectChain>,
+    /// At least one sitemap was only partially downloaded, or the site ran out of its
+    /// `with_max_urls_per_site` budget
+    pub truncated: bool,
+    pub truncated_sitemaps: Vec<TruncatedSitemap>,
+    /// Limits were stepped down after repeated timeouts from this site
+    pub degraded: bool,
+    pub degradation_level: usize,
+    /// Responses taken from the Wayback Machine because the live fetch failed
+    pub archived_captures: Vec<ArchivedCapture>,
+    /// URLs listed by those archived sitemaps, with the capture time they came from
+    pub archived_urls: HashMap<String, String>,
+    /// lastmod, priority, depth, extension fields, media entries, and hreflang alternates
+    /// per URL; only collected with `with_url_metadata` or one of the options that turn it on
+    pub url_metadata: HashMap<String, UrlMetadata>,
+    /// DTDs and entity references that sitemaps ignored, each prefixed with its sitemap URL
+    pub security_warnings: Vec<String>,
+    /// Decoded body of every sitemap parsed, by sitemap URL; only kept with `with_keep_bodies`
+    pub raw_bodies: HashMap<String, String>,
+    /// User agent from the pool that the site's host last answered; only with `with_user_agents`
+    pub user_agent: Option<String>,
+    /// The run was interrupted while this site was being parsed (or before it started),
+    /// so fetches may have been skipped or cut off
+    pub interrupted: bool,
+    /// The site ran past the parser's `with_site_timeout` deadline; `urls` holds what
+    /// was found by then and the sitemaps still pending are in `errors`
+    pub timed_out: bool,
+    /// The site listed more URLs than `with_max_urls_per_site` allows: `urls` holds that
+    /// many and, unless deterministic, the sitemaps left by then weren't fetched
+    pub url_limit_reached: bool,
+    /// Requests whose first attempt failed and were retried; only with `with_retry_policy`
+    pub retries: Vec<RetryRecord>,
+    /// Status, bytes, and timings of every robots.txt and sitemap fetch made for the site
+    pub fetch_stats: Vec<FetchStats>,
+    /// Responses the HTTP cache served after a 304; only with `with_http_cache`
+    pub cache_hits: usize,
+    /// Responses downloaded in full with the HTTP cache on, because nothing was cached or it had changed
+    pub cache_misses: usize,
+    /// URLs dropped, and child sitemaps not fetched, because their lastmod is before
+    /// the cutoff; only with `with_modified_since`
+    pub unmodified_urls: usize,
+    pub unmodified_sitemaps: usize,
     pub errors: Vec<String>,
+    /// The same failures, typed; see [`ParsedSiteResult::record_error`]
+    pub error_details: Vec<SitemapError>,
     pub total_requests: usize,
     pub parse_time: f64,
+    pub memory: MemoryUsage,
 }
 
 impl ParsedSiteResult {
@@ -25,10 +135,396 @@ impl ParsedSiteResult {
         Self {
             base_url,
             urls: HashSet::new(),
+            streamed_urls: 0,
+            disallowed_urls: 0,
+            duplicate_urls: 0,
+            crawl_delay: None,
             sitemaps_found: Vec::new(),
+            bing_sitemaps: Vec::new(),
+            coalesced_sitemaps: Vec::new(),
+            decompressed_sitemaps: Vec::new(),
+            transcoded_sitemaps: HashMap::new(),
+            redirects: HashMap::new(),
+            truncated: false,
+            truncated_sitemaps: Vec::new(),
+            degraded: false,
+            degradation_level: 0,
+            archived_captures: Vec::new(),
+            archived_urls: HashMap::new(),
+            url_metadata: HashMap::new(),
+            security_warnings: Vec::new(),
+            raw_bodies: HashMap::new(),
+            user_agent: None,
+            interrupted: false,
+            timed_out: false,
+            url_limit_reached: false,
+            retries: Vec::new(),
+            fetch_stats: Vec::new(),
+            cache_hits: 0,
+            cache_misses: 0,
+            unmodified_urls: 0,
+            unmodified_sitemaps: 0,
             errors: Vec::new(),
+            error_details: Vec::new(),
             total_requests: 0,
             parse_time: 0.0,
+            memory: MemoryUsage::default(),
+        }
+    }
+
+    /// A site the run was interrupted before starting
+    pub fn interrupted(base_url: String) -> Self {
+        let mut result = Self::new(base_url);
+        let message = format!("Not parsed {}: the run was interrupted first", result.base_url);
+        result.record_error(message.clone(), SitemapError::Interrupted { url: result.base_url.clone(), message });
+        result.interrupted = true;
+        result
+    }
+
+    /// A site whose parse failed outright with `e`
+    pub fn failed(base_url: String, e: &(dyn std::error::Error + Send + Sync + 'static)) -> Self {
+        let mut result = Self::new(base_url);
+        let error = SitemapError::from_fetch(&result.base_url, e);
+        result.record_error(format!("Failed to parse {}: {}", result.base_url, e), error);
+        result
+    }
+
+    /// Record a failure: `message` in `errors` and `error` in `error_details`
+    pub fn record_error(&mut self, message: String, error: SitemapError) {
+        self.errors.push(message);
+        self.error_details.push(error);
+    }
+
+    /// URLs found, whether kept in `urls` or streamed
+    pub fn url_count(&self) -> usize {
+        self.urls.len() + self.streamed_urls
+    }
+
+    /// Body bytes downloaded across the site's fetches
+    pub fn bytes_downloaded(&self) -> usize {
+        self.fetch_stats.iter().map(|stats| stats.bytes).sum()
+    }
+
+    /// URLs as a list, sorted when a stable order is wanted
+    pub fn url_list(&self, sorted: bool) -> Vec<String> {
+        let mut urls: Vec<String> = self.urls.iter().cloned().collect();
+        if sorted {
+            urls.sort_unstable();
+        }
+        urls
+    }
+
+    /// Keep the first `max_urls` URLs in sorted order, along with their metadata;
+    /// whether any had to go
+    fn keep_first_urls(&mut self, max_urls: usize) -> bool {
+        if self.urls.len() <= max_urls {
+            return false;
+        }
+        let mut urls: Vec<String> = self.urls.drain().collect();
+        urls.sort_unstable();
+        urls.truncate(max_urls);
+        self.urls = urls.into_iter().collect();
+        self.url_metadata.retain(|url, _| self.urls.contains(url));
+        self.archived_urls.retain(|url, _| self.urls.contains(url));
+        true
+    }
+
+    /// Sort every list whose order depends on async completion order
+    pub fn sort_lists(&mut self) {
+        self.sitemaps_found.sort();
+        self.sitemaps_found.dedup();
+        self.bing_sitemaps.sort();
+        self.bing_sitemaps.dedup();
+        self.coalesced_sitemaps.sort();
+        self.decompressed_sitemaps.sort();
+        self.truncated_sitemaps.sort_by(|a, b| a.url.cmp(&b.url));
+        self.archived_captures.sort_by(|a, b| a.url.cmp(&b.url));
+        self.security_warnings.sort();
+        self.retries.sort_by(|a, b| a.url.cmp(&b.url));
+        self.fetch_stats.sort_by(|a, b| a.url.cmp(&b.url));
+        self.errors.sort();
+        self.error_details.sort_by_cached_key(ToString::to_string);
+    }
+}
+
+/// Why a response body was cut short
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TruncationReason {
+    /// The body (or its decompressed form) exceeded `max_response_bytes`
+    SizeLimit,
+    /// The connection dropped part-way through the body
+    Interrupted,
+}
+
+impl fmt::Display for TruncationReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TruncationReason::SizeLimit => write!(f, "size_limit"),
+            TruncationReason::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+/// A sitemap whose URLs were kept even though its body was only partially downloaded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TruncatedSitemap {
+    pub url: String,
+    pub bytes_read: usize,
+    /// Content-Length advertised by the server, if any
+    pub content_length: Option<u64>,
+    pub reason: TruncationReason,
+}
+
+/// Per-site state shared by every fetch made on behalf of one site
+struct SiteContext {
+    degradation: SiteDegradation,
+    /// Response bodies currently held for this site
+    buffers: MemoryTracker,
+    /// Size of the shared-fetch results this site started
+    cache_bytes: AtomicUsize,
+    retries: RetryLog,
+    fetches: FetchLog,
+    /// Set for streamed parses, which send URLs here as each sitemap is parsed
+    sink: Option<UrlSink>,
+    /// The site's robots.txt rules for us; only set with `with_respect_robots`
+    robots: OnceLock<RobotsPolicy>,
+    /// URLs dropped because robots.txt disallows them
+    disallowed: AtomicUsize,
+    /// The site's base URL as given, for progress events and checkpoint records
+    site: String,
+    progress: Arc<SiteProgress>,
+    /// Where fetched sitemaps are recorded and replayed from; not set for streamed parses
+    checkpoint: Option<Arc<Checkpoint>>,
+    /// When the site has to be done by; only with `with_site_timeout`
+    deadline: Option<Instant>,
+    /// A fetch was given up on because the deadline passed
+    deadline_hit: AtomicBool,
+    /// URLs the site may still take; only with `with_max_urls_per_site`
+    url_budget: Option<AtomicUsize>,
+    /// URLs were cut from a sitemap, or a sitemap skipped, because the budget ran out
+    url_budget_hit: AtomicBool,
+    /// Sitemap fetches shared with the other sites of a batch; only set for batches
+    coalescer: Option<Arc<SitemapCoalescer<SharedFetch>>>,
+}
+
+impl SiteContext {
+    fn new(parser: &RustSitemapParser) -> Self {
+        Self::with_cap(parser, None)
+    }
+
+    /// A site whose in-flight requests start out limited to `cap`
+    fn with_cap(parser: &RustSitemapParser, cap: Option<usize>) -> Self {
+        let slots = parser.max_sitemaps.max(parser.max_nested_per_level);
+        Self {
+            degradation: cap.map_or_else(|| SiteDegradation::new(slots), |cap| SiteDegradation::capped(slots, cap)),
+            buffers: MemoryTracker::new(),
+            cache_bytes: AtomicUsize::new(0),
+            retries: RetryLog::default(),
+            fetches: FetchLog::default(),
+            sink: None,
+            robots: OnceLock::new(),
+            disallowed: AtomicUsize::new(0),
+            site: String::new(),
+            progress: Arc::default(),
+            checkpoint: None,
+            deadline: None,
+            deadline_hit: AtomicBool::new(false),
+            url_budget: parser.max_urls_per_site.map(AtomicUsize::new),
+            url_budget_hit: AtomicBool::new(false),
+            coalescer: None,
+        }
+    }
+
+    /// Wait until `pacer` allows another request to `url`'s host, falling back to the
+    /// site's Crawl-delay on the host its robots.txt speaks for
+    async fn pace(&self, pacer: &HostPacer, url: &str) {
+        let Ok(url) = Url::parse(url) else { return };
+        let Some(host) = url.host_str() else { return };
+        let crawl_delay = self.robots.get().filter(|robots| robots.applies_to(&url)).and_then(|robots| robots.crawl_delay);
+        pacer.wait(host, crawl_delay).await;
+    }
+
+    /// Run the fetch of `url`, unless the site's deadline has passed or passes first
+    async fn before_deadline<T>(
+        &self,
+        url: &str,
+        fetch: impl Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync>>>,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(deadline) = self.deadline else { return fetch.await };
+        let missed = || {
+            self.deadline_hit.store(true, Ordering::SeqCst);
+            SitemapError::Timeout { url: url.to_string(), message: format!("Site deadline passed before {} was fetched", url) }.into()
+        };
+        if Instant::now() >= deadline {
+            return Err(missed());
+        }
+        tokio::select! {
+            fetched = fetch => fetched,
+            _ = tokio::time::sleep_until(deadline.into()) => Err(missed()),
+        }
+    }
+
+    /// Whether the site's URL budget is spent, so no more sitemaps should be fetched
+    fn out_of_urls(&self) -> bool {
+        let spent = self.url_budget.as_ref().is_some_and(|budget| budget.load(Ordering::SeqCst) == 0);
+        if spent {
+            self.url_budget_hit.store(true, Ordering::SeqCst);
+        }
+        spent
+    }
+
+    /// Take `urls` out of the site's URL budget, dropping those it has no room for
+    fn take_urls(&self, urls: &mut HashSet<String>) {
+        let Some(budget) = &self.url_budget else { return };
+        let wanted = urls.len();
+        let left = budget.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| Some(left.saturating_sub(wanted))).unwrap_or_default();
+        if left < wanted {
+            self.url_budget_hit.store(true, Ordering::SeqCst);
+            // Keep the same ones from run to run
+            let mut kept: Vec<String> = urls.drain().collect();
+            kept.sort_unstable();
+            kept.truncate(left);
+            urls.extend(kept);
+        }
+    }
+}
+
+/// Whether a robots.txt that failed with `error` leaves the site open to crawling:
+/// RFC 9309's "unavailable", any 4xx but 429
+fn allows_everything(error: &SitemapError) -> bool {
+    matches!(error, SitemapError::Http { status, .. } if (400..500).contains(status) && *status != 429)
+}
+
+fn is_timeout(e: &(dyn std::error::Error + Send + Sync + 'static)) -> bool {
+    e.downcast_ref::<reqwest::Error>().is_some_and(|e| e.is_timeout())
+}
+
+/// A fetched response body decoded to text
+struct FetchedBody {
+    text: String,
+    decompressed: bool,
+    bytes_read: usize,
+    content_length: Option<u64>,
+    /// `Content-Type` header
+    content_type: Option<String>,
+    /// Encoding the body was transcoded from, when it wasn't UTF-8
+    encoding: Option<&'static str>,
+    truncation: Option<TruncationReason>,
+    /// How the HTTP cache answered, if there is one
+    cache: Option<CacheUse>,
+    /// Set if the request was redirected
+    redirect: Option<RedirectChain>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CacheUse {
+    /// 304 Not Modified; the body came from the cache
+    Hit,
+    Miss,
+}
+
+/// A sitemap fetch as shared by the sites of a batch that list the same sitemap
+type SharedFetch = Result<(Arc<FetchedBody>, Option<ArchivedCapture>), SitemapError>;
+
+impl FetchedBody {
+    /// Requests the redirects before the final response took
+    fn redirect_requests(&self) -> usize {
+        self.redirect.as_ref().map_or(0, |redirect| redirect.hops.len())
+    }
+
+    /// Add this fetch's cache use to `hits` and `misses`
+    fn count_cache(&self, hits: &mut usize, misses: &mut usize) {
+        match self.cache {
+            Some(CacheUse::Hit) => *hits += 1,
+            Some(CacheUse::Miss) => *misses += 1,
+            None => {}
+        }
+    }
+}
+
+/// A response taken from a WARC input or Common Crawl, cut to `max_response_bytes`
+struct ArchivedBody {
+    bytes: Vec<u8>,
+    /// Length of the whole stored payload
+    content_length: u64,
+    content_type: Option<String>,
+    truncated: bool,
+}
+
+/// The start of a sitemap body, read just far enough to tell what kind it is
+struct PeekedSitemap {
+    kind: SitemapKind,
+    content_length: Option<u64>,
+    bytes_read: usize,
+    /// Full decoded body, only kept for indexes
+    text: Option<String>,
+}
+
+/// URLs and bookkeeping gathered from one sitemap and everything nested under it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SitemapTreeResult {
+    pub urls: HashSet<String>,
+    pub requests: usize,
+    pub duplicate_urls: usize,
+    /// Sitemaps in the tree that arrived gzipped and were decompressed transparently
+    pub decompressed_sitemaps: Vec<String>,
+    pub transcoded_sitemaps: HashMap<String, String>,
+    pub redirects: HashMap<String, RedirectChain>,
+    pub truncated_sitemaps: Vec<TruncatedSitemap>,
+    pub archived_captures: Vec<ArchivedCapture>,
+    pub archived_urls: HashMap<String, String>,
+    pub url_metadata: HashMap<String, UrlMetadata>,
+    pub security_warnings: Vec<String>,
+    pub raw_bodies: HashMap<String, String>,
+    pub cache_hits: usize,
+    pub cache_misses: usize,
+    pub unmodified_urls: usize,
+    pub unmodified_sitemaps: usize,
+    /// Sitemaps whose fetch was shared with another site in the batch
+    pub coalesced_sitemaps: Vec<String>,
+}
+
+impl SitemapTreeResult {
+    fn merge(&mut self, other: SitemapTreeResult) {
+        self.duplicate_urls += other.duplicate_urls + extend_urls(&mut self.urls, other.urls);
+        self.requests += other.requests;
+        self.cache_hits += other.cache_hits;
+        self.cache_misses += other.cache_misses;
+        self.unmodified_urls += other.unmodified_urls;
+        self.unmodified_sitemaps += other.unmodified_sitemaps;
+        self.decompressed_sitemaps.extend(other.decompressed_sitemaps);
+        self.transcoded_sitemaps.extend(other.transcoded_sitemaps);
+        self.redirects.extend(other.redirects);
+        self.truncated_sitemaps.extend(other.truncated_sitemaps);
+        self.archived_captures.extend(other.archived_captures);
+        self.archived_urls.extend(other.archived_urls);
+        merge_url_metadata(&mut self.url_metadata, other.url_metadata);
+        self.security_warnings.extend(other.security_warnings);
+        self.raw_bodies.extend(other.raw_bodies);
+        self.coalesced_sitemaps.extend(other.coalesced_sitemaps);
+    }
+}
+
+/// Add `urls` to `into`, returning how many were already there
+fn extend_urls(into: &mut HashSet<String>, urls: HashSet<String>) -> usize {
+    let listed = urls.len();
+    let before = into.len();
+    into.extend(urls);
+    listed - (into.len() - before)
+}
+
+/// Add `other` to `into`; a URL listed at several depths keeps its shallowest entry
+fn merge_url_metadata(into: &mut HashMap<String, UrlMetadata>, other: HashMap<String, UrlMetadata>) {
+    for (url, meta) in other {
+        match into.entry(url) {
+            Entry::Occupied(mut existing) if meta.depth < existing.get().depth => {
+                existing.insert(meta);
+            }
+            Entry::Occupied(_) => {}
+            Entry::Vacant(slot) => {
+                slot.insert(meta);
+            }
         }
     }
 }
@@ -40,31 +536,487 @@ pub struct RustSitemapParser {
     max_sitemaps: usize,
     max_depth: usize,
     max_nested_per_level: usize,
-    request_timeout: Duration,
+    max_response_bytes: usize,
+    retry_policy: RetryPolicy,
+    redirect_policy: RedirectPolicy,
+    hedge_delay: Option<Duration>,
+    prewarm_hosts: usize,
+    deterministic: bool,
+    quirks: bool,
+    wayback_fallback: bool,
+    url_metadata: bool,
+    url_extensions: bool,
+    url_media: bool,
+    url_alternates: bool,
+    respect_robots: bool,
+    keep_bodies: bool,
+    body_store: Option<Arc<BodyStore>>,
+    http_cache: Option<Arc<dyn HttpCache>>,
+    har: Option<Arc<HarRecorder>>,
+    archive: Option<Arc<WarcArchive>>,
+    common_crawl: Option<Arc<CommonCrawlSource>>,
+    bing: Option<Arc<BingWebmasterSource>>,
+    reputation: Option<Arc<ReputationStore>>,
+    user_agents: Option<Arc<UserAgentPool>>,
+    proxies: Option<Arc<ProxyPool>>,
+    dns: Option<DnsResolver>,
+    url_hook: Option<Arc<dyn UrlHook>>,
+    url_hook_batch_size: usize,
+    url_filter: Option<Arc<UrlFilter>>,
+    canonicalizer: Option<Arc<UrlCanonicalizer>>,
+    modified_since: Option<DateTime<Utc>>,
+    warc: Option<Arc<WarcWriter>>,
+    request_limits: Arc<RequestLimiter>,
+    host_pacer: Arc<HostPacer>,
+    fetcher: Option<Arc<dyn Fetcher>>,
+    discovery: DiscoveryPolicy,
+    robots_cache: Arc<RobotsCache>,
+    contention: Arc<ContentionTracker>,
+    monitor: Arc<RunMonitor>,
+    progress: Option<Arc<dyn ProgressListener>>,
+    shutdown: Arc<Shutdown>,
+    checkpoint: Option<Arc<Checkpoint>>,
+    site_timeout: Option<Duration>,
+    max_urls_per_site: Option<usize>,
+    /// What the client sends as its user agent, for matching robots.txt groups
+    user_agent: String,
+}
+
+/// User agent sent with every request, matching the Python parser exactly
+pub const USER_AGENT: &str = "SitemapParser/1.0 (+https://timwhite.ninja)";
+
+/// Default cap on a single response body: the sitemap protocol's 50MB limit
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 50 * 1024 * 1024;
+
+/// HTTP client settings shared by the parser's own client and each proxy's
+pub fn client_builder(timeout: Duration) -> reqwest::ClientBuilder {
+    Client::builder()
+        .timeout(timeout)
+        .user_agent(USER_AGENT)
+        .pool_max_idle_per_host(10) // Enable connection pooling
+        .pool_idle_timeout(Duration::from_secs(30))
+        .tcp_keepalive(Duration::from_secs(30))
+        // The parser follows redirects itself, to apply its redirect policy and record them
+        .redirect(reqwest::redirect::Policy::none())
+        // Time DNS lookups and new connections for each fetch's stats
+        .dns_resolver(Arc::new(TimedResolver))
+        .connector_layer(TimedConnectLayer)
 }
 
 impl RustSitemapParser {
     pub fn new(max_concurrent: usize, max_sitemaps: usize, max_depth: usize, max_nested_per_level: usize, timeout: Duration) -> Self {
-        let client = Client::builder()
-            .timeout(timeout)
-            .user_agent("SitemapParser/1.0 (+https://timwhite.ninja)") // Match Python user agent exactly
-            .pool_max_idle_per_host(10) // Enable connection pooling
-            .pool_idle_timeout(Duration::from_secs(30))
-            .tcp_keepalive(Duration::from_secs(30))
+        let client = client_builder(timeout)
             .build()
             .expect("Failed to create HTTP client");
+        Self::from_client(client, max_concurrent, max_sitemaps, max_depth, max_nested_per_level)
+    }
 
+    /// Like `new`, but sending requests through `client`, e.g. one built with
+    /// [`client_builder`] and shared by many parsers so its connection pool and resolved
+    /// hosts stay warm between them. The client's timeout applies.
+    pub fn from_client(client: Client, max_concurrent: usize, max_sitemaps: usize, max_depth: usize, max_nested_per_level: usize) -> Self {
         Self {
             client,
             max_concurrent,
             max_sitemaps,
             max_depth,
             max_nested_per_level,
-            request_timeout: timeout,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            retry_policy: RetryPolicy::default(),
+            redirect_policy: RedirectPolicy::default(),
+            hedge_delay: None,
+            prewarm_hosts: 0,
+            deterministic: false,
+            quirks: false,
+            wayback_fallback: false,
+            url_metadata: false,
+            url_extensions: false,
+            url_media: false,
+            url_alternates: false,
+            respect_robots: false,
+            keep_bodies: false,
+            body_store: None,
+            http_cache: None,
+            har: None,
+            archive: None,
+            common_crawl: None,
+            bing: None,
+            reputation: None,
+            user_agents: None,
+            proxies: None,
+            dns: None,
+            url_hook: None,
+            url_hook_batch_size: DEFAULT_HOOK_BATCH_SIZE,
+            url_filter: None,
+            canonicalizer: None,
+            modified_since: None,
+            warc: None,
+            request_limits: Arc::new(RequestLimiter::default()),
+            host_pacer: Arc::new(HostPacer::default()),
+            fetcher: None,
+            discovery: DiscoveryPolicy::default(),
+            robots_cache: Arc::new(RobotsCache::default()),
+            contention: Arc::new(ContentionTracker::new()),
+            monitor: Arc::new(RunMonitor::new()),
+            progress: None,
+            shutdown: Arc::new(Shutdown::new()),
+            checkpoint: None,
+            site_timeout: None,
+            max_urls_per_site: None,
+            user_agent: USER_AGENT.to_string(),
+        }
+    }
+
+    /// Cap the bytes read from any single response; larger sitemaps keep the URLs parsed so far
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = max_response_bytes;
+        self
+    }
+
+    /// Send a duplicate request when the first hasn't answered within `hedge_delay` (e.g. your p95 latency)
+    pub fn with_hedge_delay(mut self, hedge_delay: Option<Duration>) -> Self {
+        self.hedge_delay = hedge_delay;
+        self
+    }
+
+    /// Open connections to the first `prewarm_hosts` hosts of a batch before their sites are processed
+    pub fn with_prewarm_hosts(mut self, prewarm_hosts: usize) -> Self {
+        self.prewarm_hosts = prewarm_hosts;
+        self
+    }
+
+    /// Produce stably ordered results: sorted lists, and no cross-site coalescing
+    /// (which site ends up owning a shared fetch depends on timing)
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Tolerate common sitemap breakage (stray BOMs, HTML wrappers, unclosed tags, double-encoded entities)
+    pub fn with_quirks(mut self, quirks: bool) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// When robots.txt or a sitemap can't be fetched live, parse its latest Wayback Machine capture instead
+    pub fn with_wayback_fallback(mut self, wayback_fallback: bool) -> Self {
+        self.wayback_fallback = wayback_fallback;
+        self
+    }
+
+    /// Keep each URL's lastmod, priority, and depth (for scoring); off by default as it roughly doubles memory
+    pub fn with_url_metadata(mut self, url_metadata: bool) -> Self {
+        self.url_metadata = url_metadata;
+        self
+    }
+
+    /// Also keep unrecognized `<url>` children (vendor extensions) as raw key/value pairs in
+    /// each URL's metadata; turns on metadata collection
+    pub fn with_url_extensions(mut self, url_extensions: bool) -> Self {
+        self.url_extensions = url_extensions;
+        self
+    }
+
+    /// Also collect Google image, video, and news sitemap entries into each URL's
+    /// metadata; turns on metadata collection
+    pub fn with_url_media(mut self, url_media: bool) -> Self {
+        self.url_media = url_media;
+        self
+    }
+
+    /// Also collect each URL's `<xhtml:link rel="alternate">` language variants as
+    /// `(hreflang, href)` pairs; turns on metadata collection
+    pub fn with_url_alternates(mut self, url_alternates: bool) -> Self {
+        self.url_alternates = url_alternates;
+        self
+    }
+
+    /// Leave out URLs the site's robots.txt disallows for our user agent, and space
+    /// requests to its host by its Crawl-delay (up to `MAX_CRAWL_DELAY`) unless the
+    /// host pacer has a delay of its own
+    pub fn with_respect_robots(mut self, respect_robots: bool) -> Self {
+        self.respect_robots = respect_robots;
+        self
+    }
+
+    /// Return each sitemap's decoded body in `raw_bodies`, for re-analyzing exactly what was parsed
+    pub fn with_keep_bodies(mut self, keep_bodies: bool) -> Self {
+        self.keep_bodies = keep_bodies;
+        self
+    }
+
+    /// Revalidate responses kept in `cache` with `If-None-Match` / `If-Modified-Since`,
+    /// reusing the cached body on a 304, and cache every full response that has an ETag
+    /// or Last-Modified
+    pub fn with_http_cache(mut self, cache: Option<Arc<dyn HttpCache>>) -> Self {
+        self.http_cache = cache;
+        self
+    }
+
+    /// Write each sitemap's decoded body into `body_store`, including bodies that fail to parse
+    pub fn with_body_store(mut self, body_store: Option<Arc<BodyStore>>) -> Self {
+        self.body_store = body_store;
+        self
+    }
+
+    /// Record every HTTP exchange into `har` for export as a HAR file
+    pub fn with_har(mut self, har: Option<Arc<HarRecorder>>) -> Self {
+        self.har = har;
+        self
+    }
+
+    /// Write the recorded HAR to `path`; a no-op unless recording was enabled
+    pub fn write_har(&self, path: &std::path::Path) -> std::io::Result<()> {
+        match &self.har {
+            Some(har) => har.write_to(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Serve every response from previously captured WARC records instead of the network
+    pub fn with_archive(mut self, archive: Option<Arc<WarcArchive>>) -> Self {
+        self.archive = archive;
+        self
+    }
+
+    /// Send robots.txt and sitemap requests through `fetcher` instead of the parser's client
+    pub fn with_fetcher(mut self, fetcher: Option<Arc<dyn Fetcher>>) -> Self {
+        self.fetcher = fetcher;
+        self
+    }
+
+    /// Serve every response from Common Crawl captures, without contacting the sites at all
+    pub fn with_common_crawl(mut self, common_crawl: Option<Arc<CommonCrawlSource>>) -> Self {
+        self.common_crawl = common_crawl;
+        self
+    }
+
+    /// Also parse the sitemaps registered for each site in Bing Webmaster Tools, alongside robots.txt's
+    pub fn with_bing_webmaster(mut self, bing: Option<Arc<BingWebmasterSource>>) -> Self {
+        self.bing = bing;
+        self
+    }
+
+    /// Remember hosts that answer 429 or 403 in `reputation`, and back off them from the
+    /// start of later parses; see [`HostReputation::advice`](crate::reputation::HostReputation::advice)
+    pub fn with_reputation(mut self, reputation: Option<Arc<ReputationStore>>) -> Self {
+        self.reputation = reputation;
+        self
+    }
+
+    /// Send requests with user agents from `user_agents` instead of [`USER_AGENT`], sticking
+    /// to one per host and rotating to the next when the host challenges it
+    pub fn with_user_agents(mut self, user_agents: Option<Arc<UserAgentPool>>) -> Self {
+        self.user_agents = user_agents;
+        self
+    }
+
+    /// Send requests through the proxies in `proxies` instead of directly, retrying on
+    /// another proxy when one fails and leaving out proxies that keep failing
+    pub fn with_proxies(mut self, proxies: Option<Arc<ProxyPool>>) -> Self {
+        self.proxies = proxies;
+        self
+    }
+
+    /// Check every URL against `dns`'s policy before fetching it, redirects included,
+    /// failing refused ones with [`SitemapError::Blocked`]. Build the client with the same
+    /// resolver (see [`ClientConfig::dns`](crate::client::ClientConfig::dns)) so its
+    /// connections use the addresses that were checked.
+    pub fn with_dns(mut self, dns: Option<DnsResolver>) -> Self {
+        self.dns = dns;
+        self
+    }
+
+    /// Pass every sitemap's URLs through `hook`, `batch_size` at a time, keeping only what
+    /// it returns; a URL it rewrites loses its lastmod, priority, and extensions
+    pub fn with_url_hook(mut self, hook: Option<Arc<dyn UrlHook>>, batch_size: usize) -> Self {
+        self.url_hook = hook;
+        self.url_hook_batch_size = batch_size;
+        self
+    }
+
+    /// Keep only the URLs `filter` accepts and follow only the nested sitemaps it
+    /// accepts, checked as each sitemap is parsed
+    pub fn with_url_filter(mut self, filter: Option<Arc<UrlFilter>>) -> Self {
+        self.url_filter = filter;
+        self
+    }
+
+    /// Rewrite each URL to `canonicalizer`'s canonical form as its sitemap is parsed,
+    /// before the URL hook, so variants of one page are kept (and counted) once
+    pub fn with_canonicalizer(mut self, canonicalizer: Option<Arc<UrlCanonicalizer>>) -> Self {
+        self.canonicalizer = canonicalizer;
+        self
+    }
+
+    /// Only return URLs changed since `cutoff`: child sitemaps whose index lastmod is
+    /// older are never fetched, and URLs whose lastmod is older are dropped. Entries
+    /// without a lastmod are kept, since they may have changed.
+    pub fn with_modified_since(mut self, cutoff: Option<DateTime<Utc>>) -> Self {
+        self.modified_since = cutoff;
+        self
+    }
+
+    /// Retry requests that time out, fail to connect, or get one of `policy`'s statuses,
+    /// backing off exponentially with jitter (longer if Retry-After asks); every
+    /// retried request is recorded in the site's `retries`
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Keep the robots.txt files sites answer with in `cache`, for `fetch_robots`; share
+    /// one between parsers to answer from robots.txt another parser already fetched
+    pub fn with_robots_cache(mut self, cache: Arc<RobotsCache>) -> Self {
+        self.robots_cache = cache;
+        self
+    }
+
+    /// Look for sitemaps as `policy` says when robots.txt lists none or can't be fetched
+    pub fn with_discovery(mut self, policy: DiscoveryPolicy) -> Self {
+        self.discovery = policy;
+        self
+    }
+
+    /// Follow redirects only as far as `policy` allows; every request that redirected
+    /// is recorded in the site's `redirects` with its chain and final URL
+    pub fn with_redirect_policy(mut self, policy: RedirectPolicy) -> Self {
+        self.redirect_policy = policy;
+        self
+    }
+
+    /// Hold every request to `limits`' overall and per-host caps, on top of each site's
+    /// own limits; share it between parsers to cap them together
+    pub fn with_request_limits(mut self, limits: Arc<RequestLimiter>) -> Self {
+        self.request_limits = limits;
+        self
+    }
+
+    /// Space requests to each host by `pacer`'s delay, or by its Crawl-delay when the
+    /// pacer has none and `with_respect_robots` is on; share it between parsers to pace
+    /// them together
+    pub fn with_host_pacer(mut self, pacer: Arc<HostPacer>) -> Self {
+        self.host_pacer = pacer;
+        self
+    }
+
+    /// Match robots.txt groups against `user_agent`, for a client given to `from_client`
+    /// that sends its own instead of [`USER_AGENT`]
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Count queueing and in-flight requests into `contention`, to share it between parsers
+    pub fn with_contention(mut self, contention: Arc<ContentionTracker>) -> Self {
+        self.contention = contention;
+        self
+    }
+
+    /// Record each site's status and outcome into `monitor`, to share it with a dashboard
+    pub fn with_monitor(mut self, monitor: Arc<RunMonitor>) -> Self {
+        self.monitor = monitor;
+        self
+    }
+
+    /// Report each site's start and finish, every sitemap fetched, and failures to `listener`
+    pub fn with_progress(mut self, listener: Option<Arc<dyn ProgressListener>>) -> Self {
+        self.progress = listener;
+        self
+    }
+
+    /// Stop gracefully when `shutdown` is requested: no new sites or fetches start, and
+    /// fetches in flight get its grace period to finish
+    pub fn with_shutdown(mut self, shutdown: Arc<Shutdown>) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Record finished sites and fetched sitemaps to `checkpoint`, and skip what an
+    /// earlier run already recorded there; streamed parses neither record nor skip
+    pub fn with_checkpoint(mut self, checkpoint: Option<Arc<Checkpoint>>) -> Self {
+        self.checkpoint = checkpoint;
+        self
+    }
+
+    /// Give each site at most `timeout` from when it starts, whatever the per-request
+    /// timeout. Fetches still pending then are given up on, and the site's result
+    /// keeps the URLs found so far with `timed_out` set
+    pub fn with_site_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.site_timeout = timeout;
+        self
+    }
+
+    /// Keep at most `max_urls` URLs per site, counted as its sitemaps list them. Once
+    /// they're reached no further sitemaps are fetched, and the site's result is
+    /// marked `truncated` and `url_limit_reached`. Sites don't share sitemap fetches
+    /// while a limit is set, since each spends its own budget. With `with_deterministic`
+    /// every sitemap is still read, and the first `max_urls` URLs in sorted order kept.
+    pub fn with_max_urls_per_site(mut self, max_urls: Option<usize>) -> Self {
+        self.max_urls_per_site = max_urls;
+        self
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.shutdown.is_requested()
+    }
+
+    /// Sitemaps Bing Webmaster Tools has for `site_url`; a failed lookup is recorded in `result.errors`
+    async fn bing_sitemaps(&self, site_url: &str, result: &mut ParsedSiteResult) -> Vec<String> {
+        let Some(bing) = &self.bing else { return Vec::new() };
+        if self.is_offline() {
+            return Vec::new();
         }
+        result.total_requests += 1;
+        match bing.sitemaps(&self.client, site_url).await {
+            Ok(sitemaps) => {
+                debug!("🦀 Bing Webmaster Tools lists {} sitemaps for {}", sitemaps.len(), site_url);
+                sitemaps
+            }
+            Err(e) => {
+                result.record_error(format!("Could not list Bing Webmaster sitemaps for {}: {}", site_url, e), SitemapError::from_fetch(site_url, e.as_ref()));
+                Vec::new()
+            }
+        }
+    }
+
+    /// Responses come from recorded captures rather than the live sites
+    fn is_offline(&self) -> bool {
+        self.archive.is_some() || self.common_crawl.is_some()
+    }
+
+    /// Append every fully or partially read response body to a WARC file
+    pub fn with_warc_output(mut self, warc: Option<Arc<WarcWriter>>) -> Self {
+        self.warc = warc;
+        self
     }
 
-    fn normalize_url(&self, url: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    /// The captured body for `url`, cut to `max_response_bytes`, its stored length, and
+    /// whether it is incomplete (cut here, or truncated by the crawler that captured it)
+    async fn archived_body(&self, url: &str) -> Result<ArchivedBody, Box<dyn std::error::Error + Send + Sync>> {
+        let response = match (&self.archive, &self.common_crawl) {
+            (Some(archive), _) => archive.get(url).cloned(),
+            (None, Some(common_crawl)) => Some(common_crawl.fetch(url).await.inspect_err(|e| warn!("🦀 Common Crawl lookup for {} failed: {}", url, e))?),
+            (None, None) => None,
+        };
+        let Some(response) = response else {
+            warn!("🦀 No capture of {} in the WARC input", url);
+            return Err(format!("No capture of {} in the WARC input", url).into());
+        };
+        if !(200..300).contains(&response.status) {
+            return Err(format!("HTTP {} for {} (archived)", response.status, url).into());
+        }
+        let end = response.body.len().min(self.max_response_bytes);
+        let content_type = response.headers.iter().find(|(name, _)| name.eq_ignore_ascii_case("content-type")).map(|(_, value)| value.clone());
+        Ok(ArchivedBody {
+            bytes: response.body[..end].to_vec(),
+            content_length: response.body.len() as u64,
+            content_type,
+            truncated: response.truncated || end < response.body.len(),
+        })
+    }
+
+    pub(crate) fn normalize_url(&self, url: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
         let mut normalized = url.to_string();
         
         // Add https if no scheme
@@ -95,292 +1047,2732 @@ impl RustSitemapParser {
         Ok(result)
     }
 
-    async fn fetch_url(&self, url: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        debug!("🦀 Attempting to fetch URL: {}", url);
-        
-        let response = self.client.get(url).send().await;
-        
-        match response {
-            Ok(resp) => {
-                debug!("🦀 Got HTTP response for {}: {}", url, resp.status());
+    /// Send a `method` request (GET, or HEAD for a probe) for `url`, following its
+    /// redirects as the redirect policy allows and returning the hops taken along with
+    /// the final response. A custom fetcher only ever sends GETs.
+    async fn send_following(&self, method: &Method, url: &str, headers: &HeaderMap) -> Result<(reqwest::Response, Vec<RedirectHop>), Box<dyn std::error::Error + Send + Sync>> {
+        let mut hops = Vec::new();
+        let mut current = url.to_string();
+        loop {
+            if let Some(dns) = &self.dns {
+                dns.check(&current).await?;
+            }
+            let resp = match &self.fetcher {
+                Some(fetcher) => into_response(fetcher.fetch(&current, headers).await?, &current)?,
+                None => self.send_request(method, &current, headers).await?,
+            };
+            let Some(next) = self.redirect_policy.next(&current, resp.status(), resp.headers(), hops.len())? else {
+                return Ok((resp, hops));
+            };
+            debug!("🦀 {} redirected ({}) to {}", current, resp.status(), next);
+            hops.push(RedirectHop { url: std::mem::replace(&mut current, next.into()), status: resp.status().as_u16() });
+        }
+    }
+
+    /// Send a request with the host's user agent from the pool, retrying with the next
+    /// agent each time the host answers with a challenge
+    async fn send_request(&self, method: &Method, url: &str, headers: &HeaderMap) -> Result<reqwest::Response, Box<dyn std::error::Error + Send + Sync>> {
+        let host = Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string)).unwrap_or_default();
+        let Some(pool) = &self.user_agents else {
+            return self.send_proxied(method, url, &host, None, headers).await;
+        };
+        let mut agent = pool.agent_for(&host);
+        loop {
+            let resp = self.send_proxied(method, url, &host, Some(pool.agent(agent)), headers).await?;
+            if !is_challenge(resp.status(), resp.headers()) {
                 if resp.status().is_success() {
-                    match resp.text().await {
-                        Ok(content) => {
-                            debug!("🦀 Successfully read content from {}: {} bytes", url, content.len());
-                            Ok(content)
-                        }
-                        Err(e) => {
-                            error!("🦀 Failed to read response body from {}: {}", url, e);
-                            Err(e.into())
-                        }
-                    }
-                } else {
-                    warn!("🦀 HTTP error for {}: {}", url, resp.status());
-                    Err(format!("HTTP {} for {}", resp.status(), url).into())
+                    pool.record_success(&host, agent);
                 }
+                return Ok(resp);
             }
-            Err(e) => {
-                error!("🦀 Request failed for {}: {}", url, e);
-                Err(e.into())
+            match pool.rotate(&host, agent) {
+                Some(next) => {
+                    info!("🦀 {} challenged user agent {:?} with {}, trying {:?}", host, pool.agent(agent), resp.status(), pool.agent(next));
+                    agent = next;
+                }
+                None => return Ok(resp),
             }
         }
     }
 
-    fn process_sitemap<'a>(
-        &'a self,
-        sitemap_url: &'a str,
-        base_url: &'a str,
-        visited: &'a mut HashSet<String>,
-        max_depth: usize,
-    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<(HashSet<String>, usize), Box<dyn std::error::Error + Send + Sync>>> + Send + 'a>> {
-        Box::pin(async move {
-            if visited.contains(sitemap_url) || max_depth == 0 {
-                return Ok((HashSet::new(), 0));
+    /// Send a request through the proxy pool, if there is one, trying up to `MAX_ATTEMPTS`
+    /// proxies while the ones tried fail
+    async fn send_proxied(&self, method: &Method, url: &str, host: &str, user_agent: Option<&str>, headers: &HeaderMap) -> Result<reqwest::Response, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(proxies) = &self.proxies else {
+            return Ok(self.send_hedged(&self.client, method, url, user_agent, headers).await?);
+        };
+        let mut tried = Vec::new();
+        let mut last = None;
+        while tried.len() < MAX_ATTEMPTS {
+            let Some(proxy) = proxies.pick(host, &tried) else { break };
+            let outcome = self.send_hedged(proxies.client(proxy), method, url, user_agent, headers).await;
+            if !is_proxy_failure(&outcome) {
+                proxies.record_success(proxy);
+                return Ok(outcome?);
             }
+            if proxies.record_failure(proxy) {
+                warn!("🦀 Ejecting proxy {} after {} failures in a row", proxies.name(proxy), MAX_CONSECUTIVE_FAILURES);
+            }
+            debug!("🦀 Request for {} through proxy {} failed", url, proxies.name(proxy));
+            tried.push(proxy);
+            last = Some(outcome);
+        }
+        match last {
+            Some(outcome) => Ok(outcome?),
+            None => Err("No live proxy left in the pool; every proxy has been ejected".into()),
+        }
+    }
+
+    /// Send a request, hedging it with a second identical one if the first is slow.
+    ///
+    /// Whichever request succeeds first wins and the other is dropped (cancelling it).
+    /// If one of them fails we keep waiting on the other before giving up.
+    async fn send_hedged(&self, client: &Client, method: &Method, url: &str, user_agent: Option<&str>, headers: &HeaderMap) -> Result<reqwest::Response, reqwest::Error> {
+        let request = || match user_agent {
+            Some(user_agent) => client.request(method.clone(), url).headers(headers.clone()).header(reqwest::header::USER_AGENT, user_agent),
+            None => client.request(method.clone(), url).headers(headers.clone()),
+        };
+        let Some(delay) = self.hedge_delay else {
+            return request().send().await;
+        };
+
+        let primary = request().send();
+        tokio::pin!(primary);
+        tokio::select! {
+            res = &mut primary => return res,
+            _ = tokio::time::sleep(delay) => {}
+        }
 
-            visited.insert(sitemap_url.to_string());
-            let mut request_count = 1;
+        debug!("🦀 No response from {} after {:?}, sending hedged request", url, delay);
+        let hedge = request().send();
+        tokio::pin!(hedge);
+        tokio::select! {
+            res = &mut primary => match res {
+                Ok(resp) => Ok(resp),
+                Err(_) => hedge.await,
+            },
+            res = &mut hedge => match res {
+                Ok(resp) => {
+                    debug!("🦀 Hedged request to {} answered first", url);
+                    Ok(resp)
+                }
+                Err(_) => primary.await,
+            },
+        }
+    }
 
-            let content = self.fetch_url(sitemap_url).await?;
-            let SitemapParseResult { urls, nested_sitemaps } = parse_sitemap_xml(&content, base_url)?;
+    /// Look `url` up in the Wayback Machine after its live fetch failed with `live_error`.
+    ///
+    /// `None` means the fallback is off, the run is offline, or there is no usable
+    /// capture, in which case the caller reports the live error as before.
+    async fn wayback_fallback(&self, url: &str, live_error: &(dyn std::error::Error + Send + Sync), retries: &RetryLog, fetches: &FetchLog) -> Option<(FetchedBody, ArchivedCapture)> {
+        if !self.wayback_fallback || self.is_offline() || self.shutdown.is_requested() {
+            return None;
+        }
+        let capture = match latest_snapshot(&self.client, url).await {
+            Ok(Some(capture)) => capture,
+            Ok(None) => {
+                debug!("🦀 No Wayback Machine capture of {}", url);
+                return None;
+            }
+            Err(e) => {
+                warn!("🦀 Wayback Machine lookup for {} failed: {}", url, e);
+                return None;
+            }
+        };
+        info!("🦀 {} failed ({}), using Wayback Machine capture from {}", url, live_error, capture.captured_at);
+        match self.fetch_url(&capture.snapshot_url, Some(retries), Some(fetches)).await {
+            Ok(body) => Some((body, capture)),
+            Err(e) => {
+                warn!("🦀 Could not read Wayback Machine capture {}: {}", capture.snapshot_url, e);
+                None
+            }
+        }
+    }
 
-            let mut all_urls = urls;
+    /// Fetch `url`, unless the run is being interrupted; a fetch still going when the
+    /// interrupt's grace period ends is dropped. Retried requests go into `retries`, and
+    /// the fetch's stats into `fetches`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "request", skip_all, fields(url = %url)))]
+    async fn fetch_url(&self, url: &str, retries: Option<&RetryLog>, fetches: Option<&FetchLog>) -> Result<FetchedBody, Box<dyn std::error::Error + Send + Sync>> {
+        if self.shutdown.is_requested() {
+            return Err(SitemapError::Interrupted { url: url.to_string(), message: format!("Interrupted before fetching {}", url) }.into());
+        }
+        tokio::select! {
+            fetched = self.fetch_measured(url, retries, fetches) => fetched,
+            _ = self.shutdown.grace_expired() => Err(SitemapError::Interrupted { url: url.to_string(), message: format!("Interrupted while fetching {}", url) }.into()),
+        }
+    }
 
-            // Process nested sitemaps concurrently for better performance
-            if !nested_sitemaps.is_empty() && max_depth > 1 {
-                // Pre-filter and collect URLs to process, avoiding borrowing conflicts
-                let urls_to_process: Vec<String> = nested_sitemaps.iter()
-                    .filter(|url| !visited.contains(*url))
-                    .take(self.max_nested_per_level) // Use configurable limit
-                    .cloned()
-                    .collect();
+    /// Fetch `url` with retries, adding its stats to `fetches`
+    async fn fetch_measured(&self, url: &str, retries: Option<&RetryLog>, fetches: Option<&FetchLog>) -> Result<FetchedBody, Box<dyn std::error::Error + Send + Sync>> {
+        let started = Instant::now();
+        let mut stats = FetchStats::new(url);
+        let result = self.fetch_retrying(url, retries, &mut stats).await;
+        if let Some(log) = fetches {
+            stats.total = started.elapsed();
+            if let Err(e) = &result {
+                stats.error = Some(SitemapError::from_fetch(url, e.as_ref()).kind().to_string());
+            }
+            log.push(stats);
+        }
+        result
+    }
 
-                // Mark URLs as visited before processing to prevent duplicates
-                for url in &urls_to_process {
-                    visited.insert(url.clone());
+    /// Fetch `url`, retrying transient failures as the retry policy allows; offline runs
+    /// and interrupted ones don't retry. `stats` ends up describing the last attempt.
+    async fn fetch_retrying(&self, url: &str, retries: Option<&RetryLog>, stats: &mut FetchStats) -> Result<FetchedBody, Box<dyn std::error::Error + Send + Sync>> {
+        let mut attempts = Vec::new();
+        loop {
+            *stats = FetchStats { retries: attempts.len(), ..FetchStats::new(url) };
+            let result = self.fetch_recorded(url, stats).await;
+            let e = match result {
+                Ok(_) if attempts.is_empty() => return result,
+                Ok(_) => {
+                    info!("🦀 {} succeeded after {} failed attempts", url, attempts.len());
+                    if let Some(log) = retries {
+                        log.push(RetryRecord { url: url.to_string(), attempts, recovered: true });
+                    }
+                    return result;
                 }
-
-                let futures: Vec<_> = urls_to_process.iter()
-                    .map(|nested_url| {
-                        self.fetch_and_process_single_sitemap(nested_url, base_url, max_depth - 1)
-                    })
-                    .collect();
-
-                let results = join_all(futures).await;
-                
-                for result in results {
-                    match result {
-                        Ok((nested_urls, nested_requests)) => {
-                            all_urls.extend(nested_urls);
-                            request_count += nested_requests;
-                        }
-                        Err(e) => {
-                            warn!("🦀 Error processing nested sitemap: {}", e);
-                        }
+                Err(ref e) => e,
+            };
+            let policy = &self.retry_policy;
+            let (kind, retryable) = policy.classify(e.as_ref());
+            let give_up = !retryable || attempts.len() >= policy.max_retries || self.is_offline() || self.shutdown.is_requested();
+            let wait = if give_up { Duration::ZERO } else { policy.backoff(attempts.len(), e.as_ref()) };
+            attempts.push(RetryAttempt { error: kind, backoff: wait });
+            if give_up {
+                if attempts.len() > 1 {
+                    if let Some(log) = retries {
+                        log.push(RetryRecord { url: url.to_string(), attempts, recovered: false });
                     }
                 }
+                return result;
             }
-
-            Ok((all_urls, request_count))
-        })
+            debug!("🦀 Retrying {} in {:?} after: {}", url, wait, e);
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.shutdown.requested() => return result,
+            }
+        }
     }
 
-    async fn fetch_and_process_single_sitemap(
-        &self,
-        sitemap_url: &str, 
-        base_url: &str,
-        max_depth: usize,
-    ) -> Result<(HashSet<String>, usize), Box<dyn std::error::Error + Send + Sync>> {
-        debug!("🦀 Processing single sitemap: {} (depth: {})", sitemap_url, max_depth);
-        
-        if max_depth == 0 {
-            return Ok((HashSet::new(), 0));
-        }
+    async fn fetch_recorded(&self, url: &str, stats: &mut FetchStats) -> Result<FetchedBody, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(har) = &self.har else {
+            return self.fetch_body(url, None, stats).await;
+        };
 
-        let mut request_count = 1;
-        let content = self.fetch_url(sitemap_url).await?;
-        let SitemapParseResult { urls, nested_sitemaps } = parse_sitemap_xml(&content, base_url)?;
-        
-        let mut all_urls = urls;
-        
-        // Process nested sitemaps recursively if depth allows
-        if !nested_sitemaps.is_empty() && max_depth > 1 {
+        let mut exchange = HarExchange::start("GET", url);
+        let result = self.fetch_body(url, Some(&mut exchange), stats).await;
+        match &result {
+            Ok(body) => {
+                if let Some(reason) = body.truncation {
+                    exchange.note(format!("partial body ({})", reason));
+                }
+            }
+            Err(e) => exchange.note(e.to_string()),
+        }
+        har.record(exchange);
+        result
+    }
+
+    async fn fetch_body(&self, url: &str, mut har: Option<&mut HarExchange>, stats: &mut FetchStats) -> Result<FetchedBody, Box<dyn std::error::Error + Send + Sync>> {
+        debug!("🦀 Attempting to fetch URL: {}", url);
+
+        if self.is_offline() {
+            let archived = self.archived_body(url).await?;
+            let body = decode_body(&archived.bytes, self.max_response_bytes, archived.truncated, archived.content_type.as_deref())?;
+            if let Some(exchange) = har {
+                exchange.body(&body.text, archived.bytes.len());
+                exchange.note("served from an archived capture");
+            }
+            return Ok(FetchedBody {
+                text: body.text,
+                decompressed: body.decompressed,
+                bytes_read: archived.bytes.len(),
+                content_length: Some(archived.content_length),
+                content_type: archived.content_type,
+                encoding: body.encoding,
+                cache: None,
+                truncation: (archived.truncated || body.truncated).then_some(TruncationReason::SizeLimit),
+                redirect: None,
+            });
+        }
+
+        let host = Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string)).unwrap_or_default();
+        // Held until the body is read, since the connection stays busy until then
+        let _slot = self.contention.wait_for_request(self.request_limits.acquire(&host)).await;
+        let _in_flight = self.contention.request(&host);
+        let cached = self.http_cache.as_ref().and_then(|cache| cache.get(url));
+        let conditional = cached.as_ref().map(CachedResponse::conditional_headers).unwrap_or_default();
+        let timings = Arc::new(ConnectionTimings::default());
+        let sent = Instant::now();
+        let response = timed(Arc::clone(&timings), self.send_following(&Method::GET, url, &conditional)).await;
+        timings.apply(stats);
+        
+        match response {
+            Ok((mut resp, hops)) => {
+                debug!("🦀 Got HTTP response for {}: {}", url, resp.status());
+                stats.ttfb = Some(sent.elapsed());
+                stats.status = Some(resp.status().as_u16());
+                stats.redirects = hops.len();
+                let redirect = (!hops.is_empty() || Url::parse(url).ok().as_ref() != Some(resp.url()))
+                    .then(|| RedirectChain { hops, final_url: resp.url().to_string() });
+                if let Some(exchange) = har.as_deref_mut() {
+                    exchange.response(&resp);
+                }
+                if let (StatusCode::NOT_MODIFIED, Some(cached)) = (resp.status(), cached) {
+                    debug!("🦀 {} is unchanged, using the cached copy", url);
+                    stats.cache_hit = true;
+                    let body = decode_body(&cached.body, self.max_response_bytes, false, cached.content_type.as_deref())?;
+                    if let Some(exchange) = har {
+                        exchange.body(&body.text, 0);
+                        exchange.note("served from the HTTP cache after a 304");
+                    }
+                    return Ok(FetchedBody {
+                        text: body.text,
+                        decompressed: body.decompressed,
+                        bytes_read: 0,
+                        content_length: Some(cached.body.len() as u64),
+                        content_type: cached.content_type,
+                        encoding: body.encoding,
+                        truncation: body.truncated.then_some(TruncationReason::SizeLimit),
+                        cache: Some(CacheUse::Hit),
+                        redirect,
+                    });
+                }
+                if !resp.status().is_success() {
+                    warn!("🦀 HTTP error for {}: {}", url, resp.status());
+                    if let (Some(store), 429 | 403) = (&self.reputation, resp.status().as_u16()) {
+                        if let Some(host) = Url::parse(url).ok().as_ref().and_then(Url::host_str) {
+                            store.record_throttle(host, resp.status().as_u16(), resp.headers());
+                        }
+                    }
+                    return Err(HttpStatusError::new(resp.status(), url, resp.headers()).into());
+                }
+
+                // Decode chunk by chunk so oversized or cut-off bodies still yield what arrived,
+                // and a gzipped body is inflated as it comes in rather than held twice
+                let content_length = resp.content_length();
+                let content_type = resp.headers().get(reqwest::header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).map(str::to_string);
+                let mut decoder = BodyDecoder::new(self.max_response_bytes).with_content_type(content_type.as_deref());
+                // Only the WARC output and the HTTP cache need the body as it came over the wire
+                let keep_raw = self.warc.is_some() || self.http_cache.is_some();
+                let mut bytes = Vec::new();
+                let mut bytes_read = 0;
+                let mut truncation = None;
+                loop {
+                    match resp.chunk().await {
+                        Ok(Some(chunk)) => {
+                            let remaining = self.max_response_bytes - bytes_read;
+                            let kept = &chunk[..chunk.len().min(remaining)];
+                            bytes_read += kept.len();
+                            decoder.push(kept);
+                            if keep_raw {
+                                bytes.extend_from_slice(kept);
+                            }
+                            if chunk.len() > remaining || decoder.is_full() {
+                                warn!("🦀 Response from {} exceeded {} bytes, keeping partial body", url, self.max_response_bytes);
+                                truncation = Some(TruncationReason::SizeLimit);
+                                break;
+                            }
+                        }
+                        Ok(None) => break,
+                        Err(e) if bytes_read > 0 => {
+                            warn!("🦀 Body from {} cut off after {} bytes: {}", url, bytes_read, e);
+                            truncation = Some(TruncationReason::Interrupted);
+                            break;
+                        }
+                        Err(e) => {
+                            error!("🦀 Failed to read response body from {}: {}", url, e);
+                            return Err(e.into());
+                        }
+                    }
+                }
+                debug!("🦀 Successfully read content from {}: {} bytes", url, bytes_read);
+                stats.bytes = bytes_read;
+
+                if let Some(warc) = &self.warc {
+                    if let Err(e) = warc.write_exchange(url, &resp, &bytes, truncation) {
+                        warn!("🦀 Could not write {} to WARC output: {}", url, e);
+                    }
+                }
+
+                let body = decoder.finish(truncation.is_some())?;
+                if body.decompressed {
+                    debug!("🦀 Transparently decompressed gzip body from {}", url);
+                }
+                if body.truncated && truncation.is_none() {
+                    truncation = Some(TruncationReason::SizeLimit);
+                }
+
+                if let Some(exchange) = har {
+                    exchange.body(&body.text, bytes_read);
+                }
+
+                let cache = self.http_cache.as_ref().map(|cache| {
+                    // A partial body would be served as if it were whole
+                    if truncation.is_none() {
+                        if let Some(response) = CachedResponse::from_response(resp.headers(), bytes) {
+                            cache.put(url, response);
+                        }
+                    }
+                    CacheUse::Miss
+                });
+
+                Ok(FetchedBody {
+                    text: body.text,
+                    decompressed: body.decompressed,
+                    bytes_read,
+                    content_length,
+                    content_type,
+                    encoding: body.encoding,
+                    truncation,
+                    cache,
+                    redirect,
+                })
+            }
+            Err(e) => {
+                error!("🦀 Request failed for {}: {}", url, e);
+                Err(e)
+            }
+        }
+    }
+
+    /// How sitemap bodies are parsed, from the parser's options
+    fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            quirks: self.quirks,
+            extensions: self.url_extensions,
+            media: self.url_media,
+            alternates: self.url_alternates,
+            filter: self.url_filter.clone(),
+            modified_since: self.modified_since,
+        }
+    }
+
+    /// Whether results carry `url_metadata`, asked for directly or by an option that fills it
+    fn keeps_url_metadata(&self) -> bool {
+        self.url_metadata || self.url_extensions || self.url_media || self.url_alternates
+    }
+
+    /// Fetch and parse one sitemap, sharing the fetch with the batch's other sites if
+    /// they list it too.
+    ///
+    /// Returns the body with what it parsed to, whether the fetch was shared from another
+    /// site, and the hold on its memory, which the caller keeps while it processes nested
+    /// sitemaps.
+    async fn fetch_sitemap<'c>(
+        &self,
+        sitemap_url: &str,
+        base_url: &str,
+        ctx: &'c SiteContext,
+    ) -> Result<(SitemapRecord, Arc<FetchedBody>, bool, MemoryHold<'c>), Box<dyn std::error::Error + Send + Sync>> {
+        let (body, archived, coalesced) = match &ctx.coalescer {
+            Some(coalescer) => {
+                let (fetched, started_here) = coalescer.fetch(sitemap_url, || async {
+                    let (body, archived) = self.download_sitemap(sitemap_url, ctx).await
+                        .map_err(|e| SitemapError::from_fetch(sitemap_url, e.as_ref()))?;
+                    Ok((Arc::new(body), archived))
+                }).await;
+                let (body, archived) = fetched?;
+                if started_here {
+                    ctx.cache_bytes.fetch_add(body.text.capacity(), Ordering::SeqCst);
+                } else {
+                    debug!("🦀 Reusing shared fetch of {} for {}", sitemap_url, ctx.site);
+                }
+                (body, archived, !started_here)
+            }
+            None => {
+                let (body, archived) = self.download_sitemap(sitemap_url, ctx).await?;
+                (Arc::new(body), archived, false)
+            }
+        };
+        // The body stays alive while nested sitemaps are processed
+        let held = ctx.buffers.hold(body.bytes_read + body.text.capacity());
+        let parsed = parse_sitemap_body(&body.text, base_url, body.content_type.as_deref(), &self.parse_options())
+            .map_err(|e| SitemapError::XmlParse { url: sitemap_url.to_string(), message: e.to_string() })?;
+        let record = SitemapRecord {
+            site: ctx.site.clone(),
+            url: sitemap_url.to_string(),
+            parsed,
+            decompressed: body.decompressed,
+            encoding: body.encoding.map(str::to_string),
+            redirect: body.redirect.clone(),
+            truncated: body.truncation.map(|reason| TruncatedSitemap {
+                url: sitemap_url.to_string(),
+                bytes_read: body.bytes_read,
+                content_length: body.content_length,
+                reason,
+            }),
+            archived,
+        };
+        Ok((record, body, coalesced, held))
+    }
+
+    /// Fetch one sitemap's body for `ctx`'s site, falling back to the Wayback Machine if that's on
+    async fn download_sitemap(
+        &self,
+        sitemap_url: &str,
+        ctx: &SiteContext,
+    ) -> Result<(FetchedBody, Option<ArchivedCapture>), Box<dyn std::error::Error + Send + Sync>> {
+        tokio::select! {
+            _ = ctx.pace(&self.host_pacer, sitemap_url) => {}
+            // fetch_url turns this into an interrupted error
+            _ = self.shutdown.requested() => {}
+        }
+        let slot = if ctx.degradation.limits_requests() {
+            self.contention.wait_for_request(ctx.degradation.acquire()).await
+        } else {
+            None
+        };
+        let fetched = self.fetch_url(sitemap_url, Some(&ctx.retries), Some(&ctx.fetches)).await;
+        ctx.degradation.release(slot);
+
+        let (body, archived) = match fetched {
+            Ok(body) => (body, None),
+            Err(e) => {
+                if is_timeout(e.as_ref()) {
+                    if let Some(level) = ctx.degradation.record_timeout() {
+                        warn!("🦀 Repeated timeouts while fetching {}, stepping site limits down to level {}", sitemap_url, level);
+                    }
+                }
+                match self.wayback_fallback(sitemap_url, e.as_ref(), &ctx.retries, &ctx.fetches).await {
+                    Some((body, capture)) => (body, Some(capture)),
+                    None => return Err(e),
+                }
+            }
+        };
+        if let Some(store) = &self.body_store {
+            if let Err(e) = store.store(sitemap_url, &body.text) {
+                warn!("🦀 Could not store body of {}: {}", sitemap_url, e);
+            }
+        }
+        Ok((body, archived))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "sitemap", skip_all, fields(url = %sitemap_url, depth = max_depth)))]
+    async fn fetch_and_process_single_sitemap(
+        &self,
+        sitemap_url: &str, 
+        base_url: &str,
+        max_depth: usize,
+        ctx: &SiteContext,
+    ) -> Result<SitemapTreeResult, Box<dyn std::error::Error + Send + Sync>> {
+        debug!("🦀 Processing single sitemap: {} (depth: {})", sitemap_url, max_depth);
+        
+        if max_depth == 0 {
+            return Ok(SitemapTreeResult::default());
+        }
+        if ctx.out_of_urls() {
+            debug!("🦀 Not fetching {}: {} has reached its URL limit", sitemap_url, ctx.site);
+            return Ok(SitemapTreeResult::default());
+        }
+
+        let replayed = ctx.checkpoint.as_ref().and_then(|checkpoint| checkpoint.take_sitemap(&ctx.site, sitemap_url));
+        let (record, body, coalesced, _held) = match replayed {
+            Some(record) => {
+                debug!("🦀 Replaying {} from the checkpoint", sitemap_url);
+                (record, None, false, None)
+            }
+            None => {
+                let (record, body, coalesced, held) = ctx.before_deadline(sitemap_url, self.fetch_sitemap(sitemap_url, base_url, ctx)).await?;
+                if let Some(checkpoint) = &ctx.checkpoint {
+                    if let Err(e) = checkpoint.record_sitemap(&record) {
+                        warn!("🦀 Could not record {} in checkpoint {}: {}", sitemap_url, checkpoint.path().display(), e);
+                    }
+                }
+                (record, Some(body), coalesced, Some(held))
+            }
+        };
+        // Replayed sitemaps cost nothing this run, and a shared fetch is billed to the site that made it
+        let billed = body.as_ref().filter(|_| !coalesced);
+        let SitemapRecord { parsed, decompressed, encoding, redirect, truncated, archived, .. } = record;
+        let SitemapParseResult { mut urls, nested_sitemaps, mut metadata, security_warnings, unmodified_urls, unmodified_sitemaps } = parsed;
+        let duplicate_urls = self.canonicalizer.as_ref().map_or(0, |canonicalizer| canonicalizer.canonicalize_all(&mut urls, &mut metadata));
+        if let Some(hook) = &self.url_hook {
+            urls = apply_hook(Arc::clone(hook), sitemap_url, urls, self.url_hook_batch_size).await?;
+        }
+        if let Some(robots) = ctx.robots.get() {
+            let listed = urls.len();
+            urls.retain(|url| robots.is_allowed(url));
+            ctx.disallowed.fetch_add(listed - urls.len(), Ordering::SeqCst);
+        }
+        ctx.take_urls(&mut urls);
+        self.report(|| ctx.progress.sitemap(&ctx.site, sitemap_url, urls.len()));
+        let level_here = (self.max_depth + 1).saturating_sub(max_depth);
+
+        let mut tree = SitemapTreeResult {
+            urls,
+            requests: billed.map_or(0, |body| 1 + body.redirect_requests()),
+            duplicate_urls,
+            security_warnings: security_warnings.into_iter().map(|w| format!("{}: {}", sitemap_url, w)).collect(),
+            unmodified_urls,
+            unmodified_sitemaps,
+            ..Default::default()
+        };
+        if self.keeps_url_metadata() {
+            tree.url_metadata = tree.urls.iter()
+                .map(|url| (url.clone(), UrlMetadata {
+                    depth: level_here,
+                    source_sitemap: Some(sitemap_url.to_string()),
+                    ..metadata.remove(url).unwrap_or_default()
+                }))
+                .collect();
+        }
+        if let Some(capture) = archived {
+            if billed.is_some() {
+                // The failed live request, the availability lookup, and the capture itself
+                tree.requests += 2;
+            }
+            tree.archived_urls = tree.urls.iter().map(|url| (url.clone(), capture.captured_at.clone())).collect();
+            tree.archived_captures.push(capture);
+        }
+        if decompressed {
+            tree.decompressed_sitemaps.push(sitemap_url.to_string());
+        }
+        if let Some(encoding) = encoding {
+            tree.transcoded_sitemaps.insert(sitemap_url.to_string(), encoding);
+        }
+        if let Some(redirect) = redirect {
+            tree.redirects.insert(sitemap_url.to_string(), redirect);
+        }
+        tree.truncated_sitemaps.extend(truncated);
+        if let Some(body) = billed {
+            body.count_cache(&mut tree.cache_hits, &mut tree.cache_misses);
+        }
+        if let (Some(body), true) = (body, self.keep_bodies) {
+            let text = Arc::try_unwrap(body).map_or_else(|body| body.text.clone(), |body| body.text);
+            tree.raw_bodies.insert(sitemap_url.to_string(), text);
+        }
+        if coalesced {
+            tree.coalesced_sitemaps.push(sitemap_url.to_string());
+        }
+        if let Some(sink) = &ctx.sink {
+            sink.send(std::mem::take(&mut tree.urls), self.deterministic).await?;
+        }
+        
+        // Process nested sitemaps recursively if depth allows (a degraded site gets less depth)
+        if !nested_sitemaps.is_empty() && max_depth > 1 && level_here < ctx.degradation.depth(self.max_depth) {
+            let nested_limit = ctx.degradation.nested_limit(self.max_nested_per_level);
             debug!("🦀 Found {} nested sitemaps in {}, processing up to {} with depth {}", 
-                   nested_sitemaps.len(), sitemap_url, self.max_nested_per_level, max_depth - 1);
+                   nested_sitemaps.len(), sitemap_url, nested_limit, max_depth - 1);
             
             // Limit nested sitemaps to process 
             let limited_nested: Vec<_> = nested_sitemaps.iter()
-                .take(self.max_nested_per_level)
+                .take(nested_limit)
                 .cloned()
                 .collect();
             
             // Process nested sitemaps concurrently
             let futures: Vec<_> = limited_nested.iter()
                 .map(|nested_url| {
-                    self.fetch_and_process_single_sitemap(nested_url, base_url, max_depth - 1)
+                    self.fetch_and_process_single_sitemap(nested_url, base_url, max_depth - 1, ctx)
                 })
                 .collect();
 
             let results = join_all(futures).await;
             
-            for result in results {
+            for (nested_url, result) in limited_nested.iter().zip(results) {
                 match result {
-                    Ok((nested_urls, nested_requests)) => {
-                        all_urls.extend(nested_urls);
-                        request_count += nested_requests;
-                    }
+                    Ok(nested) => tree.merge(nested),
                     Err(e) => {
                         warn!("🦀 Error processing nested sitemap: {}", e);
+                        self.report(|| ctx.progress.error(&ctx.site, nested_url, e.to_string()));
                     }
                 }
             }
         }
         
-        debug!("🦀 Completed processing {}: {} total URLs, {} requests", sitemap_url, all_urls.len(), request_count);
-        Ok((all_urls, request_count))
+        debug!("🦀 Completed processing {}: {} total URLs, {} requests", sitemap_url, tree.urls.len(), tree.requests);
+        Ok(tree)
+    }
+
+    /// Fetch the first `max_sitemaps` of `sitemaps` with their nested sitemaps and merge
+    /// them into `result`; true if every one of them turned out not to exist
+    async fn process_top_level_sitemaps(
+        &self,
+        sitemaps: &[String],
+        base_url: &str,
+        normalized_url: &str,
+        ctx: &SiteContext,
+        result: &mut ParsedSiteResult,
+    ) -> bool {
+        let limited_sitemaps: Vec<_> = sitemaps.iter().take(self.max_sitemaps).cloned().collect();
+        debug!("🦀 Processing first {} sitemaps out of {} total", limited_sitemaps.len(), sitemaps.len());
+
+        // Process sitemaps concurrently for better performance
+        let futures: Vec<_> = limited_sitemaps.iter()
+            .map(|sitemap_url| {
+                self.fetch_and_process_single_sitemap(sitemap_url, normalized_url, self.max_depth, ctx)
+            })
+            .collect();
+
+        let results = join_all(futures).await;
+        let none_exist = results.iter().all(|r| r.as_ref().is_err_and(|e| SitemapError::from_fetch(base_url, e.as_ref()).is_not_found()));
+
+        for (sitemap_url, single_result) in limited_sitemaps.iter().zip(results) {
+            match single_result {
+                Ok(tree) => {
+                    result.duplicate_urls += tree.duplicate_urls + extend_urls(&mut result.urls, tree.urls);
+                    result.total_requests += tree.requests;
+                    result.cache_hits += tree.cache_hits;
+                    result.cache_misses += tree.cache_misses;
+                    result.unmodified_urls += tree.unmodified_urls;
+                    result.unmodified_sitemaps += tree.unmodified_sitemaps;
+                    result.decompressed_sitemaps.extend(tree.decompressed_sitemaps);
+                    result.transcoded_sitemaps.extend(tree.transcoded_sitemaps);
+                    result.redirects.extend(tree.redirects);
+                    result.truncated_sitemaps.extend(tree.truncated_sitemaps);
+                    result.archived_captures.extend(tree.archived_captures);
+                    result.archived_urls.extend(tree.archived_urls);
+                    merge_url_metadata(&mut result.url_metadata, tree.url_metadata);
+                    result.security_warnings.extend(tree.security_warnings);
+                    result.raw_bodies.extend(tree.raw_bodies);
+                    result.coalesced_sitemaps.extend(tree.coalesced_sitemaps);
+                }
+                Err(e) => {
+                    self.report(|| ctx.progress.error(base_url, sitemap_url, e.to_string()));
+                    result.record_error(format!("Error processing sitemap: {}", e), SitemapError::from_fetch(sitemap_url, e.as_ref()));
+                }
+            }
+        }
+        none_exist
+    }
+
+    /// The discovery policy's candidate sitemaps for a site whose robots.txt listed none,
+    /// less those a HEAD probe found missing
+    async fn probe_candidates(&self, normalized_url: &str, ctx: &SiteContext, result: &mut ParsedSiteResult) -> Vec<String> {
+        let candidates = self.discovery.candidates(normalized_url);
+        // Archived captures and custom fetchers only answer GETs
+        if !self.discovery.probe || self.is_offline() || self.fetcher.is_some() {
+            return candidates;
+        }
+        let probes = join_all(candidates.iter().map(|url| async move {
+            ctx.pace(&self.host_pacer, url).await;
+            ctx.before_deadline(url, async { Ok(self.probe(url).await) }).await.unwrap_or((Probe::Unknown, 0))
+        })).await;
+        result.total_requests += probes.iter().map(|(_, requests)| requests).sum::<usize>();
+        candidates.into_iter()
+            .zip(probes)
+            .filter(|(_, (probe, _))| *probe != Probe::Missing)
+            .map(|(url, _)| url)
+            .collect()
+    }
+
+    /// Send a HEAD for `url`, returning what it says and the requests it took
+    async fn probe(&self, url: &str) -> (Probe, usize) {
+        if self.shutdown.is_requested() {
+            return (Probe::Unknown, 0);
+        }
+        let host = Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string)).unwrap_or_default();
+        let _slot = self.contention.wait_for_request(self.request_limits.acquire(&host)).await;
+        let _in_flight = self.contention.request(&host);
+        let mut exchange = HarExchange::start("HEAD", url);
+        let probed = match self.send_following(&Method::HEAD, url, &HeaderMap::new()).await {
+            Ok((resp, hops)) => {
+                exchange.response(&resp);
+                (Probe::from_status(resp.status()), 1 + hops.len())
+            }
+            Err(e) => {
+                exchange.note(e.to_string());
+                (Probe::Unknown, 1)
+            }
+        };
+        debug!("🦀 Probed {}: {:?}", url, probed.0);
+        if let Some(har) = &self.har {
+            har.record(exchange);
+        }
+        probed
+    }
+
+    /// The sitemaps the site's homepage links with `<link rel="sitemap">`, if the
+    /// discovery policy looks there
+    async fn homepage_sitemaps(&self, normalized_url: &str, ctx: &SiteContext, result: &mut ParsedSiteResult) -> Vec<String> {
+        if !self.discovery.homepage_links || self.shutdown.is_requested() {
+            return Vec::new();
+        }
+        ctx.pace(&self.host_pacer, normalized_url).await;
+        let page = ctx.before_deadline(normalized_url, self.fetch_url(normalized_url, Some(&ctx.retries), Some(&ctx.fetches))).await;
+        match page {
+            Ok(page) => {
+                result.total_requests += 1 + page.redirect_requests();
+                page.count_cache(&mut result.cache_hits, &mut result.cache_misses);
+                let page_url = page.redirect.as_ref().map_or(normalized_url, |redirect| redirect.final_url.as_str());
+                sitemap_links(&page.text, page_url)
+            }
+            Err(e) => {
+                debug!("🦀 Could not read the homepage {} for sitemap links: {}", normalized_url, e);
+                Vec::new()
+            }
+        }
     }
 
     pub async fn parse_site(&self, base_url: &str) -> Result<ParsedSiteResult, Box<dyn std::error::Error + Send + Sync>> {
+        self.parse_site_with(base_url, None, None).await
+    }
+
+    /// Parse a site, sending each sitemap's URLs to `sink` as soon as it's parsed
+    /// instead of collecting them; the result's `urls` stays empty and
+    /// `streamed_urls` counts what was sent.
+    ///
+    /// URLs are deduplicated within each sitemap only, so one listed by several
+    /// sitemaps is sent once per sitemap.
+    pub async fn parse_site_streaming(&self, base_url: &str, sink: UrlSink) -> Result<ParsedSiteResult, Box<dyn std::error::Error + Send + Sync>> {
+        self.parse_site_with(base_url, None, Some(sink)).await
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(name = "site", skip_all, fields(site = %base_url)))]
+    async fn parse_site_with(
+        &self,
+        base_url: &str,
+        coalescer: Option<Arc<SitemapCoalescer<SharedFetch>>>,
+        sink: Option<UrlSink>,
+    ) -> Result<ParsedSiteResult, Box<dyn std::error::Error + Send + Sync>> {
+        let checkpoint = self.checkpoint.clone().filter(|_| sink.is_none());
+        if let Some(result) = checkpoint.as_ref().and_then(|checkpoint| checkpoint.completed(base_url)) {
+            info!("🦀 Skipping {}, finished before the checkpoint", base_url);
+            return Ok(result);
+        }
+        let site = self.monitor.site(base_url);
+        let progress = Arc::new(SiteProgress::default());
+        self.report(|| progress.event(ProgressKind::SiteStarted, base_url));
+        let parsed = self.parse_site_unmonitored(base_url, coalescer, sink, checkpoint.clone(), Arc::clone(&progress)).await;
+        site.finish(&parsed);
+        if let (Some(checkpoint), Ok(result)) = (&checkpoint, &parsed) {
+            // An interrupted site is parsed again on resume, replaying the sitemaps it got to
+            if !result.interrupted {
+                if let Err(e) = checkpoint.record_site(result) {
+                    warn!("🦀 Could not record {} in checkpoint {}: {}", base_url, checkpoint.path().display(), e);
+                }
+            }
+        }
+        self.report(|| {
+            let (urls, errors) = parsed.as_ref().map_or((0, 1), |r| (r.url_count(), r.errors.len()));
+            ProgressEvent { urls, errors, ..progress.event(ProgressKind::SiteFinished, base_url) }
+        });
+        parsed
+    }
+
+    /// Send a progress event, built only when someone is listening
+    fn report(&self, event: impl FnOnce() -> ProgressEvent) {
+        if let Some(listener) = &self.progress {
+            listener.on_progress(event());
+        }
+    }
+
+    async fn parse_site_unmonitored(
+        &self,
+        base_url: &str,
+        coalescer: Option<Arc<SitemapCoalescer<SharedFetch>>>,
+        sink: Option<UrlSink>,
+        checkpoint: Option<Arc<Checkpoint>>,
+        progress: Arc<SiteProgress>,
+    ) -> Result<ParsedSiteResult, Box<dyn std::error::Error + Send + Sync>> {
         let start_time = Instant::now();
+        let started_at = unix_now();
+        let deadline = self.site_timeout.map(|timeout| start_time + timeout);
+        let _in_flight = self.contention.site();
+        let _active = self.shutdown.site();
+        if self.shutdown.is_requested() {
+            return Ok(ParsedSiteResult::interrupted(base_url.to_string()));
+        }
         let mut result = ParsedSiteResult::new(base_url.to_string());
 
         debug!("🦀 Starting to parse site: {}", base_url);
         let normalized_url = self.normalize_url(base_url)?;
+        let host = Url::parse(&normalized_url).ok().and_then(|u| u.host_str().map(str::to_string));
+        let advice = self.host_advice(host.as_deref());
+        if let Some(until) = advice.skip_until {
+            let until = chrono::DateTime::from_timestamp(until as i64, 0).unwrap_or_default();
+            let error = SitemapError::Skipped { base_url: base_url.to_string(), until: until.to_rfc3339() };
+            result.record_error(error.to_string(), error);
+            result.parse_time = start_time.elapsed().as_secs_f64();
+            return Ok(result);
+        }
+        if !advice.wait.is_zero() {
+            info!("🦀 Waiting {:?} before {}, as its Retry-After asked", advice.wait, base_url);
+            // No longer than the site's deadline, which then fails its fetches
+            let wait = deadline.map_or(advice.wait, |deadline| advice.wait.min(deadline.saturating_duration_since(Instant::now())));
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.shutdown.requested() => return Ok(ParsedSiteResult::interrupted(base_url.to_string())),
+            }
+        }
+        // Deterministic runs read every sitemap and cap the URLs once they're all in, so reruns keep the same ones
+        let cap_after = self.max_urls_per_site.filter(|_| self.deterministic && sink.is_none());
+        let ctx = Arc::new(SiteContext {
+            sink,
+            site: base_url.to_string(),
+            progress,
+            checkpoint,
+            deadline,
+            url_budget: self.max_urls_per_site.filter(|_| cap_after.is_none()).map(AtomicUsize::new),
+            coalescer,
+            ..SiteContext::with_cap(self, advice.concurrency)
+        });
         let robots_url = format!("{}/robots.txt", normalized_url.trim_end_matches('/'));
 
         debug!("🦀 Fetching robots.txt from: {}", robots_url);
+        tokio::select! {
+            _ = ctx.pace(&self.host_pacer, &robots_url) => {}
+            // fetch_url turns this into an interrupted error
+            _ = self.shutdown.requested() => {}
+        }
         // Fetch robots.txt
-        match self.fetch_url(&robots_url).await {
-            Ok(robots_content) => {
+        let robots = ctx.before_deadline(&robots_url, async {
+            match self.fetch_url(&robots_url, Some(&ctx.retries), Some(&ctx.fetches)).await {
+                Ok(body) => Ok(body),
+                Err(e) => match self.wayback_fallback(&robots_url, e.as_ref(), &ctx.retries, &ctx.fetches).await {
+                    Some((body, capture)) => {
+                        result.total_requests += 2;
+                        result.archived_captures.push(capture);
+                        Ok(body)
+                    }
+                    None => Err(e),
+                },
+            }
+        }).await;
+        if let Ok(body) = &robots {
+            body.count_cache(&mut result.cache_hits, &mut result.cache_misses);
+            result.total_requests += body.redirect_requests();
+            if let Some(redirect) = &body.redirect {
+                result.redirects.insert(robots_url.clone(), redirect.clone());
+            }
+        }
+        // The robots.txt body counts as held until its sitemaps are done
+        let _robots_held = robots.as_ref().ok().map(|body| ctx.buffers.hold(body.bytes_read + body.text.capacity()));
+        let from_robots = match &robots {
+            Ok(robots_body) if self.respect_robots => {
+                debug!("🦀 Successfully fetched robots.txt for {}, applying its rules", base_url);
+                result.total_requests += 1;
+                let robots = self.remember_robots(&normalized_url, RobotsTxt::parse(&robots_body.text, &normalized_url));
+                let agent = match (&self.user_agents, &host) {
+                    (Some(pool), Some(host)) => pool.agent(pool.agent_for(host)),
+                    _ => self.user_agent.as_str(),
+                };
+                let policy = robots.policy_for(agent, &normalized_url);
+                if let Some(delay) = policy.crawl_delay {
+                    info!("🦀 {} asks for a crawl delay of {:?}", base_url, delay);
+                    // robots.txt itself was the first request, unless the pacer's own delay already spaced it
+                    if let (None, Some(host)) = (self.host_pacer.delay(), &host) {
+                        self.host_pacer.record(host, Some(delay));
+                    }
+                }
+                result.crawl_delay = policy.crawl_delay;
+                let _ = ctx.robots.set(policy);
+                Some(robots.sitemaps.clone())
+            }
+            Ok(robots_body) => {
                 debug!("🦀 Successfully fetched robots.txt for {}", base_url);
                 result.total_requests += 1;
-                
-                let sitemaps = parse_robots_txt(&robots_content, &normalized_url);
-                
-                if sitemaps.is_empty() {
-                    // Try common sitemap locations
-                    result.sitemaps_found = vec![
-                        format!("{}/sitemap.xml", normalized_url.trim_end_matches('/')),
-                        format!("{}/sitemap_index.xml", normalized_url.trim_end_matches('/')),
-                        format!("{}/sitemaps.xml", normalized_url.trim_end_matches('/')),
-                    ];
-                } else {
-                    result.sitemaps_found = sitemaps;
+                let robots = self.remember_robots(&normalized_url, RobotsTxt::parse(&robots_body.text, &normalized_url));
+                Some(robots.sitemaps.clone())
+            }
+            Err(e) => {
+                if allows_everything(&SitemapError::from_fetch(&robots_url, e.as_ref())) {
+                    self.remember_robots(&normalized_url, RobotsTxt::default());
                 }
+                self.report(|| ctx.progress.error(base_url, &robots_url, e.to_string()));
+                let error = SitemapError::RobotsFetch { url: robots_url.clone(), cause: Box::new(SitemapError::from_fetch(&robots_url, e.as_ref())) };
+                result.record_error(error.to_string(), error);
+                None
+            }
+        };
+        let from_bing = self.bing_sitemaps(&normalized_url, &mut result).await;
+        // Discovery carries on past a robots.txt that failed, unless the host couldn't be
+        // reached or asked us to back off
+        let keep_looking = robots.as_ref().err().is_none_or(|e| {
+            !matches!(
+                SitemapError::from_fetch(&robots_url, e.as_ref()),
+                SitemapError::Network { .. } | SitemapError::Interrupted { .. } | SitemapError::Http { status: 429, .. }
+            )
+        });
 
-                // Use configurable max_sitemaps limit
-                let limited_sitemaps: Vec<_> = result.sitemaps_found.iter().take(self.max_sitemaps).cloned().collect();
-                debug!("🦀 Processing first {} sitemaps out of {} total", limited_sitemaps.len(), result.sitemaps_found.len());
+        if keep_looking || !from_bing.is_empty() {
+            let mut sitemaps = from_robots.unwrap_or_default();
+            for sitemap_url in &from_bing {
+                if !sitemaps.contains(sitemap_url) {
+                    sitemaps.push(sitemap_url.clone());
+                }
+            }
+            result.bing_sitemaps = from_bing;
 
-                // Process sitemaps concurrently for better performance
-                let futures: Vec<_> = limited_sitemaps.iter()
-                    .map(|sitemap_url| {
-                        self.fetch_and_process_single_sitemap(sitemap_url, &normalized_url, self.max_depth) // Start with max_depth
-                    })
-                    .collect();
-
-                let results = join_all(futures).await;
-                
-                for single_result in results {
-                    match single_result {
-                        Ok((urls, requests)) => {
-                            result.urls.extend(urls);
-                            result.total_requests += requests;
-                        }
-                        Err(e) => {
-                            result.errors.push(format!("Error processing sitemap: {}", e));
-                        }
-                    }
+            let guessed = sitemaps.is_empty();
+            result.sitemaps_found = if guessed {
+                self.probe_candidates(&normalized_url, &ctx, &mut result).await
+            } else {
+                sitemaps
+            };
+            let found = result.sitemaps_found.clone();
+            let mut none_exist = self.process_top_level_sitemaps(&found, base_url, &normalized_url, &ctx, &mut result).await && guessed;
+            if none_exist {
+                let linked = self.homepage_sitemaps(&normalized_url, &ctx, &mut result).await;
+                if !linked.is_empty() {
+                    info!("🦀 {} has no sitemap at the usual paths; following the {} its homepage links", base_url, linked.len());
+                    result.sitemaps_found.extend(linked.iter().cloned());
+                    none_exist = self.process_top_level_sitemaps(&linked, base_url, &normalized_url, &ctx, &mut result).await;
                 }
             }
-            Err(e) => {
-                result.errors.push(format!("Could not fetch robots.txt from {}: {}", robots_url, e));
+            if none_exist {
+                let error = SitemapError::NoSitemap { base_url: base_url.to_string() };
+                result.record_error(error.to_string(), error);
             }
         }
 
+        if let Some(max_urls) = cap_after {
+            if result.keep_first_urls(max_urls) {
+                ctx.url_budget_hit.store(true, Ordering::SeqCst);
+            }
+        }
+        result.url_limit_reached = ctx.url_budget_hit.load(Ordering::SeqCst);
+        result.truncated = !result.truncated_sitemaps.is_empty() || result.url_limit_reached;
+        result.streamed_urls = ctx.sink.as_ref().map_or(0, UrlSink::sent);
+        result.disallowed_urls = ctx.disallowed.load(Ordering::SeqCst);
+        result.interrupted = self.shutdown.is_requested();
+        if let (Some(timeout), true) = (self.site_timeout, ctx.deadline_hit.load(Ordering::SeqCst)) {
+            // Not interrupted: a checkpoint records the site as done, partial as it is
+            result.timed_out = true;
+            let error = SitemapError::Timeout { url: base_url.to_string(), message: format!("{} ran past its site deadline of {:?}; kept the URLs found by then", base_url, timeout) };
+            result.record_error(error.to_string(), error);
+        }
+        result.retries = ctx.retries.take();
+        result.fetch_stats = ctx.fetches.take();
+        result.total_requests += result.retries.iter().map(RetryRecord::extra_requests).sum::<usize>();
+        result.degradation_level = ctx.degradation.level();
+        result.degraded = result.degradation_level > 0;
+        result.memory = MemoryUsage {
+            url_bytes: url_set_bytes(&result.urls),
+            peak_buffer_bytes: ctx.buffers.peak(),
+            cache_bytes: ctx.cache_bytes.load(Ordering::SeqCst),
+        };
+        if let (Some(pool), Some(host)) = (&self.user_agents, &host) {
+            result.user_agent = pool.succeeded(host);
+        }
+        if let (Some(store), Some(host), false) = (&self.reputation, &host, self.is_offline()) {
+            if result.url_count() > 0 {
+                store.record_success(host, ctx.degradation.slots(), started_at);
+            }
+            if let Err(e) = store.save() {
+                warn!("🦀 Could not save host reputations: {}", e);
+            }
+        }
+        if self.deterministic {
+            result.sort_lists();
+        }
         result.parse_time = start_time.elapsed().as_secs_f64();
         Ok(result)
     }
 
+    /// How to approach `host` given what the reputation store remembers about it
+    fn host_advice(&self, host: Option<&str>) -> HostAdvice {
+        match (&self.reputation, host) {
+            (Some(store), Some(host)) if !self.is_offline() => {
+                let advice = store.advice(host);
+                if let Some(concurrency) = advice.concurrency {
+                    info!("🦀 {} throttled us recently, starting it with {} requests in flight", host, concurrency);
+                }
+                advice
+            }
+            _ => HostAdvice::default(),
+        }
+    }
+
+    /// Resolve DNS and complete the TLS handshake for the first `prewarm_hosts` distinct hosts.
+    ///
+    /// A HEAD for robots.txt leaves an idle pooled connection behind, so the real
+    /// fetch for that site skips straight to sending its request.
+    async fn prewarm(&self, robots_urls: Vec<String>) {
+        debug!("🦀 Pre-warming connections to {} hosts", robots_urls.len());
+        let futures: Vec<_> = robots_urls.iter()
+            .map(|robots_url| async move {
+                if let Some(dns) = &self.dns {
+                    if let Err(e) = dns.check(robots_url).await {
+                        debug!("🦀 Not pre-warming {}: {}", robots_url, e);
+                        return;
+                    }
+                }
+                let mut exchange = HarExchange::start("HEAD", robots_url);
+                match self.client.head(robots_url).send().await {
+                    Ok(resp) => {
+                        debug!("🦀 Pre-warmed connection for {}", robots_url);
+                        exchange.response(&resp);
+                    }
+                    Err(e) => {
+                        debug!("🦀 Pre-warming {} failed: {}", robots_url, e);
+                        exchange.note(e.to_string());
+                    }
+                }
+                if let Some(har) = &self.har {
+                    har.record(exchange);
+                }
+            })
+            .collect();
+        join_all(futures).await;
+    }
+
+    fn prewarm_targets(&self, base_urls: &[String]) -> Vec<String> {
+        if self.is_offline() || self.fetcher.is_some() {
+            return Vec::new();
+        }
+        let mut hosts = HashSet::new();
+        base_urls.iter()
+            .filter_map(|base_url| self.normalize_url(base_url).ok())
+            .filter(|normalized| {
+                Url::parse(normalized).ok().and_then(|u| u.host_str().map(str::to_string)).is_some_and(|host| hosts.insert(host))
+            })
+            .take(self.prewarm_hosts)
+            .map(|normalized| format!("{}/robots.txt", normalized.trim_end_matches('/')))
+            .collect()
+    }
+
     pub async fn parse_multiple_sites(&self, base_urls: Vec<String>) -> Result<Vec<ParsedSiteResult>, Box<dyn std::error::Error + Send + Sync>> {
+        self.parse_multiple_sites_with(base_urls, |result| result).await
+    }
+
+    /// Parse sites like [`parse_multiple_sites`](Self::parse_multiple_sites), handing
+    /// each result to `on_result` as soon as its site is done rather than once the
+    /// whole batch is. What `on_result` returns is collected in input order.
+    pub async fn parse_multiple_sites_with<T, F>(&self, base_urls: Vec<String>, on_result: F) -> Result<Vec<T>, Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: Fn(ParsedSiteResult) -> T + Sync,
+    {
+        let on_result = &on_result;
         let site_count = base_urls.len();
         info!("🦀 Rust parser starting to process {} sites concurrently with semaphore limit {}", site_count, self.max_concurrent);
         
         // Create semaphore to limit concurrent sites (exactly like Python)
         let semaphore = std::sync::Arc::new(Semaphore::new(self.max_concurrent));
+
+        // Sites in the batch that resolve to the same sitemap URL share a single fetch
+        let coalescer = (!self.deterministic).then(|| Arc::new(SitemapCoalescer::new()));
+
+        // Warm up connections for the first hosts while the batch gets going
+        let prewarm_targets = self.prewarm_targets(&base_urls);
         
         // Process sites concurrently with semaphore limit (matching Python exactly)
         let futures: Vec<_> = base_urls.into_iter()
             .enumerate()
             .map(|(i, base_url)| {
                 let semaphore_clone = semaphore.clone();
+                let coalescer = coalescer.clone();
                 async move {
                     // Acquire semaphore permit (same as Python's `async with semaphore:`)
-                    let _permit = semaphore_clone.acquire().await.map_err(|e| format!("Semaphore error: {}", e))?;
+                    let _permit = tokio::select! {
+                        permit = self.contention.wait_for_site(semaphore_clone.acquire()) => permit.map_err(|e| format!("Semaphore error: {}", e))?,
+                        _ = self.shutdown.requested() => return Ok(on_result(ParsedSiteResult::interrupted(base_url))),
+                    };
                     
                     info!("🦀 Starting site {}/{}: {}", i + 1, site_count, base_url);
-                    match self.parse_site(&base_url).await {
+                    let result = match self.parse_site_with(&base_url, coalescer, None).await {
                         Ok(result) => {
                             info!("🦀 Successfully parsed {}: {} URLs found", base_url, result.urls.len());
-                            Ok(result)
+                            result
                         },
                         Err(e) => {
                             error!("🦀 Failed to parse {}: {}", base_url, e);
-                            let mut error_result = ParsedSiteResult::new(base_url.clone());
-                            error_result.errors.push(format!("Failed to parse {}: {}", base_url, e));
-                            Ok(error_result)
+                            ParsedSiteResult::failed(base_url, e.as_ref())
                         }
-                    }
+                    };
+                    Ok(on_result(result))
                 }
             })
             .collect();
         
         // Wait for all sites to complete (same as Python's `await asyncio.gather()`)
-        let results: Result<Vec<_>, _> = join_all(futures).await.into_iter().collect();
+        let (_, site_results) = futures::join!(self.prewarm(prewarm_targets), join_all(futures));
+        let results: Result<Vec<_>, _> = site_results.into_iter().collect();
         
         info!("🦀 Rust parser completed processing all {} sites concurrently", site_count);
         results
     }
 
-    /// Parse specific sitemap URLs directly without robots.txt discovery
-    pub async fn parse_specific_sitemaps(&self, sitemap_urls: Vec<String>) -> Result<HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
-        info!("🦀 Starting to parse {} specific sitemap URLs", sitemap_urls.len());
-        
-        // Pre-compute base URLs to avoid borrowing issues
-        let url_pairs: Vec<(String, String)> = sitemap_urls.iter().map(|sitemap_url| {
-            let base_url = if let Ok(parsed_url) = url::Url::parse(sitemap_url) {
-                format!("{}://{}", parsed_url.scheme(), parsed_url.host_str().unwrap_or(""))
-            } else {
-                sitemap_url.clone() // fallback
-            };
-            (sitemap_url.clone(), base_url)
-        }).collect();
-        
-        // Process all sitemaps concurrently
-        let sitemap_futures: Vec<_> = url_pairs.iter().map(|(sitemap_url, base_url)| {
-            self.fetch_and_process_single_sitemap(sitemap_url, base_url, 1)
-        }).collect();
-
-        // Wait for all sitemaps to complete
-        let sitemap_results = join_all(sitemap_futures).await;
-        
-        let mut all_urls = HashSet::new();
-        let mut total_requests = 0;
-        
-        for (i, result) in sitemap_results.into_iter().enumerate() {
-            match result {
-                Ok((urls, requests)) => {
-                    debug!("🦀 Sitemap {}/{} found {} URLs", i + 1, sitemap_urls.len(), urls.len());
-                    all_urls.extend(urls);
-                    total_requests += requests;
-                }
+    /// Parse sites as `base_urls` yields them, yielding each result once it's done.
+    ///
+    /// At most `max_concurrent` sites are in flight, and the next base URL is only
+    /// pulled when one finishes, so neither the input nor the results have to fit in
+    /// memory. Results come out in completion order, or input order when deterministic.
+    /// Sitemaps aren't coalesced across sites, since a stream-wide table would only grow.
+    pub fn parse_site_stream<'a>(&'a self, base_urls: impl Stream<Item = String> + Send + 'a) -> BoxStream<'a, ParsedSiteResult> {
+        // An interrupt stops pulling base URLs; sites already pulled wind down as usual
+        let sites = base_urls.take_until(self.shutdown.requested()).map(move |base_url| async move {
+            match self.parse_site(&base_url).await {
+                Ok(result) => result,
                 Err(e) => {
-                    warn!("🦀 Failed to process sitemap {}: {}", sitemap_urls[i], e);
+                    error!("🦀 Failed to parse {}: {}", base_url, e);
+                    ParsedSiteResult::failed(base_url, e.as_ref())
                 }
             }
+        });
+        let limit = self.max_concurrent.max(1);
+        if self.deterministic {
+            sites.buffered(limit).boxed()
+        } else {
+            sites.buffer_unordered(limit).boxed()
         }
-        
-        info!("🦀 Completed parsing specific sitemaps: {} total URLs, {} requests", all_urls.len(), total_requests);
-        Ok(all_urls)
+    }
+
+    /// Parse sites like [`Self::parse_site_stream`], writing each one's URLs to `path`
+    /// as it finishes and returning only what was written.
+    pub async fn parse_sites_to_file(&self, base_urls: Vec<String>, path: &std::path::Path, format: ExportFormat) -> std::io::Result<ExportSummary> {
+        let mut exporter = UrlExporter::create(path, format)?;
+        let mut results = self.parse_site_stream(futures::stream::iter(base_urls));
+        while let Some(result) = results.next().await {
+            exporter.write_site(&result, self.deterministic)?;
+        }
+        let summary = exporter.finish()?;
+        info!("🦀 Exported {} URLs from {} sites to {} ({} bytes)", summary.urls, summary.sites, path.display(), summary.bytes_written);
+        Ok(summary)
+    }
+
+    /// Fetch only as much of a sitemap as it takes to identify it.
+    ///
+    /// Urlsets are abandoned once their root element shows up, which drops the
+    /// connection instead of downloading the rest; indexes are read in full.
+    async fn peek_sitemap(&self, url: &str) -> Result<PeekedSitemap, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(har) = &self.har else {
+            return self.peek_body(url, None).await;
+        };
+
+        let mut exchange = HarExchange::start("GET", url);
+        let result = self.peek_body(url, Some(&mut exchange)).await;
+        match &result {
+            Ok(peeked) if peeked.kind != SitemapKind::Index => {
+                exchange.note(format!("dry run stopped reading after {} bytes", peeked.bytes_read));
+            }
+            Ok(_) => {}
+            Err(e) => exchange.note(e.to_string()),
+        }
+        har.record(exchange);
+        result
+    }
+
+    async fn peek_body(&self, url: &str, mut har: Option<&mut HarExchange>) -> Result<PeekedSitemap, Box<dyn std::error::Error + Send + Sync>> {
+        if self.is_offline() {
+            let archived = self.archived_body(url).await?;
+            let content_type = archived.content_type.as_deref();
+            let kind = sniff_kind(&decode_body(&archived.bytes, PEEK_BYTES, true, content_type)?.text);
+            let text = if kind == SitemapKind::Index {
+                Some(decode_body(&archived.bytes, self.max_response_bytes, true, content_type)?.text)
+            } else {
+                None
+            };
+            if let Some(exchange) = har {
+                exchange.body(text.as_deref().unwrap_or(""), archived.bytes.len());
+            }
+            return Ok(PeekedSitemap { kind, content_length: Some(archived.content_length), bytes_read: archived.bytes.len(), text });
+        }
+
+        let host = Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string)).unwrap_or_default();
+        let _slot = self.contention.wait_for_request(self.request_limits.acquire(&host)).await;
+        let (mut resp, _) = self.send_following(&Method::GET, url, &HeaderMap::new()).await?;
+        if let Some(exchange) = har.as_deref_mut() {
+            exchange.response(&resp);
+        }
+        if !resp.status().is_success() {
+            return Err(format!("HTTP {} for {}", resp.status(), url).into());
+        }
+
+        let content_length = resp.content_length();
+        let content_type = resp.headers().get(reqwest::header::CONTENT_TYPE).and_then(|v| v.to_str().ok());
+        let mut decoder = BodyDecoder::new(self.max_response_bytes).with_content_type(content_type);
+        let mut bytes_read = 0;
+        let mut kind = SitemapKind::Unknown;
+        while let Some(chunk) = resp.chunk().await? {
+            bytes_read += chunk.len();
+            decoder.push(&chunk);
+            if kind == SitemapKind::Unknown {
+                let decoded = decoder.decoded();
+                kind = sniff_kind(&String::from_utf8_lossy(&decoded[..decoded.len().min(PEEK_BYTES)]));
+            }
+            let undecided = kind == SitemapKind::Unknown && bytes_read >= PEEK_BYTES;
+            if kind == SitemapKind::Urlset || undecided || bytes_read >= self.max_response_bytes || decoder.is_full() {
+                break;
+            }
+        }
+
+        let text = if kind == SitemapKind::Index {
+            Some(decoder.finish(true)?.text)
+        } else {
+            None
+        };
+        if let Some(exchange) = har {
+            exchange.body(text.as_deref().unwrap_or(""), bytes_read);
+        }
+        Ok(PeekedSitemap { kind, content_length, bytes_read, text })
+    }
+
+    /// Plan one sitemap and, for indexes, the children a real run would follow
+    async fn plan_sitemap(&self, sitemap_url: &str, base_url: &str, max_depth: usize) -> (Vec<PlannedSitemap>, u64, Vec<String>) {
+        let level = self.max_depth + 1 - max_depth;
+        let (peeked, mut bytes, mut errors) = match self.peek_sitemap(sitemap_url).await {
+            Ok(peeked) => {
+                let bytes = match peeked.kind {
+                    SitemapKind::Index => peeked.bytes_read as u64,
+                    _ => peeked.content_length.unwrap_or(0),
+                };
+                (Some(peeked), bytes, Vec::new())
+            }
+            Err(e) => (None, 0, vec![format!("Error planning sitemap {}: {}", sitemap_url, e)]),
+        };
+
+        let mut planned = vec![PlannedSitemap {
+            url: sitemap_url.to_string(),
+            kind: peeked.as_ref().map_or(SitemapKind::Failed, |p| p.kind),
+            level,
+            content_length: peeked.as_ref().and_then(|p| p.content_length),
+        }];
+
+        let Some(text) = peeked.and_then(|p| p.text) else {
+            return (planned, bytes, errors);
+        };
+        if max_depth <= 1 {
+            return (planned, bytes, errors);
+        }
+
+        let nested = match parse_sitemap_xml_with(&text, base_url, self.quirks) {
+            Ok(parsed) => parsed.nested_sitemaps,
+            Err(e) => {
+                errors.push(format!("Error planning sitemap {}: {}", sitemap_url, e));
+                return (planned, bytes, errors);
+            }
+        };
+        let futures: Vec<_> = nested.iter()
+            .take(self.max_nested_per_level)
+            .map(|nested_url| self.plan_sitemap(nested_url, base_url, max_depth - 1))
+            .collect();
+        for (children, child_bytes, child_errors) in join_all(futures).await {
+            planned.extend(children);
+            bytes += child_bytes;
+            errors.extend(child_errors);
+        }
+        (planned, bytes, errors)
+    }
+
+    /// Tell search engines about added or changed URLs through IndexNow, one submission per endpoint and batch
+    pub async fn submit_indexnow(&self, endpoints: &[String], key: &str, key_location: Option<&str>, urls: &[String]) -> Vec<IndexNowSubmission> {
+        submit_urls(&self.client, endpoints, key, key_location, urls).await
+    }
+
+    /// Parse each sitemap Search Console has for `property` and compare its URL count with
+    /// the one Google reports. Indexes are followed up to `max_depth`, so shallow limits
+    /// undercount them; pending sitemaps are parsed but have no count to compare.
+    #[cfg(feature = "search-console")]
+    pub async fn compare_search_console(
+        &self,
+        source: &SearchConsoleSource,
+        property: &str,
+    ) -> Result<Vec<SitemapCountComparison>, Box<dyn std::error::Error + Send + Sync>> {
+        let submitted = source.sitemaps(&self.client, property).await?;
+        info!("🦀 Search Console lists {} sitemaps for {}", submitted.len(), property);
+        let ctx = SiteContext::new(self);
+
+        let parsed = join_all(submitted.iter().map(|sitemap| {
+            self.fetch_and_process_single_sitemap(&sitemap.path, &sitemap.path, self.max_depth, &ctx)
+        }))
+        .await;
+
+        Ok(submitted
+            .into_iter()
+            .zip(parsed)
+            .map(|(sitemap, tree)| {
+                let (parsed_urls, error) = match tree {
+                    Ok(tree) => (Some(tree.urls.len()), None),
+                    Err(e) => (None, Some(e.to_string())),
+                };
+                let comparison = SitemapCountComparison { sitemap, parsed_urls, error };
+                if comparison.is_discrepancy() {
+                    warn!("🦀 {} has {:?} URLs, Search Console reports {}", comparison.sitemap.path, comparison.parsed_urls, comparison.sitemap.submitted_urls);
+                }
+                comparison
+            })
+            .collect())
+    }
+
+    /// The robots.txt of the site at `base_url`, parsed, from the robots cache when a
+    /// parse or an earlier call already fetched it.
+    ///
+    /// As RFC 9309 has it, a robots.txt answering 4xx (other than 429) allows
+    /// everything. Any other failure is returned, and not cached; the RFC says to treat
+    /// the site as disallowing everything until robots.txt can be read.
+    pub async fn fetch_robots(&self, base_url: &str) -> Result<Arc<RobotsTxt>, SitemapError> {
+        let normalized_url = self.normalize_url(base_url).map_err(|e| SitemapError::from_fetch(base_url, e.as_ref()))?;
+        let Some(origin) = RobotsCache::origin(&normalized_url) else {
+            return Err(SitemapError::InvalidUrl { url: base_url.to_string(), message: format!("{} has no host to fetch robots.txt from", base_url) });
+        };
+        if let Some(robots) = self.robots_cache.get(&origin) {
+            return Ok(robots);
+        }
+        let robots_url = format!("{}/robots.txt", origin);
+        if let Some(host) = Url::parse(&robots_url).ok().and_then(|url| url.host_str().map(str::to_string)) {
+            self.host_pacer.wait(&host, None).await;
+        }
+        match self.fetch_url(&robots_url, None, None).await {
+            Ok(body) => Ok(self.robots_cache.insert(&origin, RobotsTxt::parse(&body.text, &normalized_url))),
+            Err(e) => match SitemapError::from_fetch(&robots_url, e.as_ref()) {
+                error if allows_everything(&error) => Ok(self.robots_cache.insert(&origin, RobotsTxt::default())),
+                error => Err(SitemapError::RobotsFetch { url: robots_url, cause: Box::new(error) }),
+            },
+        }
+    }
+
+    /// Cache `robots`, the robots.txt of the site at `site_url`
+    fn remember_robots(&self, site_url: &str, robots: RobotsTxt) -> Arc<RobotsTxt> {
+        match RobotsCache::origin(site_url) {
+            Some(origin) => self.robots_cache.insert(&origin, robots),
+            None => Arc::new(robots),
+        }
+    }
+
+    /// Dry run: discover a site's sitemaps and expand its indexes, but only report
+    /// what a full parse would fetch instead of downloading any urlsets
+    pub async fn plan_site(&self, base_url: &str) -> Result<SitePlan, Box<dyn std::error::Error + Send + Sync>> {
+        let start_time = Instant::now();
+        let mut plan = SitePlan::new(base_url.to_string());
+
+        let normalized_url = self.normalize_url(base_url)?;
+        let robots_url = format!("{}/robots.txt", normalized_url.trim_end_matches('/'));
+        plan.estimated_requests += 1;
+        plan.requests_made += 1;
+
+        let sitemaps = match self.fetch_url(&robots_url, None, None).await {
+            Ok(robots_body) => {
+                plan.estimated_bytes += robots_body.bytes_read as u64;
+                parse_robots_txt(&robots_body.text, &normalized_url)
+            }
+            Err(e) => {
+                plan.errors.push(format!("Could not fetch robots.txt from {}: {}", robots_url, e));
+                plan.plan_time = start_time.elapsed().as_secs_f64();
+                return Ok(plan);
+            }
+        };
+        let sitemaps = if sitemaps.is_empty() {
+            self.discovery.candidates(&normalized_url)
+        } else {
+            sitemaps
+        };
+
+        let futures: Vec<_> = sitemaps.iter()
+            .take(self.max_sitemaps)
+            .map(|sitemap_url| self.plan_sitemap(sitemap_url, &normalized_url, self.max_depth))
+            .collect();
+        for (planned, bytes, errors) in join_all(futures).await {
+            plan.sitemaps.extend(planned);
+            plan.estimated_bytes += bytes;
+            plan.errors.extend(errors);
+        }
+
+        plan.estimated_requests += plan.sitemaps.len();
+        plan.requests_made += plan.sitemaps.len();
+        plan.unknown_sizes = plan.sitemaps.iter()
+            .filter(|s| s.kind != SitemapKind::Index && s.content_length.is_none())
+            .count();
+        plan.plan_time = start_time.elapsed().as_secs_f64();
+        Ok(plan)
+    }
+
+    /// Dry-run several sites, at most `max_concurrent` at a time
+    /// Estimate a site's URL count and download size within seconds.
+    ///
+    /// Plans the site (indexes in full, only the first chunk of each urlset), then
+    /// downloads `samples` urlsets spread across the plan to learn bytes per URL,
+    /// and scales that by every other urlset's Content-Length.
+    pub async fn estimate_site(&self, base_url: &str, samples: usize) -> Result<SiteEstimate, Box<dyn std::error::Error + Send + Sync>> {
+        let start_time = Instant::now();
+        let plan = self.plan_site(base_url).await?;
+        let mut estimate = SiteEstimate::new(base_url.to_string());
+        estimate.requests_made = plan.requests_made;
+        estimate.errors = plan.errors.clone();
+
+        let candidates = urlsets(&plan);
+        estimate.urlsets = candidates.len();
+        let picked: Vec<&str> = sample_indices(candidates.len(), samples).into_iter().map(|i| candidates[i].url.as_str()).collect();
+        let futures: Vec<_> = picked.iter()
+            .map(|url| async move {
+                let body = self.fetch_url(url, None, None).await?;
+                let parsed = parse_sitemap_xml_with(&body.text, base_url, self.quirks)?;
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(SampledSitemap { url: url.to_string(), urls: parsed.urls.len(), bytes: body.bytes_read })
+            })
+            .collect();
+        for (url, sampled) in picked.iter().zip(join_all(futures).await) {
+            estimate.requests_made += 1;
+            match sampled {
+                Ok(sample) => estimate.samples.push(sample),
+                Err(e) => estimate.errors.push(format!("Error sampling sitemap {}: {}", url, e)),
+            }
+        }
+
+        (estimate.estimated_urls, estimate.estimated_bytes) = extrapolate(&plan, &estimate.samples);
+        estimate.estimate_time = start_time.elapsed().as_secs_f64();
+        Ok(estimate)
+    }
+
+    pub async fn estimate_multiple_sites(&self, base_urls: Vec<String>, samples: usize) -> Vec<SiteEstimate> {
+        let semaphore = Semaphore::new(self.max_concurrent);
+        let futures: Vec<_> = base_urls.into_iter()
+            .map(|base_url| {
+                let semaphore = &semaphore;
+                async move {
+                    let _permit = semaphore.acquire().await;
+                    self.estimate_site(&base_url, samples).await.unwrap_or_else(|e| {
+                        let mut estimate = SiteEstimate::new(base_url.clone());
+                        estimate.errors.push(format!("Failed to estimate {}: {}", base_url, e));
+                        estimate
+                    })
+                }
+            })
+            .collect();
+        join_all(futures).await
+    }
+
+    pub async fn plan_multiple_sites(&self, base_urls: Vec<String>) -> Vec<SitePlan> {
+        let semaphore = Semaphore::new(self.max_concurrent);
+        let futures: Vec<_> = base_urls.into_iter()
+            .map(|base_url| {
+                let semaphore = &semaphore;
+                async move {
+                    let _permit = semaphore.acquire().await;
+                    self.plan_site(&base_url).await.unwrap_or_else(|e| {
+                        let mut plan = SitePlan::new(base_url.clone());
+                        plan.errors.push(format!("Failed to plan {}: {}", base_url, e));
+                        plan
+                    })
+                }
+            })
+            .collect();
+        join_all(futures).await
+    }
+
+    /// Parse specific sitemap URLs directly without robots.txt discovery
+    pub async fn parse_specific_sitemaps(&self, sitemap_urls: Vec<String>) -> Result<HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
+        info!("🦀 Starting to parse {} specific sitemap URLs", sitemap_urls.len());
+        
+        let ctx = SiteContext::new(self);
+
+        // Pre-compute base URLs to avoid borrowing issues
+        let url_pairs: Vec<(String, String)> = sitemap_urls.iter().map(|sitemap_url| {
+            let base_url = if let Ok(parsed_url) = url::Url::parse(sitemap_url) {
+                format!("{}://{}", parsed_url.scheme(), parsed_url.host_str().unwrap_or(""))
+            } else {
+                sitemap_url.clone() // fallback
+            };
+            (sitemap_url.clone(), base_url)
+        }).collect();
+        
+        // Process all sitemaps concurrently
+        let sitemap_futures: Vec<_> = url_pairs.iter().map(|(sitemap_url, base_url)| {
+            self.fetch_and_process_single_sitemap(sitemap_url, base_url, 1, &ctx)
+        }).collect();
+
+        // Wait for all sitemaps to complete
+        let sitemap_results = join_all(sitemap_futures).await;
+        
+        let mut all_urls = HashSet::new();
+        let mut total_requests = 0;
+        
+        for (i, result) in sitemap_results.into_iter().enumerate() {
+            match result {
+                Ok(tree) => {
+                    debug!("🦀 Sitemap {}/{} found {} URLs", i + 1, sitemap_urls.len(), tree.urls.len());
+                    all_urls.extend(tree.urls);
+                    total_requests += tree.requests;
+                }
+                Err(e) => {
+                    warn!("🦀 Failed to process sitemap {}: {}", sitemap_urls[i], e);
+                }
+            }
+        }
+        
+        info!("🦀 Completed parsing specific sitemaps: {} total URLs, {} requests", all_urls.len(), total_requests);
+        Ok(all_urls)
+    }
+
+    /// Parse a sitemap already in hand, raw or gzipped and in any supported format,
+    /// without the network. `base_url` is where the document came from; relative
+    /// nested sitemap references resolve against it.
+    ///
+    /// Nested sitemaps it lists are read through `fetch`, as deep as `max_depth` and
+    /// `max_nested_per_level` allow, and every nested reference is listed in
+    /// `sitemaps_found` whether or not it was read. Parse options, the URL hook,
+    /// `keep_bodies`, and `max_response_bytes` apply as they do to fetched sitemaps.
+    /// Fails if the document itself can't be decoded or parsed; nested sitemaps that
+    /// can't be are recorded in `errors`.
+    pub fn parse_sitemap_bytes(&self, content: &[u8], base_url: &str, fetch: Option<&dyn SitemapFetch>) -> Result<ParsedSiteResult, SitemapError> {
+        let start_time = Instant::now();
+        let mut result = ParsedSiteResult::new(base_url.to_string());
+        let mut seen = HashSet::from([base_url.to_string()]);
+        self.parse_local(content, base_url, 1, fetch, &mut seen, &mut result)?;
+        result.truncated = !result.truncated_sitemaps.is_empty();
+        result.memory = MemoryUsage { url_bytes: url_set_bytes(&result.urls), ..Default::default() };
+        if self.deterministic {
+            result.sort_lists();
+        }
+        result.parse_time = start_time.elapsed().as_secs_f64();
+        Ok(result)
+    }
+
+    /// Read the sitemap at `path` and parse it as [`Self::parse_sitemap_bytes`] does;
+    /// `base_url` defaults to the file's `file://` URL
+    pub fn parse_sitemap_file(&self, path: &Path, base_url: Option<&str>, fetch: Option<&dyn SitemapFetch>) -> Result<ParsedSiteResult, Box<dyn std::error::Error + Send + Sync>> {
+        let content = std::fs::read(path)?;
+        let base_url = match base_url {
+            Some(base_url) => base_url.to_string(),
+            None => file_url(path)?,
+        };
+        Ok(self.parse_sitemap_bytes(&content, &base_url, fetch)?)
+    }
+
+    /// Parse the local sitemap `sitemap_url` at nesting `level` into `result`, then
+    /// the nested sitemaps `fetch` has that weren't parsed yet
+    fn parse_local(
+        &self,
+        content: &[u8],
+        sitemap_url: &str,
+        level: usize,
+        fetch: Option<&dyn SitemapFetch>,
+        seen: &mut HashSet<String>,
+        result: &mut ParsedSiteResult,
+    ) -> Result<(), SitemapError> {
+        let unparsable = |e: Box<dyn std::error::Error + Send + Sync>| SitemapError::XmlParse { url: sitemap_url.to_string(), message: e.to_string() };
+        let body = decode_body(content, self.max_response_bytes, false, None).map_err(unparsable)?;
+        let parsed = parse_sitemap_body(&body.text, sitemap_url, None, &self.parse_options()).map_err(unparsable)?;
+        let SitemapParseResult { mut urls, nested_sitemaps, mut metadata, security_warnings, unmodified_urls, unmodified_sitemaps } = parsed;
+        if let Some(canonicalizer) = &self.canonicalizer {
+            result.duplicate_urls += canonicalizer.canonicalize_all(&mut urls, &mut metadata);
+        }
+        if let Some(hook) = &self.url_hook {
+            urls = apply_hook_blocking(hook.as_ref(), sitemap_url, urls, self.url_hook_batch_size)
+                .map_err(|e| SitemapError::Other { url: sitemap_url.to_string(), message: e.to_string() })?;
+        }
+        debug!("🦀 Parsed local sitemap {}: {} URLs, {} nested sitemaps", sitemap_url, urls.len(), nested_sitemaps.len());
+
+        if self.keeps_url_metadata() {
+            let here = urls.iter().map(|url| (url.clone(), UrlMetadata {
+                depth: level,
+                source_sitemap: Some(sitemap_url.to_string()),
+                ..metadata.remove(url).unwrap_or_default()
+            }));
+            merge_url_metadata(&mut result.url_metadata, here.collect());
+        }
+        result.duplicate_urls += extend_urls(&mut result.urls, urls);
+        result.security_warnings.extend(security_warnings.into_iter().map(|w| format!("{}: {}", sitemap_url, w)));
+        result.unmodified_urls += unmodified_urls;
+        result.unmodified_sitemaps += unmodified_sitemaps;
+        if body.decompressed {
+            result.decompressed_sitemaps.push(sitemap_url.to_string());
+        }
+        if let Some(encoding) = body.encoding {
+            result.transcoded_sitemaps.insert(sitemap_url.to_string(), encoding.to_string());
+        }
+        if body.truncated {
+            result.truncated_sitemaps.push(TruncatedSitemap {
+                url: sitemap_url.to_string(),
+                bytes_read: content.len(),
+                content_length: Some(content.len() as u64),
+                reason: TruncationReason::SizeLimit,
+            });
+        }
+        if self.keep_bodies {
+            result.raw_bodies.insert(sitemap_url.to_string(), body.text);
+        }
+        for nested in &nested_sitemaps {
+            if !result.sitemaps_found.contains(nested) {
+                result.sitemaps_found.push(nested.clone());
+            }
+        }
+
+        let Some(fetch) = fetch else { return Ok(()) };
+        if level >= self.max_depth {
+            return Ok(());
+        }
+        for nested in nested_sitemaps.iter().take(self.max_nested_per_level) {
+            if !seen.insert(nested.clone()) {
+                continue;
+            }
+            let outcome = match fetch.fetch(nested) {
+                Ok(Some(content)) => self.parse_local(&content, nested, level + 1, Some(fetch), seen, result),
+                Ok(None) => Err(SitemapError::Other { url: nested.clone(), message: format!("No stored copy of {}", nested) }),
+                Err(e) => Err(SitemapError::Other { url: nested.clone(), message: format!("Failed to read {}: {}", nested, e) }),
+            };
+            if let Err(e) = outcome {
+                result.record_error(format!("Error processing sitemap: {}", e), e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn parser() -> RustSitemapParser {
+        RustSitemapParser::new(10, 10, 2, 5, Duration::from_secs(5))
+    }
+
+    /// A request as the test server read it
+    struct Request {
+        method: String,
+        path: String,
+        /// The header lines, lowercased
+        headers: String,
+        /// The server's own `http://127.0.0.1:port`
+        site: String,
+    }
+
+    impl Request {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers.lines().find_map(|line| line.strip_prefix(name)?.strip_prefix(':')).map(str::trim)
+        }
+    }
+
+    /// A status, extra headers, and a body to answer with, or `None` to leave the request hanging
+    type Reply = Option<(u16, Vec<(&'static str, String)>, Vec<u8>)>;
+
+    fn reply(status: u16, headers: Vec<(&'static str, String)>, body: impl Into<Vec<u8>>) -> Reply {
+        Some((status, headers, body.into()))
+    }
+
+    fn ok(body: impl Into<Vec<u8>>) -> Reply {
+        reply(200, Vec::new(), body)
+    }
+
+    /// Serves HTTP/1.1 on a local port, answering each connection with `route` and closing it; returns the base URL
+    async fn serve(route: impl FnMut(&Request) -> Reply + Send + 'static) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let (route, site) = (Arc::new(Mutex::new(route)), base_url.clone());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let (route, site) = (Arc::clone(&route), site.clone());
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    let n = stream.read(&mut buf).await.unwrap_or(0);
+                    let text = String::from_utf8_lossy(&buf[..n]);
+                    let (line, headers) = text.split_once("\r\n").unwrap_or((&text, ""));
+                    let mut parts = line.split(' ').map(String::from);
+                    let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+                    let request = Request { method, path, headers: headers.to_lowercase(), site };
+                    let reply = {
+                        let mut route = route.lock().unwrap();
+                        route(&request)
+                    };
+                    let Some((status, headers, body)) = reply else {
+                        return tokio::time::sleep(Duration::from_secs(30)).await;
+                    };
+                    let mut head = format!("HTTP/1.1 {}\r\n", reqwest::StatusCode::from_u16(status).unwrap());
+                    for (name, value) in headers {
+                        head.push_str(&format!("{}: {}\r\n", name, value));
+                    }
+                    head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", body.len()));
+                    let body = if request.method == "HEAD" { &[][..] } else { &body[..] };
+                    let _ = stream.write_all(&[head.as_bytes(), body].concat()).await;
+                });
+            }
+        });
+        base_url
+    }
+
+    #[tokio::test]
+    async fn test_plan_site_expands_indexes_only() {
+        let config = crate::synthetic::SyntheticConfig {
+            urls_per_sitemap: 5000,
+            depth: 2,
+            fanout: 3,
+            ..Default::default()
+        };
+        let server = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+
+        let plan = parser().plan_site(&server.base_url()).await.unwrap();
+        assert!(plan.errors.is_empty(), "{:?}", plan.errors);
+        let kinds: Vec<_> = plan.sitemaps.iter().map(|s| (s.kind, s.level)).collect();
+        assert_eq!(kinds, vec![
+            (SitemapKind::Index, 1),
+            (SitemapKind::Urlset, 2),
+            (SitemapKind::Urlset, 2),
+            (SitemapKind::Urlset, 2),
+        ]);
+        assert_eq!(plan.estimated_requests, 5);
+        assert_eq!(plan.unknown_sizes, 0);
+        assert!(plan.estimated_bytes > 3 * 5000 * 50);
+    }
+
+    #[tokio::test]
+    async fn test_progress_events() {
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<crate::progress::ProgressEvent>>);
+
+        impl ProgressListener for Recorder {
+            fn on_progress(&self, event: crate::progress::ProgressEvent) {
+                self.0.lock().unwrap().push(event);
+            }
+        }
+
+        let config = crate::synthetic::SyntheticConfig { urls_per_sitemap: 10, depth: 2, fanout: 3, ..Default::default() };
+        let server = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+        let recorder = Arc::new(Recorder::default());
+        let parser = parser().with_progress(Some(recorder.clone()));
+
+        parser.parse_site(&server.base_url()).await.unwrap();
+        let events = std::mem::take(&mut *recorder.0.lock().unwrap());
+        let kinds: Vec<String> = events.iter().map(|e| e.kind.to_string()).collect();
+        assert_eq!(kinds, ["site_started", "sitemap_fetched", "sitemap_fetched", "sitemap_fetched", "sitemap_fetched", "site_finished"]);
+        assert_eq!(events[1].sitemap_url.as_deref(), Some(format!("{}sitemap.xml", server.base_url()).as_str()));
+        assert_eq!((events[4].urls, events[4].sitemaps), (30, 4));
+        assert_eq!((events[5].urls, events[5].sitemaps, events[5].errors), (30, 4, 0));
+
+        // Nothing listens on a port that was just freed
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let base_url = format!("http://{}", closed);
+        parser.parse_site(&base_url).await.unwrap();
+        let events = std::mem::take(&mut *recorder.0.lock().unwrap());
+        assert_eq!(events.iter().map(|e| e.kind).collect::<Vec<_>>(), [ProgressKind::SiteStarted, ProgressKind::Error, ProgressKind::SiteFinished]);
+        assert_eq!(events[1].sitemap_url, Some(format!("{}/robots.txt", base_url)));
+        assert!(events[1].message.is_some());
+        assert_eq!(events[2].errors, 1);
+    }
+
+    #[tokio::test]
+    async fn test_har_records_every_exchange() {
+        let config = crate::synthetic::SyntheticConfig {
+            urls_per_sitemap: 10,
+            depth: 2,
+            fanout: 2,
+            ..Default::default()
+        };
+        let server = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+        let har = Arc::new(HarRecorder::new());
+        let parser = parser().with_har(Some(Arc::clone(&har)));
+
+        parser.parse_site(&server.base_url()).await.unwrap();
+
+        let log = har.to_json();
+        let entries = log["log"]["entries"].as_array().unwrap();
+        // robots.txt, the index, and its two urlsets
+        assert_eq!(entries.len(), 4);
+        assert!(entries.iter().all(|e| e["response"]["status"] == 200));
+        assert!(entries.iter().any(|e| e["response"]["content"]["text"].as_str().unwrap().contains("<urlset")));
+    }
+
+    #[tokio::test]
+    async fn test_sorted_result_lists() {
+        let mut result = ParsedSiteResult::new("https://example.com".to_string());
+        result.urls.extend(["https://example.com/b".to_string(), "https://example.com/a".to_string()]);
+        result.errors = vec!["z failed".to_string(), "a failed".to_string()];
+        result.sitemaps_found = ["https://example.com/b.xml", "https://example.com/a.xml", "https://example.com/b.xml"].map(String::from).into();
+        result.bing_sitemaps = ["https://example.com/z.xml", "https://example.com/y.xml"].map(String::from).into();
+        result.sort_lists();
+
+        assert_eq!(result.url_list(true), vec!["https://example.com/a", "https://example.com/b"]);
+        assert_eq!(result.errors, vec!["a failed", "z failed"]);
+        assert_eq!(result.sitemaps_found, ["https://example.com/a.xml", "https://example.com/b.xml"]);
+        assert_eq!(result.bing_sitemaps, ["https://example.com/y.xml", "https://example.com/z.xml"]);
+
+        // Listed out of order and twice, but every run reports them alike
+        let base_url = serve(|request| match request.path.as_str() {
+            "/robots.txt" => ok(format!("Sitemap: {0}/b.xml\nSitemap: {0}/a.xml\nSitemap: {0}/b.xml\n", request.site)),
+            page => ok(format!("<urlset><url><loc>{}{}</loc></url></urlset>", request.site, page.trim_end_matches(".xml"))),
+        })
+        .await;
+        let parser = parser().with_deterministic(true);
+        let first = parser.parse_site(&base_url).await.unwrap();
+        let second = parser.parse_site(&base_url).await.unwrap();
+        assert_eq!(first.sitemaps_found, [format!("{}/a.xml", base_url), format!("{}/b.xml", base_url)]);
+        assert_eq!(first.sitemaps_found, second.sitemaps_found);
+        assert_eq!(first.bing_sitemaps, second.bing_sitemaps);
+    }
+
+    #[tokio::test]
+    async fn test_estimate_site_matches_full_parse() {
+        let config = crate::synthetic::SyntheticConfig {
+            urls_per_sitemap: 400,
+            depth: 2,
+            fanout: 4,
+            ..Default::default()
+        };
+        let server = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+
+        let estimate = parser().estimate_site(&server.base_url(), 2).await.unwrap();
+        assert!(estimate.errors.is_empty(), "{:?}", estimate.errors);
+        assert_eq!(estimate.urlsets, 4);
+        assert_eq!(estimate.samples.len(), 2);
+        // Every urlset has the same shape, so the estimate lands within rounding of the truth
+        assert!(estimate.estimated_urls.abs_diff(1600) <= 4, "{}", estimate.estimated_urls);
+        assert_eq!(estimate.requests_made, 1 + 5 + 2);
+    }
+
+    #[tokio::test]
+    async fn test_warc_output_replays_as_input() {
+        let config = crate::synthetic::SyntheticConfig {
+            urls_per_sitemap: 10,
+            depth: 2,
+            fanout: 2,
+            ..Default::default()
+        };
+        let path = std::env::temp_dir().join(format!("sitemap-parser-{}.warc.gz", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (base_url, live) = {
+            let server = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+            let warc = Arc::new(WarcWriter::create(&path).unwrap());
+            let result = parser().with_warc_output(Some(warc)).parse_site(&server.base_url()).await.unwrap();
+            (server.base_url(), result)
+        };
+
+        // The server is gone, so everything has to come out of the archive
+        let archive = WarcArchive::load(&[&path]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(archive.len(), 4);
+        let replayed = parser().with_archive(Some(Arc::new(archive))).parse_site(&base_url).await.unwrap();
+        assert!(replayed.errors.is_empty(), "{:?}", replayed.errors);
+        assert_eq!(replayed.urls, live.urls);
+        assert_eq!(live.urls.len(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_url_metadata_records_depth() {
+        let config = crate::synthetic::SyntheticConfig {
+            urls_per_sitemap: 5,
+            depth: 2,
+            fanout: 2,
+            ..Default::default()
+        };
+        let server = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+
+        let plain = parser().parse_site(&server.base_url()).await.unwrap();
+        assert!(plain.url_metadata.is_empty());
+
+        let result = parser().with_url_metadata(true).parse_site(&server.base_url()).await.unwrap();
+        assert_eq!(result.url_metadata.len(), 10);
+        assert!(result.url_metadata.values().all(|m| m.depth == 2 && m.lastmod.as_deref() == Some("2024-01-01")));
+        // Each of the index's two urlsets is the source of its own five URLs
+        let sources: HashSet<_> = result.url_metadata.values().map(|m| m.source_sitemap.as_deref().unwrap()).collect();
+        assert_eq!(sources.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_raw_bodies_kept_and_stored() {
+        let config = crate::synthetic::SyntheticConfig {
+            urls_per_sitemap: 5,
+            depth: 2,
+            fanout: 2,
+            ..Default::default()
+        };
+        let server = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+        let dir = std::env::temp_dir().join(format!("sitemap-bodies-{}", uuid::Uuid::new_v4()));
+        let store = Arc::new(BodyStore::create(&dir).unwrap());
+
+        let plain = parser().parse_site(&server.base_url()).await.unwrap();
+        assert!(plain.raw_bodies.is_empty());
+
+        let result = parser()
+            .with_keep_bodies(true)
+            .with_body_store(Some(store))
+            .parse_site(&server.base_url())
+            .await
+            .unwrap();
+        assert_eq!(result.raw_bodies.len(), 3);
+        for (url, body) in &result.raw_bodies {
+            let stored = std::fs::read_to_string(dir.join(crate::bodies::body_file_name(url))).unwrap();
+            assert_eq!(&stored, body);
+        }
+        let index = std::fs::read_to_string(dir.join("index.jsonl")).unwrap();
+        assert_eq!(index.lines().count(), 3);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_throttling_host_is_remembered() {
+        // Answers every request with a 429 asking for an hour's break
+        let base_url = serve(|_| reply(429, vec![("Retry-After", "3600".into())], "")).await;
+        let path = std::env::temp_dir().join(format!("sitemap-reputation-{}.json", uuid::Uuid::new_v4()));
+
+        let store = Arc::new(ReputationStore::open(&path).unwrap());
+        let first = parser().with_reputation(Some(store)).parse_site(&base_url).await.unwrap();
+        assert!(first.total_requests == 0 && !first.errors.is_empty());
+
+        // A later run reads the file and leaves the host alone
+        let store = Arc::new(ReputationStore::open(&path).unwrap());
+        assert_eq!(store.get("127.0.0.1").unwrap().throttle_status, Some(429));
+        let second = parser().with_reputation(Some(store)).parse_site(&base_url).await.unwrap();
+        assert_eq!(second.errors.len(), 1);
+        assert!(second.errors[0].starts_with(&format!("Skipped {}: it asked for no requests until", base_url)));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_user_agent_rotates_past_challenges() {
+        // Serves a one-URL site, but only to the "good" agent
+        let base_url = serve(|request| match (request.header("user-agent"), request.path.as_str()) {
+            (Some("good"), "/robots.txt") => ok(format!("Sitemap: {}/sitemap.xml\n", request.site)),
+            (Some("good"), _) => ok(format!(r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"><url><loc>{}/page</loc></url></urlset>"#, request.site)),
+            _ => reply(403, Vec::new(), ""),
+        })
+        .await;
+
+        let pool = UserAgentPool::new(vec!["bad".to_string(), "good".to_string(), "worse".to_string()]).unwrap();
+        let result = parser().with_user_agents(Some(Arc::new(pool))).parse_site(&base_url).await.unwrap();
+        assert_eq!(result.urls.len(), 1, "{:?}", result.errors);
+        assert_eq!(result.user_agent.as_deref(), Some("good"));
+
+        let without = parser().parse_site(&base_url).await.unwrap();
+        assert!(without.urls.is_empty() && without.user_agent.is_none());
+    }
+
+    #[test]
+    fn test_parse_sitemap_bytes_resolves_nested_through_fetch() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let index = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+            <sitemap><loc>/posts.xml</loc></sitemap>
+            <sitemap><loc>https://example.com/gone.xml</loc></sitemap>
+        </sitemapindex>"#;
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(index.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        let fetch = |url: &str| -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>> {
+            Ok((url == "https://example.com/posts.xml").then(|| br#"<urlset><url><loc>https://example.com/posts/1</loc></url></urlset>"#.to_vec()))
+        };
+
+        let parser = parser().with_url_metadata(true);
+        let result = parser.parse_sitemap_bytes(&gzipped, "https://example.com/sitemap.xml.gz", Some(&fetch)).unwrap();
+        assert_eq!(result.urls, HashSet::from(["https://example.com/posts/1".to_string()]));
+        assert_eq!(result.sitemaps_found, ["https://example.com/posts.xml", "https://example.com/gone.xml"]);
+        assert_eq!(result.decompressed_sitemaps, ["https://example.com/sitemap.xml.gz"]);
+        assert_eq!(result.url_metadata["https://example.com/posts/1"].depth, 2);
+        assert_eq!(result.error_details, [SitemapError::Other {
+            url: "https://example.com/gone.xml".to_string(),
+            message: "No stored copy of https://example.com/gone.xml".to_string(),
+        }]);
+        assert_eq!(result.total_requests, 0);
+
+        // Without a fetch, nested sitemaps are only listed
+        let result = parser.parse_sitemap_bytes(index.as_bytes(), "https://example.com/sitemap.xml", None).unwrap();
+        assert!(result.urls.is_empty() && result.errors.is_empty());
+        assert_eq!(result.sitemaps_found.len(), 2);
+
+        // A gzip header over garbage
+        let err = parser.parse_sitemap_bytes(b"\x1f\x8b\x08\x00garbage", "https://example.com/broken.xml.gz", None).unwrap_err();
+        assert_eq!(err.kind(), "xml_parse");
+
+        let path = std::env::temp_dir().join(format!("sitemap-local-{}.xml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "https://example.com/a\nhttps://example.com/b\n").unwrap();
+        let result = parser.parse_sitemap_file(&path, None, None).unwrap();
+        assert!(result.base_url.starts_with("file://"));
+        assert_eq!(result.urls.len(), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_custom_fetcher_replaces_the_client() {
+        use crate::fetcher::{FetchResponse, Fetcher};
+        use futures::future::BoxFuture;
+
+        /// Serves a site from memory, recording the URLs asked for
+        struct Mirror(Mutex<Vec<String>>);
+
+        impl Fetcher for Mirror {
+            fn fetch<'a>(&'a self, url: &'a str, _headers: &'a HeaderMap) -> BoxFuture<'a, Result<FetchResponse, Box<dyn std::error::Error + Send + Sync>>> {
+                self.0.lock().unwrap().push(url.to_string());
+                let response = http::Response::builder();
+                let response = match url {
+                    "https://mirror.test/robots.txt" => response.body("Sitemap: https://mirror.test/old.xml\n".into()),
+                    "https://mirror.test/old.xml" => response.status(301).header("location", "/sitemap.xml").body(reqwest::Body::from("")),
+                    "https://mirror.test/sitemap.xml" => response.body(r#"<urlset><url><loc>https://mirror.test/page</loc></url></urlset>"#.into()),
+                    _ => response.status(404).body(reqwest::Body::from("")),
+                };
+                Box::pin(async move { Ok(response?) })
+            }
+        }
+
+        let mirror = Arc::new(Mirror(Mutex::new(Vec::new())));
+        let parser = parser().with_fetcher(Some(mirror.clone()));
+        let result = parser.parse_site("https://mirror.test").await.unwrap();
+
+        assert_eq!(result.urls, HashSet::from(["https://mirror.test/page".to_string()]));
+        assert_eq!(result.redirects["https://mirror.test/old.xml"].final_url, "https://mirror.test/sitemap.xml");
+        assert_eq!(*mirror.0.lock().unwrap(), ["https://mirror.test/robots.txt", "https://mirror.test/old.xml", "https://mirror.test/sitemap.xml"]);
+        assert!(result.fetch_stats.iter().all(|stats| stats.status == Some(200)));
+        assert!(parser.prewarm_targets(&["https://mirror.test".to_string()]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_stats_are_recorded() {
+        let robots = |site: &str| format!("Sitemap: {0}/sitemap.xml\nSitemap: {0}/missing.xml\n", site);
+        let sitemap = |site: &str| format!(r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"><url><loc>{}/page</loc></url></urlset>"#, site);
+        let server = serve(move |request| {
+            let site = request.site.replace("127.0.0.1", "localhost");
+            match request.path.as_str() {
+                "/robots.txt" => ok(robots(&site)),
+                "/sitemap.xml" => ok(sitemap(&site)),
+                _ => reply(404, Vec::new(), ""),
+            }
+        })
+        .await;
+        // By name, so the host is resolved
+        let base_url = server.replace("127.0.0.1", "localhost");
+        let (robots_len, sitemap_len) = (robots(&base_url).len(), sitemap(&base_url).len());
+
+        let mut result = parser().parse_site(&base_url).await.unwrap();
+        result.sort_lists();
+        let urls: Vec<_> = result.fetch_stats.iter().map(|stats| stats.url.as_str()).collect();
+        assert_eq!(urls, [format!("{}/missing.xml", base_url), format!("{}/robots.txt", base_url), format!("{}/sitemap.xml", base_url)]);
+        let [missing, robots, sitemap] = &result.fetch_stats[..] else { unreachable!() };
+        assert_eq!((robots.status, robots.bytes), (Some(200), robots_len));
+        assert_eq!((sitemap.status, sitemap.bytes), (Some(200), sitemap_len));
+        // Every response closed its connection, so each fetch looked up the host and connected
+        for stats in &result.fetch_stats {
+            assert!(stats.dns.is_some() && stats.connect.is_some(), "{:?}", stats);
+            assert!(stats.ttfb.unwrap() <= stats.total);
+            assert_eq!((stats.retries, stats.redirects, stats.cache_hit), (0, 0, false));
+        }
+        assert_eq!((missing.status, missing.bytes, missing.error.as_deref()), (Some(404), 0, Some("http")));
+        assert_eq!(robots.error, None);
+        assert_eq!(result.bytes_downloaded(), robots_len + sitemap_len);
+    }
+
+    #[tokio::test]
+    async fn test_retries_are_recorded() {
+        // sitemap.xml answers 503 once and then works; broken.xml always answers 500
+        let mut sitemap_requests = 0;
+        let base_url = serve(move |request| match request.path.as_str() {
+            "/robots.txt" => ok(format!("Sitemap: {0}/sitemap.xml\nSitemap: {0}/broken.xml\n", request.site)),
+            "/sitemap.xml" => {
+                sitemap_requests += 1;
+                match sitemap_requests {
+                    1 => reply(503, Vec::new(), ""),
+                    _ => ok(format!(r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"><url><loc>{}/page</loc></url></urlset>"#, request.site)),
+                }
+            }
+            _ => reply(500, Vec::new(), ""),
+        })
+        .await;
+
+        let policy = RetryPolicy { max_retries: 2, base_backoff: Duration::from_millis(50), jitter: 0.0, ..Default::default() };
+        let mut result = parser().with_retry_policy(policy.clone()).parse_site(&base_url).await.unwrap();
+        result.sort_lists();
+        assert_eq!(result.urls.len(), 1, "{:?}", result.errors);
+        assert_eq!(result.retries.len(), 2);
+        let broken = &result.retries[0];
+        assert!(broken.url.ends_with("/broken.xml") && !broken.recovered);
+        assert_eq!(broken.attempts.iter().map(|a| a.error.as_str()).collect::<Vec<_>>(), ["http 500"; 3]);
+        assert_eq!(broken.attempts.iter().map(|a| a.backoff).collect::<Vec<_>>(), [Duration::from_millis(50), Duration::from_millis(100), Duration::ZERO]);
+        let recovered = &result.retries[1];
+        assert!(recovered.url.ends_with("/sitemap.xml") && recovered.recovered);
+        assert_eq!(recovered.attempts.len(), 1);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains("HTTP 500 Internal Server Error for"), "{:?}", result.errors);
+
+        let without = parser().parse_site(&base_url).await.unwrap();
+        assert!(without.retries.is_empty());
+
+        // With only 503s retried, the 500s give up after one attempt and leave no record
+        let only_503 = RetryPolicy { retry_statuses: vec![503], ..policy };
+        let result = parser().with_retry_policy(only_503).parse_site(&base_url).await.unwrap();
+        assert!(result.retries.is_empty());
+        assert_eq!(result.errors.len(), 1, "{:?}", result.errors);
+    }
+
+    #[tokio::test]
+    async fn test_error_details_tell_missing_sitemaps_from_network_failures() {
+        let base_url = serve(|request| match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/robots.txt") => ok("User-agent: *"),
+            _ => reply(404, Vec::new(), ""),
+        })
+        .await;
+
+        // HEAD probes rule every candidate out without fetching it
+        let result = parser().parse_site(&base_url).await.unwrap();
+        let kinds: Vec<&str> = result.error_details.iter().map(SitemapError::kind).collect();
+        assert_eq!(kinds, ["no_sitemap"], "{:?}", result.errors);
+        assert!(result.sitemaps_found.is_empty());
+
+        let unprobed = parser().with_discovery(DiscoveryPolicy { probe: false, ..Default::default() });
+        let result = unprobed.parse_site(&base_url).await.unwrap();
+        let kinds: Vec<&str> = result.error_details.iter().map(SitemapError::kind).collect();
+        assert_eq!(kinds, ["http", "http", "http", "http", "http", "no_sitemap"], "{:?}", result.errors);
+        assert!(result.error_details[..5].iter().all(SitemapError::is_not_found));
+        assert_eq!(result.errors.len(), result.error_details.len());
+        assert_eq!(result.errors[5], result.error_details[5].to_string());
+
+        // Nothing listens on a port freed up again
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let result = parser().parse_site(&format!("http://{}", closed)).await.unwrap();
+        match result.error_details.as_slice() {
+            [SitemapError::RobotsFetch { cause, .. }] => assert_eq!(cause.kind(), "network", "{:?}", cause),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_discovery_falls_back_to_homepage_links() {
+        // robots.txt fails, HEAD isn't allowed, and only the homepage knows the sitemap
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&requests);
+        let base_url = serve(move |request| {
+            log.lock().unwrap().push(format!("{} {}", request.method, request.path));
+            match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/robots.txt") => reply(500, Vec::new(), ""),
+                ("HEAD", _) => reply(405, Vec::new(), ""),
+                ("GET", "/") => ok(r#"<html><head><link rel="sitemap" href="/s/main.xml"></head></html>"#),
+                ("GET", "/s/main.xml") => ok(format!("<urlset><url><loc>{}/page</loc></url></urlset>", request.site)),
+                _ => reply(404, Vec::new(), ""),
+            }
+        })
+        .await;
+
+        let discovery = DiscoveryPolicy { candidate_paths: vec!["/sitemap.xml".to_string()], ..Default::default() };
+        let result = parser().with_discovery(discovery).parse_site(&base_url).await.unwrap();
+        assert_eq!(result.urls, HashSet::from([format!("{}/page", base_url)]));
+        assert_eq!(result.sitemaps_found, [format!("{}/sitemap.xml", base_url), format!("{}/s/main.xml", base_url)]);
+        let kinds: Vec<&str> = result.error_details.iter().map(SitemapError::kind).collect();
+        assert_eq!(kinds, ["robots_fetch", "http"], "{:?}", result.errors);
+        assert_eq!(*requests.lock().unwrap(), ["GET /robots.txt", "HEAD /sitemap.xml", "GET /sitemap.xml", "GET /", "GET /s/main.xml"]);
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_winds_down_within_grace() {
+        // Accepts connections and never answers
+        let base_url = serve(|_| None).await;
+
+        let shutdown = Arc::new(Shutdown::new());
+        let parser = RustSitemapParser::new(1, 10, 2, 5, Duration::from_secs(30)).with_shutdown(Arc::clone(&shutdown));
+        let started = Instant::now();
+        let interrupt = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            shutdown.request(Duration::from_millis(100));
+        };
+        let (results, _) = tokio::join!(parser.parse_multiple_sites(vec![base_url.clone(), base_url]), interrupt);
+        let results = results.unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(results.iter().all(|r| r.interrupted));
+        assert!(results[0].errors[0].contains("Interrupted while fetching"), "{:?}", results[0].errors);
+        assert!(results[1].errors[0].starts_with("Not parsed"), "{:?}", results[1].errors);
+        assert!(parser.is_interrupted());
+    }
+
+    #[tokio::test]
+    async fn test_redirects_follow_policy_and_are_recorded() {
+        use crate::redirect::RedirectHop;
+        let base_url = serve(|request| {
+            let site = &request.site;
+            match request.path.as_str() {
+                "/robots.txt" => reply(301, vec![("Location", "/moved/robots.txt".into())], ""),
+                "/moved/robots.txt" => ok(format!("Sitemap: {0}/a.xml\nSitemap: {0}/b.xml", site)),
+                "/a.xml" => reply(302, vec![("Location", "/real/a.xml".into())], ""),
+                // Same server, another host name
+                "/b.xml" => reply(301, vec![("Location", format!("{}/real/b.xml", site.replace("127.0.0.1", "localhost")))], ""),
+                page => ok(format!("<urlset><url><loc>{}{}</loc></url></urlset>", site, page.trim_end_matches(".xml"))),
+            }
+        })
+        .await;
+
+        let result = parser().parse_site(&base_url).await.unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let expected: HashSet<String> = [format!("{}/real/a", base_url), format!("{}/real/b", base_url)].into();
+        assert_eq!(result.urls, expected);
+        let a = &result.redirects[&format!("{}/a.xml", base_url)];
+        assert_eq!(a.hops, [RedirectHop { url: format!("{}/a.xml", base_url), status: 302 }]);
+        assert_eq!(a.final_url, format!("{}/real/a.xml", base_url));
+        assert_eq!(result.redirects[&format!("{}/robots.txt", base_url)].final_url, format!("{}/moved/robots.txt", base_url));
+        assert_eq!(result.redirects[&format!("{}/b.xml", base_url)].final_url, format!("{}/real/b.xml", base_url.replace("127.0.0.1", "localhost")));
+        // Each redirect was a request of its own
+        assert_eq!(result.total_requests, 6);
+
+        let same_host = parser().with_redirect_policy(RedirectPolicy { cross_host: false, ..Default::default() });
+        let result = same_host.parse_site(&base_url).await.unwrap();
+        assert_eq!(result.urls, HashSet::from([format!("{}/real/a", base_url)]));
+        let kinds: Vec<_> = result.error_details.iter().map(SitemapError::kind).collect();
+        assert_eq!(kinds, ["redirect_refused"]);
+
+        // Without robots.txt, the usual locations are probed instead
+        let none = parser().with_redirect_policy(RedirectPolicy { max_redirects: 0, ..Default::default() });
+        let result = none.parse_site(&base_url).await.unwrap();
+        assert!(matches!(&result.error_details[0], SitemapError::RobotsFetch { cause, .. } if cause.kind() == "too_many_redirects"));
+        assert_eq!(result.sitemaps_found[0], format!("{}/sitemap.xml", base_url));
+        assert!(result.urls.contains(&format!("{}/sitemap", base_url)));
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_resumes_interrupted_site() {
+        use std::sync::atomic::AtomicBool;
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        // b.xml never answers until this is cleared
+        let stall = Arc::new(AtomicBool::new(true));
+        let (log, stalling) = (Arc::clone(&requested), Arc::clone(&stall));
+        let base_url = serve(move |request| {
+            log.lock().unwrap().push(request.path.clone());
+            match request.path.as_str() {
+                "/robots.txt" => ok(format!("Sitemap: {}/index.xml", request.site)),
+                "/index.xml" => ok(format!("<sitemapindex><sitemap><loc>{0}/a.xml</loc></sitemap><sitemap><loc>{0}/b.xml</loc></sitemap></sitemapindex>", request.site)),
+                "/b.xml" if stalling.load(Ordering::SeqCst) => None,
+                page => ok(format!("<urlset><url><loc>{}{}</loc></url></urlset>", request.site, page.trim_end_matches(".xml"))),
+            }
+        })
+        .await;
+        let dir = std::env::temp_dir().join(format!("sitemap-checkpoint-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("run.ckpt");
+        let parser = |shutdown: Arc<Shutdown>| parser().with_shutdown(shutdown).with_checkpoint(Some(Arc::new(Checkpoint::open(&path).unwrap())));
+
+        // The run is interrupted while b.xml hangs, after robots.txt, index.xml, and a.xml
+        let shutdown = Arc::new(Shutdown::new());
+        let first = parser(Arc::clone(&shutdown));
+        let interrupt = async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            shutdown.request(Duration::from_millis(100));
+        };
+        let (results, _) = tokio::join!(first.parse_multiple_sites(vec![base_url.clone()]), interrupt);
+        assert!(results.unwrap()[0].interrupted);
+
+        // Resuming fetches robots.txt and b.xml, and replays the rest
+        stall.store(false, Ordering::SeqCst);
+        requested.lock().unwrap().clear();
+        let resumed = parser(Arc::new(Shutdown::new())).parse_multiple_sites(vec![base_url.clone()]).await.unwrap().remove(0);
+        assert_eq!(*requested.lock().unwrap(), ["/robots.txt", "/b.xml"]);
+        let expected: HashSet<String> = [format!("{}/a", base_url), format!("{}/b", base_url)].into();
+        assert_eq!(resumed.urls, expected);
+        assert_eq!(resumed.total_requests, 2);
+
+        // Once finished, the site comes straight from the checkpoint
+        requested.lock().unwrap().clear();
+        let again = parser(Arc::new(Shutdown::new())).parse_multiple_sites(vec![base_url]).await.unwrap().remove(0);
+        assert!(requested.lock().unwrap().is_empty());
+        assert_eq!(again.urls, expected);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_site_timeout_keeps_partial_urls() {
+        // A site whose b.xml never answers
+        let slow_url = serve(|request| match request.path.as_str() {
+            "/robots.txt" => ok(format!("Sitemap: {0}/a.xml\nSitemap: {0}/b.xml", request.site)),
+            "/b.xml" => None,
+            _ => ok(format!("<urlset><url><loc>{}/a</loc></url></urlset>", request.site)),
+        })
+        .await;
+        let config = crate::synthetic::SyntheticConfig { urls_per_sitemap: 5, depth: 1, ..Default::default() };
+        let fast = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+
+        let finished = Mutex::new(Vec::new());
+        let started = Instant::now();
+        let results = parser()
+            .with_site_timeout(Some(Duration::from_millis(500)))
+            .parse_multiple_sites_with(vec![slow_url.clone(), fast.base_url()], |result| {
+                finished.lock().unwrap().push(result.base_url.clone());
+                result
+            })
+            .await
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        // Handed over as each finished, returned in input order
+        assert_eq!(*finished.lock().unwrap(), [fast.base_url(), slow_url.clone()]);
+        let (slow, fast) = (&results[0], &results[1]);
+        assert!(slow.timed_out && !slow.interrupted);
+        assert_eq!(slow.urls, HashSet::from([format!("{}/a", slow_url)]));
+        let kinds: Vec<_> = slow.error_details.iter().map(|e| (e.kind(), e.url())).collect();
+        assert_eq!(kinds, [("timeout", format!("{}/b.xml", slow_url).as_str()), ("timeout", slow_url.as_str())]);
+        assert!(!fast.timed_out);
+        assert_eq!(fast.urls.len(), 5, "{:?}", fast.errors);
+    }
+
+    #[tokio::test]
+    async fn test_max_urls_per_site_caps_urls() {
+        let config = crate::synthetic::SyntheticConfig { urls_per_sitemap: 10, depth: 2, fanout: 5, ..Default::default() };
+        let server = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+
+        let capped = parser().with_max_urls_per_site(Some(25)).parse_site(&server.base_url()).await.unwrap();
+        assert_eq!(capped.urls.len(), 25, "{:?}", capped.errors);
+        assert!(capped.url_limit_reached && capped.truncated);
+        assert!(capped.truncated_sitemaps.is_empty() && capped.errors.is_empty());
+
+        let roomy = parser().with_max_urls_per_site(Some(50)).parse_site(&server.base_url()).await.unwrap();
+        assert_eq!(roomy.urls.len(), 50);
+        assert!(!roomy.url_limit_reached && !roomy.truncated);
+
+        // Deterministic runs keep the first URLs of the whole site, in sorted order
+        let everything = parser().parse_site(&server.base_url()).await.unwrap().url_list(true);
+        let deterministic = parser().with_max_urls_per_site(Some(25)).with_deterministic(true);
+        let first = deterministic.parse_site(&server.base_url()).await.unwrap();
+        assert_eq!(first.url_list(true), everything[..25]);
+        assert!(first.url_limit_reached && first.truncated);
+        let again = deterministic.parse_site(&server.base_url()).await.unwrap();
+        assert_eq!(again.urls, first.urls);
+
+        // Once the budget is spent, no further sitemaps are fetched
+        let ctx = SiteContext::new(&parser().with_max_urls_per_site(Some(3)));
+        let mut urls: HashSet<String> = ["a", "b"].map(String::from).into();
+        ctx.take_urls(&mut urls);
+        assert!(!ctx.out_of_urls());
+        let mut urls: HashSet<String> = ["d", "c"].map(String::from).into();
+        ctx.take_urls(&mut urls);
+        assert_eq!(urls, HashSet::from(["c".to_string()]));
+        assert!(ctx.out_of_urls() && ctx.url_budget_hit.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_url_hook_filters_before_results() {
+        struct FirstTwo;
+        impl UrlHook for FirstTwo {
+            fn apply(&self, _sitemap_url: &str, urls: Vec<String>) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+                Ok(urls.into_iter().take(2).collect())
+            }
+        }
+
+        let config = crate::synthetic::SyntheticConfig { urls_per_sitemap: 5, depth: 1, ..Default::default() };
+        let server = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+        let result = parser()
+            .with_url_metadata(true)
+            .with_url_hook(Some(Arc::new(FirstTwo)), 100)
+            .parse_site(&server.base_url())
+            .await
+            .unwrap();
+        assert_eq!(result.urls.len(), 2, "{:?}", result.errors);
+        assert_eq!(result.url_metadata.keys().collect::<HashSet<_>>(), result.urls.iter().collect());
+    }
+
+    #[tokio::test]
+    async fn test_text_sitemap_uses_content_type() {
+        let base_url = serve(|request| {
+            let body = match request.path.as_str() {
+                "/robots.txt" => format!("Sitemap: {}/pages.txt\n", request.site),
+                // Only the header marks this as a text sitemap
+                _ => format!("# exported pages\n{0}/a\n{0}/b\n", request.site),
+            };
+            reply(200, vec![("Content-Type", "text/plain; charset=utf-8".into())], body)
+        })
+        .await;
+
+        let result = parser().parse_site(&base_url).await.unwrap();
+        assert_eq!(result.urls, HashSet::from([format!("{}/a", base_url), format!("{}/b", base_url)]));
+    }
+
+    #[tokio::test]
+    async fn test_latin1_sitemap_is_transcoded() {
+        let base_url = serve(|request| {
+            let body = match request.path.as_str() {
+                "/robots.txt" => format!("Sitemap: {}/sitemap.xml\n", request.site).into_bytes(),
+                _ => [format!("<urlset><url><loc>{}/caf", request.site).as_bytes(), b"\xe9</loc></url></urlset>"].concat(),
+            };
+            reply(200, vec![("Content-Type", "text/xml; charset=ISO-8859-1".into())], body)
+        })
+        .await;
+
+        let result = parser().parse_site(&base_url).await.unwrap();
+        assert_eq!(result.urls, HashSet::from([format!("{}/café", base_url)]), "{:?}", result.errors);
+        assert_eq!(result.transcoded_sitemaps, HashMap::from([(format!("{}/sitemap.xml", base_url), "windows-1252".to_string())]));
+    }
+
+    #[tokio::test]
+    async fn test_respect_robots_filters_and_paces() {
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&requested);
+        let base_url = serve(move |request| {
+            log.lock().unwrap().push((request.path.clone(), Instant::now()));
+            match request.path.as_str() {
+                "/robots.txt" => ok(format!("User-agent: *\nDisallow: /private\nCrawl-delay: 0.3\nSitemap: {0}/a.xml\nSitemap: {0}/b.xml\n", request.site)),
+                "/a.xml" => ok(format!(r#"<urlset><url><loc>{0}/public</loc></url><url><loc>{0}/private/x</loc></url></urlset>"#, request.site)),
+                _ => ok(format!(r#"<urlset><url><loc>{0}/private/y</loc></url><url><loc>https://other.example/private</loc></url></urlset>"#, request.site)),
+            }
+        })
+        .await;
+
+        let result = parser().with_respect_robots(true).parse_site(&base_url).await.unwrap();
+        let mut urls: Vec<&str> = result.urls.iter().map(String::as_str).collect();
+        urls.sort();
+        assert_eq!(urls, [format!("{}/public", base_url).as_str(), "https://other.example/private"]);
+        assert_eq!(result.disallowed_urls, 2);
+        assert_eq!(result.crawl_delay, Some(Duration::from_millis(300)));
+        let requested = std::mem::take(&mut *requested.lock().unwrap());
+        assert_eq!(requested.len(), 3);
+        for pair in requested.windows(2) {
+            assert!(pair[1].1 - pair[0].1 >= Duration::from_millis(250), "{:?}", requested);
+        }
+
+        let ignoring = parser().parse_site(&base_url).await.unwrap();
+        assert_eq!((ignoring.urls.len(), ignoring.disallowed_urls), (4, 0));
+    }
+
+    #[tokio::test]
+    async fn test_coalesced_sitemap_is_filtered_by_each_sites_robots() {
+        // Both sites list the same sitemap, but only the first disallows /private
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&fetches);
+        let base_url = serve(move |request| match request.path.as_str() {
+            "/robots.txt" if request.header("host").is_some_and(|host| host.starts_with("localhost")) => ok(format!("Sitemap: {}/shared.xml\n", request.site)),
+            "/robots.txt" => ok(format!("User-agent: *\nDisallow: /private\nSitemap: {}/shared.xml\n", request.site)),
+            _ => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                ok(format!(r#"<urlset><url><loc>{0}/public</loc></url><url><loc>{0}/private</loc></url></urlset>"#, request.site))
+            }
+        })
+        .await;
+        let other_url = base_url.replace("127.0.0.1", "localhost");
+
+        let results = parser().with_respect_robots(true).parse_multiple_sites(vec![base_url, other_url]).await.unwrap();
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert_eq!((results[0].urls.len(), results[0].disallowed_urls), (1, 1));
+        assert_eq!((results[1].urls.len(), results[1].disallowed_urls), (2, 0));
+        // Whichever site asked second reused the other's fetch and isn't billed for it
+        assert_eq!(results[0].coalesced_sitemaps.len() + results[1].coalesced_sitemaps.len(), 1);
+        assert_eq!(results[0].total_requests + results[1].total_requests, 3);
+    }
+
+    #[tokio::test]
+    async fn test_url_filter_drops_urls_and_prunes_sitemaps() {
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&requested);
+        let base_url = serve(move |request| {
+            log.lock().unwrap().push(request.path.clone());
+            match request.path.as_str() {
+                "/robots.txt" => ok(format!("Sitemap: {}/index.xml\n", request.site)),
+                "/index.xml" => ok(format!(r#"<sitemapindex><sitemap><loc>{0}/sitemap-products.xml</loc></sitemap><sitemap><loc>{0}/sitemap-posts.xml</loc></sitemap></sitemapindex>"#, request.site)),
+                _ => ok(format!(r#"<urlset><url><loc>{0}/products/a</loc></url><url><loc>{0}/products/b?ref=feed</loc></url><url><loc>{0}/about</loc></url></urlset>"#, request.site)),
+            }
+        })
+        .await;
+
+        let patterns = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let filter = UrlFilter::new(&patterns(&["/products/**"]), &patterns(&["re:ref=feed"]), &patterns(&["**/sitemap-products.xml"])).unwrap();
+        let result = parser().with_url_filter(Some(Arc::new(filter))).parse_site(&base_url).await.unwrap();
+        assert_eq!(result.urls, HashSet::from([format!("{}/products/a", base_url)]));
+        let mut requested = std::mem::take(&mut *requested.lock().unwrap());
+        requested.sort();
+        assert_eq!(requested, ["/index.xml", "/robots.txt", "/sitemap-products.xml"]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_robots_caches_per_origin() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let served = Arc::clone(&requests);
+        let base_url = serve(move |request| {
+            served.fetch_add(1, Ordering::SeqCst);
+            match request.path.as_str() {
+                "/robots.txt" => ok(format!("User-agent: *\nDisallow: /private/\nCrawl-delay: 2\n\nUser-agent: BadBot\nDisallow: /\n\nSitemap: {}/sitemap.xml\n", request.site)),
+                _ => ok(format!("<urlset><url><loc>{}/a</loc></url></urlset>", request.site)),
+            }
+        })
+        .await;
+
+        let parser = parser();
+        parser.parse_site(&base_url).await.unwrap();
+        let fetched = requests.load(Ordering::SeqCst);
+        // parse_site already read robots.txt, so this costs nothing
+        let robots = parser.fetch_robots(&format!("{}/some/page", base_url)).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), fetched);
+        assert_eq!(robots.sitemaps, [format!("{}/sitemap.xml", base_url)]);
+        let policy = robots.policy_for("MyCrawler/1.0", &base_url);
+        assert!(policy.is_allowed(&format!("{}/public", base_url)) && !policy.is_allowed(&format!("{}/private/x", base_url)));
+        assert_eq!(policy.crawl_delay, Some(Duration::from_secs(2)));
+        assert!(!robots.policy_for("BadBot", &base_url).is_allowed(&format!("{}/public", base_url)));
+
+        let fresh = RustSitemapParser::new(10, 10, 2, 5, Duration::from_secs(5));
+        assert_eq!(fresh.fetch_robots(&base_url).await.unwrap(), robots);
+        fresh.fetch_robots(&base_url).await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), fetched + 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_robots_statuses() {
+        let status = Arc::new(Mutex::new(404));
+        let answer = Arc::clone(&status);
+        let base_url = serve(move |_| reply(*answer.lock().unwrap(), Vec::new(), "")).await;
+
+        // Unavailable: everything allowed
+        let robots = parser().fetch_robots(&base_url).await.unwrap();
+        assert!(robots.policy_for("MyCrawler", &base_url).is_allowed(&format!("{}/anything", base_url)));
+
+        // Unreachable: raised, not cached
+        *status.lock().unwrap() = 503;
+        let parser = parser();
+        let error = parser.fetch_robots(&base_url).await.unwrap_err();
+        assert_eq!(error.kind(), "robots_fetch");
+        *status.lock().unwrap() = 410;
+        assert!(parser.fetch_robots(&base_url).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_dns_policy_refuses_before_fetching() {
+        use crate::client::ClientConfig;
+        use crate::dns::{DnsPolicy, DnsResolver};
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&requests);
+        let base_url = serve(move |request| {
+            seen.lock().unwrap().push(request.path.clone());
+            match request.path.as_str() {
+                "/robots.txt" => ok(format!("Sitemap: {}/denied.xml\nSitemap: {}/ok.xml\n", request.site.replace("127.0.0.1", "localhost"), request.site)),
+                _ => ok(format!("<urlset><url><loc>{}/page</loc></url></urlset>", request.site)),
+            }
+        })
+        .await;
+        let with_dns = |policy: DnsPolicy| {
+            let dns = DnsResolver::new(policy);
+            let client = ClientConfig { dns: Some(dns.clone()), ..Default::default() }.build(Duration::from_secs(5)).unwrap();
+            RustSitemapParser::from_client(client, 10, 10, 2, 5).with_dns(Some(dns))
+        };
+
+        let policy = DnsPolicy {
+            block_private: true,
+            allow_networks: vec!["127.0.0.0/8".parse().unwrap()],
+            deny_hosts: vec!["localhost".into()],
+            ..Default::default()
+        };
+        let result = with_dns(policy).parse_site(&base_url).await.unwrap();
+        assert_eq!(result.urls, HashSet::from([format!("{}/page", base_url)]));
+        let blocked: Vec<&str> = result.error_details.iter().filter(|e| e.kind() == "blocked").map(SitemapError::url).collect();
+        assert_eq!(blocked, [format!("{}/denied.xml", base_url.replace("127.0.0.1", "localhost"))]);
+        assert_eq!(*requests.lock().unwrap(), ["/robots.txt", "/ok.xml"]);
+
+        let result = with_dns(DnsPolicy { block_private: true, ..Default::default() }).parse_site(&base_url).await.unwrap();
+        assert!(result.urls.is_empty());
+        assert!(result.error_details.iter().any(|e| e.to_string().contains("the DNS policy refuses address 127.0.0.1")), "{:?}", result.errors);
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_canonicalizer_collapses_url_variants() {
+        use crate::canonical::TrailingSlash;
+        let base_url = serve(|request| match request.path.as_str() {
+            "/robots.txt" => ok(format!("Sitemap: {0}/a.xml\nSitemap: {0}/b.xml\n", request.site)),
+            "/a.xml" => ok(format!(r#"<urlset><url><loc>{0}/a</loc></url><url><loc>{0}/a/</loc></url><url><loc>{0}/b?utm_source=x</loc></url></urlset>"#, request.site)),
+            _ => ok(format!(r#"<urlset><url><loc>{0}/b</loc></url><url><loc>{0}/c#reviews</loc></url></urlset>"#, request.site)),
+        })
+        .await;
+
+        let raw = parser().parse_site(&base_url).await.unwrap();
+        assert_eq!((raw.urls.len(), raw.duplicate_urls), (5, 0));
+
+        let canonicalizer = UrlCanonicalizer { trailing_slash: TrailingSlash::Strip, strip_params: vec!["utm_*".into()], ..Default::default() };
+        let result = parser().with_canonicalizer(Some(Arc::new(canonicalizer))).parse_site(&base_url).await.unwrap();
+        let expected: HashSet<String> = ["a", "b", "c"].iter().map(|path| format!("{}/{}", base_url, path)).collect();
+        assert_eq!(result.urls, expected);
+        // /a/ within a.xml, and /b across the two sitemaps
+        assert_eq!(result.duplicate_urls, 2);
+    }
+
+    #[tokio::test]
+    async fn test_http_cache_revalidates_unchanged_sitemaps() {
+        let base_url = serve(|request| {
+            let etag = format!("\"{}\"", request.path.trim_start_matches('/'));
+            if request.header("if-none-match") == Some(etag.as_str()) {
+                return reply(304, vec![("ETag", etag)], "");
+            }
+            let body = match request.path.as_str() {
+                "/robots.txt" => format!("User-agent: *\nSitemap: {}/a.xml\n", request.site),
+                _ => format!(r#"<urlset><url><loc>{0}/one</loc></url><url><loc>{0}/two</loc></url></urlset>"#, request.site),
+            };
+            reply(200, vec![("ETag", etag)], body)
+        })
+        .await;
+
+        let parser = parser().with_http_cache(Some(Arc::new(crate::cache::MemoryCache::default())));
+        let first = parser.parse_site(&base_url).await.unwrap();
+        assert_eq!((first.cache_hits, first.cache_misses), (0, 2));
+        let second = parser.parse_site(&base_url).await.unwrap();
+        assert_eq!((second.cache_hits, second.cache_misses), (2, 0));
+        assert_eq!(second.urls, first.urls);
+        assert_eq!(second.urls.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_request_limits_cap_fan_out() {
+        let config = crate::synthetic::SyntheticConfig { urls_per_sitemap: 10, depth: 2, fanout: 5, ..Default::default() };
+        let server = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+        let contention = Arc::new(ContentionTracker::new());
+        let parser = parser()
+            .with_request_limits(Arc::new(RequestLimiter::new(None, Some(2))))
+            .with_contention(Arc::clone(&contention));
+
+        let result = parser.parse_site(&server.base_url()).await.unwrap();
+        assert_eq!(result.urls.len(), 50, "{:?}", result.errors);
+        let snapshot = contention.snapshot();
+        assert_eq!(snapshot.peak_host_in_flight.map(|(_, most)| most), Some(2));
+        assert!(snapshot.peak_requests_waiting > 0);
+    }
+
+    #[tokio::test]
+    async fn test_host_pacer_spaces_requests_to_one_host() {
+        let config = crate::synthetic::SyntheticConfig { urls_per_sitemap: 10, depth: 2, fanout: 3, ..Default::default() };
+        let server = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+        let parser = parser().with_host_pacer(Arc::new(HostPacer::new(Some(Duration::from_millis(100)))));
+
+        let started = Instant::now();
+        let result = parser.parse_site(&server.base_url()).await.unwrap();
+        assert_eq!(result.urls.len(), 30, "{:?}", result.errors);
+        // robots.txt, the index, and its three children: four gaps between five requests
+        assert_eq!(result.total_requests, 5);
+        assert!(started.elapsed() >= Duration::from_millis(400), "{:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_streamed_parse_sends_batches_instead_of_collecting() {
+        let config = crate::synthetic::SyntheticConfig { urls_per_sitemap: 10, depth: 2, fanout: 3, ..Default::default() };
+        let server = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+        let (sink, mut receiver) = UrlSink::channel(4, 1);
+        let (parser, base_url) = (parser(), server.base_url());
+
+        let (parsed, received) = tokio::join!(parser.parse_site_streaming(&base_url, sink), async {
+            let mut urls = HashSet::new();
+            while let Some(batch) = receiver.recv().await {
+                assert!(batch.len() <= 4);
+                urls.extend(batch);
+            }
+            urls
+        });
+        let result = parsed.unwrap();
+        assert!(result.urls.is_empty());
+        assert_eq!((result.streamed_urls, received.len()), (30, 30), "{:?}", result.errors);
+    }
+
+    #[tokio::test]
+    async fn test_parse_site_stream_pulls_input_lazily() {
+        let config = crate::synthetic::SyntheticConfig {
+            urls_per_sitemap: 5,
+            depth: 1,
+            ..Default::default()
+        };
+        let server = crate::synthetic::SyntheticServer::start(&config).await.unwrap();
+        let pulled = AtomicUsize::new(0);
+        let base_urls = futures::stream::iter((0..100).map(|_| {
+            pulled.fetch_add(1, Ordering::SeqCst);
+            server.base_url()
+        }));
+        let parser = RustSitemapParser::new(2, 10, 1, 5, Duration::from_secs(5)).with_deterministic(true);
+
+        let mut results = parser.parse_site_stream(base_urls);
+        let first = results.next().await.unwrap();
+        assert_eq!(first.urls.len(), 5);
+        // Only the sites in flight have been taken from the input, plus the one waiting on a slot
+        assert!(pulled.load(Ordering::SeqCst) <= 3, "{}", pulled.load(Ordering::SeqCst));
+
+        assert_eq!(results.count().await, 99);
+    }
+
+    #[test]
+    fn test_prewarm_targets_dedupes_hosts() {
+        let parser = parser().with_prewarm_hosts(2);
+        let base_urls = vec![
+            "https://example.com".to_string(),
+            "example.com/blog".to_string(),
+            "https://other.com/".to_string(),
+            "https://third.com/".to_string(),
+        ];
+
+        assert_eq!(
+            parser.prewarm_targets(&base_urls),
+            vec!["https://example.com/robots.txt", "https://other.com/robots.txt"]
+        );
+    }
+
+    #[test]
+    fn test_prewarm_disabled_by_default() {
+        assert!(parser().prewarm_targets(&["https://example.com".to_string()]).is_empty());
     }
 }
@@ -0,0 +1,113 @@
+use std::fmt;
+
+use crate::json::is_json_sitemap;
+
+/// How much of a sitemap body to read while looking for its root element
+pub const PEEK_BYTES: usize = 16 * 1024;
+
+/// What a dry run learned about one sitemap from the start of its body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SitemapKind {
+    /// A `<sitemapindex>`, downloaded in full so its children can be planned
+    Index,
+    /// A `<urlset>`, abandoned after the first chunk
+    Urlset,
+    /// Neither root element showed up in the first `PEEK_BYTES`
+    Unknown,
+    /// The request failed; a real run would still spend a request on it
+    Failed,
+}
+
+impl fmt::Display for SitemapKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SitemapKind::Index => write!(f, "index"),
+            SitemapKind::Urlset => write!(f, "urlset"),
+            SitemapKind::Unknown => write!(f, "unknown"),
+            SitemapKind::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+/// A sitemap a real run would fetch
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedSitemap {
+    pub url: String,
+    pub kind: SitemapKind,
+    /// 1 for sitemaps listed in robots.txt, 2 for their children, and so on
+    pub level: usize,
+    /// Content-Length advertised by the server, if any
+    pub content_length: Option<u64>,
+}
+
+/// What a full parse of one site would fetch, worked out without downloading any urlsets
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitePlan {
+    pub base_url: String,
+    pub sitemaps: Vec<PlannedSitemap>,
+    /// Requests a real run would make: robots.txt plus every planned sitemap
+    pub estimated_requests: usize,
+    /// robots.txt and index bodies as read, plus advertised sizes of everything else
+    pub estimated_bytes: u64,
+    /// Planned sitemaps that gave no Content-Length, so aren't in `estimated_bytes`
+    pub unknown_sizes: usize,
+    /// Requests the dry run itself made
+    pub requests_made: usize,
+    pub errors: Vec<String>,
+    pub plan_time: f64,
+}
+
+impl SitePlan {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            base_url,
+            sitemaps: Vec::new(),
+            estimated_requests: 0,
+            estimated_bytes: 0,
+            unknown_sizes: 0,
+            requests_made: 0,
+            errors: Vec::new(),
+            plan_time: 0.0,
+        }
+    }
+}
+
+/// Identify a sitemap from the start of its body by whichever root element appears first,
+/// or for JSON by whether it lists nested `sitemaps`
+pub fn sniff_kind(prefix: &str) -> SitemapKind {
+    if is_json_sitemap(prefix) {
+        return if prefix.contains("\"sitemaps\"") { SitemapKind::Index } else { SitemapKind::Urlset };
+    }
+    let find = |tag: &str| {
+        // Allow a namespace prefix like <sm:urlset
+        prefix.match_indices(tag).map(|(at, _)| at).find(|&at| {
+            let before = prefix[..at].rfind('<').map(|lt| &prefix[lt + 1..at]);
+            let named = before.is_some_and(|ns| ns.is_empty() || (ns.ends_with(':') && !ns.contains(|c: char| c.is_whitespace() || c == '>')));
+            let after = prefix[at + tag.len()..].chars().next();
+            named && after.is_none_or(|c| c.is_whitespace() || c == '>' || c == '/')
+        })
+    };
+    match (find("urlset"), find("sitemapindex")) {
+        (Some(u), Some(i)) if i < u => SitemapKind::Index,
+        (Some(_), _) => SitemapKind::Urlset,
+        (None, Some(_)) => SitemapKind::Index,
+        (None, None) => SitemapKind::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_kind() {
+        assert_eq!(sniff_kind(r#"<?xml version="1.0"?><urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"><url>"#), SitemapKind::Urlset);
+        assert_eq!(sniff_kind("<?xml version=\"1.0\"?>\n<sitemapindex>"), SitemapKind::Index);
+        assert_eq!(sniff_kind("<sm:sitemapindex xmlns:sm=\"http://www.sitemaps.org/schemas/sitemap/0.9\">"), SitemapKind::Index);
+        // A comment mentioning urlset isn't an element
+        assert_eq!(sniff_kind("<!-- not a urlset --><sitemapindex>"), SitemapKind::Index);
+        assert_eq!(sniff_kind("<?xml version=\"1.0\"?><urls"), SitemapKind::Unknown);
+        assert_eq!(sniff_kind(r#"{"sitemaps": [{"url": "/a.json"}]}"#), SitemapKind::Index);
+        assert_eq!(sniff_kind(r#"[{"url": "/a", "lastmod": "2024-01-01"}]"#), SitemapKind::Urlset);
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What a progress event reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressKind {
+    SiteStarted,
+    /// A sitemap was fetched and parsed
+    SitemapFetched,
+    /// robots.txt or a sitemap failed; the site's parse carries on without it
+    Error,
+    SiteFinished,
+}
+
+impl fmt::Display for ProgressKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgressKind::SiteStarted => write!(f, "site_started"),
+            ProgressKind::SitemapFetched => write!(f, "sitemap_fetched"),
+            ProgressKind::Error => write!(f, "error"),
+            ProgressKind::SiteFinished => write!(f, "site_finished"),
+        }
+    }
+}
+
+/// One step of a site's parse, with the site's running totals
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressEvent {
+    pub kind: ProgressKind,
+    pub base_url: String,
+    /// The sitemap fetched, or the robots.txt or sitemap that failed
+    pub sitemap_url: Option<String>,
+    /// URLs found on the site so far; the final count once it finishes
+    pub urls: usize,
+    pub sitemaps: usize,
+    pub errors: usize,
+    /// What went wrong, for errors
+    pub message: Option<String>,
+}
+
+/// Receives progress events as a parse runs, e.g. to drive a progress bar.
+///
+/// Events arrive from whichever worker made the progress, so a listener shared by
+/// several sites sees their events interleaved.
+pub trait ProgressListener: Send + Sync {
+    fn on_progress(&self, event: ProgressEvent);
+}
+
+/// A site's running totals for its progress events
+#[derive(Debug, Default)]
+pub(crate) struct SiteProgress {
+    urls: AtomicUsize,
+    sitemaps: AtomicUsize,
+    errors: AtomicUsize,
+}
+
+impl SiteProgress {
+    /// Count a fetched sitemap and its URLs, returning the event reporting it. URLs
+    /// listed by several sitemaps are counted once per sitemap.
+    pub(crate) fn sitemap(&self, base_url: &str, sitemap_url: &str, urls: usize) -> ProgressEvent {
+        let urls = self.urls.fetch_add(urls, Ordering::SeqCst) + urls;
+        let sitemaps = self.sitemaps.fetch_add(1, Ordering::SeqCst) + 1;
+        ProgressEvent {
+            sitemap_url: Some(sitemap_url.to_string()),
+            urls,
+            sitemaps,
+            ..self.event(ProgressKind::SitemapFetched, base_url)
+        }
+    }
+
+    /// Count an error, returning the event reporting it
+    pub(crate) fn error(&self, base_url: &str, url: &str, message: String) -> ProgressEvent {
+        self.errors.fetch_add(1, Ordering::SeqCst);
+        ProgressEvent { sitemap_url: Some(url.to_string()), message: Some(message), ..self.event(ProgressKind::Error, base_url) }
+    }
+
+    /// An event carrying the totals so far
+    pub(crate) fn event(&self, kind: ProgressKind, base_url: &str) -> ProgressEvent {
+        ProgressEvent {
+            kind,
+            base_url: base_url.to_string(),
+            sitemap_url: None,
+            urls: self.urls.load(Ordering::SeqCst),
+            sitemaps: self.sitemaps.load(Ordering::SeqCst),
+            errors: self.errors.load(Ordering::SeqCst),
+            message: None,
+        }
+    }
+}
@@ -0,0 +1,3896 @@
+use log::{info, warn, error, debug};
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use ipnet::IpNet;
+use pyo3::exceptions::PyStopAsyncIteration;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyIterator};
+use pyo3_async_runtimes::tokio::future_into_py;
+use pyo3_async_runtimes::TaskLocals;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use url::Url;
+
+use crate::agents::UserAgentPool;
+use crate::anomalies::{detect_anomalies, Anomaly, AnomalyThresholds};
+use crate::bing::BingWebmasterSource;
+use crate::bodies::BodyStore;
+use crate::cache::{DiskCache, HttpCache, MemoryCache};
+use crate::canonical::{TrailingSlash, UrlCanonicalizer};
+use crate::checkpoint::Checkpoint;
+use crate::client::ClientConfig;
+use crate::har::HarRecorder;
+use crate::hooks::{UrlHook, DEFAULT_HOOK_BATCH_SIZE};
+use crate::limits::{HostPacer, RequestLimiter};
+use crate::local::{file_url, SitemapFetch};
+use crate::reputation::ReputationStore;
+use crate::robots::{RobotsCache, RobotsTxt};
+use crate::memory::MemoryUsage;
+use crate::commoncrawl::CommonCrawlSource;
+use crate::contention::{ContentionTracker, DurationStats};
+use crate::discovery::DiscoveryPolicy;
+use crate::dns::{parse_nameserver, parse_network, DnsPolicy, DnsResolver};
+use crate::export::{ExportFormat, ExportSummary};
+use crate::fetcher::{FetchResponse, Fetcher};
+use crate::filter::UrlFilter;
+use crate::monitor::{FinishedSite, MonitorSnapshot, RunMonitor};
+use crate::progress::{ProgressEvent, ProgressListener};
+use crate::warc::{WarcArchive, WarcWriter};
+use crate::wayback::ArchivedCapture;
+use crate::score::{parse_lastmod, rank_urls, ScoreWeights, ScoredUrl};
+use crate::templates::{mine_templates, UrlTemplate};
+use crate::media::{ImageEntry, NewsEntry, VideoEntry};
+use crate::sitemap::UrlMetadata;
+use crate::synthetic::{SyntheticConfig, SyntheticSite};
+use crate::telemetry::FetchStats;
+use crate::proxies::{ProxyAssignment, ProxyPool};
+use crate::redirect::{RedirectChain, RedirectPolicy, MAX_REDIRECTS};
+use crate::retry::{RetryAttempt, RetryPolicy, RetryRecord, RetryReport};
+use crate::shutdown::{Shutdown, DEFAULT_GRACE};
+use crate::stream::{UrlSink, DEFAULT_STREAM_BATCH_SIZE};
+use crate::parser::{ParsedSiteResult, RustSitemapParser, TruncatedSitemap, DEFAULT_MAX_RESPONSE_BYTES};
+use crate::writer::{SitemapWriteSummary, SitemapWriter, MAX_URLS_PER_SITEMAP};
+use crate::snapshot::{diff, SitemapSnapshot, SnapshotDiff, SnapshotFormat};
+
+/// Details of a sitemap that was only partially downloaded
+#[pyclass]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TruncationInfo {
+    #[pyo3(get)]
+    pub sitemap_url: String,
+    #[pyo3(get)]
+    pub bytes_read: usize,
+    #[pyo3(get)]
+    pub content_length: Option<u64>,
+    #[pyo3(get)]
+    pub reason: String,
+}
+
+#[pymethods]
+impl TruncationInfo {
+    fn __repr__(&self) -> String {
+        format!(
+            "TruncationInfo(sitemap_url='{}', bytes_read={}, content_length={:?}, reason='{}')",
+            self.sitemap_url, self.bytes_read, self.content_length, self.reason
+        )
+    }
+}
+
+impl From<TruncatedSitemap> for TruncationInfo {
+    fn from(t: TruncatedSitemap) -> Self {
+        Self {
+            sitemap_url: t.url,
+            bytes_read: t.bytes_read,
+            content_length: t.content_length,
+            reason: t.reason.to_string(),
+        }
+    }
+}
+
+/// A response read from the Wayback Machine because the live fetch failed
+#[pyclass]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArchiveInfo {
+    #[pyo3(get)]
+    pub url: String,
+    #[pyo3(get)]
+    pub snapshot_url: String,
+    #[pyo3(get)]
+    pub captured_at: String,
+}
+
+#[pymethods]
+impl ArchiveInfo {
+    fn __repr__(&self) -> String {
+        format!("ArchiveInfo(url='{}', captured_at='{}')", self.url, self.captured_at)
+    }
+}
+
+impl From<ArchivedCapture> for ArchiveInfo {
+    fn from(c: ArchivedCapture) -> Self {
+        Self {
+            url: c.url,
+            snapshot_url: c.snapshot_url,
+            captured_at: c.captured_at,
+        }
+    }
+}
+
+/// Where a robots.txt or sitemap request that redirected ended up
+#[pyclass]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RedirectInfo {
+    /// `(url, status)` of each response that redirected, starting with the URL requested
+    #[pyo3(get)]
+    pub hops: Vec<(String, u16)>,
+    #[pyo3(get)]
+    pub final_url: String,
+}
+
+#[pymethods]
+impl RedirectInfo {
+    fn __repr__(&self) -> String {
+        format!("RedirectInfo(hops={}, final_url='{}')", self.hops.len(), self.final_url)
+    }
+}
+
+impl From<RedirectChain> for RedirectInfo {
+    fn from(r: RedirectChain) -> Self {
+        Self {
+            hops: r.hops.into_iter().map(|hop| (hop.url, hop.status)).collect(),
+            final_url: r.final_url,
+        }
+    }
+}
+
+/// lastmod, priority, and sitemap depth recorded for one URL
+#[pyclass(name = "UrlMetadata")]
+#[derive(Clone, Debug)]
+pub struct PyUrlMetadata {
+    #[pyo3(get)]
+    pub lastmod: Option<String>,
+    #[pyo3(get)]
+    pub changefreq: Option<String>,
+    #[pyo3(get)]
+    pub priority: Option<f64>,
+    #[pyo3(get)]
+    pub depth: usize,
+    /// Sitemap the URL was listed in
+    #[pyo3(get)]
+    pub source_sitemap: Option<String>,
+    /// `(key, value)` pairs from unrecognized `<url>` children; empty unless the parser ran with `url_extensions=True`
+    #[pyo3(get)]
+    pub extensions: Vec<(String, String)>,
+    /// Image, video, and news sitemap entries; empty unless the parser ran with `url_media=True`
+    #[pyo3(get)]
+    pub images: Vec<PyImageEntry>,
+    #[pyo3(get)]
+    pub videos: Vec<PyVideoEntry>,
+    #[pyo3(get)]
+    pub news: Option<PyNewsEntry>,
+    /// `(hreflang, href)` language variants; empty unless the parser ran with `url_alternates=True`
+    #[pyo3(get)]
+    pub alternates: Vec<(String, String)>,
+}
+
+#[pymethods]
+impl PyUrlMetadata {
+    fn __repr__(&self) -> String {
+        format!("UrlMetadata(lastmod={:?}, changefreq={:?}, priority={:?}, depth={})", self.lastmod, self.changefreq, self.priority, self.depth)
+    }
+}
+
+impl From<UrlMetadata> for PyUrlMetadata {
+    fn from(m: UrlMetadata) -> Self {
+        Self {
+            lastmod: m.lastmod,
+            changefreq: m.changefreq,
+            priority: m.priority,
+            depth: m.depth,
+            source_sitemap: m.source_sitemap,
+            extensions: m.extensions,
+            images: m.images.into_iter().map(PyImageEntry::from).collect(),
+            videos: m.videos.into_iter().map(PyVideoEntry::from).collect(),
+            news: m.news.map(PyNewsEntry::from),
+            alternates: m.alternates,
+        }
+    }
+}
+
+/// An `<image:image>` from a Google image sitemap
+#[pyclass(name = "ImageEntry")]
+#[derive(Clone, Debug)]
+pub struct PyImageEntry {
+    #[pyo3(get)]
+    pub loc: String,
+    #[pyo3(get)]
+    pub caption: Option<String>,
+    #[pyo3(get)]
+    pub title: Option<String>,
+    #[pyo3(get)]
+    pub geo_location: Option<String>,
+    #[pyo3(get)]
+    pub license: Option<String>,
+}
+
+#[pymethods]
+impl PyImageEntry {
+    fn __repr__(&self) -> String {
+        format!("ImageEntry(loc='{}', title={:?})", self.loc, self.title)
+    }
+}
+
+impl From<ImageEntry> for PyImageEntry {
+    fn from(i: ImageEntry) -> Self {
+        Self {
+            loc: i.loc,
+            caption: i.caption,
+            title: i.title,
+            geo_location: i.geo_location,
+            license: i.license,
+        }
+    }
+}
+
+/// A `<video:video>` from a Google video sitemap
+#[pyclass(name = "VideoEntry")]
+#[derive(Clone, Debug)]
+pub struct PyVideoEntry {
+    #[pyo3(get)]
+    pub thumbnail_loc: Option<String>,
+    #[pyo3(get)]
+    pub title: Option<String>,
+    #[pyo3(get)]
+    pub description: Option<String>,
+    #[pyo3(get)]
+    pub content_loc: Option<String>,
+    #[pyo3(get)]
+    pub player_loc: Option<String>,
+    /// Length in seconds
+    #[pyo3(get)]
+    pub duration: Option<u32>,
+    #[pyo3(get)]
+    pub expiration_date: Option<String>,
+    #[pyo3(get)]
+    pub publication_date: Option<String>,
+    #[pyo3(get)]
+    pub rating: Option<f64>,
+    #[pyo3(get)]
+    pub view_count: Option<u64>,
+    #[pyo3(get)]
+    pub family_friendly: Option<bool>,
+    #[pyo3(get)]
+    pub requires_subscription: Option<bool>,
+    #[pyo3(get)]
+    pub live: Option<bool>,
+    #[pyo3(get)]
+    pub uploader: Option<String>,
+    #[pyo3(get)]
+    pub tags: Vec<String>,
+}
+
+#[pymethods]
+impl PyVideoEntry {
+    fn __repr__(&self) -> String {
+        format!("VideoEntry(title={:?}, content_loc={:?}, duration={:?})", self.title, self.content_loc, self.duration)
+    }
+}
+
+impl From<VideoEntry> for PyVideoEntry {
+    fn from(v: VideoEntry) -> Self {
+        Self {
+            thumbnail_loc: v.thumbnail_loc,
+            title: v.title,
+            description: v.description,
+            content_loc: v.content_loc,
+            player_loc: v.player_loc,
+            duration: v.duration,
+            expiration_date: v.expiration_date,
+            publication_date: v.publication_date,
+            rating: v.rating,
+            view_count: v.view_count,
+            family_friendly: v.family_friendly,
+            requires_subscription: v.requires_subscription,
+            live: v.live,
+            uploader: v.uploader,
+            tags: v.tags,
+        }
+    }
+}
+
+/// A `<news:news>` from a Google News sitemap
+#[pyclass(name = "NewsEntry")]
+#[derive(Clone, Debug)]
+pub struct PyNewsEntry {
+    #[pyo3(get)]
+    pub publication_name: Option<String>,
+    #[pyo3(get)]
+    pub publication_language: Option<String>,
+    #[pyo3(get)]
+    pub publication_date: Option<String>,
+    #[pyo3(get)]
+    pub title: Option<String>,
+    #[pyo3(get)]
+    pub keywords: Vec<String>,
+}
+
+#[pymethods]
+impl PyNewsEntry {
+    fn __repr__(&self) -> String {
+        format!("NewsEntry(publication_name={:?}, title={:?}, publication_date={:?})", self.publication_name, self.title, self.publication_date)
+    }
+}
+
+impl From<NewsEntry> for PyNewsEntry {
+    fn from(n: NewsEntry) -> Self {
+        Self {
+            publication_name: n.publication_name,
+            publication_language: n.publication_language,
+            publication_date: n.publication_date,
+            title: n.title,
+            keywords: n.keywords,
+        }
+    }
+}
+
+/// A URL from `SitemapResult.entries` with the sitemap's metadata for it
+#[pyclass(name = "UrlEntry")]
+#[derive(Clone, Debug)]
+pub struct PyUrlEntry {
+    #[pyo3(get)]
+    pub url: String,
+    /// As written in the sitemap
+    #[pyo3(get)]
+    pub lastmod: Option<String>,
+    /// `lastmod` as Unix seconds, if it's a date the parser understands
+    #[pyo3(get)]
+    pub lastmod_timestamp: Option<f64>,
+    #[pyo3(get)]
+    pub changefreq: Option<String>,
+    #[pyo3(get)]
+    pub priority: Option<f64>,
+    /// Sitemap nesting level the URL was found at; `None` without metadata
+    #[pyo3(get)]
+    pub depth: Option<usize>,
+    /// Sitemap the URL was listed in; `None` without metadata
+    #[pyo3(get)]
+    pub source_sitemap: Option<String>,
+    /// Empty unless the parser ran with `url_media=True`
+    #[pyo3(get)]
+    pub images: Vec<PyImageEntry>,
+    #[pyo3(get)]
+    pub videos: Vec<PyVideoEntry>,
+    #[pyo3(get)]
+    pub news: Option<PyNewsEntry>,
+    /// `(hreflang, href)` language variants; empty unless the parser ran with `url_alternates=True`
+    #[pyo3(get)]
+    pub alternates: Vec<(String, String)>,
+}
+
+#[pymethods]
+impl PyUrlEntry {
+    fn __repr__(&self) -> String {
+        format!("UrlEntry(url='{}', lastmod={:?}, changefreq={:?}, priority={:?})", self.url, self.lastmod, self.changefreq, self.priority)
+    }
+}
+
+impl PyUrlEntry {
+    fn new(url: &str, metadata: Option<&UrlMetadata>) -> Self {
+        let lastmod = metadata.and_then(|m| m.lastmod.clone());
+        Self {
+            url: url.to_string(),
+            lastmod_timestamp: lastmod.as_deref().and_then(parse_lastmod).map(|t| t.timestamp_millis() as f64 / 1000.0),
+            lastmod,
+            changefreq: metadata.and_then(|m| m.changefreq.clone()),
+            priority: metadata.and_then(|m| m.priority),
+            depth: metadata.map(|m| m.depth),
+            source_sitemap: metadata.and_then(|m| m.source_sitemap.clone()),
+            images: metadata.map(|m| m.images.iter().cloned().map(PyImageEntry::from).collect()).unwrap_or_default(),
+            videos: metadata.map(|m| m.videos.iter().cloned().map(PyVideoEntry::from).collect()).unwrap_or_default(),
+            news: metadata.and_then(|m| m.news.clone()).map(PyNewsEntry::from),
+            alternates: metadata.map(|m| m.alternates.clone()).unwrap_or_default(),
+        }
+    }
+}
+
+/// A URL with its score and tier from `SitemapResult.score_urls()`
+#[pyclass(name = "ScoredUrl")]
+#[derive(Clone, Debug)]
+pub struct PyScoredUrl {
+    #[pyo3(get)]
+    pub url: String,
+    #[pyo3(get)]
+    pub score: f64,
+    #[pyo3(get)]
+    pub tier: usize,
+}
+
+#[pymethods]
+impl PyScoredUrl {
+    fn __repr__(&self) -> String {
+        format!("ScoredUrl(url='{}', score={:.3}, tier={})", self.url, self.score, self.tier)
+    }
+}
+
+impl From<ScoredUrl> for PyScoredUrl {
+    fn from(s: ScoredUrl) -> Self {
+        Self {
+            url: s.url,
+            score: s.score,
+            tier: s.tier,
+        }
+    }
+}
+
+/// A URL shape and how many of the result's URLs follow it
+#[pyclass(name = "UrlTemplate")]
+#[derive(Clone, Debug)]
+pub struct PyUrlTemplate {
+    #[pyo3(get)]
+    pub template: String,
+    #[pyo3(get)]
+    pub count: usize,
+    #[pyo3(get)]
+    pub examples: Vec<String>,
+}
+
+#[pymethods]
+impl PyUrlTemplate {
+    fn __repr__(&self) -> String {
+        format!("UrlTemplate(template='{}', count={})", self.template, self.count)
+    }
+}
+
+impl From<UrlTemplate> for PyUrlTemplate {
+    fn from(t: UrlTemplate) -> Self {
+        Self {
+            template: t.template,
+            count: t.count,
+            examples: t.examples,
+        }
+    }
+}
+
+/// A group of suspicious entries from `SitemapResult.anomalies()`
+#[pyclass(name = "Anomaly")]
+#[derive(Clone, Debug)]
+pub struct PyAnomaly {
+    /// "off_domain", "query_variants", "future_lastmod", or "long_url"
+    #[pyo3(get)]
+    pub kind: String,
+    #[pyo3(get)]
+    pub count: usize,
+    #[pyo3(get)]
+    pub examples: Vec<String>,
+    #[pyo3(get)]
+    pub detail: String,
+}
+
+#[pymethods]
+impl PyAnomaly {
+    fn __repr__(&self) -> String {
+        format!("Anomaly(kind='{}', count={}, detail='{}')", self.kind, self.count, self.detail)
+    }
+}
+
+impl From<Anomaly> for PyAnomaly {
+    fn from(a: Anomaly) -> Self {
+        Self {
+            kind: a.kind.to_string(),
+            count: a.count,
+            examples: a.examples,
+            detail: a.detail,
+        }
+    }
+}
+
+/// Approximate memory attributed to one site's parse, in bytes
+#[pyclass]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MemoryInfo {
+    /// Heap held by the returned URL set
+    #[pyo3(get)]
+    pub url_bytes: usize,
+    /// Most response-body bytes held at once
+    #[pyo3(get)]
+    pub peak_buffer_bytes: usize,
+    /// Shared-fetch results left in the batch's coalescing cache
+    #[pyo3(get)]
+    pub cache_bytes: usize,
+    /// Upper bound: all of the above at their peaks together
+    #[pyo3(get)]
+    pub peak_bytes: usize,
+}
+
+#[pymethods]
+impl MemoryInfo {
+    fn __repr__(&self) -> String {
+        format!(
+            "MemoryInfo(url_bytes={}, peak_buffer_bytes={}, cache_bytes={}, peak_bytes={})",
+            self.url_bytes, self.peak_buffer_bytes, self.cache_bytes, self.peak_bytes
+        )
+    }
+}
+
+impl From<MemoryUsage> for MemoryInfo {
+    fn from(m: MemoryUsage) -> Self {
+        Self {
+            url_bytes: m.url_bytes,
+            peak_buffer_bytes: m.peak_buffer_bytes,
+            cache_bytes: m.cache_bytes,
+            peak_bytes: m.peak_bytes(),
+        }
+    }
+}
+
+/// One failed attempt at a retried request
+#[pyclass(name = "RetryAttempt")]
+#[derive(Clone, Debug)]
+pub struct PyRetryAttempt {
+    /// "timeout", "connect", "network", or "http <status>"
+    #[pyo3(get)]
+    pub error: String,
+    /// Wait before the next attempt; 0 after the last one
+    #[pyo3(get)]
+    pub backoff_seconds: f64,
+}
+
+#[pymethods]
+impl PyRetryAttempt {
+    fn __repr__(&self) -> String {
+        format!("RetryAttempt(error='{}', backoff_seconds={:.2})", self.error, self.backoff_seconds)
+    }
+}
+
+impl From<RetryAttempt> for PyRetryAttempt {
+    fn from(a: RetryAttempt) -> Self {
+        Self { error: a.error, backoff_seconds: a.backoff.as_secs_f64() }
+    }
+}
+
+/// A request whose first attempt failed, with every failed attempt and how it ended
+#[pyclass(name = "RetryRecord")]
+#[derive(Clone, Debug)]
+pub struct PyRetryRecord {
+    #[pyo3(get)]
+    pub url: String,
+    #[pyo3(get)]
+    pub attempts: Vec<PyRetryAttempt>,
+    /// A later attempt succeeded
+    #[pyo3(get)]
+    pub recovered: bool,
+}
+
+#[pymethods]
+impl PyRetryRecord {
+    fn __repr__(&self) -> String {
+        format!("RetryRecord(url='{}', attempts={}, recovered={})", self.url, self.attempts.len(), self.recovered)
+    }
+}
+
+impl From<RetryRecord> for PyRetryRecord {
+    fn from(r: RetryRecord) -> Self {
+        Self { url: r.url, attempts: r.attempts.into_iter().map(PyRetryAttempt::from).collect(), recovered: r.recovered }
+    }
+}
+
+/// Status, size, and timings of one robots.txt or sitemap fetch; the response and
+/// timing fields describe its last attempt
+#[pyclass(name = "FetchStats")]
+#[derive(Clone, Debug)]
+pub struct PyFetchStats {
+    #[pyo3(get)]
+    pub url: String,
+    /// Status of the final response; `None` if none arrived
+    #[pyo3(get)]
+    pub status: Option<u16>,
+    /// Body bytes read off the wire, before decompression
+    #[pyo3(get)]
+    pub bytes: usize,
+    /// `None` if a pooled connection was reused or the host is an IP address
+    #[pyo3(get)]
+    pub dns_seconds: Option<f64>,
+    /// TCP and TLS setup after DNS; `None` if a pooled connection was reused
+    #[pyo3(get)]
+    pub connect_seconds: Option<f64>,
+    /// Until the final response's headers arrived, redirects included
+    #[pyo3(get)]
+    pub ttfb_seconds: Option<f64>,
+    /// The whole fetch, retries and their backoff included
+    #[pyo3(get)]
+    pub total_seconds: f64,
+    #[pyo3(get)]
+    pub retries: usize,
+    #[pyo3(get)]
+    pub redirects: usize,
+    #[pyo3(get)]
+    pub cache_hit: bool,
+    /// The failed fetch's error kind, as in `SitemapError.kind`
+    #[pyo3(get)]
+    pub error: Option<String>,
+}
+
+#[pymethods]
+impl PyFetchStats {
+    fn __repr__(&self) -> String {
+        format!(
+            "FetchStats(url='{}', status={:?}, bytes={}, total_seconds={:.3}, retries={})",
+            self.url, self.status, self.bytes, self.total_seconds, self.retries
+        )
+    }
+}
+
+impl From<FetchStats> for PyFetchStats {
+    fn from(f: FetchStats) -> Self {
+        Self {
+            url: f.url,
+            status: f.status,
+            bytes: f.bytes,
+            dns_seconds: f.dns.map(|d| d.as_secs_f64()),
+            connect_seconds: f.connect.map(|d| d.as_secs_f64()),
+            ttfb_seconds: f.ttfb.map(|d| d.as_secs_f64()),
+            total_seconds: f.total.as_secs_f64(),
+            retries: f.retries,
+            redirects: f.redirects,
+            cache_hit: f.cache_hit,
+            error: f.error,
+        }
+    }
+}
+
+/// Retries summed up over one site, separating flaky-but-recovered hosts from broken ones
+#[pyclass(name = "RetryReport")]
+#[derive(Clone, Debug)]
+pub struct PyRetryReport {
+    /// Requests retried at least once
+    #[pyo3(get)]
+    pub retried: usize,
+    #[pyo3(get)]
+    pub recovered: usize,
+    #[pyo3(get)]
+    pub failed: usize,
+    /// Failed attempts per error kind
+    #[pyo3(get)]
+    pub errors: HashMap<String, usize>,
+    #[pyo3(get)]
+    pub total_backoff_seconds: f64,
+    /// Hosts whose retried requests all recovered
+    #[pyo3(get)]
+    pub flaky_hosts: Vec<String>,
+    /// Hosts with a request that still failed after its retries
+    #[pyo3(get)]
+    pub broken_hosts: Vec<String>,
+}
+
+#[pymethods]
+impl PyRetryReport {
+    fn __repr__(&self) -> String {
+        format!(
+            "RetryReport(retried={}, recovered={}, failed={}, flaky_hosts={}, broken_hosts={})",
+            self.retried, self.recovered, self.failed, self.flaky_hosts.len(), self.broken_hosts.len()
+        )
+    }
+}
+
+impl From<RetryReport> for PyRetryReport {
+    fn from(r: RetryReport) -> Self {
+        Self {
+            retried: r.retried,
+            recovered: r.recovered,
+            failed: r.failed,
+            errors: r.errors.into_iter().collect(),
+            total_backoff_seconds: r.total_backoff.as_secs_f64(),
+            flaky_hosts: r.flaky_hosts,
+            broken_hosts: r.broken_hosts,
+        }
+    }
+}
+
+/// Sitemap parsing result returned to Python
+// The module is named so pickle can find the class again when loading
+#[pyclass(module = "sitemap_parser_showdown.rust_parser")]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SitemapResult {
+    #[pyo3(get)]
+    pub base_url: String,
+    #[pyo3(get)]
+    pub urls: Vec<String>,
+    /// URLs yielded by `RustParser.stream_site` rather than kept in `urls`
+    #[pyo3(get)]
+    pub streamed_urls: usize,
+    /// URLs left out because robots.txt disallows them; 0 unless the parser ran with `respect_robots=True`
+    #[pyo3(get)]
+    pub disallowed_urls: usize,
+    /// URLs dropped as duplicates: listed by several sitemaps, or canonicalizing to one URL
+    #[pyo3(get)]
+    pub duplicate_urls: usize,
+    /// Seconds between requests the site's robots.txt asked for; only read with `respect_robots=True`
+    #[pyo3(get)]
+    pub crawl_delay: Option<f64>,
+    /// Responses answered 304 Not Modified and served from the HTTP cache
+    #[pyo3(get)]
+    pub cache_hits: usize,
+    /// Responses downloaded in full with the HTTP cache on
+    #[pyo3(get)]
+    pub cache_misses: usize,
+    /// URLs left out because their lastmod is before `modified_since`
+    #[pyo3(get)]
+    pub unmodified_urls: usize,
+    /// Child sitemaps not fetched because their index lastmod is before `modified_since`
+    #[pyo3(get)]
+    pub unmodified_sitemaps: usize,
+    #[pyo3(get)]
+    pub sitemaps_found: Vec<String>,
+    /// Sitemaps Bing Webmaster Tools listed for the site; empty unless the parser has a `bing_api_key`
+    #[pyo3(get)]
+    pub bing_sitemaps: Vec<String>,
+    #[pyo3(get)]
+    pub coalesced_sitemaps: Vec<String>,
+    #[pyo3(get)]
+    pub decompressed_sitemaps: Vec<String>,
+    /// Sitemaps that weren't UTF-8, mapped to the encoding each was transcoded from
+    #[pyo3(get)]
+    pub transcoded_sitemaps: HashMap<String, String>,
+    /// robots.txt and sitemaps that redirected, by the URL requested
+    #[pyo3(get)]
+    pub redirects: HashMap<String, RedirectInfo>,
+    #[pyo3(get)]
+    pub truncated: bool,
+    #[pyo3(get)]
+    pub truncated_sitemaps: Vec<TruncationInfo>,
+    #[pyo3(get)]
+    pub degraded: bool,
+    #[pyo3(get)]
+    pub degradation_level: usize,
+    #[pyo3(get)]
+    pub archived_captures: Vec<ArchiveInfo>,
+    #[pyo3(get)]
+    pub archived_urls: HashMap<String, String>,
+    /// DTDs and entity references found in sitemaps and ignored, as "<sitemap url>: <what>"
+    #[pyo3(get)]
+    pub security_warnings: Vec<String>,
+    /// Decoded body of each sitemap, by sitemap URL; empty unless the parser ran with `keep_bodies=True`
+    #[pyo3(get)]
+    pub raw_bodies: HashMap<String, String>,
+    /// User agent from `user_agents` that the site's host last answered successfully
+    #[pyo3(get)]
+    pub user_agent: Option<String>,
+    /// The run was interrupted while this site was parsed, so its URLs may be incomplete
+    #[pyo3(get)]
+    pub interrupted: bool,
+    /// The interruption came from the call's `CancelToken`
+    #[pyo3(get)]
+    pub cancelled: bool,
+    /// The site ran past `site_timeout_seconds`; its URLs are those found by then
+    #[pyo3(get)]
+    pub timed_out: bool,
+    /// The site listed more than `max_urls_per_site` URLs, so only that many were kept
+    #[pyo3(get)]
+    pub url_limit_reached: bool,
+    #[pyo3(get)]
+    pub errors: Vec<String>,
+    #[pyo3(get)]
+    pub parse_time: f64,
+    #[pyo3(get)]
+    pub total_requests: usize,
+    #[pyo3(get)]
+    pub memory: MemoryInfo,
+    /// Kept in core form; converted on access since it can hold millions of entries
+    url_metadata: HashMap<String, UrlMetadata>,
+    retries: Vec<RetryRecord>,
+    fetch_stats: Vec<FetchStats>,
+    error_details: Vec<crate::error::SitemapError>,
+}
+
+#[pymethods]
+impl SitemapResult {
+    #[new]
+    fn new(base_url: String) -> Self {
+        Self {
+            base_url,
+            urls: Vec::new(),
+            streamed_urls: 0,
+            disallowed_urls: 0,
+            duplicate_urls: 0,
+            crawl_delay: None,
+            cache_hits: 0,
+            cache_misses: 0,
+            unmodified_urls: 0,
+            unmodified_sitemaps: 0,
+            sitemaps_found: Vec::new(),
+            bing_sitemaps: Vec::new(),
+            coalesced_sitemaps: Vec::new(),
+            decompressed_sitemaps: Vec::new(),
+            transcoded_sitemaps: HashMap::new(),
+            redirects: HashMap::new(),
+            truncated: false,
+            truncated_sitemaps: Vec::new(),
+            degraded: false,
+            degradation_level: 0,
+            archived_captures: Vec::new(),
+            archived_urls: HashMap::new(),
+            security_warnings: Vec::new(),
+            raw_bodies: HashMap::new(),
+            user_agent: None,
+            interrupted: false,
+            cancelled: false,
+            timed_out: false,
+            url_limit_reached: false,
+            errors: Vec::new(),
+            parse_time: 0.0,
+            total_requests: 0,
+            memory: MemoryUsage::default().into(),
+            url_metadata: HashMap::new(),
+            retries: Vec::new(),
+            fetch_stats: Vec::new(),
+            error_details: Vec::new(),
+        }
+    }
+
+    /// `errors` as exception instances, e.g. `HttpError` with a `status` or a
+    /// `RobotsFetchError` with its `cause`; every one is a `SitemapError` with `kind` and `url`
+    #[getter]
+    fn error_details(&self, py: Python<'_>) -> PyResult<Vec<PyObject>> {
+        self.error_details.iter()
+            .map(|e| Ok(crate::exceptions::to_py_err(py, e)?.into_value(py).into_any()))
+            .collect()
+    }
+
+    /// Raise the first of `error_details`, if the site found no URLs; a site with URLs
+    /// and some failed sitemaps is returned as a partial result instead
+    fn raise_if_failed(&self, py: Python<'_>) -> PyResult<()> {
+        match self.error_details.first() {
+            Some(e) if self.urls.is_empty() && self.streamed_urls == 0 => Err(crate::exceptions::to_py_err(py, e)?),
+            _ => Ok(()),
+        }
+    }
+
+    /// Requests whose first attempt failed and were retried; empty unless the parser ran with `max_retries`
+    #[getter]
+    fn retries(&self) -> Vec<PyRetryRecord> {
+        self.retries.iter().cloned().map(PyRetryRecord::from).collect()
+    }
+
+    /// `retries` summed up: counts, error kinds, backoff, and which hosts recovered
+    #[getter]
+    fn retry_report(&self) -> PyRetryReport {
+        RetryReport::from_records(&self.retries).into()
+    }
+
+    /// Status, bytes, and DNS, connect, first-byte, and total times of every robots.txt
+    /// and sitemap fetch, to see where a slow site spent its time
+    #[getter]
+    fn fetch_stats(&self) -> Vec<PyFetchStats> {
+        self.fetch_stats.iter().cloned().map(PyFetchStats::from).collect()
+    }
+
+    /// Body bytes downloaded across `fetch_stats`
+    #[getter]
+    fn bytes_downloaded(&self) -> usize {
+        self.fetch_stats.iter().map(|stats| stats.bytes).sum()
+    }
+
+    /// lastmod, changefreq, priority, depth, extensions, media, and alternates per URL; empty unless the parser ran with `url_metadata=True`, `url_extensions=True`, `url_media=True`, or `url_alternates=True`
+    #[getter]
+    fn url_metadata(&self) -> HashMap<String, PyUrlMetadata> {
+        self.url_metadata.iter().map(|(url, m)| (url.clone(), m.clone().into())).collect()
+    }
+
+    /// Every URL in `urls` order with its lastmod, changefreq, and priority; those are
+    /// `None` unless the parser ran with `url_metadata=True` and the sitemap gave them
+    #[getter]
+    fn entries(&self) -> Vec<PyUrlEntry> {
+        self.urls.iter().map(|url| PyUrlEntry::new(url, self.url_metadata.get(url))).collect()
+    }
+
+    /// `urls` as a `pyarrow.RecordBatch` with `url`, `lastmod` (a UTC timestamp),
+    /// `priority`, and `source_sitemap` columns, built in Rust and handed over through
+    /// the Arrow C data interface without a Python object per URL.
+    ///
+    /// The metadata columns are null unless the parser ran with `url_metadata=True`.
+    /// Needs pyarrow installed and a build with the `arrow` feature.
+    #[cfg(feature = "arrow")]
+    fn to_arrow(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let batch = crate::export::url_record_batch(&self.urls, &self.url_metadata)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to build Arrow batch: {}", e)))?;
+        let data = arrow_array::Array::into_data(arrow_array::StructArray::from(batch));
+        let mut array = arrow_array::ffi::FFI_ArrowArray::new(&data);
+        let mut schema = arrow_array::ffi::FFI_ArrowSchema::try_from(data.data_type())
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to export Arrow schema: {}", e)))?;
+        let pyarrow = py.import("pyarrow")
+            .map_err(|_| PyErr::new::<pyo3::exceptions::PyImportError, _>("to_arrow() needs pyarrow installed"))?;
+        // pyarrow moves both structs' contents out and takes over releasing them
+        let batch = pyarrow.getattr("RecordBatch")?
+            .call_method1("_import_from_c", (&raw mut array as usize, &raw mut schema as usize))?;
+        Ok(batch.unbind())
+    }
+
+    /// `to_arrow()` as a pandas DataFrame, converted by pyarrow without going through
+    /// Python lists
+    #[cfg(feature = "arrow")]
+    fn to_pandas(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let batch = self.to_arrow(py)?;
+        batch.call_method0(py, "to_pandas")
+    }
+
+    /// Rank this result's URLs by a weighted score, best first.
+    ///
+    /// The score is `priority_weight * priority + recency_weight * recency +
+    /// depth_weight / depth + section weight`, where recency halves every
+    /// `half_life_days` since lastmod and `section_weights` maps path prefixes
+    /// (e.g. `"/blog"`) to a bonus or penalty. The ranking is split into `tiers`
+    /// equal buckets before `top_n` cuts it short.
+    #[pyo3(signature = (priority_weight = 1.0, recency_weight = 1.0, depth_weight = 0.5, section_weights = None, half_life_days = 30.0, tiers = 1, top_n = None))]
+    #[allow(clippy::too_many_arguments)]
+    fn score_urls(
+        &self,
+        priority_weight: f64,
+        recency_weight: f64,
+        depth_weight: f64,
+        section_weights: Option<HashMap<String, f64>>,
+        half_life_days: f64,
+        tiers: usize,
+        top_n: Option<usize>,
+    ) -> PyResult<Vec<PyScoredUrl>> {
+        if half_life_days <= 0.0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("half_life_days must be positive"));
+        }
+        let weights = ScoreWeights {
+            priority: priority_weight,
+            recency: recency_weight,
+            depth: depth_weight,
+            sections: section_weights.unwrap_or_default().into_iter().collect(),
+            half_life_days,
+        };
+        let ranked = rank_urls(&self.urls, &self.url_metadata, &weights, tiers, chrono::Utc::now());
+        Ok(ranked.into_iter().take(top_n.unwrap_or(usize::MAX)).map(PyScoredUrl::from).collect())
+    }
+
+    /// Infer URL templates like `/blog/{yyyy}/{mm}/{slug}` with a count per template, most common first.
+    ///
+    /// IDs, dates, UUIDs, and hashes are recognised by shape; a path position with
+    /// more than `max_literals` distinct values becomes `{slug}`.
+    #[pyo3(signature = (max_literals = 20, top_n = None))]
+    fn url_templates(&self, max_literals: usize, top_n: Option<usize>) -> Vec<PyUrlTemplate> {
+        mine_templates(&self.urls, max_literals)
+            .into_iter()
+            .take(top_n.unwrap_or(usize::MAX))
+            .map(PyUrlTemplate::from)
+            .collect()
+    }
+
+    /// Flag suspicious entries that hint at a compromised or misconfigured sitemap,
+    /// grouped and most widespread first.
+    ///
+    /// Looks for URLs on other domains (subdomains of the site are fine), more than
+    /// `max_query_variants` query-string variants of one path, lastmods more than
+    /// `future_tolerance_days` ahead (needs `url_metadata=True`), and URLs far longer
+    /// than the site's usual ones: beyond the upper quartile plus 3 interquartile
+    /// ranges and at least `min_long_length` characters, or over 2048 in any case.
+    #[pyo3(signature = (max_query_variants = 1000, future_tolerance_days = 1.0, min_long_length = 200))]
+    fn anomalies(&self, max_query_variants: usize, future_tolerance_days: f64, min_long_length: usize) -> Vec<PyAnomaly> {
+        let thresholds = AnomalyThresholds {
+            max_query_variants,
+            future_tolerance: chrono::Duration::seconds((future_tolerance_days * 86_400.0) as i64),
+            min_long_length,
+            ..Default::default()
+        };
+        detect_anomalies(&self.base_url, &self.urls, &self.url_metadata, &thresholds, chrono::Utc::now())
+            .into_iter()
+            .map(PyAnomaly::from)
+            .collect()
+    }
+
+    /// The URL list as one gzip-compressed blob of newline-separated URLs, for shipping
+    /// through queues or to subprocesses; read it back with `unpack_urls` or `gzip.decompress`
+    #[pyo3(signature = (level = 6))]
+    fn urls_compressed<'py>(&self, py: Python<'py>, level: u32) -> PyResult<Bound<'py, PyBytes>> {
+        let packed = py
+            .allow_threads(|| crate::transfer::pack_urls(&self.urls, level))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to compress URLs: {}", e)))?;
+        Ok(PyBytes::new(py, &packed))
+    }
+
+    /// The whole result as JSON, private fields like `url_metadata` and `retries`
+    /// included, so `SitemapResult.from_json` gives it back unchanged. `indent` pretty-prints
+    /// like `json.dumps`.
+    #[pyo3(signature = (indent = None))]
+    fn to_json(&self, indent: Option<usize>) -> PyResult<String> {
+        let json = match indent {
+            Some(indent) => {
+                let indent = " ".repeat(indent);
+                let mut out = Vec::new();
+                let mut serializer = serde_json::Serializer::with_formatter(&mut out, serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes()));
+                self.serialize(&mut serializer).map(|_| String::from_utf8(out).expect("serde_json writes UTF-8"))
+            }
+            None => serde_json::to_string(self),
+        };
+        json.map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Failed to serialize result: {}", e)))
+    }
+
+    /// A result written by `to_json`
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        serde_json::from_str(json).map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Not a SitemapResult: {}", e)))
+    }
+
+    /// The `to_json` document as plain dicts and lists
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        Ok(py.import("json")?.call_method1("loads", (self.to_json(None)?,))?.unbind())
+    }
+
+    /// Pickle support, so results cross `multiprocessing` and celery boundaries
+    fn __getstate__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let state = serde_json::to_vec(self).map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Failed to serialize result: {}", e)))?;
+        Ok(PyBytes::new(py, &state))
+    }
+
+    fn __setstate__(&mut self, state: &[u8]) -> PyResult<()> {
+        *self = serde_json::from_slice(state).map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Not a pickled SitemapResult: {}", e)))?;
+        Ok(())
+    }
+
+    /// What pickle passes to `__new__` before `__setstate__` fills in the rest
+    fn __getnewargs__(&self) -> (String,) {
+        (self.base_url.clone(),)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "SitemapResult(base_url='{}', urls={}, sitemaps={}, errors={}, time={:.2}s, requests={}, truncated={})",
+            self.base_url,
+            self.urls.len(),
+            self.sitemaps_found.len(),
+            self.errors.len(),
+            self.parse_time,
+            self.total_requests,
+            self.truncated
+        )
+    }
+
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+}
+
+impl SitemapResult {
+    /// Convert a core result, sorting the URL list when `deterministic` is set
+    fn from_parsed(r: ParsedSiteResult, deterministic: bool) -> Self {
+        let urls = r.url_list(deterministic);
+        let mut result = SitemapResult::new(r.base_url);
+        result.urls = urls;
+        result.streamed_urls = r.streamed_urls;
+        result.disallowed_urls = r.disallowed_urls;
+        result.duplicate_urls = r.duplicate_urls;
+        result.crawl_delay = r.crawl_delay.map(|delay| delay.as_secs_f64());
+        result.sitemaps_found = r.sitemaps_found;
+        result.bing_sitemaps = r.bing_sitemaps;
+        result.coalesced_sitemaps = r.coalesced_sitemaps;
+        result.decompressed_sitemaps = r.decompressed_sitemaps;
+        result.transcoded_sitemaps = r.transcoded_sitemaps;
+        result.redirects = r.redirects.into_iter().map(|(url, redirect)| (url, redirect.into())).collect();
+        result.truncated = r.truncated;
+        result.truncated_sitemaps = r.truncated_sitemaps.into_iter().map(TruncationInfo::from).collect();
+        result.degraded = r.degraded;
+        result.degradation_level = r.degradation_level;
+        result.archived_captures = r.archived_captures.into_iter().map(ArchiveInfo::from).collect();
+        result.archived_urls = r.archived_urls;
+        result.security_warnings = r.security_warnings;
+        result.raw_bodies = r.raw_bodies;
+        result.user_agent = r.user_agent;
+        result.interrupted = r.interrupted;
+        result.timed_out = r.timed_out;
+        result.url_limit_reached = r.url_limit_reached;
+        result.total_requests = r.total_requests;
+        result.errors = r.errors;
+        result.error_details = r.error_details;
+        result.parse_time = r.parse_time;
+        result.memory = r.memory.into();
+        result.url_metadata = r.url_metadata;
+        result.retries = r.retries;
+        result.fetch_stats = r.fetch_stats;
+        result.cache_hits = r.cache_hits;
+        result.cache_misses = r.cache_misses;
+        result.unmodified_urls = r.unmodified_urls;
+        result.unmodified_sitemaps = r.unmodified_sitemaps;
+        result
+    }
+}
+
+/// One batch of URLs sent to one IndexNow endpoint
+#[pyclass(name = "IndexNowSubmission")]
+#[derive(Clone, Debug)]
+pub struct PyIndexNowSubmission {
+    #[pyo3(get)]
+    pub endpoint: String,
+    #[pyo3(get)]
+    pub host: String,
+    #[pyo3(get)]
+    pub urls: usize,
+    #[pyo3(get)]
+    pub status: Option<u16>,
+    #[pyo3(get)]
+    pub accepted: bool,
+    #[pyo3(get)]
+    pub message: String,
+}
+
+#[pymethods]
+impl PyIndexNowSubmission {
+    fn __repr__(&self) -> String {
+        format!(
+            "IndexNowSubmission(endpoint='{}', host='{}', urls={}, status={:?}, accepted={})",
+            self.endpoint, self.host, self.urls, self.status, self.accepted
+        )
+    }
+}
+
+impl From<crate::indexnow::IndexNowSubmission> for PyIndexNowSubmission {
+    fn from(s: crate::indexnow::IndexNowSubmission) -> Self {
+        Self {
+            endpoint: s.endpoint,
+            host: s.host,
+            urls: s.urls,
+            status: s.status,
+            accepted: s.accepted,
+            message: s.message,
+        }
+    }
+}
+
+/// A sitemap submitted in Search Console, with Google's URL count next to ours
+#[cfg(feature = "search-console")]
+#[pyclass(name = "SitemapCountComparison")]
+#[derive(Clone, Debug)]
+pub struct PySitemapCountComparison {
+    #[pyo3(get)]
+    pub sitemap: String,
+    #[pyo3(get)]
+    pub is_index: bool,
+    #[pyo3(get)]
+    pub is_pending: bool,
+    /// Web URLs Search Console says the sitemap (or index) submitted
+    #[pyo3(get)]
+    pub reported_urls: u64,
+    #[pyo3(get)]
+    pub parsed_urls: Option<usize>,
+    /// Parsed minus reported, or `None` if the sitemap failed to parse or is pending
+    #[pyo3(get)]
+    pub difference: Option<i64>,
+    #[pyo3(get)]
+    pub discrepancy: bool,
+    #[pyo3(get)]
+    pub reported_errors: u64,
+    #[pyo3(get)]
+    pub reported_warnings: u64,
+    #[pyo3(get)]
+    pub last_downloaded: Option<String>,
+    /// Why parsing the sitemap failed
+    #[pyo3(get)]
+    pub error: Option<String>,
+}
+
+#[cfg(feature = "search-console")]
+#[pymethods]
+impl PySitemapCountComparison {
+    fn __repr__(&self) -> String {
+        format!(
+            "SitemapCountComparison(sitemap='{}', reported_urls={}, parsed_urls={:?}, discrepancy={})",
+            self.sitemap, self.reported_urls, self.parsed_urls, self.discrepancy
+        )
+    }
+}
+
+#[cfg(feature = "search-console")]
+impl From<crate::search_console::SitemapCountComparison> for PySitemapCountComparison {
+    fn from(c: crate::search_console::SitemapCountComparison) -> Self {
+        Self {
+            difference: c.difference(),
+            discrepancy: c.is_discrepancy(),
+            sitemap: c.sitemap.path,
+            is_index: c.sitemap.is_index,
+            is_pending: c.sitemap.is_pending,
+            reported_urls: c.sitemap.submitted_urls,
+            parsed_urls: c.parsed_urls,
+            reported_errors: c.sitemap.errors,
+            reported_warnings: c.sitemap.warnings,
+            last_downloaded: c.sitemap.last_downloaded,
+            error: c.error,
+        }
+    }
+}
+
+/// A live terminal dashboard of a parser's sites; use it as a context manager or call `close()`
+#[cfg(feature = "tui")]
+#[pyclass(name = "Dashboard")]
+pub struct PyDashboard {
+    dashboard: crate::tui::Dashboard,
+}
+
+#[cfg(feature = "tui")]
+#[pymethods]
+impl PyDashboard {
+    /// Stop drawing and restore the terminal; safe to call more than once
+    fn close(&mut self) -> PyResult<()> {
+        self.dashboard
+            .close()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to restore terminal: {}", e)))
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __exit__(&mut self, _exc_type: PyObject, _exc_value: PyObject, _traceback: PyObject) -> PyResult<bool> {
+        self.close()?;
+        Ok(false)
+    }
+}
+
+/// Request counts and health of one proxy in a parser's pool
+#[pyclass(name = "ProxyStats")]
+#[derive(Clone, Debug)]
+pub struct PyProxyStats {
+    /// Proxy URL without credentials
+    #[pyo3(get)]
+    pub proxy: String,
+    #[pyo3(get)]
+    pub requests: usize,
+    #[pyo3(get)]
+    pub failures: usize,
+    #[pyo3(get)]
+    pub ejected: bool,
+}
+
+#[pymethods]
+impl PyProxyStats {
+    fn __repr__(&self) -> String {
+        format!(
+            "ProxyStats(proxy='{}', requests={}, failures={}, ejected={})",
+            self.proxy, self.requests, self.failures, self.ejected
+        )
+    }
+}
+
+impl From<crate::proxies::ProxyStats> for PyProxyStats {
+    fn from(s: crate::proxies::ProxyStats) -> Self {
+        Self { proxy: s.proxy, requests: s.requests, failures: s.failures, ejected: s.ejected }
+    }
+}
+
+/// Count, total, longest, and mean of a set of waits or requests, in seconds
+#[pyclass(name = "DurationStats")]
+#[derive(Clone, Debug)]
+pub struct PyDurationStats {
+    #[pyo3(get)]
+    pub count: u64,
+    #[pyo3(get)]
+    pub total_seconds: f64,
+    #[pyo3(get)]
+    pub max_seconds: f64,
+    #[pyo3(get)]
+    pub mean_seconds: f64,
+}
+
+#[pymethods]
+impl PyDurationStats {
+    fn __repr__(&self) -> String {
+        format!(
+            "DurationStats(count={}, total={:.3}s, max={:.3}s, mean={:.3}s)",
+            self.count, self.total_seconds, self.max_seconds, self.mean_seconds
+        )
+    }
+}
+
+impl From<DurationStats> for PyDurationStats {
+    fn from(s: DurationStats) -> Self {
+        Self {
+            count: s.count,
+            total_seconds: s.total.as_secs_f64(),
+            max_seconds: s.max.as_secs_f64(),
+            mean_seconds: s.mean().as_secs_f64(),
+        }
+    }
+}
+
+/// Queue depths, wait times, and in-flight counts of a `RustParser` since it was created
+#[pyclass(name = "ContentionStats")]
+#[derive(Clone, Debug)]
+pub struct PyContentionStats {
+    /// Sites waiting for one of the `max_concurrent` slots right now
+    #[pyo3(get)]
+    pub sites_waiting: usize,
+    #[pyo3(get)]
+    pub peak_sites_waiting: usize,
+    #[pyo3(get)]
+    pub sites_in_flight: usize,
+    /// Time sites spent waiting for a `max_concurrent` slot
+    #[pyo3(get)]
+    pub site_waits: PyDurationStats,
+    /// Requests waiting on their site's in-flight limit (degraded or reputation-capped sites) or on `max_concurrent_requests` / `max_requests_per_host`
+    #[pyo3(get)]
+    pub requests_waiting: usize,
+    #[pyo3(get)]
+    pub peak_requests_waiting: usize,
+    #[pyo3(get)]
+    pub request_waits: PyDurationStats,
+    #[pyo3(get)]
+    pub requests_in_flight: usize,
+    /// Time from sending each request to having its whole body
+    #[pyo3(get)]
+    pub request_times: PyDurationStats,
+    /// Requests in flight per host right now
+    #[pyo3(get)]
+    pub hosts_in_flight: HashMap<String, usize>,
+    /// The most requests ever in flight to one host, as (host, count)
+    #[pyo3(get)]
+    pub peak_host_in_flight: Option<(String, usize)>,
+}
+
+#[pymethods]
+impl PyContentionStats {
+    fn __repr__(&self) -> String {
+        format!(
+            "ContentionStats(sites_waiting={}, sites_in_flight={}, requests_waiting={}, requests_in_flight={}, site_wait={:.3}s, request_wait={:.3}s, request_time={:.3}s)",
+            self.sites_waiting,
+            self.sites_in_flight,
+            self.requests_waiting,
+            self.requests_in_flight,
+            self.site_waits.total_seconds,
+            self.request_waits.total_seconds,
+            self.request_times.total_seconds
+        )
+    }
+}
+
+impl From<crate::contention::ContentionSnapshot> for PyContentionStats {
+    fn from(s: crate::contention::ContentionSnapshot) -> Self {
+        Self {
+            sites_waiting: s.sites_waiting,
+            peak_sites_waiting: s.peak_sites_waiting,
+            sites_in_flight: s.sites_in_flight,
+            site_waits: s.site_waits.into(),
+            requests_waiting: s.requests_waiting,
+            peak_requests_waiting: s.peak_requests_waiting,
+            request_waits: s.request_waits.into(),
+            requests_in_flight: s.requests_in_flight,
+            request_times: s.request_times.into(),
+            hosts_in_flight: s.hosts_in_flight,
+            peak_host_in_flight: s.peak_host_in_flight,
+        }
+    }
+}
+
+/// A site that finished parsing, as `RunStatus.recent` lists it
+#[pyclass(name = "FinishedSite")]
+#[derive(Clone, Debug)]
+pub struct PyFinishedSite {
+    #[pyo3(get)]
+    pub base_url: String,
+    /// "ok", "partial" (URLs and errors), "failed" (errors only), or "interrupted"
+    #[pyo3(get)]
+    pub outcome: String,
+    #[pyo3(get)]
+    pub urls: usize,
+    #[pyo3(get)]
+    pub errors: usize,
+    #[pyo3(get)]
+    pub elapsed_seconds: f64,
+}
+
+#[pymethods]
+impl PyFinishedSite {
+    fn __repr__(&self) -> String {
+        format!("FinishedSite(base_url='{}', outcome='{}', urls={}, errors={})", self.base_url, self.outcome, self.urls, self.errors)
+    }
+}
+
+impl From<FinishedSite> for PyFinishedSite {
+    fn from(s: FinishedSite) -> Self {
+        Self {
+            base_url: s.base_url,
+            outcome: s.outcome.to_string(),
+            urls: s.urls,
+            errors: s.errors,
+            elapsed_seconds: s.elapsed.as_secs_f64(),
+        }
+    }
+}
+
+/// What `parse_site_to_file` or `parse_multiple_sites_to_file` wrote, in place of the URLs
+#[pyclass(name = "ExportSummary")]
+#[derive(Clone, Debug)]
+pub struct PyExportSummary {
+    #[pyo3(get)]
+    pub path: PathBuf,
+    /// "jsonl", "csv", or "parquet"
+    #[pyo3(get)]
+    pub format: String,
+    #[pyo3(get)]
+    pub sites: usize,
+    /// Sites that gave no URLs, only errors
+    #[pyo3(get)]
+    pub failed_sites: usize,
+    #[pyo3(get)]
+    pub urls: usize,
+    #[pyo3(get)]
+    pub errors: Vec<String>,
+    #[pyo3(get)]
+    pub bytes_written: u64,
+    #[pyo3(get)]
+    pub parse_time: f64,
+}
+
+#[pymethods]
+impl PyExportSummary {
+    fn __repr__(&self) -> String {
+        format!(
+            "ExportSummary(path='{}', format='{}', sites={}, urls={}, bytes_written={})",
+            self.path.display(), self.format, self.sites, self.urls, self.bytes_written,
+        )
+    }
+}
+
+impl PyExportSummary {
+    fn new(summary: ExportSummary, path: PathBuf, format: ExportFormat, parse_time: f64) -> Self {
+        Self {
+            path,
+            format: format.to_string(),
+            sites: summary.sites,
+            failed_sites: summary.failed_sites,
+            urls: summary.urls,
+            errors: summary.errors,
+            bytes_written: summary.bytes_written,
+            parse_time,
+        }
+    }
+}
+
+/// `format` as an `ExportFormat`, or ValueError naming the ones there are
+fn parse_export_format(format: &str) -> PyResult<ExportFormat> {
+    ExportFormat::parse(format).ok_or_else(|| {
+        PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown export format {:?}; use \"jsonl\", \"csv\", or \"parquet\"", format))
+    })
+}
+
+/// Files written by a `SitemapWriter`
+#[pyclass(name = "SitemapWriteSummary")]
+#[derive(Clone, Debug)]
+pub struct PySitemapWriteSummary {
+    /// The sitemap index to submit or list in robots.txt
+    #[pyo3(get)]
+    pub index: PathBuf,
+    #[pyo3(get)]
+    pub sitemaps: Vec<PathBuf>,
+    #[pyo3(get)]
+    pub urls: usize,
+    #[pyo3(get)]
+    pub bytes_written: u64,
+}
+
+#[pymethods]
+impl PySitemapWriteSummary {
+    fn __repr__(&self) -> String {
+        format!("SitemapWriteSummary(index='{}', sitemaps={}, urls={}, bytes_written={})", self.index.display(), self.sitemaps.len(), self.urls, self.bytes_written)
+    }
+}
+
+impl From<SitemapWriteSummary> for PySitemapWriteSummary {
+    fn from(s: SitemapWriteSummary) -> Self {
+        Self { index: s.index, sitemaps: s.sitemaps, urls: s.urls, bytes_written: s.bytes_written }
+    }
+}
+
+/// Writes URLs out as sitemap files: urlsets of up to `max_urls_per_file` URLs,
+/// optionally gzipped, plus a sitemap index of them written by `finish()` (or on
+/// leaving a `with` block). `base_url` is where `directory` will be served from.
+#[pyclass(name = "SitemapWriter")]
+pub struct PySitemapWriter {
+    writer: Option<SitemapWriter>,
+    summary: Option<PySitemapWriteSummary>,
+}
+
+#[pymethods]
+impl PySitemapWriter {
+    #[new]
+    #[pyo3(signature = (directory, base_url, gzip = false, max_urls_per_file = MAX_URLS_PER_SITEMAP, prefix = "sitemap"))]
+    fn new(directory: PathBuf, base_url: &str, gzip: bool, max_urls_per_file: usize, prefix: &str) -> PyResult<Self> {
+        let writer = SitemapWriter::create(&directory, base_url).map_err(write_error)?
+            .with_gzip(gzip)
+            .with_max_urls(max_urls_per_file)
+            .with_prefix(prefix);
+        Ok(Self { writer: Some(writer), summary: None })
+    }
+
+    /// Add a URL; `images` are image URLs. ValueError if the sitemap protocol wouldn't
+    /// accept the entry, e.g. a relative URL or a priority above 1.0.
+    #[pyo3(signature = (url, lastmod = None, changefreq = None, priority = None, images = None))]
+    fn add(&mut self, url: &str, lastmod: Option<String>, changefreq: Option<String>, priority: Option<f64>, images: Option<Vec<String>>) -> PyResult<()> {
+        let metadata = UrlMetadata {
+            lastmod,
+            changefreq,
+            priority,
+            images: images.unwrap_or_default().into_iter().map(|loc| ImageEntry { loc, ..Default::default() }).collect(),
+            ..Default::default()
+        };
+        self.writer()?.add(url, &metadata).map_err(write_error)
+    }
+
+    /// Add a `UrlEntry` from a parse result, with its lastmod, changefreq, priority, and images
+    fn add_entry(&mut self, entry: PyRef<'_, PyUrlEntry>) -> PyResult<()> {
+        let metadata = UrlMetadata {
+            lastmod: entry.lastmod.clone(),
+            changefreq: entry.changefreq.clone(),
+            priority: entry.priority,
+            images: entry.images.iter().map(|i| ImageEntry {
+                loc: i.loc.clone(),
+                caption: i.caption.clone(),
+                title: i.title.clone(),
+                geo_location: i.geo_location.clone(),
+                license: i.license.clone(),
+            }).collect(),
+            ..Default::default()
+        };
+        self.writer()?.add(&entry.url, &metadata).map_err(write_error)
+    }
+
+    /// Close the last urlset and write the index. Calling it again returns the same summary.
+    fn finish(&mut self) -> PyResult<PySitemapWriteSummary> {
+        if let Some(writer) = self.writer.take() {
+            self.summary = Some(writer.finish().map_err(write_error)?.into());
+        }
+        Ok(self.summary.clone().expect("finish ran"))
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __exit__(&mut self, _exc_type: PyObject, _exc_value: PyObject, _traceback: PyObject) -> PyResult<bool> {
+        self.finish()?;
+        Ok(false)
+    }
+}
+
+impl PySitemapWriter {
+    fn writer(&mut self) -> PyResult<&mut SitemapWriter> {
+        self.writer.as_mut().ok_or_else(|| PyErr::new::<pyo3::exceptions::PyValueError, _>("SitemapWriter is finished"))
+    }
+}
+
+/// ValueError for entries the protocol rejects, OSError for failed writes
+fn write_error(e: std::io::Error) -> PyErr {
+    if e.kind() == std::io::ErrorKind::InvalidInput {
+        PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+    } else {
+        PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to write sitemap: {}", e))
+    }
+}
+
+/// The URLs of one or more parse results and their lastmods, saved between runs to
+/// see what changed with `SitemapSnapshot.diff(previous, current)`
+#[pyclass(name = "SitemapSnapshot")]
+#[derive(Clone, Debug)]
+pub struct PySitemapSnapshot {
+    inner: SitemapSnapshot,
+}
+
+#[pymethods]
+impl PySitemapSnapshot {
+    /// Snapshot every URL in `results` (a `SitemapResult` or a list of them), taken now.
+    /// Lastmods are only there for results parsed with `url_metadata=True`.
+    #[staticmethod]
+    fn from_results(results: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let results: Vec<PyRef<'_, SitemapResult>> = match results.downcast::<SitemapResult>() {
+            Ok(result) => vec![result.borrow()],
+            Err(_) => results.extract()?,
+        };
+        let mut inner = SitemapSnapshot::new();
+        for result in &results {
+            for url in &result.urls {
+                inner.urls.insert(url.clone(), result.url_metadata.get(url).and_then(|meta| meta.lastmod.clone()));
+            }
+        }
+        Ok(Self { inner })
+    }
+
+    /// Read a snapshot saved in either format
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<Self> {
+        SitemapSnapshot::load(&path).map(|inner| Self { inner }).map_err(|e| snapshot_error(e, &path))
+    }
+
+    /// Write the snapshot to `path`, replacing it atomically. `format` is "json" or
+    /// "binary" (gzipped, much smaller); by default a `.json` path gets JSON and any
+    /// other gets binary.
+    #[pyo3(signature = (path, format = None))]
+    fn save(&self, path: PathBuf, format: Option<&str>) -> PyResult<()> {
+        let format = match format {
+            Some(name) => SnapshotFormat::parse(name).ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown snapshot format {:?}; use \"json\" or \"binary\"", name))
+            })?,
+            None => SnapshotFormat::for_path(&path),
+        };
+        self.inner.save(&path, format).map_err(|e| snapshot_error(e, &path))
+    }
+
+    /// Added, removed, and lastmod-changed URLs going from `previous` to `current`
+    #[staticmethod]
+    fn diff(previous: PyRef<'_, Self>, current: PyRef<'_, Self>) -> PySnapshotDiff {
+        diff(&previous.inner, &current.inner).into()
+    }
+
+    /// When the snapshot was made, as an RFC 3339 UTC timestamp
+    #[getter]
+    fn taken_at(&self) -> String {
+        self.inner.taken_at.clone()
+    }
+
+    /// `{url: lastmod or None}`
+    #[getter]
+    fn urls(&self) -> HashMap<String, Option<String>> {
+        self.inner.urls.iter().map(|(url, lastmod)| (url.clone(), lastmod.clone())).collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.urls.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("SitemapSnapshot(taken_at='{}', urls={})", self.inner.taken_at, self.inner.urls.len())
+    }
+}
+
+/// ValueError for a file that isn't a snapshot, OSError for one that can't be read or written
+fn snapshot_error(e: std::io::Error, path: &Path) -> PyErr {
+    if e.kind() == std::io::ErrorKind::InvalidData {
+        PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+    } else {
+        PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to access snapshot {}: {}", path.display(), e))
+    }
+}
+
+/// What changed between two snapshots, each list sorted by URL
+#[pyclass(name = "SnapshotDiff")]
+#[derive(Clone, Debug)]
+pub struct PySnapshotDiff {
+    #[pyo3(get)]
+    pub added: Vec<String>,
+    #[pyo3(get)]
+    pub removed: Vec<String>,
+    /// `(url, previous lastmod, current lastmod)` for URLs in both whose lastmod changed
+    #[pyo3(get)]
+    pub lastmod_changed: Vec<(String, Option<String>, Option<String>)>,
+}
+
+#[pymethods]
+impl PySnapshotDiff {
+    fn __bool__(&self) -> bool {
+        !(self.added.is_empty() && self.removed.is_empty() && self.lastmod_changed.is_empty())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("SnapshotDiff(added={}, removed={}, lastmod_changed={})", self.added.len(), self.removed.len(), self.lastmod_changed.len())
+    }
+}
+
+impl From<SnapshotDiff> for PySnapshotDiff {
+    fn from(d: SnapshotDiff) -> Self {
+        Self {
+            added: d.added,
+            removed: d.removed,
+            lastmod_changed: d.lastmod_changed.into_iter().map(|c| (c.url, c.previous, c.current)).collect(),
+        }
+    }
+}
+
+/// One step of a site's parse, passed to the `progress` callback of `parse_site` and
+/// `parse_multiple_sites`
+#[pyclass(name = "ProgressEvent")]
+#[derive(Clone, Debug)]
+pub struct PyProgressEvent {
+    /// "site_started", "sitemap_fetched", "error", or "site_finished"
+    #[pyo3(get)]
+    pub kind: String,
+    #[pyo3(get)]
+    pub base_url: String,
+    /// The sitemap fetched, or the robots.txt or sitemap that failed
+    #[pyo3(get)]
+    pub sitemap_url: Option<String>,
+    /// URLs found on the site so far, counted once per sitemap listing them; the site's final count on "site_finished"
+    #[pyo3(get)]
+    pub urls: usize,
+    /// Sitemaps fetched for the site so far
+    #[pyo3(get)]
+    pub sitemaps: usize,
+    /// Errors on the site so far; all of them on "site_finished"
+    #[pyo3(get)]
+    pub errors: usize,
+    /// What went wrong, on "error"
+    #[pyo3(get)]
+    pub message: Option<String>,
+}
+
+#[pymethods]
+impl PyProgressEvent {
+    fn __repr__(&self) -> String {
+        format!("ProgressEvent(kind='{}', base_url='{}', urls={}, sitemaps={}, errors={})", self.kind, self.base_url, self.urls, self.sitemaps, self.errors)
+    }
+}
+
+impl From<ProgressEvent> for PyProgressEvent {
+    fn from(e: ProgressEvent) -> Self {
+        Self {
+            kind: e.kind.to_string(),
+            base_url: e.base_url,
+            sitemap_url: e.sitemap_url,
+            urls: e.urls,
+            sitemaps: e.sitemaps,
+            errors: e.errors,
+            message: e.message,
+        }
+    }
+}
+
+/// Cancels the `parse_site` or `parse_multiple_sites` calls it's passed to, leaving the
+/// parser free for other calls
+#[pyclass(name = "CancelToken")]
+pub struct PyCancelToken {
+    shutdown: Arc<Shutdown>,
+}
+
+#[pymethods]
+impl PyCancelToken {
+    #[new]
+    fn new() -> Self {
+        Self { shutdown: Arc::new(Shutdown::new()) }
+    }
+
+    /// Stop the calls promptly: no new sites or fetches start and fetches in flight are
+    /// dropped. Each call returns what it has, with its sites marked `interrupted` and
+    /// `cancelled`. A token can't be reset; use a new one for the next call.
+    fn cancel(&self) {
+        info!("🦀 Cancelling parse");
+        self.shutdown.request(Duration::ZERO);
+    }
+
+    #[getter]
+    fn cancelled(&self) -> bool {
+        self.shutdown.is_requested()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("CancelToken(cancelled={})", self.cancelled())
+    }
+}
+
+/// Live per-site status of a parser's runs, from `RustParser.run_status()`
+#[pyclass(name = "RunStatus")]
+#[derive(Clone, Debug)]
+pub struct PyRunStatus {
+    /// Seconds since the parser was created
+    #[pyo3(get)]
+    pub elapsed_seconds: f64,
+    #[pyo3(get)]
+    pub finished: usize,
+    #[pyo3(get)]
+    pub failed: usize,
+    #[pyo3(get)]
+    pub urls: usize,
+    #[pyo3(get)]
+    pub errors: usize,
+    #[pyo3(get)]
+    pub sites_per_minute: f64,
+    #[pyo3(get)]
+    pub urls_per_second: f64,
+    /// `(base_url, seconds)` for every site being parsed, slowest first
+    #[pyo3(get)]
+    pub in_flight: Vec<(String, f64)>,
+    /// The last 200 finished sites, newest first
+    #[pyo3(get)]
+    pub recent: Vec<PyFinishedSite>,
+}
+
+#[pymethods]
+impl PyRunStatus {
+    fn __repr__(&self) -> String {
+        format!(
+            "RunStatus(finished={}, failed={}, in_flight={}, urls={}, sites_per_minute={:.1})",
+            self.finished, self.failed, self.in_flight.len(), self.urls, self.sites_per_minute
+        )
+    }
+}
+
+impl From<MonitorSnapshot> for PyRunStatus {
+    fn from(s: MonitorSnapshot) -> Self {
+        Self {
+            elapsed_seconds: s.elapsed.as_secs_f64(),
+            sites_per_minute: s.sites_per_minute(),
+            urls_per_second: s.urls_per_second(),
+            finished: s.finished,
+            failed: s.failed,
+            urls: s.urls,
+            errors: s.errors,
+            in_flight: s.in_flight.into_iter().map(|(site, elapsed)| (site, elapsed.as_secs_f64())).collect(),
+            recent: s.recent.into_iter().map(PyFinishedSite::from).collect(),
+        }
+    }
+}
+
+/// Rust-powered sitemap parser exposed to Python
+#[pyclass]
+pub struct RustParser {
+    config: ParserConfig,
+    /// Every robots.txt this parser has read, for `fetch_robots`
+    robots_cache: Arc<RobotsCache>,
+    contention: Arc<ContentionTracker>,
+    monitor: Arc<RunMonitor>,
+    shutdown: Arc<Shutdown>,
+    /// Built on first use and shared by every call after, so its connection pool and
+    /// resolved hosts stay warm; `None` again once closed
+    client: Mutex<Option<reqwest::Client>>,
+    closed: AtomicBool,
+}
+
+impl RustParser {
+    /// Run `job` on the shared runtime, feeding its results to the returned handle
+    fn spawn_job(&self, job: crate::job::ParseJob, max_buffered: usize) -> PyResult<PyParseJob> {
+        let job = Arc::new(job);
+        let parser = self.build_parser()?;
+        let deterministic = self.config.deterministic;
+        let har_path = self.config.har_path.clone();
+        let (sender, receiver) = tokio::sync::mpsc::channel(max_buffered.max(1));
+
+        let runner = Arc::clone(&job);
+        pyo3_async_runtimes::tokio::get_runtime().spawn(async move {
+            let ran = runner
+                .run(&parser, |parsed| {
+                    let sender = sender.clone();
+                    async move { sender.send(Ok(SitemapResult::from_parsed(parsed, deterministic))).await.is_ok() }
+                })
+                .await;
+            write_har(&parser, har_path.as_deref());
+            if let Err(e) = ran {
+                let error = PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to save job state: {}", e));
+                let _ = sender.send(Err(error)).await;
+            }
+        });
+
+        Ok(PyParseJob { job, results: SiteResultStream::new(receiver) })
+    }
+
+    /// The parser's HTTP client, built the first time a call needs it
+    fn client(&self) -> PyResult<reqwest::Client> {
+        let mut client = self.client.lock().unwrap();
+        // Checked under the lock, so a call can't build a new client after `close()` dropped it
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("RustParser is closed"));
+        }
+        if let Some(client) = &*client {
+            return Ok(client.clone());
+        }
+        let built = self.config.client_config.build(Duration::from_secs(self.config.timeout_seconds))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to create HTTP client: {}", e)))?;
+        Ok(client.insert(built).clone())
+    }
+
+    fn build_parser(&self) -> PyResult<RustSitemapParser> {
+        Ok(self.config.parser(self.client()?)
+            .with_robots_cache(Arc::clone(&self.robots_cache))
+            .with_contention(self.contention.clone())
+            .with_monitor(self.monitor.clone())
+            .with_shutdown(self.shutdown.clone()))
+    }
+
+    /// The stop a single call runs under: the parser's, linked to the call's cancel token if it has one
+    fn call_shutdown(&self, token: Option<&Arc<Shutdown>>) -> Arc<Shutdown> {
+        match token {
+            Some(token) => Arc::new(Shutdown::linked(vec![self.shutdown.clone(), Arc::clone(token)])),
+            None => self.shutdown.clone(),
+        }
+    }
+}
+
+#[pymethods]
+impl RustParser {
+    /// Options past the first five are keyword-only; see `ParserOptions` for the list
+    #[new]
+    #[pyo3(signature = (
+        max_concurrent = 10,
+        max_sitemaps = 10,
+        max_depth = 2,
+        max_nested_per_level = 5,
+        timeout_seconds = 30,
+        **options,
+    ))]
+    fn new(
+        max_concurrent: usize,
+        max_sitemaps: usize,
+        max_depth: usize,
+        max_nested_per_level: usize,
+        timeout_seconds: u64,
+        options: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Self> {
+        let options = ParserOptions::from_kwargs("RustParser", options)?;
+        Ok(Self {
+            config: ParserConfig::new(max_concurrent, max_sitemaps, max_depth, max_nested_per_level, timeout_seconds, options)?,
+            robots_cache: Arc::new(RobotsCache::default()),
+            contention: Arc::new(ContentionTracker::new()),
+            monitor: Arc::new(RunMonitor::new()),
+            shutdown: register_shutdown(),
+            client: Mutex::new(None),
+            closed: AtomicBool::new(false),
+        })
+    }
+
+    /// Parse a single site's sitemaps.
+    ///
+    /// `progress`, if given, is called with a `ProgressEvent` as the site starts and
+    /// finishes, as each sitemap is fetched, and when robots.txt or a sitemap fails.
+    /// `cancel`, a `CancelToken`, stops the parse early when cancelled.
+    #[pyo3(signature = (base_url, progress = None, cancel = None))]
+    fn parse_site<'py>(&self, py: Python<'py>, base_url: String, progress: Option<PyObject>, cancel: Option<PyRef<'_, PyCancelToken>>) -> PyResult<Bound<'py, PyAny>> {
+        let token = cancel.map(|token| Arc::clone(&token.shutdown));
+        let parser = self.build_parser()?
+            .with_progress(progress_callback(progress)?)
+            .with_shutdown(self.call_shutdown(token.as_ref()));
+        let deterministic = self.config.deterministic;
+        let har_path = self.config.har_path.clone();
+
+        future_into_py(py, async move {
+            let start_time = Instant::now();
+            let parsed = parser.parse_site(&base_url).await.unwrap_or_else(|e| ParsedSiteResult::failed(base_url, e.as_ref()));
+            let mut result = SitemapResult::from_parsed(parsed, deterministic);
+            write_har(&parser, har_path.as_deref());
+
+            result.cancelled = result.interrupted && token.is_some_and(|token| token.is_requested());
+            result.parse_time = start_time.elapsed().as_secs_f64();
+            Ok(result)
+        })
+    }
+
+    /// Parse a single site, yielding its URLs in batches as each sitemap is parsed
+    /// instead of collecting them all first.
+    ///
+    /// At most `max_buffered` unread batches are held before parsing pauses, so
+    /// memory stays bounded however many URLs the site lists. A URL listed by several
+    /// sitemaps is yielded once per sitemap. Returns a `UrlBatchStream`.
+    #[pyo3(signature = (base_url, batch_size = DEFAULT_STREAM_BATCH_SIZE, max_buffered = 10))]
+    fn stream_site(&self, base_url: String, batch_size: usize, max_buffered: usize) -> PyResult<UrlBatchStream> {
+        let parser = self.build_parser()?;
+        let deterministic = self.config.deterministic;
+        let har_path = self.config.har_path.clone();
+        let (sink, receiver) = UrlSink::channel(batch_size, max_buffered);
+        let finished = Arc::new(Mutex::new(None));
+        let finished_here = Arc::clone(&finished);
+
+        pyo3_async_runtimes::tokio::get_runtime().spawn(async move {
+            let start_time = Instant::now();
+            // Keeps the stream open until the result is in place
+            let last_sender = sink.clone();
+            let parsed = tokio::select! {
+                parsed = parser.parse_site_streaming(&base_url, sink) => parsed,
+                // The stream was dropped; stop fetching
+                _ = last_sender.closed() => return,
+            };
+            write_har(&parser, har_path.as_deref());
+            let parsed = parsed.unwrap_or_else(|e| ParsedSiteResult::failed(base_url.clone(), e.as_ref()));
+            let mut result = SitemapResult::from_parsed(parsed, deterministic);
+            result.parse_time = start_time.elapsed().as_secs_f64();
+            info!("🦀 Finished streaming {}: {} URLs", base_url, result.streamed_urls);
+            *finished_here.lock().unwrap() = Some(result);
+            drop(last_sender);
+        });
+
+        Ok(UrlBatchStream { batches: ChannelReader::new(receiver), finished })
+    }
+
+    /// Parse specific sitemap URLs directly (bypassing robots.txt discovery)
+    fn parse_sitemaps<'py>(&self, py: Python<'py>, sitemap_urls: Vec<String>) -> PyResult<Bound<'py, PyAny>> {
+        let parser = self.build_parser()?;
+        let deterministic = self.config.deterministic;
+        let har_path = self.config.har_path.clone();
+
+        future_into_py(py, async move {
+            let parsed = parser.parse_specific_sitemaps(sitemap_urls).await;
+            write_har(&parser, har_path.as_deref());
+            match parsed {
+                Ok(urls) => {
+                    let mut url_vec: Vec<String> = urls.into_iter().collect();
+                    if deterministic {
+                        url_vec.sort_unstable();
+                    }
+                    info!("🦀 Finished parsing specific sitemaps, found {} URLs", url_vec.len());
+                    Ok(url_vec)
+                }
+                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                    format!("Failed to parse sitemaps: {}", e)
+                ))
+            }
+        })
+    }
+
+    /// Parse multiple sites concurrently.
+    ///
+    /// `progress` is called as in `parse_site`, with every site's events interleaved;
+    /// count "site_finished" events to track how many sites are done. `on_result` is
+    /// called with each site's `SitemapResult` as soon as that site is done, while the
+    /// others carry on; the returned list still holds them all, in input order.
+    /// Cancelling `cancel` returns the finished sites plus the rest marked `cancelled`.
+    #[pyo3(signature = (base_urls, progress = None, cancel = None, on_result = None))]
+    fn parse_multiple_sites<'py>(&self, py: Python<'py>, base_urls: Vec<String>, progress: Option<PyObject>, cancel: Option<PyRef<'_, PyCancelToken>>, on_result: Option<PyObject>) -> PyResult<Bound<'py, PyAny>> {
+        let token = cancel.map(|token| Arc::clone(&token.shutdown));
+        let parser = self.build_parser()?
+            .with_progress(progress_callback(progress)?)
+            .with_shutdown(self.call_shutdown(token.as_ref()));
+        let on_result = result_callback(on_result)?;
+        let deterministic = self.config.deterministic;
+        let har_path = self.config.har_path.clone();
+
+        future_into_py(py, async move {
+            let parsed = parser
+                .parse_multiple_sites_with(base_urls, |r| deliver_result(r, deterministic, token.as_deref(), on_result.as_ref()))
+                .await;
+            write_har(&parser, har_path.as_deref());
+            match parsed {
+                Ok(results) => results.into_iter().collect::<PyResult<Vec<_>>>(),
+                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                    format!("Failed to parse sites: {}", e)
+                ))
+            }
+        })
+    }
+
+    /// Parse a single site and write its URLs to `path` instead of returning them.
+    ///
+    /// `format` is "jsonl", "csv", or "parquet" (only in builds with the `parquet`
+    /// feature). Rows hold the site, URL, and any metadata kept; returns an `ExportSummary`.
+    #[pyo3(signature = (base_url, path, format = "jsonl"))]
+    fn parse_site_to_file<'py>(&self, py: Python<'py>, base_url: String, path: PathBuf, format: &str) -> PyResult<Bound<'py, PyAny>> {
+        self.parse_multiple_sites_to_file(py, vec![base_url], path, format)
+    }
+
+    /// Parse multiple sites concurrently, writing each one's URLs to `path` as it
+    /// finishes, so the batch never holds more than the sites in flight.
+    #[pyo3(signature = (base_urls, path, format = "jsonl"))]
+    fn parse_multiple_sites_to_file<'py>(&self, py: Python<'py>, base_urls: Vec<String>, path: PathBuf, format: &str) -> PyResult<Bound<'py, PyAny>> {
+        let format = parse_export_format(format)?;
+        let parser = self.build_parser()?;
+        let har_path = self.config.har_path.clone();
+
+        future_into_py(py, async move {
+            let start_time = Instant::now();
+            let exported = parser.parse_sites_to_file(base_urls, &path, format).await;
+            write_har(&parser, har_path.as_deref());
+            match exported {
+                Ok(summary) => Ok(PyExportSummary::new(summary, path, format, start_time.elapsed().as_secs_f64())),
+                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyOSError, _>(
+                    format!("Failed to export to {}: {}", path.display(), e)
+                ))
+            }
+        })
+    }
+
+    /// Parse sites from an iterator or async iterator of base URLs, yielding results as they finish.
+    ///
+    /// Base URLs are pulled only as sites complete, and at most `max_buffered` unread
+    /// results are held before parsing pauses, so huge batches run in bounded memory.
+    /// Returns a `SiteResultStream`, usable with both `for` and `async for`.
+    #[pyo3(signature = (base_urls, max_buffered = 100))]
+    fn parse_sites_stream(&self, py: Python<'_>, base_urls: Bound<'_, PyAny>, max_buffered: usize) -> PyResult<SiteResultStream> {
+        let input = BaseUrlSource::new(py, &base_urls)?;
+        let parser = self.build_parser()?;
+        let deterministic = self.config.deterministic;
+        let har_path = self.config.har_path.clone();
+        let (sender, receiver) = tokio::sync::mpsc::channel(max_buffered.max(1));
+
+        pyo3_async_runtimes::tokio::get_runtime().spawn(async move {
+            let input_error = Arc::new(std::sync::Mutex::new(None));
+            let mut results = parser.parse_site_stream(input.into_stream(Arc::clone(&input_error)));
+            while let Some(parsed) = results.next().await {
+                // A closed channel means the stream was dropped; stop starting new sites
+                if sender.send(Ok(SitemapResult::from_parsed(parsed, deterministic))).await.is_err() {
+                    break;
+                }
+            }
+            drop(results);
+            write_har(&parser, har_path.as_deref());
+            let input_error = input_error.lock().unwrap().take();
+            if let Some(e) = input_error {
+                let _ = sender.send(Err(e)).await;
+            }
+        });
+
+        Ok(SiteResultStream::new(receiver))
+    }
+
+    /// Start a batch job that can be paused, resumed, and cancelled while it runs.
+    ///
+    /// With `state_path`, the job's queue is saved there as it goes, so
+    /// `resume_job()` can finish it after a restart.
+    #[pyo3(signature = (base_urls, state_path = None, max_buffered = 100))]
+    fn start_job(&self, base_urls: Vec<String>, state_path: Option<PathBuf>, max_buffered: usize) -> PyResult<PyParseJob> {
+        let job = crate::job::ParseJob::new(base_urls, state_path);
+        job.save()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to save job state: {}", e)))?;
+        self.spawn_job(job, max_buffered)
+    }
+
+    /// Continue the job saved at `state_path`, starting with the sites it hadn't finished
+    #[pyo3(signature = (state_path, max_buffered = 100))]
+    fn resume_job(&self, state_path: PathBuf, max_buffered: usize) -> PyResult<PyParseJob> {
+        let job = crate::job::ParseJob::load(state_path.clone()).map_err(|e| {
+            PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to load job state {}: {}", state_path.display(), e))
+        })?;
+        let progress = job.progress();
+        info!("🦀 Resuming job from {}: {} of {} sites left", state_path.display(), progress.pending, progress.total);
+        self.spawn_job(job, max_buffered)
+    }
+
+    /// Work for the coordinator at `coordinator` ("host:port") until it runs out of shards.
+    ///
+    /// Shards are parsed with this parser's settings; returns how many sites this worker parsed.
+    fn run_worker<'py>(&self, py: Python<'py>, coordinator: String) -> PyResult<Bound<'py, PyAny>> {
+        let parser = self.build_parser()?;
+        let har_path = self.config.har_path.clone();
+
+        future_into_py(py, async move {
+            let worked = crate::distributed::run_worker(&parser, &coordinator).await;
+            write_har(&parser, har_path.as_deref());
+            worked.map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Worker for {} failed: {}", coordinator, e))
+            })
+        })
+    }
+
+    /// Submit added or changed URLs to IndexNow, batched per host, and report each endpoint's answer.
+    ///
+    /// `key` must be served at `key_location`, or at `https://<host>/<key>.txt` by default.
+    /// `endpoints` defaults to the shared api.indexnow.org endpoint.
+    #[pyo3(signature = (urls, key, key_location = None, endpoints = None))]
+    fn submit_indexnow<'py>(
+        &self,
+        py: Python<'py>,
+        urls: Vec<String>,
+        key: String,
+        key_location: Option<String>,
+        endpoints: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let parser = self.build_parser()?;
+        let endpoints = endpoints.unwrap_or_else(|| vec![crate::indexnow::DEFAULT_ENDPOINT.to_string()]);
+
+        future_into_py(py, async move {
+            let submissions = parser.submit_indexnow(&endpoints, &key, key_location.as_deref(), &urls).await;
+            Ok(submissions.into_iter().map(PyIndexNowSubmission::from).collect::<Vec<_>>())
+        })
+    }
+
+    /// Compare URL counts of the sitemaps submitted for a Search Console property with
+    /// what this parser finds in them.
+    ///
+    /// Authenticate with either an OAuth `access_token` or a `service_account_file` key
+    /// whose account has access to the property. Only available in builds with the
+    /// `search-console` feature.
+    #[cfg(feature = "search-console")]
+    #[pyo3(signature = (property, access_token = None, service_account_file = None))]
+    fn compare_search_console<'py>(
+        &self,
+        py: Python<'py>,
+        property: String,
+        access_token: Option<String>,
+        service_account_file: Option<PathBuf>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let source = match (access_token, service_account_file) {
+            (Some(token), None) => crate::search_console::SearchConsoleSource::with_access_token(token),
+            (None, Some(path)) => crate::search_console::SearchConsoleSource::from_service_account_file(&path)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid service account key: {}", e)))?,
+            _ => return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("Pass exactly one of access_token and service_account_file")),
+        };
+        let parser = self.build_parser()?;
+        let har_path = self.config.har_path.clone();
+
+        future_into_py(py, async move {
+            let compared = parser.compare_search_console(&source, &property).await;
+            write_har(&parser, har_path.as_deref());
+            match compared {
+                Ok(comparisons) => Ok(comparisons.into_iter().map(PySitemapCountComparison::from).collect::<Vec<_>>()),
+                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                    format!("Failed to compare Search Console sitemaps for {}: {}", property, e)
+                ))
+            }
+        })
+    }
+
+    /// Stop this parser's runs gracefully: no new sites or fetches start, fetches in flight
+    /// get `grace_seconds` to finish, and runs return what they have, with the sites
+    /// involved marked `interrupted`. Jobs save those sites as pending. The parser stays
+    /// stopped; create a new one to parse again.
+    #[pyo3(signature = (grace_seconds = DEFAULT_GRACE.as_secs_f64()))]
+    fn interrupt(&self, grace_seconds: f64) -> PyResult<()> {
+        let grace = Duration::try_from_secs_f64(grace_seconds)
+            .map_err(|_| PyErr::new::<pyo3::exceptions::PyValueError, _>("grace_seconds must be a non-negative number"))?;
+        info!("🦀 Interrupting parser; in-flight fetches get {:?} to finish", grace);
+        self.shutdown.request(grace);
+        Ok(())
+    }
+
+    #[getter]
+    fn interrupted(&self) -> bool {
+        self.shutdown.is_requested()
+    }
+
+    /// Shut the parser down for good: runs still going are interrupted as by `interrupt()`
+    /// and waited for up to `grace_seconds` (plus a second), then the HTTP client and its
+    /// pooled connections are dropped. Later calls raise `RuntimeError`; closing twice is
+    /// fine. Use the parser as a context manager to close it on the way out.
+    #[pyo3(signature = (grace_seconds = DEFAULT_GRACE.as_secs_f64()))]
+    fn close(&self, py: Python<'_>, grace_seconds: f64) -> PyResult<()> {
+        let grace = Duration::try_from_secs_f64(grace_seconds)
+            .map_err(|_| PyErr::new::<pyo3::exceptions::PyValueError, _>("grace_seconds must be a non-negative number"))?;
+        {
+            let mut client = self.client.lock().unwrap();
+            if self.closed.swap(true, Ordering::SeqCst) {
+                return Ok(());
+            }
+            // Runs still going hold their own handle on the pool until they finish
+            client.take();
+        }
+        info!("🦀 Closing parser; runs in flight get {:?} to finish", grace);
+        self.shutdown.request(grace);
+        let shutdown = Arc::clone(&self.shutdown);
+        py.allow_threads(|| {
+            if !shutdown.wait_idle(grace + Duration::from_secs(1)) {
+                warn!("🦀 Closed parser with sites still being parsed");
+            }
+        });
+        Ok(())
+    }
+
+    #[getter]
+    fn closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __exit__(&self, py: Python<'_>, _exc_type: PyObject, _exc_value: PyObject, _traceback: PyObject) -> PyResult<bool> {
+        self.close(py, DEFAULT_GRACE.as_secs_f64())?;
+        Ok(false)
+    }
+
+    /// Queue depths, wait times, and per-host in-flight counts across this parser's runs so far.
+    ///
+    /// Can be called while a run is going, to watch it live.
+    fn contention_stats(&self) -> PyContentionStats {
+        self.contention.snapshot().into()
+    }
+
+    /// Sites in flight with how long they've taken, recently finished sites, and totals
+    /// across this parser's runs so far; can be polled while a run is going
+    fn run_status(&self) -> PyRunStatus {
+        self.monitor.snapshot().into()
+    }
+
+    /// Draw a live dashboard of this parser's sites on the terminal: progress, throughput,
+    /// error counts, the slowest sites in flight, and recently finished ones.
+    ///
+    /// `total` is how many sites the run should finish, for the progress bar. Log lines
+    /// printed meanwhile tear through the dashboard, so silence logging while it's up.
+    /// Needs the `tui` feature.
+    #[cfg(feature = "tui")]
+    #[pyo3(signature = (total = None))]
+    fn dashboard(&self, total: Option<usize>) -> PyResult<PyDashboard> {
+        let dashboard = crate::tui::Dashboard::start(self.monitor.clone(), self.contention.clone(), total)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to start dashboard: {}", e)))?;
+        Ok(PyDashboard { dashboard })
+    }
+
+    /// Requests and failures per proxy so far, and which are ejected; empty without `proxies`
+    fn proxy_stats(&self) -> Vec<PyProxyStats> {
+        self.config.proxies.as_ref().map(|pool| pool.stats().into_iter().map(PyProxyStats::from).collect()).unwrap_or_default()
+    }
+
+    /// The site's robots.txt as a `RobotsPolicy`, fetched once per origin and reused for
+    /// a day, including robots.txt files this parser read while parsing sites. Raises
+    /// `RobotsFetchError` when it can't be read; a 4xx answer allows everything.
+    fn fetch_robots<'py>(&self, py: Python<'py>, base_url: String) -> PyResult<Bound<'py, PyAny>> {
+        let parser = self.build_parser()?;
+
+        future_into_py(py, async move {
+            match parser.fetch_robots(&base_url).await {
+                Ok(robots) => {
+                    let site_url = parser.normalize_url(&base_url).map_or_else(|_| base_url.clone(), |url| url.trim_end_matches('/').to_string());
+                    Ok(PyRobotsPolicy { base_url, site_url, robots })
+                }
+                Err(e) => Python::with_gil(|py| Err(crate::exceptions::to_py_err(py, &e)?)),
+            }
+        })
+    }
+
+    /// Dry run: report what parsing a site would fetch, without downloading any urlsets
+    fn plan_site<'py>(&self, py: Python<'py>, base_url: String) -> PyResult<Bound<'py, PyAny>> {
+        let parser = self.build_parser()?;
+        let har_path = self.config.har_path.clone();
+
+        future_into_py(py, async move {
+            let planned = parser.plan_site(&base_url).await;
+            write_har(&parser, har_path.as_deref());
+            match planned {
+                Ok(plan) => Ok(PySitePlan::from(plan)),
+                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                    format!("Failed to plan {}: {}", base_url, e)
+                ))
+            }
+        })
+    }
+
+    /// Dry-run multiple sites concurrently
+    fn plan_multiple_sites<'py>(&self, py: Python<'py>, base_urls: Vec<String>) -> PyResult<Bound<'py, PyAny>> {
+        let parser = self.build_parser()?;
+        let har_path = self.config.har_path.clone();
+
+        future_into_py(py, async move {
+            let plans = parser.plan_multiple_sites(base_urls).await;
+            write_har(&parser, har_path.as_deref());
+            Ok(plans.into_iter().map(PySitePlan::from).collect::<Vec<_>>())
+        })
+    }
+
+    /// Estimate a site's URL count and download size from its indexes and a few sampled urlsets
+    #[pyo3(signature = (base_url, samples = 2))]
+    fn estimate_site<'py>(&self, py: Python<'py>, base_url: String, samples: usize) -> PyResult<Bound<'py, PyAny>> {
+        let parser = self.build_parser()?;
+        let har_path = self.config.har_path.clone();
+
+        future_into_py(py, async move {
+            let estimated = parser.estimate_site(&base_url, samples).await;
+            write_har(&parser, har_path.as_deref());
+            match estimated {
+                Ok(estimate) => Ok(PySiteEstimate::from(estimate)),
+                Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                    format!("Failed to estimate {}: {}", base_url, e)
+                ))
+            }
+        })
+    }
+
+    /// Estimate multiple sites concurrently, e.g. to triage a batch before parsing it
+    #[pyo3(signature = (base_urls, samples = 2))]
+    fn estimate_multiple_sites<'py>(&self, py: Python<'py>, base_urls: Vec<String>, samples: usize) -> PyResult<Bound<'py, PyAny>> {
+        let parser = self.build_parser()?;
+        let har_path = self.config.har_path.clone();
+
+        future_into_py(py, async move {
+            let estimates = parser.estimate_multiple_sites(base_urls, samples).await;
+            write_har(&parser, har_path.as_deref());
+            Ok(estimates.into_iter().map(PySiteEstimate::from).collect::<Vec<_>>())
+        })
+    }
+
+    /// Parse a sitemap already in memory, raw or gzipped, without the network.
+    /// `fetch(url) -> bytes | None` supplies nested sitemaps; without it they're only
+    /// listed in `sitemaps_found`
+    #[pyo3(signature = (content, base_url, fetch = None))]
+    fn parse_sitemap_bytes(&self, py: Python<'_>, content: Vec<u8>, base_url: String, fetch: Option<PyObject>) -> PyResult<SitemapResult> {
+        let parser = self.build_parser()?;
+        let fetch = sitemap_fetch(fetch)?;
+        let parsed = py.allow_threads(|| parser.parse_sitemap_bytes(&content, &base_url, fetch.as_ref().map(|f| f as &dyn SitemapFetch)));
+        match parsed {
+            Ok(parsed) => Ok(SitemapResult::from_parsed(parsed, self.config.deterministic)),
+            Err(e) => Err(crate::exceptions::to_py_err(py, &e)?),
+        }
+    }
+
+    /// Parse the sitemap file at `path` like `parse_sitemap_bytes`; `base_url`
+    /// defaults to the file's `file://` URL
+    #[pyo3(signature = (path, base_url = None, fetch = None))]
+    fn parse_sitemap_file(&self, py: Python<'_>, path: PathBuf, base_url: Option<String>, fetch: Option<PyObject>) -> PyResult<SitemapResult> {
+        let unreadable = |e: std::io::Error| PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to read {}: {}", path.display(), e));
+        let content = std::fs::read(&path).map_err(unreadable)?;
+        let base_url = match base_url {
+            Some(base_url) => base_url,
+            None => file_url(&path).map_err(unreadable)?,
+        };
+        self.parse_sitemap_bytes(py, content, base_url, fetch)
+    }
+}
+
+/// Where `parse_sites_stream` pulls base URLs from
+enum BaseUrlSource {
+    Iter(Arc<Py<PyIterator>>),
+    /// An async iterator, driven on the event loop that was running when the stream was created
+    AsyncIter(Py<PyAny>, TaskLocals),
+}
+
+impl BaseUrlSource {
+    fn new(py: Python<'_>, base_urls: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if base_urls.hasattr("__aiter__")? {
+            let locals = pyo3_async_runtimes::tokio::get_current_locals(py)?;
+            Ok(Self::AsyncIter(base_urls.call_method0("__aiter__")?.unbind(), locals))
+        } else {
+            Ok(Self::Iter(Arc::new(PyIterator::from_object(base_urls)?.unbind())))
+        }
+    }
+
+    /// Base URLs until the source is exhausted; a failure ends the stream and is left in `error`
+    fn into_stream(self, error: Arc<std::sync::Mutex<Option<PyErr>>>) -> impl futures::Stream<Item = String> + Send {
+        futures::stream::unfold(self, move |source| {
+            let error = Arc::clone(&error);
+            async move {
+                match source.next().await? {
+                    Ok(base_url) => Some((base_url, source)),
+                    Err(e) => {
+                        *error.lock().unwrap() = Some(e);
+                        None
+                    }
+                }
+            }
+        })
+    }
+
+    async fn next(&self) -> Option<PyResult<String>> {
+        match self {
+            Self::Iter(iter) => {
+                // Python iterators may block (file reads, database cursors), so keep them off the runtime's workers
+                let iter = Arc::clone(iter);
+                tokio::task::spawn_blocking(move || {
+                    Python::with_gil(|py| iter.bind(py).clone().next().map(|item| item.and_then(|item| item.extract::<String>())))
+                })
+                .await
+                .unwrap_or_else(|e| Some(Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Base URL iterator failed: {}", e)))))
+            }
+            Self::AsyncIter(aiter, locals) => {
+                let awaitable = Python::with_gil(|py| {
+                    let awaitable = aiter.bind(py).call_method0("__anext__")?;
+                    pyo3_async_runtimes::into_future_with_locals(locals, awaitable)
+                });
+                let next = match awaitable {
+                    Ok(awaitable) => awaitable.await,
+                    Err(e) => Err(e),
+                };
+                Python::with_gil(|py| match next {
+                    Ok(item) => Some(item.extract::<String>(py)),
+                    Err(e) if e.is_instance_of::<PyStopAsyncIteration>(py) => None,
+                    Err(e) => Some(Err(e)),
+                })
+            }
+        }
+    }
+}
+
+/// The reading end of a channel behind a Python iterator, usable from both `for` and `async for`
+struct ChannelReader<T> {
+    receiver: Arc<tokio::sync::Mutex<tokio::sync::mpsc::Receiver<T>>>,
+}
+
+impl<T: Send + 'static> ChannelReader<T> {
+    fn new(receiver: tokio::sync::mpsc::Receiver<T>) -> Self {
+        Self { receiver: Arc::new(tokio::sync::Mutex::new(receiver)) }
+    }
+
+    /// Wait for the next item without holding the GIL; `None` once the channel closes.
+    /// Ctrl-C raises `KeyboardInterrupt` while waiting, rather than once an item arrives.
+    fn next_blocking(&self, py: Python<'_>) -> PyResult<Option<T>> {
+        loop {
+            let receiver = Arc::clone(&self.receiver);
+            let received = py.allow_threads(move || {
+                pyo3_async_runtimes::tokio::get_runtime()
+                    .block_on(tokio::time::timeout(SIGNAL_CHECK_INTERVAL, async { receiver.lock().await.recv().await }))
+            });
+            match received {
+                Ok(item) => return Ok(item),
+                Err(_) => py.check_signals()?,
+            }
+        }
+    }
+
+    /// An awaitable for the next item, passed through `convert`, that raises
+    /// `StopAsyncIteration` once the channel closes
+    fn next_awaitable<'py, U>(&self, py: Python<'py>, convert: fn(T) -> PyResult<U>) -> PyResult<Bound<'py, PyAny>>
+    where
+        U: for<'a> IntoPyObject<'a> + Send + 'static,
+    {
+        let receiver = Arc::clone(&self.receiver);
+        future_into_py(py, async move {
+            match receiver.lock().await.recv().await {
+                Some(item) => convert(item),
+                None => Err(PyStopAsyncIteration::new_err(())),
+            }
+        })
+    }
+}
+
+/// Results from `RustParser.parse_sites_stream`, in completion order (input order when deterministic).
+///
+/// Iterate with `for` from plain threads or `async for` inside an event loop. An
+/// exception raised by the base URL iterator is re-raised once the sites started
+/// before it have been yielded.
+#[pyclass]
+pub struct SiteResultStream {
+    results: ChannelReader<PyResult<SitemapResult>>,
+}
+
+impl SiteResultStream {
+    fn new(receiver: tokio::sync::mpsc::Receiver<PyResult<SitemapResult>>) -> Self {
+        Self { results: ChannelReader::new(receiver) }
+    }
+
+    fn next_blocking(&self, py: Python<'_>) -> PyResult<Option<SitemapResult>> {
+        self.results.next_blocking(py)?.transpose()
+    }
+
+    fn next_awaitable<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        self.results.next_awaitable(py, |result| result)
+    }
+}
+
+#[pymethods]
+impl SiteResultStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<SitemapResult>> {
+        self.next_blocking(py)
+    }
+
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        self.next_awaitable(py)
+    }
+}
+
+/// URL batches from `RustParser.stream_site`, each a list of up to `batch_size` URLs.
+///
+/// Iterate with `for` or `async for`; once the stream ends, `result` holds the
+/// site's result with everything but its URLs.
+#[pyclass]
+pub struct UrlBatchStream {
+    batches: ChannelReader<Vec<String>>,
+    finished: Arc<Mutex<Option<SitemapResult>>>,
+}
+
+#[pymethods]
+impl UrlBatchStream {
+    /// The site's sitemaps, errors, and counts, with `urls` empty and `streamed_urls`
+    /// set; `None` until the stream ends
+    #[getter]
+    fn result(&self) -> Option<SitemapResult> {
+        self.finished.lock().unwrap().clone()
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<Vec<String>>> {
+        self.batches.next_blocking(py)
+    }
+
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        self.batches.next_awaitable(py, Ok)
+    }
+}
+
+/// Iterator over a blob from `SitemapResult.urls_compressed()`, decompressing as it goes
+#[pyclass(name = "PackedUrls")]
+pub struct PyPackedUrls {
+    urls: crate::transfer::UnpackedUrls<std::io::Cursor<Vec<u8>>>,
+}
+
+#[pymethods]
+impl PyPackedUrls {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> PyResult<Option<String>> {
+        self.urls
+            .next()
+            .transpose()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid compressed URL list: {}", e)))
+    }
+}
+
+/// Where a `ParseJob` stands
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct JobProgress {
+    /// "running", "paused", "cancelled", or "finished"
+    #[pyo3(get)]
+    pub status: String,
+    #[pyo3(get)]
+    pub total: usize,
+    #[pyo3(get)]
+    pub completed: usize,
+    /// Completed sites that produced errors and no URLs
+    #[pyo3(get)]
+    pub failed: usize,
+    #[pyo3(get)]
+    pub in_flight: usize,
+    #[pyo3(get)]
+    pub pending: usize,
+}
+
+#[pymethods]
+impl JobProgress {
+    fn __repr__(&self) -> String {
+        format!(
+            "JobProgress(status='{}', completed={}/{}, failed={}, in_flight={}, pending={})",
+            self.status, self.completed, self.total, self.failed, self.in_flight, self.pending
+        )
+    }
+}
+
+impl From<crate::job::JobProgress> for JobProgress {
+    fn from(p: crate::job::JobProgress) -> Self {
+        Self {
+            status: p.status.to_string(),
+            total: p.total,
+            completed: p.completed,
+            failed: p.failed,
+            in_flight: p.in_flight,
+            pending: p.pending,
+        }
+    }
+}
+
+/// A batch run that can be paused, resumed, and cancelled; iterate it (`for` or `async for`) for results.
+///
+/// Results are buffered up to `max_buffered`, after which the job waits for them
+/// to be read. With a state path, `RustParser.resume_job()` continues a cancelled
+/// or interrupted job from the sites it hadn't finished.
+#[pyclass(name = "ParseJob")]
+pub struct PyParseJob {
+    job: Arc<crate::job::ParseJob>,
+    results: SiteResultStream,
+}
+
+#[pymethods]
+impl PyParseJob {
+    /// Stop starting new sites and save the queue; in-flight sites still finish
+    fn pause(&self) -> PyResult<()> {
+        self.job
+            .pause()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to save job state: {}", e)))
+    }
+
+    fn resume(&self) {
+        self.job.resume();
+    }
+
+    /// Stop now; in-flight sites go back into the saved queue
+    fn cancel(&self) {
+        self.job.cancel();
+    }
+
+    #[getter]
+    fn status(&self) -> String {
+        self.job.status().to_string()
+    }
+
+    #[getter]
+    fn progress(&self) -> JobProgress {
+        self.job.progress().into()
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<SitemapResult>> {
+        self.results.next_blocking(py)
+    }
+
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        self.results.next_awaitable(py)
+    }
+
+    fn __repr__(&self) -> String {
+        let progress = self.job.progress();
+        format!("ParseJob(status='{}', completed={}/{})", progress.status, progress.completed, progress.total)
+    }
+}
+
+/// A sitemap a real run would fetch
+#[pyclass(name = "PlannedSitemap")]
+#[derive(Clone, Debug)]
+pub struct PyPlannedSitemap {
+    #[pyo3(get)]
+    pub url: String,
+    /// "index", "urlset", "unknown", or "failed"
+    #[pyo3(get)]
+    pub kind: String,
+    #[pyo3(get)]
+    pub level: usize,
+    #[pyo3(get)]
+    pub content_length: Option<u64>,
+}
+
+#[pymethods]
+impl PyPlannedSitemap {
+    fn __repr__(&self) -> String {
+        format!(
+            "PlannedSitemap(url='{}', kind='{}', level={}, content_length={:?})",
+            self.url, self.kind, self.level, self.content_length
+        )
+    }
+}
+
+/// A site's robots.txt, from `RustParser.fetch_robots()`, answering for any user agent
+#[pyclass(name = "RobotsPolicy")]
+#[derive(Clone, Debug)]
+pub struct PyRobotsPolicy {
+    /// The site it was fetched for
+    #[pyo3(get)]
+    pub base_url: String,
+    /// `base_url` with its scheme filled in
+    site_url: String,
+    robots: Arc<RobotsTxt>,
+}
+
+impl PyRobotsPolicy {
+    /// `url` made absolute against the site, so paths work too
+    fn absolute(&self, url: &str) -> String {
+        Url::parse(&self.site_url).and_then(|site| site.join(url)).map_or_else(|_| url.to_string(), String::from)
+    }
+}
+
+#[pymethods]
+impl PyRobotsPolicy {
+    /// Whether `user_agent` may fetch `url` (absolute, or a path on the site). Groups
+    /// are matched on the agent's product token, and URLs on other hosts are allowed,
+    /// since their own robots.txt decides.
+    fn can_fetch(&self, user_agent: &str, url: &str) -> bool {
+        self.robots.policy_for(user_agent, &self.site_url).is_allowed(&self.absolute(url))
+    }
+
+    /// Seconds `user_agent` should wait between requests, capped at 60; `None` if unset
+    fn crawl_delay(&self, user_agent: &str) -> Option<f64> {
+        self.robots.policy_for(user_agent, &self.site_url).crawl_delay.map(|delay| delay.as_secs_f64())
+    }
+
+    /// URLs of its `Sitemap:` lines
+    fn sitemaps(&self) -> Vec<String> {
+        self.robots.sitemaps.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("RobotsPolicy(base_url='{}', groups={}, sitemaps={})", self.base_url, self.robots.groups.len(), self.robots.sitemaps.len())
+    }
+}
+
+/// What parsing a site would fetch, from a dry run
+#[pyclass(name = "SitePlan")]
+#[derive(Clone, Debug)]
+pub struct PySitePlan {
+    #[pyo3(get)]
+    pub base_url: String,
+    #[pyo3(get)]
+    pub sitemaps: Vec<PyPlannedSitemap>,
+    #[pyo3(get)]
+    pub estimated_requests: usize,
+    #[pyo3(get)]
+    pub estimated_bytes: u64,
+    /// Planned sitemaps without a Content-Length, not counted in `estimated_bytes`
+    #[pyo3(get)]
+    pub unknown_sizes: usize,
+    #[pyo3(get)]
+    pub requests_made: usize,
+    #[pyo3(get)]
+    pub errors: Vec<String>,
+    #[pyo3(get)]
+    pub plan_time: f64,
+}
+
+#[pymethods]
+impl PySitePlan {
+    fn __repr__(&self) -> String {
+        format!(
+            "SitePlan(base_url='{}', sitemaps={}, estimated_requests={}, estimated_bytes={}, unknown_sizes={}, errors={})",
+            self.base_url,
+            self.sitemaps.len(),
+            self.estimated_requests,
+            self.estimated_bytes,
+            self.unknown_sizes,
+            self.errors.len()
+        )
+    }
+}
+
+impl From<crate::plan::SitePlan> for PySitePlan {
+    fn from(p: crate::plan::SitePlan) -> Self {
+        Self {
+            base_url: p.base_url,
+            sitemaps: p
+                .sitemaps
+                .into_iter()
+                .map(|s| PyPlannedSitemap {
+                    url: s.url,
+                    kind: s.kind.to_string(),
+                    level: s.level,
+                    content_length: s.content_length,
+                })
+                .collect(),
+            estimated_requests: p.estimated_requests,
+            estimated_bytes: p.estimated_bytes,
+            unknown_sizes: p.unknown_sizes,
+            requests_made: p.requests_made,
+            errors: p.errors,
+            plan_time: p.plan_time,
+        }
+    }
+}
+
+/// A site's approximate size, from `estimate_site()`
+#[pyclass(name = "SiteEstimate")]
+#[derive(Clone, Debug)]
+pub struct PySiteEstimate {
+    #[pyo3(get)]
+    pub base_url: String,
+    #[pyo3(get)]
+    pub urlsets: usize,
+    #[pyo3(get)]
+    pub estimated_urls: u64,
+    #[pyo3(get)]
+    pub estimated_bytes: u64,
+    /// Urlsets downloaded in full to calibrate the estimate
+    #[pyo3(get)]
+    pub sampled_sitemaps: Vec<String>,
+    #[pyo3(get)]
+    pub sampled_urls: usize,
+    #[pyo3(get)]
+    pub requests_made: usize,
+    #[pyo3(get)]
+    pub errors: Vec<String>,
+    #[pyo3(get)]
+    pub estimate_time: f64,
+}
+
+#[pymethods]
+impl PySiteEstimate {
+    fn __repr__(&self) -> String {
+        format!(
+            "SiteEstimate(base_url='{}', estimated_urls={}, estimated_bytes={}, urlsets={}, sampled={}, errors={})",
+            self.base_url,
+            self.estimated_urls,
+            self.estimated_bytes,
+            self.urlsets,
+            self.sampled_sitemaps.len(),
+            self.errors.len()
+        )
+    }
+}
+
+impl From<crate::estimate::SiteEstimate> for PySiteEstimate {
+    fn from(e: crate::estimate::SiteEstimate) -> Self {
+        Self {
+            base_url: e.base_url,
+            urlsets: e.urlsets,
+            estimated_urls: e.estimated_urls,
+            estimated_bytes: e.estimated_bytes,
+            sampled_urls: e.samples.iter().map(|s| s.urls).sum(),
+            sampled_sitemaps: e.samples.into_iter().map(|s| s.url).collect(),
+            requests_made: e.requests_made,
+            errors: e.errors,
+            estimate_time: e.estimate_time,
+        }
+    }
+}
+
+/// Write the run's HAR file if one was requested; a failed write is logged rather than failing the parse
+fn write_har(parser: &RustSitemapParser, har_path: Option<&Path>) {
+    let Some(path) = har_path else { return };
+    match parser.write_har(path) {
+        Ok(()) => info!("🦀 Wrote HAR to {}", path.display()),
+        Err(e) => error!("🦀 Failed to write HAR to {}: {}", path.display(), e),
+    }
+}
+
+/// Read the given WARC files up front so a bad path fails at construction rather than mid-run
+fn load_warc_input(paths: Option<&[PathBuf]>) -> PyResult<Option<Arc<WarcArchive>>> {
+    let Some(paths) = paths else { return Ok(None) };
+    let archive = WarcArchive::load(paths).map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to read WARC input: {}", e))
+    })?;
+    info!("🦀 Loaded {} archived responses from {} WARC files", archive.len(), paths.len());
+    Ok(Some(Arc::new(archive)))
+}
+
+/// Set up lookups against a Common Crawl crawl; it replaces the network, so it can't be combined with WARC input
+fn common_crawl_source(crawl: Option<String>, timeout_seconds: u64, has_warc_input: bool) -> PyResult<Option<Arc<CommonCrawlSource>>> {
+    let Some(crawl) = crawl else { return Ok(None) };
+    if has_warc_input {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("common_crawl and warc_input are alternative sources; pass only one"));
+    }
+    let source = CommonCrawlSource::new(Some(crawl), tokio::time::Duration::from_secs(timeout_seconds)).map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to set up Common Crawl source: {}", e))
+    })?;
+    Ok(Some(Arc::new(source)))
+}
+
+fn open_body_store(dir: Option<&Path>) -> PyResult<Option<Arc<BodyStore>>> {
+    let Some(dir) = dir else { return Ok(None) };
+    let store = BodyStore::create(dir).map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to open body directory {}: {}", dir.display(), e))
+    })?;
+    Ok(Some(Arc::new(store)))
+}
+
+/// On disk in `dir` if given, else in memory if `enabled`
+fn open_http_cache(enabled: bool, dir: Option<&Path>) -> PyResult<Option<Arc<dyn HttpCache>>> {
+    match dir {
+        Some(dir) => {
+            let cache = DiskCache::open(dir).map_err(|e| {
+                PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to open HTTP cache directory {}: {}", dir.display(), e))
+            })?;
+            Ok(Some(Arc::new(cache)))
+        }
+        None if enabled => Ok(Some(Arc::new(MemoryCache::default()))),
+        None => Ok(None),
+    }
+}
+
+fn open_reputation_store(path: Option<&Path>) -> PyResult<Option<Arc<ReputationStore>>> {
+    let Some(path) = path else { return Ok(None) };
+    let store = ReputationStore::open(path).map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to open host reputation store {}: {}", path.display(), e))
+    })?;
+    Ok(Some(Arc::new(store)))
+}
+
+fn open_checkpoint(path: Option<&Path>) -> PyResult<Option<Arc<Checkpoint>>> {
+    let Some(path) = path else { return Ok(None) };
+    let checkpoint = Checkpoint::open(path).map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to open checkpoint {}: {}", path.display(), e))
+    })?;
+    Ok(Some(Arc::new(checkpoint)))
+}
+
+fn user_agent_pool(agents: Option<Vec<String>>) -> PyResult<Option<Arc<UserAgentPool>>> {
+    let Some(agents) = agents else { return Ok(None) };
+    let pool = UserAgentPool::new(agents)
+        .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyValueError, _>("user_agents needs at least one user agent"))?;
+    Ok(Some(Arc::new(pool)))
+}
+
+fn proxy_pool(proxies: Option<Vec<String>>, assignment: &str, timeout_seconds: u64, config: &ClientConfig) -> PyResult<Option<Arc<ProxyPool>>> {
+    let Some(proxies) = proxies else { return Ok(None) };
+    let assignment: ProxyAssignment = assignment.parse().map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    let timeout = tokio::time::Duration::from_secs(timeout_seconds);
+    let pool = ProxyPool::new(&proxies, assignment, || config.apply(crate::parser::client_builder(timeout)))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid proxy pool: {}", e)))?;
+    Ok(Some(Arc::new(pool)))
+}
+
+/// Declares `ParserOptions`, the keyword options `RustParser()` and
+/// `parse_sitemaps_rust()` share, from one list of names, types, and defaults
+macro_rules! parser_options {
+    ($($name:ident: $ty:ty = $default:expr,)*) => {
+        struct ParserOptions {
+            $($name: $ty,)*
+        }
+
+        impl ParserOptions {
+            /// The options given in `kwargs`, defaults for the rest; a keyword that isn't
+            /// one of them is a `TypeError`, as it would be for a Python function
+            fn from_kwargs(caller: &str, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+                let mut options = Self { $($name: $default,)* };
+                let Some(kwargs) = kwargs else { return Ok(options) };
+                for (key, value) in kwargs {
+                    let key: String = key.extract()?;
+                    match key.as_str() {
+                        $(stringify!($name) => {
+                            options.$name = value.extract().map_err(|e| {
+                                PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!("{}() argument '{}': {}", caller, key, e))
+                            })?;
+                        })*
+                        _ => return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!("{}() got an unexpected keyword argument '{}'", caller, key))),
+                    }
+                }
+                Ok(options)
+            }
+        }
+    };
+}
+
+parser_options! {
+    max_response_bytes: usize = DEFAULT_MAX_RESPONSE_BYTES,
+    hedge_delay_ms: Option<u64> = None,
+    prewarm_hosts: usize = 0,
+    deterministic: bool = false,
+    quirks: bool = false,
+    wayback_fallback: bool = false,
+    url_metadata: bool = false,
+    url_extensions: bool = false,
+    url_media: bool = false,
+    url_alternates: bool = false,
+    keep_bodies: bool = false,
+    body_dir: Option<PathBuf> = None,
+    har_path: Option<PathBuf> = None,
+    warc_input: Option<Vec<PathBuf>> = None,
+    warc_output: Option<PathBuf> = None,
+    common_crawl: Option<String> = None,
+    bing_api_key: Option<String> = None,
+    reputation_path: Option<PathBuf> = None,
+    user_agents: Option<Vec<String>> = None,
+    proxies: Option<Vec<String>> = None,
+    proxy_assignment: String = "round_robin".to_string(),
+    max_retries: usize = 0,
+    retry_backoff_ms: u64 = 500,
+    retry_max_backoff_ms: u64 = 30_000,
+    retry_jitter: f64 = 0.1,
+    retry_statuses: Option<Vec<u16>> = None,
+    url_hook: Option<PyObject> = None,
+    url_hook_batch_size: usize = DEFAULT_HOOK_BATCH_SIZE,
+    max_concurrent_requests: Option<usize> = None,
+    max_requests_per_host: Option<usize> = None,
+    host_delay_ms: Option<u64> = None,
+    respect_robots: bool = false,
+    http_cache: bool = false,
+    http_cache_dir: Option<PathBuf> = None,
+    include: Option<Vec<String>> = None,
+    exclude: Option<Vec<String>> = None,
+    include_sitemaps: Option<Vec<String>> = None,
+    modified_since: Option<String> = None,
+    headers: Option<HashMap<String, String>> = None,
+    user_agent: Option<String> = None,
+    proxy: Option<String> = None,
+    danger_accept_invalid_certs: bool = false,
+    http2: bool = true,
+    checkpoint: Option<PathBuf> = None,
+    site_timeout_seconds: Option<f64> = None,
+    max_redirects: usize = MAX_REDIRECTS,
+    cross_host_redirects: bool = true,
+    fetcher: Option<PyObject> = None,
+    sitemap_paths: Option<Vec<String>> = None,
+    probe_sitemaps: bool = true,
+    homepage_sitemap_links: bool = true,
+    max_urls_per_site: Option<usize> = None,
+    canonicalize: bool = false,
+    trailing_slash: String = "keep".to_string(),
+    sort_query: bool = false,
+    strip_params: Option<Vec<String>> = None,
+    dns_cache_ttl_seconds: Option<u64> = None,
+    block_private_ips: bool = false,
+    allow_networks: Option<Vec<String>> = None,
+    deny_networks: Option<Vec<String>> = None,
+    allow_hosts: Option<Vec<String>> = None,
+    deny_hosts: Option<Vec<String>> = None,
+    dns_overrides: Option<HashMap<String, Vec<String>>> = None,
+    dns_servers: Option<Vec<String>> = None,
+}
+
+/// `ParserOptions` checked, with their files, stores, and pools opened: everything a
+/// `RustParser` or `parse_sitemaps_rust()` call configures its parsers with
+struct ParserConfig {
+    max_concurrent: usize,
+    max_sitemaps: usize,
+    max_depth: usize,
+    max_nested_per_level: usize,
+    timeout_seconds: u64,
+    max_response_bytes: usize,
+    hedge_delay_ms: Option<u64>,
+    prewarm_hosts: usize,
+    deterministic: bool,
+    quirks: bool,
+    wayback_fallback: bool,
+    url_metadata: bool,
+    url_extensions: bool,
+    url_media: bool,
+    url_alternates: bool,
+    respect_robots: bool,
+    http_cache: Option<Arc<dyn HttpCache>>,
+    url_filter: Option<Arc<UrlFilter>>,
+    canonicalizer: Option<Arc<UrlCanonicalizer>>,
+    modified_since: Option<chrono::DateTime<chrono::Utc>>,
+    keep_bodies: bool,
+    body_store: Option<Arc<BodyStore>>,
+    har_path: Option<PathBuf>,
+    archive: Option<Arc<WarcArchive>>,
+    common_crawl: Option<Arc<CommonCrawlSource>>,
+    bing: Option<Arc<BingWebmasterSource>>,
+    reputation: Option<Arc<ReputationStore>>,
+    checkpoint: Option<Arc<Checkpoint>>,
+    site_timeout: Option<Duration>,
+    max_urls_per_site: Option<usize>,
+    user_agents: Option<Arc<UserAgentPool>>,
+    proxies: Option<Arc<ProxyPool>>,
+    url_hook: Option<Arc<dyn UrlHook>>,
+    url_hook_batch_size: usize,
+    /// A Python callable sending requests in place of the HTTP client
+    fetcher: Option<Arc<PyObject>>,
+    retry_policy: RetryPolicy,
+    redirect_policy: RedirectPolicy,
+    discovery: DiscoveryPolicy,
+    warc: Option<Arc<WarcWriter>>,
+    /// Shared by every parser made from this config, so concurrent calls are capped together
+    request_limits: Arc<RequestLimiter>,
+    /// Shared like `request_limits`, so concurrent calls pace each host together
+    host_pacer: Arc<HostPacer>,
+    client_config: ClientConfig,
+}
+
+impl ParserConfig {
+    fn new(max_concurrent: usize, max_sitemaps: usize, max_depth: usize, max_nested_per_level: usize, timeout_seconds: u64, options: ParserOptions) -> PyResult<Self> {
+        let dns = dns_resolver(&options)?;
+        let client_config = client_config(options.headers, options.user_agent, options.proxy, options.danger_accept_invalid_certs, options.http2, options.proxies.is_some(), dns)?;
+        Ok(Self {
+            max_concurrent,
+            max_sitemaps,
+            max_depth,
+            max_nested_per_level,
+            timeout_seconds,
+            max_response_bytes: options.max_response_bytes,
+            hedge_delay_ms: options.hedge_delay_ms,
+            prewarm_hosts: options.prewarm_hosts,
+            deterministic: options.deterministic,
+            quirks: options.quirks,
+            wayback_fallback: options.wayback_fallback,
+            url_metadata: options.url_metadata,
+            url_extensions: options.url_extensions,
+            url_media: options.url_media,
+            url_alternates: options.url_alternates,
+            respect_robots: options.respect_robots,
+            http_cache: open_http_cache(options.http_cache, options.http_cache_dir.as_deref())?,
+            url_filter: url_filter(options.include, options.exclude, options.include_sitemaps)?,
+            canonicalizer: canonicalizer(options.canonicalize, &options.trailing_slash, options.sort_query, options.strip_params)?,
+            modified_since: options.modified_since.as_deref().map(parse_modified_since).transpose()?,
+            keep_bodies: options.keep_bodies,
+            body_store: open_body_store(options.body_dir.as_deref())?,
+            har_path: options.har_path,
+            common_crawl: common_crawl_source(options.common_crawl, timeout_seconds, options.warc_input.is_some())?,
+            bing: options.bing_api_key.map(|key| Arc::new(BingWebmasterSource::new(key))),
+            reputation: open_reputation_store(options.reputation_path.as_deref())?,
+            checkpoint: open_checkpoint(options.checkpoint.as_deref())?,
+            site_timeout: site_timeout(options.site_timeout_seconds)?,
+            max_urls_per_site: options.max_urls_per_site,
+            user_agents: user_agent_pool(options.user_agents)?,
+            url_hook: url_hook(options.url_hook, options.url_hook_batch_size)?,
+            url_hook_batch_size: options.url_hook_batch_size,
+            fetcher: checked_fetcher(options.fetcher)?,
+            retry_policy: retry_policy(options.max_retries, options.retry_backoff_ms, options.retry_max_backoff_ms, options.retry_jitter, options.retry_statuses)?,
+            redirect_policy: RedirectPolicy { max_redirects: options.max_redirects, cross_host: options.cross_host_redirects },
+            discovery: discovery_policy(options.sitemap_paths, options.probe_sitemaps, options.homepage_sitemap_links),
+            archive: load_warc_input(options.warc_input.as_deref())?,
+            warc: open_warc_output(options.warc_output.as_deref())?,
+            request_limits: Arc::new(RequestLimiter::new(options.max_concurrent_requests, options.max_requests_per_host)),
+            host_pacer: Arc::new(HostPacer::new(options.host_delay_ms.map(Duration::from_millis))),
+            proxies: proxy_pool(options.proxies, &options.proxy_assignment, timeout_seconds, &client_config)?,
+            client_config,
+        })
+    }
+
+    /// A parser with these options, sending its requests through `client`
+    fn parser(&self, client: reqwest::Client) -> RustSitemapParser {
+        RustSitemapParser::from_client(client, self.max_concurrent, self.max_sitemaps, self.max_depth, self.max_nested_per_level)
+            .with_max_response_bytes(self.max_response_bytes)
+            .with_hedge_delay(self.hedge_delay_ms.map(tokio::time::Duration::from_millis))
+            .with_prewarm_hosts(self.prewarm_hosts)
+            .with_deterministic(self.deterministic)
+            .with_quirks(self.quirks)
+            .with_wayback_fallback(self.wayback_fallback)
+            .with_url_metadata(self.url_metadata)
+            .with_url_extensions(self.url_extensions)
+            .with_url_media(self.url_media)
+            .with_url_alternates(self.url_alternates)
+            .with_respect_robots(self.respect_robots)
+            .with_http_cache(self.http_cache.clone())
+            .with_url_filter(self.url_filter.clone())
+            .with_canonicalizer(self.canonicalizer.clone())
+            .with_modified_since(self.modified_since)
+            .with_keep_bodies(self.keep_bodies)
+            .with_body_store(self.body_store.clone())
+            .with_har(self.har_path.as_ref().map(|_| Arc::new(HarRecorder::new())))
+            .with_archive(self.archive.clone())
+            .with_common_crawl(self.common_crawl.clone())
+            .with_bing_webmaster(self.bing.clone())
+            .with_reputation(self.reputation.clone())
+            .with_checkpoint(self.checkpoint.clone())
+            .with_site_timeout(self.site_timeout)
+            .with_max_urls_per_site(self.max_urls_per_site)
+            .with_user_agents(self.user_agents.clone())
+            .with_proxies(self.proxies.clone())
+            .with_dns(self.client_config.dns.clone())
+            .with_retry_policy(self.retry_policy.clone())
+            .with_redirect_policy(self.redirect_policy.clone())
+            .with_discovery(self.discovery.clone())
+            .with_url_hook(self.url_hook.clone(), self.url_hook_batch_size)
+            .with_fetcher(self.fetcher.as_ref().map(|fetcher| python_fetcher(Arc::clone(fetcher))))
+            .with_warc_output(self.warc.clone())
+            .with_request_limits(self.request_limits.clone())
+            .with_host_pacer(self.host_pacer.clone())
+            .with_user_agent(self.client_config.user_agent())
+    }
+}
+
+/// Client settings from `RustParser` options; `proxy` and a `proxies` pool are alternatives
+fn client_config(
+    headers: Option<HashMap<String, String>>,
+    user_agent: Option<String>,
+    proxy: Option<String>,
+    danger_accept_invalid_certs: bool,
+    http2: bool,
+    has_proxies: bool,
+    dns: Option<DnsResolver>,
+) -> PyResult<ClientConfig> {
+    let headers = ClientConfig::parse_headers(headers.iter().flatten().map(|(name, value)| (name.as_str(), value.as_str())))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    if let Some(proxy) = &proxy {
+        if has_proxies {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("proxy and proxies are alternatives; pass only one"));
+        }
+        ClientConfig::check_proxy(proxy)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid proxy: {}", e)))?;
+    }
+    if danger_accept_invalid_certs {
+        warn!("🦀 TLS certificate checks are off; only use danger_accept_invalid_certs against hosts you trust");
+    }
+    Ok(ClientConfig { headers, user_agent, proxy, danger_accept_invalid_certs, http2, dns })
+}
+
+/// Name resolution and address checks from the DNS options, if any are given
+fn dns_resolver(options: &ParserOptions) -> PyResult<Option<DnsResolver>> {
+    let networks = |option: &str, networks: &Option<Vec<String>>| -> PyResult<Vec<IpNet>> {
+        networks.iter().flatten()
+            .map(|network| parse_network(network).map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid entry in {}: {}", option, e))))
+            .collect()
+    };
+    let mut overrides = HashMap::new();
+    for (host, addrs) in options.dns_overrides.iter().flatten() {
+        let addrs = addrs.iter()
+            .map(|addr| addr.trim().parse::<IpAddr>().map_err(|_| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid address for {} in dns_overrides: {:?}", host, addr))))
+            .collect::<PyResult<Vec<IpAddr>>>()?;
+        overrides.insert(host.trim_end_matches('.').to_ascii_lowercase(), addrs);
+    }
+    let policy = DnsPolicy {
+        cache_ttl: options.dns_cache_ttl_seconds.map(Duration::from_secs),
+        block_private: options.block_private_ips,
+        allow_networks: networks("allow_networks", &options.allow_networks)?,
+        deny_networks: networks("deny_networks", &options.deny_networks)?,
+        allow_hosts: options.allow_hosts.clone().unwrap_or_default(),
+        deny_hosts: options.deny_hosts.clone().unwrap_or_default(),
+        overrides,
+        nameservers: options.dns_servers.iter().flatten()
+            .map(|server| parse_nameserver(server).map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid entry in dns_servers: {}", e))))
+            .collect::<PyResult<_>>()?,
+    };
+    Ok((policy != DnsPolicy::default()).then(|| DnsResolver::new(policy)))
+}
+
+/// A Python callable `hook(sitemap_url, urls) -> urls`, called with the GIL held
+struct PythonUrlHook(PyObject);
+
+impl UrlHook for PythonUrlHook {
+    fn apply(&self, sitemap_url: &str, urls: Vec<String>) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        Python::with_gil(|py| {
+            let returned = self.0.call1(py, (sitemap_url, urls))?;
+            returned.extract::<Vec<String>>(py)
+        })
+        .map_err(|e| e.to_string().into())
+    }
+}
+
+/// A Python callable `fetcher(url, headers)` answering with the body of a 200 as
+/// `bytes` or with a `(status, headers, body)` tuple, or with an awaitable of either,
+/// awaited on the event loop of the call that built the parser
+struct PythonFetcher {
+    fetcher: Arc<PyObject>,
+    locals: Option<TaskLocals>,
+}
+
+impl Fetcher for PythonFetcher {
+    fn fetch<'a>(&'a self, url: &'a str, headers: &'a HeaderMap) -> BoxFuture<'a, Result<FetchResponse, Box<dyn std::error::Error + Send + Sync>>> {
+        let headers: HashMap<String, String> = headers.iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let (fetcher, url) = (Arc::clone(&self.fetcher), url.to_string());
+        Box::pin(async move {
+            // Synchronous fetchers may block, so keep them off the runtime's workers
+            let returned = tokio::task::spawn_blocking(move || Python::with_gil(|py| fetcher.call1(py, (url, headers)))).await??;
+            let awaitable = Python::with_gil(|py| returned.bind(py).hasattr("__await__"))?;
+            let returned = match (awaitable, &self.locals) {
+                (false, _) => returned,
+                (true, Some(locals)) => Python::with_gil(|py| pyo3_async_runtimes::into_future_with_locals(locals, returned.into_bound(py)))?.await?,
+                (true, None) => return Err("An async fetcher needs a running event loop; call the parser from async code or pass a synchronous fetcher".into()),
+            };
+            Ok(Python::with_gil(|py| fetch_response(returned.bind(py)))?)
+        })
+    }
+}
+
+/// What a Python fetcher returned, as a response
+fn fetch_response(returned: &Bound<'_, PyAny>) -> PyResult<FetchResponse> {
+    let (status, headers, body) = if returned.is_instance_of::<PyBytes>() {
+        (200, HashMap::new(), returned.extract::<Vec<u8>>()?)
+    } else {
+        returned.extract::<(u16, HashMap<String, String>, Vec<u8>)>()?
+    };
+    let mut response = http::Response::builder().status(status);
+    for (name, value) in headers {
+        response = response.header(name, value);
+    }
+    response.body(body.into())
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid fetcher response: {}", e)))
+}
+
+/// A parser's fetcher for the call being made, awaiting async results on its event loop, if it has one
+fn python_fetcher(fetcher: Arc<PyObject>) -> Arc<dyn Fetcher> {
+    let locals = Python::with_gil(|py| pyo3_async_runtimes::tokio::get_current_locals(py).ok());
+    Arc::new(PythonFetcher { fetcher, locals })
+}
+
+/// Where to look for sitemaps robots.txt doesn't list, from the discovery options
+fn discovery_policy(sitemap_paths: Option<Vec<String>>, probe_sitemaps: bool, homepage_sitemap_links: bool) -> DiscoveryPolicy {
+    DiscoveryPolicy {
+        candidate_paths: sitemap_paths.unwrap_or_else(|| DiscoveryPolicy::default().candidate_paths),
+        probe: probe_sitemaps,
+        homepage_links: homepage_sitemap_links,
+    }
+}
+
+fn checked_fetcher(fetcher: Option<PyObject>) -> PyResult<Option<Arc<PyObject>>> {
+    let Some(fetcher) = fetcher else { return Ok(None) };
+    if !Python::with_gil(|py| fetcher.bind(py).is_callable()) {
+        return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>("fetcher must be callable"));
+    }
+    Ok(Some(Arc::new(fetcher)))
+}
+
+/// A Python callable `fetch(url) -> bytes | None`, called with the GIL held
+struct PythonSitemapFetch(PyObject);
+
+impl SitemapFetch for PythonSitemapFetch {
+    fn fetch(&self, url: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + Sync>> {
+        Python::with_gil(|py| {
+            let returned = self.0.call1(py, (url,))?;
+            returned.extract::<Option<Vec<u8>>>(py)
+        })
+        .map_err(|e| e.to_string().into())
+    }
+}
+
+fn sitemap_fetch(fetch: Option<PyObject>) -> PyResult<Option<PythonSitemapFetch>> {
+    let Some(fetch) = fetch else { return Ok(None) };
+    if !Python::with_gil(|py| fetch.bind(py).is_callable()) {
+        return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>("fetch must be callable"));
+    }
+    Ok(Some(PythonSitemapFetch(fetch)))
+}
+
+fn url_hook(hook: Option<PyObject>, batch_size: usize) -> PyResult<Option<Arc<dyn UrlHook>>> {
+    let Some(hook) = hook else { return Ok(None) };
+    if batch_size == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("url_hook_batch_size must be at least 1"));
+    }
+    if !Python::with_gil(|py| hook.bind(py).is_callable()) {
+        return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>("url_hook must be callable"));
+    }
+    Ok(Some(Arc::new(PythonUrlHook(hook))))
+}
+
+/// `None` unless some pattern was given; a pattern that isn't a valid regex is a ValueError
+fn url_filter(include: Option<Vec<String>>, exclude: Option<Vec<String>>, sitemaps: Option<Vec<String>>) -> PyResult<Option<Arc<UrlFilter>>> {
+    let (include, exclude, sitemaps) = (include.unwrap_or_default(), exclude.unwrap_or_default(), sitemaps.unwrap_or_default());
+    if include.is_empty() && exclude.is_empty() && sitemaps.is_empty() {
+        return Ok(None);
+    }
+    let filter = UrlFilter::new(&include, &exclude, &sitemaps)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid URL pattern: {}", e)))?;
+    Ok(Some(Arc::new(filter)))
+}
+
+/// URL canonicalization per `RustParser` options; any of them other than its default turns it on
+fn canonicalizer(canonicalize: bool, trailing_slash: &str, sort_query: bool, strip_params: Option<Vec<String>>) -> PyResult<Option<Arc<UrlCanonicalizer>>> {
+    let trailing_slash: TrailingSlash = trailing_slash.parse().map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+    let canonicalizer = UrlCanonicalizer { trailing_slash, sort_query, strip_params: strip_params.unwrap_or_default() };
+    if !canonicalize && canonicalizer == UrlCanonicalizer::default() {
+        return Ok(None);
+    }
+    Ok(Some(Arc::new(canonicalizer)))
+}
+
+/// An ISO 8601 timestamp or date; one without a UTC offset, as `datetime.isoformat()`
+/// gives for naive datetimes, is taken as UTC
+fn parse_modified_since(since: &str) -> PyResult<chrono::DateTime<chrono::Utc>> {
+    crate::score::parse_cutoff(since)
+        .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("modified_since is not an ISO 8601 timestamp: {:?}", since)))
+}
+
+/// A Python callable `callback(event)`, called with the GIL held. Its exceptions are
+/// logged rather than raised, so a broken progress bar can't fail the parse.
+struct PythonProgress(PyObject);
+
+impl ProgressListener for PythonProgress {
+    fn on_progress(&self, event: ProgressEvent) {
+        Python::with_gil(|py| {
+            if let Err(e) = self.0.call1(py, (PyProgressEvent::from(event),)) {
+                warn!("🦀 Progress callback failed: {}", e);
+            }
+        })
+    }
+}
+
+fn progress_callback(callback: Option<PyObject>) -> PyResult<Option<Arc<dyn ProgressListener>>> {
+    let Some(callback) = callback else { return Ok(None) };
+    if !Python::with_gil(|py| callback.bind(py).is_callable()) {
+        return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>("progress must be callable"));
+    }
+    Ok(Some(Arc::new(PythonProgress(callback))))
+}
+
+fn result_callback(callback: Option<PyObject>) -> PyResult<Option<PyObject>> {
+    let Some(callback) = callback else { return Ok(None) };
+    if !Python::with_gil(|py| callback.bind(py).is_callable()) {
+        return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>("on_result must be callable"));
+    }
+    Ok(Some(callback))
+}
+
+/// Convert a site's result as soon as it's done and hand it to `on_result`, whose
+/// exceptions are logged like a progress callback's. `cancel` is the call's token,
+/// for telling a cancelled site from one the parser was shut down under.
+fn deliver_result(parsed: ParsedSiteResult, deterministic: bool, cancel: Option<&Shutdown>, on_result: Option<&PyObject>) -> PyResult<Py<SitemapResult>> {
+    let mut result = SitemapResult::from_parsed(parsed, deterministic);
+    result.cancelled = result.interrupted && cancel.is_some_and(Shutdown::is_requested);
+    Python::with_gil(|py| {
+        let result = Py::new(py, result)?;
+        if let Some(callback) = on_result {
+            if let Err(e) = callback.call1(py, (result.clone_ref(py),)) {
+                warn!("🦀 on_result callback failed: {}", e);
+            }
+        }
+        Ok(result)
+    })
+}
+
+/// A per-site deadline in seconds; it has to be positive
+fn site_timeout(seconds: Option<f64>) -> PyResult<Option<Duration>> {
+    match seconds {
+        Some(seconds) if !(seconds.is_finite() && seconds > 0.0) => {
+            Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("site_timeout_seconds must be a positive number"))
+        }
+        seconds => Ok(seconds.map(Duration::from_secs_f64)),
+    }
+}
+
+fn retry_policy(max_retries: usize, backoff_ms: u64, max_backoff_ms: u64, jitter: f64, statuses: Option<Vec<u16>>) -> PyResult<RetryPolicy> {
+    if !(0.0..=1.0).contains(&jitter) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("retry_jitter must be between 0 and 1"));
+    }
+    if let Some(status) = statuses.iter().flatten().find(|status| !(100..=599).contains(*status)) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid HTTP status in retry_statuses: {}", status)));
+    }
+    Ok(RetryPolicy {
+        max_retries,
+        base_backoff: Duration::from_millis(backoff_ms),
+        max_backoff: Duration::from_millis(max_backoff_ms),
+        jitter,
+        retry_statuses: statuses.unwrap_or_else(|| crate::retry::RETRY_STATUSES.to_vec()),
+    })
+}
+
+/// How often blocking calls let Python run its signal handlers, so Ctrl-C lands promptly
+const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Clients `parse_sitemaps_rust` calls share, by timeout in seconds
+static SHARED_CLIENTS: Mutex<Option<HashMap<u64, reqwest::Client>>> = Mutex::new(None);
+
+/// The process-wide client for requests timing out after `timeout_seconds`, built on first use
+fn shared_client(timeout_seconds: u64) -> PyResult<reqwest::Client> {
+    let mut clients = SHARED_CLIENTS.lock().unwrap();
+    let clients = clients.get_or_insert_with(HashMap::new);
+    if let Some(client) = clients.get(&timeout_seconds) {
+        return Ok(client.clone());
+    }
+    let client = crate::parser::client_builder(Duration::from_secs(timeout_seconds)).build()
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to create HTTP client: {}", e)))?;
+    clients.insert(timeout_seconds, client.clone());
+    Ok(client)
+}
+
+/// Stop signals of every parser created from Python, interrupted together at interpreter exit
+static SHUTDOWNS: Mutex<Vec<Weak<Shutdown>>> = Mutex::new(Vec::new());
+
+fn register_shutdown() -> Arc<Shutdown> {
+    let shutdown = Arc::new(Shutdown::new());
+    let mut shutdowns = SHUTDOWNS.lock().unwrap();
+    shutdowns.retain(|s| s.strong_count() > 0);
+    shutdowns.push(Arc::downgrade(&shutdown));
+    shutdown
+}
+
+/// Registered with `atexit`: interrupt every live parser, and give runs still going in
+/// the background (jobs, streams) the grace period to finish and save their state
+#[pyfunction]
+fn interrupt_all_parsers(py: Python<'_>) {
+    let live: Vec<Arc<Shutdown>> = SHUTDOWNS.lock().unwrap().iter().filter_map(Weak::upgrade).collect();
+    for shutdown in &live {
+        shutdown.request(DEFAULT_GRACE);
+    }
+    py.allow_threads(|| {
+        let until = Instant::now() + DEFAULT_GRACE + Duration::from_secs(1);
+        for shutdown in &live {
+            if !shutdown.wait_idle(until.saturating_duration_since(Instant::now())) {
+                warn!("🦀 Exiting with sites still being parsed");
+                break;
+            }
+        }
+    });
+}
+
+fn open_warc_output(path: Option<&Path>) -> PyResult<Option<Arc<WarcWriter>>> {
+    let Some(path) = path else { return Ok(None) };
+    let writer = WarcWriter::create(path).map_err(|e| {
+        PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to open WARC output {}: {}", path.display(), e))
+    })?;
+    Ok(Some(Arc::new(writer)))
+}
+
+/// Synchronous convenience function for parsing multiple sites; it takes the same
+/// keyword options as `RustParser`, plus `on_result`
+#[pyfunction]
+#[pyo3(signature = (
+    base_urls,
+    max_concurrent = 10,
+    max_sitemaps = 10,
+    max_depth = 2,
+    max_nested_per_level = 5,
+    timeout_seconds = 30,
+    on_result = None,
+    **options,
+))]
+#[allow(clippy::too_many_arguments)]
+fn parse_sitemaps_rust(
+    py: Python<'_>,
+    base_urls: Vec<String>,
+    max_concurrent: usize,
+    max_sitemaps: usize,
+    max_depth: usize,
+    max_nested_per_level: usize,
+    timeout_seconds: u64,
+    on_result: Option<PyObject>,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<Vec<Py<SitemapResult>>> {
+    info!("🦀 Starting Rust sitemap parsing for {} URLs", base_urls.len());
+    debug!("🦀 Configuration: max_concurrent={}, max_sitemaps={}, max_depth={}, max_nested_per_level={}, timeout={}s", 
+           max_concurrent, max_sitemaps, max_depth, max_nested_per_level, timeout_seconds);
+    
+    // The runtime and client outlive the call, so repeated calls reuse worker threads and warm connections
+    let rt = pyo3_async_runtimes::tokio::get_runtime();
+    let options = ParserOptions::from_kwargs("parse_sitemaps_rust", options)?;
+    let config = ParserConfig::new(max_concurrent, max_sitemaps, max_depth, max_nested_per_level, timeout_seconds, options)?;
+    let client = if config.client_config.is_default() {
+        shared_client(timeout_seconds)?
+    } else {
+        config.client_config.build(Duration::from_secs(timeout_seconds))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to create HTTP client: {}", e)))?
+    };
+    let (deterministic, har_path) = (config.deterministic, config.har_path.clone());
+    let on_result = result_callback(on_result)?;
+    let shutdown = register_shutdown();
+    let parser = config.parser(client).with_shutdown(Arc::clone(&shutdown));
+
+    // Without the GIL, so a `url_hook` can take it from the blocking pool
+    py.allow_threads(|| rt.block_on(async {
+        // Ctrl-C stops the run gracefully and returns what it has; a second one aborts it
+        let parsing = parser.parse_multiple_sites_with(base_urls, |r| deliver_result(r, deterministic, None, on_result.as_ref()));
+        tokio::pin!(parsing);
+        let mut signal_checks = tokio::time::interval(SIGNAL_CHECK_INTERVAL);
+        let parsed = loop {
+            tokio::select! {
+                parsed = &mut parsing => break parsed,
+                _ = signal_checks.tick() => {
+                    if let Err(e) = Python::with_gil(|py| py.check_signals()) {
+                        if shutdown.is_requested() {
+                            return Err(e);
+                        }
+                        warn!("🦀 Interrupted; letting in-flight fetches finish for up to {:?}", DEFAULT_GRACE);
+                        shutdown.request(DEFAULT_GRACE);
+                    }
+                }
+            }
+        };
+        write_har(&parser, har_path.as_deref());
+        match parsed {
+            Ok(results) => results.into_iter().collect(),
+            Err(e) => Err(PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(
+                format!("Failed to parse sites: {}", e)
+            ))
+        }
+    }))
+}
+
+/// Shard `base_urls` across workers that connect to `bind`, returning every site's result once all are parsed.
+///
+/// Workers are `RustParser.run_worker()` calls or `sitemap-parser worker` processes on
+/// any machine that can reach the coordinator. A worker's unfinished shards are handed
+/// to another if it disconnects. Merged results carry URLs, sitemaps, errors, and
+/// request counts, but not the per-site extras like memory or metadata.
+#[pyfunction]
+#[pyo3(signature = (base_urls, bind = "127.0.0.1:7878", shard_size = 100, deterministic = false))]
+fn coordinate_sites(py: Python<'_>, base_urls: Vec<String>, bind: &str, shard_size: usize, deterministic: bool) -> PyResult<Vec<SitemapResult>> {
+    let merged = py.allow_threads(|| {
+        pyo3_async_runtimes::tokio::get_runtime().block_on(async {
+            crate::distributed::Coordinator::bind(bind, base_urls, shard_size).await?.run().await
+        })
+    });
+    let mut results = merged
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Coordinator on {} failed: {}", bind, e)))?;
+    if deterministic {
+        results.sort_by(|a, b| a.base_url.cmp(&b.base_url));
+    }
+    Ok(results.into_iter().map(|r| SitemapResult::from_parsed(r, deterministic)).collect())
+}
+
+/// One step of a health check
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct HealthCheck {
+    #[pyo3(get)]
+    pub name: String,
+    /// "ok", "failed", or "skipped"
+    #[pyo3(get)]
+    pub status: String,
+    #[pyo3(get)]
+    pub detail: String,
+    #[pyo3(get)]
+    pub elapsed_ms: f64,
+}
+
+#[pymethods]
+impl HealthCheck {
+    fn __repr__(&self) -> String {
+        format!("HealthCheck(name='{}', status='{}', detail='{}')", self.name, self.status, self.detail)
+    }
+}
+
+/// Structured diagnosis of the network environment
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct HealthReport {
+    #[pyo3(get)]
+    pub target: String,
+    #[pyo3(get)]
+    pub ok: bool,
+    #[pyo3(get)]
+    pub checks: Vec<HealthCheck>,
+}
+
+#[pymethods]
+impl HealthReport {
+    fn __repr__(&self) -> String {
+        let failed: Vec<&str> = self.checks.iter().filter(|c| c.status == "failed").map(|c| c.name.as_str()).collect();
+        format!("HealthReport(target='{}', ok={}, failed={:?})", self.target, self.ok, failed)
+    }
+}
+
+impl From<crate::health::HealthReport> for HealthReport {
+    fn from(r: crate::health::HealthReport) -> Self {
+        Self {
+            target: r.target.clone(),
+            ok: r.ok(),
+            checks: r
+                .checks
+                .into_iter()
+                .map(|c| HealthCheck {
+                    name: c.name.to_string(),
+                    status: c.status.to_string(),
+                    detail: c.detail,
+                    elapsed_ms: c.elapsed.as_secs_f64() * 1000.0,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Check DNS, proxy, TCP, TLS, and an HTTP round trip to `url`, for diagnosing runs that find nothing
+#[pyfunction]
+#[pyo3(signature = (url = "https://www.example.com/", timeout_seconds = 10))]
+fn health_check(py: Python<'_>, url: &str, timeout_seconds: u64) -> HealthReport {
+    let report = py.allow_threads(|| {
+        pyo3_async_runtimes::tokio::get_runtime()
+            .block_on(crate::health::health_check(url, tokio::time::Duration::from_secs(timeout_seconds)))
+    });
+    report.into()
+}
+
+/// Local HTTP server for a generated sitemap tree, for benchmarking without real sites
+#[pyclass(name = "SyntheticServer")]
+pub struct PySyntheticServer {
+    inner: crate::synthetic::SyntheticServer,
+}
+
+#[pymethods]
+impl PySyntheticServer {
+    #[getter]
+    fn base_url(&self) -> String {
+        self.inner.base_url()
+    }
+
+    #[getter]
+    fn expected_urls(&self) -> usize {
+        self.inner.expected_urls
+    }
+
+    fn stop(&self) {
+        self.inner.stop();
+    }
+
+    fn __repr__(&self) -> String {
+        format!("SyntheticServer(base_url='{}', expected_urls={})", self.inner.base_url(), self.inner.expected_urls)
+    }
+}
+
+/// Serve a synthetic sitemap tree from memory on an ephemeral localhost port
+#[pyfunction]
+#[pyo3(signature = (urls_per_sitemap = 1000, depth = 2, fanout = 5, broken_every = 0, gzip = false))]
+fn serve_synthetic_site(py: Python<'_>, urls_per_sitemap: usize, depth: usize, fanout: usize, broken_every: usize, gzip: bool) -> PyResult<PySyntheticServer> {
+    let config = SyntheticConfig { urls_per_sitemap, depth, fanout, broken_every, gzip };
+    let inner = py
+        .allow_threads(|| pyo3_async_runtimes::tokio::get_runtime().block_on(crate::synthetic::SyntheticServer::start(&config)))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to start synthetic server: {}", e)))?;
+    info!("🦀 Synthetic site serving {} URLs at {}", inner.expected_urls, inner.base_url());
+    Ok(PySyntheticServer { inner })
+}
+
+/// Write a synthetic sitemap tree to `directory`, returning the number of URLs in well-formed urlsets.
+///
+/// Pass an existing `robots_txt` body to have its `Sitemap:` directive merged into it
+/// instead of writing the generator's own robots.txt.
+#[pyfunction]
+#[pyo3(signature = (directory, base_url, urls_per_sitemap = 1000, depth = 2, fanout = 5, broken_every = 0, gzip = false, robots_txt = None))]
+#[allow(clippy::too_many_arguments)]
+fn write_synthetic_site(
+    directory: PathBuf,
+    base_url: &str,
+    urls_per_sitemap: usize,
+    depth: usize,
+    fanout: usize,
+    broken_every: usize,
+    gzip: bool,
+    robots_txt: Option<&str>,
+) -> PyResult<usize> {
+    let config = SyntheticConfig { urls_per_sitemap, depth, fanout, broken_every, gzip };
+    let mut site = SyntheticSite::generate(&config, base_url);
+    if let Some(existing) = robots_txt {
+        let sitemap_url = format!("{}/sitemap.xml", base_url.trim_end_matches('/'));
+        site.files.insert("/robots.txt".to_string(), crate::robots::merge_sitemap_directives(existing, &[sitemap_url]).into_bytes());
+    }
+    site.write_to_dir(&directory)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyOSError, _>(format!("Failed to write synthetic site: {}", e)))?;
+    Ok(site.expected_urls)
+}
+
+/// `Sitemap:` lines declaring each of `sitemap_urls`, one per line
+/// Iterate over the URLs in a blob from `SitemapResult.urls_compressed()` without building a list
+#[pyfunction]
+fn unpack_urls(packed: Vec<u8>) -> PyPackedUrls {
+    PyPackedUrls { urls: crate::transfer::unpack_urls(std::io::Cursor::new(packed)) }
+}
+
+#[pyfunction]
+fn sitemap_directives(sitemap_urls: Vec<String>) -> String {
+    crate::robots::sitemap_directives(&sitemap_urls)
+}
+
+/// Add `Sitemap:` directives for `sitemap_urls` to an existing robots.txt body.
+///
+/// Sitemaps it already declares are left alone, and so is every other line.
+#[pyfunction]
+fn merge_robots_txt(robots_txt: &str, sitemap_urls: Vec<String>) -> String {
+    crate::robots::merge_sitemap_directives(robots_txt, &sitemap_urls)
+}
+
+/// Serve tracing spans and tokio task data to tokio-console (default address 127.0.0.1:6669).
+///
+/// Call before the first parse so every task is seen. Task details need the
+/// extension built with `RUSTFLAGS="--cfg tokio_unstable"`.
+#[cfg(feature = "console")]
+#[pyfunction]
+#[pyo3(signature = (server_addr = None))]
+fn enable_tokio_console(server_addr: Option<String>) -> PyResult<()> {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let mut builder = console_subscriber::ConsoleLayer::builder().with_default_env();
+    if let Some(addr) = server_addr {
+        let addr: std::net::SocketAddr = addr
+            .parse()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Invalid console address {}: {}", addr, e)))?;
+        builder = builder.server_addr(addr);
+    }
+
+    // Set the subscriber directly rather than via `init()`, which would also try
+    // to install a `log` bridge and collide with pyo3-log
+    let subscriber = tracing_subscriber::registry().with(builder.spawn());
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Failed to enable tokio-console: {}", e)))
+}
+
+/// The Rust sitemap parser module
+#[pymodule]
+fn rust_parser(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    // Initialize logging to send Rust logs to Python
+    pyo3_log::init();
+    
+    m.add_class::<SitemapResult>()?;
+    crate::exceptions::register(m)?;
+    m.add_class::<TruncationInfo>()?;
+    m.add_class::<MemoryInfo>()?;
+    m.add_class::<ArchiveInfo>()?;
+    m.add_class::<RedirectInfo>()?;
+    m.add_class::<PyUrlMetadata>()?;
+    m.add_class::<PyUrlEntry>()?;
+    m.add_class::<PyImageEntry>()?;
+    m.add_class::<PyVideoEntry>()?;
+    m.add_class::<PyNewsEntry>()?;
+    m.add_class::<PyScoredUrl>()?;
+    m.add_class::<PyUrlTemplate>()?;
+    m.add_class::<PyAnomaly>()?;
+    m.add_class::<HealthCheck>()?;
+    m.add_class::<HealthReport>()?;
+    m.add_class::<PySitePlan>()?;
+    m.add_class::<PyRobotsPolicy>()?;
+    m.add_class::<PyPlannedSitemap>()?;
+    m.add_class::<PySiteEstimate>()?;
+    m.add_class::<PyIndexNowSubmission>()?;
+    m.add_class::<PyProxyStats>()?;
+    m.add_class::<PyRetryAttempt>()?;
+    m.add_class::<PyRetryRecord>()?;
+    m.add_class::<PyRetryReport>()?;
+    m.add_class::<PyFetchStats>()?;
+    m.add_class::<PyDurationStats>()?;
+    m.add_class::<PyContentionStats>()?;
+    m.add_class::<PyFinishedSite>()?;
+    m.add_class::<PyExportSummary>()?;
+    m.add_class::<PySitemapWriter>()?;
+    m.add_class::<PySitemapWriteSummary>()?;
+    m.add_class::<PySitemapSnapshot>()?;
+    m.add_class::<PySnapshotDiff>()?;
+    m.add_class::<PyRunStatus>()?;
+    m.add_class::<PyProgressEvent>()?;
+    m.add_class::<PyCancelToken>()?;
+    #[cfg(feature = "search-console")]
+    m.add_class::<PySitemapCountComparison>()?;
+    #[cfg(feature = "tui")]
+    m.add_class::<PyDashboard>()?;
+    m.add_class::<RustParser>()?;
+    m.add_class::<SiteResultStream>()?;
+    m.add_class::<UrlBatchStream>()?;
+    m.add_class::<PyParseJob>()?;
+    m.add_class::<JobProgress>()?;
+    m.add_class::<PySyntheticServer>()?;
+    m.add_class::<PyPackedUrls>()?;
+    m.add_function(wrap_pyfunction!(parse_sitemaps_rust, m)?)?;
+    m.add_function(wrap_pyfunction!(coordinate_sites, m)?)?;
+    m.add_function(wrap_pyfunction!(serve_synthetic_site, m)?)?;
+    m.add_function(wrap_pyfunction!(write_synthetic_site, m)?)?;
+    m.add_function(wrap_pyfunction!(sitemap_directives, m)?)?;
+    m.add_function(wrap_pyfunction!(merge_robots_txt, m)?)?;
+    m.add_function(wrap_pyfunction!(health_check, m)?)?;
+    m.add_function(wrap_pyfunction!(unpack_urls, m)?)?;
+    let atexit = m.py().import("atexit")?;
+    atexit.call_method1("register", (wrap_pyfunction!(interrupt_all_parsers, m)?,))?;
+    #[cfg(feature = "console")]
+    m.add_function(wrap_pyfunction!(enable_tokio_console, m)?)?;
+    Ok(())
+}
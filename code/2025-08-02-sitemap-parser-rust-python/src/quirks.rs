@@ -0,0 +1,171 @@
+use quick_xml::escape::unescape;
+use std::borrow::Cow;
+
+/// Root elements a sitemap document can start with
+const ROOT_TAGS: [&str; 2] = ["urlset", "sitemapindex"];
+
+/// Byte-order mark, which some CMSes emit at the start of every included template
+pub const BOM: char = '\u{feff}';
+
+/// How many times to decode a `<loc>` value; enough for `&amp;amp;amp;`
+const MAX_ENTITY_PASSES: usize = 3;
+
+/// Repair document-level damage before the XML reader sees the content.
+///
+/// Strips stray BOMs wherever they appear, unwraps sitemaps that were served as
+/// HTML-escaped text inside a page (usually a `<pre>` block from a "view sitemap"
+/// template), and cuts away anything around the root element, such as HTML
+/// wrappers, PHP warnings or a misplaced XML declaration.
+pub fn repair(content: &str) -> Cow<'_, str> {
+    let mut content = if content.contains(BOM) {
+        Cow::Owned(content.replace(BOM, ""))
+    } else {
+        Cow::Borrowed(content)
+    };
+
+    if find_root(&content, "<").is_none() {
+        if let Some(unwrapped) = unwrap_escaped(&content) {
+            content = Cow::Owned(unwrapped);
+        }
+    }
+
+    match root_span(&content) {
+        Some((start, end)) if !is_prolog(&content[..start]) || end < content.len() => Cow::Owned(content[start..end].to_string()),
+        _ => content,
+    }
+}
+
+/// Whether `prefix` is a well-formed prolog: an optional XML declaration at the
+/// very start, then only processing instructions, comments and whitespace
+fn is_prolog(prefix: &str) -> bool {
+    let mut rest = prefix;
+    let mut at_start = true;
+    loop {
+        let trimmed = rest.trim_start();
+        if trimmed.is_empty() {
+            return true;
+        }
+        // The XML declaration is only valid as the very first thing in the document
+        if trimmed.starts_with("<?xml ") && !(at_start && trimmed.len() == rest.len()) {
+            return false;
+        }
+        let close = if trimmed.starts_with("<?") {
+            "?>"
+        } else if trimmed.starts_with("<!--") {
+            "-->"
+        } else {
+            return false;
+        };
+        match trimmed.find(close) {
+            Some(end) => rest = &trimmed[end + close.len()..],
+            None => return false,
+        }
+        at_start = false;
+    }
+}
+
+/// Decode a `<loc>` value, including entities that were encoded more than once
+pub fn decode_loc(raw: &str) -> Cow<'_, str> {
+    let mut decoded = Cow::Borrowed(raw);
+    for _ in 0..MAX_ENTITY_PASSES {
+        if !decoded.contains('&') {
+            break;
+        }
+        match unescape(&decoded) {
+            Ok(Cow::Owned(next)) => decoded = Cow::Owned(next),
+            // Nothing left to decode, or an entity we don't know; keep what we have
+            _ => break,
+        }
+    }
+    decoded
+}
+
+/// Byte offset of the first root tag opened with `open` (`<` or `&lt;`)
+fn find_root(content: &str, open: &str) -> Option<(usize, &'static str)> {
+    ROOT_TAGS
+        .iter()
+        .filter_map(|tag| find_tag(content, open, tag).map(|at| (at, *tag)))
+        .min_by_key(|(at, _)| *at)
+}
+
+/// Find `{open}{tag}` followed by whitespace, `>` or `/`, so `<urlset` doesn't match `<urlsetfoo`
+fn find_tag(content: &str, open: &str, tag: &str) -> Option<usize> {
+    let needle = format!("{}{}", open, tag);
+    content.match_indices(&needle).map(|(at, _)| at).find(|at| {
+        let rest = &content[at + needle.len()..];
+        rest.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/' || c == '&')
+    })
+}
+
+/// Span from the root start tag through its end tag, or to the end of the content if it was never closed
+fn root_span(content: &str) -> Option<(usize, usize)> {
+    let (start, tag) = find_root(content, "<")?;
+    let close = format!("</{}>", tag);
+    let end = content[start..]
+        .rfind(&close)
+        .map(|at| start + at + close.len())
+        .unwrap_or(content.len());
+    Some((start, end))
+}
+
+/// Unescape an HTML-escaped sitemap embedded in a page
+fn unwrap_escaped(content: &str) -> Option<String> {
+    let (start, tag) = find_root(content, "&lt;")?;
+    let close = format!("&lt;/{}&gt;", tag);
+    let end = content[start..]
+        .find(&close)
+        .map(|at| start + at + close.len())
+        .unwrap_or(content.len());
+    unescape(&content[start..end]).ok().map(Cow::into_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sitemap::parse_sitemap_xml_with;
+    use std::path::Path;
+
+    #[test]
+    fn test_repair_leaves_clean_documents_alone() {
+        let xml = "<?xml version=\"1.0\"?><urlset><url><loc>https://example.com/</loc></url></urlset>";
+        assert!(matches!(repair(xml), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_decode_loc() {
+        assert_eq!(decode_loc("https://example.com/?a=1&amp;amp;b=2"), "https://example.com/?a=1&b=2");
+        assert_eq!(decode_loc("https://example.com/?a=1&b=2"), "https://example.com/?a=1&b=2");
+        assert_eq!(decode_loc("https://example.com/&nbsp;"), "https://example.com/&nbsp;");
+    }
+
+    /// Every `<name>.xml` in the corpus is parsed with quirks enabled and compared
+    /// against `<name>.expected`: one URL per line, nested sitemaps prefixed with `sitemap `.
+    #[test]
+    fn test_quirks_corpus() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus/quirks");
+        let mut checked = 0;
+
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "xml") {
+                continue;
+            }
+            let content = String::from_utf8_lossy(&std::fs::read(&path).unwrap()).into_owned();
+            let expected = std::fs::read_to_string(path.with_extension("expected")).unwrap();
+
+            let result = parse_sitemap_xml_with(&content, "https://example.com/", true).unwrap();
+            let mut actual: Vec<String> = result.urls.into_iter().collect();
+            actual.sort();
+            actual.extend(result.nested_sitemaps.into_iter().map(|s| format!("sitemap {}", s)));
+
+            let mut expected: Vec<&str> = expected.lines().filter(|l| !l.is_empty()).collect();
+            let split = expected.iter().position(|l| l.starts_with("sitemap ")).unwrap_or(expected.len());
+            expected[..split].sort();
+
+            assert_eq!(actual, expected, "{}", path.display());
+            checked += 1;
+        }
+
+        assert!(checked > 0, "no corpus files in {}", dir.display());
+    }
+}
@@ -0,0 +1,116 @@
+use reqwest::header::{HeaderMap, LOCATION};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::error::SitemapError;
+
+/// Redirects followed before giving up, as many as browsers and reqwest allow
+pub const MAX_REDIRECTS: usize = 10;
+
+/// Which redirects the parser follows. It follows them itself rather than leaving
+/// it to the HTTP client, so every hop is checked here and recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedirectPolicy {
+    /// Redirects followed for one request; 0 treats every redirect as an error
+    pub max_redirects: usize,
+    /// Follow redirects to a different host, like `www.example.com` to `example.com`.
+    /// Off, only changes of scheme, port, or path on the same host are followed.
+    pub cross_host: bool,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self { max_redirects: MAX_REDIRECTS, cross_host: true }
+    }
+}
+
+/// One response that redirected
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedirectHop {
+    pub url: String,
+    pub status: u16,
+}
+
+/// Where a robots.txt or sitemap request that redirected ended up
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedirectChain {
+    /// Each redirect in order, starting with the URL requested; empty if the HTTP
+    /// client followed them itself (one passed to `from_client` that wasn't built
+    /// with `client_builder`)
+    pub hops: Vec<RedirectHop>,
+    /// The URL the response finally came from
+    pub final_url: String,
+}
+
+impl RedirectPolicy {
+    /// The URL to request next when `url` answered `status` with `headers`, or `None`
+    /// if the response isn't a redirect to follow. Fails if the policy doesn't allow
+    /// it; `followed` is how many redirects this request already went through.
+    pub fn next(&self, url: &str, status: StatusCode, headers: &HeaderMap, followed: usize) -> Result<Option<Url>, SitemapError> {
+        // 304 is a 3xx too, but answers a conditional request
+        if !matches!(status.as_u16(), 301 | 302 | 303 | 307 | 308) {
+            return Ok(None);
+        }
+        let Some(location) = headers.get(LOCATION).and_then(|value| value.to_str().ok()) else {
+            return Ok(None);
+        };
+        let current = Url::parse(url).map_err(|e| SitemapError::InvalidUrl { url: url.to_string(), message: e.to_string() })?;
+        let refuse = |location: String, why: String| SitemapError::RedirectRefused {
+            message: format!("Refused redirect from {} to {}: {}", url, location, why),
+            url: url.to_string(),
+            location,
+        };
+        let target = current.join(location).map_err(|e| refuse(location.to_string(), e.to_string()))?;
+        if followed >= self.max_redirects {
+            return Err(SitemapError::TooManyRedirects {
+                url: url.to_string(),
+                message: format!("{} redirected more than {} times", url, self.max_redirects),
+            });
+        }
+        if !matches!(target.scheme(), "http" | "https") {
+            return Err(refuse(target.to_string(), format!("{} URLs aren't fetched", target.scheme())));
+        }
+        if !self.cross_host && target.host_str() != current.host_str() {
+            return Err(refuse(target.to_string(), "redirects to other hosts aren't followed".to_string()));
+        }
+        Ok(Some(target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn to(location: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(LOCATION, HeaderValue::from_str(location).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_policy_resolves_and_refuses_redirects() {
+        let policy = RedirectPolicy { max_redirects: 2, cross_host: false };
+        let url = "http://example.com/sitemap.xml";
+        let next = policy.next(url, StatusCode::MOVED_PERMANENTLY, &to("https://example.com/sitemap.xml"), 0).unwrap();
+        assert_eq!(next.unwrap().as_str(), "https://example.com/sitemap.xml");
+        let next = policy.next(url, StatusCode::FOUND, &to("/sitemaps/index.xml"), 1).unwrap();
+        assert_eq!(next.unwrap().as_str(), "http://example.com/sitemaps/index.xml");
+        // Not redirects to follow
+        assert_eq!(policy.next(url, StatusCode::NOT_MODIFIED, &to("/other.xml"), 0), Ok(None));
+        assert_eq!(policy.next(url, StatusCode::MOVED_PERMANENTLY, &HeaderMap::new(), 0), Ok(None));
+
+        let err = policy.next(url, StatusCode::FOUND, &to("/again.xml"), 2).unwrap_err();
+        assert_eq!(err.kind(), "too_many_redirects");
+        let err = policy.next(url, StatusCode::MOVED_PERMANENTLY, &to("https://www.example.com/sitemap.xml"), 0).unwrap_err();
+        assert_eq!(err, SitemapError::RedirectRefused {
+            url: url.to_string(),
+            location: "https://www.example.com/sitemap.xml".to_string(),
+            message: format!("Refused redirect from {} to https://www.example.com/sitemap.xml: redirects to other hosts aren't followed", url),
+        });
+        assert_eq!(policy.next(url, StatusCode::FOUND, &to("file:///etc/passwd"), 0).unwrap_err().kind(), "redirect_refused");
+        let open = RedirectPolicy::default();
+        assert!(open.next(url, StatusCode::MOVED_PERMANENTLY, &to("https://www.example.com/sitemap.xml"), 0).unwrap().is_some());
+    }
+}
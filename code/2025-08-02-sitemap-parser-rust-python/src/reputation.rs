@@ -0,0 +1,230 @@
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::job::write_atomically;
+
+/// How long after a 429 or 403 a host starts its parses with reduced concurrency
+pub const THROTTLE_MEMORY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Longest a site waits out a remembered Retry-After; later ones skip the site for the run
+pub const MAX_RETRY_WAIT: Duration = Duration::from_secs(5 * 60);
+
+/// Hosts that haven't throttled us for this long are dropped from the store
+const FORGET_AFTER: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// What we remember about one host; times are Unix seconds
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostReputation {
+    /// When the host last answered 429 or 403
+    pub throttled_at: Option<u64>,
+    /// That answer's status code
+    pub throttle_status: Option<u16>,
+    /// Until when the host's Retry-After asked us to stay away
+    pub retry_until: Option<u64>,
+    /// When a parse of the host last finished without being throttled
+    pub succeeded_at: Option<u64>,
+    /// In-flight request limit that parse ran with
+    pub concurrency: Option<usize>,
+}
+
+/// How to treat a host at the start of a site's parse
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HostAdvice {
+    /// Wait this long before the first request
+    pub wait: Duration,
+    /// The host asked for more than `MAX_RETRY_WAIT`; don't contact it this run
+    pub skip_until: Option<u64>,
+    /// Cap on the site's in-flight requests
+    pub concurrency: Option<usize>,
+}
+
+impl HostReputation {
+    /// Back off a host that throttled us recently.
+    ///
+    /// A pending Retry-After is waited out, or the host is skipped if that's longer
+    /// than `MAX_RETRY_WAIT`. Within `THROTTLE_MEMORY` of a throttle, a host that has
+    /// been parsed cleanly since keeps that parse's concurrency, and one that hasn't
+    /// gets half of it (one request at a time if it never had a clean parse).
+    pub fn advice(&self, now: u64) -> HostAdvice {
+        let mut advice = HostAdvice::default();
+        if let Some(until) = self.retry_until.filter(|&until| until > now) {
+            let remaining = Duration::from_secs(until - now);
+            if remaining > MAX_RETRY_WAIT {
+                advice.skip_until = Some(until);
+            } else {
+                advice.wait = remaining;
+            }
+        }
+        let Some(throttled_at) = self.throttled_at else { return advice };
+        if now.saturating_sub(throttled_at) < THROTTLE_MEMORY.as_secs() {
+            let recovered = self.succeeded_at.is_some_and(|at| at > throttled_at);
+            advice.concurrency = Some(match self.concurrency {
+                Some(concurrency) if recovered => concurrency,
+                Some(concurrency) => (concurrency / 2).max(1),
+                None => 1,
+            });
+        }
+        advice
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "throttled_at": self.throttled_at,
+            "throttle_status": self.throttle_status,
+            "retry_until": self.retry_until,
+            "succeeded_at": self.succeeded_at,
+            "concurrency": self.concurrency,
+        })
+    }
+
+    fn from_json(value: &Value) -> Self {
+        Self {
+            throttled_at: value["throttled_at"].as_u64(),
+            throttle_status: value["throttle_status"].as_u64().map(|s| s as u16),
+            retry_until: value["retry_until"].as_u64(),
+            succeeded_at: value["succeeded_at"].as_u64(),
+            concurrency: value["concurrency"].as_u64().map(|c| c as usize),
+        }
+    }
+}
+
+/// Host reputations kept in a JSON file between runs.
+///
+/// Only hosts that have answered 429 or 403 are tracked, so the file stays small
+/// however many sites a run covers. It is rewritten as each site finishes.
+pub struct ReputationStore {
+    path: PathBuf,
+    hosts: Mutex<HashMap<String, HostReputation>>,
+}
+
+impl ReputationStore {
+    /// Load the store at `path`, or start an empty one if the file doesn't exist yet
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let mut hosts = HashMap::new();
+        match std::fs::read(path) {
+            Ok(bytes) => {
+                let value: Value = serde_json::from_slice(&bytes).map_err(std::io::Error::other)?;
+                let now = unix_now();
+                for (host, reputation) in value["hosts"].as_object().into_iter().flatten() {
+                    let reputation = HostReputation::from_json(reputation);
+                    let last_seen = reputation.throttled_at.max(reputation.retry_until).unwrap_or(0);
+                    if now.saturating_sub(last_seen) < FORGET_AFTER.as_secs() {
+                        hosts.insert(host.clone(), reputation);
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(Self { path: path.to_path_buf(), hosts: Mutex::new(hosts) })
+    }
+
+    pub fn get(&self, host: &str) -> Option<HostReputation> {
+        self.hosts.lock().unwrap().get(host).cloned()
+    }
+
+    pub fn advice(&self, host: &str) -> HostAdvice {
+        self.get(host).map(|reputation| reputation.advice(unix_now())).unwrap_or_default()
+    }
+
+    /// Note a 429 or 403 from `host`, with the Retry-After it sent if any
+    pub fn record_throttle(&self, host: &str, status: u16, headers: &HeaderMap) {
+        let now = Utc::now();
+        let mut hosts = self.hosts.lock().unwrap();
+        let reputation = hosts.entry(host.to_string()).or_default();
+        reputation.throttled_at = Some(now.timestamp() as u64);
+        reputation.throttle_status = Some(status);
+        if let Some(until) = retry_after(headers, now) {
+            reputation.retry_until = Some(until);
+        }
+    }
+
+    /// Note that a parse of `host` that began at `started_at` ran with `concurrency`
+    /// in-flight requests and was never throttled. Hosts not in the store are ignored.
+    pub fn record_success(&self, host: &str, concurrency: usize, started_at: u64) {
+        let mut hosts = self.hosts.lock().unwrap();
+        if let Some(reputation) = hosts.get_mut(host) {
+            if reputation.throttled_at.is_none_or(|at| at < started_at) {
+                reputation.succeeded_at = Some(unix_now());
+                reputation.concurrency = Some(concurrency);
+            }
+        }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        // Held while writing so concurrent saves don't race on the temporary file
+        let hosts = self.hosts.lock().unwrap();
+        let hosts: Map<String, Value> = hosts.iter().map(|(host, reputation)| (host.clone(), reputation.to_json())).collect();
+        let contents = serde_json::to_vec_pretty(&json!({ "hosts": hosts })).map_err(std::io::Error::other)?;
+        write_atomically(&self.path, &contents)
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// When a Retry-After header, in seconds or as an HTTP date, lets us back (Unix seconds)
+pub(crate) fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<u64> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    let until = match value.parse::<u64>() {
+        Ok(seconds) => now.timestamp() as u64 + seconds,
+        Err(_) => DateTime::parse_from_rfc2822(value).ok()?.timestamp().max(0) as u64,
+    };
+    Some(until)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "120".parse().unwrap());
+        assert_eq!(retry_after(&headers, now), Some(now.timestamp() as u64 + 120));
+        headers.insert(RETRY_AFTER, "Sat, 01 Jun 2024 13:00:00 GMT".parse().unwrap());
+        assert_eq!(retry_after(&headers, now), Some(now.timestamp() as u64 + 3600));
+        headers.insert(RETRY_AFTER, "soon".parse().unwrap());
+        assert_eq!(retry_after(&headers, now), None);
+    }
+
+    #[test]
+    fn test_advice() {
+        let now = 1_000_000;
+        let throttled = HostReputation { throttled_at: Some(now - 600), concurrency: Some(8), ..Default::default() };
+        assert_eq!(throttled.advice(now), HostAdvice { concurrency: Some(4), ..Default::default() });
+
+        let recovered = HostReputation { succeeded_at: Some(now - 60), ..throttled.clone() };
+        assert_eq!(recovered.advice(now).concurrency, Some(8));
+
+        let waiting = HostReputation { retry_until: Some(now + 30), ..throttled.clone() };
+        assert_eq!(waiting.advice(now).wait, Duration::from_secs(30));
+        let banned = HostReputation { retry_until: Some(now + 3600), ..throttled.clone() };
+        assert_eq!(banned.advice(now).skip_until, Some(now + 3600));
+
+        let yesterday = HostReputation { throttled_at: Some(now - THROTTLE_MEMORY.as_secs() - 1), ..Default::default() };
+        assert_eq!(yesterday.advice(now), HostAdvice::default());
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let path = std::env::temp_dir().join(format!("sitemap-reputation-{}.json", uuid::Uuid::new_v4()));
+        let store = ReputationStore::open(&path).unwrap();
+        store.record_throttle("slow.example", 429, &HeaderMap::new());
+        store.record_success("fine.example", 10, 0);
+        store.save().unwrap();
+
+        let reopened = ReputationStore::open(&path).unwrap();
+        assert_eq!(reopened.get("slow.example").unwrap().throttle_status, Some(429));
+        assert_eq!(reopened.advice("slow.example").concurrency, Some(1));
+        assert!(reopened.get("fine.example").is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
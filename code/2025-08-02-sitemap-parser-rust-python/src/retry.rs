@@ -0,0 +1,256 @@
+use chrono::Utc;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::Duration;
+use url::Url;
+
+use crate::reputation::retry_after;
+
+/// Default backoff before the first retry; each later retry waits twice as long
+pub const BASE_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Default longest backoff between two attempts, including one a Retry-After asked for
+pub const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Statuses retried by default: throttling and the 5xx errors that are usually transient
+pub const RETRY_STATUSES: &[u16] = &[429, 500, 502, 503, 504];
+
+/// When and how long to wait before trying a failed request again
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying
+    pub max_retries: usize,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Each backoff is randomly shortened or lengthened by up to this fraction of
+    /// itself, so sites that failed together don't all retry together
+    pub jitter: f64,
+    /// HTTP statuses worth another try; timeouts and connection errors always are
+    pub retry_statuses: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            base_backoff: BASE_BACKOFF,
+            max_backoff: MAX_BACKOFF,
+            jitter: 0.1,
+            retry_statuses: RETRY_STATUSES.to_vec(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Short name for what went wrong with an attempt, and whether trying again might help.
+    ///
+    /// Timeouts, connection failures, and `retry_statuses` are worth another try;
+    /// other statuses and anything that isn't a network error are not.
+    pub fn classify(&self, e: &(dyn std::error::Error + Send + Sync + 'static)) -> (String, bool) {
+        if let Some(e) = e.downcast_ref::<HttpStatusError>() {
+            let retryable = self.retry_statuses.contains(&e.status.as_u16());
+            return (format!("http {}", e.status.as_u16()), retryable);
+        }
+        match e.downcast_ref::<reqwest::Error>() {
+            Some(e) if e.is_timeout() => ("timeout".to_string(), true),
+            Some(e) if e.is_connect() => ("connect".to_string(), true),
+            Some(e) if e.is_request() || e.is_body() => ("network".to_string(), true),
+            _ => ("other".to_string(), false),
+        }
+    }
+
+    /// Backoff after the `retry`th failed attempt (0-based): exponential from
+    /// `base_backoff` with jitter, or longer if the server's Retry-After asked,
+    /// capped at `max_backoff`
+    pub fn backoff(&self, retry: usize, e: &(dyn std::error::Error + Send + Sync + 'static)) -> Duration {
+        let exponential = self.base_backoff.saturating_mul(1 << retry.min(16)).min(self.max_backoff);
+        let jittered = exponential.mul_f64((1.0 + self.jitter * (2.0 * unit_random() - 1.0)).max(0.0));
+        let asked = e.downcast_ref::<HttpStatusError>().and_then(|e| e.retry_after).unwrap_or_default();
+        jittered.max(asked).min(self.max_backoff)
+    }
+}
+
+/// A random number in [0, 1), from the randomly keyed hasher std already seeds
+fn unit_random() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// A non-success HTTP status, kept typed so retries can tell 503s from 404s
+#[derive(Debug)]
+pub struct HttpStatusError {
+    pub status: StatusCode,
+    pub url: String,
+    /// How long the response's Retry-After asked us to wait
+    pub retry_after: Option<Duration>,
+}
+
+impl HttpStatusError {
+    pub fn new(status: StatusCode, url: &str, headers: &HeaderMap) -> Self {
+        let now = Utc::now();
+        let retry_after = retry_after(headers, now).map(|until| Duration::from_secs(until.saturating_sub(now.timestamp() as u64)));
+        Self { status, url: url.to_string(), retry_after }
+    }
+}
+
+impl fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HTTP {} for {}", self.status, self.url)
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+/// One failed attempt at a request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryAttempt {
+    /// "timeout", "connect", "network", "http 503", ...
+    pub error: String,
+    /// Wait before the next attempt; zero after the last one
+    pub backoff: Duration,
+}
+
+/// Every attempt at a request whose first try failed and was retried
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryRecord {
+    pub url: String,
+    /// The failed attempts, in order
+    pub attempts: Vec<RetryAttempt>,
+    /// A later attempt succeeded
+    pub recovered: bool,
+}
+
+impl RetryRecord {
+    /// Requests made beyond the one a request normally costs
+    pub fn extra_requests(&self) -> usize {
+        self.attempts.len() + usize::from(self.recovered) - 1
+    }
+
+    fn host(&self) -> String {
+        Url::parse(&self.url).ok().and_then(|u| u.host_str().map(str::to_string)).unwrap_or_default()
+    }
+}
+
+/// Retry records for one site, filled in by its concurrent fetches
+#[derive(Default)]
+pub struct RetryLog(Mutex<Vec<RetryRecord>>);
+
+impl RetryLog {
+    pub fn push(&self, record: RetryRecord) {
+        self.0.lock().unwrap().push(record);
+    }
+
+    pub fn take(&self) -> Vec<RetryRecord> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+/// Retries summed up over a site's records
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RetryReport {
+    /// Requests that were retried at least once
+    pub retried: usize,
+    pub recovered: usize,
+    pub failed: usize,
+    /// Failed attempts per error kind
+    pub errors: BTreeMap<String, usize>,
+    pub total_backoff: Duration,
+    /// Hosts whose retried requests all recovered in the end
+    pub flaky_hosts: Vec<String>,
+    /// Hosts with a request that still failed after its retries
+    pub broken_hosts: Vec<String>,
+}
+
+impl RetryReport {
+    pub fn from_records(records: &[RetryRecord]) -> Self {
+        let mut report = Self { retried: records.len(), ..Default::default() };
+        let mut hosts: BTreeMap<String, bool> = BTreeMap::new();
+        for record in records {
+            if record.recovered {
+                report.recovered += 1;
+            } else {
+                report.failed += 1;
+            }
+            for attempt in &record.attempts {
+                *report.errors.entry(attempt.error.clone()).or_default() += 1;
+                report.total_backoff += attempt.backoff;
+            }
+            *hosts.entry(record.host()).or_insert(true) &= record.recovered;
+        }
+        for (host, recovered) in hosts {
+            if recovered {
+                report.flaky_hosts.push(host);
+            } else {
+                report.broken_hosts.push(host);
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::RETRY_AFTER;
+
+    #[test]
+    fn test_classify_and_backoff() {
+        let policy = RetryPolicy { jitter: 0.0, ..Default::default() };
+        let mut headers = HeaderMap::new();
+        let unavailable = HttpStatusError::new(StatusCode::SERVICE_UNAVAILABLE, "https://a.example/sitemap.xml", &headers);
+        assert_eq!(policy.classify(&unavailable), ("http 503".to_string(), true));
+        assert_eq!(unavailable.to_string(), "HTTP 503 Service Unavailable for https://a.example/sitemap.xml");
+        let missing = HttpStatusError::new(StatusCode::NOT_FOUND, "https://a.example/sitemap.xml", &headers);
+        assert!(!policy.classify(&missing).1);
+        let other: Box<dyn std::error::Error + Send + Sync> = "Invalid XML".into();
+        assert!(!policy.classify(other.as_ref()).1);
+
+        assert_eq!(policy.backoff(0, &unavailable), BASE_BACKOFF);
+        assert_eq!(policy.backoff(2, &unavailable), BASE_BACKOFF * 4);
+        assert_eq!(policy.backoff(20, &unavailable), MAX_BACKOFF);
+        headers.insert(RETRY_AFTER, "7".parse().unwrap());
+        let throttled = HttpStatusError::new(StatusCode::TOO_MANY_REQUESTS, "https://a.example/", &headers);
+        assert!(policy.backoff(0, &throttled) >= Duration::from_secs(6));
+    }
+
+    #[test]
+    fn test_policy_jitter_and_statuses() {
+        let headers = HeaderMap::new();
+        let forbidden = HttpStatusError::new(StatusCode::FORBIDDEN, "https://a.example/", &headers);
+        let policy = RetryPolicy { base_backoff: Duration::from_secs(1), jitter: 0.5, retry_statuses: vec![403], ..Default::default() };
+        assert!(policy.classify(&forbidden).1);
+        let unavailable = HttpStatusError::new(StatusCode::SERVICE_UNAVAILABLE, "https://a.example/", &headers);
+        assert!(!policy.classify(&unavailable).1);
+
+        let waits: Vec<Duration> = (0..50).map(|_| policy.backoff(1, &forbidden)).collect();
+        assert!(waits.iter().all(|w| (Duration::from_secs(1)..=Duration::from_secs(3)).contains(w)), "{:?}", waits);
+        assert!(waits.iter().any(|w| *w != waits[0]));
+        let capped = RetryPolicy { max_backoff: Duration::from_secs(2), ..policy };
+        assert!(capped.backoff(10, &forbidden) <= Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_report_separates_flaky_from_broken() {
+        let attempt = |error: &str| RetryAttempt { error: error.to_string(), backoff: Duration::from_secs(1) };
+        let records = vec![
+            RetryRecord { url: "https://flaky.example/a.xml".into(), attempts: vec![attempt("timeout")], recovered: true },
+            RetryRecord { url: "https://broken.example/a.xml".into(), attempts: vec![attempt("http 503"), attempt("http 503")], recovered: false },
+            RetryRecord { url: "https://broken.example/b.xml".into(), attempts: vec![attempt("connect")], recovered: true },
+        ];
+        let report = RetryReport::from_records(&records);
+        assert_eq!((report.retried, report.recovered, report.failed), (3, 2, 1));
+        assert_eq!(report.errors["http 503"], 2);
+        assert_eq!(report.total_backoff, Duration::from_secs(4));
+        assert_eq!(report.flaky_hosts, vec!["flaky.example"]);
+        assert_eq!(report.broken_hosts, vec!["broken.example"]);
+        assert_eq!(records[1].extra_requests(), 1);
+        assert_eq!(records[0].extra_requests(), 1);
+    }
+}
@@ -1,12 +1,21 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use url::Url;
 
+/// Longest Crawl-delay honored; some sites ask for a day between requests
+pub const MAX_CRAWL_DELAY: Duration = Duration::from_secs(60);
+
+/// How long a fetched robots.txt is reused, the most RFC 9309 allows
+pub const ROBOTS_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
 /// Parse robots.txt content and extract sitemap URLs
 pub fn parse_robots_txt(content: &str, base_url: &str) -> Vec<String> {
     let mut sitemaps = Vec::new();
     
     for line in content.lines() {
         let line = line.trim();
-        if line.to_lowercase().starts_with("sitemap:") {
+        if line.get(..8).is_some_and(|prefix| prefix.eq_ignore_ascii_case("sitemap:")) {
             if let Some(sitemap_url) = line.get(8..).map(|s| s.trim()) {
                 if !sitemap_url.is_empty() {
                     // Handle relative URLs
@@ -44,6 +53,251 @@ pub fn parse_robots_txt(content: &str, base_url: &str) -> Vec<String> {
     sitemaps
 }
 
+/// An Allow or Disallow line
+#[derive(Debug, Clone, PartialEq)]
+pub struct RobotsRule {
+    pub allow: bool,
+    /// Path pattern, where `*` matches any run of characters and a trailing `$` anchors the end
+    pub pattern: String,
+}
+
+/// Rules for the user agents named by consecutive `User-agent:` lines
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RobotsGroup {
+    /// Lowercased user agent tokens, `*` for everyone else
+    pub agents: Vec<String>,
+    pub rules: Vec<RobotsRule>,
+    pub crawl_delay: Option<Duration>,
+}
+
+/// A parsed robots.txt: its user-agent groups and `Sitemap:` URLs
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RobotsTxt {
+    pub groups: Vec<RobotsGroup>,
+    pub sitemaps: Vec<String>,
+}
+
+/// What one robots.txt asks of one user agent on the site's host
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RobotsPolicy {
+    host: Option<String>,
+    rules: Vec<RobotsRule>,
+    /// Capped at `MAX_CRAWL_DELAY`
+    pub crawl_delay: Option<Duration>,
+}
+
+impl RobotsTxt {
+    /// Parse user-agent groups as RFC 9309 describes them: a group is one or more
+    /// `User-agent:` lines followed by its rules, and unknown lines are ignored
+    pub fn parse(content: &str, base_url: &str) -> Self {
+        let mut groups: Vec<RobotsGroup> = Vec::new();
+        let mut in_agents = false;
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else { continue };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        groups.push(RobotsGroup::default());
+                        in_agents = true;
+                    }
+                    if let Some(group) = groups.last_mut() {
+                        group.agents.push(value.to_ascii_lowercase());
+                    }
+                }
+                key @ ("allow" | "disallow") => {
+                    in_agents = false;
+                    // An empty Disallow allows everything, which is the default anyway
+                    if let (Some(group), false) = (groups.last_mut(), value.is_empty()) {
+                        group.rules.push(RobotsRule { allow: key == "allow", pattern: value.to_string() });
+                    }
+                }
+                "crawl-delay" => {
+                    in_agents = false;
+                    let delay = value.parse::<f64>().ok().filter(|d| d.is_finite() && *d >= 0.0);
+                    if let (Some(group), Some(delay)) = (groups.last_mut(), delay) {
+                        group.crawl_delay = Some(Duration::from_secs_f64(delay.min(MAX_CRAWL_DELAY.as_secs_f64())));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self { groups, sitemaps: parse_robots_txt(content, base_url) }
+    }
+
+    /// Rules for `user_agent`, matched case-insensitively on its product token (the part
+    /// before any `/`). Every group naming it applies; failing that, every `*` group.
+    pub fn policy_for(&self, user_agent: &str, base_url: &str) -> RobotsPolicy {
+        let token = user_agent.split(['/', ' ']).next().unwrap_or("").to_ascii_lowercase();
+        let named: Vec<&RobotsGroup> = self.groups.iter().filter(|g| g.agents.contains(&token)).collect();
+        let groups = if named.is_empty() {
+            self.groups.iter().filter(|g| g.agents.iter().any(|a| a == "*")).collect()
+        } else {
+            named
+        };
+        RobotsPolicy {
+            host: Url::parse(base_url).ok().and_then(|u| u.host_str().map(str::to_string)),
+            rules: groups.iter().flat_map(|g| g.rules.iter().cloned()).collect(),
+            crawl_delay: groups.iter().filter_map(|g| g.crawl_delay).max(),
+        }
+    }
+}
+
+impl RobotsPolicy {
+    /// Whether `url` is on the host this robots.txt speaks for
+    pub fn applies_to(&self, url: &Url) -> bool {
+        url.host_str().is_some() && url.host_str() == self.host.as_deref()
+    }
+
+    /// Whether `url` may be crawled: the longest matching rule wins, Allow on a tie,
+    /// and no match allows it. URLs on other hosts follow their own robots.txt, so
+    /// they're always allowed here.
+    pub fn is_allowed(&self, url: &str) -> bool {
+        let Ok(url) = Url::parse(url) else { return true };
+        if !self.applies_to(&url) {
+            return true;
+        }
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        self.rules
+            .iter()
+            .filter(|rule| pattern_matches(&rule.pattern, &path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+}
+
+/// Parsed robots.txt files by origin (scheme, host, and port), each reused until it is
+/// `ttl` old
+#[derive(Debug)]
+pub struct RobotsCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, Arc<RobotsTxt>)>>,
+}
+
+impl Default for RobotsCache {
+    fn default() -> Self {
+        Self::new(ROBOTS_CACHE_TTL)
+    }
+}
+
+impl RobotsCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// The origin `url`'s robots.txt is cached under
+    pub fn origin(url: &str) -> Option<String> {
+        let origin = Url::parse(url).ok()?.origin();
+        origin.is_tuple().then(|| origin.ascii_serialization())
+    }
+
+    /// The robots.txt cached for `origin`, unless it has expired
+    pub fn get(&self, origin: &str) -> Option<Arc<RobotsTxt>> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(origin) {
+            Some((fetched, robots)) if fetched.elapsed() < self.ttl => Some(Arc::clone(robots)),
+            Some(_) => {
+                entries.remove(origin);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, origin: &str, robots: RobotsTxt) -> Arc<RobotsTxt> {
+        let robots = Arc::new(robots);
+        self.entries.lock().unwrap().insert(origin.to_string(), (Instant::now(), Arc::clone(&robots)));
+        robots
+    }
+}
+
+/// Whether a robots.txt path pattern matches `path` from its start
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = path.strip_prefix(first) else { return false };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+/// `Sitemap:` lines declaring each of `sitemap_urls`, ready to paste into robots.txt
+pub fn sitemap_directives(sitemap_urls: &[String]) -> String {
+    sitemap_urls.iter().map(|url| format!("Sitemap: {}\n", url)).collect()
+}
+
+/// Declare `sitemap_urls` in an existing robots.txt without touching any other line.
+///
+/// URLs the file already declares (relative or absolute) are skipped. New lines go
+/// right after the last existing `Sitemap:` line, or at the end of the file after a
+/// blank line. `Sitemap:` is independent of user-agent groups, so either spot applies
+/// to every crawler. The file's line ending style is kept.
+pub fn merge_sitemap_directives(robots_txt: &str, sitemap_urls: &[String]) -> String {
+    let base_url = sitemap_urls.first().map(String::as_str).unwrap_or("");
+    let declared = parse_robots_txt(robots_txt, base_url);
+    let mut missing: Vec<&String> = Vec::new();
+    for url in sitemap_urls {
+        if !declared.contains(url) && !missing.contains(&url) {
+            missing.push(url);
+        }
+    }
+    if missing.is_empty() {
+        return robots_txt.to_string();
+    }
+
+    let newline = if robots_txt.contains("\r\n") { "\r\n" } else { "\n" };
+    let lines: String = missing.iter().map(|url| format!("Sitemap: {}{}", url, newline)).collect();
+
+    // End of the last Sitemap line, newline included
+    let mut last_directive = None;
+    let mut offset = 0;
+    for line in robots_txt.split_inclusive('\n') {
+        offset += line.len();
+        if line.trim().get(..8).is_some_and(|prefix| prefix.eq_ignore_ascii_case("sitemap:")) {
+            last_directive = Some(offset);
+        }
+    }
+
+    let mut merged = String::with_capacity(robots_txt.len() + lines.len() + 2);
+    match last_directive {
+        Some(at) => {
+            merged.push_str(&robots_txt[..at]);
+            if !robots_txt[..at].ends_with('\n') {
+                merged.push_str(newline);
+            }
+            merged.push_str(&lines);
+            merged.push_str(&robots_txt[at..]);
+        }
+        None => {
+            merged.push_str(robots_txt);
+            if !robots_txt.trim().is_empty() {
+                if !robots_txt.ends_with('\n') {
+                    merged.push_str(newline);
+                }
+                merged.push_str(newline);
+            }
+            merged.push_str(&lines);
+        }
+    }
+    merged
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -86,4 +340,88 @@ Allow: /public/"#;
         
         assert!(sitemaps.is_empty());
     }
+
+    #[test]
+    fn test_parse_robots_txt_non_ascii_lines() {
+        // Lowercasing changes the byte length of some characters; none of this may panic
+        let content = "İİİİ: /x\nSitemap:é\nsİtemap: /nope.xml\nsitemap: /ok.xml";
+        let sitemaps = parse_robots_txt(content, "https://example.com");
+
+        assert_eq!(sitemaps, vec!["https://example.com/%C3%A9", "https://example.com/ok.xml"]);
+    }
+
+    #[test]
+    fn test_robots_groups_and_rules() {
+        let content = "User-agent: Googlebot\nDisallow: /\n\nUser-agent: SitemapParser\nUser-agent: other\nDisallow: /private\nAllow: /private/press\nDisallow: /*.pdf$\nCrawl-delay: 2.5\n\nUser-agent: *\nDisallow: /tmp # scratch\nCrawl-delay: 3600\nSitemap: /sitemap.xml\n";
+        let robots = RobotsTxt::parse(content, "https://example.com");
+        assert_eq!(robots.groups.len(), 3);
+        assert_eq!(robots.groups[1].agents, ["sitemapparser", "other"]);
+        assert_eq!(robots.sitemaps, ["https://example.com/sitemap.xml"]);
+
+        let ours = robots.policy_for("SitemapParser/1.0 (+https://timwhite.ninja)", "https://example.com");
+        assert_eq!(ours.crawl_delay, Some(Duration::from_millis(2500)));
+        assert!(!ours.is_allowed("https://example.com/private/a"));
+        assert!(ours.is_allowed("https://example.com/private/press/a"));
+        assert!(!ours.is_allowed("https://example.com/files/report.pdf"));
+        assert!(ours.is_allowed("https://example.com/files/report.pdf?v=2"));
+        assert!(ours.is_allowed("https://example.com/tmp/a"));
+        assert!(ours.is_allowed("https://cdn.example.com/private/a"));
+
+        let others = robots.policy_for("curl/8.0", "https://example.com");
+        assert_eq!(others.crawl_delay, Some(MAX_CRAWL_DELAY));
+        assert!(!others.is_allowed("https://example.com/tmp/a"));
+        assert!(others.is_allowed("https://example.com/private/a"));
+        assert_eq!(RobotsTxt::default().policy_for("x", "https://example.com"), RobotsPolicy { host: Some("example.com".to_string()), ..Default::default() });
+    }
+
+    #[test]
+    fn test_robots_cache_by_origin() {
+        assert_eq!(RobotsCache::origin("https://Example.com:443/a/b?c").as_deref(), Some("https://example.com"));
+        assert_eq!(RobotsCache::origin("http://example.com:8080/").as_deref(), Some("http://example.com:8080"));
+        assert_eq!(RobotsCache::origin("data:text/plain,hi"), None);
+
+        let cache = RobotsCache::default();
+        let robots = RobotsTxt::parse("User-agent: *\nDisallow: /private/\n", "https://example.com");
+        cache.insert("https://example.com", robots.clone());
+        assert_eq!(cache.get("https://example.com").as_deref(), Some(&robots));
+        assert!(cache.get("http://example.com").is_none());
+
+        let expired = RobotsCache::new(Duration::ZERO);
+        expired.insert("https://example.com", robots);
+        assert!(expired.get("https://example.com").is_none());
+    }
+
+    #[test]
+    fn test_pattern_matches() {
+        assert!(pattern_matches("/a*/c", "/a/b/c/d"));
+        assert!(pattern_matches("/", "/anything"));
+        assert!(!pattern_matches("/a*/c$", "/a/b/c/d"));
+        assert!(pattern_matches("/a*/c$", "/a/b/c"));
+        assert!(pattern_matches("*.php", "/x/index.php?q=1"));
+        assert!(!pattern_matches("/b", "/a/b"));
+    }
+
+    #[test]
+    fn test_merge_sitemap_directives() {
+        let robots = "User-agent: *\nDisallow: /private/\n\nSitemap: /sitemap.xml\n\n# trailing comment\n";
+        let urls = vec!["https://example.com/sitemap.xml".to_string(), "https://example.com/news.xml".to_string()];
+
+        let merged = merge_sitemap_directives(robots, &urls);
+        assert_eq!(
+            merged,
+            "User-agent: *\nDisallow: /private/\n\nSitemap: /sitemap.xml\nSitemap: https://example.com/news.xml\n\n# trailing comment\n"
+        );
+        // Already declared, so nothing changes
+        assert_eq!(merge_sitemap_directives(&merged, &urls), merged);
+    }
+
+    #[test]
+    fn test_merge_sitemap_directives_appends_with_crlf() {
+        let urls = vec!["https://example.com/sitemap.xml".to_string()];
+        assert_eq!(
+            merge_sitemap_directives("User-agent: *\r\nDisallow:", &urls),
+            "User-agent: *\r\nDisallow:\r\n\r\nSitemap: https://example.com/sitemap.xml\r\n"
+        );
+        assert_eq!(merge_sitemap_directives("", &urls), sitemap_directives(&urls));
+    }
 }
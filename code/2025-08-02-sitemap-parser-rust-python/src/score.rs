@@ -0,0 +1,168 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use std::collections::HashMap;
+use url::Url;
+
+use crate::sitemap::UrlMetadata;
+
+/// Priority assumed for URLs that don't give one, as the sitemap protocol specifies
+const DEFAULT_PRIORITY: f64 = 0.5;
+
+/// How much each signal counts toward a URL's score
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreWeights {
+    /// Multiplies `<priority>` (0.0–1.0)
+    pub priority: f64,
+    /// Multiplies lastmod recency: 1.0 for today, halving every `half_life_days`
+    pub recency: f64,
+    /// Multiplies 1/depth, so URLs listed closer to robots.txt rank higher
+    pub depth: f64,
+    /// Added for URLs under a path prefix like `/blog`; the longest matching prefix applies
+    pub sections: Vec<(String, f64)>,
+    pub half_life_days: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            priority: 1.0,
+            recency: 1.0,
+            depth: 0.5,
+            sections: Vec::new(),
+            half_life_days: 30.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredUrl {
+    pub url: String,
+    pub score: f64,
+    /// 0 for the best-ranked bucket
+    pub tier: usize,
+}
+
+/// Score one URL; missing metadata counts as default priority, no recency, and depth 1
+pub fn score_url(url: &str, metadata: Option<&UrlMetadata>, weights: &ScoreWeights, now: DateTime<Utc>) -> f64 {
+    let priority = metadata.and_then(|m| m.priority).unwrap_or(DEFAULT_PRIORITY);
+    let recency = metadata
+        .and_then(|m| m.lastmod.as_deref())
+        .and_then(parse_lastmod)
+        .map(|lastmod| {
+            // Future dates are clock skew or wishful thinking; treat them as today
+            let age_days = (now - lastmod).num_seconds().max(0) as f64 / 86_400.0;
+            0.5_f64.powf(age_days / weights.half_life_days)
+        })
+        .unwrap_or(0.0);
+    let depth = metadata.map_or(1, |m| m.depth.max(1)) as f64;
+
+    weights.priority * priority + weights.recency * recency + weights.depth / depth + section_weight(url, &weights.sections)
+}
+
+/// Score and sort `urls` best first (ties by URL), splitting the ranking into `tiers` equal buckets
+pub fn rank_urls<'a>(
+    urls: impl IntoIterator<Item = &'a String>,
+    metadata: &HashMap<String, UrlMetadata>,
+    weights: &ScoreWeights,
+    tiers: usize,
+    now: DateTime<Utc>,
+) -> Vec<ScoredUrl> {
+    let mut scored: Vec<ScoredUrl> = urls
+        .into_iter()
+        .map(|url| ScoredUrl {
+            url: url.clone(),
+            score: score_url(url, metadata.get(url), weights, now),
+            tier: 0,
+        })
+        .collect();
+    scored.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.url.cmp(&b.url)));
+
+    let total = scored.len();
+    let tiers = tiers.max(1);
+    for (rank, entry) in scored.iter_mut().enumerate() {
+        entry.tier = rank * tiers / total;
+    }
+    scored
+}
+
+/// Parse the W3C datetime forms sitemaps use: full timestamps, dates, and bare year-months
+pub fn parse_lastmod(lastmod: &str) -> Option<DateTime<Utc>> {
+    let lastmod = lastmod.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(lastmod) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(lastmod, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(&format!("{}-01", lastmod), "%Y-%m-%d"))
+        .ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+/// A cutoff such as `modified_since`: any lastmod form, or a timestamp without an
+/// offset, taken as UTC
+pub fn parse_cutoff(cutoff: &str) -> Option<DateTime<Utc>> {
+    parse_lastmod(cutoff)
+        .or_else(|| NaiveDateTime::parse_from_str(cutoff.trim(), "%Y-%m-%dT%H:%M:%S%.f").ok().map(|naive| naive.and_utc()))
+}
+
+fn section_weight(url: &str, sections: &[(String, f64)]) -> f64 {
+    let Ok(parsed) = Url::parse(url) else { return 0.0 };
+    let path = parsed.path();
+    sections
+        .iter()
+        .filter(|(prefix, _)| {
+            let prefix = prefix.trim_end_matches('/');
+            // `/blog` covers `/blog` and `/blog/...` but not `/blogger`
+            path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+        .max_by_key(|(prefix, _)| prefix.trim_end_matches('/').len())
+        .map_or(0.0, |(_, weight)| *weight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(lastmod: Option<&str>, priority: Option<f64>, depth: usize) -> UrlMetadata {
+        UrlMetadata { lastmod: lastmod.map(str::to_string), priority, depth, ..Default::default() }
+    }
+
+    #[test]
+    fn test_parse_lastmod_forms() {
+        let expected = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(0, 0, 0).unwrap().and_utc();
+        assert_eq!(parse_lastmod("2024-03-01"), Some(expected));
+        assert_eq!(parse_lastmod("2024-03"), Some(expected));
+        assert_eq!(parse_lastmod("2024-03-01T02:00:00+02:00"), Some(expected));
+        assert_eq!(parse_lastmod("last tuesday"), None);
+    }
+
+    #[test]
+    fn test_rank_urls_orders_and_tiers() {
+        let now = parse_lastmod("2024-06-01").unwrap();
+        let urls: Vec<String> = ["https://example.com/blog/new", "https://example.com/blog/old", "https://example.com/tag/x", "https://example.com/about"]
+            .iter()
+            .map(|u| u.to_string())
+            .collect();
+        let metadata = HashMap::from([
+            (urls[0].clone(), meta(Some("2024-06-01"), Some(0.8), 1)),
+            (urls[1].clone(), meta(Some("2023-06-01"), Some(0.8), 2)),
+            (urls[2].clone(), meta(Some("2024-06-01"), Some(0.8), 1)),
+        ]);
+        let weights = ScoreWeights {
+            sections: vec![("/tag".to_string(), -5.0), ("/blog/".to_string(), 0.1)],
+            ..Default::default()
+        };
+
+        let ranked = rank_urls(&urls, &metadata, &weights, 2, now);
+        let order: Vec<_> = ranked.iter().map(|s| s.url.as_str()).collect();
+        assert_eq!(order, vec![urls[0].as_str(), urls[1].as_str(), urls[3].as_str(), urls[2].as_str()]);
+        assert!((ranked[0].score - (0.8 + 1.0 + 0.5 + 0.1)).abs() < 1e-9);
+        assert_eq!(ranked.iter().map(|s| s.tier).collect::<Vec<_>>(), vec![0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn test_section_prefix_respects_segments() {
+        let sections = vec![("/blog".to_string(), 1.0), ("/blog/drafts".to_string(), -1.0)];
+        assert_eq!(section_weight("https://example.com/blog/post", &sections), 1.0);
+        assert_eq!(section_weight("https://example.com/blog/drafts/x", &sections), -1.0);
+        assert_eq!(section_weight("https://example.com/blogger", &sections), 0.0);
+    }
+}
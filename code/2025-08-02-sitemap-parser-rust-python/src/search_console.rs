@@ -0,0 +1,217 @@
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+const SITES_API: &str = "https://www.googleapis.com/webmasters/v3/sites/";
+const READONLY_SCOPE: &str = "https://www.googleapis.com/auth/webmasters.readonly";
+const JWT_BEARER_GRANT: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+
+/// Refresh a service-account token this long before Google says it expires
+const TOKEN_MARGIN: Duration = Duration::from_secs(60);
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// A sitemap as Search Console reports it for a property
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SubmittedSitemap {
+    pub path: String,
+    pub is_index: bool,
+    /// Submitted but not processed by Google yet, so its counts are not meaningful
+    pub is_pending: bool,
+    /// Web page URLs Google read from it; for an index, the sum over its sitemaps
+    pub submitted_urls: u64,
+    pub errors: u64,
+    pub warnings: u64,
+    pub last_downloaded: Option<String>,
+}
+
+/// How Search Console's count for a submitted sitemap compares with ours
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitemapCountComparison {
+    pub sitemap: SubmittedSitemap,
+    /// Distinct URLs we parsed from the sitemap (and its children), or `None` if it failed
+    pub parsed_urls: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl SitemapCountComparison {
+    /// Parsed minus reported; `None` when there is nothing to compare
+    pub fn difference(&self) -> Option<i64> {
+        if self.sitemap.is_pending {
+            return None;
+        }
+        self.parsed_urls.map(|parsed| parsed as i64 - self.sitemap.submitted_urls as i64)
+    }
+
+    pub fn is_discrepancy(&self) -> bool {
+        self.parsed_urls.is_none() || self.difference().is_some_and(|d| d != 0)
+    }
+}
+
+enum Credentials {
+    AccessToken(String),
+    ServiceAccount { client_email: String, key: EncodingKey, token_uri: String },
+}
+
+/// Lists the sitemaps submitted for a property through the Search Console API.
+///
+/// Authenticates either with an OAuth access token obtained elsewhere, or with a
+/// service account's JSON key; the service account must be added as a user of the
+/// property in Search Console. Only the read-only scope is requested.
+pub struct SearchConsoleSource {
+    credentials: Credentials,
+    /// Service-account token and when to stop using it
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl SearchConsoleSource {
+    pub fn with_access_token(token: String) -> Self {
+        Self { credentials: Credentials::AccessToken(token), token: Mutex::new(None) }
+    }
+
+    /// Load a service account key file as downloaded from the Google Cloud console
+    pub fn from_service_account_file(path: &Path) -> Result<Self, Error> {
+        let key = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        Self::from_service_account_json(&key)
+    }
+
+    pub fn from_service_account_json(key: &str) -> Result<Self, Error> {
+        let key: Value = serde_json::from_str(key)?;
+        let field = |name: &str| key[name].as_str().ok_or_else(|| format!("Service account key has no {}", name));
+        let credentials = Credentials::ServiceAccount {
+            client_email: field("client_email")?.to_string(),
+            key: EncodingKey::from_rsa_pem(field("private_key")?.as_bytes())?,
+            token_uri: key["token_uri"].as_str().unwrap_or("https://oauth2.googleapis.com/token").to_string(),
+        };
+        Ok(Self { credentials, token: Mutex::new(None) })
+    }
+
+    /// Sitemaps submitted for `property`, e.g. `https://example.com/` or `sc-domain:example.com`
+    pub async fn sitemaps(&self, client: &Client, property: &str) -> Result<Vec<SubmittedSitemap>, Error> {
+        let mut endpoint = Url::parse(SITES_API)?;
+        endpoint.path_segments_mut().map_err(|_| "Invalid Search Console endpoint")?.pop_if_empty().push(property).push("sitemaps");
+        let token = self.access_token(client).await?;
+        let resp = client.get(endpoint).bearer_auth(token).send().await?;
+        let status = resp.status();
+        let answer: Value = resp.json().await?;
+        if !status.is_success() {
+            let message = answer["error"]["message"].as_str().unwrap_or("no details");
+            return Err(format!("Search Console API returned HTTP {}: {}", status.as_u16(), message).into());
+        }
+        Ok(sitemaps_from_answer(&answer))
+    }
+
+    async fn access_token(&self, client: &Client) -> Result<String, Error> {
+        let (client_email, key, token_uri) = match &self.credentials {
+            Credentials::AccessToken(token) => return Ok(token.clone()),
+            Credentials::ServiceAccount { client_email, key, token_uri } => (client_email, key, token_uri),
+        };
+        if let Some((token, until)) = self.token.lock().unwrap().as_ref() {
+            if Instant::now() < *until {
+                return Ok(token.clone());
+            }
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let claims = json!({
+            "iss": client_email,
+            "scope": READONLY_SCOPE,
+            "aud": token_uri,
+            "iat": now,
+            "exp": now + 3600,
+        });
+        let assertion = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, key)?;
+        let answer: Value = client
+            .post(token_uri.as_str())
+            .form(&[("grant_type", JWT_BEARER_GRANT), ("assertion", assertion.as_str())])
+            .send()
+            .await?
+            .json()
+            .await?;
+        let token = answer["access_token"].as_str().ok_or_else(|| {
+            let reason = answer["error_description"].as_str().or(answer["error"].as_str()).unwrap_or("no token returned");
+            format!("Service account token request failed: {}", reason)
+        })?;
+        let lifetime = Duration::from_secs(answer["expires_in"].as_u64().unwrap_or(3600));
+        *self.token.lock().unwrap() = Some((token.to_string(), Instant::now() + lifetime.saturating_sub(TOKEN_MARGIN)));
+        Ok(token.to_string())
+    }
+}
+
+fn sitemaps_from_answer(answer: &Value) -> Vec<SubmittedSitemap> {
+    // The API encodes its int64 counts as strings
+    let count = |value: &Value| value.as_str().and_then(|s| s.parse().ok()).or(value.as_u64()).unwrap_or(0);
+    let Some(sitemaps) = answer["sitemap"].as_array() else { return Vec::new() };
+    sitemaps
+        .iter()
+        .filter_map(|sitemap| {
+            let submitted_urls = sitemap["contents"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|content| content["type"] == "web")
+                .map(|content| count(&content["submitted"]))
+                .sum();
+            Some(SubmittedSitemap {
+                path: sitemap["path"].as_str()?.to_string(),
+                is_index: sitemap["isSitemapsIndex"].as_bool().unwrap_or(false),
+                is_pending: sitemap["isPending"].as_bool().unwrap_or(false),
+                submitted_urls,
+                errors: count(&sitemap["errors"]),
+                warnings: count(&sitemap["warnings"]),
+                last_downloaded: sitemap["lastDownloaded"].as_str().map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sitemaps_from_answer() {
+        let answer = json!({
+            "sitemap": [
+                {
+                    "path": "https://example.com/sitemap_index.xml",
+                    "lastSubmitted": "2024-05-01T10:00:00.000Z",
+                    "isPending": false,
+                    "isSitemapsIndex": true,
+                    "lastDownloaded": "2024-06-02T08:30:00.000Z",
+                    "warnings": "2",
+                    "errors": "0",
+                    "contents": [
+                        { "type": "web", "submitted": "1520", "indexed": "0" },
+                        { "type": "image", "submitted": "300", "indexed": "0" }
+                    ]
+                },
+                { "path": "https://example.com/new.xml", "isPending": true }
+            ]
+        });
+        let sitemaps = sitemaps_from_answer(&answer);
+        assert_eq!(sitemaps[0].submitted_urls, 1520);
+        assert_eq!(sitemaps[0].warnings, 2);
+        assert!(sitemaps[0].is_index);
+        assert_eq!(sitemaps[1], SubmittedSitemap { path: "https://example.com/new.xml".to_string(), is_pending: true, ..Default::default() });
+        assert!(sitemaps_from_answer(&json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_comparison_discrepancies() {
+        let reported = SubmittedSitemap { submitted_urls: 100, ..Default::default() };
+        let compare = |sitemap: &SubmittedSitemap, parsed| SitemapCountComparison { sitemap: sitemap.clone(), parsed_urls: parsed, error: None };
+
+        assert!(!compare(&reported, Some(100)).is_discrepancy());
+        assert_eq!(compare(&reported, Some(90)).difference(), Some(-10));
+        assert!(compare(&reported, None).is_discrepancy());
+
+        let pending = SubmittedSitemap { is_pending: true, ..Default::default() };
+        assert_eq!(compare(&pending, Some(5)).difference(), None);
+        assert!(!compare(&pending, Some(5)).is_discrepancy());
+    }
+}
@@ -0,0 +1,181 @@
+use futures::future::{select_all, BoxFuture};
+use futures::FutureExt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// How long in-flight requests get to finish after Ctrl-C or interpreter shutdown
+pub const DEFAULT_GRACE: Duration = Duration::from_secs(10);
+
+/// A graceful stop shared by everything one parser runs.
+///
+/// Once requested, no new site or fetch starts, fetches already in flight get until
+/// the grace period ends to finish, and the sites involved come back flagged
+/// `interrupted` with whatever they gathered. A stop can't be taken back.
+///
+/// A stop can also be linked to others, such as its parser's and a caller's cancel
+/// token, and then counts as requested as soon as any of them is.
+pub struct Shutdown {
+    deadline: watch::Sender<Option<Instant>>,
+    active_sites: AtomicUsize,
+    parents: Vec<Arc<Shutdown>>,
+}
+
+/// Held while a site is being parsed, so a stop can wait for the parse to wind down
+pub struct ActiveSite<'a>(&'a Shutdown);
+
+impl Drop for ActiveSite<'_> {
+    fn drop(&mut self) {
+        self.0.count_site(false);
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::linked(Vec::new())
+    }
+
+    /// A stop that is also requested whenever one of `parents` is; its sites count as
+    /// active in them too, so waiting on a parent waits for them
+    pub fn linked(parents: Vec<Arc<Shutdown>>) -> Self {
+        Self { deadline: watch::Sender::new(None), active_sites: AtomicUsize::new(0), parents }
+    }
+
+    /// Stop starting work and give what's in flight `grace` to finish; later requests
+    /// don't move the deadline
+    pub fn request(&self, grace: Duration) {
+        self.deadline.send_if_modified(|deadline| {
+            let first = deadline.is_none();
+            if first {
+                *deadline = Some(Instant::now() + grace);
+            }
+            first
+        });
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.deadline.borrow().is_some() || self.parents.iter().any(|parent| parent.is_requested())
+    }
+
+    /// Resolves once a stop is requested, here or in a parent
+    pub fn requested(&self) -> BoxFuture<'_, ()> {
+        let mut deadline = self.deadline.subscribe();
+        let own = async move {
+            let _ = deadline.wait_for(Option::is_some).await;
+        };
+        self.first_of(own.boxed(), Shutdown::requested)
+    }
+
+    /// Resolves once a stop is requested and its grace period is over, here or in a parent
+    pub fn grace_expired(&self) -> BoxFuture<'_, ()> {
+        let mut deadline = self.deadline.subscribe();
+        let own = async move {
+            let Ok(Some(deadline)) = deadline.wait_for(Option::is_some).await.map(|d| *d) else { return };
+            tokio::time::sleep_until(deadline).await;
+        };
+        self.first_of(own.boxed(), Shutdown::grace_expired)
+    }
+
+    /// `own`, or whichever parent's `wait` finishes first
+    fn first_of<'a>(&'a self, own: BoxFuture<'a, ()>, wait: fn(&'a Shutdown) -> BoxFuture<'a, ()>) -> BoxFuture<'a, ()> {
+        if self.parents.is_empty() {
+            return own;
+        }
+        let mut waits: Vec<BoxFuture<'a, ()>> = self.parents.iter().map(|parent| wait(parent)).collect();
+        waits.push(own);
+        select_all(waits).map(|_| ()).boxed()
+    }
+
+    pub fn site(&self) -> ActiveSite<'_> {
+        self.count_site(true);
+        ActiveSite(self)
+    }
+
+    fn count_site(&self, started: bool) {
+        if started {
+            self.active_sites.fetch_add(1, Ordering::SeqCst);
+        } else {
+            self.active_sites.fetch_sub(1, Ordering::SeqCst);
+        }
+        for parent in &self.parents {
+            parent.count_site(started);
+        }
+    }
+
+    /// Block the calling thread until no site is being parsed, or `timeout` passes;
+    /// returns whether everything wound down
+    pub fn wait_idle(&self, timeout: Duration) -> bool {
+        let until = std::time::Instant::now() + timeout;
+        while self.active_sites.load(Ordering::SeqCst) > 0 {
+            if std::time::Instant::now() >= until {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_grace_period_starts_at_first_request() {
+        let shutdown = Shutdown::new();
+        assert!(!shutdown.is_requested());
+        let started = Instant::now();
+
+        let expired = shutdown.grace_expired();
+        tokio::pin!(expired);
+        assert!(futures::poll!(expired.as_mut()).is_pending());
+
+        shutdown.request(Duration::from_millis(50));
+        shutdown.request(Duration::from_secs(60));
+        assert!(shutdown.is_requested());
+        shutdown.requested().await;
+        expired.await;
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(50) && elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn test_linked_stop_follows_any_parent() {
+        let parser = Arc::new(Shutdown::new());
+        let token = Arc::new(Shutdown::new());
+        let call = Shutdown::linked(vec![Arc::clone(&parser), Arc::clone(&token)]);
+        let site = call.site();
+        assert!(!parser.wait_idle(Duration::ZERO));
+
+        let expired = call.grace_expired();
+        tokio::pin!(expired);
+        assert!(futures::poll!(expired.as_mut()).is_pending());
+        token.request(Duration::ZERO);
+        assert!(call.is_requested() && !parser.is_requested());
+        call.requested().await;
+        expired.await;
+
+        drop(site);
+        assert!(parser.wait_idle(Duration::ZERO));
+        let other = Shutdown::linked(vec![Arc::clone(&parser)]);
+        parser.request(Duration::from_secs(60));
+        other.requested().await;
+    }
+
+    #[test]
+    fn test_wait_idle() {
+        let shutdown = Shutdown::new();
+        let site = shutdown.site();
+        assert!(!shutdown.wait_idle(Duration::from_millis(30)));
+        drop(site);
+        assert!(shutdown.wait_idle(Duration::ZERO));
+    }
+}
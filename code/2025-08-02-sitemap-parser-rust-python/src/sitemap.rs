@@ -1,86 +1,494 @@
-use quick_xml::events::Event;
-use quick_xml::Reader;
-use std::collections::HashSet;
+use chrono::{DateTime, Months, TimeDelta, Utc};
+use log::warn;
+use quick_xml::escape::{resolve_xml_entity, unescape};
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::{NsReader, Reader};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use url::Url;
 
-#[derive(Debug, Default)]
+use crate::feed;
+use crate::filter::UrlFilter;
+use crate::json;
+use crate::media::{ImageEntry, MediaCapture, NewsEntry, VideoEntry};
+use crate::quirks::{self, BOM};
+use crate::score::parse_lastmod;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct SitemapParseResult {
     pub urls: HashSet<String>,
     pub nested_sitemaps: Vec<String>,
+    /// `<lastmod>` / `<changefreq>` / `<priority>` for the URLs that gave any
+    pub metadata: HashMap<String, UrlMetadata>,
+    /// Hostile or suspicious constructs that were ignored, such as DTDs and entity declarations
+    pub security_warnings: Vec<String>,
+    /// URLs and nested sitemaps left out because their lastmod is before `ParseOptions::modified_since`
+    pub unmodified_urls: usize,
+    pub unmodified_sitemaps: usize,
+}
+
+/// Optional fields of a `<url>` entry, plus where the parser found it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UrlMetadata {
+    /// W3C datetime exactly as written, e.g. `2024-01-01` or `2024-01-01T12:00:00+00:00`
+    pub lastmod: Option<String>,
+    /// One of the protocol's values (`always`, `hourly`, ... `never`), lowercased
+    pub changefreq: Option<String>,
+    pub priority: Option<f64>,
+    /// Sitemap nesting level the URL was listed at (1 = a sitemap from robots.txt);
+    /// filled in by the site parser, 0 straight out of `parse_sitemap_xml`
+    pub depth: usize,
+    /// Sitemap the URL was listed in; filled in by the site parser like `depth`
+    pub source_sitemap: Option<String>,
+    /// Unrecognized children of `<url>` as raw key/value pairs in document order, keyed
+    /// like `image:image/image:loc` or `xhtml:link@hreflang`; only collected with
+    /// `ParseOptions::extensions`
+    pub extensions: Vec<(String, String)>,
+    /// Google image, video, and news extension entries; only collected with
+    /// `ParseOptions::media`
+    pub images: Vec<ImageEntry>,
+    pub videos: Vec<VideoEntry>,
+    pub news: Option<NewsEntry>,
+    /// `(hreflang, href)` of each `<xhtml:link rel="alternate">`, as written; only
+    /// collected with `ParseOptions::alternates`
+    pub alternates: Vec<(String, String)>,
+}
+
+impl UrlMetadata {
+    /// Whether the entry gave anything worth keeping
+    pub fn has_fields(&self) -> bool {
+        self.lastmod.is_some() || self.changefreq.is_some() || self.priority.is_some() || !self.extensions.is_empty()
+            || !self.images.is_empty() || !self.videos.is_empty() || self.news.is_some()
+            || !self.alternates.is_empty()
+    }
+}
+
+/// Frequencies the sitemap protocol allows for `<changefreq>`
+const CHANGEFREQS: &[&str] = &["always", "hourly", "daily", "weekly", "monthly", "yearly", "never"];
+
+/// A `<changefreq>` value lowercased, or `None` if the protocol doesn't define it
+pub(crate) fn parse_changefreq(text: &str) -> Option<String> {
+    let text = text.trim().to_ascii_lowercase();
+    CHANGEFREQS.contains(&text.as_str()).then_some(text)
+}
+
+/// How to parse a sitemap beyond its URLs and nested sitemaps
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Tolerate common real-world breakage; see [`parse_sitemap_xml_with`]
+    pub quirks: bool,
+    /// Keep unrecognized `<url>` children in `UrlMetadata::extensions`
+    pub extensions: bool,
+    /// Collect `<image:image>`, `<video:video>`, and `<news:news>` into `UrlMetadata`
+    pub media: bool,
+    /// Collect `<xhtml:link rel="alternate" hreflang="..">` into `UrlMetadata::alternates`
+    pub alternates: bool,
+    /// Drop URLs and nested sitemaps it rejects as they are read
+    pub filter: Option<Arc<UrlFilter>>,
+    /// Drop URLs, and skip nested sitemaps, whose lastmod says they haven't changed since
+    pub modified_since: Option<DateTime<Utc>>,
+}
+
+impl ParseOptions {
+    pub(crate) fn keeps_url(&self, url: &str) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.keeps_url(url))
+    }
+
+    pub(crate) fn follows_sitemap(&self, sitemap_url: &str) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.follows_sitemap(sitemap_url))
+    }
+
+    /// Whether `lastmod` is before `modified_since`. Entries without a readable lastmod
+    /// count as modified, and a bare date or year-month counts as modified if any of
+    /// that day or month falls on or after the cutoff.
+    pub(crate) fn is_unmodified(&self, lastmod: Option<&str>) -> bool {
+        let (Some(since), Some(lastmod)) = (self.modified_since, lastmod) else { return false };
+        let Some(start) = parse_lastmod(lastmod) else { return false };
+        let end = match lastmod.trim().len() {
+            7 => start.checked_add_months(Months::new(1)),
+            10 => start.checked_add_signed(TimeDelta::days(1)),
+            _ => return start < since,
+        };
+        end.is_some_and(|end| end <= since)
+    }
+}
+
+const DOCTYPE_IGNORED: &str = "DOCTYPE declaration ignored; sitemaps never need a DTD";
+
+/// Ceilings on a single document, so XML bombs and pathologically nested input fail
+/// fast instead of grinding through it. Real sitemaps nest four or five elements deep
+/// and a full 50,000-URL one is well under a million events.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct XmlLimits {
+    max_depth: usize,
+    max_events: usize,
+    max_entity_refs: usize,
+}
+
+impl Default for XmlLimits {
+    fn default() -> Self {
+        Self { max_depth: 32, max_events: 5_000_000, max_entity_refs: 1_000_000 }
+    }
+}
+
+/// How much of its `XmlLimits` a document has used so far
+#[derive(Debug, Default)]
+pub(crate) struct XmlUsage {
+    depth: usize,
+    events: usize,
+    entity_refs: usize,
+}
+
+impl XmlUsage {
+    pub(crate) fn track(&mut self, event: &Event, limits: &XmlLimits) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.events += 1;
+        match event {
+            Event::Start(_) => self.depth += 1,
+            // Quirks mode accepts end tags that close nothing
+            Event::End(_) => self.depth = self.depth.saturating_sub(1),
+            Event::GeneralRef(_) => self.entity_refs += 1,
+            _ => {}
+        }
+        if self.depth > limits.max_depth {
+            Err(format!("Sitemap XML nests elements more than {} deep; refusing to parse it", limits.max_depth).into())
+        } else if self.events > limits.max_events {
+            Err(format!("Sitemap XML has more than {} parse events; refusing to parse it", limits.max_events).into())
+        } else if self.entity_refs > limits.max_entity_refs {
+            Err(format!("Sitemap XML has more than {} entity references; refusing to parse it", limits.max_entity_refs).into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A `<url>` or `<sitemap>` child other than `<loc>` whose text we keep
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MetaField {
+    Lastmod,
+    Changefreq,
+    Priority,
+    SitemapLastmod,
 }
 
 /// Parse sitemap XML content and extract URLs and nested sitemap references
 pub fn parse_sitemap_xml(content: &str, base_url: &str) -> Result<SitemapParseResult, Box<dyn std::error::Error + Send + Sync>> {
+    parse_sitemap_xml_with(content, base_url, false)
+}
+
+/// Parse sitemap XML content, optionally tolerating common real-world breakage.
+///
+/// A document rooted at `<rss>` or `<feed>`, such as a Google Merchant product
+/// feed, is read as a feed instead: each item's product page link becomes a URL.
+/// A body starting with `[` or `{` is a JSON sitemap, whatever its content type;
+/// see `json::parse_json_sitemap` for the shapes understood. A body that isn't
+/// markup and starts with an http(s) URL is a text sitemap, one URL per line.
+///
+/// With `quirks` set the document is repaired first (stray BOMs, HTML wrappers,
+/// HTML-escaped sitemaps), mismatched end tags are accepted, a `<loc>` left
+/// unclosed ends at the next tag, and entities in `<loc>` values are decoded,
+/// including double-encoded ones like `&amp;amp;`.
+///
+/// Sitemaps never need a DTD, so any DOCTYPE is cut out before parsing and noted in
+/// `security_warnings`. Entities it might have declared are never expanded: a
+/// `<loc>` that uses anything but the five predefined XML entities or a character
+/// reference is dropped, which defeats XXE and entity-expansion payloads.
+///
+/// Documents nested more than 32 elements deep, or with more than five million parse
+/// events or a million entity references, are rejected with an error as soon as the
+/// limit is crossed, without falling back to the lenient scanner.
+pub fn parse_sitemap_xml_with(content: &str, base_url: &str, quirks: bool) -> Result<SitemapParseResult, Box<dyn std::error::Error + Send + Sync>> {
+    parse_sitemap_xml_with_options(content, base_url, &ParseOptions { quirks, ..Default::default() })
+}
+
+/// Parse sitemap XML content as [`parse_sitemap_xml_with`] does, with every option spelled out
+pub fn parse_sitemap_xml_with_options(
+    content: &str,
+    base_url: &str,
+    options: &ParseOptions,
+) -> Result<SitemapParseResult, Box<dyn std::error::Error + Send + Sync>> {
+    parse_with_limits(content, base_url, None, options, &XmlLimits::default())
+}
+
+/// Parse a sitemap body of any supported format, using the response's `Content-Type`
+/// where sniffing the body can't tell; see [`detect_format`]
+pub fn parse_sitemap_body(
+    content: &str,
+    base_url: &str,
+    content_type: Option<&str>,
+    options: &ParseOptions,
+) -> Result<SitemapParseResult, Box<dyn std::error::Error + Send + Sync>> {
+    parse_with_limits(content, base_url, content_type, options, &XmlLimits::default())
+}
+
+/// What kind of document a sitemap body is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SitemapFormat {
+    /// A `<urlset>` or `<sitemapindex>`, or anything unrecognized, which gets the XML parser's fallbacks
+    Xml,
+    /// RSS 2.0 or Atom
+    Feed,
+    Json,
+    /// One URL per line, as the sitemap protocol allows
+    Text,
+}
+
+/// Tell a sitemap body's format from its content, falling back on `content_type`.
+///
+/// The body wins because servers routinely label XML `text/plain` or
+/// `application/octet-stream`: a leading `[` or `{` is JSON, an `<rss>` or `<feed>`
+/// root is a feed, and any other markup is XML. A body that isn't markup is a text
+/// sitemap if its first line is an http(s) URL or it was served as `text/plain`.
+pub fn detect_format(content: &str, content_type: Option<&str>) -> SitemapFormat {
+    if json::is_json_sitemap(content) {
+        return SitemapFormat::Json;
+    }
+    let body = content.trim_start_matches(|c: char| c == BOM || c.is_whitespace());
+    if body.starts_with('<') {
+        return match root_local_name(body) {
+            Some(root) if feed::is_feed_root(&root) => SitemapFormat::Feed,
+            _ => SitemapFormat::Xml,
+        };
+    }
+    let mime = content_type
+        .and_then(|value| value.split(';').next())
+        .map(|mime| mime.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+    let first_line = body.lines().next().unwrap_or_default().trim();
+    if is_text_sitemap_url(first_line) || (mime == "text/plain" && !body.starts_with("&lt;")) {
+        SitemapFormat::Text
+    } else {
+        SitemapFormat::Xml
+    }
+}
+
+/// Whether a text sitemap line is a URL the protocol allows: absolute http or https
+fn is_text_sitemap_url(line: &str) -> bool {
+    let scheme = line.get(..8).unwrap_or(line).to_ascii_lowercase();
+    (scheme.starts_with("http://") || scheme.starts_with("https://")) && Url::parse(line).is_ok()
+}
+
+/// Parse a text sitemap: one absolute URL per line. Blank lines, and lines that
+/// aren't http(s) URLs such as `#` comments, are skipped. Text sitemaps can't nest
+/// or carry metadata, so only `urls` is filled in.
+pub(crate) fn parse_text_sitemap(content: &str, options: &ParseOptions, result: &mut SitemapParseResult) {
+    let content = content.trim_start_matches(BOM);
+    let urls = content.lines().map(str::trim).filter(|line| is_text_sitemap_url(line) && options.keeps_url(line));
+    result.urls.extend(urls.map(str::to_string));
+}
+
+fn parse_with_limits(
+    content: &str,
+    base_url: &str,
+    content_type: Option<&str>,
+    options: &ParseOptions,
+    limits: &XmlLimits,
+) -> Result<SitemapParseResult, Box<dyn std::error::Error + Send + Sync>> {
+    let quirks = options.quirks;
     let mut result = SitemapParseResult::default();
-    let mut reader = Reader::from_str(content);
-    reader.config_mut().trim_text(true);
+    match detect_format(content, content_type) {
+        SitemapFormat::Json => {
+            json::parse_json_sitemap(content, base_url, options, &mut result)?;
+            return Ok(result);
+        }
+        SitemapFormat::Text => {
+            parse_text_sitemap(content, options, &mut result);
+            return Ok(result);
+        }
+        SitemapFormat::Xml | SitemapFormat::Feed => {}
+    }
+    let stripped = strip_doctype(content, &mut result.security_warnings);
+    let content = if quirks { quirks::repair(&stripped) } else { Cow::Borrowed(stripped.as_ref()) };
+    // Leading whitespace or a BOM ahead of the XML declaration would make it "misplaced"
+    let content = content.trim_start_matches(|c: char| c == BOM || c.is_whitespace());
+
+    if root_local_name(content).is_some_and(|root| feed::is_feed_root(&root)) {
+        feed::parse_product_feed(content, base_url, options, limits, &mut result)?;
+        return Ok(result);
+    }
+
+    let mut reader = NsReader::from_str(content);
+    // Untrimmed, so text split by an entity reference keeps the spaces around it;
+    // every consumer trims the text it collects
+    reader.config_mut().trim_text(false);
+    if quirks {
+        let config = reader.config_mut();
+        config.check_end_names = false;
+        config.allow_unmatched_ends = true;
+        config.allow_dangling_amp = true;
+    }
     
     let mut buf = Vec::new();
+    let mut protocol_ns = ProtocolNamespace::default();
+    let mut root_seen = false;
     let mut in_url = false;
     let mut in_sitemap = false;
-    let mut in_image = false;  // Track if we're inside an image element
+    // Elements open inside the <url> or <sitemap> being read that the protocol doesn't
+    // define, like <image:image>; a <loc> or <lastmod> within one belongs to it
+    let mut extension_depth = 0usize;
     let mut in_loc = false;
+    // The <loc> being read used an entity only a DTD could define; it's discarded
+    let mut loc_rejected = false;
     let mut current_text = String::new();
+    // The <url> being read: its page URL and fields, which may come in any order
+    let mut page_loc: Option<String> = None;
+    let mut page_meta = UrlMetadata::default();
+    // URLs found before the <url> being read, to tell whether it added its page URL or repeated one
+    let mut urls_before = 0;
+    // The <sitemap> being read: where its nested sitemap would go, and its lastmod
+    let mut sitemap_index = 0;
+    let mut sitemap_lastmod: Option<String> = None;
+    let mut meta_field: Option<MetaField> = None;
+    let mut meta_text = String::new();
+    let mut extensions = ExtensionCapture::default();
+    let mut media = MediaCapture::default();
+    let mut usage = XmlUsage::default();
 
     loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                let name_bytes = e.local_name();
-                if let Ok(name_str) = std::str::from_utf8(name_bytes.as_ref()) {
-                    match name_str {
-                        "url" => in_url = true,
-                        "sitemap" => in_sitemap = true,
-                        "image" => in_image = true,  // Track image elements
-                        "loc" => {
+        // Each event along with whether it's a tag the protocol defines: one of its names,
+        // in the namespace the root element is in
+        let event = reader.read_resolved_event_into(&mut buf).map(|(ns, event)| {
+            let protocol = match &event {
+                Event::Start(e) | Event::Empty(e) => {
+                    if !root_seen && matches!(e.local_name().as_ref(), b"urlset" | b"sitemapindex") {
+                        root_seen = true;
+                        protocol_ns = ProtocolNamespace::of(&ns);
+                    }
+                    protocol_ns.contains(&ns) && is_protocol_element(e.local_name().as_ref())
+                }
+                Event::End(e) => protocol_ns.contains(&ns) && is_protocol_element(e.local_name().as_ref()),
+                _ => false,
+            };
+            (protocol, event)
+        });
+        if let Ok((protocol, event)) = &event {
+            usage.track(event, limits)?;
+            if options.extensions && in_url {
+                extensions.observe(event, *protocol);
+            }
+            if options.media && in_url {
+                media.observe(event);
+            }
+            if let (true, true, Event::Start(e) | Event::Empty(e)) = (options.alternates, in_url, event) {
+                page_meta.alternates.extend(alternate_link(e));
+            }
+        }
+        match event {
+            Ok((protocol, Event::Start(ref e))) => {
+                if quirks && in_loc {
+                    // Unclosed <loc>: it ends where the next element starts
+                    page_loc = push_loc(&mut result, &current_text, in_url, in_sitemap, base_url, options)?.or(page_loc);
+                    in_loc = false;
+                    current_text.clear();
+                }
+                if (in_url || in_sitemap) && (extension_depth > 0 || !protocol) {
+                    extension_depth += 1;
+                } else if protocol {
+                    match e.local_name().as_ref() {
+                        b"url" => {
+                            in_url = true;
+                            page_loc = None;
+                            page_meta = UrlMetadata::default();
+                            urls_before = result.urls.len();
+                            extensions = ExtensionCapture::default();
+                            media = MediaCapture::default();
+                        }
+                        b"sitemap" => {
+                            in_sitemap = true;
+                            sitemap_index = result.nested_sitemaps.len();
+                            sitemap_lastmod = None;
+                        }
+                        b"loc" => {
                             in_loc = true;
+                            loc_rejected = false;
                             current_text.clear();
                         }
+                        b"lastmod" if in_url => meta_field = Some(MetaField::Lastmod),
+                        b"changefreq" if in_url => meta_field = Some(MetaField::Changefreq),
+                        b"priority" if in_url => meta_field = Some(MetaField::Priority),
+                        b"lastmod" if in_sitemap => meta_field = Some(MetaField::SitemapLastmod),
                         _ => {}
                     }
+                    if meta_field.is_some() {
+                        meta_text.clear();
+                    }
                 }
             }
-            Ok(Event::End(ref e)) => {
-                let name_bytes = e.local_name();
-                if let Ok(name_str) = std::str::from_utf8(name_bytes.as_ref()) {
-                    match name_str {
-                        "url" => in_url = false,
-                        "sitemap" => in_sitemap = false,
-                        "image" => in_image = false,  // Reset image tracking
-                        "loc" => {
-                            if in_loc {
-                                let url = current_text.trim();
-                                if !url.is_empty() {
-                                    if in_sitemap {
-                                        // This is a nested sitemap reference
-                                        let absolute_url = make_absolute_url(url, base_url)?;
-                                        result.nested_sitemaps.push(absolute_url);
-                                    } else if in_url && !in_image {
-                                        // This is a regular URL, but NOT an image URL
-                                        // Only include URLs that are directly in <url> elements, not in <image> elements
-                                        result.urls.insert(url.to_string());
-                                    }
-                                    // Skip URLs that are in image elements (in_image = true)
+            Ok((protocol, Event::End(ref e))) => {
+                let local_name = e.local_name();
+                if quirks && in_loc && !(protocol && local_name.as_ref() == b"loc") {
+                    // Unclosed <loc>: it ends where its parent does
+                    page_loc = push_loc(&mut result, &current_text, in_url, in_sitemap, base_url, options)?.or(page_loc);
+                    in_loc = false;
+                    current_text.clear();
+                }
+                if extension_depth > 0 {
+                    extension_depth -= 1;
+                } else if protocol {
+                    match local_name.as_ref() {
+                        b"url" => {
+                            in_url = false;
+                            page_meta.extensions = std::mem::take(&mut extensions.fields);
+                            (page_meta.images, page_meta.videos, page_meta.news) = std::mem::take(&mut media).finish();
+                            if options.is_unmodified(page_meta.lastmod.as_deref()) {
+                                // Another entry listing the same URL may have kept it
+                                if let (Some(loc), true) = (page_loc.take(), result.urls.len() > urls_before) {
+                                    result.urls.remove(&loc);
+                                    result.unmodified_urls += 1;
                                 }
-                                in_loc = false;
-                                current_text.clear();
+                            } else if let (Some(loc), true) = (page_loc.take(), page_meta.has_fields()) {
+                                result.metadata.insert(loc, std::mem::take(&mut page_meta));
+                            }
+                        }
+                        b"sitemap" => {
+                            in_sitemap = false;
+                            if options.is_unmodified(sitemap_lastmod.as_deref()) && result.nested_sitemaps.len() > sitemap_index {
+                                result.nested_sitemaps.truncate(sitemap_index);
+                                result.unmodified_sitemaps += 1;
                             }
                         }
+                        b"loc" if in_loc => {
+                            page_loc = push_loc(&mut result, &current_text, in_url, in_sitemap, base_url, options)?.or(page_loc);
+                            in_loc = false;
+                            current_text.clear();
+                        }
+                        b"lastmod" | b"changefreq" | b"priority" => meta_field = None,
                         _ => {}
                     }
                 }
             }
-            Ok(Event::Text(e)) => {
-                if in_loc {
-                    // Convert to string directly without unescaping for now
-                    current_text.push_str(&String::from_utf8_lossy(&e));
+            Ok((_, Event::Text(_) | Event::GeneralRef(_) | Event::CData(_))) if in_loc && loc_rejected => {}
+            Ok((_, ref text @ (Event::Text(_) | Event::GeneralRef(_) | Event::CData(_)))) if in_loc => {
+                if let Err(entity) = push_text(&mut current_text, text) {
+                    reject_entity(&entity, &mut result.security_warnings);
+                    loc_rejected = true;
+                    current_text.clear();
                 }
             }
-            Ok(Event::CData(e)) => {
-                if in_loc {
-                    current_text.push_str(&String::from_utf8_lossy(&e));
+            Ok((_, ref text @ (Event::Text(_) | Event::GeneralRef(_) | Event::CData(_)))) if meta_field.is_some() => {
+                // Text split by a reference is set again as each piece arrives, so a field
+                // quirks leaves unclosed still gets a value
+                let _ = push_text(&mut meta_text, text);
+                let text = meta_text.trim();
+                match meta_field {
+                    Some(MetaField::Lastmod) => page_meta.lastmod = Some(text.to_string()),
+                    Some(MetaField::Changefreq) => page_meta.changefreq = parse_changefreq(text),
+                    Some(MetaField::Priority) => page_meta.priority = text.parse().ok().filter(|p: &f64| (0.0..=1.0).contains(p)),
+                    Some(MetaField::SitemapLastmod) => sitemap_lastmod = Some(text.to_string()),
+                    None => {}
                 }
             }
-            Ok(Event::Eof) => break,
+            // Stylesheets, generator comments and the declaration carry nothing we keep, but
+            // must not end the document the way an error does and hand it to the fallback
+            Ok((_, Event::Decl(_) | Event::PI(_) | Event::Comment(_))) => {}
+            // Only reachable when quirks repairs uncovered one, e.g. in an HTML-escaped sitemap
+            Ok((_, Event::DocType(_))) if !result.security_warnings.iter().any(|w| w.starts_with(DOCTYPE_IGNORED)) => {
+                result.security_warnings.push(DOCTYPE_IGNORED.to_string());
+            }
+            Ok((_, Event::Eof)) => break,
             Err(e) => {
                 // Try to handle malformed XML gracefully
                 eprintln!("XML parsing error: {}, continuing...", e);
@@ -91,16 +499,221 @@ pub fn parse_sitemap_xml(content: &str, base_url: &str) -> Result<SitemapParseRe
         buf.clear();
     }
 
-    // Fallback: if we couldn't parse as structured XML, try a simpler approach
-    if result.urls.is_empty() && result.nested_sitemaps.is_empty() {
-        parse_fallback(content, base_url, &mut result)?;
+    // Fallback: if we couldn't parse as structured XML, try a simpler approach. Entries
+    // left out for being unmodified mean the structure parsed, so there's nothing to recover
+    let skipped_unmodified = result.unmodified_urls + result.unmodified_sitemaps > 0;
+    if result.urls.is_empty() && result.nested_sitemaps.is_empty() && !skipped_unmodified {
+        parse_fallback(content, base_url, options, &mut result)?;
     }
 
     Ok(result)
 }
 
+/// Local name of the document's root element, e.g. `urlset` or `rss`
+fn root_local_name(content: &str) -> Option<Vec<u8>> {
+    let mut reader = Reader::from_str(content);
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(e) | Event::Empty(e) => return Some(e.local_name().as_ref().to_vec()),
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Children of `<url>` the sitemap protocol defines; anything else is an extension
+fn is_url_field(local_name: &[u8]) -> bool {
+    matches!(local_name, b"url" | b"loc" | b"lastmod" | b"changefreq" | b"priority")
+}
+
+/// Local names of the elements the sitemap protocol defines
+fn is_protocol_element(local_name: &[u8]) -> bool {
+    is_url_field(local_name) || matches!(local_name, b"urlset" | b"sitemapindex" | b"sitemap")
+}
+
+/// The namespace a document's protocol elements are in: whatever its `<urlset>` or
+/// `<sitemapindex>` is in, so the sitemaps.org namespace, older or misspelled ones, and
+/// none at all all work, while an extension's `<video:loc>` or `<foo:url>` never counts
+#[derive(Debug, Default)]
+enum ProtocolNamespace {
+    #[default]
+    None,
+    Uri(Vec<u8>),
+    /// A prefix the document uses without declaring it
+    Undeclared(Vec<u8>),
+}
+
+impl ProtocolNamespace {
+    fn of(ns: &ResolveResult) -> Self {
+        match ns {
+            ResolveResult::Unbound => Self::None,
+            ResolveResult::Bound(Namespace(uri)) => Self::Uri(uri.to_vec()),
+            ResolveResult::Unknown(prefix) => Self::Undeclared(prefix.clone()),
+        }
+    }
+
+    fn contains(&self, ns: &ResolveResult) -> bool {
+        match (self, ns) {
+            (Self::None, ResolveResult::Unbound) => true,
+            (Self::Uri(uri), ResolveResult::Bound(Namespace(ns))) => uri == ns,
+            (Self::Undeclared(prefix), ResolveResult::Unknown(ns)) => prefix == ns,
+            _ => false,
+        }
+    }
+}
+
+/// Append the characters of a text, CDATA, or entity reference event to `text`, with
+/// references resolved and CDATA taken literally. An entity only a DTD could define is
+/// left out and returned as the error.
+pub(crate) fn push_text(text: &mut String, event: &Event) -> Result<(), String> {
+    match event {
+        // The reader reports entity references separately, so text has none left to unescape
+        Event::Text(e) => text.push_str(&String::from_utf8_lossy(e)),
+        Event::CData(e) => text.push_str(&String::from_utf8_lossy(e)),
+        Event::GeneralRef(e) => {
+            let name = String::from_utf8_lossy(e);
+            match (e.resolve_char_ref(), resolve_xml_entity(&name)) {
+                (Ok(Some(c)), _) => text.push(c),
+                (_, Some(resolved)) => text.push_str(resolved),
+                // A character reference to no character, like `&#0;`, is kept as written
+                _ if e.is_char_ref() => {
+                    text.push('&');
+                    text.push_str(&name);
+                    text.push(';');
+                }
+                _ => return Err(name.into_owned()),
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// An attribute's value with references resolved, or as written if it uses an entity
+/// only a DTD could define
+fn attribute_value(attr: &Attribute) -> String {
+    match attr.unescape_value() {
+        Ok(value) => value.into_owned(),
+        Err(_) => String::from_utf8_lossy(&attr.value).into_owned(),
+    }
+}
+
+/// Collects extension elements inside one `<url>` as raw key/value pairs.
+///
+/// A key is the element's qualified name as written, with its ancestors below
+/// `<url>` joined by `/` (`image:image/image:loc`), and the value is its text with
+/// references resolved. Each attribute becomes its own pair keyed
+/// `name@attribute` (`xhtml:link@hreflang`); an empty element with no attributes is
+/// recorded with an empty value so flags aren't lost.
+#[derive(Debug, Default)]
+struct ExtensionCapture {
+    path: Vec<String>,
+    text: String,
+    fields: Vec<(String, String)>,
+}
+
+impl ExtensionCapture {
+    /// Take in the next event; `protocol` says whether it's a tag in the protocol's namespace
+    fn observe(&mut self, event: &Event, protocol: bool) {
+        match event {
+            Event::Start(e) if !(self.path.is_empty() && protocol && is_url_field(e.local_name().as_ref())) => {
+                self.open(e);
+            }
+            Event::Empty(e) if !(self.path.is_empty() && protocol && is_url_field(e.local_name().as_ref())) => {
+                if !self.open(e) {
+                    self.fields.push((self.path.join("/"), String::new()));
+                }
+                self.path.pop();
+            }
+            Event::Text(_) | Event::CData(_) | Event::GeneralRef(_) if !self.path.is_empty() => {
+                // An entity only a DTD could define is left out of the value
+                let _ = push_text(&mut self.text, event);
+            }
+            Event::End(_) if !self.path.is_empty() => {
+                let text = std::mem::take(&mut self.text);
+                let text = text.trim();
+                if !text.is_empty() {
+                    self.fields.push((self.path.join("/"), text.to_string()));
+                }
+                self.path.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Enter an element, recording its attributes; returns whether it had any
+    fn open(&mut self, e: &BytesStart) -> bool {
+        self.text.clear();
+        self.path.push(String::from_utf8_lossy(e.name().as_ref()).into_owned());
+        let key = self.path.join("/");
+        let before = self.fields.len();
+        for attr in e.attributes().flatten() {
+            let name = String::from_utf8_lossy(attr.key.as_ref());
+            if name == "xmlns" || name.starts_with("xmlns:") {
+                continue;
+            }
+            self.fields.push((format!("{}@{}", key, name), attribute_value(&attr)));
+        }
+        self.fields.len() > before
+    }
+}
+
+/// `(hreflang, href)` if `e` is a `<link rel="alternate">` carrying both
+fn alternate_link(e: &BytesStart) -> Option<(String, String)> {
+    if e.local_name().as_ref() != b"link" {
+        return None;
+    }
+    let (mut rel, mut hreflang, mut href) = (None, None, None);
+    for attr in e.attributes().flatten() {
+        let value = attribute_value(&attr).trim().to_string();
+        match attr.key.local_name().as_ref() {
+            b"rel" => rel = Some(value),
+            b"hreflang" => hreflang = Some(value),
+            b"href" => href = Some(value),
+            _ => {}
+        }
+    }
+    if !rel?.eq_ignore_ascii_case("alternate") {
+        return None;
+    }
+    Some((hreflang.filter(|h| !h.is_empty())?, href.filter(|h| !h.is_empty())?))
+}
+
+/// Record the text of a finished `<loc>` according to the element it was found in,
+/// returning it if it was a page URL
+fn push_loc(
+    result: &mut SitemapParseResult,
+    text: &str,
+    in_url: bool,
+    in_sitemap: bool,
+    base_url: &str,
+    options: &ParseOptions,
+) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let url = text.trim_matches(|c: char| c == BOM || c.is_whitespace());
+    if url.is_empty() {
+        return Ok(None);
+    }
+    let url = if options.quirks { quirks::decode_loc(url) } else { url.into() };
+
+    if in_sitemap {
+        // This is a nested sitemap reference
+        let absolute_url = make_absolute_url(&url, base_url)?;
+        if options.follows_sitemap(&absolute_url) {
+            result.nested_sitemaps.push(absolute_url);
+        }
+    } else if in_url && options.keeps_url(&url) {
+        let url = url.into_owned();
+        result.urls.insert(url.clone());
+        return Ok(Some(url));
+    }
+    Ok(None)
+}
+
 /// Fallback parser for malformed or non-standard XML
-fn parse_fallback(content: &str, base_url: &str, result: &mut SitemapParseResult) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+fn parse_fallback(content: &str, base_url: &str, options: &ParseOptions, result: &mut SitemapParseResult) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Commented-out entries and stylesheet instructions aren't part of the sitemap
+    let content = strip_comments_and_pis(content);
+    let content = content.as_ref();
     // Simple regex-like approach to find <loc> tags
     let loc_pattern = "<loc>";
     let end_loc_pattern = "</loc>";
@@ -110,16 +723,28 @@ fn parse_fallback(content: &str, base_url: &str, result: &mut SitemapParseResult
         let absolute_start = start + loc_start + loc_pattern.len();
         if let Some(loc_end) = content[absolute_start..].find(end_loc_pattern) {
             let url = content[absolute_start..absolute_start + loc_end].trim();
-            if !url.is_empty() {
-                // Check if this might be in a sitemap context by looking backwards
-                let context_start = (start + loc_start).saturating_sub(100);
+            let undeclared = undeclared_entity(url);
+            if let Some(name) = undeclared {
+                reject_entity(name, &mut result.security_warnings);
+            }
+            if !url.is_empty() && undeclared.is_none() {
+                let url = unescape(url).unwrap_or(Cow::Borrowed(url));
+                let url = url.as_ref();
+                // Check if this might be in a sitemap context by looking backwards,
+                // backing up to a char boundary so multi-byte text can't split the slice
+                let mut context_start = (start + loc_start).saturating_sub(100);
+                while !content.is_char_boundary(context_start) {
+                    context_start -= 1;
+                }
                 let context = &content[context_start..start + loc_start];
                 
                 if context.contains("<sitemap") && !context.contains("</sitemap>") {
                     // Likely a sitemap reference
                     let absolute_url = make_absolute_url(url, base_url)?;
-                    result.nested_sitemaps.push(absolute_url);
-                } else {
+                    if options.follows_sitemap(&absolute_url) {
+                        result.nested_sitemaps.push(absolute_url);
+                    }
+                } else if options.keeps_url(url) {
                     // Likely a regular URL
                     result.urls.insert(url.to_string());
                 }
@@ -133,8 +758,111 @@ fn parse_fallback(content: &str, base_url: &str, result: &mut SitemapParseResult
     Ok(())
 }
 
+/// Remove `<!-- -->` comments and `<? ?>` processing instructions; an unterminated one runs to the end
+fn strip_comments_and_pis(content: &str) -> Cow<'_, str> {
+    if !content.contains("<!--") && !content.contains("<?") {
+        return Cow::Borrowed(content);
+    }
+    let mut kept = String::with_capacity(content.len());
+    let mut rest = content;
+    loop {
+        let next = [("<!--", "-->"), ("<?", "?>")]
+            .into_iter()
+            .filter_map(|(open, close)| rest.find(open).map(|at| (at, open, close)))
+            .min_by_key(|(at, _, _)| *at);
+        let Some((at, open, close)) = next else { break };
+        kept.push_str(&rest[..at]);
+        let body = &rest[at + open.len()..];
+        match body.find(close) {
+            Some(end) => rest = &body[end + close.len()..],
+            None => return Cow::Owned(kept),
+        }
+    }
+    kept.push_str(rest);
+    Cow::Owned(kept)
+}
+
+/// Remove a DOCTYPE declaration, internal subset included, noting what it tried to declare
+fn strip_doctype<'a>(content: &'a str, warnings: &mut Vec<String>) -> Cow<'a, str> {
+    let Some((start, end)) = doctype_span(content) else { return Cow::Borrowed(content) };
+    let doctype = &content[start..end];
+    let mut warning = DOCTYPE_IGNORED.to_string();
+    if doctype.contains("<!ENTITY") {
+        let external = doctype.contains("SYSTEM") || doctype.contains("PUBLIC");
+        warning.push_str(if external { " (it declared external entities)" } else { " (it declared entities)" });
+    }
+    warn!("🦀 {}", warning);
+    warnings.push(warning);
+    Cow::Owned(format!("{}{}", &content[..start], &content[end..]))
+}
+
+/// Byte range of the `<!DOCTYPE ...>` in the prolog, skipping `>` inside its internal subset
+/// or quoted strings. Past the root element's start, `<!DOCTYPE` is only text or a comment.
+fn doctype_span(content: &str) -> Option<(usize, usize)> {
+    let mut start = 0;
+    loop {
+        let rest = content[start..].trim_start_matches(|c: char| c == BOM || c.is_whitespace());
+        start = content.len() - rest.len();
+        if rest.starts_with("<!DOCTYPE") {
+            break;
+        }
+        // The XML declaration, processing instructions, and comments may come first
+        let close = if rest.starts_with("<?") {
+            "?>"
+        } else if rest.starts_with("<!--") {
+            "-->"
+        } else {
+            return None;
+        };
+        start += rest.find(close)? + close.len();
+    }
+    let mut subset_depth = 0;
+    let mut quote = None;
+    for (i, c) in content[start..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[') => subset_depth += 1,
+            (None, ']') => subset_depth -= 1,
+            (None, '>') if subset_depth <= 0 => return Some((start, start + i + 1)),
+            _ => {}
+        }
+    }
+    // Unterminated: everything after it belongs to the declaration, root element included
+    Some((start, content.len()))
+}
+
+/// Entities XML defines without a DTD, plus character references
+pub(crate) fn is_predefined_entity(name: &str) -> bool {
+    matches!(name, "lt" | "gt" | "amp" | "apos" | "quot") || name.starts_with('#')
+}
+
+/// The first entity reference in `text` that only a DTD could define
+fn undeclared_entity(text: &str) -> Option<&str> {
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        rest = &rest[amp + 1..];
+        let Some(semi) = rest.find(';') else { break };
+        let name = &rest[..semi];
+        let is_name = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':' | '#'));
+        if is_name && !is_predefined_entity(name) {
+            return Some(name);
+        }
+    }
+    None
+}
+
+pub(crate) fn reject_entity(name: &str, warnings: &mut Vec<String>) {
+    let warning = format!("<loc> dropped: it references entity &{}; which only a DTD could define", name);
+    if !warnings.contains(&warning) {
+        warn!("🦀 {}", warning);
+        warnings.push(warning);
+    }
+}
+
 /// Convert a potentially relative URL to an absolute URL
-fn make_absolute_url(url: &str, base_url: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+pub(crate) fn make_absolute_url(url: &str, base_url: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
     if url.starts_with("http://") || url.starts_with("https://") {
         Ok(url.to_string())
     } else if url.starts_with('/') {
@@ -178,6 +906,166 @@ mod tests {
         assert!(result.nested_sitemaps.is_empty());
     }
 
+    #[test]
+    fn test_parse_url_metadata() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <lastmod>2024-05-01T10:00:00+00:00</lastmod>
+    <loc>https://example.com/news</loc>
+    <changefreq>Daily</changefreq>
+    <priority>0.9</priority>
+  </url>
+  <url>
+    <loc>https://example.com/about</loc>
+    <changefreq>fortnightly</changefreq>
+    <priority>7</priority>
+  </url>
+  <url>
+    <loc>https://example.com/plain</loc>
+  </url>
+</urlset>"#;
+
+        let result = parse_sitemap_xml(xml, "https://example.com").unwrap();
+        assert_eq!(result.urls.len(), 3);
+        let news = &result.metadata["https://example.com/news"];
+        assert_eq!(news.lastmod.as_deref(), Some("2024-05-01T10:00:00+00:00"));
+        assert_eq!(news.changefreq.as_deref(), Some("daily"));
+        assert_eq!(news.priority, Some(0.9));
+        // Unknown frequencies and out-of-range priorities are dropped, leaving nothing to record
+        assert!(!result.metadata.contains_key("https://example.com/about"));
+        assert!(!result.metadata.contains_key("https://example.com/plain"));
+    }
+
+    #[test]
+    fn test_extension_elements_captured() {
+        let xml = r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+        xmlns:shop="https://shop.example/ns" xmlns:xhtml="http://www.w3.org/1999/xhtml">
+  <url>
+    <loc>https://example.com/p/1</loc>
+    <changefreq>daily</changefreq>
+    <shop:price currency="EUR">19.99</shop:price>
+    <shop:stock><shop:count>4</shop:count><shop:warehouse>A&amp;B</shop:warehouse></shop:stock>
+    <xhtml:link rel="alternate" hreflang="de" href="https://example.com/de/p/1"/>
+    <shop:featured/>
+  </url>
+  <url><loc>https://example.com/p/2</loc></url>
+</urlset>"#;
+        let field = |k: &str, v: &str| (k.to_string(), v.to_string());
+
+        let plain = parse_sitemap_xml(xml, "https://example.com").unwrap();
+        assert_eq!(plain.metadata.len(), 1);
+        assert!(plain.metadata["https://example.com/p/1"].extensions.is_empty());
+
+        let options = ParseOptions { extensions: true, ..Default::default() };
+        let result = parse_sitemap_xml_with_options(xml, "https://example.com", &options).unwrap();
+        assert_eq!(result.urls.len(), 2);
+        assert_eq!(result.metadata["https://example.com/p/1"].extensions, vec![
+            field("shop:price@currency", "EUR"),
+            field("shop:price", "19.99"),
+            field("shop:stock/shop:count", "4"),
+            field("shop:stock/shop:warehouse", "A&B"),
+            field("xhtml:link@rel", "alternate"),
+            field("xhtml:link@hreflang", "de"),
+            field("xhtml:link@href", "https://example.com/de/p/1"),
+            field("shop:featured", ""),
+        ]);
+        assert!(!result.metadata.contains_key("https://example.com/p/2"));
+    }
+
+    #[test]
+    fn test_media_entries_captured() {
+        let xml = r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+        xmlns:image="http://www.google.com/schemas/sitemap-image/1.1"
+        xmlns:v="http://www.google.com/schemas/sitemap-video/1.1"
+        xmlns:news="http://www.google.com/schemas/sitemap-news/0.9">
+  <url>
+    <loc>https://example.com/article</loc>
+    <lastmod>2024-06-01</lastmod>
+    <image:image><image:loc>https://cdn.example.com/a.jpg</image:loc><image:caption>Tom &amp; Jerry</image:caption></image:image>
+    <image:image><image:caption>no location</image:caption></image:image>
+    <image:image><image:loc>https://cdn.example.com/b.jpg</image:loc></image:image>
+    <v:video>
+      <v:thumbnail_loc>https://cdn.example.com/t.jpg</v:thumbnail_loc>
+      <v:title>Grilling steaks</v:title>
+      <v:content_loc>https://cdn.example.com/v.mp4</v:content_loc>
+      <v:player_loc allow_embed="yes">https://example.com/player?v=1</v:player_loc>
+      <v:duration>600</v:duration>
+      <v:rating>9.5</v:rating>
+      <v:family_friendly>YES</v:family_friendly>
+      <v:live>maybe</v:live>
+      <v:tag>steak</v:tag><v:tag>grill</v:tag>
+    </v:video>
+    <news:news>
+      <news:publication><news:name>Example Times</news:name><news:language>en</news:language></news:publication>
+      <news:publication_date>2024-06-01T08:00:00Z</news:publication_date>
+      <news:title>Breaking</news:title>
+      <news:keywords>politics, election ,</news:keywords>
+    </news:news>
+  </url>
+  <url><loc>https://example.com/plain</loc></url>
+</urlset>"#;
+        let plain = parse_sitemap_xml(xml, "https://example.com").unwrap();
+        assert!(plain.metadata["https://example.com/article"].images.is_empty());
+        assert_eq!(plain.urls.len(), 2);
+
+        let options = ParseOptions { media: true, ..Default::default() };
+        let result = parse_sitemap_xml_with_options(xml, "https://example.com", &options).unwrap();
+        assert_eq!(result.urls.len(), 2);
+        let meta = &result.metadata["https://example.com/article"];
+        assert_eq!(meta.lastmod.as_deref(), Some("2024-06-01"));
+        assert_eq!(meta.images, vec![
+            ImageEntry { loc: "https://cdn.example.com/a.jpg".to_string(), caption: Some("Tom & Jerry".to_string()), ..Default::default() },
+            ImageEntry { loc: "https://cdn.example.com/b.jpg".to_string(), ..Default::default() },
+        ]);
+        assert_eq!(meta.videos, vec![VideoEntry {
+            thumbnail_loc: Some("https://cdn.example.com/t.jpg".to_string()),
+            title: Some("Grilling steaks".to_string()),
+            content_loc: Some("https://cdn.example.com/v.mp4".to_string()),
+            player_loc: Some("https://example.com/player?v=1".to_string()),
+            duration: Some(600),
+            family_friendly: Some(true),
+            tags: vec!["steak".to_string(), "grill".to_string()],
+            ..Default::default()
+        }]);
+        assert_eq!(meta.news, Some(NewsEntry {
+            publication_name: Some("Example Times".to_string()),
+            publication_language: Some("en".to_string()),
+            publication_date: Some("2024-06-01T08:00:00Z".to_string()),
+            title: Some("Breaking".to_string()),
+            keywords: vec!["politics".to_string(), "election".to_string()],
+        }));
+        assert!(!result.metadata.contains_key("https://example.com/plain"));
+    }
+
+    #[test]
+    fn test_hreflang_alternates_captured() {
+        let xml = r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9" xmlns:xhtml="http://www.w3.org/1999/xhtml">
+  <url>
+    <loc>https://example.com/en/about</loc>
+    <xhtml:link rel="alternate" hreflang="en" href="https://example.com/en/about"/>
+    <xhtml:link rel="alternate" hreflang="de-AT" href="https://example.com/at/ueber?a=1&amp;b=2"/>
+    <xhtml:link rel="Alternate" hreflang="x-default" href="https://example.com/about"></xhtml:link>
+    <xhtml:link rel="canonical" href="https://example.com/en/about"/>
+    <xhtml:link rel="alternate" href="https://example.com/no-lang"/>
+  </url>
+  <url><loc>https://example.com/en/contact</loc></url>
+</urlset>"#;
+        let plain = parse_sitemap_xml(xml, "https://example.com").unwrap();
+        assert!(plain.metadata.is_empty());
+
+        let options = ParseOptions { alternates: true, ..Default::default() };
+        let result = parse_sitemap_xml_with_options(xml, "https://example.com", &options).unwrap();
+        assert_eq!(result.urls.len(), 2);
+        let pair = |lang: &str, href: &str| (lang.to_string(), href.to_string());
+        assert_eq!(result.metadata["https://example.com/en/about"].alternates, vec![
+            pair("en", "https://example.com/en/about"),
+            pair("de-AT", "https://example.com/at/ueber?a=1&b=2"),
+            pair("x-default", "https://example.com/about"),
+        ]);
+        assert!(!result.metadata.contains_key("https://example.com/en/contact"));
+    }
+
     #[test]
     fn test_parse_sitemapindex() {
         let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -225,4 +1113,312 @@ mod tests {
         let result = parse_sitemap_xml(xml, "https://example.com").unwrap();
         assert_eq!(result.urls.len(), 2);
     }
+
+    #[test]
+    fn test_parse_truncated_urlset_keeps_complete_entries() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>https://example.com/page1</loc>
+  </url>
+  <url>
+    <loc>https://example.com/page2</loc>
+  </url>
+  <url>
+    <loc>https://example.com/pa"#;
+
+        let result = parse_sitemap_xml(xml, "https://example.com").unwrap();
+        assert_eq!(result.urls.len(), 2);
+        assert!(!result.urls.iter().any(|u| u.ends_with("/pa")));
+    }
+
+    #[test]
+    fn test_entity_references_unescaped() {
+        let xml = r#"<urlset>
+  <url><loc>https://example.com/?a=1&amp;b=2</loc></url>
+  <url><loc>https://example.com/?c=3&#38;d=4&#x26;e=5</loc><lastmod>2024-01-01T00:00:00&#43;00:00</lastmod></url>
+  <url><loc><![CDATA[https://example.com/?f=6&g=7]]></loc></url>
+  <url><loc>https://example.com/?h=8&amp;amp;i=9</loc></url>
+</urlset>"#;
+
+        let result = parse_sitemap_xml(xml, "https://example.com").unwrap();
+        assert!(result.urls.contains("https://example.com/?a=1&b=2"));
+        assert!(result.urls.contains("https://example.com/?c=3&d=4&e=5"));
+        assert!(result.urls.contains("https://example.com/?f=6&g=7"));
+        assert!(result.urls.contains("https://example.com/?h=8&amp;i=9"));
+        assert_eq!(result.metadata["https://example.com/?c=3&d=4&e=5"].lastmod.as_deref(), Some("2024-01-01T00:00:00+00:00"));
+
+        // Quirks mode also undoes the double escaping
+        let result = parse_sitemap_xml_with(xml, "https://example.com", true).unwrap();
+        assert!(result.urls.contains("https://example.com/?a=1&b=2"));
+        assert!(result.urls.contains("https://example.com/?h=8&i=9"));
+    }
+
+    #[test]
+    fn test_extension_media_and_alternate_values_unescaped() {
+        let xml = r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9" xmlns:image="http://www.google.com/schemas/sitemap-image/1.1" xmlns:xhtml="http://www.w3.org/1999/xhtml" xmlns:shop="https://example.com/shop">
+  <url>
+    <loc>https://example.com/p</loc>
+    <image:image><image:loc>https://example.com/img?w=1&amp;h=2</image:loc></image:image>
+    <shop:note>Fish &amp; chips &#38; peas</shop:note>
+    <xhtml:link rel="alternate" hreflang="de" href="https://example.com/de?a=1&amp;b=2"/>
+  </url>
+</urlset>"#;
+
+        let options = ParseOptions { extensions: true, media: true, alternates: true, ..Default::default() };
+        let result = parse_sitemap_xml_with_options(xml, "https://example.com", &options).unwrap();
+        let meta = &result.metadata["https://example.com/p"];
+        assert_eq!(meta.images[0].loc, "https://example.com/img?w=1&h=2");
+        assert!(meta.extensions.contains(&("shop:note".to_string(), "Fish & chips & peas".to_string())), "{:?}", meta.extensions);
+        assert!(meta.extensions.contains(&("image:image/image:loc".to_string(), "https://example.com/img?w=1&h=2".to_string())));
+        assert!(meta.extensions.contains(&("xhtml:link@href".to_string(), "https://example.com/de?a=1&b=2".to_string())));
+        assert_eq!(meta.alternates, vec![("de".to_string(), "https://example.com/de?a=1&b=2".to_string())]);
+    }
+
+    #[test]
+    fn test_foreign_namespace_elements_are_not_protocol() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sm:urlset xmlns:sm="http://www.sitemaps.org/schemas/sitemap/0.9" xmlns:foo="https://example.com/foo" xmlns:video="http://www.google.com/schemas/sitemap-video/1.1">
+  <sm:url>
+    <sm:loc>https://example.com/page</sm:loc>
+    <foo:loc>https://example.com/foo-loc</foo:loc>
+    <foo:lastmod>1999-01-01</foo:lastmod>
+    <sm:lastmod>2024-02-02</sm:lastmod>
+    <video:video><video:loc>https://example.com/video-loc</video:loc><sm:loc>https://example.com/nested</sm:loc></video:video>
+  </sm:url>
+  <foo:url><foo:loc>https://example.com/foo-url</foo:loc></foo:url>
+  <url><loc>https://example.com/unqualified</loc></url>
+</sm:urlset>"#;
+
+        let options = ParseOptions { extensions: true, ..Default::default() };
+        let result = parse_sitemap_xml_with_options(xml, "https://example.com", &options).unwrap();
+        assert_eq!(result.urls, ["https://example.com/page".to_string()].into());
+        let meta = &result.metadata["https://example.com/page"];
+        assert_eq!(meta.lastmod.as_deref(), Some("2024-02-02"));
+        assert!(meta.extensions.contains(&("foo:loc".to_string(), "https://example.com/foo-loc".to_string())), "{:?}", meta.extensions);
+    }
+
+    #[test]
+    fn test_fallback_context_with_multibyte_text() {
+        // 100 bytes before the second <loc> lands inside a multi-byte character
+        let xml = format!("<loc>https://example.com/a</loc>{}<loc>https://example.com/b</loc>", "é".repeat(60));
+
+        let result = parse_sitemap_xml(&xml, "https://example.com").unwrap();
+        assert_eq!(result.urls.len(), 2);
+    }
+
+    #[test]
+    fn test_xxe_entity_is_never_expanded() {
+        let xml = r#"<?xml version="1.0"?>
+<!DOCTYPE urlset [
+  <!ENTITY xxe SYSTEM "file:///etc/passwd">
+]>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url><loc>&xxe;</loc></url>
+  <url><loc>https://example.com/ok?a=1&amp;b=2</loc></url>
+</urlset>"#;
+
+        for quirks in [false, true] {
+            let result = parse_sitemap_xml_with(xml, "https://example.com", quirks).unwrap();
+            assert_eq!(result.urls.len(), 1, "{:?}", result.urls);
+            assert!(result.urls.iter().all(|u| u.starts_with("https://example.com/ok")));
+            assert_eq!(result.security_warnings, vec![
+                "DOCTYPE declaration ignored; sitemaps never need a DTD (it declared external entities)".to_string(),
+                "<loc> dropped: it references entity &xxe; which only a DTD could define".to_string(),
+            ]);
+        }
+    }
+
+    #[test]
+    fn test_billion_laughs_fails_fast() {
+        let mut xml = String::from("<?xml version=\"1.0\"?>\n<!DOCTYPE lolz [\n  <!ENTITY lol \"lol\">\n");
+        for i in 1..10 {
+            let previous = if i == 1 { "lol".to_string() } else { format!("lol{}", i - 1) };
+            xml.push_str(&format!("  <!ENTITY lol{} \"{}\">\n", i, format!("&{};", previous).repeat(10)));
+        }
+        xml.push_str("]>\n<urlset><url><loc>&lol9;</loc></url></urlset>");
+
+        let started = std::time::Instant::now();
+        let result = parse_sitemap_xml(&xml, "https://example.com").unwrap();
+        assert!(result.urls.is_empty(), "{:?}", result.urls);
+        assert!(result.nested_sitemaps.is_empty());
+        assert_eq!(result.security_warnings.len(), 2);
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_locs_inside_dtd_are_not_parsed() {
+        // Neither the structured parser nor the fallback may see markup hidden in the DTD
+        let xml = r#"<!DOCTYPE x [<!ENTITY a "<loc>https://evil.example/</loc>"> <!-- ] > -->]><urlset><url><loc>&a;</loc></url></urlset>"#;
+
+        let result = parse_sitemap_xml(xml, "https://example.com").unwrap();
+        assert!(result.urls.is_empty(), "{:?}", result.urls);
+        assert_eq!(result.security_warnings[0], "DOCTYPE declaration ignored; sitemaps never need a DTD (it declared entities)");
+    }
+
+    #[test]
+    fn test_external_dtd_is_ignored() {
+        let xml = r#"<?xml version="1.0"?>
+<!DOCTYPE urlset SYSTEM "http://attacker.example/evil.dtd">
+<urlset><url><loc>https://example.com/a</loc></url></urlset>"#;
+
+        let result = parse_sitemap_xml(xml, "https://example.com").unwrap();
+        assert_eq!(result.urls.len(), 1);
+        assert_eq!(result.security_warnings, vec!["DOCTYPE declaration ignored; sitemaps never need a DTD".to_string()]);
+        assert_eq!(undeclared_entity("https://example.com/?a=1&amp;b=&#38;"), None);
+    }
+
+    #[test]
+    fn test_doctype_only_stripped_from_prolog() {
+        let xml = r#"<?xml version="1.0"?>
+<!-- generated -->
+<urlset>
+  <url><loc>https://example.com/a</loc></url>
+  <!-- <!DOCTYPE x [ -->
+  <url><loc><![CDATA[https://example.com/b?q=<!DOCTYPE>]]></loc></url>
+</urlset>"#;
+
+        let result = parse_sitemap_xml(xml, "https://example.com").unwrap();
+        assert_eq!(result.urls.len(), 2, "{:?}", result.urls);
+        assert!(result.urls.contains("https://example.com/b?q=<!DOCTYPE>"));
+        assert!(result.security_warnings.is_empty(), "{:?}", result.security_warnings);
+        assert_eq!(doctype_span("<?xml version=\"1.0\"?><!-- c --> <!DOCTYPE x><urlset/>"), Some((32, 44)));
+    }
+
+    #[test]
+    fn test_deep_nesting_is_rejected() {
+        let xml = format!("<urlset>{}<url><loc>https://example.com/a</loc></url>", "<x>".repeat(100_000));
+
+        let started = std::time::Instant::now();
+        for quirks in [false, true] {
+            let error = parse_sitemap_xml_with(&xml, "https://example.com", quirks).unwrap_err();
+            assert!(error.to_string().contains("more than 32 deep"), "{}", error);
+        }
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+
+        // Nesting that comes back up doesn't accumulate
+        let wide = format!("<urlset>{}</urlset>", "<url><loc>https://example.com/a</loc></url>".repeat(100));
+        assert_eq!(parse_sitemap_xml(&wide, "https://example.com").unwrap().urls.len(), 1);
+    }
+
+    #[test]
+    fn test_event_and_entity_limits() {
+        let limits = XmlLimits { max_depth: 32, max_events: 50, max_entity_refs: 5 };
+        let many_urls: String = (0..20).map(|i| format!("<url><loc>https://example.com/{}</loc></url>", i)).collect();
+        let xml = format!("<urlset>{}</urlset>", many_urls);
+        let error = parse_with_limits(&xml, "https://example.com", None, &ParseOptions::default(), &limits).unwrap_err();
+        assert!(error.to_string().contains("more than 50 parse events"), "{}", error);
+
+        let xml = format!("<urlset><url><loc>https://example.com/?{}</loc></url></urlset>", "a=1&amp;".repeat(10));
+        let error = parse_with_limits(&xml, "https://example.com", None, &ParseOptions::default(), &limits).unwrap_err();
+        assert!(error.to_string().contains("more than 5 entity references"), "{}", error);
+        assert_eq!(parse_sitemap_xml(&xml, "https://example.com").unwrap().urls.len(), 1);
+    }
+
+    #[test]
+    fn test_decorated_sitemap_parses_structurally() {
+        let xml = "\u{feff}\r\n<?xml version=\"1.0\" encoding=\"UTF-8\"?>\r\n\
+<?xml-stylesheet type=\"text/xsl\" href=\"//example.com/main-sitemap.xsl?v=1>2\"?>\r\n\
+<!-- generated by a plugin -->\r\n\
+<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\r\n\
+  <!-- <url><loc>https://example.com/retired</loc></url> -->\r\n\
+  <url><loc>\u{feff}https://example.com/a</loc><lastmod><!-- today -->2024-01-01</lastmod></url>\r\n\
+  <url><loc>https://example.com/<?php echo 1 ?>b</loc></url>\r\n\
+</urlset>\r\n<!-- served in 12ms -->";
+
+        let result = parse_sitemap_xml(xml, "https://example.com").unwrap();
+        let mut urls: Vec<_> = result.urls.iter().map(String::as_str).collect();
+        urls.sort();
+        assert_eq!(urls, vec!["https://example.com/a", "https://example.com/b"]);
+        assert_eq!(result.metadata["https://example.com/a"].lastmod.as_deref(), Some("2024-01-01"));
+    }
+
+    #[test]
+    fn test_fallback_skips_comments_and_pis() {
+        // Nothing structural to find, so the fallback runs; it must not resurrect hidden entries
+        let xml = "<urlset><!-- <url><loc>https://example.com/old</loc></url> --><?pi <loc>https://example.com/pi</loc>?></urlset>";
+
+        let result = parse_sitemap_xml(xml, "https://example.com").unwrap();
+        assert!(result.urls.is_empty(), "{:?}", result.urls);
+        assert_eq!(strip_comments_and_pis("<a/><!-- x --><b/><!-- open"), "<a/><b/>");
+    }
+
+    #[test]
+    fn test_text_sitemap() {
+        let text = "\u{feff}https://example.com/a\r\n\n  https://example.com/b?x=1  \n# generated nightly\n/relative\nftp://example.com/file\nHTTPS://example.com/c\n";
+
+        let result = parse_sitemap_xml(text, "https://example.com/sitemap.txt").unwrap();
+        let mut urls: Vec<_> = result.urls.iter().map(String::as_str).collect();
+        urls.sort();
+        assert_eq!(urls, vec!["HTTPS://example.com/c", "https://example.com/a", "https://example.com/b?x=1"]);
+        assert!(result.metadata.is_empty() && result.nested_sitemaps.is_empty());
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let rss = "<?xml version=\"1.0\"?><rss version=\"2.0\"><channel/></rss>";
+        assert_eq!(detect_format(rss, Some("text/plain")), SitemapFormat::Feed);
+        assert_eq!(detect_format("<urlset/>", Some("text/plain; charset=utf-8")), SitemapFormat::Xml);
+        assert_eq!(detect_format(" [\"https://example.com/\"]", None), SitemapFormat::Json);
+        assert_eq!(detect_format("https://example.com/a\n", None), SitemapFormat::Text);
+        // A leading comment only reads as text when the server says so
+        let commented = "# pages\nhttps://example.com/a\n";
+        assert_eq!(detect_format(commented, None), SitemapFormat::Xml);
+        assert_eq!(detect_format(commented, Some("Text/Plain; charset=utf-8")), SitemapFormat::Text);
+        assert_eq!(parse_sitemap_body(commented, "https://example.com", Some("text/plain"), &ParseOptions::default()).unwrap().urls.len(), 1);
+    }
+
+    #[test]
+    fn test_modified_since_skips_old_sitemaps_and_urls() {
+        let since = parse_lastmod("2024-06-01T12:00:00Z");
+        let options = ParseOptions { modified_since: since, ..Default::default() };
+        let index = r#"<sitemapindex>
+            <sitemap><loc>https://example.com/old.xml</loc><lastmod>2024-05-31</lastmod></sitemap>
+            <sitemap><lastmod>2024-06-01</lastmod><loc>https://example.com/same-day.xml</loc></sitemap>
+            <sitemap><loc>https://example.com/undated.xml</loc></sitemap>
+        </sitemapindex>"#;
+        let result = parse_sitemap_xml_with_options(index, "https://example.com", &options).unwrap();
+        assert_eq!(result.nested_sitemaps, ["https://example.com/same-day.xml", "https://example.com/undated.xml"]);
+        assert_eq!(result.unmodified_sitemaps, 1);
+
+        let urlset = r#"<urlset>
+            <url><loc>https://example.com/a</loc><lastmod>2024-06-02T00:00:00+00:00</lastmod></url>
+            <url><lastmod>2024-05</lastmod><loc>https://example.com/b</loc></url>
+            <url><loc>https://example.com/c</loc></url>
+            <url><loc>https://example.com/c</loc><lastmod>2023-01-01</lastmod></url>
+        </urlset>"#;
+        let result = parse_sitemap_xml_with_options(urlset, "https://example.com", &options).unwrap();
+        let mut urls: Vec<_> = result.urls.iter().map(String::as_str).collect();
+        urls.sort();
+        // The second entry for /c repeats a URL an undated entry already kept
+        assert_eq!(urls, ["https://example.com/a", "https://example.com/c"]);
+        assert_eq!(result.unmodified_urls, 1);
+        assert!(!result.metadata.contains_key("https://example.com/b"));
+
+        let json = r#"{"sitemaps": [{"url": "/old.json", "lastmod": "2024-01-01"}], "urls": [{"url": "/new", "lastmod": "2024-07-01"}, {"url": "/old", "lastmod": "2024-01-01"}]}"#;
+        let result = parse_sitemap_body(json, "https://example.com", None, &options).unwrap();
+        assert_eq!((result.nested_sitemaps.len(), result.unmodified_sitemaps), (0, 1));
+        assert_eq!(result.urls, HashSet::from(["https://example.com/new".to_string()]));
+    }
+
+    #[test]
+    fn test_modified_since_all_stale_stays_empty() {
+        let options = ParseOptions { modified_since: parse_lastmod("2024-06-01"), ..Default::default() };
+        let index = r#"<sitemapindex>
+            <sitemap><loc>https://example.com/a.xml</loc><lastmod>2024-01-01</lastmod></sitemap>
+            <sitemap><loc>https://example.com/b.xml</loc><lastmod>2024-02-01</lastmod></sitemap>
+        </sitemapindex>"#;
+        let result = parse_sitemap_xml_with_options(index, "https://example.com", &options).unwrap();
+        // The lenient fallback must not bring the stale children back
+        assert!(result.nested_sitemaps.is_empty());
+        assert_eq!(result.unmodified_sitemaps, 2);
+
+        let urlset = r#"<urlset>
+            <url><loc>https://example.com/a</loc><lastmod>2024-01-01</lastmod></url>
+            <url><loc>https://example.com/b</loc><lastmod>2023-12-31</lastmod></url>
+        </urlset>"#;
+        let result = parse_sitemap_xml_with_options(urlset, "https://example.com", &options).unwrap();
+        assert!(result.urls.is_empty());
+        assert_eq!(result.unmodified_urls, 2);
+    }
 }
@@ -0,0 +1,286 @@
+use chrono::{SecondsFormat, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::{json, Map, Value};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::job::write_atomically;
+use crate::parser::ParsedSiteResult;
+use crate::score::parse_lastmod;
+
+/// First bytes of a binary snapshot file, ending in the format version
+const BINARY_MAGIC: &[u8; 8] = b"SMSNAP\x00\x01";
+
+/// How a snapshot is stored on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    /// `{"version": 1, "taken_at": ..., "urls": {url: lastmod or null}}`
+    Json,
+    /// Gzipped length-prefixed records behind a magic header; a fraction of the JSON's size
+    Binary,
+}
+
+impl SnapshotFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(SnapshotFormat::Json),
+            "binary" | "bin" => Some(SnapshotFormat::Binary),
+            _ => None,
+        }
+    }
+
+    /// JSON for a `.json` path, binary for anything else
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => SnapshotFormat::Json,
+            _ => SnapshotFormat::Binary,
+        }
+    }
+}
+
+/// The URLs a run found and their lastmods, saved so the next run can be compared
+/// against it with [`diff`].
+///
+/// Lastmods come from the results' `url_metadata`, so a parser without
+/// `with_url_metadata` (or an option that turns it on) gives a snapshot with none,
+/// and only additions and removals show up in its diffs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SitemapSnapshot {
+    /// RFC 3339 UTC time the snapshot was made
+    pub taken_at: String,
+    /// `lastmod` as written, by URL; `None` where the sitemap gave none
+    pub urls: BTreeMap<String, Option<String>>,
+}
+
+/// What changed between two snapshots, each list sorted by URL
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapshotDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// URLs in both whose lastmod moved, appeared, or went away
+    pub lastmod_changed: Vec<LastmodChange>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LastmodChange {
+    pub url: String,
+    pub previous: Option<String>,
+    pub current: Option<String>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.lastmod_changed.is_empty()
+    }
+}
+
+impl SitemapSnapshot {
+    /// An empty snapshot taken now
+    pub fn new() -> Self {
+        Self { taken_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true), urls: BTreeMap::new() }
+    }
+
+    /// A snapshot of every URL in `results`, taken now
+    pub fn from_results<'a>(results: impl IntoIterator<Item = &'a ParsedSiteResult>) -> Self {
+        let mut snapshot = Self::new();
+        for result in results {
+            snapshot.add_result(result);
+        }
+        snapshot
+    }
+
+    pub fn add_result(&mut self, result: &ParsedSiteResult) {
+        for url in &result.urls {
+            let lastmod = result.url_metadata.get(url).and_then(|meta| meta.lastmod.clone());
+            self.urls.insert(url.clone(), lastmod);
+        }
+    }
+
+    /// Write to `path`, replacing any file there atomically
+    pub fn save(&self, path: &Path, format: SnapshotFormat) -> io::Result<()> {
+        let bytes = match format {
+            SnapshotFormat::Json => serde_json::to_vec(&self.to_json()).map_err(io::Error::other)?,
+            SnapshotFormat::Binary => self.to_binary()?,
+        };
+        write_atomically(path, &bytes)
+    }
+
+    /// Read a snapshot written by [`save`](Self::save) in either format
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let snapshot = match bytes.strip_prefix(BINARY_MAGIC) {
+            Some(records) => Self::from_binary(records),
+            None => serde_json::from_slice(&bytes).ok().and_then(|value| Self::from_json(&value)),
+        };
+        snapshot.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a sitemap snapshot", path.display())))
+    }
+
+    fn to_json(&self) -> Value {
+        let urls: Map<String, Value> = self.urls.iter().map(|(url, lastmod)| (url.clone(), json!(lastmod))).collect();
+        json!({ "version": 1, "taken_at": self.taken_at, "urls": urls })
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        if value["version"] != 1 {
+            return None;
+        }
+        let urls = value["urls"].as_object()?.iter()
+            .map(|(url, lastmod)| Some((url.clone(), if lastmod.is_null() { None } else { Some(lastmod.as_str()?.to_string()) })))
+            .collect::<Option<_>>()?;
+        Some(Self { taken_at: value["taken_at"].as_str()?.to_string(), urls })
+    }
+
+    fn to_binary(&self) -> io::Result<Vec<u8>> {
+        let mut out = GzEncoder::new(BINARY_MAGIC.to_vec(), Compression::default());
+        write_field(&mut out, &self.taken_at)?;
+        out.write_all(&(self.urls.len() as u64).to_le_bytes())?;
+        for (url, lastmod) in &self.urls {
+            write_field(&mut out, url)?;
+            match lastmod {
+                Some(lastmod) => {
+                    out.write_all(&[1])?;
+                    write_field(&mut out, lastmod)?;
+                }
+                None => out.write_all(&[0])?,
+            }
+        }
+        out.finish()
+    }
+
+    fn from_binary(records: &[u8]) -> Option<Self> {
+        let mut input = GzDecoder::new(records);
+        let taken_at = read_field(&mut input)?;
+        let mut count = [0; 8];
+        input.read_exact(&mut count).ok()?;
+        let mut urls = BTreeMap::new();
+        for _ in 0..u64::from_le_bytes(count) {
+            let url = read_field(&mut input)?;
+            let mut flag = [0];
+            input.read_exact(&mut flag).ok()?;
+            let lastmod = match flag[0] {
+                0 => None,
+                1 => Some(read_field(&mut input)?),
+                _ => return None,
+            };
+            urls.insert(url, lastmod);
+        }
+        Some(Self { taken_at, urls })
+    }
+}
+
+/// `text` behind its length as a little-endian u32
+fn write_field(out: &mut impl Write, text: &str) -> io::Result<()> {
+    let len = u32::try_from(text.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "snapshot field over 4GB"))?;
+    out.write_all(&len.to_le_bytes())?;
+    out.write_all(text.as_bytes())
+}
+
+fn read_field(input: &mut impl Read) -> Option<String> {
+    let mut len = [0; 4];
+    input.read_exact(&mut len).ok()?;
+    let len = u32::from_le_bytes(len) as u64;
+    // Read through `take` so a corrupt length can't make us allocate it up front
+    let mut bytes = Vec::new();
+    input.take(len).read_to_end(&mut bytes).ok()?;
+    if bytes.len() as u64 != len {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// URLs added to and removed from `previous` in `current`, and those whose lastmod
+/// changed. Lastmods that parse are compared as instants, so `2024-05-01` and
+/// `2024-05-01T00:00:00Z` are the same; others are compared as written.
+pub fn diff(previous: &SitemapSnapshot, current: &SitemapSnapshot) -> SnapshotDiff {
+    let mut diff = SnapshotDiff::default();
+    let mut before = previous.urls.iter().peekable();
+    let mut after = current.urls.iter().peekable();
+    loop {
+        let order = match (before.peek(), after.peek()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some((old, _)), Some((new, _))) => old.cmp(new),
+        };
+        match order {
+            Ordering::Less => diff.removed.push(before.next().expect("peeked").0.clone()),
+            Ordering::Greater => diff.added.push(after.next().expect("peeked").0.clone()),
+            Ordering::Equal => {
+                let ((url, old), (_, new)) = (before.next().expect("peeked"), after.next().expect("peeked"));
+                if !same_lastmod(old.as_deref(), new.as_deref()) {
+                    diff.lastmod_changed.push(LastmodChange { url: url.clone(), previous: old.clone(), current: new.clone() });
+                }
+            }
+        }
+    }
+    diff
+}
+
+fn same_lastmod(old: Option<&str>, new: Option<&str>) -> bool {
+    match (old, new) {
+        (Some(old), Some(new)) => match (parse_lastmod(old), parse_lastmod(new)) {
+            (Some(old), Some(new)) => old == new,
+            _ => old.trim() == new.trim(),
+        },
+        (old, new) => old == new,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(urls: &[(&str, Option<&str>)]) -> SitemapSnapshot {
+        SitemapSnapshot {
+            taken_at: "2024-06-01T00:00:00Z".to_string(),
+            urls: urls.iter().map(|(url, lastmod)| (url.to_string(), lastmod.map(str::to_string))).collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_finds_added_removed_and_changed() {
+        let previous = snapshot(&[
+            ("https://example.com/a", Some("2024-05-01")),
+            ("https://example.com/b", Some("2024-05-01")),
+            ("https://example.com/c", None),
+            ("https://example.com/gone", None),
+        ]);
+        let current = snapshot(&[
+            ("https://example.com/a", Some("2024-05-01T00:00:00Z")),
+            ("https://example.com/b", Some("2024-05-02")),
+            ("https://example.com/c", Some("2024-05-02")),
+            ("https://example.com/new", None),
+        ]);
+
+        let changes = diff(&previous, &current);
+        assert_eq!(changes.added, ["https://example.com/new"]);
+        assert_eq!(changes.removed, ["https://example.com/gone"]);
+        let changed: Vec<_> = changes.lastmod_changed.iter().map(|c| (c.url.as_str(), c.previous.as_deref(), c.current.as_deref())).collect();
+        assert_eq!(changed, [
+            ("https://example.com/b", Some("2024-05-01"), Some("2024-05-02")),
+            ("https://example.com/c", None, Some("2024-05-02")),
+        ]);
+        assert!(diff(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_round_trips_in_both_formats() {
+        let original = snapshot(&[("https://example.com/a?q=1", Some("2024-05-01")), ("https://example.com/ü", None)]);
+        let dir = std::env::temp_dir().join(format!("sitemap-snapshot-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["day.json", "day.snap"] {
+            let path = dir.join(name);
+            original.save(&path, SnapshotFormat::for_path(&path)).unwrap();
+            assert_eq!(SitemapSnapshot::load(&path).unwrap(), original);
+        }
+        assert!(std::fs::read(dir.join("day.snap")).unwrap().starts_with(BINARY_MAGIC));
+
+        std::fs::write(dir.join("bad.snap"), [BINARY_MAGIC.as_slice(), b"not gzip"].concat()).unwrap();
+        assert_eq!(SitemapSnapshot::load(&dir.join("bad.snap")).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+
+/// URLs per batch unless configured otherwise
+pub const DEFAULT_STREAM_BATCH_SIZE: usize = 1000;
+
+/// Where a streamed parse sends each sitemap's URLs instead of collecting them.
+///
+/// The channel is bounded, so a slow reader pauses the parse rather than letting
+/// URLs pile up in memory.
+#[derive(Clone)]
+pub struct UrlSink {
+    sender: Sender<Vec<String>>,
+    batch_size: usize,
+    sent: Arc<AtomicUsize>,
+}
+
+impl UrlSink {
+    /// A sink and the receiving end of its channel, which holds at most `max_buffered` batches
+    pub fn channel(batch_size: usize, max_buffered: usize) -> (Self, Receiver<Vec<String>>) {
+        let (sender, receiver) = mpsc::channel(max_buffered.max(1));
+        (Self { sender, batch_size: batch_size.max(1), sent: Arc::new(AtomicUsize::new(0)) }, receiver)
+    }
+
+    /// Send `urls` in batches, waiting for room in the channel; sorted first when a
+    /// stable order is wanted. Fails once the receiver is gone.
+    pub async fn send(&self, urls: HashSet<String>, sorted: bool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut urls: Vec<String> = urls.into_iter().collect();
+        if sorted {
+            urls.sort_unstable();
+        }
+        let mut rest = urls.into_iter().peekable();
+        while rest.peek().is_some() {
+            let batch: Vec<String> = rest.by_ref().take(self.batch_size).collect();
+            let count = batch.len();
+            self.sender.send(batch).await.map_err(|_| "URL stream was closed by its reader")?;
+            self.sent.fetch_add(count, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    /// URLs sent so far
+    pub fn sent(&self) -> usize {
+        self.sent.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once the receiver is dropped
+    pub async fn closed(&self) {
+        self.sender.closed().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_batches_wait_for_reader() {
+        let (sink, mut receiver) = UrlSink::channel(2, 1);
+        let urls: HashSet<String> = (0..5).map(|i| format!("https://a.example/{}", i)).collect();
+
+        let sending = tokio::spawn({
+            let sink = sink.clone();
+            async move { sink.send(urls, true).await }
+        });
+        tokio::task::yield_now().await;
+        // One batch buffered, the second waiting for room
+        assert!(sink.sent() <= 2);
+        let mut batches = Vec::new();
+        while let Some(batch) = receiver.recv().await {
+            batches.push(batch);
+            if batches.len() == 3 {
+                break;
+            }
+        }
+        sending.await.unwrap().unwrap();
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 2, 1]);
+        assert_eq!(batches[0], vec!["https://a.example/0", "https://a.example/1"]);
+        assert_eq!(sink.sent(), 5);
+
+        drop(receiver);
+        let e = sink.send(HashSet::from(["https://a.example/late".to_string()]), false).await.unwrap_err();
+        assert_eq!(e.to_string(), "URL stream was closed by its reader");
+    }
+}
@@ -0,0 +1,267 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::debug;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::robots::sitemap_directives;
+
+/// Shape of a generated sitemap tree
+#[derive(Debug, Clone)]
+pub struct SyntheticConfig {
+    /// URLs in every leaf urlset
+    pub urls_per_sitemap: usize,
+    /// Levels in the tree: 1 is a single urlset, 2 an index of urlsets, and so on
+    pub depth: usize,
+    /// Children per sitemap index
+    pub fanout: usize,
+    /// Make every Nth urlset malformed XML (0 disables)
+    pub broken_every: usize,
+    /// Serve urlsets gzipped (without a `.gz` suffix, to exercise sniffing)
+    pub gzip: bool,
+}
+
+impl Default for SyntheticConfig {
+    fn default() -> Self {
+        Self {
+            urls_per_sitemap: 1000,
+            depth: 2,
+            fanout: 5,
+            broken_every: 0,
+            gzip: false,
+        }
+    }
+}
+
+/// An in-memory site: robots.txt plus a generated sitemap tree, keyed by path
+#[derive(Debug, Clone, Default)]
+pub struct SyntheticSite {
+    pub files: BTreeMap<String, Vec<u8>>,
+    /// URLs in well-formed urlsets, i.e. what a full parse should find at minimum
+    pub expected_urls: usize,
+}
+
+impl SyntheticSite {
+    /// Generate a site whose absolute URLs all live under `base_url`
+    pub fn generate(config: &SyntheticConfig, base_url: &str) -> Self {
+        let base = base_url.trim_end_matches('/');
+        let mut site = SyntheticSite::default();
+        let mut urlset_count = 0;
+
+        site.add_sitemap(config, base, "/sitemap.xml", config.depth.max(1), &mut urlset_count);
+        site.files.insert(
+            "/robots.txt".to_string(),
+            format!("User-agent: *\nAllow: /\n\n{}", sitemap_directives(&[format!("{}/sitemap.xml", base)])).into_bytes(),
+        );
+        site
+    }
+
+    fn add_sitemap(&mut self, config: &SyntheticConfig, base: &str, path: &str, depth: usize, urlset_count: &mut usize) {
+        if depth <= 1 {
+            *urlset_count += 1;
+            let broken = config.broken_every > 0 && urlset_count.is_multiple_of(config.broken_every);
+            let xml = urlset_xml(base, path, config.urls_per_sitemap, broken);
+            if !broken {
+                self.expected_urls += config.urls_per_sitemap;
+            }
+            let body = if config.gzip { gzip(xml.as_bytes()) } else { xml.into_bytes() };
+            self.files.insert(path.to_string(), body);
+            return;
+        }
+
+        let stem = path.trim_end_matches(".xml");
+        let children: Vec<String> = (0..config.fanout).map(|i| format!("{}-{}.xml", stem, i)).collect();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+        for child in &children {
+            xml.push_str(&format!("  <sitemap><loc>{}{}</loc></sitemap>\n", base, child));
+        }
+        xml.push_str("</sitemapindex>\n");
+        self.files.insert(path.to_string(), xml.into_bytes());
+
+        for child in &children {
+            self.add_sitemap(config, base, child, depth - 1, urlset_count);
+        }
+    }
+
+    /// Write every file under `dir`, mirroring the URL paths
+    pub fn write_to_dir(&self, dir: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        for (path, body) in &self.files {
+            std::fs::write(dir.join(path.trim_start_matches('/')), body)?;
+        }
+        Ok(())
+    }
+}
+
+fn urlset_xml(base: &str, path: &str, count: usize, broken: bool) -> String {
+    let stem = path.trim_start_matches('/').trim_end_matches(".xml");
+    let mut xml = String::with_capacity(count * 80);
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for i in 0..count {
+        xml.push_str(&format!(
+            "  <url><loc>{}/{}/page-{}</loc><lastmod>2024-01-01</lastmod></url>\n",
+            base, stem, i
+        ));
+    }
+    if broken {
+        // Unclosed elements and a dangling tag, as served by a crashed CMS
+        xml.push_str("  <url><loc>");
+    } else {
+        xml.push_str("</urlset>\n");
+    }
+    xml
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(data).expect("writing to a Vec cannot fail");
+    encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+/// A local HTTP server for a synthetic site; stops when dropped
+pub struct SyntheticServer {
+    pub addr: SocketAddr,
+    pub expected_urls: usize,
+    handle: JoinHandle<()>,
+}
+
+impl SyntheticServer {
+    /// Bind to an ephemeral localhost port, generate the site for it, and start serving
+    pub async fn start(config: &SyntheticConfig) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let site = Arc::new(SyntheticSite::generate(config, &format!("http://{}", addr)));
+        let expected_urls = site.expected_urls;
+        debug!("🦀 Serving synthetic site with {} files on {}", site.files.len(), addr);
+
+        let handle = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let site = Arc::clone(&site);
+                tokio::spawn(async move {
+                    let _ = serve_connection(stream, &site).await;
+                });
+            }
+        });
+
+        Ok(Self { addr, expected_urls, handle })
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+
+    pub fn stop(&self) {
+        self.handle.abort();
+    }
+}
+
+impl Drop for SyntheticServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Answer one HTTP/1.1 request and close the connection
+async fn serve_connection(mut stream: TcpStream, site: &SyntheticSite) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 || request.len() > 16 * 1024 {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("/");
+
+    let (status, body) = match site.files.get(path) {
+        Some(body) => ("200 OK", body.as_slice()),
+        None => ("404 Not Found", &b"not found"[..]),
+    };
+    let content_type = if path.ends_with(".txt") { "text/plain" } else { "application/xml" };
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nContent-Type: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len(),
+        content_type
+    );
+    stream.write_all(head.as_bytes()).await?;
+    if method != "HEAD" {
+        stream.write_all(body).await?;
+    }
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sitemap::parse_sitemap_xml;
+
+    #[test]
+    fn test_generate_tree_shape() {
+        let config = SyntheticConfig {
+            urls_per_sitemap: 10,
+            depth: 3,
+            fanout: 2,
+            ..Default::default()
+        };
+        let site = SyntheticSite::generate(&config, "http://localhost:8000");
+
+        // robots + root index + 2 indexes + 4 urlsets
+        assert_eq!(site.files.len(), 8);
+        assert_eq!(site.expected_urls, 40);
+
+        let root = String::from_utf8_lossy(&site.files["/sitemap.xml"]).into_owned();
+        let parsed = parse_sitemap_xml(&root, "http://localhost:8000").unwrap();
+        assert_eq!(parsed.nested_sitemaps, vec!["http://localhost:8000/sitemap-0.xml", "http://localhost:8000/sitemap-1.xml"]);
+
+        let leaf = String::from_utf8_lossy(&site.files["/sitemap-0-1.xml"]).into_owned();
+        assert_eq!(parse_sitemap_xml(&leaf, "http://localhost:8000").unwrap().urls.len(), 10);
+    }
+
+    #[test]
+    fn test_broken_urlsets_are_not_expected() {
+        let config = SyntheticConfig {
+            urls_per_sitemap: 5,
+            depth: 2,
+            fanout: 4,
+            broken_every: 2,
+            gzip: true,
+        };
+        let site = SyntheticSite::generate(&config, "http://localhost:8000");
+
+        assert_eq!(site.expected_urls, 10);
+        assert!(site.files["/sitemap-0.xml"].starts_with(&[0x1f, 0x8b]));
+    }
+
+    #[tokio::test]
+    async fn test_parse_served_site() {
+        let config = SyntheticConfig {
+            urls_per_sitemap: 50,
+            depth: 2,
+            fanout: 3,
+            broken_every: 0,
+            gzip: true,
+        };
+        let server = SyntheticServer::start(&config).await.unwrap();
+        let parser = crate::parser::RustSitemapParser::new(1, 10, 2, 5, std::time::Duration::from_secs(5));
+
+        let result = parser.parse_site(&server.base_url()).await.unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.urls.len(), server.expected_urls);
+        assert_eq!(result.decompressed_sitemaps.len(), 3);
+        assert!(result.memory.url_bytes > 0);
+        assert!(result.memory.peak_buffer_bytes > 0);
+    }
+}
@@ -0,0 +1,193 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// What one robots.txt or sitemap fetch took. Response and timing fields describe its
+/// final attempt; `total` covers every attempt and the backoff between them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FetchStats {
+    pub url: String,
+    /// Status of the final response; `None` if none arrived
+    pub status: Option<u16>,
+    /// Body bytes read off the wire, before decompression; 0 when the HTTP cache supplied it
+    pub bytes: usize,
+    /// Resolving the host; `None` if a pooled connection was reused or the host is an IP address
+    pub dns: Option<Duration>,
+    /// Opening the connection after DNS, TCP and TLS handshakes included; `None` if a
+    /// pooled connection was reused
+    pub connect: Option<Duration>,
+    /// From sending the request until the final response's headers arrived, redirects included
+    pub ttfb: Option<Duration>,
+    pub total: Duration,
+    /// Attempts after the first
+    pub retries: usize,
+    pub redirects: usize,
+    /// The server answered 304 and the body came from the HTTP cache
+    pub cache_hit: bool,
+    /// Kind of the error the fetch failed with, as in [`crate::SitemapError::kind`]
+    pub error: Option<String>,
+}
+
+impl FetchStats {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            status: None,
+            bytes: 0,
+            dns: None,
+            connect: None,
+            ttfb: None,
+            total: Duration::ZERO,
+            retries: 0,
+            redirects: 0,
+            cache_hit: false,
+            error: None,
+        }
+    }
+}
+
+/// Fetch stats gathered by a site's concurrent fetches
+#[derive(Debug, Default)]
+pub struct FetchLog(Mutex<Vec<FetchStats>>);
+
+impl FetchLog {
+    pub fn push(&self, stats: FetchStats) {
+        self.0.lock().unwrap().push(stats);
+    }
+
+    pub fn take(&self) -> Vec<FetchStats> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+/// DNS and connection time spent on behalf of one request
+#[derive(Debug, Default)]
+pub(crate) struct ConnectionTimings {
+    dns: Mutex<Option<Duration>>,
+    /// Whole connection setup, DNS included
+    connect: Mutex<Option<Duration>>,
+}
+
+impl ConnectionTimings {
+    fn add(slot: &Mutex<Option<Duration>>, elapsed: Duration) {
+        let mut slot = slot.lock().unwrap();
+        *slot = Some(slot.unwrap_or_default() + elapsed);
+    }
+
+    /// Fill in `stats`' DNS and connect times, keeping the two apart
+    pub(crate) fn apply(&self, stats: &mut FetchStats) {
+        let dns = *self.dns.lock().unwrap();
+        stats.dns = dns;
+        stats.connect = self.connect.lock().unwrap().map(|connect| connect.saturating_sub(dns.unwrap_or_default()));
+    }
+}
+
+tokio::task_local! {
+    /// Set around a request so the resolver and connector can charge their time to it.
+    /// A connection hyper finishes in the background after another request took a pooled
+    /// one runs outside the scope and isn't charged to anyone.
+    static CONNECTION: Arc<ConnectionTimings>;
+}
+
+/// Run `request`, collecting the DNS and connection time it spends into `timings`
+pub(crate) async fn timed<F: Future>(timings: Arc<ConnectionTimings>, request: F) -> F::Output {
+    CONNECTION.scope(timings, request).await
+}
+
+fn charge(slot: fn(&ConnectionTimings) -> &Mutex<Option<Duration>>, elapsed: Duration) {
+    let _ = CONNECTION.try_with(|timings| ConnectionTimings::add(slot(timings), elapsed));
+}
+
+/// Charge a lookup made outside [`TimedResolver`] to the current request
+pub(crate) fn charge_dns(elapsed: Duration) {
+    charge(|timings| &timings.dns, elapsed);
+}
+
+/// The system resolver, timed
+#[derive(Debug, Default)]
+pub(crate) struct TimedResolver;
+
+impl Resolve for TimedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let started = Instant::now();
+            // The connector sets the port
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            charge(|timings| &timings.dns, started.elapsed());
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Wraps the client's connector to time each new connection
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct TimedConnectLayer;
+
+impl<S> Layer<S> for TimedConnectLayer {
+    type Service = TimedConnect<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimedConnect(inner)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct TimedConnect<S>(S);
+
+impl<S, R> Service<R> for TimedConnect<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let connecting = self.0.call(request);
+        Box::pin(async move {
+            let started = Instant::now();
+            let connection = connecting.await;
+            charge(|timings| &timings.connect, started.elapsed());
+            connection
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connection_time_is_charged_to_the_scoped_request() {
+        let timings = Arc::new(ConnectionTimings::default());
+        timed(Arc::clone(&timings), async {
+            charge(|timings| &timings.dns, Duration::from_millis(5));
+            charge(|timings| &timings.connect, Duration::from_millis(20));
+            charge(|timings| &timings.connect, Duration::from_millis(10));
+        }).await;
+        // Outside any request's scope
+        charge(|timings| &timings.connect, Duration::from_secs(1));
+
+        let mut stats = FetchStats::new("https://example.com/sitemap.xml");
+        timings.apply(&mut stats);
+        assert_eq!(stats.dns, Some(Duration::from_millis(5)));
+        assert_eq!(stats.connect, Some(Duration::from_millis(25)));
+
+        let reused = ConnectionTimings::default();
+        reused.apply(&mut stats);
+        assert_eq!((stats.dns, stats.connect), (None, None));
+    }
+}
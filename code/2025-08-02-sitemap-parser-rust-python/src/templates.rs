@@ -0,0 +1,204 @@
+use std::collections::{BTreeMap, HashSet};
+use url::Url;
+
+/// Example URLs kept per template
+const EXAMPLES: usize = 3;
+
+/// A URL shape shared by many URLs, e.g. `/blog/{yyyy}/{mm}/{slug}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlTemplate {
+    pub template: String,
+    pub count: usize,
+    pub examples: Vec<String>,
+}
+
+/// One path segment position in the template trie
+#[derive(Debug, Default)]
+struct Node {
+    children: BTreeMap<String, Node>,
+    /// URLs whose path ends here
+    count: usize,
+    examples: Vec<String>,
+}
+
+impl Node {
+    fn insert(&mut self, tokens: &[String], url: &str) {
+        let mut node = self;
+        for token in tokens {
+            node = node.children.entry(token.clone()).or_default();
+        }
+        node.count += 1;
+        if node.examples.len() < EXAMPLES {
+            node.examples.push(url.to_string());
+        }
+    }
+
+    fn merge(&mut self, other: Node) {
+        self.count += other.count;
+        let room = EXAMPLES.saturating_sub(self.examples.len());
+        self.examples.extend(other.examples.into_iter().take(room));
+        for (token, child) in other.children {
+            self.children.entry(token).or_default().merge(child);
+        }
+    }
+
+    /// Fold positions with more than `max_literals` distinct literal segments into `{slug}`, top down.
+    ///
+    /// Below a placeholder (`/{yyyy}/{mm}/...`) any two different literals are enough,
+    /// since what follows a date or ID is almost always per-item.
+    fn generalize(&mut self, max_literals: usize, under_placeholder: bool) {
+        let literals = self.children.keys().filter(|token| !token.starts_with('{')).count();
+        let limit = if under_placeholder { 1 } else { max_literals };
+        if literals > limit {
+            let (literal, typed): (BTreeMap<_, _>, BTreeMap<_, _>) =
+                std::mem::take(&mut self.children).into_iter().partition(|(token, _)| !token.starts_with('{'));
+            self.children = typed;
+            let slug = self.children.entry("{slug}".to_string()).or_default();
+            for (_, child) in literal {
+                slug.merge(child);
+            }
+        }
+        for (token, child) in self.children.iter_mut() {
+            child.generalize(max_literals, token.starts_with('{'));
+        }
+    }
+
+    fn collect(self, path: &mut Vec<String>, out: &mut Vec<UrlTemplate>) {
+        if self.count > 0 {
+            out.push(UrlTemplate {
+                template: format!("/{}", path.join("/")),
+                count: self.count,
+                examples: self.examples,
+            });
+        }
+        for (token, child) in self.children {
+            path.push(token);
+            child.collect(path, out);
+            path.pop();
+        }
+    }
+}
+
+/// Infer URL templates from a set of URLs, most common first.
+///
+/// Numeric IDs, dates, UUIDs, and hashes are recognised per segment; any other
+/// position with more than `max_literals` distinct values (or more than one, right
+/// after a placeholder) becomes `{slug}`. Query strings are ignored. The host is only part of the template when the
+/// URLs span several hosts.
+pub fn mine_templates<'a>(urls: impl IntoIterator<Item = &'a String>, max_literals: usize) -> Vec<UrlTemplate> {
+    let parsed: Vec<(&String, Url)> = urls.into_iter().filter_map(|u| Url::parse(u).ok().map(|p| (u, p))).collect();
+    let hosts: HashSet<&str> = parsed.iter().filter_map(|(_, p)| p.host_str()).collect();
+    let with_host = hosts.len() > 1;
+
+    let mut root = Node::default();
+    for (url, parsed) in &parsed {
+        let mut tokens = Vec::new();
+        if with_host {
+            tokens.push(parsed.host_str().unwrap_or("").to_string());
+        }
+        let segments = parsed.path().split('/').filter(|s| !s.is_empty());
+        for segment in segments {
+            let token = classify(segment, tokens.last().map(String::as_str));
+            tokens.push(token);
+        }
+        root.insert(&tokens, url);
+    }
+
+    // With hosts in front, the first level is hosts and must stay literal
+    if with_host {
+        for child in root.children.values_mut() {
+            child.generalize(max_literals, false);
+        }
+    } else {
+        root.generalize(max_literals, false);
+    }
+
+    let mut templates = Vec::new();
+    root.collect(&mut Vec::new(), &mut templates);
+    if with_host {
+        for t in &mut templates {
+            t.template.remove(0);
+        }
+    }
+    templates.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.template.cmp(&b.template)));
+    templates
+}
+
+/// Replace a segment with a placeholder when its shape gives its meaning away
+fn classify(segment: &str, previous: Option<&str>) -> String {
+    let digits = !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit());
+    let number = || segment.parse::<u32>().unwrap_or(0);
+    let placeholder = match (digits, segment.len(), previous) {
+        (true, 4, _) if (1990..=2100).contains(&number()) => "{yyyy}",
+        (true, 2, Some("{yyyy}")) if (1..=12).contains(&number()) => "{mm}",
+        (true, 2, Some("{mm}")) if (1..=31).contains(&number()) => "{dd}",
+        (true, _, _) => "{id}",
+        _ if is_uuid(segment) => "{uuid}",
+        _ if is_hash(segment) => "{hash}",
+        _ => return segment.to_string(),
+    };
+    placeholder.to_string()
+}
+
+fn is_uuid(segment: &str) -> bool {
+    segment.len() == 36
+        && segment.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+/// Long hex strings with both digits and letters, as content hashes are; excludes words like `deadbeef`
+fn is_hash(segment: &str) -> bool {
+    segment.len() >= 16
+        && segment.bytes().all(|b| b.is_ascii_hexdigit())
+        && segment.bytes().any(|b| b.is_ascii_digit())
+        && segment.bytes().any(|b| b.is_ascii_alphabetic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(paths: impl IntoIterator<Item = String>) -> Vec<String> {
+        paths.into_iter().map(|p| format!("https://example.com{}", p)).collect()
+    }
+
+    #[test]
+    fn test_mine_templates() {
+        let mut paths: Vec<String> = (0..30).map(|i| format!("/product/item-{}", i)).collect();
+        paths.extend((0..5).map(|i| format!("/blog/2024/0{}/post-{}", i + 1, i)));
+        paths.extend((0..4).map(|i| format!("/order/{}", 1000 + i)));
+        paths.push("/about".to_string());
+        paths.push("/contact".to_string());
+
+        let templates = mine_templates(&urls(paths), 10);
+        let summary: Vec<_> = templates.iter().map(|t| (t.template.as_str(), t.count)).collect();
+        assert_eq!(summary, vec![
+            ("/product/{slug}", 30),
+            ("/blog/{yyyy}/{mm}/{slug}", 5),
+            ("/order/{id}", 4),
+            ("/about", 1),
+            ("/contact", 1),
+        ]);
+        assert_eq!(templates[0].examples.len(), EXAMPLES);
+    }
+
+    #[test]
+    fn test_classify_segments() {
+        assert_eq!(classify("2023", None), "{yyyy}");
+        assert_eq!(classify("12", Some("{yyyy}")), "{mm}");
+        assert_eq!(classify("12", None), "{id}");
+        assert_eq!(classify("123e4567-e89b-12d3-a456-426614174000", None), "{uuid}");
+        assert_eq!(classify("9f86d081884c7d659a2feaa0c55ad015", None), "{hash}");
+        assert_eq!(classify("shoes", None), "shoes");
+    }
+
+    #[test]
+    fn test_hosts_stay_literal() {
+        let urls = vec!["https://a.example.com/x/1".to_string(), "https://b.example.com/x/2".to_string()];
+        let templates = mine_templates(&urls, 10);
+        let names: Vec<_> = templates.iter().map(|t| t.template.as_str()).collect();
+        assert_eq!(names, vec!["a.example.com/x/{id}", "b.example.com/x/{id}"]);
+    }
+}
@@ -0,0 +1,63 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{BufRead, BufReader, Read, Write};
+
+/// Pack `urls` into one blob for handing to queues and other processes: newline-separated
+/// UTF-8, gzipped at `level` (0-9).
+///
+/// The blob is a plain gzip stream, so anything with a gzip decoder can read it.
+/// URLs never contain a raw newline, so no escaping is needed.
+pub fn pack_urls<'a>(urls: impl IntoIterator<Item = &'a String>, level: u32) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level.min(9)));
+    for (i, url) in urls.into_iter().enumerate() {
+        if i > 0 {
+            encoder.write_all(b"\n")?;
+        }
+        encoder.write_all(url.as_bytes())?;
+    }
+    encoder.finish()
+}
+
+/// Iterator over the URLs of a packed list, decompressing as it goes
+pub struct UnpackedUrls<R: Read> {
+    lines: BufReader<GzDecoder<R>>,
+    buffer: String,
+}
+
+/// Read back a list written by [`pack_urls`] from a byte slice, a file, or any other
+/// reader, one URL at a time rather than decompressing the whole list up front
+pub fn unpack_urls<R: Read>(packed: R) -> UnpackedUrls<R> {
+    UnpackedUrls { lines: BufReader::new(GzDecoder::new(packed)), buffer: String::new() }
+}
+
+impl<R: Read> Iterator for UnpackedUrls<R> {
+    type Item = std::io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.buffer.clear();
+        match self.lines.read_line(&mut self.buffer) {
+            Ok(0) => None,
+            Ok(_) => Some(Ok(self.buffer.trim_end_matches('\n').to_string())),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_round_trip() {
+        let urls: Vec<String> = (0..1000).map(|i| format!("https://example.com/products/{}", i)).collect();
+        let packed = pack_urls(&urls, 6).unwrap();
+        assert!(packed.len() < urls.iter().map(String::len).sum::<usize>() / 4);
+
+        let unpacked: Vec<String> = unpack_urls(packed.as_slice()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(unpacked, urls);
+
+        assert_eq!(unpack_urls(pack_urls(&[], 6).unwrap().as_slice()).count(), 0);
+        assert!(unpack_urls(&b"not gzip"[..]).next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::cursor::{Hide, Show};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::widgets::{Block, Gauge, Paragraph, Row, Table};
+use ratatui::{Frame, Terminal};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::contention::{ContentionSnapshot, ContentionTracker};
+use crate::monitor::{MonitorSnapshot, RunMonitor, SiteOutcome};
+
+/// How often the dashboard redraws
+pub const REFRESH: Duration = Duration::from_millis(250);
+
+/// A live terminal view of a parser's sites, drawn from a background thread until closed.
+///
+/// The terminal is left in cooked mode, so Ctrl-C still reaches the caller and
+/// interrupts the run gracefully instead of being swallowed by the dashboard.
+pub struct Dashboard {
+    stop: Arc<AtomicBool>,
+    drawing: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Dashboard {
+    /// Take over the terminal's alternate screen; `total` is the number of sites the
+    /// run expects to finish, if known, for the progress bar
+    pub fn start(monitor: Arc<RunMonitor>, contention: Arc<ContentionTracker>, total: Option<usize>) -> io::Result<Self> {
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        let mut terminal = match Terminal::new(CrosstermBackend::new(io::stdout())) {
+            Ok(terminal) => terminal,
+            Err(e) => {
+                let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+                return Err(e);
+            }
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = Arc::clone(&stop);
+        let drawing = std::thread::spawn(move || {
+            let mut drawn = terminal.clear();
+            while drawn.is_ok() && !stopping.load(Ordering::SeqCst) {
+                drawn = terminal.draw(|frame| render(frame, &monitor.snapshot(), &contention.snapshot(), total)).map(|_| ());
+                std::thread::sleep(REFRESH);
+            }
+            let restored = execute!(io::stdout(), Show, LeaveAlternateScreen);
+            drawn.and(restored)
+        });
+        Ok(Self { stop, drawing: Some(drawing) })
+    }
+
+    /// Stop drawing and give the terminal back; later calls do nothing
+    pub fn close(&mut self) -> io::Result<()> {
+        self.stop.store(true, Ordering::SeqCst);
+        match self.drawing.take() {
+            Some(drawing) => drawing.join().unwrap_or_else(|_| Err(io::Error::other("dashboard thread panicked"))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+fn render(frame: &mut Frame, run: &MonitorSnapshot, contention: &ContentionSnapshot, total: Option<usize>) {
+    let [progress, throughput, tables] = Layout::vertical([Constraint::Length(3), Constraint::Length(3), Constraint::Min(0)]).areas(frame.area());
+    let [slowest, recent] = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(tables);
+
+    let (ratio, label) = match total {
+        Some(total) if total > 0 => ((run.finished as f64 / total as f64).min(1.0), format!("{}/{} sites", run.finished, total)),
+        _ => (0.0, format!("{} sites", run.finished)),
+    };
+    let gauge = Gauge::default()
+        .block(Block::bordered().title(" sitemap-parser "))
+        .gauge_style(Style::default().fg(Color::Green))
+        .ratio(ratio)
+        .label(label);
+    frame.render_widget(gauge, progress);
+
+    let stats = format!(
+        "elapsed {}  │  {:.1} sites/min  │  {:.0} URLs/s  │  {} URLs  │  {} failed  │  {} errors  │  {} requests in flight",
+        clock(run.elapsed),
+        run.sites_per_minute(),
+        run.urls_per_second(),
+        run.urls,
+        run.failed,
+        run.errors,
+        contention.requests_in_flight,
+    );
+    frame.render_widget(Paragraph::new(stats).block(Block::bordered().title(" Throughput ")), throughput);
+
+    let rows = run.in_flight.iter().map(|(site, elapsed)| Row::new([site.clone(), format!("{:.1}s", elapsed.as_secs_f64())]));
+    let table = Table::new(rows, [Constraint::Min(20), Constraint::Length(10)])
+        .header(Row::new(["Site", "Elapsed"]).bold())
+        .block(Block::bordered().title(format!(" Slowest in flight ({}) ", run.in_flight.len())));
+    frame.render_widget(table, slowest);
+
+    let rows = run.recent.iter().map(|site| {
+        let color = match site.outcome {
+            SiteOutcome::Ok => Color::Green,
+            SiteOutcome::Partial => Color::Yellow,
+            SiteOutcome::Failed => Color::Red,
+            SiteOutcome::Interrupted => Color::DarkGray,
+        };
+        Row::new([site.outcome.to_string(), site.base_url.clone(), site.urls.to_string(), site.errors.to_string(), format!("{:.1}s", site.elapsed.as_secs_f64())])
+            .style(Style::default().fg(color))
+    });
+    let widths = [Constraint::Length(11), Constraint::Min(20), Constraint::Length(9), Constraint::Length(6), Constraint::Length(8)];
+    let table = Table::new(rows, widths)
+        .header(Row::new(["Status", "Site", "URLs", "Errors", "Time"]).bold())
+        .block(Block::bordered().title(" Recently finished "));
+    frame.render_widget(table, recent);
+}
+
+fn clock(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::FinishedSite;
+    use ratatui::backend::TestBackend;
+
+    #[test]
+    fn test_render_shows_progress_slowest_and_recent() {
+        let run = MonitorSnapshot {
+            elapsed: Duration::from_secs(3725),
+            finished: 2,
+            failed: 1,
+            urls: 1500,
+            errors: 1,
+            in_flight: vec![("https://slow.example".to_string(), Duration::from_secs(95))],
+            recent: vec![FinishedSite {
+                base_url: "https://broken.example".to_string(),
+                outcome: SiteOutcome::Failed,
+                urls: 0,
+                errors: 1,
+                elapsed: Duration::from_secs(3),
+            }],
+        };
+        let mut terminal = Terminal::new(TestBackend::new(160, 12)).unwrap();
+        terminal.draw(|frame| render(frame, &run, &ContentionSnapshot::default(), Some(4))).unwrap();
+
+        let screen: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("2/4 sites"));
+        assert!(screen.contains("elapsed 1:02:05"));
+        assert!(screen.contains("https://slow.example") && screen.contains("95.0s"));
+        assert!(screen.contains("failed") && screen.contains("https://broken.example"));
+    }
+}
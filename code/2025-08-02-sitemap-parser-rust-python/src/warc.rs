@@ -0,0 +1,354 @@
+use chrono::{SecondsFormat, Utc};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use url::Url;
+use uuid::Uuid;
+
+use crate::parser::{TruncationReason, USER_AGENT};
+
+/// A recorded HTTP response, as stored in a WARC `response` record
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchivedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// Payload with any chunked transfer-encoding removed
+    pub body: Vec<u8>,
+    /// The crawler stored only part of the payload (`WARC-Truncated`)
+    pub truncated: bool,
+}
+
+/// Responses read from one or more WARC files, looked up by target URI
+#[derive(Debug, Default)]
+pub struct WarcArchive {
+    responses: HashMap<String, ArchivedResponse>,
+}
+
+impl WarcArchive {
+    /// Load every `response` record from the given files; `.gz` files may hold one gzip member per record
+    pub fn load(paths: &[impl AsRef<Path>]) -> std::io::Result<Self> {
+        let mut archive = WarcArchive::default();
+        for path in paths {
+            let path = path.as_ref();
+            let file = File::open(path)?;
+            let reader: Box<dyn Read> = if path.extension().is_some_and(|ext| ext == "gz") {
+                Box::new(MultiGzDecoder::new(file))
+            } else {
+                Box::new(file)
+            };
+            archive.read_records(BufReader::new(reader))?;
+        }
+        Ok(archive)
+    }
+
+    fn read_records(&mut self, mut reader: impl BufRead) -> std::io::Result<()> {
+        while let Some(headers) = read_header_block(&mut reader)? {
+            let length: usize = header(&headers, "Content-Length").and_then(|v| v.parse().ok()).unwrap_or(0);
+            let mut block = vec![0; length];
+            reader.read_exact(&mut block)?;
+
+            let is_response = header(&headers, "WARC-Type") == Some("response");
+            let target = header(&headers, "WARC-Target-URI").map(|uri| uri.trim_matches(|c| c == '<' || c == '>'));
+            if let (true, Some(target)) = (is_response, target) {
+                if let Some(mut response) = parse_http_response(&block) {
+                    response.truncated = header(&headers, "WARC-Truncated").is_some();
+                    self.insert(target, response);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Later captures replace earlier ones, except that an error never replaces a success
+    fn insert(&mut self, target: &str, response: ArchivedResponse) {
+        let key = normalize(target);
+        let keep_existing = self
+            .responses
+            .get(&key)
+            .is_some_and(|existing| is_success(existing.status) && !is_success(response.status));
+        if !keep_existing {
+            self.responses.insert(key, response);
+        }
+    }
+
+    pub fn get(&self, url: &str) -> Option<&ArchivedResponse> {
+        self.responses.get(&normalize(url))
+    }
+
+    pub fn len(&self) -> usize {
+        self.responses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty()
+    }
+}
+
+/// The response record in a gzipped WARC fragment, such as a single record fetched by byte range
+pub fn read_gzipped_response(bytes: &[u8]) -> std::io::Result<Option<ArchivedResponse>> {
+    let mut archive = WarcArchive::default();
+    archive.read_records(BufReader::new(MultiGzDecoder::new(bytes)))?;
+    Ok(archive.responses.into_values().next())
+}
+
+fn is_success(status: u16) -> bool {
+    (200..300).contains(&status)
+}
+
+fn normalize(url: &str) -> String {
+    Url::parse(url).map(String::from).unwrap_or_else(|_| url.to_string())
+}
+
+/// Read `WARC/1.x` header lines up to the blank line, skipping the separator between records
+fn read_header_block(reader: &mut impl BufRead) -> std::io::Result<Option<Vec<(String, String)>>> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.starts_with("WARC/") {
+            break;
+        }
+    }
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            return Ok(Some(headers));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+}
+
+/// Split a raw HTTP/1.x response into status, headers, and de-chunked body
+fn parse_http_response(raw: &[u8]) -> Option<ArchivedResponse> {
+    let split = raw.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&raw[..split]);
+    let mut lines = head.lines();
+    let status = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(n, v)| (n.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let payload = &raw[split + 4..];
+    let chunked = header(&headers, "Transfer-Encoding").is_some_and(|v| v.eq_ignore_ascii_case("chunked"));
+    let body = if chunked { dechunk(payload) } else { payload.to_vec() };
+    Some(ArchivedResponse { status, headers, body, truncated: false })
+}
+
+/// Undo chunked transfer-encoding, keeping whatever complete chunks there are
+fn dechunk(mut payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    while let Some(line_end) = payload.windows(2).position(|w| w == b"\r\n") {
+        let size_field = String::from_utf8_lossy(&payload[..line_end]);
+        let size_hex = size_field.split(';').next().unwrap_or("").trim();
+        let Ok(size) = usize::from_str_radix(size_hex, 16) else { break };
+        let start = line_end + 2;
+        if size == 0 || start + size > payload.len() {
+            break;
+        }
+        body.extend_from_slice(&payload[start..start + size]);
+        payload = payload.get(start + size + 2..).unwrap_or(&[]);
+    }
+    body
+}
+
+/// Appends fetched responses to a WARC file, one request and one response record each.
+///
+/// Files ending in `.gz` get one gzip member per record, the usual layout for
+/// `.warc.gz`. Appending means several runs can share one file.
+pub struct WarcWriter {
+    file: Mutex<File>,
+    gzip: bool,
+}
+
+impl WarcWriter {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let is_new = file.metadata()?.len() == 0;
+        let writer = Self {
+            file: Mutex::new(file),
+            gzip: path.extension().is_some_and(|ext| ext == "gz"),
+        };
+        if is_new {
+            let info = format!(
+                "software: {}/{}\r\nformat: WARC File Format 1.1\r\nhttp-header-user-agent: {}\r\n",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION"),
+                USER_AGENT
+            );
+            let filename = path.file_name().map(|f| f.to_string_lossy().into_owned()).unwrap_or_default();
+            writer.write_record("warcinfo", &[("WARC-Filename", filename.as_str())], "application/warc-fields", info.as_bytes())?;
+        }
+        Ok(writer)
+    }
+
+    /// Record a GET and the response it got; `body` is the payload exactly as received
+    pub fn write_exchange(
+        &self,
+        url: &str,
+        resp: &reqwest::Response,
+        body: &[u8],
+        truncation: Option<TruncationReason>,
+    ) -> std::io::Result<()> {
+        let target = Url::parse(url).map_err(std::io::Error::other)?;
+        let mut request_line = target.path().to_string();
+        if let Some(query) = target.query() {
+            request_line.push('?');
+            request_line.push_str(query);
+        }
+        let host = match target.port() {
+            Some(port) => format!("{}:{}", target.host_str().unwrap_or(""), port),
+            None => target.host_str().unwrap_or("").to_string(),
+        };
+        let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\n\r\n", request_line, host, USER_AGENT);
+
+        let mut response = format!(
+            "HTTP/1.1 {} {}\r\n",
+            resp.status().as_u16(),
+            resp.status().canonical_reason().unwrap_or("")
+        )
+        .into_bytes();
+        for (name, value) in resp.headers() {
+            // The payload below is already de-chunked
+            if name == reqwest::header::TRANSFER_ENCODING {
+                continue;
+            }
+            response.extend_from_slice(name.as_str().as_bytes());
+            response.extend_from_slice(b": ");
+            response.extend_from_slice(value.as_bytes());
+            response.extend_from_slice(b"\r\n");
+        }
+        response.extend_from_slice(b"\r\n");
+        response.extend_from_slice(body);
+
+        let response_id = record_id();
+        let payload_digest = sha1_digest(body);
+        let mut response_headers = vec![
+            ("WARC-Record-ID", response_id.as_str()),
+            ("WARC-Target-URI", url),
+            ("WARC-Payload-Digest", payload_digest.as_str()),
+        ];
+        let truncated = truncation.map(|reason| match reason {
+            TruncationReason::SizeLimit => "length",
+            TruncationReason::Interrupted => "disconnect",
+        });
+        if let Some(truncated) = truncated {
+            response_headers.push(("WARC-Truncated", truncated));
+        }
+
+        self.write_record("request", &[("WARC-Target-URI", url), ("WARC-Concurrent-To", response_id.as_str())], "application/http; msgtype=request", request.as_bytes())?;
+        self.write_record("response", &response_headers, "application/http; msgtype=response", &response)
+    }
+
+    fn write_record(&self, warc_type: &str, extra: &[(&str, &str)], content_type: &str, block: &[u8]) -> std::io::Result<()> {
+        let mut record = format!(
+            "WARC/1.1\r\nWARC-Type: {}\r\nWARC-Date: {}\r\n",
+            warc_type,
+            Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true)
+        );
+        if !extra.iter().any(|(name, _)| *name == "WARC-Record-ID") {
+            record.push_str(&format!("WARC-Record-ID: {}\r\n", record_id()));
+        }
+        for (name, value) in extra {
+            record.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        record.push_str(&format!(
+            "WARC-Block-Digest: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+            sha1_digest(block),
+            content_type,
+            block.len()
+        ));
+
+        let mut bytes = record.into_bytes();
+        bytes.extend_from_slice(block);
+        bytes.extend_from_slice(b"\r\n\r\n");
+        if self.gzip {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&bytes)?;
+            bytes = encoder.finish()?;
+        }
+        self.file.lock().unwrap().write_all(&bytes)
+    }
+}
+
+fn record_id() -> String {
+    format!("<urn:uuid:{}>", Uuid::new_v4())
+}
+
+/// `sha1:` plus the base32 SHA-1, the digest format WARC tools expect
+fn sha1_digest(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let digest = sha1_smol::Sha1::from(data).digest().bytes();
+
+    // 160 bits is exactly 32 base32 characters, so no padding
+    let mut encoded = String::with_capacity(37);
+    encoded.push_str("sha1:");
+    let mut buffer: u64 = 0;
+    let mut bits = 0;
+    for byte in digest {
+        buffer = (buffer << 8) | byte as u64;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha1_digest_base32() {
+        // Known digest of the empty payload
+        assert_eq!(sha1_digest(b""), "sha1:3I42H3S6NNFQ2MSVX7XZKYAYSCX5QBYJ");
+    }
+
+    #[test]
+    fn test_parse_chunked_response() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+        let response = parse_http_response(raw).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"hello world");
+    }
+
+    #[test]
+    fn test_read_records_prefers_success() {
+        let record = |uri: &str, http: &str| {
+            format!(
+                "WARC/1.0\r\nWARC-Type: response\r\nWARC-Target-URI: {}\r\nContent-Length: {}\r\n\r\n{}\r\n\r\n",
+                uri,
+                http.len(),
+                http
+            )
+        };
+        let warc = [
+            record("https://example.com/robots.txt", "HTTP/1.1 200 OK\r\n\r\nSitemap: /a.xml"),
+            record("https://example.com/robots.txt", "HTTP/1.1 503 Unavailable\r\n\r\n"),
+            "WARC/1.0\r\nWARC-Type: request\r\nContent-Length: 0\r\n\r\n\r\n\r\n".to_string(),
+        ]
+        .concat();
+
+        let mut archive = WarcArchive::default();
+        archive.read_records(warc.as_bytes()).unwrap();
+        assert_eq!(archive.len(), 1);
+        assert_eq!(archive.get("https://example.com/robots.txt").unwrap().body, b"Sitemap: /a.xml");
+    }
+}
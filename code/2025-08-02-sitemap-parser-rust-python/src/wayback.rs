@@ -0,0 +1,74 @@
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use url::Url;
+
+const AVAILABILITY_API: &str = "https://archive.org/wayback/available";
+
+/// A Wayback Machine capture standing in for a response the live site couldn't give
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedCapture {
+    /// The URL that failed live
+    pub url: String,
+    /// Where the raw capture was read from
+    pub snapshot_url: String,
+    /// Capture time as `YYYY-MM-DDTHH:MM:SSZ`
+    pub captured_at: String,
+}
+
+/// Ask the availability API for the most recent successful capture of `url`
+pub async fn latest_snapshot(client: &Client, url: &str) -> Result<Option<ArchivedCapture>, Box<dyn std::error::Error + Send + Sync>> {
+    let query = Url::parse_with_params(AVAILABILITY_API, &[("url", url)])?;
+    let answer: Value = client.get(query).send().await?.error_for_status()?.json().await?;
+    Ok(capture_from_answer(url, &answer))
+}
+
+fn capture_from_answer(url: &str, answer: &Value) -> Option<ArchivedCapture> {
+    let closest = &answer["archived_snapshots"]["closest"];
+    let available = closest["available"].as_bool().unwrap_or(false);
+    let status_ok = closest["status"].as_str().is_some_and(|s| s.starts_with('2'));
+    let timestamp = closest["timestamp"].as_str().filter(|t| t.len() == 14 && t.bytes().all(|b| b.is_ascii_digit()))?;
+    if !available || !status_ok {
+        return None;
+    }
+    Some(ArchivedCapture {
+        url: url.to_string(),
+        // `id_` asks for the body exactly as captured, without the replay toolbar or rewritten links
+        snapshot_url: format!("https://web.archive.org/web/{}id_/{}", timestamp, url),
+        captured_at: format!(
+            "{}-{}-{}T{}:{}:{}Z",
+            &timestamp[0..4],
+            &timestamp[4..6],
+            &timestamp[6..8],
+            &timestamp[8..10],
+            &timestamp[10..12],
+            &timestamp[12..14]
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_capture_from_answer() {
+        let answer = json!({
+            "url": "example.com/sitemap.xml",
+            "archived_snapshots": {
+                "closest": {
+                    "status": "200",
+                    "available": true,
+                    "url": "http://web.archive.org/web/20240315120102/https://example.com/sitemap.xml",
+                    "timestamp": "20240315120102"
+                }
+            }
+        });
+        let capture = capture_from_answer("https://example.com/sitemap.xml", &answer).unwrap();
+        assert_eq!(capture.snapshot_url, "https://web.archive.org/web/20240315120102id_/https://example.com/sitemap.xml");
+        assert_eq!(capture.captured_at, "2024-03-15T12:01:02Z");
+
+        assert!(capture_from_answer("https://example.com/sitemap.xml", &json!({ "archived_snapshots": {} })).is_none());
+    }
+}
@@ -0,0 +1,318 @@
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use quick_xml::escape::escape;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use crate::media::ImageEntry;
+use crate::score::parse_lastmod;
+use crate::sitemap::{parse_changefreq, UrlMetadata};
+
+/// URLs the sitemap protocol allows in one urlset, and sitemaps in one index
+pub const MAX_URLS_PER_SITEMAP: usize = 50_000;
+
+/// Largest urlset the protocol allows, counted before compression
+pub const MAX_SITEMAP_BYTES: u64 = 50 * 1024 * 1024;
+
+/// URLs must be shorter than this many characters
+const MAX_URL_CHARS: usize = 2048;
+
+const URLSET_OPEN: &str = concat!(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+    "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\" xmlns:image=\"http://www.google.com/schemas/sitemap-image/1.1\">\n",
+);
+const URLSET_CLOSE: &str = "</urlset>\n";
+
+/// What a `SitemapWriter` wrote
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SitemapWriteSummary {
+    /// The sitemap index listing every urlset file; the one to submit or put in robots.txt
+    pub index: PathBuf,
+    /// Urlset files, in the order their URLs were added
+    pub sitemaps: Vec<PathBuf>,
+    pub urls: usize,
+    /// Size on disk of every file, the index included
+    pub bytes_written: u64,
+}
+
+/// Writes URLs out as sitemap files, the inverse of parsing them.
+///
+/// URLs go into `<prefix>-1.xml`, `<prefix>-2.xml`, ... in the target directory, a
+/// new file starting whenever one reaches the URL limit or 50MB, and [`finish`]
+/// writes `<prefix>_index.xml` pointing at all of them under `base_url`. Each entry
+/// takes the [`UrlMetadata`] the parser produces: `lastmod`, `changefreq`,
+/// `priority`, and `images` are written, the other extension fields are not.
+///
+/// Values are XML-escaped as they're written, so pass URLs as plain URLs. Entries
+/// the protocol would reject (a relative URL, a priority outside 0.0 to 1.0, an
+/// unknown changefreq, a lastmod that isn't a W3C datetime) fail with
+/// `InvalidInput` and nothing is written for them.
+///
+/// [`finish`]: SitemapWriter::finish
+pub struct SitemapWriter {
+    dir: PathBuf,
+    /// Public URL of `dir`, ending in `/`
+    base_url: Url,
+    prefix: String,
+    gzip: bool,
+    max_urls: usize,
+    current: Option<Shard>,
+    /// Finished urlsets with the latest lastmod of their URLs
+    written: Vec<(String, Option<String>)>,
+    summary: SitemapWriteSummary,
+}
+
+/// The urlset file being written
+struct Shard {
+    out: ShardOut,
+    urls: usize,
+    /// Bytes before compression, which the protocol's size limit counts
+    bytes: u64,
+    lastmod: Option<(DateTime<Utc>, String)>,
+}
+
+enum ShardOut {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl ShardOut {
+    fn write_str(&mut self, text: &str) -> io::Result<()> {
+        match self {
+            ShardOut::Plain(out) => out.write_all(text.as_bytes()),
+            ShardOut::Gzip(out) => out.write_all(text.as_bytes()),
+        }
+    }
+
+    fn finish(self) -> io::Result<File> {
+        let out = match self {
+            ShardOut::Plain(out) => out,
+            ShardOut::Gzip(out) => out.finish()?,
+        };
+        out.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+impl SitemapWriter {
+    /// Write into `dir`, creating it if needed, for files that will be served from
+    /// `base_url` (e.g. `https://example.com/sitemaps/`)
+    pub fn create(dir: &Path, base_url: &str) -> io::Result<Self> {
+        let mut base_url = Url::parse(base_url).map_err(|e| invalid(format!("Invalid base URL {:?}: {}", base_url, e)))?;
+        if !matches!(base_url.scheme(), "http" | "https") {
+            return Err(invalid(format!("Base URL {} isn't http(s)", base_url)));
+        }
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        std::fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            base_url,
+            prefix: "sitemap".to_string(),
+            gzip: false,
+            max_urls: MAX_URLS_PER_SITEMAP,
+            current: None,
+            written: Vec::new(),
+            summary: SitemapWriteSummary::default(),
+        })
+    }
+
+    /// Gzip the urlset files, naming them `.xml.gz`; the index stays plain XML
+    pub fn with_gzip(mut self, gzip: bool) -> Self {
+        self.gzip = gzip;
+        self
+    }
+
+    /// URLs per urlset file, at most [`MAX_URLS_PER_SITEMAP`]; 0 is treated as 1
+    pub fn with_max_urls(mut self, max_urls: usize) -> Self {
+        self.max_urls = max_urls.clamp(1, MAX_URLS_PER_SITEMAP);
+        self
+    }
+
+    /// Start of every file name, `sitemap` by default
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Add one URL, starting a new urlset file if the current one is full
+    pub fn add(&mut self, url: &str, metadata: &UrlMetadata) -> io::Result<()> {
+        let entry = url_entry(url, metadata)?;
+        let full = self.current.as_ref().is_some_and(|shard| {
+            shard.urls >= self.max_urls || shard.bytes + (entry.len() + URLSET_CLOSE.len()) as u64 > MAX_SITEMAP_BYTES
+        });
+        if full {
+            self.close_shard()?;
+        }
+        if self.current.is_none() {
+            self.current = Some(self.open_shard()?);
+        }
+        let shard = self.current.as_mut().expect("a urlset was just opened");
+        shard.out.write_str(&entry)?;
+        shard.urls += 1;
+        shard.bytes += entry.len() as u64;
+        if let Some(lastmod) = &metadata.lastmod {
+            let at = parse_lastmod(lastmod).expect("url_entry checked the lastmod");
+            if shard.lastmod.as_ref().is_none_or(|(latest, _)| at > *latest) {
+                shard.lastmod = Some((at, lastmod.trim().to_string()));
+            }
+        }
+        self.summary.urls += 1;
+        Ok(())
+    }
+
+    /// Close the last urlset and write the index, returning what was written
+    pub fn finish(mut self) -> io::Result<SitemapWriteSummary> {
+        self.close_shard()?;
+        let mut index = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+        for (file_name, lastmod) in &self.written {
+            let loc = self.base_url.join(file_name).map_err(|e| invalid(e.to_string()))?;
+            let _ = write!(index, "  <sitemap>\n    <loc>{}</loc>\n", escape(loc.as_str()));
+            if let Some(lastmod) = lastmod {
+                let _ = writeln!(index, "    <lastmod>{}</lastmod>", escape(lastmod.as_str()));
+            }
+            index.push_str("  </sitemap>\n");
+        }
+        index.push_str("</sitemapindex>\n");
+
+        let path = self.dir.join(format!("{}_index.xml", self.prefix));
+        std::fs::write(&path, &index)?;
+        self.summary.bytes_written += index.len() as u64;
+        self.summary.index = path;
+        Ok(self.summary)
+    }
+
+    fn open_shard(&mut self) -> io::Result<Shard> {
+        if self.written.len() >= MAX_URLS_PER_SITEMAP {
+            return Err(invalid(format!("A sitemap index can't list more than {} sitemaps", MAX_URLS_PER_SITEMAP)));
+        }
+        let file_name = format!("{}-{}.xml{}", self.prefix, self.written.len() + 1, if self.gzip { ".gz" } else { "" });
+        let path = self.dir.join(&file_name);
+        let file = BufWriter::new(File::create(&path)?);
+        let mut out = if self.gzip { ShardOut::Gzip(GzEncoder::new(file, Compression::default())) } else { ShardOut::Plain(file) };
+        out.write_str(URLSET_OPEN)?;
+        self.written.push((file_name, None));
+        self.summary.sitemaps.push(path);
+        Ok(Shard { out, urls: 0, bytes: URLSET_OPEN.len() as u64, lastmod: None })
+    }
+
+    fn close_shard(&mut self) -> io::Result<()> {
+        let Some(mut shard) = self.current.take() else { return Ok(()) };
+        shard.out.write_str(URLSET_CLOSE)?;
+        let file = shard.out.finish()?;
+        file.sync_all()?;
+        self.summary.bytes_written += file.metadata()?.len();
+        if let Some(last) = self.written.last_mut() {
+            last.1 = shard.lastmod.map(|(_, lastmod)| lastmod);
+        }
+        Ok(())
+    }
+}
+
+/// The `<url>` element for `url`, or `InvalidInput` if the protocol wouldn't accept it
+fn url_entry(url: &str, metadata: &UrlMetadata) -> io::Result<String> {
+    let mut entry = format!("  <url>\n    <loc>{}</loc>\n", escape(check_url(url)?));
+    if let Some(lastmod) = &metadata.lastmod {
+        if parse_lastmod(lastmod).is_none() {
+            return Err(invalid(format!("lastmod {:?} of {} isn't a W3C datetime", lastmod, url)));
+        }
+        let _ = writeln!(entry, "    <lastmod>{}</lastmod>", escape(lastmod.trim()));
+    }
+    if let Some(changefreq) = &metadata.changefreq {
+        let changefreq = parse_changefreq(changefreq).ok_or_else(|| invalid(format!("changefreq {:?} of {} isn't one the protocol defines", changefreq, url)))?;
+        let _ = writeln!(entry, "    <changefreq>{}</changefreq>", changefreq);
+    }
+    if let Some(priority) = metadata.priority {
+        if !(0.0..=1.0).contains(&priority) {
+            return Err(invalid(format!("priority {} of {} is outside 0.0 to 1.0", priority, url)));
+        }
+        let _ = writeln!(entry, "    <priority>{}</priority>", priority);
+    }
+    for image in &metadata.images {
+        image_entry(&mut entry, image)?;
+    }
+    entry.push_str("  </url>\n");
+    Ok(entry)
+}
+
+fn image_entry(entry: &mut String, image: &ImageEntry) -> io::Result<()> {
+    let _ = write!(entry, "    <image:image>\n      <image:loc>{}</image:loc>\n", escape(check_url(&image.loc)?));
+    let fields = [("caption", &image.caption), ("title", &image.title), ("geo_location", &image.geo_location), ("license", &image.license)];
+    for (name, value) in fields {
+        if let Some(value) = value {
+            let _ = writeln!(entry, "      <image:{0}>{1}</image:{0}>", name, escape(value.as_str()));
+        }
+    }
+    entry.push_str("    </image:image>\n");
+    Ok(())
+}
+
+/// `url` if it's an absolute http(s) URL short enough for the protocol
+fn check_url(url: &str) -> io::Result<&str> {
+    let parsed = Url::parse(url).map_err(|e| invalid(format!("{:?} isn't an absolute URL: {}", url, e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(invalid(format!("{} isn't http(s)", url)));
+    }
+    if url.chars().count() >= MAX_URL_CHARS {
+        return Err(invalid(format!("{}... is {} characters or longer", url.chars().take(80).collect::<String>(), MAX_URL_CHARS)));
+    }
+    Ok(url)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sitemap::{parse_sitemap_xml_with_options, ParseOptions};
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_writes_shards_and_index_the_parser_reads_back() {
+        let dir = std::env::temp_dir().join(format!("sitemap-writer-{}", uuid::Uuid::new_v4()));
+        let mut writer = SitemapWriter::create(&dir, "https://example.com/maps").unwrap().with_gzip(true).with_max_urls(2);
+        let photo = UrlMetadata {
+            lastmod: Some("2024-03-01".to_string()),
+            changefreq: Some("Weekly".to_string()),
+            priority: Some(0.8),
+            images: vec![ImageEntry { loc: "https://example.com/a.jpg".to_string(), caption: Some("Fish & chips".to_string()), ..Default::default() }],
+            ..Default::default()
+        };
+        writer.add("https://example.com/a?x=1&y=2", &photo).unwrap();
+        writer.add("https://example.com/b", &UrlMetadata { lastmod: Some("2024-05-01T10:00:00Z".to_string()), ..Default::default() }).unwrap();
+        writer.add("https://example.com/c", &UrlMetadata::default()).unwrap();
+        // Rejected entries leave nothing behind
+        assert_eq!(writer.add("/relative", &UrlMetadata::default()).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(writer.add("https://example.com/d", &UrlMetadata { priority: Some(1.5), ..Default::default() }).is_err());
+        assert!(writer.add("https://example.com/d", &UrlMetadata { lastmod: Some("last week".to_string()), ..Default::default() }).is_err());
+        let summary = writer.finish().unwrap();
+
+        assert_eq!(summary.urls, 3);
+        assert_eq!(summary.sitemaps, vec![dir.join("sitemap-1.xml.gz"), dir.join("sitemap-2.xml.gz")]);
+        assert_eq!(summary.index, dir.join("sitemap_index.xml"));
+        let on_disk: u64 = summary.sitemaps.iter().chain([&summary.index]).map(|path| std::fs::metadata(path).unwrap().len()).sum();
+        assert_eq!(summary.bytes_written, on_disk);
+
+        let index = std::fs::read_to_string(&summary.index).unwrap();
+        let parsed = parse_sitemap_xml_with_options(&index, "https://example.com/", &ParseOptions::default()).unwrap();
+        assert_eq!(parsed.nested_sitemaps, ["https://example.com/maps/sitemap-1.xml.gz", "https://example.com/maps/sitemap-2.xml.gz"]);
+        assert!(index.contains("<lastmod>2024-05-01T10:00:00Z</lastmod>"), "{}", index);
+
+        let mut first = String::new();
+        GzDecoder::new(File::open(&summary.sitemaps[0]).unwrap()).read_to_string(&mut first).unwrap();
+        let options = ParseOptions { media: true, ..Default::default() };
+        let parsed = parse_sitemap_xml_with_options(&first, "https://example.com/", &options).unwrap();
+        assert_eq!(parsed.urls.len(), 2);
+        let meta = &parsed.metadata["https://example.com/a?x=1&y=2"];
+        assert_eq!((meta.lastmod.as_deref(), meta.changefreq.as_deref(), meta.priority), (Some("2024-03-01"), Some("weekly"), Some(0.8)));
+        assert_eq!(meta.images[0].loc, "https://example.com/a.jpg");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}